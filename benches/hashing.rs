@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use file_organizer_rust::utils::hashing::{same_contents, Algorithm, Hashing};
+use std::{fs, hint::black_box};
+
+/// Bytes hashed or compared per iteration
+const SIZE: usize = 16 * 1024 * 1024;
+
+fn contents() -> Vec<u8> {
+    (0..SIZE).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn algorithms(c: &mut Criterion) {
+    let data = contents();
+    let mut group = c.benchmark_group("digest");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    for algorithm in [Algorithm::Sha256, Algorithm::Blake3, Algorithm::Xxh3] {
+        let hashing = Hashing::new(algorithm);
+        group.bench_function(algorithm.name(), |b| b.iter(|| hashing.digest(black_box(data.as_slice())).unwrap()));
+    }
+    group.finish();
+}
+
+fn buffer_sizes(c: &mut Criterion) {
+    let data = contents();
+    let mut group = c.benchmark_group("buffer_size");
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    for kib in [4, 64, 1024] {
+        let hashing = Hashing {
+            buffer_size: kib * 1024,
+            ..Hashing::new(Algorithm::Blake3)
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{} KiB", kib)), &hashing, |b, hashing| {
+            b.iter(|| hashing.digest(black_box(data.as_slice())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn files(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b, early) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("early"));
+    let data = contents();
+    fs::write(&a, &data).unwrap();
+    fs::write(&b, &data).unwrap();
+    let mut differs = data;
+    differs[0] ^= 1;
+    fs::write(&early, differs).unwrap();
+
+    let hashing = Hashing::new(Algorithm::Sha256);
+    let mut group = c.benchmark_group("file");
+    group.bench_function("quick_hash", |bench| bench.iter(|| hashing.quick_hash(&a).unwrap()));
+    group.throughput(Throughput::Bytes(SIZE as u64));
+    group.bench_function("same_contents", |bench| bench.iter(|| same_contents(&a, &b).unwrap()));
+    group.bench_function("same_contents_early_difference", |bench| {
+        bench.iter(|| same_contents(&a, &early).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, algorithms, buffer_sizes, files);
+criterion_main!(benches);
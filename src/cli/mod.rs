@@ -1,14 +1,49 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use dialoguer::{theme::ColorfulTheme, Select};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use image::ImageFormat;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use crate::modules::{
-    directory_flattener::DirectoryFlattener,
-    image_optimizer::ImageOptimizer,
+use file_organizer_rust::utils::prompt::{Confirm, Select};
+use file_organizer_rust::utils::{hooks, metrics};
+use file_organizer_rust::utils::session::{self, Recording};
+use file_organizer_rust::utils::{
+    create_spinner, get_directory_from_user, delete::DeleteMode, expand::expand_path, hashing::Algorithm, journal::Journal, parse_age, parse_size, permissions::parse_mode, secure_delete::Pass, transfer::{TransferMode, Verify},
+};
+use file_organizer_rust::modules::{
+    directory_flattener::{DirectoryFlattener, SymlinkPolicy},
+    image_optimizer::{CandidateFilter, ColorMode, IccMode, ImageOptimizer},
     file_deduplicator::FileDeduplicator,
-    file_categorizer::FileCategorizer,
-    archive_manager::ArchiveManager,
-    base::FileOrganizer,
+    file_categorizer::{
+        Composition, ConflictPolicy, DateSource, FileCategorizer, HiddenPolicy, ReportFormat,
+        UnmatchedPolicy,
+    },
+    archive_manager::{self, index::ArchiveIndex, ArchiveManager, ArchiveType, CompressionLevel},
+    age_archiver::{AgeArchiver, AgeSource},
+    junk_cleaner::JunkCleaner,
+    empty_dir_cleaner::EmptyDirCleaner,
+    disk_usage::UsageReporter,
+    checksum::ChecksumManager,
+    broken_links::{BrokenLinkFinder, LinkAction},
+    directory_sync::DirectorySync,
+    compare::TreeComparer,
+    file_shredder::FileShredder,
+    inventory::{Column, InventoryExporter, InventoryFormat},
+    name_sanitizer::{NamePolicy, NameSanitizer, Target},
+    permissions::PermissionNormalizer,
+    text_normalizer::{BomPolicy, Conversion, Newline, TextNormalizer},
+    file_splitter::FileSplitter,
+    file_renamer::{self, CaseTransform, DatePrefix, ExtensionStyle, FileRenamer, NamePattern, Replacement},
+    download_cleanup::DownloadCleaner,
+    daemon::{Daemon, DaemonRules},
+    base::{print_errors, print_inaccessible, FileOrganizer, Summary},
 };
 
 #[derive(Parser)]
@@ -16,51 +51,1916 @@ use crate::modules::{
 pub struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// How files are deleted: trash (default), permanent, or dry-run to only list them
+    #[arg(long, global = true, value_parser = ["trash", "permanent", "dry-run"])]
+    delete_mode: Option<String>,
+    /// How moves to another filesystem check the copy before deleting the source: size or hash
+    #[arg(long, global = true, value_parser = ["size", "hash"])]
+    verify: Option<String>,
+    /// Copy, convert, or archive even when the destination filesystem looks too small
+    #[arg(long, global = true)]
+    force: bool,
+    /// Walk directories in name order, so repeated runs over the same tree make the same choices
+    /// (always on for archives)
+    #[arg(long, global = true)]
+    sorted: bool,
+    /// Delete, move, or archive files without first checking that they are unchanged since the
+    /// scan; faster on trees nothing else writes to
+    #[arg(long, global = true)]
+    assume_static: bool,
+    /// Hash every file afresh instead of reusing digests of unchanged files from earlier runs
+    #[arg(long, global = true)]
+    no_hash_cache: bool,
+    /// Run without the on_success and on_failure hooks from the config
+    #[arg(long, global = true)]
+    no_hooks: bool,
+    /// Print where the time went after the run, e.g. "scanning 12 s · hashing 9 m 41 s (412 MB/s)",
+    /// and include it in JSON summaries
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Save the command and every answer given to its prompts to this file, to run it again with --replay
+    #[arg(long, global = true, value_parser = expand_path)]
+    record: Option<PathBuf>,
+    /// Run a recorded session again, answering its prompts from the recording except those marked
+    /// ask = true
+    // The conflict sits here because --replay, unlike --record, is not on
+    // every subcommand
+    #[arg(long, value_parser = expand_path, conflicts_with = "record")]
+    replay: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Categorize files based on type and date
-    Categorize { recursive: bool },
+    Categorize(Box<CategorizeArgs>),
+    /// Reverse a previous run using its undo manifest
+    Undo {
+        /// Manifest written by the run to undo
+        #[arg(long, value_parser = expand_path)]
+        manifest: PathBuf,
+    },
     /// Flatten directory structure
-    DirectoryFlatten { recursive: bool },
+    DirectoryFlatten {
+        /// Flatten the whole tree instead of only the immediate subfolders
+        #[arg(short, long)]
+        recursive: bool,
+        /// Only flatten files at most this many levels below the input (2 = immediate subfolders)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+        max_depth: Option<u64>,
+        /// Also flatten git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Leave the emptied subdirectories in place
+        #[arg(long)]
+        keep_empty_dirs: bool,
+        /// List every planned move, renames and skips included, and ask before flattening
+        #[arg(long)]
+        dry_run: bool,
+        /// Copy files into the output directory, leaving the input tree intact
+        #[arg(long)]
+        copy: bool,
+        /// Flatten into this directory instead of the input directory
+        #[arg(long, value_parser = expand_path)]
+        output_dir: Option<PathBuf>,
+        /// Only flatten files with these extensions, e.g. mp3,flac
+        #[arg(long, value_delimiter = ',')]
+        extensions: Vec<String>,
+        /// Only flatten files whose relative path matches this glob (repeatable), e.g. "*.pdf"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob in place (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Separator between path components when prefixing names with their original path
+        #[arg(long, default_value = "_")]
+        prefix_separator: String,
+        /// Prefix every file with its original path, not only colliding names
+        #[arg(long)]
+        prefix_all: bool,
+        /// Delete extra copies whose contents match a file already flattened
+        #[arg(long)]
+        delete_identical: bool,
+        /// Print a progress line every 5% instead of a progress bar
+        #[arg(short, long)]
+        quiet: bool,
+        /// Keep older versions that lose a collision in this folder (default: <root>/superseded)
+        #[arg(long, value_parser = expand_path)]
+        superseded_dir: Option<PathBuf>,
+        /// Delete older versions that lose a collision instead of keeping them
+        #[arg(long, conflicts_with = "superseded_dir")]
+        delete_superseded: bool,
+        /// With newest-wins, keep the larger file when modification times are equal
+        #[arg(long)]
+        size_tiebreak: bool,
+        /// What to do with symlinks to files: skip, move (rewriting relative targets), or copy their targets
+        #[arg(long, value_parser = ["skip", "move", "copy"])]
+        symlinks: Option<String>,
+        /// Walk into symlinked directories, which may point outside the input
+        #[arg(long)]
+        follow_symlinks: bool,
+        /// Ask how to resolve every name collision, however many there are
+        #[arg(long)]
+        interactive_conflicts: bool,
+        /// When deciding each collision, ask only if there are at most this many (0 never asks)
+        #[arg(long, default_value_t = 10)]
+        conflict_prompt_limit: usize,
+        /// Write a CSV report of the moves instead of asking after the run
+        #[arg(long)]
+        report: bool,
+        /// Where to write the report (default: <input>/flatten-report-<timestamp>.csv)
+        #[arg(long, requires = "report", value_parser = expand_path)]
+        report_path: Option<PathBuf>,
+    },
     /// Optimize images
-    ImageOptimize { recursive: bool },
+    ImageOptimize {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Convert images to grayscale before encoding
+        #[arg(long)]
+        grayscale: bool,
+        /// Grayscale bit depth (implies --grayscale)
+        #[arg(long, value_parser = ["1", "2", "4", "8"])]
+        bit_depth: Option<String>,
+        /// Output filename template, e.g. "{stem}_{width}x{height}.{ext}"
+        #[arg(long)]
+        name_template: Option<String>,
+        /// Only convert files at least this large, e.g. "4MB"
+        #[arg(long)]
+        min_file_size: Option<String>,
+        /// Only convert files at most this large, e.g. "20MB"
+        #[arg(long)]
+        max_file_size: Option<String>,
+        /// Only convert images at least this many pixels wide
+        #[arg(long)]
+        min_width: Option<u32>,
+        /// Only convert images at least this many pixels tall
+        #[arg(long)]
+        min_height: Option<u32>,
+        /// Find images by their content rather than their file extension
+        #[arg(long)]
+        detect_by_content: bool,
+        /// Shrink quality (then dimensions) until each output fits, e.g. "500KB"
+        #[arg(long)]
+        target_size: Option<String>,
+        /// Only convert these source formats, e.g. "jpg,png"
+        #[arg(long, value_delimiter = ',')]
+        from: Vec<String>,
+        /// Re-encode images that are already in the target format
+        #[arg(long)]
+        recompress: bool,
+        /// Give outputs the current time instead of the source's mtime and permissions
+        #[arg(long)]
+        no_preserve_metadata: bool,
+        /// Convert images above this many megapixels one at a time (default 100)
+        #[arg(long)]
+        large_image_mp: Option<u64>,
+        /// Exit successfully even when some images fail to convert
+        #[arg(long)]
+        ignore_errors: bool,
+        /// List planned conversions and estimate savings without writing outputs
+        #[arg(long)]
+        dry_run: bool,
+        /// Convert pixels to sRGB instead of keeping embedded color profiles
+        #[arg(long)]
+        normalize_srgb: bool,
+    },
     /// Find and handle duplicate files
-    Deduplicate { recursive: bool },
-    /// Manage archives (create, extract, update, split)
-    Archive { recursive: bool },
+    Deduplicate {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Also scan git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Abort on the first file that cannot be hashed, deleted, or moved
+        #[arg(long)]
+        fail_fast: bool,
+        /// Overwrite duplicates before deleting them (needs --delete-mode permanent)
+        #[arg(long)]
+        secure: bool,
+    },
+    /// Manage archives (create, extract, update, split), or index and search what they hold
+    Archive {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Store files with identical contents once when creating or updating; extraction recreates the rest
+        #[arg(long)]
+        dedupe: bool,
+        #[command(subcommand)]
+        command: Option<ArchiveCommand>,
+    },
+    /// Pack files older than a threshold into archives, verify them, and remove the originals
+    ArchiveOld {
+        /// Archive files in subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to archive old files from
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Where archives and archive-index.csv go (default: next to the input directory)
+        #[arg(long, value_parser = expand_path)]
+        output_dir: Option<PathBuf>,
+        /// Archive files at least this old, e.g. "90d", "6m", or "2y"
+        #[arg(long)]
+        older_than: Option<String>,
+        /// Judge age by last access instead of last modification, where the filesystem keeps it
+        #[arg(long)]
+        by_access_time: bool,
+        /// Write one archive per year and month of the files' dates
+        #[arg(long)]
+        by_month: bool,
+        /// Archive format: zip, tar, tar.gz, or tar.zst
+        #[arg(long, value_parser = ["zip", "tar", "tar.gz", "tar.zst"])]
+        format: Option<String>,
+        /// Compression level: none, fast, balanced (default), or best
+        #[arg(long, value_parser = ["none", "fast", "balanced", "best"])]
+        compression: Option<String>,
+        /// Only archive files whose relative path matches this glob (repeatable), e.g. "*.log"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Keep files whose relative path matches this glob (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also archive inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// List the files that qualify and the estimated archive sizes without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first file or archive that fails
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Delete junk files and folders (.DS_Store, Thumbs.db, @eaDir, __MACOSX, ...)
+    CleanJunk {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to clean
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Also clean inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Abort on the first entry that cannot be deleted
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Remove empty directories, deepest first, so nested empty folders collapse in one pass
+    CleanEmptyDirs {
+        /// Look at the whole tree instead of only the immediate subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to clean
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Also remove directories holding only junk files (.DS_Store, Thumbs.db, ...), deleting the junk
+        #[arg(long)]
+        delete_junk: bool,
+        /// Only remove directories with nothing at all in them, keeping those holding only empty directories
+        #[arg(long)]
+        keep_parents: bool,
+        /// Keep directories modified more recently than this, e.g. "12h" or "7d"
+        #[arg(long)]
+        min_age: Option<String>,
+        /// Also remove the input directory itself when it ends up empty
+        #[arg(long)]
+        remove_root: bool,
+        /// Also look inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Abort on the first entry that cannot be removed
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Report what takes up space: largest files, heaviest directories, and usage by type
+    Usage {
+        /// Count the whole tree instead of only the top-level files
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to measure
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Rows in the largest-files and heaviest-directories tables
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+        /// Only count files whose relative path matches this glob (repeatable), e.g. "*.mkv"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob out (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also count git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Export the report (csv or json) instead of asking after it
+        #[arg(long, value_parser = ["csv", "json"])]
+        export: Option<String>,
+        /// Where to write the export (default: <input>/usage-report-<timestamp>.<ext>)
+        #[arg(long, requires = "export", value_parser = expand_path)]
+        export_path: Option<PathBuf>,
+    },
+    /// Write a SHA256SUMS-style manifest of a directory, or verify a directory against one
+    Checksum {
+        /// Cover the whole tree instead of only the top-level files
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to checksum, or with --check the directory the manifest's paths are
+        /// relative to (default: the manifest's directory)
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Digest algorithm: sha256, blake3, or xxh3 (with --check, default: from the manifest)
+        #[arg(long, value_parser = ["sha256", "blake3", "xxh3"])]
+        algorithm: Option<String>,
+        /// Where to write the manifest (default: <input>/SHA256SUMS, BLAKE3SUMS, or XXH3SUMS)
+        #[arg(long, conflicts_with = "check", value_parser = expand_path)]
+        output: Option<PathBuf>,
+        /// Verify the files against this manifest instead of writing one, like sha256sum -c
+        #[arg(short = 'c', long, value_parser = expand_path)]
+        check: Option<PathBuf>,
+        /// Check every entry again instead of resuming an interrupted verification
+        #[arg(long, requires = "check")]
+        fresh: bool,
+        /// Only cover files whose relative path matches this glob (repeatable), e.g. "*.mkv"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob out (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also cover git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Abort on the first file that cannot be read
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Find symlinks whose targets are missing, and delete, repair, or retarget them
+    BrokenLinks {
+        /// Check the whole tree instead of only the top-level links
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to check
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Also count links resolving outside this directory as broken (repeatable)
+        #[arg(long, value_delimiter = ',', value_parser = expand_path)]
+        allowed_root: Vec<PathBuf>,
+        /// What to do with broken links instead of asking: report, delete, repair, or retarget
+        /// (ask for each)
+        #[arg(long, value_parser = ["report", "delete", "repair", "retarget"])]
+        action: Option<String>,
+        /// Do not look for files that dangling links' targets moved to
+        #[arg(long)]
+        no_search: bool,
+        /// Only check links whose relative path matches this glob (repeatable), e.g. "music/**"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave links whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also check inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// List the broken links and what would happen to them without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Write a report (csv or json) instead of asking after the run
+        #[arg(long, value_parser = ["csv", "json"])]
+        report: Option<String>,
+        /// Where to write the report (default: <input>/broken-links-<timestamp>.<ext>)
+        #[arg(long, requires = "report", value_parser = expand_path)]
+        report_path: Option<PathBuf>,
+        /// Abort on the first link that cannot be checked or changed
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Make a destination directory match a source: copy new and changed files, optionally delete extras
+    Sync {
+        /// Sync subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to copy from
+        #[arg(long, value_parser = expand_path)]
+        source: Option<PathBuf>,
+        /// Directory to make match the source (created if missing)
+        #[arg(long, value_parser = expand_path)]
+        destination: Option<PathBuf>,
+        /// Delete destination files the source no longer has (listed before asking)
+        #[arg(long, conflicts_with = "no_delete")]
+        delete: bool,
+        /// Keep destination files the source no longer has, without asking
+        #[arg(long)]
+        no_delete: bool,
+        /// Only sync files whose relative path matches this glob (repeatable), e.g. "photos/**"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone on both sides (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also sync git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// List the files to add (+), update (~), and delete (-) without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first file that cannot be copied or deleted
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Compare two directory trees: files only in one, and files in both that differ
+    Compare {
+        /// Compare subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+        /// The first directory, A
+        #[arg(long, value_parser = expand_path)]
+        left: Option<PathBuf>,
+        /// The second directory, B
+        #[arg(long, value_parser = expand_path)]
+        right: Option<PathBuf>,
+        /// Hash files of equal size instead of comparing modification times
+        #[arg(long)]
+        deep: bool,
+        /// Digest algorithm for --deep: sha256, blake3, or xxh3 (default: sha256)
+        #[arg(long, requires = "deep", value_parser = ["sha256", "blake3", "xxh3"])]
+        algorithm: Option<String>,
+        /// Count files of equal size as the same whatever their modification times
+        #[arg(long, conflicts_with = "deep")]
+        ignore_mtime: bool,
+        /// Walk into symlinked directories and compare the files links point to
+        #[arg(long)]
+        follow_links: bool,
+        /// Only compare files whose relative path matches this glob (repeatable), e.g. "*.mkv"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob out on both sides (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also compare git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Output format: tree (default), csv, or json
+        #[arg(long, value_parser = ["tree", "csv", "json"])]
+        format: Option<String>,
+        /// Write the csv or json output to this file instead of printing it, and print the tree
+        #[arg(long, requires = "format", value_parser = expand_path)]
+        output: Option<PathBuf>,
+        /// Abort on the first file that cannot be read
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Overwrite files before deleting them, so they cannot be recovered from the disk
+    Shred {
+        /// Shred files in subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to shred files in
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Only shred files whose relative path matches this glob (repeatable), e.g. "*.pdf"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Keep files whose relative path matches this glob (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also shred inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Overwrite passes in order, comma-separated: zeros, ones, random (default: random,zeros)
+        #[arg(long, value_delimiter = ',')]
+        passes: Vec<String>,
+        /// Keep the file's length after overwriting instead of truncating it
+        #[arg(long)]
+        no_truncate: bool,
+        /// Unlink files under their own name instead of renaming them first
+        #[arg(long)]
+        no_rename: bool,
+        /// List the files and storage caveats without touching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first file that cannot be shredded
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Export every file's path, size, times, and MIME type, optionally with digests and image
+    /// details, as CSV or JSON Lines
+    Inventory {
+        /// List files in subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to take an inventory of
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Output format: csv (default) or jsonl
+        #[arg(long, value_parser = ["csv", "jsonl"])]
+        format: Option<String>,
+        /// Where to write the inventory, or - for standard output
+        /// (default: ./inventory-<timestamp>.<ext>)
+        #[arg(long, value_parser = expand_path)]
+        output: Option<PathBuf>,
+        /// Exactly these columns, comma-separated, from: path, size, mtime, ctime, extension,
+        /// mime, hash, width, height, exif_date, duration (default: path through mime, plus
+        /// --hash and --media columns)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
+        /// Add a hash column: sha256 (default), blake3, or xxh3
+        #[arg(long, num_args = 0..=1, default_missing_value = "sha256", value_parser = ["sha256", "blake3", "xxh3"])]
+        hash: Option<String>,
+        /// Add width and height columns for images and videos, EXIF dates, and video durations
+        #[arg(long)]
+        media: bool,
+        /// Walk into symlinked directories and list the files links point to
+        #[arg(long)]
+        follow_links: bool,
+        /// Only list files whose relative path matches this glob (repeatable), e.g. "*.jpg"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob out (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also list git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Abort on the first file that cannot be read
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Split one large file into numbered chunks with a manifest of their SHA-256 digests
+    SplitFile {
+        /// File to split
+        #[arg(long, value_parser = expand_path)]
+        file: Option<PathBuf>,
+        /// Size of each chunk, e.g. "100MB" or "4GB"
+        #[arg(long)]
+        chunk_size: Option<String>,
+        /// Where the chunks and manifest go (default: next to the file)
+        #[arg(long, value_parser = expand_path)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Join the chunks a split manifest lists back into the original file, checking each first
+    JoinFile {
+        /// The <file>.split.json manifest written by split-file
+        #[arg(long, value_parser = expand_path)]
+        manifest: Option<PathBuf>,
+        /// Where to write the joined file (default: its original name next to the chunks)
+        #[arg(long, value_parser = expand_path)]
+        output: Option<PathBuf>,
+        /// Replace an existing file at the output path
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Rename files in bulk: find/replace, case, numbering, date prefixes, extensions
+    Rename {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory with the files to rename
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Text to find in every name (without its extension)
+        #[arg(long)]
+        find: Option<String>,
+        /// What to replace the found text with; $1 or ${name} refer to groups with --regex
+        #[arg(long, requires = "find", default_value = "")]
+        replace: String,
+        /// Treat --find as a regular expression
+        #[arg(long, requires = "find")]
+        regex: bool,
+        /// Change the case of names: lower, upper, or title
+        #[arg(long, value_parser = ["lower", "upper", "title"])]
+        case: Option<String>,
+        /// Build new names from a pattern, e.g. "{name}_{n:03}" or "holiday_{n}"
+        #[arg(long)]
+        pattern: Option<String>,
+        /// First number of the {n} counter in each directory
+        #[arg(long, default_value_t = 1)]
+        start: usize,
+        /// Put the file's date in front of its name: mtime, or exif (falling back to mtime)
+        #[arg(long, value_parser = ["mtime", "exif"])]
+        date_prefix: Option<String>,
+        /// Format of the date prefix, e.g. "%Y%m%d" (default "%Y-%m-%d")
+        #[arg(long, requires = "date_prefix")]
+        date_format: Option<String>,
+        /// Rewrite extensions: lower, upper, or canonical (lower case, jpeg → jpg)
+        #[arg(long, value_parser = ["lower", "upper", "canonical"])]
+        ext: Option<String>,
+        /// Only rename files whose relative path matches this glob (repeatable), e.g. "*.jpg"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also rename inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Show the new names without renaming anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first file that cannot be renamed
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Rename files and directories whose names break on another filesystem: illegal and control
+    /// characters, trailing dots and spaces, mixed Unicode forms, emoji, and overlong names
+    SanitizeNames {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory with the names to sanitize
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Filesystem whose rules names follow: windows, posix, or fat32 (default: windows)
+        #[arg(long, value_parser = ["windows", "posix", "fat32"])]
+        target: Option<String>,
+        /// Spell names in plain ASCII, e.g. café as cafe (the default for fat32)
+        #[arg(long, conflicts_with = "unicode")]
+        ascii: bool,
+        /// Keep letters outside ASCII on fat32
+        #[arg(long)]
+        unicode: bool,
+        /// Remove emoji from names
+        #[arg(long)]
+        strip_emoji: bool,
+        /// Longest name allowed, in bytes for posix and UTF-16 units otherwise (default: 255)
+        #[arg(long)]
+        max_length: Option<usize>,
+        /// Character put in place of each one the target does not allow (default: _)
+        #[arg(long)]
+        replacement: Option<char>,
+        /// Keep the Unicode form of names instead of composing them to NFC
+        #[arg(long)]
+        no_normalize: bool,
+        /// Keep leading spaces and trailing dots and spaces
+        #[arg(long)]
+        no_trim: bool,
+        /// Only rename files, leaving directory names alone
+        #[arg(long)]
+        files_only: bool,
+        /// Only rename files whose relative path matches this glob (repeatable), e.g. "*.mkv"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also rename inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Show the new names without renaming anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first name that cannot be changed
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Give files and directories the same modes, and optionally owner (Unix only)
+    Permissions {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to normalize permissions in
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Octal mode for files (default: 644)
+        #[arg(long)]
+        file_mode: Option<String>,
+        /// Octal mode for directories (default: 755)
+        #[arg(long)]
+        dir_mode: Option<String>,
+        /// Keep files that are executable now executable
+        #[arg(long)]
+        keep_exec: bool,
+        /// Do not make scripts and binaries executable based on their contents
+        #[arg(long)]
+        no_detect_exec: bool,
+        /// Give everything this owner, as user or user:group (names or ids); needs root for the user
+        #[arg(long)]
+        owner: Option<String>,
+        /// Give everything this group (name or id)
+        #[arg(long, conflicts_with = "owner")]
+        group: Option<String>,
+        /// Only change files whose relative path matches this glob (repeatable), e.g. "*.jpg"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also change inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Show every change without making it
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first entry that cannot be changed
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Report the encodings and line endings of text files, and convert them to UTF-8 with LF or CRLF
+    NormalizeText {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory with the text files
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Convert files to UTF-8 with these line endings: lf or crlf (default: ask)
+        #[arg(long, value_parser = ["lf", "crlf"], conflicts_with = "report_only")]
+        to: Option<String>,
+        /// Byte order marks in converted files: keep, add, or remove (default: keep)
+        #[arg(long, requires = "to", value_parser = ["keep", "add", "remove"])]
+        bom: Option<String>,
+        /// Only report the encodings and line endings found, converting nothing
+        #[arg(long)]
+        report_only: bool,
+        /// Only look at files with these extensions, e.g. md,txt (default: common text formats)
+        #[arg(long, value_delimiter = ',')]
+        extensions: Vec<String>,
+        /// Only look at files whose relative path matches this glob (repeatable), e.g. "docs/**"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also look inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Show every conversion without making it
+        #[arg(long)]
+        dry_run: bool,
+        /// Write a per-file report (csv or json) instead of asking
+        #[arg(long, value_parser = ["csv", "json"])]
+        report: Option<String>,
+        /// Where to write the report (default: <input>/text-report-<timestamp>.<ext>)
+        #[arg(long, requires = "report", value_parser = expand_path)]
+        report_path: Option<PathBuf>,
+        /// Abort on the first file that cannot be read or converted
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Remove numbered copies of downloads, like "report (1).pdf", that match the file they are
+    /// named after, and move screenshots into one folder as Screenshot_YYYY-MM-DD_HH-MM-SS
+    CleanDownloads {
+        /// Process subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+        /// Directory to clean up, e.g. ~/Downloads or ~/Desktop
+        #[arg(long, value_parser = expand_path)]
+        input_dir: Option<PathBuf>,
+        /// Where screenshots go (default: <input>/Screenshots)
+        #[arg(long, value_parser = expand_path, conflicts_with = "no_screenshots")]
+        screenshots_dir: Option<PathBuf>,
+        /// Leave numbered copies alone
+        #[arg(long)]
+        no_copies: bool,
+        /// Leave screenshots alone
+        #[arg(long, conflicts_with = "no_copies")]
+        no_screenshots: bool,
+        /// Only look at files whose relative path matches this glob (repeatable), e.g. "*.pdf"
+        #[arg(long)]
+        include: Vec<String>,
+        /// Leave files whose relative path matches this glob alone (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Also look inside git, Mercurial, and Subversion working copies
+        #[arg(long)]
+        include_repos: bool,
+        /// Show what would be removed and moved without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Abort on the first file that cannot be removed or moved
+        #[arg(long)]
+        fail_fast: bool,
+    },
+    /// Keep running and pass files that arrive in watched directories through the actions of a
+    /// rules file
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+}
+
+/// Options of `categorize`, boxed as there are far more of them than of
+/// any other command
+#[derive(Args)]
+pub struct CategorizeArgs {
+    /// Process subdirectories recursively
+    #[arg(short, long)]
+    recursive: bool,
+    /// Copy files into categories instead of moving them
+    #[arg(long)]
+    copy: bool,
+    /// Directory to categorize; repeat or separate with commas to sweep several
+    /// into one organized tree
+    #[arg(long, value_delimiter = ',', value_parser = expand_path)]
+    input_dir: Vec<PathBuf>,
+    /// Create the categorized tree here instead of inside the input directory
+    #[arg(long, value_parser = expand_path)]
+    output_dir: Option<PathBuf>,
+    /// Put the file type rule's folders here instead of under by_type/
+    #[arg(long, value_parser = expand_path)]
+    type_dest: Option<PathBuf>,
+    /// Put the date rule's folders here instead of under by_date/
+    #[arg(long, value_parser = expand_path)]
+    date_dest: Option<PathBuf>,
+    /// Preferred date for the date rule: created, modified, or exif (also the creation time
+    /// recorded in videos)
+    #[arg(long, value_parser = ["created", "modified", "exif"])]
+    date_source: Option<String>,
+    /// Folder template for the date rule, e.g. "%Y/%m", "%Y-%m", or "%Y/Q%q"
+    #[arg(long)]
+    date_format: Option<String>,
+    /// Buckets for the size rule, e.g. "tiny:1MB,small:10MB,medium:100MB,large:1GB,huge"
+    #[arg(long)]
+    size_buckets: Option<String>,
+    /// Buckets for the age rule, e.g. "recent:30d,this year:365d,1-3 years:3y,ancient"
+    #[arg(long)]
+    age_buckets: Option<String>,
+    /// Options for the alphabetical rule: case-sensitive, no-digits, no-symbols, two-letter
+    #[arg(long)]
+    alphabetical: Option<String>,
+    /// How multiple rules combine: nested folders or first match wins
+    #[arg(long, value_parser = ["nested", "priority"])]
+    compose: Option<String>,
+    /// Group type folders by kind (by_type/images/jpg) instead of by extension only
+    #[arg(long, value_parser = ["flat", "grouped"])]
+    type_grouping: Option<String>,
+    /// Merge by_type alias folders from earlier runs (jpeg, JPG, ...) into their canonical folder and exit
+    #[arg(long)]
+    merge_aliases: bool,
+    /// Files no rule matches: uncategorized, sniff (Uncategorized/<content type>), leave, or prompt
+    #[arg(long, value_parser = ["uncategorized", "sniff", "leave", "prompt"])]
+    unmatched: Option<String>,
+    /// Hidden and junk files (.DS_Store, Thumbs.db, dotfiles): skip, categorize, or delete-junk
+    #[arg(long, value_parser = ["skip", "categorize", "delete-junk"])]
+    hidden: Option<String>,
+    /// Print per-file details
+    #[arg(short, long)]
+    verbose: bool,
+    /// Use a saved custom rule set instead of prompting for rules
+    #[arg(long)]
+    ruleset: Option<String>,
+    /// Import rule sets from a TOML file into the config before running
+    #[arg(long, value_parser = expand_path)]
+    import_rulesets: Option<PathBuf>,
+    /// Export saved rule sets to a TOML file and exit
+    #[arg(long, value_parser = expand_path)]
+    export_rulesets: Option<PathBuf>,
+    /// When the target exists: rename, skip, overwrite, or skip-identical
+    #[arg(long, value_parser = ["rename", "skip", "overwrite", "skip-identical"])]
+    on_conflict: Option<String>,
+    /// Show the resulting layout (file lists with --verbose) and ask before proceeding
+    #[arg(long)]
+    dry_run: bool,
+    /// Also write the undo manifest into the input directory
+    #[arg(long)]
+    manifest_in_input: bool,
+    /// Also categorize inside git, Mercurial, and Subversion working copies
+    #[arg(long)]
+    include_repos: bool,
+    /// Defer files modified within this many seconds (0 only defers unfinished downloads)
+    #[arg(long, default_value_t = 60)]
+    grace_period: u64,
+    /// Write a move report (csv or json) instead of asking after the run
+    #[arg(long, value_parser = ["csv", "json"])]
+    report: Option<String>,
+    /// Where to write the move report (default: <input>/categorize-report-<timestamp>.<ext>)
+    #[arg(long, requires = "report", value_parser = expand_path)]
+    report_path: Option<PathBuf>,
+    /// Abort on the first file that cannot be transferred instead of reporting it at the end
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+#[derive(Subcommand)]
+pub enum ArchiveCommand {
+    /// Record the entries of every zip and tar archive in a directory in the archive index; only
+    /// new and changed archives are read
+    Index {
+        /// Directory with the archives
+        #[arg(long, value_parser = expand_path)]
+        input_dir: PathBuf,
+        /// Index archives in subdirectories too
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Find files in the indexed archives by substring, or by glob such as "invoice-2021-*.pdf"
+    Search {
+        /// Text the entry's path contains (ignoring case), or a glob its path or name matches
+        pattern: String,
+        /// Only search archives under this directory
+        #[arg(long, value_parser = expand_path)]
+        within: Option<PathBuf>,
+        /// Extract the matching entries into this directory, one folder per archive, without
+        /// asking
+        #[arg(long, value_parser = expand_path)]
+        extract_to: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DaemonCommand {
+    /// Watch the directories of a rules file until stopped with Ctrl-C or SIGTERM
+    Run {
+        /// Rules file (TOML) naming the watched directories and their actions
+        #[arg(long, value_parser = expand_path)]
+        rules: PathBuf,
+    },
+    /// Check a rules file and show what it would do, without touching any file
+    Check {
+        /// Rules file (TOML) to check
+        #[arg(long, value_parser = expand_path)]
+        rules: PathBuf,
+    },
 }
 
 impl Cli {
     pub fn new() -> Result<Self> {
-        Ok(Self::parse())
+        let cli = Self::parse();
+        let Some(path) = &cli.replay else {
+            return Ok(cli);
+        };
+        if cli.command.is_some() {
+            anyhow::bail!("--replay runs the command that was recorded; give it without a command");
+        }
+        let recording = Recording::load(path)?;
+        // Flags given now apply on top of the recorded command line
+        let args = command_line();
+        let extra = without_flag(&args[1..], "--replay");
+        let replayed = Self::try_parse_from(args[..1].iter().chain(&recording.args).chain(&extra))
+            .with_context(|| format!("The command line recorded in {} no longer parses", path.display()))?;
+        session::start_replay(recording).with_context(|| format!("Cannot replay {}", path.display()))?;
+        Ok(replayed)
     }
 
+    /// Runs the command, saving its answers when `--record` asks for it
     pub async fn run(&self) -> Result<()> {
+        if !self.no_hooks {
+            hooks::enable();
+        }
+        if self.timings {
+            metrics::enable();
+        }
+        let Some(path) = &self.record else {
+            return self.run_command().await;
+        };
+        session::start_recording(without_flag(&command_line()[1..], "--record"));
+        let result = self.run_command().await;
+        let Some(mut recording) = session::finish_recording() else {
+            return result;
+        };
+        // A run that failed is not worth repeating
+        result?;
+        if console::user_attended() && !recording.answers.is_empty() {
+            let answers: Vec<String> = recording
+                .answers
+                .iter()
+                .map(|answer| format!("{}: {}", answer.prompt, answer.answer.describe()))
+                .collect();
+            let ask = dialoguer::MultiSelect::with_theme(&ColorfulTheme::default())
+                .with_prompt("Which answers should a replay ask for each time? (space to select, enter when done)")
+                .items(&answers)
+                .interact()?;
+            for index in ask {
+                recording.answers[index].ask = true;
+            }
+        }
+        recording.save(path)?;
+        println!(
+            "Recorded {} answers in {}; run them again with --replay {}",
+            recording.answers.len(),
+            path.display(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// The `--delete-mode` every module deletes files with
+    fn delete_mode(&self) -> DeleteMode {
+        match self.delete_mode.as_deref() {
+            Some("permanent") => DeleteMode::Permanent,
+            Some("dry-run") => DeleteMode::DryRun,
+            _ => DeleteMode::Trash,
+        }
+    }
+
+    /// The `--verify` level for moves that fall back to copying
+    fn verify(&self) -> Verify {
+        match self.verify.as_deref() {
+            Some("hash") => Verify::Hash,
+            _ => Verify::Size,
+        }
+    }
+
+    async fn run_command(&self) -> Result<()> {
         match &self.command {
             Some(cmd) => {
                 match cmd {
-                    Commands::Categorize { recursive } => {
-                        let organizer = FileCategorizer::new(*recursive);
-                        organizer.run().await?;
+                    Commands::Categorize(args) => {
+                        let CategorizeArgs {
+                            recursive,
+                            copy,
+                            input_dir,
+                            output_dir,
+                            type_dest,
+                            date_dest,
+                            date_source,
+                            date_format,
+                            size_buckets,
+                            age_buckets,
+                            alphabetical,
+                            compose,
+                            type_grouping,
+                            merge_aliases,
+                            unmatched,
+                            hidden,
+                            verbose,
+                            ruleset,
+                            import_rulesets,
+                            export_rulesets,
+                            on_conflict,
+                            dry_run,
+                            manifest_in_input,
+                            grace_period,
+                            include_repos,
+                            report,
+                            report_path,
+                            fail_fast,
+                        } = args.as_ref();
+                        if let Some(path) = export_rulesets {
+                            return FileCategorizer::export_rulesets(path);
+                        }
+                        if let Some(path) = import_rulesets {
+                            FileCategorizer::import_rulesets(path)?;
+                        }
+
+                        let mut organizer = FileCategorizer::new(*recursive)
+                            .with_verify(self.verify())
+                            .with_force(self.force)
+                            .with_sorted(self.sorted)
+                            .with_verbose(*verbose)
+                            .with_dry_run(*dry_run)
+                            .with_journal_in_input(*manifest_in_input)
+                            .with_grace_period(*grace_period)
+                            .with_include_repos(*include_repos)
+                            .with_merge_aliases(*merge_aliases)
+                            .with_delete_mode(self.delete_mode())
+                            .with_fail_fast(*fail_fast);
+                        if let Some(name) = ruleset {
+                            organizer = organizer.with_ruleset(name.clone());
+                        }
+                        if !input_dir.is_empty() {
+                            organizer = organizer.with_input_dirs(input_dir.clone());
+                        }
+                        if *copy {
+                            organizer = organizer.with_mode(TransferMode::Copy);
+                        }
+                        if let Some(dir) = output_dir {
+                            organizer = organizer.with_output_dir(dir.clone());
+                        }
+                        if let Some(dir) = type_dest {
+                            organizer = organizer.with_type_dest(dir.clone());
+                        }
+                        if let Some(dir) = date_dest {
+                            organizer = organizer.with_date_dest(dir.clone());
+                        }
+                        if let Some(source) = date_source {
+                            organizer = organizer.with_date_source(match source.as_str() {
+                                "modified" => DateSource::Modified,
+                                "exif" => DateSource::ExifFirst,
+                                _ => DateSource::Created,
+                            });
+                        }
+                        if let Some(format) = date_format {
+                            organizer = organizer.with_date_format(format.clone())?;
+                        }
+                        if let Some(spec) = size_buckets {
+                            organizer = organizer.with_size_buckets(spec)?;
+                        }
+                        if let Some(spec) = age_buckets {
+                            organizer = organizer.with_age_buckets(spec)?;
+                        }
+                        if let Some(spec) = alphabetical {
+                            organizer = organizer.with_alphabetical(spec)?;
+                        }
+                        if let Some(compose) = compose {
+                            organizer = organizer.with_composition(match compose.as_str() {
+                                "priority" => Composition::Priority,
+                                _ => Composition::Nested,
+                            });
+                        }
+                        if let Some(grouping) = type_grouping {
+                            organizer = organizer.with_type_grouping(grouping == "grouped");
+                        }
+                        if let Some(policy) = unmatched {
+                            organizer = organizer.with_unmatched(match policy.as_str() {
+                                "uncategorized" => UnmatchedPolicy::Uncategorized,
+                                "sniff" => UnmatchedPolicy::UncategorizedByContent,
+                                "leave" => UnmatchedPolicy::LeaveInPlace,
+                                _ => UnmatchedPolicy::Prompt,
+                            });
+                        }
+                        if let Some(policy) = hidden {
+                            organizer = organizer.with_hidden_policy(match policy.as_str() {
+                                "categorize" => HiddenPolicy::Categorize,
+                                "delete-junk" => HiddenPolicy::DeleteJunk,
+                                _ => HiddenPolicy::Skip,
+                            });
+                        }
+                        if let Some(policy) = on_conflict {
+                            organizer = organizer.with_on_conflict(match policy.as_str() {
+                                "skip" => ConflictPolicy::Skip,
+                                "overwrite" => ConflictPolicy::Overwrite,
+                                "skip-identical" => ConflictPolicy::SkipIdentical,
+                                _ => ConflictPolicy::Rename,
+                            });
+                        }
+                        if let Some(format) = report {
+                            let format = match format.as_str() {
+                                "json" => ReportFormat::Json,
+                                _ => ReportFormat::Csv,
+                            };
+                            organizer = organizer.with_report(format, report_path.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
                     }
-                    Commands::DirectoryFlatten { recursive } => {
-                        let organizer = DirectoryFlattener::new(*recursive);
+                    Commands::Undo { manifest } => {
+                        undo(manifest)?;
+                    }
+                    Commands::DirectoryFlatten {
+                        recursive,
+                        max_depth,
+                        include_repos,
+                        keep_empty_dirs,
+                        dry_run,
+                        copy,
+                        output_dir,
+                        extensions,
+                        include,
+                        exclude,
+                        prefix_separator,
+                        prefix_all,
+                        delete_identical,
+                        quiet,
+                        superseded_dir,
+                        delete_superseded,
+                        size_tiebreak,
+                        symlinks,
+                        follow_symlinks,
+                        interactive_conflicts,
+                        conflict_prompt_limit,
+                        report,
+                        report_path,
+                    } => {
+                        let mut organizer = DirectoryFlattener::new(*recursive)
+                            .with_verify(self.verify())
+                            .with_force(self.force)
+                            .with_sorted(self.sorted)
+                            .with_include_repos(*include_repos)
+                            .with_keep_empty_dirs(*keep_empty_dirs)
+                            .with_dry_run(*dry_run)
+                            .with_extensions(extensions.clone())
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_quiet(*quiet)
+                            .with_follow_symlinks(*follow_symlinks)
+                            .with_delete_superseded(*delete_superseded)
+                            .with_delete_mode(self.delete_mode())
+                            .with_size_tiebreak(*size_tiebreak)
+                            .with_interactive_conflicts(*interactive_conflicts)
+                            .with_conflict_prompt_limit(*conflict_prompt_limit)
+                            .with_prefix_separator(prefix_separator)?;
+                        if let Some(depth) = max_depth {
+                            organizer = organizer.with_max_depth(*depth as usize);
+                        }
+                        if *prefix_all {
+                            organizer = organizer.with_prefix_all(true);
+                        }
+                        if *delete_identical {
+                            organizer = organizer.with_delete_identical(true);
+                        }
+                        if *report {
+                            organizer = organizer.with_report(report_path.clone());
+                        }
+                        if let Some(dir) = superseded_dir {
+                            organizer = organizer.with_superseded_dir(dir.clone());
+                        }
+                        if let Some(policy) = symlinks {
+                            organizer = organizer.with_symlink_policy(match policy.as_str() {
+                                "move" => SymlinkPolicy::Move,
+                                "copy" => SymlinkPolicy::Copy,
+                                _ => SymlinkPolicy::Skip,
+                            });
+                        }
+                        if *copy {
+                            organizer = organizer.with_mode(TransferMode::Copy);
+                        }
+                        if let Some(dir) = output_dir {
+                            organizer = organizer.with_output_dir(dir.clone());
+                        }
                         organizer.run().await?;
                     }
-                    Commands::ImageOptimize { recursive } => {
-                        let organizer = ImageOptimizer::new(*recursive);
+                    Commands::ImageOptimize {
+                        recursive,
+                        grayscale,
+                        bit_depth,
+                        name_template,
+                        min_file_size,
+                        max_file_size,
+                        min_width,
+                        min_height,
+                        detect_by_content,
+                        target_size,
+                        from,
+                        recompress,
+                        no_preserve_metadata,
+                        large_image_mp,
+                        ignore_errors,
+                        dry_run,
+                        normalize_srgb,
+                    } => {
+                        let filter = CandidateFilter {
+                            min_file_size: min_file_size.as_deref().map(parse_size).transpose()?,
+                            max_file_size: max_file_size.as_deref().map(parse_size).transpose()?,
+                            min_width: *min_width,
+                            min_height: *min_height,
+                        };
+                        let mut organizer = ImageOptimizer::new(*recursive)
+                            .with_force(self.force)
+                            .with_filter(filter)
+                            .with_content_detection(*detect_by_content)
+                            .with_recompress(*recompress)
+                            .with_preserve_metadata(!*no_preserve_metadata)
+                            .with_ignore_errors(*ignore_errors)
+                            .with_dry_run(*dry_run);
+                        if *normalize_srgb {
+                            organizer = organizer.with_icc_mode(IccMode::NormalizeSrgb);
+                        }
+                        if !from.is_empty() {
+                            organizer = organizer.with_source_formats(parse_source_formats(from)?);
+                        }
+                        if let Some(megapixels) = large_image_mp {
+                            organizer = organizer.with_large_image_pixels(megapixels * 1_000_000);
+                        }
+                        if let Some(size) = target_size {
+                            organizer = organizer.with_target_size(parse_size(size)?);
+                        }
+                        if let Some(template) = name_template {
+                            organizer = organizer.with_name_template(template)?;
+                        }
+                        if *grayscale || bit_depth.is_some() {
+                            let bit_depth = bit_depth.as_deref().unwrap_or("8").parse()?;
+                            organizer = organizer.with_color_mode(ColorMode::Grayscale { bit_depth });
+                        }
                         organizer.run().await?;
                     }
-                    Commands::Deduplicate { recursive } => {
-                        let organizer = FileDeduplicator::new(*recursive);
+                    Commands::Deduplicate { recursive, include_repos, fail_fast, secure } => {
+                        let delete_mode = match (self.delete_mode(), *secure) {
+                            (DeleteMode::Permanent, true) => DeleteMode::Secure,
+                            (_, true) => anyhow::bail!("--secure needs --delete-mode permanent"),
+                            (mode, false) => mode,
+                        };
+                        let organizer = FileDeduplicator::new(*recursive)
+                            .with_verify(self.verify())
+                            .with_sorted(self.sorted)
+                            .with_assume_static(self.assume_static)
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_include_repos(*include_repos)
+                            .with_fail_fast(*fail_fast)
+                            .with_delete_mode(delete_mode);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Archive { recursive, dedupe, command } => match command {
+                        None => {
+                            let organizer = ArchiveManager::new(*recursive)
+                                .with_force(self.force)
+                                .with_assume_static(self.assume_static)
+                                .with_dedupe(*dedupe)
+                                .with_hash_cache(!self.no_hash_cache);
+                            organizer.run().await?;
+                        }
+                        Some(ArchiveCommand::Index { input_dir, recursive }) => {
+                            index_archives(input_dir, *recursive)?;
+                        }
+                        Some(ArchiveCommand::Search { pattern, within, extract_to }) => {
+                            search_archives(pattern, within.as_deref(), extract_to.as_deref())?;
+                        }
+                    },
+                    Commands::ArchiveOld {
+                        recursive,
+                        input_dir,
+                        output_dir,
+                        older_than,
+                        by_access_time,
+                        by_month,
+                        format,
+                        compression,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = AgeArchiver::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast)
+                            .with_delete_mode(self.delete_mode())
+                            .with_force(self.force)
+                            .with_assume_static(self.assume_static);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if let Some(dir) = output_dir {
+                            organizer = organizer.with_output_dir(dir.clone());
+                        }
+                        if let Some(age) = older_than {
+                            organizer = organizer.with_min_age(parse_age(age)?);
+                        }
+                        if *by_access_time {
+                            organizer = organizer.with_age_source(AgeSource::Accessed);
+                        }
+                        if *by_month {
+                            organizer = organizer.with_by_month(true);
+                        }
+                        if let Some(format) = format {
+                            organizer = organizer.with_archive_type(match format.as_str() {
+                                "tar" => ArchiveType::Tar,
+                                "tar.gz" => ArchiveType::TarGz,
+                                "tar.zst" => ArchiveType::TarZst,
+                                _ => ArchiveType::Zip,
+                            });
+                        }
+                        if let Some(level) = compression {
+                            organizer = organizer.with_compression(match level.as_str() {
+                                "none" => CompressionLevel::None,
+                                "fast" => CompressionLevel::Fast,
+                                "best" => CompressionLevel::Best,
+                                _ => CompressionLevel::Balanced,
+                            });
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::CleanJunk { recursive, input_dir, include_repos, fail_fast } => {
+                        let mut organizer = JunkCleaner::new(*recursive)
+                            .with_include_repos(*include_repos)
+                            .with_fail_fast(*fail_fast)
+                            .with_delete_mode(self.delete_mode());
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::CleanEmptyDirs {
+                        recursive,
+                        input_dir,
+                        delete_junk,
+                        keep_parents,
+                        min_age,
+                        remove_root,
+                        include_repos,
+                        fail_fast,
+                    } => {
+                        let mut organizer = EmptyDirCleaner::new(*recursive)
+                            .with_delete_junk(*delete_junk)
+                            .with_nested(!*keep_parents)
+                            .with_protect_root(!*remove_root)
+                            .with_include_repos(*include_repos)
+                            .with_fail_fast(*fail_fast)
+                            .with_delete_mode(self.delete_mode());
+                        if let Some(age) = min_age {
+                            organizer = organizer.with_min_age(parse_age(age)?);
+                        }
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Usage {
+                        recursive,
+                        input_dir,
+                        top,
+                        include,
+                        exclude,
+                        include_repos,
+                        export,
+                        export_path,
+                    } => {
+                        let mut organizer = UsageReporter::new(*recursive)
+                            .with_top(*top)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if let Some(format) = export {
+                            let format = match format.as_str() {
+                                "json" => ReportFormat::Json,
+                                _ => ReportFormat::Csv,
+                            };
+                            organizer = organizer.with_export(Some(format), export_path.clone());
+                        }
                         organizer.run().await?;
                     }
-                    Commands::Archive { recursive } => {
-                        let organizer = ArchiveManager::new(*recursive);
+                    Commands::Checksum {
+                        recursive,
+                        input_dir,
+                        algorithm,
+                        output,
+                        check,
+                        fresh,
+                        include,
+                        exclude,
+                        include_repos,
+                        fail_fast,
+                    } => {
+                        let mut organizer = ChecksumManager::new(*recursive)
+                            .with_fresh(*fresh)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_fail_fast(*fail_fast);
+                        organizer = match check {
+                            Some(manifest) => organizer.with_verify(manifest.clone()),
+                            None => organizer.with_generate(output.clone()),
+                        };
+                        if let Some(algorithm) = algorithm {
+                            organizer = organizer.with_algorithm(match algorithm.as_str() {
+                                "blake3" => Algorithm::Blake3,
+                                "xxh3" => Algorithm::Xxh3,
+                                _ => Algorithm::Sha256,
+                            });
+                        }
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::BrokenLinks {
+                        recursive,
+                        input_dir,
+                        allowed_root,
+                        action,
+                        no_search,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        report,
+                        report_path,
+                        fail_fast,
+                    } => {
+                        let mut organizer = BrokenLinkFinder::new(*recursive)
+                            .with_allowed_roots(allowed_root.clone())
+                            .with_find_repairs(!*no_search)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_dry_run(*dry_run)
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if let Some(action) = action {
+                            organizer = organizer.with_action(match action.as_str() {
+                                "delete" => LinkAction::Delete,
+                                "repair" => LinkAction::Repair,
+                                "retarget" => LinkAction::Retarget,
+                                _ => LinkAction::Report,
+                            });
+                        }
+                        if let Some(format) = report {
+                            let format = match format.as_str() {
+                                "json" => ReportFormat::Json,
+                                _ => ReportFormat::Csv,
+                            };
+                            organizer = organizer.with_report(Some(format), report_path.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Sync {
+                        recursive,
+                        source,
+                        destination,
+                        delete,
+                        no_delete,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = DirectorySync::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast)
+                            .with_delete_mode(self.delete_mode())
+                            .with_verify(self.verify())
+                            .with_force(self.force);
+                        if let Some(dir) = source {
+                            organizer = organizer.with_source(dir.clone());
+                        }
+                        if let Some(dir) = destination {
+                            organizer = organizer.with_destination(dir.clone());
+                        }
+                        if *delete || *no_delete {
+                            organizer = organizer.with_delete(*delete);
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Compare {
+                        recursive,
+                        left,
+                        right,
+                        deep,
+                        algorithm,
+                        ignore_mtime,
+                        follow_links,
+                        include,
+                        exclude,
+                        include_repos,
+                        format,
+                        output,
+                        fail_fast,
+                    } => {
+                        let format = match format.as_deref() {
+                            Some("csv") => Some(ReportFormat::Csv),
+                            Some("json") => Some(ReportFormat::Json),
+                            _ => None,
+                        };
+                        let mut organizer = TreeComparer::new(*recursive)
+                            .with_deep(*deep)
+                            .with_ignore_mtime(*ignore_mtime)
+                            .with_follow_links(*follow_links)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_report(format, output.clone())
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = left {
+                            organizer = organizer.with_left(dir.clone());
+                        }
+                        if let Some(dir) = right {
+                            organizer = organizer.with_right(dir.clone());
+                        }
+                        if let Some(algorithm) = algorithm {
+                            organizer = organizer.with_algorithm(match algorithm.as_str() {
+                                "blake3" => Algorithm::Blake3,
+                                "xxh3" => Algorithm::Xxh3,
+                                _ => Algorithm::Sha256,
+                            });
+                        }
+                        check_differences(organizer.run().await?)?;
+                    }
+                    Commands::Shred {
+                        recursive,
+                        input_dir,
+                        include,
+                        exclude,
+                        include_repos,
+                        passes,
+                        no_truncate,
+                        no_rename,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = FileShredder::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_truncate(!*no_truncate)
+                            .with_rename(!*no_rename)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if !passes.is_empty() {
+                            let passes = passes.iter().map(|pass| Pass::from_name(pass)).collect::<Result<Vec<_>>>()?;
+                            organizer = organizer.with_passes(passes);
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Inventory {
+                        recursive,
+                        input_dir,
+                        format,
+                        output,
+                        columns,
+                        hash,
+                        media,
+                        follow_links,
+                        include,
+                        exclude,
+                        include_repos,
+                        fail_fast,
+                    } => {
+                        let columns = columns.iter().map(|name| Column::from_name(name)).collect::<Result<Vec<_>>>()?;
+                        let algorithm = hash.as_deref().map(|algorithm| match algorithm {
+                            "blake3" => Algorithm::Blake3,
+                            "xxh3" => Algorithm::Xxh3,
+                            _ => Algorithm::Sha256,
+                        });
+                        let mut organizer = InventoryExporter::new(*recursive)
+                            .with_format(match format.as_deref() {
+                                Some("jsonl") => InventoryFormat::JsonLines,
+                                _ => InventoryFormat::Csv,
+                            })
+                            .with_output(output.clone())
+                            .with_columns(columns)
+                            .with_algorithm(algorithm)
+                            .with_media(*media)
+                            .with_follow_links(*follow_links)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::SplitFile { file, chunk_size, output_dir } => {
+                        let mut organizer = FileSplitter::new(false).with_split().with_force(self.force);
+                        if let Some(file) = file {
+                            organizer = organizer.with_file(file.clone());
+                        }
+                        if let Some(size) = chunk_size {
+                            organizer = organizer.with_chunk_size(parse_size(size)?);
+                        }
+                        if let Some(dir) = output_dir {
+                            organizer = organizer.with_output_dir(dir.clone());
+                        }
                         organizer.run().await?;
                     }
+                    Commands::JoinFile { manifest, output, overwrite } => {
+                        let mut organizer = FileSplitter::new(false)
+                            .with_join()
+                            .with_overwrite(*overwrite)
+                            .with_force(self.force);
+                        if let Some(manifest) = manifest {
+                            organizer = organizer.with_manifest(manifest.clone());
+                        }
+                        if let Some(path) = output {
+                            organizer = organizer.with_output(path.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Rename {
+                        recursive,
+                        input_dir,
+                        find,
+                        replace,
+                        regex,
+                        case,
+                        pattern,
+                        start,
+                        date_prefix,
+                        date_format,
+                        ext,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = FileRenamer::new(*recursive)
+                            .with_include_repos(*include_repos)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_start(*start)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if let Some(find) = find {
+                            organizer = organizer.with_replacement(if *regex {
+                                Replacement::regex(find, replace)?
+                            } else {
+                                Replacement::literal(find, replace)?
+                            });
+                        }
+                        if let Some(case) = case {
+                            organizer = organizer.with_case(match case.as_str() {
+                                "upper" => CaseTransform::Upper,
+                                "title" => CaseTransform::Title,
+                                _ => CaseTransform::Lower,
+                            });
+                        }
+                        if let Some(pattern) = pattern {
+                            organizer = organizer.with_pattern(NamePattern::parse(pattern)?);
+                        }
+                        if let Some(source) = date_prefix {
+                            let mut prefix = DatePrefix::new(match source.as_str() {
+                                "exif" => file_renamer::DateSource::Exif,
+                                _ => file_renamer::DateSource::Modified,
+                            });
+                            if let Some(format) = date_format {
+                                prefix = prefix.with_format(format)?;
+                            }
+                            organizer = organizer.with_date_prefix(prefix);
+                        }
+                        if let Some(style) = ext {
+                            organizer = organizer.with_extension_style(match style.as_str() {
+                                "upper" => ExtensionStyle::Upper,
+                                "canonical" => ExtensionStyle::Canonical,
+                                _ => ExtensionStyle::Lower,
+                            });
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::SanitizeNames {
+                        recursive,
+                        input_dir,
+                        target,
+                        ascii,
+                        unicode,
+                        strip_emoji,
+                        max_length,
+                        replacement,
+                        no_normalize,
+                        no_trim,
+                        files_only,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = NameSanitizer::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_directories(!*files_only)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        // Any policy flag skips the policy prompts
+                        let preset = target.is_some()
+                            || *ascii
+                            || *unicode
+                            || *strip_emoji
+                            || max_length.is_some()
+                            || replacement.is_some()
+                            || *no_normalize
+                            || *no_trim;
+                        if preset {
+                            let mut policy = NamePolicy::new(Target::from_name(target.as_deref().unwrap_or("windows"))?);
+                            policy.ascii = (policy.ascii || *ascii) && !*unicode;
+                            policy.strip_emoji = *strip_emoji;
+                            policy.normalize = !*no_normalize;
+                            policy.trim = !*no_trim;
+                            if let Some(length) = max_length {
+                                policy.max_length = *length;
+                            }
+                            if let Some(replacement) = replacement {
+                                policy.replacement = *replacement;
+                            }
+                            organizer = organizer.with_policy(policy);
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Permissions {
+                        recursive,
+                        input_dir,
+                        file_mode,
+                        dir_mode,
+                        keep_exec,
+                        no_detect_exec,
+                        owner,
+                        group,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = PermissionNormalizer::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_keep_executable(*keep_exec)
+                            .with_detect_executable(!*no_detect_exec)
+                            .with_modes(
+                                parse_mode(file_mode.as_deref().unwrap_or("644"))?,
+                                parse_mode(dir_mode.as_deref().unwrap_or("755"))?,
+                            )
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        match owner.as_deref().map(|owner| owner.split_once(':').unwrap_or((owner, ""))) {
+                            Some((user, group)) => {
+                                organizer = organizer.with_owner(user.to_string());
+                                if !group.is_empty() {
+                                    organizer = organizer.with_group(group.to_string());
+                                }
+                            }
+                            None => {
+                                if let Some(group) = group {
+                                    organizer = organizer.with_group(group.clone());
+                                }
+                            }
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::NormalizeText {
+                        recursive,
+                        input_dir,
+                        to,
+                        bom,
+                        report_only,
+                        extensions,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        report,
+                        report_path,
+                        fail_fast,
+                    } => {
+                        let mut organizer = TextNormalizer::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        if !extensions.is_empty() {
+                            organizer = organizer.with_extensions(extensions.clone());
+                        }
+                        if let Some(newline) = to {
+                            organizer = organizer.with_conversion(Some(Conversion {
+                                newline: Newline::from_name(newline)?,
+                                bom: BomPolicy::from_name(bom.as_deref().unwrap_or("keep"))?,
+                            }));
+                        } else if *report_only {
+                            organizer = organizer.with_conversion(None);
+                        }
+                        if let Some(format) = report {
+                            let format = match format.as_str() {
+                                "json" => ReportFormat::Json,
+                                _ => ReportFormat::Csv,
+                            };
+                            organizer = organizer.with_report(Some(format), report_path.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::CleanDownloads {
+                        recursive,
+                        input_dir,
+                        screenshots_dir,
+                        no_copies,
+                        no_screenshots,
+                        include,
+                        exclude,
+                        include_repos,
+                        dry_run,
+                        fail_fast,
+                    } => {
+                        let mut organizer = DownloadCleaner::new(*recursive)
+                            .with_include(include.clone())
+                            .with_exclude(exclude.clone())
+                            .with_include_repos(*include_repos)
+                            .with_screenshots_dir(screenshots_dir.clone())
+                            .with_copies(!*no_copies)
+                            .with_screenshots(!*no_screenshots)
+                            .with_delete_mode(self.delete_mode())
+                            .with_hash_cache(!self.no_hash_cache)
+                            .with_dry_run(*dry_run)
+                            .with_fail_fast(*fail_fast);
+                        if let Some(dir) = input_dir {
+                            organizer = organizer.with_input_dir(dir.clone());
+                        }
+                        check_failures(organizer.run().await?)?;
+                    }
+                    Commands::Daemon { command } => match command {
+                        DaemonCommand::Run { rules } => {
+                            run_daemon(Daemon::new(DaemonRules::load(rules)?, self.verify())?).await?;
+                        }
+                        DaemonCommand::Check { rules } => {
+                            let daemon = Daemon::new(DaemonRules::load(rules)?, self.verify())?;
+                            println!("{} is valid:", rules.display());
+                            for line in daemon.describe() {
+                                println!("  {}", line);
+                            }
+                        }
+                    },
                 }
             }
             None => {
@@ -71,6 +1971,22 @@ impl Cli {
                     "Optimize images",
                     "Find duplicates",
                     "Manage archives",
+                    "Clean junk files",
+                    "Rename files",
+                    "Remove empty directories",
+                    "Report disk usage",
+                    "Generate or verify checksums",
+                    "Find broken links",
+                    "Sync directories",
+                    "Archive old files",
+                    "Split or join a large file",
+                    "Compare directories",
+                    "Securely delete files",
+                    "Export a file inventory",
+                    "Sanitize file names",
+                    "Normalize permissions",
+                    "Normalize text files",
+                    "Clean up downloads and screenshots",
                 ];
                 
                 let selection = Select::with_theme(&ColorfulTheme::default())
@@ -79,36 +1995,344 @@ impl Cli {
                     .default(0)
                     .interact()?;
 
-                let recursive = dialoguer::Confirm::with_theme(&ColorfulTheme::default())
+                let recursive = Confirm::with_theme(&ColorfulTheme::default())
                     .with_prompt("Process subdirectories recursively?")
                     .default(true)
                     .interact()?;
 
                 match selection {
                     0 => {
-                        let organizer = FileCategorizer::new(recursive);
-                        organizer.run().await?;
+                        let organizer = FileCategorizer::new(recursive)
+                            .with_verify(self.verify())
+                            .with_force(self.force)
+                            .with_sorted(self.sorted);
+                        check_failures(organizer.run().await?)?;
                     }
                     1 => {
-                        let organizer = DirectoryFlattener::new(recursive);
+                        let organizer = DirectoryFlattener::new(recursive)
+                            .with_verify(self.verify())
+                            .with_force(self.force)
+                            .with_sorted(self.sorted);
                         organizer.run().await?;
                     }
                     2 => {
-                        let organizer = ImageOptimizer::new(recursive);
+                        let organizer = ImageOptimizer::new(recursive).with_force(self.force);
                         organizer.run().await?;
                     }
                     3 => {
-                        let organizer = FileDeduplicator::new(recursive);
-                        organizer.run().await?;
+                        let organizer = FileDeduplicator::new(recursive)
+                            .with_delete_mode(self.delete_mode())
+                            .with_verify(self.verify())
+                            .with_sorted(self.sorted)
+                            .with_assume_static(self.assume_static)
+                            .with_hash_cache(!self.no_hash_cache);
+                        check_failures(organizer.run().await?)?;
                     }
                     4 => {
-                        let organizer = ArchiveManager::new(recursive);
+                        let organizer = ArchiveManager::new(recursive)
+                            .with_force(self.force)
+                            .with_assume_static(self.assume_static)
+                            .with_hash_cache(!self.no_hash_cache);
                         organizer.run().await?;
                     }
+                    5 => {
+                        let organizer = JunkCleaner::new(recursive).with_delete_mode(self.delete_mode());
+                        check_failures(organizer.run().await?)?;
+                    }
+                    6 => {
+                        let organizer = FileRenamer::new(recursive);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    7 => {
+                        let organizer = EmptyDirCleaner::new(recursive).with_delete_mode(self.delete_mode());
+                        check_failures(organizer.run().await?)?;
+                    }
+                    8 => {
+                        let organizer = UsageReporter::new(recursive);
+                        organizer.run().await?;
+                    }
+                    9 => {
+                        let organizer = ChecksumManager::new(recursive).with_hash_cache(!self.no_hash_cache);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    10 => {
+                        let organizer = BrokenLinkFinder::new(recursive).with_hash_cache(!self.no_hash_cache);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    11 => {
+                        let organizer = DirectorySync::new(recursive)
+                            .with_delete_mode(self.delete_mode())
+                            .with_verify(self.verify())
+                            .with_force(self.force);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    12 => {
+                        let organizer = AgeArchiver::new(recursive)
+                            .with_delete_mode(self.delete_mode())
+                            .with_force(self.force)
+                            .with_assume_static(self.assume_static);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    13 => {
+                        let organizer = FileSplitter::new(recursive).with_force(self.force);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    14 => {
+                        let organizer = TreeComparer::new(recursive).with_hash_cache(!self.no_hash_cache);
+                        check_differences(organizer.run().await?)?;
+                    }
+                    15 => {
+                        let organizer = FileShredder::new(recursive);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    16 => {
+                        let organizer = InventoryExporter::new(recursive).with_hash_cache(!self.no_hash_cache);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    17 => {
+                        let organizer = NameSanitizer::new(recursive);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    18 => {
+                        let organizer = PermissionNormalizer::new(recursive);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    19 => {
+                        let organizer = TextNormalizer::new(recursive);
+                        check_failures(organizer.run().await?)?;
+                    }
+                    20 => {
+                        let organizer = DownloadCleaner::new(recursive)
+                            .with_delete_mode(self.delete_mode())
+                            .with_hash_cache(!self.no_hash_cache);
+                        check_failures(organizer.run().await?)?;
+                    }
                     _ => unreachable!(),
                 }
             }
         }
         Ok(())
     }
-} 
\ No newline at end of file
+}
+
+/// The program's arguments, as `--record` saves them
+fn command_line() -> Vec<String> {
+    std::env::args_os().map(|arg| arg.to_string_lossy().into_owned()).collect()
+}
+
+/// `args` without `flag` and its value, given as `flag value` or
+/// `flag=value`
+fn without_flag(args: &[String], flag: &str) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            args.next();
+        } else if !arg.starts_with(&format!("{}=", flag)) {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Exits unsuccessfully when a run completed but some files failed; the
+/// module has already listed them
+fn check_failures(summary: Summary) -> Result<()> {
+    if summary.failed > 0 {
+        anyhow::bail!("{} files could not be processed", summary.failed);
+    }
+    Ok(())
+}
+
+/// Exits unsuccessfully when a comparison found differences or files it
+/// could not compare, so scripts can check a copy by the exit code
+fn check_differences(summary: Summary) -> Result<()> {
+    if summary.failed > 0 {
+        anyhow::bail!("{} files differ or could not be compared", summary.failed);
+    }
+    Ok(())
+}
+
+/// Brings the archive index up to date with the archives in `dir`
+fn index_archives(dir: &Path, recursive: bool) -> Result<()> {
+    let path = ArchiveIndex::default_path()?;
+    let mut index = ArchiveIndex::load(&path)?;
+    let spinner = create_spinner("Indexing archives...");
+    let summary = index.refresh(dir, recursive, &*spinner)?;
+    index.save(&path)?;
+    spinner.finish_with_message(format!(
+        "{} archives indexed, {} unchanged, {} dropped; {} entries in the index",
+        summary.indexed, summary.unchanged, summary.dropped, summary.entries
+    ));
+    print_inaccessible(&summary.inaccessible, "archives inside them were not indexed");
+    print_errors(&summary.errors);
+    check_failures(Summary {
+        failed: summary.errors.len(),
+        ..Summary::default()
+    })
+}
+
+/// Lists the indexed entries matching `pattern`, then extracts them into
+/// `extract_to`, or asks whether to when it is not given
+fn search_archives(pattern: &str, within: Option<&Path>, extract_to: Option<&Path>) -> Result<()> {
+    let index = ArchiveIndex::load(&ArchiveIndex::default_path()?)?;
+    if index.archives.is_empty() {
+        anyhow::bail!("The archive index is empty; run `archive index --input-dir <dir>` first");
+    }
+    let hits = index.search(pattern, within)?;
+    if hits.is_empty() {
+        println!("No indexed entry matches '{}'", pattern);
+        return Ok(());
+    }
+    let mut by_archive: Vec<(&Path, Vec<&str>)> = Vec::new();
+    for hit in &hits {
+        match by_archive.last_mut() {
+            Some((archive, names)) if *archive == hit.archive => names.push(hit.entry.name.as_str()),
+            _ => by_archive.push((hit.archive, vec![hit.entry.name.as_str()])),
+        }
+        let modified = hit.entry.modified.map(|time| time.format("%Y-%m-%d %H:%M").to_string());
+        println!(
+            "{}  {:>10}  {:<16}  {}",
+            hit.entry.name,
+            format_size(hit.entry.size, BINARY),
+            modified.unwrap_or_default(),
+            hit.archive.display()
+        );
+    }
+    println!();
+    println!("{} entries in {} archives", hits.len(), by_archive.len());
+
+    let output_dir = match extract_to {
+        Some(dir) => dir.to_path_buf(),
+        // Nobody to ask when the output goes to a pipe
+        None if !console::user_attended() => return Ok(()),
+        None => {
+            let extract = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Extract these entries?")
+                .default(false)
+                .interact()?;
+            if !extract {
+                return Ok(());
+            }
+            get_directory_from_user("Extract into")?
+        }
+    };
+    for (archive, names) in by_archive {
+        let dir = output_dir.join(archive_stem(archive));
+        let summary = archive_manager::index::extract_entries(archive, &names, &dir)?;
+        println!("{} entries extracted into {}", summary.extracted.len(), dir.display());
+        for path in summary.existing {
+            println!("  {} already exists, not overwritten", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// The archive's file name without its `.zip`, `.tar.zst`, ... extension
+fn archive_stem(archive: &Path) -> String {
+    let stem = archive.file_stem().unwrap_or_default().to_string_lossy();
+    stem.strip_suffix(".tar").unwrap_or(&stem).to_string()
+}
+
+/// Runs the daemon on a worker thread until Ctrl-C or SIGTERM, then lets
+/// it finish the file in progress
+async fn run_daemon(mut daemon: Daemon) -> Result<()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&stop);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        println!("Stopping after the file in progress...");
+        flag.store(true, Ordering::SeqCst);
+    });
+    tokio::task::spawn_blocking(move || daemon.run(&stop)).await?
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is one
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = terminate.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Replays a journal in reverse and reports the files it could not restore
+fn undo(manifest: &Path) -> Result<()> {
+    let journal = Journal::load(manifest)?;
+    println!(
+        "Undoing {} run from {} ({} files)",
+        journal.operation,
+        journal.created,
+        journal.entries.len()
+    );
+
+    let summary = journal.undo();
+    println!("{} files restored", summary.restored);
+    for path in &summary.deleted {
+        println!("Warning: {} was deleted by the run and cannot be restored", path.display());
+    }
+    if !summary.failed.is_empty() {
+        for (path, reason) in &summary.failed {
+            println!("Could not restore {}: {}", path.display(), reason);
+        }
+        anyhow::bail!("{} files could not be restored", summary.failed.len());
+    }
+    Ok(())
+}
+
+/// Maps `--from` extensions such as "jpg" or "webp" to image formats
+fn parse_source_formats(extensions: &[String]) -> Result<Vec<ImageFormat>> {
+    extensions
+        .iter()
+        .map(|ext| match ImageFormat::from_extension(ext.trim()) {
+            Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif)) => {
+                Ok(format)
+            }
+            _ => anyhow::bail!("Unsupported source format: {} (expected jpg, png, webp, or gif)", ext),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    /// Parses `args` on a thread with the 8 MiB stack the binary's main
+    /// thread has; an unoptimized parse of every subcommand outgrows the
+    /// 2 MiB a test thread gets
+    fn parse(args: &'static [&'static str]) -> Cli {
+        std::thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn(move || Cli::try_parse_from(args).unwrap())
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn recursive_is_a_flag() {
+        let cli = parse(&["file-organizer-rust", "image-optimize", "--recursive"]);
+        assert!(matches!(cli.command, Some(Commands::ImageOptimize { recursive: true, .. })));
+        let cli = parse(&["file-organizer-rust", "image-optimize"]);
+        assert!(matches!(cli.command, Some(Commands::ImageOptimize { recursive: false, .. })));
+    }
+
+    #[test]
+    fn timings_are_off_unless_asked_for() {
+        assert!(!parse(&["file-organizer-rust", "image-optimize"]).timings);
+        assert!(parse(&["file-organizer-rust", "image-optimize", "--timings"]).timings);
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+
+use crate::utils::{atomic::write_atomic, names::Normalization};
+
+/// A named set of custom categorization rules: category -> extensions or
+/// `/pattern/flags` entries, kept in declaration order
+pub type Ruleset = IndexMap<String, Vec<String>>;
+
+/// User configuration stored as TOML in the platform config directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub categorize: CategorizeConfig,
+    #[serde(default)]
+    pub junk: JunkConfig,
+    #[serde(default)]
+    pub names: NamesConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+}
+
+/// `[junk]`: file and directory names treated as junk on top of the
+/// built-in lists (`.DS_Store`, `Thumbs.db`, `@eaDir`, ...), left out of
+/// every module's walk
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JunkConfig {
+    #[serde(default)]
+    pub files: Vec<String>,
+    #[serde(default)]
+    pub dirs: Vec<String>,
+}
+
+/// `[names]`: how the file and folder names the tool creates are written
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NamesConfig {
+    /// "nfc" (default), "nfd", or "none" to keep names as they are
+    #[serde(default)]
+    pub normalization: Normalization,
+}
+
+/// `[hooks]`: shell commands run when an operation finishes, given its
+/// summary in `FO_*` variables and as JSON on stdin. `[hooks.modules.<command>]`
+/// overrides them for one command, e.g. `[hooks.modules.archive-old]`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// Seconds a hook may run before it is killed
+    #[serde(default = "default_hook_timeout")]
+    pub timeout: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, Hooks>,
+}
+
+/// The hooks of one command
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self { on_success: None, on_failure: None, timeout: default_hook_timeout(), modules: BTreeMap::new() }
+    }
+}
+
+impl HooksConfig {
+    /// The command to run when `module` finishes, successfully or not; a
+    /// command's own hook replaces the global one
+    pub fn command(&self, module: &str, success: bool) -> Option<&str> {
+        let hooks = self.modules.get(module);
+        let (own, global) = if success {
+            (hooks.and_then(|hooks| hooks.on_success.as_deref()), self.on_success.as_deref())
+        } else {
+            (hooks.and_then(|hooks| hooks.on_failure.as_deref()), self.on_failure.as_deref())
+        };
+        own.or(global)
+    }
+}
+
+fn default_hook_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CategorizeConfig {
+    #[serde(default)]
+    pub rulesets: BTreeMap<String, Ruleset>,
+    /// Extension -> category entries that replace or extend the built-in preset
+    #[serde(default)]
+    pub preset_overrides: BTreeMap<String, String>,
+    /// Camera name as read from EXIF -> folder name, e.g.
+    /// "NIKON CORPORATION NIKON D750" = "Nikon D750"
+    #[serde(default)]
+    pub camera_aliases: BTreeMap<String, String>,
+    /// Extension -> canonical extension for the file type rule, on top of
+    /// the built-in aliases such as jpeg -> jpg
+    #[serde(default)]
+    pub extension_aliases: BTreeMap<String, String>,
+}
+
+/// Standalone file used to share rule sets between machines
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RulesetFile {
+    #[serde(default)]
+    pub rulesets: BTreeMap<String, Ruleset>,
+}
+
+impl Config {
+    /// `<config dir>/file-organizer-rust/config.toml`
+    pub fn path() -> Result<PathBuf> {
+        let dir = dirs::config_dir().ok_or_else(|| anyhow::anyhow!("No config directory on this platform"))?;
+        Ok(dir.join("file-organizer-rust").join("config.toml"))
+    }
+
+    /// Loads the config, returning the defaults when no file exists yet
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let config: Self = read_toml(&path)?;
+        for (name, ruleset) in &config.categorize.rulesets {
+            validate_ruleset(name, ruleset).with_context(|| format!("Invalid config {}", path.display()))?;
+        }
+        validate_preset_overrides(&config.categorize.preset_overrides)
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        for (alias, canonical) in &config.categorize.extension_aliases {
+            if !is_valid_extension(alias) || !is_valid_extension(canonical) {
+                anyhow::bail!("Invalid config {}: extension alias '{}' = '{}'", path.display(), alias, canonical);
+            }
+        }
+        for (camera, alias) in &config.categorize.camera_aliases {
+            if !is_valid_category(alias) {
+                anyhow::bail!("Invalid config {}: camera alias for '{}' is not a valid folder name", path.display(), camera);
+            }
+        }
+        for name in &config.junk.files {
+            if name.is_empty() || name.contains(['/', '\\']) {
+                anyhow::bail!("Invalid config {}: junk file name '{}'", path.display(), name);
+            }
+        }
+        for name in &config.junk.dirs {
+            if name.is_empty() || name.contains(['/', '\\']) {
+                anyhow::bail!("Invalid config {}: junk directory name '{}'", path.display(), name);
+            }
+        }
+        if config.hooks.timeout == 0 {
+            anyhow::bail!("Invalid config {}: hook timeout must be at least 1 second", path.display());
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl RulesetFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file: Self = read_toml(path)?;
+        for (name, ruleset) in &file.rulesets {
+            validate_ruleset(name, ruleset).with_context(|| format!("Invalid rule set file {}", path.display()))?;
+        }
+        Ok(file)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Checks a rule set, naming the offending rule set, category and
+/// extension in the error
+pub fn validate_ruleset(name: &str, ruleset: &Ruleset) -> Result<()> {
+    if name.trim().is_empty() {
+        anyhow::bail!("Rule set names must not be empty");
+    }
+    for (category, extensions) in ruleset {
+        if !is_valid_category(category) {
+            anyhow::bail!("Rule set '{}': invalid category name '{}'", name, category);
+        }
+        if extensions.is_empty() {
+            anyhow::bail!("Rule set '{}', category '{}': no extensions listed", name, category);
+        }
+        // Patterns are compiled, and checked, by the categorizer
+        for ext in extensions.iter().filter(|entry| !entry.starts_with('/')) {
+            if !is_valid_extension(ext) {
+                anyhow::bail!(
+                    "Rule set '{}', category '{}': invalid extension '{}' (use e.g. \"pdf\")",
+                    name,
+                    category,
+                    ext
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the `[categorize.preset_overrides]` table
+fn validate_preset_overrides(overrides: &BTreeMap<String, String>) -> Result<()> {
+    for (ext, category) in overrides {
+        if !is_valid_extension(ext) {
+            anyhow::bail!("Preset override: invalid extension '{}' (use e.g. \"pdf\")", ext);
+        }
+        if !is_valid_category(category) {
+            anyhow::bail!("Preset override for '{}': invalid category name '{}'", ext, category);
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_category(category: &str) -> bool {
+    !category.trim().is_empty() && !category.contains(['/', '\\'])
+}
+
+fn is_valid_extension(ext: &str) -> bool {
+    !ext.is_empty() && !ext.contains(['.', '/', '\\', ' '])
+}
+
+fn read_toml<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+}
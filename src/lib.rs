@@ -0,0 +1,26 @@
+//! File organization toolkit behind the `file-organizer-rust` CLI.
+//!
+//! The categorizer, deduplicator, flattener, image optimizer, and archive
+//! manager expose an options struct and an `execute` function that never
+//! touches the terminal, so they can be driven from other programs. Most
+//! also split it into `plan` and `apply`, to look at the plan in between.
+//!
+//! ```
+//! use file_organizer_rust::modules::file_deduplicator::{self, DedupeOptions, DuplicateAction};
+//!
+//! let dir = tempfile::tempdir()?;
+//! std::fs::write(dir.path().join("a.txt"), "same")?;
+//! std::fs::write(dir.path().join("b.txt"), "same")?;
+//!
+//! let options = DedupeOptions {
+//!     action: DuplicateAction::Report,
+//!     ..DedupeOptions::new(vec![dir.path().to_path_buf()])
+//! };
+//! let summary = file_deduplicator::execute(&options, ())?;
+//! assert_eq!(summary.duplicates.len(), 1);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod config;
+pub mod modules;
+pub mod utils;
@@ -1,6 +1,4 @@
 mod cli;
-mod modules;
-mod utils;
 
 use anyhow::Result;
 use cli::Cli;
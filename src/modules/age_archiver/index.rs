@@ -0,0 +1,50 @@
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::csv_field;
+
+/// Name of the index kept in the output directory
+pub const INDEX_NAME: &str = "archive-index.csv";
+
+/// Where an archived file went
+#[derive(Debug, Clone)]
+pub struct IndexRow {
+    pub original: PathBuf,
+    pub archive: PathBuf,
+    /// Entry name inside the archive
+    pub entry: String,
+    pub bytes: u64,
+    /// The date the file was archived for being older than the threshold
+    pub date: String,
+}
+
+/// Appends `rows` to the index at `path`, writing the header first when
+/// the index is new. Every run adds to the same index, so one file finds
+/// anything archived into that directory.
+pub fn append(path: &Path, rows: &[IndexRow]) -> Result<()> {
+    let is_new = !path.exists();
+    let mut file = BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+    if is_new {
+        writeln!(file, "archived,original,archive,entry,bytes,date")?;
+    }
+    let archived = Local::now().format("%Y-%m-%d %H:%M:%S");
+    for row in rows {
+        writeln!(
+            file,
+            "{},{},{},{},{},{}",
+            archived,
+            csv_field(&row.original.display().to_string()),
+            csv_field(&row.archive.display().to_string()),
+            csv_field(&row.entry),
+            row.bytes,
+            row.date
+        )?;
+    }
+    file.into_inner()?.sync_all()?;
+    Ok(())
+}
@@ -0,0 +1,732 @@
+mod index;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsString,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::utils::prompt::{Confirm, Input, Select};
+use crate::modules::archive_manager::{
+    self, estimated_size, ArchiveOptions, ArchiveType, CompressionLevel, OperationMode,
+};
+use crate::modules::base::{
+    print_changed, print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary,
+};
+use crate::utils::{
+    create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    filter::PathFilter,
+    get_directory_from_user,
+    junk::JunkList,
+    metrics::Metrics,
+    names::escaped_path,
+    parse_age,
+    progress::{Outcome, ProgressSink},
+    space::{preflight, print_estimates, SpaceEstimate},
+    walk::{Inaccessible, WalkEntry, Walker},
+};
+
+pub use index::INDEX_NAME;
+use index::IndexRow;
+
+/// Moves files that have not been touched for a while into cold storage:
+/// finds files older than a threshold, packs them into archives, reads the
+/// archives back to check every file, and only then removes the originals,
+/// keeping an index of where each file went
+pub struct AgeArchiver {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    /// `None` asks
+    min_age: Option<Duration>,
+    age_source: AgeSource,
+    /// `None` asks whether to write one archive per month
+    by_month: Option<bool>,
+    /// `None` asks
+    archive_type: Option<ArchiveType>,
+    compression: CompressionLevel,
+    /// Archive inside working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be archived
+    include: Vec<String>,
+    /// Glob patterns that keep a file out of the archives
+    exclude: Vec<String>,
+    /// List the files and archives without writing or removing anything
+    dry_run: bool,
+    /// Abort on the first file or archive that fails
+    fail_fast: bool,
+    delete_mode: DeleteMode,
+    /// Archive even when the output filesystem looks too small
+    force: bool,
+    assume_static: bool,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// Which timestamp says how old a file is
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AgeSource {
+    #[default]
+    Modified,
+    /// Last access, falling back to the modification time where the
+    /// filesystem does not keep it. Many systems update access times
+    /// lazily or not at all, so this can make files look older than they
+    /// are.
+    Accessed,
+}
+
+/// Everything an age-based archiving run needs, with no prompting
+#[derive(Debug, Clone)]
+pub struct AgeArchiveOptions {
+    pub input: PathBuf,
+    /// Where archives and the index go; must not be `input` itself
+    pub output_dir: PathBuf,
+    /// Files at least this old are archived
+    pub min_age: Duration,
+    pub age_source: AgeSource,
+    /// One archive per year and month of the files' dates instead of one
+    /// for everything
+    pub by_month: bool,
+    pub archive_type: ArchiveType,
+    pub compression: CompressionLevel,
+    pub recursive: bool,
+    /// Archive inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to `input`
+    pub filter: PathFilter,
+    /// Known junk, never archived
+    pub junk: JunkList,
+    /// How originals are removed once their archive is verified
+    pub delete_mode: DeleteMode,
+    /// Read files without checking that they are unchanged since the walk
+    pub assume_static: bool,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl AgeArchiveOptions {
+    /// A recursive run archiving files in `input` modified at least
+    /// `min_age` ago into one zip next to `input`, moving the originals to
+    /// the trash
+    pub fn new(input: PathBuf, min_age: Duration) -> Self {
+        let output_dir = input.parent().unwrap_or(&input).to_path_buf();
+        Self {
+            input,
+            output_dir,
+            min_age,
+            age_source: AgeSource::Modified,
+            by_month: false,
+            archive_type: ArchiveType::Zip,
+            compression: CompressionLevel::Balanced,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            delete_mode: DeleteMode::Trash,
+            assume_static: false,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A file old enough to archive
+#[derive(Debug, Clone, Serialize)]
+pub struct OldFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// The date that made it qualify, as `YYYY-MM-DD`
+    pub date: String,
+    #[serde(skip)]
+    metadata: Metadata,
+}
+
+/// Files going into one archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveGroup {
+    /// `YYYY-MM` when archiving by month, otherwise `before-YYYY-MM-DD`
+    pub label: String,
+    /// The archive to write, a name no existing file has
+    pub archive: PathBuf,
+    pub files: Vec<OldFile>,
+    /// Rough size of the finished archive
+    pub estimated_bytes: u64,
+}
+
+impl ArchiveGroup {
+    /// Bytes of the files going in
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum()
+    }
+}
+
+/// What [`plan`] found to archive
+#[derive(Debug, Default, Serialize)]
+pub struct AgeArchivePlan {
+    pub groups: Vec<ArchiveGroup>,
+    /// Files kept because they are newer than the threshold
+    pub too_recent: usize,
+    pub space: Vec<SpaceEstimate>,
+    /// Working copies that were not looked into
+    pub repositories: Vec<PathBuf>,
+    pub junk: usize,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl AgeArchivePlan {
+    pub fn files(&self) -> usize {
+        self.groups.iter().map(|group| group.files.len()).sum()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.groups.iter().map(ArchiveGroup::bytes).sum()
+    }
+
+    /// Lists every archive with its estimated size, and with `files` every
+    /// file going in, relative to `input`
+    pub fn print(&self, input: &Path, files: bool) {
+        for group in &self.groups {
+            println!(
+                "{}: {} files, {} (about {} archived)",
+                group.archive.display(),
+                group.files.len(),
+                format_size(group.bytes(), BINARY),
+                format_size(group.estimated_bytes, BINARY)
+            );
+            if files {
+                for file in &group.files {
+                    let relative = file.path.strip_prefix(input).unwrap_or(&file.path);
+                    println!("  {}  {} ({})", file.date, relative.display(), format_size(file.bytes, BINARY));
+                }
+            }
+        }
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct AgeArchiveSummary {
+    /// Archives written
+    pub archives: Vec<PathBuf>,
+    /// Files packed and verified
+    pub archived: usize,
+    /// Bytes of the archived files
+    pub bytes: u64,
+    /// Originals removed after their archive was verified
+    pub removed: usize,
+    /// The index the archived files were added to
+    pub index: Option<PathBuf>,
+    /// Files left alone because they changed after the walk found them
+    pub changed: Vec<PathBuf>,
+    pub repositories: Vec<PathBuf>,
+    pub junk: usize,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&AgeArchiveSummary> for Summary {
+    fn from(summary: &AgeArchiveSummary) -> Self {
+        Summary {
+            files: summary.archived,
+            bytes: summary.bytes,
+            skipped: summary.changed.len(),
+            junk: summary.junk,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Finds the files under `options.input` at least `options.min_age` old
+/// and groups them into archives, by month of their date when asked
+pub fn plan(options: &AgeArchiveOptions, progress: impl ProgressSink) -> Result<AgeArchivePlan> {
+    let input = &options.input;
+    if !input.is_dir() {
+        anyhow::bail!("{} is not a directory", input.display());
+    }
+    if options.output_dir == *input {
+        anyhow::bail!("Archives cannot go into the directory being archived; pick another or a subdirectory");
+    }
+
+    let walker = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .exclude(vec![options.output_dir.clone()])
+        .sorted(true);
+    progress.scan_started(input);
+    let walk = walker.files(input);
+    let mut plan = AgeArchivePlan {
+        repositories: walk.repositories,
+        junk: walk.junk.len(),
+        inaccessible: walk.inaccessible,
+        ..AgeArchivePlan::default()
+    };
+
+    let now = SystemTime::now();
+    let cutoff = DateTime::<Local>::from(now - options.min_age);
+    let mut groups: BTreeMap<String, Vec<OldFile>> = BTreeMap::new();
+    for entry in walk.files {
+        let time = match options.age_source {
+            AgeSource::Modified => entry.metadata.modified(),
+            AgeSource::Accessed => entry.metadata.accessed().or_else(|_| entry.metadata.modified()),
+        };
+        let Ok(time) = time else {
+            plan.inaccessible.push(Inaccessible::new(&entry.path, "no modification time"));
+            continue;
+        };
+        if now.duration_since(time).unwrap_or_default() < options.min_age {
+            plan.too_recent += 1;
+            continue;
+        }
+        let date = DateTime::<Local>::from(time);
+        let label = if options.by_month {
+            date.format("%Y-%m").to_string()
+        } else {
+            cutoff.format("before-%Y-%m-%d").to_string()
+        };
+        groups.entry(label).or_default().push(OldFile {
+            path: entry.path,
+            bytes: entry.metadata.len(),
+            date: date.format("%Y-%m-%d").to_string(),
+            metadata: entry.metadata,
+        });
+    }
+
+    let mut taken = HashSet::new();
+    for (label, files) in groups {
+        let entries = walk_entries(&files);
+        let archive = archive_path(options, &label, &mut taken);
+        plan.groups.push(ArchiveGroup {
+            estimated_bytes: estimated_size(&entries, options.archive_type, options.compression),
+            label,
+            archive,
+            files,
+        });
+    }
+    // Sized as if nothing compressed, like the archive manager; originals
+    // only free their space once removed for good
+    let stored = plan.groups.iter().map(|group| group.bytes()).sum();
+    plan.space = vec![SpaceEstimate::new(&options.output_dir, stored)?];
+    Ok(plan)
+}
+
+/// `<output>/<input name>-<label>.<ext>`, numbered `-2`, `-3`, ... when an
+/// earlier run already wrote that name
+fn archive_path(options: &AgeArchiveOptions, label: &str, taken: &mut HashSet<PathBuf>) -> PathBuf {
+    let stem = options.input.file_name().unwrap_or_default();
+    let name = |n: u32| {
+        let mut name = OsString::from(stem);
+        name.push(format!("-{}", label));
+        if n > 1 {
+            name.push(format!("-{}", n));
+        }
+        name.push(".");
+        name.push(options.archive_type.extension());
+        options.output_dir.join(name)
+    };
+    let mut n = 1;
+    while name(n).exists() || taken.contains(&name(n)) {
+        n += 1;
+    }
+    let path = name(n);
+    taken.insert(path.clone());
+    path
+}
+
+fn walk_entries(files: &[OldFile]) -> Vec<WalkEntry> {
+    files
+        .iter()
+        .map(|file| WalkEntry {
+            path: file.path.clone(),
+            metadata: file.metadata.clone(),
+        })
+        .collect()
+}
+
+/// Writes each planned archive, reads it back to check every file against
+/// its original, adds the files that match to the index, and removes those
+/// originals. A file that changed since the walk, could not be read, or
+/// does not match is left in place.
+///
+/// ```
+/// use file_organizer_rust::modules::age_archiver::{self, AgeArchiveOptions, INDEX_NAME};
+/// use std::time::{Duration, SystemTime};
+///
+/// let dir = tempfile::tempdir()?;
+/// let input = dir.path().join("projects");
+/// std::fs::create_dir(&input)?;
+/// std::fs::write(input.join("old.txt"), "old")?;
+/// std::fs::write(input.join("new.txt"), "new")?;
+/// let two_years_ago = SystemTime::now() - Duration::from_secs(2 * 365 * 24 * 3600);
+/// filetime::set_file_mtime(input.join("old.txt"), filetime::FileTime::from_system_time(two_years_ago))?;
+///
+/// let options = AgeArchiveOptions {
+///     delete_mode: file_organizer_rust::utils::delete::DeleteMode::Permanent,
+///     ..AgeArchiveOptions::new(input.clone(), Duration::from_secs(365 * 24 * 3600))
+/// };
+/// let plan = age_archiver::plan(&options, ())?;
+/// assert_eq!((plan.files(), plan.too_recent), (1, 1));
+///
+/// let summary = age_archiver::apply(&options, plan, ())?;
+/// assert_eq!((summary.archived, summary.removed), (1, 1));
+/// assert!(!input.join("old.txt").exists() && input.join("new.txt").exists());
+/// assert!(std::fs::read_to_string(dir.path().join(INDEX_NAME))?.contains("old.txt"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &AgeArchiveOptions, plan: AgeArchivePlan, progress: impl ProgressSink) -> Result<AgeArchiveSummary> {
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut summary = AgeArchiveSummary {
+        repositories: plan.repositories,
+        junk: plan.junk,
+        inaccessible: plan.inaccessible,
+        ..AgeArchiveSummary::default()
+    };
+    let archive_options = ArchiveOptions {
+        compression: options.compression,
+        assume_static: options.assume_static,
+        junk: options.junk.clone(),
+        ..ArchiveOptions::new(OperationMode::Create, options.archive_type, options.input.clone())
+    };
+    let index_path = options.output_dir.join(INDEX_NAME);
+    std::fs::create_dir_all(&options.output_dir)?;
+
+    for group in plan.groups {
+        let packed = errors.check(
+            &group.archive,
+            "archive",
+            archive_manager::pack(&archive_options, walk_entries(&group.files), &group.archive, &progress),
+        )?;
+        let Some(packed) = packed else {
+            continue;
+        };
+        summary.archives.push(group.archive.clone());
+        summary.inaccessible.extend(packed.inaccessible.iter().cloned());
+        let left_out: HashSet<&Path> = packed
+            .changed
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(packed.inaccessible.iter().map(|entry| entry.path.as_path()))
+            .collect();
+        let files: Vec<&OldFile> = group.files.iter().filter(|file| !left_out.contains(file.path.as_path())).collect();
+        summary.changed.extend(packed.changed);
+
+        progress.phase_changed("verifying", files.len() as u64);
+        let paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+        let verified = errors.check(
+            &group.archive,
+            "verify",
+            archive_manager::verify(&group.archive, options.archive_type, &options.input, &paths),
+        )?;
+        // An archive that cannot be read back keeps all its originals
+        let Some(verified) = verified else {
+            continue;
+        };
+        let verified: HashSet<PathBuf> = verified.into_iter().collect();
+        let mut rows = Vec::new();
+        for file in &files {
+            if verified.contains(&file.path) {
+                rows.push(IndexRow {
+                    original: file.path.clone(),
+                    archive: group.archive.clone(),
+                    entry: escaped_path(file.path.strip_prefix(&options.input)?),
+                    bytes: file.bytes,
+                    date: file.date.clone(),
+                });
+            } else {
+                let mismatch = anyhow::anyhow!("the copy in {} does not match, kept", group.archive.display());
+                errors.check::<()>(&file.path, "verify", Err(mismatch))?;
+            }
+        }
+        // Indexed before anything is removed, so a file is never gone
+        // without a record of where it went
+        if !rows.is_empty() {
+            index::append(&index_path, &rows)?;
+            summary.index = Some(index_path.clone());
+        }
+
+        progress.phase_changed("removing", rows.len() as u64);
+        let originals: Vec<&Path> = rows.iter().map(|row| row.original.as_path()).collect();
+        let results = safe_delete_all(&originals, options.delete_mode);
+        for (row, result) in rows.iter().zip(results) {
+            summary.archived += 1;
+            summary.bytes += row.bytes;
+            let outcome = match errors.check(&row.original, "delete", result)? {
+                Some(()) => {
+                    summary.removed += 1;
+                    Outcome::Done
+                }
+                None => Outcome::Failed,
+            };
+            progress.item_finished(&row.original, row.bytes, outcome);
+        }
+    }
+
+    summary.errors = errors.into_errors();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for AgeArchiver {
+    type Plan = AgeArchivePlan;
+    const NAME: &'static str = "archive-old";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            output_dir: None,
+            min_age: None,
+            age_source: AgeSource::default(),
+            by_month: None,
+            archive_type: None,
+            compression: CompressionLevel::Balanced,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            dry_run: false,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            force: false,
+            assume_static: false,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to archive old files from")?);
+        }
+        if self.min_age.is_none() {
+            let typed: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Archive files older than (e.g. 90d, 6m, 2y)")
+                .default("1y".to_string())
+                .interact_text()?;
+            self.min_age = Some(parse_age(&typed)?);
+        }
+        if self.archive_type.is_none() {
+            let formats = ["ZIP", "TAR", "TAR.GZ", "TAR.ZST"];
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select archive format")
+                .items(&formats)
+                .default(0)
+                .interact()?;
+            self.archive_type = Some(match selection {
+                0 => ArchiveType::Zip,
+                1 => ArchiveType::Tar,
+                2 => ArchiveType::TarGz,
+                _ => ArchiveType::TarZst,
+            });
+        }
+        if self.by_month.is_none() {
+            self.by_month = Some(
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Write one archive per month?")
+                    .default(false)
+                    .interact()?,
+            );
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<AgeArchivePlan> {
+        let spinner = create_spinner("Finding old files...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Lists the archives to write, and on a dry run every file going into
+    /// them, then asks before writing anything
+    fn review(&self, plan: &AgeArchivePlan) -> Result<bool> {
+        let input = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print(input, self.dry_run);
+        println!(
+            "{} files ({}) to archive into {} archives, {} newer files kept",
+            plan.files(),
+            format_size(plan.bytes(), BINARY),
+            plan.groups.len(),
+            plan.too_recent
+        );
+        if !plan.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to archive in them)",
+                plan.repositories.len()
+            );
+        }
+        if self.dry_run {
+            print_estimates(&plan.space);
+            println!("Dry run finished, nothing was archived or removed");
+            return Ok(false);
+        }
+        if plan.groups.is_empty() {
+            println!("No files are old enough to archive");
+            return Ok(false);
+        }
+        preflight(&plan.space, self.force)?;
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "Archive these files and remove the originals ({})?",
+                self.delete_mode.verb()
+            ))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: AgeArchivePlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Archiving...");
+        self.metrics.phase("archiving");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        println!(
+            "{} files ({}) archived into {} archives, {} originals {}",
+            summary.archived,
+            format_size(summary.bytes, BINARY),
+            summary.archives.len(),
+            summary.removed,
+            self.delete_mode.verb()
+        );
+        if let Some(index) = &summary.index {
+            println!("Index of archived files: {}", index.display());
+        }
+        print_inaccessible(&summary.inaccessible, "they were not archived");
+        print_changed(&summary.changed, "they were not archived");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl AgeArchiver {
+    /// Presets the directory so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Writes archives and the index here instead of next to the input
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    /// Archives files at least `age` old, without asking
+    pub fn with_min_age(mut self, age: Duration) -> Self {
+        self.min_age = Some(age);
+        self
+    }
+
+    /// Sets which timestamp says how old a file is
+    pub fn with_age_source(mut self, source: AgeSource) -> Self {
+        self.age_source = source;
+        self
+    }
+
+    /// Whether to write one archive per month, without asking
+    pub fn with_by_month(mut self, by_month: bool) -> Self {
+        self.by_month = Some(by_month);
+        self
+    }
+
+    /// Sets the archive format, without asking
+    pub fn with_archive_type(mut self, archive_type: ArchiveType) -> Self {
+        self.archive_type = Some(archive_type);
+        self
+    }
+
+    pub fn with_compression(mut self, compression: CompressionLevel) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Archives inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only archives files whose relative path matches one of `patterns`
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Keeps files whose relative path matches one of `patterns`
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Lists the files and archives without writing or removing anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first file or archive that fails
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Sets how originals are removed once archived
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Archives even when the output filesystem looks too small
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Reads files without first checking that they are unchanged since
+    /// the walk
+    pub fn with_assume_static(mut self, assume_static: bool) -> Self {
+        self.assume_static = assume_static;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<AgeArchiveOptions> {
+        let input = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let min_age = self.min_age.ok_or_else(|| anyhow::anyhow!("Minimum age not set"))?;
+        let mut options = AgeArchiveOptions::new(input, min_age);
+        if let Some(dir) = &self.output_dir {
+            options.output_dir = dir.clone();
+        }
+        Ok(AgeArchiveOptions {
+            age_source: self.age_source,
+            by_month: self.by_month.unwrap_or(false),
+            archive_type: self.archive_type.unwrap_or(ArchiveType::Zip),
+            compression: self.compression,
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            junk: self.junk.clone(),
+            delete_mode: self.delete_mode,
+            assume_static: self.assume_static,
+            fail_fast: self.fail_fast,
+            ..options
+        })
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::modules::file_deduplicator::HashMethod;
+use crate::utils::{
+    hash_cache::HashCache,
+    hashing::same_contents,
+    metadata::copy_file_metadata,
+    names::escaped_path,
+    progress::{Outcome, ProgressSink},
+    walk::WalkEntry,
+};
+
+/// Name of the list of left-out files at the root of an archive
+pub const MANIFEST_NAME: &str = "DUPLICATES.txt";
+
+/// First line of a list this tool wrote, so a `DUPLICATES.txt` someone
+/// archived themselves is never acted on
+const HEADER: &str = "# file-organizer-rust duplicates 1";
+
+/// Files of an archive set left out because a file with the same contents
+/// is stored
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// Each left-out file with the stored file it matches and its size
+    pub files: Vec<(PathBuf, PathBuf, u64)>,
+    /// Time spent hashing
+    pub hashing: Duration,
+}
+
+/// What storing identical files once saved
+#[derive(Debug, Clone, Serialize)]
+pub struct DedupeSavings {
+    /// Files left out and listed in `DUPLICATES.txt`
+    pub files: usize,
+    /// Bytes they hold, which were neither read nor compressed
+    pub bytes: u64,
+    pub hashing: Duration,
+    /// How long compressing them would have taken at the rate the stored
+    /// files were compressed
+    pub compressing: Duration,
+}
+
+/// Takes the files out of `files` whose contents an earlier one has, in
+/// walk order, so each contents is stored once. Only files sharing their
+/// size with another are hashed; one that cannot be hashed is stored.
+pub fn split(files: &mut Vec<WalkEntry>, cache: &HashCache, progress: &dyn ProgressSink) -> Result<Duplicates> {
+    let started = Instant::now();
+    let mut by_size: IndexMap<u64, Vec<usize>> = IndexMap::new();
+    for (index, entry) in files.iter().enumerate() {
+        // Empty files cost a header either way
+        if entry.metadata.len() > 0 {
+            by_size.entry(entry.metadata.len()).or_default().push(index);
+        }
+    }
+    let candidates: Vec<Vec<usize>> = by_size.into_values().filter(|indexes| indexes.len() > 1).collect();
+    progress.phase_changed("Hashing", candidates.iter().map(|indexes| indexes.len() as u64).sum());
+
+    let mut duplicates = Duplicates::default();
+    let mut left_out = vec![false; files.len()];
+    for indexes in candidates {
+        let mut stored: IndexMap<String, usize> = IndexMap::new();
+        for index in indexes {
+            let entry = &files[index];
+            progress.item_started(&entry.path);
+            let Ok(digest) = HashMethod::Sha256.hash(&entry.path, &entry.metadata, cache) else {
+                progress.item_finished(&entry.path, 0, Outcome::Skipped);
+                continue;
+            };
+            progress.item_finished(&entry.path, entry.metadata.len(), Outcome::Done);
+            match stored.get(&digest) {
+                Some(&original) => {
+                    left_out[index] = true;
+                    duplicates
+                        .files
+                        .push((entry.path.clone(), files[original].path.clone(), entry.metadata.len()));
+                }
+                None => {
+                    stored.insert(digest, index);
+                }
+            }
+        }
+    }
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+
+    let mut index = 0;
+    files.retain(|_| {
+        index += 1;
+        !left_out[index - 1]
+    });
+    duplicates.files.sort();
+    duplicates.hashing = started.elapsed();
+    Ok(duplicates)
+}
+
+/// `DUPLICATES.txt` for `files`, as pairs of a left-out and a stored file
+/// under `base`: one line each, the left-out path, a tab, and the stored
+/// path, both as the archive names them
+pub fn manifest<'a>(base: &Path, files: impl IntoIterator<Item = (&'a Path, &'a Path)>) -> Result<String> {
+    let mut text = format!(
+        "{}\n\
+         # These files were left out of the archive because a file with the same\n\
+         # contents is stored in it. Each line is a left-out path, a tab, and the\n\
+         # stored path. Extracting with file-organizer-rust recreates them.\n",
+        HEADER
+    );
+    for (duplicate, original) in files {
+        let duplicate = escape(&escaped_path(duplicate.strip_prefix(base)?));
+        let original = escape(&escaped_path(original.strip_prefix(base)?));
+        text.push_str(&format!("{}\t{}\n", duplicate, original));
+    }
+    Ok(text)
+}
+
+/// Files [`restore`] recreated, and those it found already in place
+#[derive(Debug, Default)]
+pub struct Restored {
+    /// Files recreated, or already there with the stored file's contents
+    pub files: usize,
+    /// Paths that already held a different file, which was kept rather
+    /// than replaced with the duplicate
+    pub kept: Vec<PathBuf>,
+}
+
+/// Recreates the files listed in a `DUPLICATES.txt` this tool wrote at the
+/// root of `output_dir`, hard-linking each to its stored file, or copying
+/// it where that fails, then removes the list. A file already at a listed
+/// path is never replaced: one with other contents is kept and reported.
+/// Without such a list, nothing is done.
+pub fn restore(output_dir: &Path) -> Result<Restored> {
+    let manifest = output_dir.join(MANIFEST_NAME);
+    let Ok(text) = fs::read_to_string(&manifest) else {
+        return Ok(Restored::default());
+    };
+    if text.lines().next() != Some(HEADER) {
+        return Ok(Restored::default());
+    }
+    let mut restored = Restored::default();
+    for line in text.lines().filter(|line| !line.starts_with('#') && !line.is_empty()) {
+        let Some((duplicate, original)) = line.split_once('\t') else {
+            anyhow::bail!("Invalid line in {}: {}", MANIFEST_NAME, line);
+        };
+        let (Some(duplicate), Some(original)) = (enclosed(&unescape(duplicate)), enclosed(&unescape(original))) else {
+            anyhow::bail!("{} names a path outside the archive: {}", MANIFEST_NAME, line);
+        };
+        let (duplicate, original) = (output_dir.join(duplicate), output_dir.join(original));
+        if let Some(parent) = duplicate.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::symlink_metadata(&duplicate).is_ok() {
+            // Such as from extracting the same archive before
+            if same_contents(&original, &duplicate).unwrap_or(false) {
+                restored.files += 1;
+            } else {
+                restored.kept.push(duplicate);
+            }
+            continue;
+        }
+        if fs::hard_link(&original, &duplicate).is_err() {
+            fs::copy(&original, &duplicate)
+                .with_context(|| format!("Failed to recreate {} from {}", duplicate.display(), original.display()))?;
+            copy_file_metadata(&original, &duplicate)?;
+        }
+        restored.files += 1;
+    }
+    fs::remove_file(&manifest)?;
+    Ok(restored)
+}
+
+/// `name` as a relative path, or `None` when it has a root or `..` and so
+/// could leave the output directory
+fn enclosed(name: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    let enclosed = path.components().any(|component| matches!(component, Component::Normal(_)))
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    enclosed.then_some(path)
+}
+
+/// Backslashes, tabs, and line breaks written as `\\`, `\t`, and `\n`, so
+/// every path fits on its line
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
@@ -0,0 +1,341 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{tar_reader, ArchiveType};
+use crate::modules::base::{ErrorLog, FileError};
+use crate::utils::{
+    atomic::write_atomic,
+    names::escaped_path,
+    progress::{Outcome, ProgressSink},
+    stamp::FileStamp,
+    walk::{Inaccessible, Walker},
+};
+
+/// What the archives under some directories hold, so a file can be found
+/// without listing every archive again. Kept as JSON in the data
+/// directory; an archive is only read again once its size or modification
+/// time changes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    /// By the archive's absolute path
+    pub archives: BTreeMap<PathBuf, IndexedArchive>,
+}
+
+/// One archive as it was when listed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedArchive {
+    pub stamp: FileStamp,
+    pub entries: Vec<IndexEntry>,
+}
+
+/// A file inside an archive; directories and links are not indexed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Path inside the archive, `/`-separated as the archive stores it
+    pub name: String,
+    pub size: u64,
+    /// Local time, when the archive records one
+    pub modified: Option<NaiveDateTime>,
+}
+
+/// Outcome of [`ArchiveIndex::refresh`]
+#[derive(Debug, Default, Serialize)]
+pub struct RefreshSummary {
+    /// Archives listed because they were new or changed
+    pub indexed: usize,
+    /// Archives that had not changed since they were last listed
+    pub unchanged: usize,
+    /// Archives that were in the index but are gone, or could no longer be
+    /// listed, and were dropped from it
+    pub dropped: usize,
+    /// Entries in the index, across every directory
+    pub entries: usize,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+/// An entry [`ArchiveIndex::search`] found
+#[derive(Debug, Clone, Copy)]
+pub struct SearchHit<'a> {
+    pub archive: &'a Path,
+    pub entry: &'a IndexEntry,
+}
+
+/// Outcome of [`extract_entries`]
+#[derive(Debug, Default, Serialize)]
+pub struct ExtractSummary {
+    pub extracted: Vec<PathBuf>,
+    /// Entries whose file already exists in the output, which is not
+    /// overwritten
+    pub existing: Vec<PathBuf>,
+}
+
+impl ArchiveIndex {
+    /// `<data dir>/file-organizer-rust/archive-contents.json`
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("No data directory on this platform"))?;
+        Ok(dir.join("file-organizer-rust").join("archive-contents.json"))
+    }
+
+    /// Loads the index saved at `path`; a missing one starts empty
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse archive index {}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read archive index {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write archive index {}", path.display()))
+    }
+
+    /// Brings the index up to date with the zip and tar archives under
+    /// `dir`: new and changed ones are listed, and ones that are gone are
+    /// dropped. Archives indexed under other directories are kept.
+    pub fn refresh(&mut self, dir: &Path, recursive: bool, progress: impl ProgressSink) -> Result<RefreshSummary> {
+        if !dir.is_dir() {
+            anyhow::bail!("Not a directory: {}", dir.display());
+        }
+        let dir = dir.canonicalize()?;
+        progress.scan_started(&dir);
+        let walk = Walker::new(recursive)
+            .include_repos(true)
+            .sorted(true)
+            .files(&dir);
+        let archives: Vec<_> = walk
+            .files
+            .into_iter()
+            .filter_map(|entry| Some((ArchiveType::from_path(&entry.path)?, entry)))
+            .collect();
+
+        let mut summary = RefreshSummary {
+            inaccessible: walk.inaccessible,
+            ..RefreshSummary::default()
+        };
+        let found: HashSet<PathBuf> = archives.iter().map(|(_, entry)| entry.path.clone()).collect();
+        let before = self.archives.len();
+        self.archives.retain(|path, _| !path.starts_with(&dir) || found.contains(path));
+        summary.dropped = before - self.archives.len();
+
+        progress.phase_changed("Indexing", archives.len() as u64);
+        let errors = ErrorLog::new(false);
+        for (archive_type, entry) in archives {
+            let stamp = FileStamp::of(&entry.metadata);
+            if self.archives.get(&entry.path).is_some_and(|indexed| indexed.stamp == stamp) {
+                summary.unchanged += 1;
+                progress.item_finished(&entry.path, 0, Outcome::Skipped);
+                continue;
+            }
+            progress.item_started(&entry.path);
+            let outcome = match errors.check(&entry.path, "list", list_entries(&entry.path, archive_type))? {
+                Some(entries) => {
+                    self.archives.insert(entry.path.clone(), IndexedArchive { stamp, entries });
+                    summary.indexed += 1;
+                    Outcome::Done
+                }
+                None => {
+                    // What the index said about it may no longer be true
+                    if self.archives.remove(&entry.path).is_some() {
+                        summary.dropped += 1;
+                    }
+                    Outcome::Failed
+                }
+            };
+            progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+        }
+        summary.entries = self.archives.values().map(|archive| archive.entries.len()).sum();
+        summary.errors = errors.into_errors();
+        Ok(summary)
+    }
+
+    /// Entries whose path contains `pattern`, ignoring case, or, when it
+    /// has any of `*?[{`, whose path or file name matches it as a glob.
+    /// Only archives under `within` are searched when it is given.
+    ///
+    /// ```
+    /// use file_organizer_rust::modules::archive_manager::{self, index::ArchiveIndex, ArchiveOptions, ArchiveType, OperationMode};
+    ///
+    /// let dir = tempfile::tempdir()?;
+    /// let taxes = dir.path().join("taxes");
+    /// std::fs::create_dir_all(taxes.join("2021"))?;
+    /// std::fs::write(taxes.join("2021").join("invoice-2021-03.pdf"), "pdf")?;
+    /// std::fs::write(taxes.join("notes.txt"), "text")?;
+    /// archive_manager::execute(ArchiveOptions::new(OperationMode::Create, ArchiveType::TarZst, taxes), ())?;
+    ///
+    /// let mut index = ArchiveIndex::default();
+    /// let summary = index.refresh(dir.path(), false, ())?;
+    /// assert_eq!((summary.indexed, summary.entries), (1, 2));
+    /// let hits = index.search("invoice-*.pdf", None)?;
+    /// assert_eq!(hits.len(), 1);
+    /// assert_eq!(hits[0].entry.name, "2021/invoice-2021-03.pdf");
+    /// assert_eq!(index.search("NOTES", None)?.len(), 1);
+    ///
+    /// // Nothing changed, so nothing is read again
+    /// assert_eq!(index.refresh(dir.path(), false, ())?.unchanged, 1);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn search(&self, pattern: &str, within: Option<&Path>) -> Result<Vec<SearchHit<'_>>> {
+        let matcher = Matcher::new(pattern)?;
+        let within = within.map(Path::canonicalize).transpose()?;
+        Ok(self
+            .archives
+            .iter()
+            .filter(|(path, _)| within.as_ref().is_none_or(|dir| path.starts_with(dir)))
+            .flat_map(|(path, archive)| {
+                archive.entries.iter().map(move |entry| SearchHit { archive: path, entry })
+            })
+            .filter(|hit| matcher.matches(&hit.entry.name))
+            .collect())
+    }
+}
+
+/// How [`ArchiveIndex::search`] compares a pattern with entry names
+enum Matcher {
+    Glob(GlobMatcher),
+    /// Lowercased
+    Substring(String),
+}
+
+impl Matcher {
+    fn new(pattern: &str) -> Result<Self> {
+        if pattern.contains(['*', '?', '[', '{']) {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("Invalid glob '{}'", pattern))?;
+            Ok(Self::Glob(glob.compile_matcher()))
+        } else {
+            Ok(Self::Substring(pattern.to_lowercase()))
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(glob) => {
+                let file_name = name.rsplit('/').next().unwrap_or(name);
+                glob.is_match(name) || glob.is_match(file_name)
+            }
+            Self::Substring(needle) => name.to_lowercase().contains(needle),
+        }
+    }
+}
+
+/// The file entries of an archive, read from its directory or entry
+/// headers without unpacking anything
+fn list_entries(archive_path: &Path, archive_type: ArchiveType) -> Result<Vec<IndexEntry>> {
+    let mut entries = Vec::new();
+    let compression = match archive_type {
+        ArchiveType::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(archive_path)?)?;
+            for i in 0..archive.len() {
+                let entry = archive.by_index_raw(i)?;
+                if entry.is_file() {
+                    let time = entry.last_modified();
+                    let modified = NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())
+                        .and_then(|date| {
+                            date.and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())
+                        });
+                    entries.push(IndexEntry {
+                        name: entry.name().to_string(),
+                        size: entry.size(),
+                        modified,
+                    });
+                }
+            }
+            return Ok(entries);
+        }
+        ArchiveType::Tar => None,
+        ArchiveType::TarGz => Some("gz"),
+        ArchiveType::TarZst => Some("zst"),
+    };
+    let mut archive = tar::Archive::new(tar_reader(archive_path, compression)?);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        if header.entry_type().is_file() {
+            let modified = header
+                .mtime()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(i64::try_from(secs).ok()?, 0))
+                .map(|time| time.with_timezone(&Local).naive_local());
+            entries.push(IndexEntry {
+                name: escaped_path(&entry.path()?),
+                size: header.size()?,
+                modified,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Unpacks only the entries named `names` of the archive at
+/// `archive_path` into `output_dir`, keeping their paths inside the
+/// archive. Existing files are not overwritten, and entries whose path
+/// would leave `output_dir` are never written.
+pub fn extract_entries(archive_path: &Path, names: &[&str], output_dir: &Path) -> Result<ExtractSummary> {
+    let archive_type = ArchiveType::from_path(archive_path)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a zip or tar archive", archive_path.display()))?;
+    let mut wanted = names.to_vec();
+    wanted.sort_unstable();
+    wanted.dedup();
+    let mut summary = ExtractSummary::default();
+    fs::create_dir_all(output_dir)?;
+
+    let compression = match archive_type {
+        ArchiveType::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(archive_path)?)?;
+            for name in wanted {
+                let mut entry = archive.by_name(name)?;
+                let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+                    continue;
+                };
+                let target = output_dir.join(relative);
+                if target.exists() {
+                    summary.existing.push(target);
+                    continue;
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                io::copy(&mut entry, &mut File::create_new(&target)?)?;
+                summary.extracted.push(target);
+            }
+            return Ok(summary);
+        }
+        ArchiveType::Tar => None,
+        ArchiveType::TarGz => Some("gz"),
+        ArchiveType::TarZst => Some("zst"),
+    };
+    let mut archive = tar::Archive::new(tar_reader(archive_path, compression)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !entry.header().entry_type().is_file() || wanted.binary_search(&escaped_path(&path).as_str()).is_err() {
+            continue;
+        }
+        let target = output_dir.join(&path);
+        if target.exists() {
+            summary.existing.push(target);
+            continue;
+        }
+        // Refuses paths with `..` or a root, returning false
+        if entry.unpack_in(output_dir)? {
+            summary.extracted.push(target);
+        }
+    }
+    Ok(summary)
+}
@@ -1,17 +1,37 @@
+mod dedupe;
+pub mod index;
+
 use anyhow::Result;
 use async_trait::async_trait;
-use dialoguer::{theme::ColorfulTheme, Select, Input};
+use dialoguer::theme::ColorfulTheme;
 use flate2::Compression;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
 use std::{
+    collections::HashMap,
+    ffi::OsString,
     fs::{self, File},
     io::{self, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use walkdir::WalkDir;
 use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 
-use crate::utils::{create_spinner, get_directory_from_user};
-use crate::modules::base::FileOrganizer;
+use crate::utils::prompt::{Confirm, Input, Select};
+use crate::utils::{
+    atomic::AtomicFile, create_spinner, get_directory_from_user, hash_cache::HashCache, hashing::{Algorithm, Hashing}, junk::JunkList, metrics::Metrics, names::escaped_path, parse_size, progress::{Outcome, ProgressSink},
+    space::{preflight, SpaceEstimate},
+    stamp::FileStamp,
+    walk::{Inaccessible, WalkEntry, Walker},
+};
+use crate::modules::base::{print_changed, print_inaccessible, FileOrganizer, Summary};
+use dedupe::Duplicates;
+pub use dedupe::{DedupeSavings, MANIFEST_NAME as DUPLICATES_MANIFEST};
+
+/// Bytes a stored file may take in an archive beyond its contents: a tar
+/// header and padding, or a zip local and central directory entry
+const ENTRY_OVERHEAD: u64 = 1024;
 
 pub struct ArchiveManager {
     recursive: bool,
@@ -21,34 +41,388 @@ pub struct ArchiveManager {
     compression_level: Option<CompressionLevel>,
     operation_mode: Option<OperationMode>,
     split_size: Option<u64>,
+    /// Add files in name order
+    sorted: bool,
+    /// Known junk, left out of created archives
+    junk: JunkList,
+    /// Junk files and directories that were left out
+    junk_skipped: Mutex<Vec<PathBuf>>,
+    /// Files and directories left out because they could not be read
+    inaccessible: Mutex<Vec<Inaccessible>>,
+    /// Trust that nothing else writes to the input during the run
+    assume_static: bool,
+    /// Files left out because they changed between the walk and reading
+    changed: Mutex<Vec<PathBuf>>,
+    /// Run even when the output filesystem looks too small
+    force: bool,
+    /// Store files with identical contents once, see
+    /// [`ArchiveOptions::dedupe`]
+    dedupe: bool,
+    /// Hash cache file for `dedupe`
+    hash_cache: Option<PathBuf>,
 }
 
-#[derive(Clone, Copy)]
-enum ArchiveType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ArchiveType {
     Zip,
     Tar,
     TarGz,
     TarZst,
 }
 
-#[derive(Clone, Copy)]
-enum CompressionLevel {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CompressionLevel {
     None,
     Fast,
     Balanced,
     Best,
 }
 
-#[derive(Clone, Copy)]
-enum OperationMode {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OperationMode {
+    /// Archive a directory
     Create,
+    /// Unpack an archive
     Extract,
+    /// Unpack an archive, add a directory's files, and pack it again
     Update,
+    /// Archive a directory into parts of at most `split_size` bytes
     Split,
 }
 
+/// Everything an archive operation needs, with no prompting. Also the plan
+/// of an interactive run.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveOptions {
+    pub operation: OperationMode,
+    /// Archive files in subdirectories too, not only the top level
+    pub recursive: bool,
+    pub archive_type: ArchiveType,
+    pub compression: CompressionLevel,
+    /// The directory to archive, or the archive to extract or update
+    pub input: PathBuf,
+    /// Where archives are written or extracted to
+    pub output_dir: PathBuf,
+    /// Required for `OperationMode::Split`
+    pub split_size: Option<u64>,
+    /// Add files in order of their path rather than filesystem order, so
+    /// archiving the same tree twice gives identical archives
+    pub sorted: bool,
+    /// Read files without first checking that they still have the size
+    /// and modification time the walk saw; only safe when nothing else
+    /// writes to the input
+    pub assume_static: bool,
+    /// Files left out of created archives
+    #[serde(skip)]
+    pub junk: JunkList,
+    /// Store each contents once when creating or updating: files with the
+    /// same contents as one stored before them are left out and listed in
+    /// `DUPLICATES.txt` at the root, which extraction recreates them from
+    pub dedupe: bool,
+    /// Hash cache file for `dedupe`, so unchanged files are not read again
+    pub hash_cache: Option<PathBuf>,
+}
+
+impl ArchiveOptions {
+    /// Recursive and sorted, with balanced compression, writing next to
+    /// `input` except for extraction, which unpacks into `input`
+    pub fn new(operation: OperationMode, archive_type: ArchiveType, input: PathBuf) -> Self {
+        let output_dir = if operation == OperationMode::Extract {
+            input.clone()
+        } else {
+            input.parent().unwrap_or(&input).to_path_buf()
+        };
+        Self {
+            operation,
+            recursive: true,
+            archive_type,
+            compression: CompressionLevel::Balanced,
+            input,
+            output_dir,
+            split_size: None,
+            sorted: true,
+            assume_static: false,
+            junk: JunkList::default(),
+            dedupe: false,
+            hash_cache: None,
+        }
+    }
+
+    /// Bytes the operation may write to `output_dir` at worst: the input
+    /// files stored without compression, or the archive unpacked. An
+    /// update unpacks the archive and then packs it again with the new
+    /// files, so it needs both.
+    pub fn required_space(&self) -> Result<u64> {
+        let stored = || -> u64 {
+            walker(self.recursive, self.sorted, &self.junk)
+                .files(&self.input)
+                .files
+                .iter()
+                .map(|entry| entry.metadata.len() + ENTRY_OVERHEAD)
+                .sum()
+        };
+        Ok(match self.operation {
+            OperationMode::Create | OperationMode::Split => stored(),
+            OperationMode::Extract => unpacked_size(&self.input, self.archive_type)?,
+            OperationMode::Update => 2 * (unpacked_size(&self.input, self.archive_type)? + stored()),
+        })
+    }
+}
+
+/// The walk every operation reads its input with: everything but known
+/// junk, working copies and symlinked files included, descending into
+/// subdirectories only when recursive
+fn walker(recursive: bool, sorted: bool, junk: &JunkList) -> Walker {
+    Walker::new(recursive)
+        .include_repos(true)
+        .symlinks(true)
+        .sorted(sorted)
+        .junk(Some(junk.clone()))
+}
+
+/// Bytes the files in an archive take once extracted, read from its
+/// directory or entry headers
+fn unpacked_size(archive_path: &Path, archive_type: ArchiveType) -> Result<u64> {
+    let compression = match archive_type {
+        ArchiveType::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(archive_path)?)?;
+            let mut size = 0;
+            for i in 0..archive.len() {
+                size += archive.by_index(i)?.size();
+            }
+            return Ok(size);
+        }
+        ArchiveType::Tar => None,
+        ArchiveType::TarGz => Some("gz"),
+        ArchiveType::TarZst => Some("zst"),
+    };
+    let mut archive = tar::Archive::new(tar_reader(archive_path, compression)?);
+    let mut size = 0;
+    for entry in archive.entries()? {
+        size += entry?.header().size()?;
+    }
+    Ok(size)
+}
+
+/// `<dir name><suffix>.<extension>`, keeping the bytes of the directory
+/// name as they are
+fn archive_name(dir: &Path, suffix: &str, archive_type: ArchiveType) -> OsString {
+    let mut name = dir.file_name().unwrap().to_os_string();
+    name.push(suffix);
+    name.push(".");
+    name.push(archive_type.extension());
+    name
+}
+
+/// Opens a tar archive, decompressing `gz` or `zst` on the fly
+fn tar_reader(archive_path: &Path, compression: Option<&str>) -> Result<Box<dyn Read>> {
+    let file = File::open(archive_path)?;
+    Ok(match compression {
+        Some("gz") => Box::new(flate2::read::GzDecoder::new(file)),
+        Some("zst") => Box::new(zstd::Decoder::new(file)?),
+        _ => Box::new(file),
+    })
+}
+
+/// Outcome of [`execute`]
+#[derive(Debug, Default, Serialize)]
+pub struct ArchiveSummary {
+    /// Archives written, or the directory an archive was extracted into
+    pub outputs: Vec<PathBuf>,
+    /// Files added to the written archives; zero for extraction
+    pub files: u64,
+    /// Known junk files and directories left out of the archives
+    pub junk: Vec<PathBuf>,
+    /// Files and directories left out of the archives because they could
+    /// not be read
+    pub inaccessible: Vec<Inaccessible>,
+    /// Files left out because they changed after the walk found them, such
+    /// as ones still being written
+    pub changed: Vec<PathBuf>,
+    /// What storing identical files once saved, when it was asked for
+    pub dedupe: Option<DedupeSavings>,
+    /// Files recreated on extraction from the archive's `DUPLICATES.txt`
+    pub restored: usize,
+    /// Files already in the output directory where extraction would have
+    /// recreated a duplicate with other contents, left as they were
+    pub kept: Vec<PathBuf>,
+}
+
+/// Runs the archive operation described by `options`
+///
+/// ```
+/// use file_organizer_rust::modules::archive_manager::{self, ArchiveOptions, ArchiveType, OperationMode};
+///
+/// let dir = tempfile::tempdir()?;
+/// let photos = dir.path().join("photos");
+/// std::fs::create_dir(&photos)?;
+/// std::fs::write(photos.join("a.jpg"), "jpeg")?;
+///
+/// let options = ArchiveOptions::new(OperationMode::Create, ArchiveType::Zip, photos);
+/// let summary = archive_manager::execute(options, ())?;
+/// assert_eq!(summary.outputs, [dir.path().join("photos.zip")]);
+/// assert_eq!(summary.files, 1);
+///
+/// // A second copy of a file is listed instead of stored, and extracting
+/// // recreates it
+/// std::fs::write(dir.path().join("photos/b.jpg"), "jpeg")?;
+/// let mut options = ArchiveOptions::new(OperationMode::Create, ArchiveType::TarGz, dir.path().join("photos"));
+/// options.dedupe = true;
+/// let summary = archive_manager::execute(options, ())?;
+/// assert_eq!(summary.files, 1);
+/// assert_eq!(summary.dedupe.unwrap().files, 1);
+///
+/// let mut options = ArchiveOptions::new(OperationMode::Extract, ArchiveType::TarGz, dir.path().join("photos.tar.gz"));
+/// options.output_dir = dir.path().join("restored");
+/// let summary = archive_manager::execute(options, ())?;
+/// assert_eq!(summary.restored, 1);
+/// assert_eq!(std::fs::read_to_string(dir.path().join("restored/b.jpg"))?, "jpeg");
+/// assert!(!dir.path().join("restored/DUPLICATES.txt").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: ArchiveOptions, progress: impl ProgressSink) -> Result<ArchiveSummary> {
+    if options.operation == OperationMode::Split && options.split_size.is_none() {
+        anyhow::bail!("Split operation needs a split size");
+    }
+    if options.operation == OperationMode::Split && options.dedupe {
+        anyhow::bail!("Split archives cannot store identical files once");
+    }
+    let operation = options.operation;
+    let mut manager = ArchiveManager::from_options(options);
+
+    let mut summary = match operation {
+        OperationMode::Create => manager.create_archive(&progress)?,
+        OperationMode::Extract => {
+            progress.phase_changed("Extracting", 0);
+            manager.extract_archive()?
+        }
+        OperationMode::Update => manager.update_archive(&progress)?,
+        OperationMode::Split => manager.split_archive(&progress)?,
+    };
+    summary.junk = manager.junk_skipped.into_inner().unwrap();
+    summary.inaccessible = manager.inaccessible.into_inner().unwrap();
+    summary.changed = manager.changed.into_inner().unwrap();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+/// Packs `files`, all under `options.input`, into a new archive at
+/// `archive_path` in `options.archive_type` and `options.compression`.
+/// Entries are named by their path relative to `options.input`, as a
+/// created archive names them. Files that changed since they were walked
+/// or cannot be read are left out and listed in the summary.
+pub fn pack(
+    options: &ArchiveOptions,
+    files: Vec<WalkEntry>,
+    archive_path: &Path,
+    progress: impl ProgressSink,
+) -> Result<ArchiveSummary> {
+    let manager = ArchiveManager::from_options(options.clone());
+    progress.phase_changed("Archiving", files.len() as u64);
+    let files = manager.write_archive(archive_path, files, None, &progress)?;
+    Ok(ArchiveSummary {
+        outputs: vec![archive_path.to_path_buf()],
+        files,
+        inaccessible: manager.inaccessible.into_inner().unwrap(),
+        changed: manager.changed.into_inner().unwrap(),
+        ..ArchiveSummary::default()
+    })
+}
+
+/// Reads every entry of the archive at `archive_path` back and returns
+/// those of `files`, all under `base`, that it holds with the same SHA-256
+/// digest as the file on disk. A file that cannot be read is left out.
+pub fn verify(archive_path: &Path, archive_type: ArchiveType, base: &Path, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let hashing = Hashing::new(Algorithm::Sha256);
+    let digests = entry_digests(archive_path, archive_type, &hashing)?;
+    let mut verified = Vec::new();
+    for path in files {
+        let name = escaped_path(path.strip_prefix(base)?);
+        let Some(archived) = digests.get(&name) else {
+            continue;
+        };
+        if hashing.digest_file(path).is_ok_and(|digest| digest == *archived) {
+            verified.push(path.clone());
+        }
+    }
+    Ok(verified)
+}
+
+/// Digest of every file entry in an archive, by its name as
+/// [`escaped_path`] writes it
+fn entry_digests(archive_path: &Path, archive_type: ArchiveType, hashing: &Hashing) -> Result<HashMap<String, String>> {
+    let mut digests = HashMap::new();
+    let compression = match archive_type {
+        ArchiveType::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(archive_path)?)?;
+            for i in 0..archive.len() {
+                let entry = archive.by_index(i)?;
+                if entry.is_file() {
+                    digests.insert(entry.name().to_string(), hashing.digest(entry)?);
+                }
+            }
+            return Ok(digests);
+        }
+        ArchiveType::Tar => None,
+        ArchiveType::TarGz => Some("gz"),
+        ArchiveType::TarZst => Some("zst"),
+    };
+    let mut archive = tar::Archive::new(tar_reader(archive_path, compression)?);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let name = escaped_path(&entry.path()?);
+            digests.insert(name, hashing.digest(entry)?);
+        }
+    }
+    Ok(digests)
+}
+
+/// Rough size of an archive of `files`: already compressed formats such as
+/// JPEG or MP4 are counted at full size and everything else at half, unless
+/// nothing is compressed
+pub fn estimated_size(files: &[WalkEntry], archive_type: ArchiveType, compression: CompressionLevel) -> u64 {
+    let compresses = archive_type != ArchiveType::Tar && compression != CompressionLevel::None;
+    files
+        .iter()
+        .map(|entry| {
+            let size = entry.metadata.len();
+            let packed = if compresses && !is_compressed(&entry.path) { size / 2 } else { size };
+            packed + ENTRY_OVERHEAD
+        })
+        .sum()
+}
+
+/// Whether the file at `path` is in a format that compressing again barely
+/// shrinks, judged by its extension
+fn is_compressed(path: &Path) -> bool {
+    const COMPRESSED: &[&str] = &[
+        "jpg", "jpeg", "png", "gif", "webp", "heic", "avif", "mp3", "m4a", "aac", "ogg", "opus", "flac", "mp4",
+        "m4v", "mkv", "mov", "avi", "webm", "zip", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "docx", "xlsx",
+        "pptx", "odt", "epub", "pdf",
+    ];
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSED.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+impl From<&ArchiveSummary> for Summary {
+    fn from(summary: &ArchiveSummary) -> Self {
+        Summary {
+            files: summary.files as usize,
+            bytes: 0,
+            skipped: summary.changed.len() + summary.kept.len(),
+            junk: summary.junk.len(),
+            inaccessible: summary.inaccessible.len(),
+            failed: 0,
+            phases: Vec::new(),
+        }
+    }
+}
+
 impl ArchiveType {
-    fn extension(&self) -> &'static str {
+    /// File extension of archives in this format
+    pub fn extension(&self) -> &'static str {
         match self {
             Self::Zip => "zip",
             Self::Tar => "tar",
@@ -56,10 +430,29 @@ impl ArchiveType {
             Self::TarZst => "tar.zst",
         }
     }
+
+    /// The format of the archive at `path`, told by its extension, which
+    /// may also be the short `.tgz` or `.tzst`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        [
+            (".tar.gz", Self::TarGz),
+            (".tgz", Self::TarGz),
+            (".tar.zst", Self::TarZst),
+            (".tzst", Self::TarZst),
+            (".tar", Self::Tar),
+            (".zip", Self::Zip),
+        ]
+        .into_iter()
+        .find_map(|(suffix, archive_type)| name.ends_with(suffix).then_some(archive_type))
+    }
 }
 
 #[async_trait]
 impl FileOrganizer for ArchiveManager {
+    type Plan = ArchiveOptions;
+    const NAME: &'static str = "archive";
+
     fn new(recursive: bool) -> Self {
         Self {
             recursive,
@@ -69,10 +462,19 @@ impl FileOrganizer for ArchiveManager {
             compression_level: None,
             operation_mode: None,
             split_size: None,
+            sorted: true,
+            junk: JunkList::default(),
+            junk_skipped: Mutex::default(),
+            inaccessible: Mutex::default(),
+            assume_static: false,
+            changed: Mutex::default(),
+            force: false,
+            dedupe: false,
+            hash_cache: None,
         }
     }
 
-    async fn run(&self) -> Result<()> {
+    fn configure(&mut self) -> Result<()> {
         let operation_options = vec!["Create Archive", "Extract Archive", "Update Archive", "Split Archive"];
         let operation_selection = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Select operation")
@@ -118,12 +520,14 @@ impl FileOrganizer for ArchiveManager {
             _ => unreachable!(),
         };
 
+        if matches!(operation_mode, OperationMode::Create | OperationMode::Update) {
+            self.dedupe = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Store files with identical contents only once?")
+                .default(self.dedupe)
+                .interact()?;
+        }
+
         let input_dir = get_directory_from_user("Enter input directory path")?;
-        let output_dir = if matches!(operation_mode, OperationMode::Extract) {
-            input_dir.clone()
-        } else {
-            input_dir.parent().unwrap_or(&input_dir).to_path_buf()
-        };
 
         let split_size = if matches!(operation_mode, OperationMode::Split) {
             let size_str: String = Input::with_theme(&ColorfulTheme::default())
@@ -134,105 +538,312 @@ impl FileOrganizer for ArchiveManager {
             None
         };
 
-        let mut this = Self {
-            recursive: self.recursive,
-            input_dir: Some(input_dir.clone()),
-            output_dir: Some(output_dir),
-            archive_type: Some(archive_type),
-            compression_level: Some(compression_level),
-            operation_mode: Some(operation_mode),
-            split_size,
+        self.input_dir = Some(input_dir);
+        self.operation_mode = Some(operation_mode);
+        self.archive_type = Some(archive_type);
+        self.compression_level = Some(compression_level);
+        self.split_size = split_size;
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<ArchiveOptions> {
+        let (Some(operation), Some(archive_type), Some(input)) =
+            (self.operation_mode, self.archive_type, self.input_dir.clone())
+        else {
+            anyhow::bail!("Archive operation not configured");
         };
+        let mut options = ArchiveOptions::new(operation, archive_type, input);
+        if let Some(dir) = &self.output_dir {
+            options.output_dir = dir.clone();
+        }
+        options.compression = self.compression_level.unwrap_or(CompressionLevel::Balanced);
+        options.recursive = self.recursive;
+        options.split_size = self.split_size;
+        options.sorted = self.sorted;
+        options.assume_static = self.assume_static;
+        options.junk = self.junk.clone();
+        // Only creating and updating store files, so only they can skip any
+        options.dedupe = self.dedupe && matches!(operation, OperationMode::Create | OperationMode::Update);
+        options.hash_cache = self.hash_cache.clone();
+        Ok(options)
+    }
+
+    fn execute(&self, plan: ArchiveOptions) -> Result<Summary> {
+        let estimate = SpaceEstimate::new(&plan.output_dir, plan.required_space()?)?;
+        preflight(&[estimate], self.force)?;
 
         let spinner = create_spinner("Processing archive...");
+        let metrics = Metrics::default();
+        let summary = execute(plan, (&*spinner, &metrics))?;
 
-        match operation_mode {
-            OperationMode::Create => this.create_archive()?,
-            OperationMode::Extract => this.extract_archive()?,
-            OperationMode::Update => this.update_archive()?,
-            OperationMode::Split => this.split_archive()?,
+        let mut skipped = Vec::new();
+        if !summary.junk.is_empty() {
+            skipped.push(format!("{} junk entries", summary.junk.len()));
         }
+        if !summary.inaccessible.is_empty() {
+            skipped.push(format!("{} unreadable entries", summary.inaccessible.len()));
+        }
+        if !summary.changed.is_empty() {
+            skipped.push(format!("{} changed files", summary.changed.len()));
+        }
+        if skipped.is_empty() {
+            spinner.finish_with_message("Archive operation completed successfully!");
+        } else {
+            spinner.finish_with_message(format!("Archive operation completed, {} skipped", skipped.join(", ")));
+        }
+        if let Some(savings) = &summary.dedupe {
+            println!(
+                "{} duplicate files left out and listed in {}, saving {} and about {:.1}s of compression \
+                 (hashing took {:.1}s)",
+                savings.files,
+                DUPLICATES_MANIFEST,
+                format_size(savings.bytes, BINARY),
+                savings.compressing.as_secs_f64(),
+                savings.hashing.as_secs_f64()
+            );
+        }
+        if summary.restored > 0 {
+            println!("{} duplicate files recreated from {}", summary.restored, DUPLICATES_MANIFEST);
+        }
+        if !summary.kept.is_empty() {
+            println!();
+            println!(
+                "{} files listed in {} were not recreated because a different file was already there:",
+                summary.kept.len(),
+                DUPLICATES_MANIFEST
+            );
+            for path in &summary.kept {
+                println!("  {}", path.display());
+            }
+        }
+        print_inaccessible(&summary.inaccessible, "they are not in the archive");
+        print_changed(&summary.changed, "they are not in the archive");
+        Ok(Summary {
+            phases: metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
 
-        spinner.finish_with_message("Archive operation completed successfully!");
-        Ok(())
+impl ArchiveManager {
+    /// A manager that carries out `options` without prompting
+    fn from_options(options: ArchiveOptions) -> Self {
+        Self {
+            recursive: options.recursive,
+            input_dir: Some(options.input),
+            output_dir: Some(options.output_dir),
+            archive_type: Some(options.archive_type),
+            compression_level: Some(options.compression),
+            operation_mode: Some(options.operation),
+            split_size: options.split_size,
+            sorted: options.sorted,
+            junk: options.junk,
+            junk_skipped: Mutex::default(),
+            inaccessible: Mutex::default(),
+            assume_static: options.assume_static,
+            changed: Mutex::default(),
+            force: false,
+            dedupe: options.dedupe,
+            hash_cache: options.hash_cache,
+        }
     }
 
-    fn is_recursive(&self) -> bool {
-        self.recursive
+    /// Runs even when the output filesystem looks too small, instead of
+    /// refusing
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
     }
 
-    fn get_input_dir(&self) -> Option<&PathBuf> {
-        self.input_dir.as_ref()
+    /// Reads files without first checking that they are unchanged since
+    /// the walk
+    pub fn with_assume_static(mut self, assume_static: bool) -> Self {
+        self.assume_static = assume_static;
+        self
     }
 
-    fn set_input_dir(&mut self, dir: PathBuf) {
-        self.input_dir = Some(dir);
+    /// Stores files with identical contents once; the prompt for it
+    /// defaults to this
+    pub fn with_dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
     }
 
-    fn process_file(&self, file: &PathBuf) -> Result<()> {
-        match self.operation_mode.unwrap() {
-            OperationMode::Create | OperationMode::Update => {
-                let input_dir = self.input_dir.as_ref().unwrap();
-                let relative_path = file.strip_prefix(input_dir)?;
-                let target_path = self.output_dir.as_ref().unwrap().join(relative_path);
-                if let Some(parent) = target_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::copy(file, target_path)?;
-            }
-            _ => {}
+    /// Keeps the digests `dedupe` takes between runs
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        // Without a cache directory every file is simply hashed again
+        self.hash_cache = if hash_cache { HashCache::default_path().ok() } else { None };
+        self
+    }
+
+    /// The files [`walker`] finds under `dir`, recording the junk and
+    /// unreadable entries it left out
+    fn walk(&self, dir: &Path) -> Vec<WalkEntry> {
+        let walk = walker(self.recursive, self.sorted, &self.junk).files(dir);
+        self.junk_skipped.lock().unwrap().extend(walk.junk);
+        self.skip_inaccessible(walk.inaccessible);
+        walk.files
+    }
+
+    /// Records entries left out because they could not be read
+    fn skip_inaccessible(&self, entries: impl IntoIterator<Item = Inaccessible>) {
+        self.inaccessible.lock().unwrap().extend(entries);
+    }
+
+    /// Whether a walked file still has the size and modification time the
+    /// walk saw. One that changed is recorded and should be left out.
+    fn unchanged(&self, entry: &WalkEntry) -> bool {
+        if self.assume_static || FileStamp::of(&entry.metadata).matches(&entry.path) {
+            return true;
         }
-        Ok(())
+        self.changed.lock().unwrap().push(entry.path.clone());
+        false
     }
 
-    fn create_directories(&self, base_dir: &PathBuf) -> Result<()> {
-        match self.operation_mode.unwrap() {
-            OperationMode::Create | OperationMode::Update => {
-                if let Some(output_dir) = &self.output_dir {
-                    fs::create_dir_all(output_dir.join(base_dir))?;
-                }
-            }
-            OperationMode::Extract => {
-                fs::create_dir_all(base_dir)?;
-            }
-            OperationMode::Split => {
-                if let Some(output_dir) = &self.output_dir {
-                    fs::create_dir_all(output_dir)?;
-                }
+    /// Opens a walked file to add to an archive. One the user may not read
+    /// is recorded as inaccessible and one that changed since the walk as
+    /// changed; both are left out, giving `None`.
+    fn open(&self, entry: &WalkEntry) -> Result<Option<File>> {
+        let path = entry.path.as_path();
+        if !self.unchanged(entry) {
+            return Ok(None);
+        }
+        match File::open(path) {
+            Ok(file) => Ok(Some(file)),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                self.skip_inaccessible([Inaccessible::new(path, e)]);
+                Ok(None)
             }
+            Err(e) => Err(e.into()),
         }
-        Ok(())
     }
-}
 
-impl ArchiveManager {
-    fn create_archive(&self) -> Result<()> {
+    /// Files that go into a new archive
+    fn archived_files(&self, dir: &Path, progress: &dyn ProgressSink) -> Vec<WalkEntry> {
+        progress.scan_started(dir);
+        let files = self.walk(dir);
+        progress.phase_changed("Archiving", files.len() as u64);
+        files
+    }
+
+    fn create_archive(&self, progress: &dyn ProgressSink) -> Result<ArchiveSummary> {
         if !matches!(self.operation_mode.unwrap(), OperationMode::Create) {
             anyhow::bail!("Invalid operation mode for create_archive");
         }
 
         let input_dir = self.input_dir.as_ref().unwrap();
         let output_dir = self.output_dir.as_ref().unwrap();
-        let archive_name = format!(
-            "{}.{}",
-            input_dir.file_name().unwrap().to_string_lossy(),
-            self.archive_type.unwrap().extension()
-        );
-        let archive_path = output_dir.join(archive_name);
+        let archive_path = output_dir.join(archive_name(input_dir, "", self.archive_type.unwrap()));
+        let mut entries = self.archived_files(input_dir, progress);
+        let duplicates = if self.dedupe {
+            if entries.iter().any(|entry| entry.path == input_dir.join(DUPLICATES_MANIFEST)) {
+                anyhow::bail!(
+                    "{} already has a {}; archive it without storing identical files once",
+                    input_dir.display(),
+                    DUPLICATES_MANIFEST
+                );
+            }
+            let duplicates = dedupe::split(&mut entries, &HashCache::open(self.hash_cache.as_deref()), progress)?;
+            progress.phase_changed("Archiving", entries.len() as u64);
+            Some(duplicates)
+        } else {
+            None
+        };
 
-        match self.archive_type.unwrap() {
-            ArchiveType::Zip => self.create_zip_archive(&archive_path)?,
-            ArchiveType::Tar => self.create_tar_archive(&archive_path, None)?,
-            ArchiveType::TarGz => self.create_tar_archive(&archive_path, Some(Compression::default()))?,
-            ArchiveType::TarZst => self.create_zst_archive(&archive_path)?,
+        let stored_bytes: u64 = entries.iter().map(|entry| entry.metadata.len()).sum();
+        let started = Instant::now();
+        let files = self.write_archive(&archive_path, entries, duplicates.as_ref(), progress)?;
+        let dedupe = duplicates.map(|duplicates| {
+            let bytes: u64 = duplicates
+                .files
+                .iter()
+                .filter(|(_, original, _)| !self.left_out(original))
+                .map(|(_, _, size)| size)
+                .sum();
+            let rate = started.elapsed().as_secs_f64() / stored_bytes.max(1) as f64;
+            DedupeSavings {
+                files: self.duplicates_kept(&duplicates).count(),
+                bytes,
+                hashing: duplicates.hashing,
+                compressing: Duration::from_secs_f64(rate * bytes as f64),
+            }
+        });
+
+        Ok(ArchiveSummary {
+            outputs: vec![archive_path],
+            files,
+            dedupe,
+            ..ArchiveSummary::default()
+        })
+    }
+
+    /// Whether the file at `path` was left out of the archive being
+    /// written, having changed since the walk or being unreadable
+    fn left_out(&self, path: &Path) -> bool {
+        self.changed.lock().unwrap().iter().any(|changed| changed == path)
+            || self.inaccessible.lock().unwrap().iter().any(|entry| entry.path == path)
+    }
+
+    /// The duplicates whose stored file made it into the archive
+    fn duplicates_kept<'a>(&'a self, duplicates: &'a Duplicates) -> impl Iterator<Item = (&'a Path, &'a Path)> {
+        duplicates
+            .files
+            .iter()
+            .filter(|(_, original, _)| !self.left_out(original))
+            .map(|(duplicate, original, _)| (duplicate.as_path(), original.as_path()))
+    }
+
+    /// `DUPLICATES.txt` for the end of an archive, or `None` when no
+    /// duplicate is left to list. The duplicates of a file that was left out
+    /// are not in the archive either, so they are recorded as changed with
+    /// it instead of listed.
+    fn duplicates_manifest(&self, duplicates: &Duplicates) -> Result<Option<Vec<u8>>> {
+        let orphans: Vec<PathBuf> = duplicates
+            .files
+            .iter()
+            .filter(|(_, original, _)| self.left_out(original))
+            .map(|(duplicate, _, _)| duplicate.clone())
+            .collect();
+        if orphans.len() == duplicates.files.len() {
+            self.changed.lock().unwrap().extend(orphans);
+            return Ok(None);
         }
+        let text = dedupe::manifest(self.input_dir.as_ref().unwrap(), self.duplicates_kept(duplicates))?;
+        self.changed.lock().unwrap().extend(orphans);
+        Ok(Some(text.into_bytes()))
+    }
 
-        Ok(())
+    /// Writes `entries` into a new archive at `archive_path`, followed by a
+    /// `DUPLICATES.txt` listing `duplicates`, returning how many were added.
+    /// The archive is written under a temporary name and renamed into place
+    /// once complete, so an interrupted run never leaves a truncated one.
+    fn write_archive(
+        &self,
+        archive_path: &Path,
+        entries: Vec<WalkEntry>,
+        duplicates: Option<&Duplicates>,
+        progress: &dyn ProgressSink,
+    ) -> Result<u64> {
+        match self.archive_type.unwrap() {
+            ArchiveType::Zip => self.create_zip_archive(archive_path, entries, duplicates, progress),
+            ArchiveType::Tar => self.create_tar_archive(archive_path, entries, duplicates, None, progress),
+            ArchiveType::TarGz => {
+                self.create_tar_archive(archive_path, entries, duplicates, Some(Compression::default()), progress)
+            }
+            ArchiveType::TarZst => self.create_zst_archive(archive_path, entries, duplicates, progress),
+        }
     }
 
-    fn create_zip_archive(&self, archive_path: &PathBuf) -> Result<()> {
-        let file = File::create(archive_path)?;
-        let mut zip = ZipWriter::new(file);
+    fn create_zip_archive(
+        &self,
+        archive_path: &Path,
+        entries: Vec<WalkEntry>,
+        duplicates: Option<&Duplicates>,
+        progress: &dyn ProgressSink,
+    ) -> Result<u64> {
+        let input_dir = self.input_dir.as_ref().unwrap();
+        let mut zip = ZipWriter::new(AtomicFile::create(archive_path)?);
         let options = FileOptions::default()
             .compression_method(match self.compression_level.unwrap() {
                 CompressionLevel::None => CompressionMethod::Stored,
@@ -240,52 +851,66 @@ impl ArchiveManager {
             })
             .unix_permissions(0o755);
 
-        let input_dir = self.input_dir.as_ref().unwrap();
         let base_path = input_dir.as_path();
+        let mut files = 0;
 
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
+        for entry in entries {
+            let path = entry.path.as_path();
+            progress.item_started(path);
+            let Some(mut f) = self.open(&entry)? else {
+                progress.item_finished(path, 0, Outcome::Skipped);
+                continue;
+            };
             let name = path.strip_prefix(base_path)?;
-
-            if path.is_file() {
-                zip.start_file(name.to_string_lossy(), options)?;
-                let mut f = File::open(path)?;
-                let mut buffer = Vec::new();
-                f.read_to_end(&mut buffer)?;
-                zip.write_all(&buffer)?;
-            }
+            zip.start_file(escaped_path(name), options)?;
+            let mut buffer = Vec::new();
+            f.read_to_end(&mut buffer)?;
+            zip.write_all(&buffer)?;
+            progress.item_finished(path, entry.metadata.len(), Outcome::Done);
+            files += 1;
+        }
+        if let Some(manifest) = duplicates.map(|duplicates| self.duplicates_manifest(duplicates)).transpose()?.flatten() {
+            zip.start_file(DUPLICATES_MANIFEST, options.unix_permissions(0o644))?;
+            zip.write_all(&manifest)?;
         }
 
-        zip.finish()?;
-        Ok(())
+        zip.finish()?.commit()?;
+        Ok(files)
     }
 
-    fn create_tar_archive(&self, archive_path: &PathBuf, compression: Option<Compression>) -> Result<()> {
-        let file = File::create(archive_path)?;
-        let writer: Box<dyn Write> = if let Some(level) = compression {
-            Box::new(flate2::write::GzEncoder::new(file, level))
-        } else {
-            Box::new(file)
-        };
-        let mut builder = tar::Builder::new(writer);
-
-        let input_dir = self.input_dir.as_ref().unwrap();
-        let base_path = input_dir.as_path();
-
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                let name = path.strip_prefix(base_path)?;
-                builder.append_path_with_name(path, name)?;
+    fn create_tar_archive(
+        &self,
+        archive_path: &Path,
+        entries: Vec<WalkEntry>,
+        duplicates: Option<&Duplicates>,
+        compression: Option<Compression>,
+        progress: &dyn ProgressSink,
+    ) -> Result<u64> {
+        let file = AtomicFile::create(archive_path)?;
+        let (file, files) = match compression {
+            Some(level) => {
+                let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, level));
+                let files = self.append_to_tar(&mut builder, entries, duplicates, progress)?;
+                (builder.into_inner()?.finish()?, files)
             }
-        }
-
-        builder.finish()?;
-        Ok(())
+            None => {
+                let mut builder = tar::Builder::new(file);
+                let files = self.append_to_tar(&mut builder, entries, duplicates, progress)?;
+                (builder.into_inner()?, files)
+            }
+        };
+        file.commit()?;
+        Ok(files)
     }
 
-    fn create_zst_archive(&self, archive_path: &PathBuf) -> Result<()> {
-        let file = File::create(archive_path)?;
+    fn create_zst_archive(
+        &self,
+        archive_path: &Path,
+        entries: Vec<WalkEntry>,
+        duplicates: Option<&Duplicates>,
+        progress: &dyn ProgressSink,
+    ) -> Result<u64> {
+        let file = AtomicFile::create(archive_path)?;
         let level = match self.compression_level.unwrap() {
             CompressionLevel::None => 1,
             CompressionLevel::Fast => 3,
@@ -295,24 +920,49 @@ impl ArchiveManager {
         
         let encoder = zstd::Encoder::new(file, level)?;
         let mut builder = tar::Builder::new(encoder);
+        let files = self.append_to_tar(&mut builder, entries, duplicates, progress)?;
 
-        let input_dir = self.input_dir.as_ref().unwrap();
-        let base_path = input_dir.as_path();
+        let encoder = builder.into_inner()?;
+        encoder.finish()?.commit()?;
+        Ok(files)
+    }
 
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                let name = path.strip_prefix(base_path)?;
-                builder.append_path_with_name(path, name)?;
-            }
+    /// Adds `entries` to a tar archive under their paths relative to the
+    /// input directory, and a `DUPLICATES.txt` listing `duplicates`,
+    /// returning how many were added
+    fn append_to_tar<W: Write>(
+        &self,
+        builder: &mut tar::Builder<W>,
+        entries: Vec<WalkEntry>,
+        duplicates: Option<&Duplicates>,
+        progress: &dyn ProgressSink,
+    ) -> Result<u64> {
+        let base_path = self.input_dir.as_ref().unwrap().as_path();
+        let mut files = 0;
+        for entry in entries {
+            let path = entry.path.as_path();
+            progress.item_started(path);
+            let Some(mut file) = self.open(&entry)? else {
+                progress.item_finished(path, 0, Outcome::Skipped);
+                continue;
+            };
+            let name = path.strip_prefix(base_path)?;
+            builder.append_file(name, &mut file)?;
+            progress.item_finished(path, entry.metadata.len(), Outcome::Done);
+            files += 1;
         }
-
-        let encoder = builder.into_inner()?;
-        encoder.finish()?;
-        Ok(())
+        if let Some(manifest) = duplicates.map(|duplicates| self.duplicates_manifest(duplicates)).transpose()?.flatten() {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(manifest.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+            builder.append_data(&mut header, DUPLICATES_MANIFEST, manifest.as_slice())?;
+        }
+        builder.finish()?;
+        Ok(files)
     }
 
-    fn extract_archive(&self) -> Result<()> {
+    fn extract_archive(&self) -> Result<ArchiveSummary> {
         if !matches!(self.operation_mode.unwrap(), OperationMode::Extract) {
             anyhow::bail!("Invalid operation mode for extract_archive");
         }
@@ -327,10 +977,16 @@ impl ArchiveManager {
             ArchiveType::TarZst => self.extract_tar_archive(input_dir, output_dir, Some("zst"))?,
         }
 
-        Ok(())
+        let restored = dedupe::restore(output_dir)?;
+        Ok(ArchiveSummary {
+            outputs: vec![output_dir.clone()],
+            restored: restored.files,
+            kept: restored.kept,
+            ..ArchiveSummary::default()
+        })
     }
 
-    fn extract_zip_archive(&self, archive_path: &PathBuf, output_dir: &PathBuf) -> Result<()> {
+    fn extract_zip_archive(&self, archive_path: &Path, output_dir: &Path) -> Result<()> {
         let file = File::open(archive_path)?;
         let mut archive = zip::ZipArchive::new(file)?;
 
@@ -355,21 +1011,14 @@ impl ArchiveManager {
         Ok(())
     }
 
-    fn extract_tar_archive(&self, archive_path: &PathBuf, output_dir: &PathBuf, compression: Option<&str>) -> Result<()> {
-        let file = File::open(archive_path)?;
-        let reader: Box<dyn Read> = match compression {
-            Some("gz") => Box::new(flate2::read::GzDecoder::new(file)),
-            Some("zst") => Box::new(zstd::Decoder::new(file)?),
-            _ => Box::new(file),
-        };
-
-        let mut archive = tar::Archive::new(reader);
+    fn extract_tar_archive(&self, archive_path: &Path, output_dir: &Path, compression: Option<&str>) -> Result<()> {
+        let mut archive = tar::Archive::new(tar_reader(archive_path, compression)?);
         archive.unpack(output_dir)?;
 
         Ok(())
     }
 
-    fn update_archive(&mut self) -> Result<()> {
+    fn update_archive(&mut self, progress: &dyn ProgressSink) -> Result<ArchiveSummary> {
         if !matches!(self.operation_mode.unwrap(), OperationMode::Update) {
             anyhow::bail!("Invalid operation mode for update_archive");
         }
@@ -381,29 +1030,43 @@ impl ArchiveManager {
         temp_manager.input_dir = self.input_dir.clone();
         temp_manager.output_dir = Some(temp_dir.clone());
         temp_manager.archive_type = self.archive_type;
+        temp_manager.junk = self.junk.clone();
         temp_manager.extract_archive()?;
 
         let input_dir = self.input_dir.as_ref().unwrap();
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.is_file() {
-                let relative_path = path.strip_prefix(input_dir)?;
-                let target_path = temp_dir.join(relative_path);
-                if let Some(parent) = target_path.parent() {
-                    fs::create_dir_all(parent)?;
+        for entry in self.walk(input_dir) {
+            if !self.unchanged(&entry) {
+                continue;
+            }
+            let path = entry.path;
+            let relative_path = path.strip_prefix(input_dir)?;
+            let target_path = temp_dir.join(relative_path);
+            if let Some(parent) = target_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // A recreated duplicate is a hard link, and copying into it
+            // would change the file it shares contents with too
+            if target_path.is_file() {
+                fs::remove_file(&target_path)?;
+            }
+            match fs::copy(&path, target_path) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    self.skip_inaccessible([Inaccessible::new(&path, e)]);
+                }
+                result => {
+                    result?;
                 }
-                fs::copy(path, target_path)?;
             }
         }
 
         self.input_dir = Some(temp_dir.clone());
-        self.create_archive()?;
+        let summary = self.create_archive(progress)?;
 
         fs::remove_dir_all(temp_dir)?;
-        Ok(())
+        Ok(summary)
     }
 
-    fn split_archive(&self) -> Result<()> {
+    fn split_archive(&self, progress: &dyn ProgressSink) -> Result<ArchiveSummary> {
         if !matches!(self.operation_mode.unwrap(), OperationMode::Split) {
             anyhow::bail!("Invalid operation mode for split_archive");
         }
@@ -415,27 +1078,27 @@ impl ArchiveManager {
         let mut current_size = 0;
         let mut current_part = 1;
         let mut current_archive = None;
+        let mut summary = ArchiveSummary::default();
 
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if !path.is_file() {
+        for entry in self.archived_files(input_dir, progress) {
+            let path = entry.path.as_path();
+            progress.item_started(path);
+            let Some(mut f) = self.open(&entry)? else {
+                progress.item_finished(path, 0, Outcome::Skipped);
                 continue;
-            }
-
-            let file_size = fs::metadata(path)?.len();
+            };
+            let file_size = entry.metadata.len();
             if current_size + file_size > split_size || current_archive.is_none() {
-                let archive_name = format!(
-                    "{}.part{}.{}",
-                    input_dir.file_name().unwrap().to_string_lossy(),
-                    current_part,
-                    self.archive_type.unwrap().extension()
-                );
-                let archive_path = output_dir.join(archive_name);
+                let part = format!(".part{}", current_part);
+                let archive_path = output_dir.join(archive_name(input_dir, &part, self.archive_type.unwrap()));
 
                 match self.archive_type.unwrap() {
                     ArchiveType::Zip => {
-                        let file = File::create(&archive_path)?;
-                        current_archive = Some(ZipWriter::new(file));
+                        let file = AtomicFile::create(&archive_path)?;
+                        if let Some(mut finished) = current_archive.replace(ZipWriter::new(file)) {
+                            finished.finish()?.commit()?;
+                        }
+                        summary.outputs.push(archive_path);
                     }
                     _ => anyhow::bail!("Split operation is currently only supported for ZIP archives"),
                 }
@@ -445,48 +1108,147 @@ impl ArchiveManager {
             }
 
             if let Some(archive) = current_archive.as_mut() {
-                let name = path.strip_prefix(input_dir)?.to_string_lossy();
+                let name = escaped_path(path.strip_prefix(input_dir)?);
                 let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
-                archive.start_file(name.to_string(), options)?;
+                archive.start_file(name, options)?;
                 
-                let mut f = File::open(path)?;
                 let mut buffer = Vec::new();
                 f.read_to_end(&mut buffer)?;
                 archive.write_all(&buffer)?;
                 
                 current_size += file_size;
+                progress.item_finished(path, file_size, Outcome::Done);
+                summary.files += 1;
             }
         }
 
         if let Some(mut archive) = current_archive {
-            archive.finish()?;
+            archive.finish()?.commit()?;
         }
 
-        Ok(())
+        Ok(summary)
     }
 }
 
-fn parse_size(size_str: &str) -> Result<u64> {
-    let size_str = size_str.trim().to_lowercase();
-    let mut num = String::new();
-    let mut unit = String::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for c in size_str.chars() {
-        if c.is_digit(10) || c == '.' {
-            num.push(c);
-        } else {
-            unit.push(c);
+    fn entry(path: PathBuf) -> WalkEntry {
+        let metadata = fs::metadata(&path).unwrap();
+        WalkEntry { path, metadata }
+    }
+
+    #[test]
+    fn a_failed_write_leaves_no_archive_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input");
+        fs::create_dir_all(input.join("unreadable")).unwrap();
+        fs::write(input.join("a.txt"), "first").unwrap();
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+
+        for archive_type in [ArchiveType::Zip, ArchiveType::Tar, ArchiveType::TarGz, ArchiveType::TarZst] {
+            let options = ArchiveOptions::new(OperationMode::Create, archive_type, input.clone());
+            // A directory opens like a file but fails once read, after
+            // `a.txt` is already in the archive
+            let files = vec![entry(input.join("a.txt")), entry(input.join("unreadable"))];
+            let archive_path = output.join("input.archive");
+            assert!(pack(&options, files, &archive_path, ()).is_err());
+            let left: Vec<_> = fs::read_dir(&output).unwrap().collect();
+            assert!(left.is_empty(), "{:?} left {:?}", archive_type, left);
         }
     }
 
-    let number: f64 = num.parse()?;
-    let multiplier = match unit.as_str() {
-        "b" => 1,
-        "kb" => 1024,
-        "mb" => 1024 * 1024,
-        "gb" => 1024 * 1024 * 1024,
-        _ => anyhow::bail!("Invalid size unit. Use B, KB, MB, or GB"),
-    };
+    /// `names` written under `root` in the given order, all with the same
+    /// contents for a name and the same modification time
+    fn tree(root: &Path, names: &[&str]) {
+        for name in names {
+            let path = root.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, name.repeat(100)).unwrap();
+            filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1_700_000_000, 0)).unwrap();
+        }
+    }
 
-    Ok((number * multiplier as f64) as u64)
-} 
\ No newline at end of file
+    #[test]
+    fn identical_trees_make_byte_identical_archives() {
+        let names = ["b/two.txt", "a.txt", "c/d/three.txt", "b/one.txt", "Z.txt"];
+        let mut reversed = names;
+        reversed.reverse();
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        tree(&first.path().join("input"), &names);
+        tree(&second.path().join("input"), &reversed);
+
+        for archive_type in [ArchiveType::Tar, ArchiveType::TarGz] {
+            let archives: Vec<Vec<u8>> = [&first, &second]
+                .iter()
+                .map(|dir| {
+                    let options = ArchiveOptions::new(OperationMode::Create, archive_type, dir.path().join("input"));
+                    let summary = execute(options, ()).unwrap();
+                    assert_eq!(summary.files, names.len() as u64);
+                    fs::read(&summary.outputs[0]).unwrap()
+                })
+                .collect();
+            assert!(archives[0] == archives[1], "{:?} archives differ", archive_type);
+        }
+    }
+
+    #[test]
+    fn extraction_never_replaces_a_file_already_at_a_duplicate_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let photos = dir.path().join("photos");
+        fs::create_dir(&photos).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            fs::write(photos.join(name), "jpeg").unwrap();
+        }
+        let mut options = ArchiveOptions::new(OperationMode::Create, ArchiveType::Zip, photos);
+        options.dedupe = true;
+        let archive = execute(options, ()).unwrap().outputs.remove(0);
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        fs::write(output.join("b.jpg"), "mine").unwrap();
+        fs::write(output.join("c.jpg"), "jpeg").unwrap();
+        let mut options = ArchiveOptions::new(OperationMode::Extract, ArchiveType::Zip, archive);
+        options.output_dir = output.clone();
+        let summary = execute(options, ()).unwrap();
+
+        assert_eq!(summary.kept, [output.join("b.jpg")]);
+        assert_eq!(summary.restored, 1);
+        assert_eq!(fs::read_to_string(output.join("b.jpg")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(output.join("a.jpg")).unwrap(), "jpeg");
+        assert_eq!(fs::read_to_string(output.join("c.jpg")).unwrap(), "jpeg");
+        assert!(!output.join(DUPLICATES_MANIFEST).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_are_not_utf8_stay_distinct_in_archives() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input");
+        fs::create_dir(&input).unwrap();
+        let names = [&b"r\xe9sum\xe9.txt"[..], &b"r\xe8sum\xe8.txt"[..]];
+        for name in names {
+            fs::write(input.join(std::ffi::OsStr::from_bytes(name)), name).unwrap();
+        }
+
+        let options = ArchiveOptions::new(OperationMode::Create, ArchiveType::Zip, input.clone());
+        let zip = execute(options, ()).unwrap().outputs.remove(0);
+        let zip = zip::ZipArchive::new(File::open(zip).unwrap()).unwrap();
+        let mut entries: Vec<&str> = zip.file_names().collect();
+        entries.sort();
+        assert_eq!(entries, ["r%E8sum%E8.txt", "r%E9sum%E9.txt"]);
+
+        let options = ArchiveOptions::new(OperationMode::Create, ArchiveType::Tar, input);
+        let tar = execute(options, ()).unwrap().outputs.remove(0);
+        let mut tar = tar::Archive::new(File::open(tar).unwrap());
+        let entries: Vec<Vec<u8>> =
+            tar.entries().unwrap().map(|entry| entry.unwrap().path_bytes().into_owned()).collect();
+        assert_eq!(entries, [names[1], names[0]]);
+    }
+
+}
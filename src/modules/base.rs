@@ -1,31 +1,238 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
+use crate::utils::{
+    hooks,
+    metrics::{format_phases, PhaseTiming},
+    walk::Inaccessible,
+};
+
+/// Tally every module returns when a run finishes
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Summary {
+    /// Files transferred, converted, archived, or otherwise acted on
+    pub files: usize,
+    /// Bytes those files held
+    pub bytes: u64,
+    /// Files found but left alone, by a filter or the module's own choice
+    pub skipped: usize,
+    /// Known junk files and directories left out of the walk, such as
+    /// `.DS_Store` or `@eaDir`
+    pub junk: usize,
+    /// Files and directories that could not be read, so were never
+    /// considered
+    pub inaccessible: usize,
+    /// Files whose processing failed without aborting the run
+    pub failed: usize,
+    /// Where the time went, printed after the run
+    pub phases: Vec<PhaseTiming>,
+}
+
+/// A per-file error that was recorded instead of aborting the run
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub path: PathBuf,
+    /// Step that failed, e.g. "hash" or "move"
+    pub phase: String,
+    pub message: String,
+}
+
+/// Error policy shared by the modules. Per-file errors are recorded and the
+/// run carries on, unless `fail_fast` asks for the first one to abort it.
+/// Setup errors, such as an unusable input or output directory, always
+/// abort and do not go through here.
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    fail_fast: bool,
+    errors: Mutex<Vec<FileError>>,
+}
+
+impl ErrorLog {
+    pub fn new(fail_fast: bool) -> Self {
+        Self {
+            fail_fast,
+            errors: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Passes a successful `result` through as `Some`. A failure is recorded
+    /// and becomes `None`, or under `fail_fast` is returned with the path
+    /// and phase attached.
+    pub fn check<T>(&self, path: &Path, phase: &str, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.fail_fast => {
+                Err(e.context(format!("Failed to {} {}", phase, path.display())))
+            }
+            Err(e) => {
+                self.errors.lock().unwrap().push(FileError {
+                    path: path.to_path_buf(),
+                    phase: phase.to_string(),
+                    message: format!("{:#}", e),
+                });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Adds errors recorded by an earlier step of the same run
+    pub fn extend(&self, errors: Vec<FileError>) {
+        self.errors.lock().unwrap().extend(errors);
+    }
+
+    pub fn into_errors(self) -> Vec<FileError> {
+        self.errors.into_inner().unwrap()
+    }
+}
+
+/// Lists the files a run could not process and why
+pub fn print_errors(errors: &[FileError]) {
+    if errors.is_empty() {
+        return;
+    }
+    println!();
+    println!("{} files could not be processed:", errors.len());
+    for error in errors {
+        println!("  {} ({}): {}", error.path.display(), error.phase, error.message);
+    }
+    println!("Use --fail-fast to stop at the first error instead");
+}
+
+/// Lists the entries a run skipped because they could not be read.
+/// `consequence` says what that means for the results.
+pub fn print_inaccessible(entries: &[Inaccessible], consequence: &str) {
+    if entries.is_empty() {
+        return;
+    }
+    println!();
+    println!("{} entries could not be read and were skipped; {}:", entries.len(), consequence);
+    for entry in entries {
+        println!("  {}: {}", entry.path.display(), entry.reason);
+    }
+}
+
+/// Lists the files a run left alone because they changed after the scan.
+/// `consequence` says what that means for the results.
+pub fn print_changed(paths: &[PathBuf], consequence: &str) {
+    if paths.is_empty() {
+        return;
+    }
+    println!();
+    println!("{} files changed during the run and were skipped; {}:", paths.len(), consequence);
+    for path in paths {
+        println!("  {}", path.display());
+    }
+    println!("Run again once nothing else is writing to them");
+}
+
+/// Lifecycle shared by the modules: settings are gathered first, every
+/// action is planned before anything on disk changes, and only then is the
+/// plan carried out
 #[async_trait]
-pub trait FileOrganizer {
+pub trait FileOrganizer: Sized + Send + 'static {
+    /// Intended actions of a run, serializable for previews and manifests
+    type Plan: Serialize + Send;
+
+    /// The command that runs the module, which its hooks are configured
+    /// under, e.g. "clean-junk"
+    const NAME: &'static str;
+
     /// Initialize a new instance of the organizer
-    fn new(recursive: bool) -> Self where Self: Sized;
-    
-    /// Run the organization process
-    async fn run(&self) -> Result<()>;
-    
-    /// Whether the organizer operates recursively on subdirectories
-    #[allow(unused)]
-    fn is_recursive(&self) -> bool;
-    
-    /// Get the input directory for the operation
-    #[allow(unused)]
-    fn get_input_dir(&self) -> Option<&PathBuf>;
-    
-    /// Set the input directory for the operation
-    #[allow(unused)]
-    fn set_input_dir(&mut self, dir: PathBuf);
-    
-    /// Process a single file
-    fn process_file(&self, file: &PathBuf) -> Result<()>;
-    
-    /// Create necessary directories for the operation
-    #[allow(unused)]
-    fn create_directories(&self, base_dir: &PathBuf) -> Result<()>;
-} 
\ No newline at end of file
+    fn new(recursive: bool) -> Self;
+
+    /// Fills in every setting a `with_*` builder did not preset, prompting
+    /// for it
+    fn configure(&mut self) -> Result<()>;
+
+    /// Scans the input and decides every action. Choices that depend on
+    /// what the scan found are settled here, prompting unless preset.
+    fn plan(&mut self) -> Result<Self::Plan>;
+
+    /// Shows the plan where the run asks for it, such as a dry run, and
+    /// returns whether to carry it out
+    fn review(&self, _plan: &Self::Plan) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Carries out the plan
+    fn execute(&self, plan: Self::Plan) -> Result<Summary>;
+
+    /// Run the organization process. Prompts, scans, and file operations
+    /// all block, so they run on a blocking thread, off the async runtime.
+    /// The configured hooks run once the plan has been carried out, however
+    /// that went; a preview or a declined plan runs none.
+    async fn run(self) -> Result<Summary> {
+        tokio::task::spawn_blocking(move || self.run_blocking(|result| hooks::finished(Self::NAME, result))).await?
+    }
+
+    /// [`run`](Self::run) on the current thread, handing what `execute`
+    /// returned to `executed`. It is not called when the run stops before
+    /// that, including when `review` declines the plan.
+    fn run_blocking(mut self, executed: impl FnOnce(&Result<Summary>)) -> Result<Summary> {
+        self.configure()?;
+        let plan = self.plan()?;
+        if !self.review(&plan)? {
+            return Ok(Summary::default());
+        }
+        let result = self.execute(plan);
+        executed(&result);
+        let summary = result?;
+        if !summary.phases.is_empty() {
+            println!("Time: {}", format_phases(&summary.phases));
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Plans one file and carries the plan out only when `approve` says so
+    struct Organizer {
+        approve: bool,
+    }
+
+    impl FileOrganizer for Organizer {
+        type Plan = Vec<PathBuf>;
+        const NAME: &'static str = "test";
+
+        fn new(_recursive: bool) -> Self {
+            Self { approve: true }
+        }
+
+        fn configure(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn plan(&mut self) -> Result<Self::Plan> {
+            Ok(vec![PathBuf::from("file")])
+        }
+
+        fn review(&self, _plan: &Self::Plan) -> Result<bool> {
+            Ok(self.approve)
+        }
+
+        fn execute(&self, plan: Self::Plan) -> Result<Summary> {
+            Ok(Summary { files: plan.len(), ..Summary::default() })
+        }
+    }
+
+    #[test]
+    fn hooks_run_only_once_the_plan_is_carried_out() {
+        let hooked = Cell::new(None);
+        let summary = Organizer { approve: false }.run_blocking(|result| hooked.set(Some(result.is_ok()))).unwrap();
+        assert_eq!(summary.files, 0);
+        assert_eq!(hooked.get(), None);
+
+        let summary = Organizer::new(true).run_blocking(|result| hooked.set(Some(result.is_ok()))).unwrap();
+        assert_eq!(summary.files, 1);
+        assert_eq!(hooked.get(), Some(true));
+    }
+}
@@ -0,0 +1,797 @@
+mod report;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::{Input, Select};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    create_spinner,
+    expand::expand_path,
+    filter::PathFilter,
+    get_directory_from_user,
+    hash_cache::HashCache,
+    hashing::Hashing,
+    journal::Journal,
+    junk::JunkList,
+    links,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    transfer::TransferMode,
+    walk::{Inaccessible, WalkEntry, Walker},
+};
+
+/// Finds symlinks whose target is gone, or lies outside the directories
+/// links may point into, and deletes them, points them at a file found
+/// elsewhere in the tree, or lets the user retarget them one by one
+pub struct BrokenLinkFinder {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Links resolving outside all of these count as broken too
+    allowed_roots: Vec<PathBuf>,
+    /// `None` asks once the broken links are listed
+    action: Option<LinkAction>,
+    /// Look for files a dangling link's target may have moved to
+    find_repairs: bool,
+    /// Check inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Glob patterns a link's relative path must match to be checked
+    include: Vec<String>,
+    /// Glob patterns that leave a link alone
+    exclude: Vec<String>,
+    /// Show what would change without changing anything
+    dry_run: bool,
+    /// Confirm repairs by digests remembered in the hash cache
+    hash_cache: bool,
+    /// `None` asks after the run; `Some(None)` writes no report
+    report: Option<Option<ReportFormat>>,
+    report_path: Option<PathBuf>,
+    /// Abort on the first link that cannot be checked, removed, or changed
+    fail_fast: bool,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// What to do with the broken links found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LinkAction {
+    /// List them and change nothing
+    Report,
+    Delete,
+    /// Point dangling links at the file found for them, leaving the rest
+    Repair,
+    /// Ask for each link what to do
+    Retarget,
+}
+
+/// Everything a broken-link scan needs, with no prompting
+#[derive(Debug, Clone)]
+pub struct LinkOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Check inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    pub filter: PathFilter,
+    pub junk: JunkList,
+    /// Links resolving outside every one of these count as broken, even
+    /// when their target exists; empty allows any target
+    pub allowed_roots: Vec<PathBuf>,
+    /// Look under the input directory for files a dangling link's target
+    /// may have moved to
+    pub find_repairs: bool,
+    /// Hash cache whose remembered digests confirm a repair; `None` goes
+    /// by name alone
+    pub hash_cache: Option<PathBuf>,
+    /// Where the undo manifest goes; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-link error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl LinkOptions {
+    /// A recursive scan of `input_dir` that looks for repairs and allows
+    /// links to point anywhere
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            allowed_roots: Vec::new(),
+            find_repairs: true,
+            hash_cache: None,
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// Why a link counts as broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum LinkProblem {
+    /// The target does not exist
+    Dangling,
+    /// The link resolves to itself through other links
+    Loop,
+    /// The target exists but lies outside the allowed directories
+    Outside,
+}
+
+impl LinkProblem {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Dangling => "missing target",
+            Self::Loop => "link loop",
+            Self::Outside => "outside allowed roots",
+        }
+    }
+}
+
+/// How a file was recognized as the one a dangling link lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MatchKind {
+    /// Same size and digest as the hash cache remembers for the old target
+    Digest,
+    /// The only file under the input directory with the target's name; the
+    /// cache knew nothing about the old target
+    Name,
+}
+
+/// A file a dangling link can be pointed at instead
+#[derive(Debug, Clone, Serialize)]
+pub struct Repair {
+    pub target: PathBuf,
+    pub matched_by: MatchKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenLink {
+    pub path: PathBuf,
+    /// The target as stored in the link, relative or absolute
+    pub target: PathBuf,
+    pub problem: LinkProblem,
+    pub repair: Option<Repair>,
+}
+
+/// Result of [`plan`]: the broken links, and what was left unchecked
+#[derive(Debug, Default, Serialize)]
+pub struct LinkPlan {
+    pub links: Vec<BrokenLink>,
+    /// Symlinks looked at
+    pub checked: usize,
+    /// Windows shortcuts (`.lnk`), which are files rather than links and
+    /// are not checked
+    pub shortcuts: Vec<PathBuf>,
+    /// Working copies that were not checked
+    pub repositories: Vec<PathBuf>,
+    pub junk: usize,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl LinkPlan {
+    /// One line per broken link, paths relative to `input`
+    pub fn print(&self, input: &Path) {
+        for link in &self.links {
+            println!(
+                "{} → {} ({})",
+                link.path.strip_prefix(input).unwrap_or(&link.path).display(),
+                link.target.display(),
+                link.problem.label()
+            );
+            if let Some(repair) = &link.repair {
+                let by = match repair.matched_by {
+                    MatchKind::Digest => "same contents",
+                    MatchKind::Name => "same name",
+                };
+                println!(
+                    "    found {} ({})",
+                    repair.target.strip_prefix(input).unwrap_or(&repair.target).display(),
+                    by
+                );
+            }
+        }
+    }
+
+    /// What `action` does to each link: `Some(target)` to point it there,
+    /// `None` to delete it. Links it leaves alone are not listed, and
+    /// [`LinkAction::Retarget`] asks, so yields nothing here.
+    pub fn fixes(&self, action: LinkAction) -> Vec<LinkFix> {
+        self.links
+            .iter()
+            .filter_map(|link| match action {
+                LinkAction::Delete => Some(LinkFix {
+                    link: link.path.clone(),
+                    new_target: None,
+                }),
+                LinkAction::Repair => link.repair.as_ref().map(|repair| LinkFix {
+                    link: link.path.clone(),
+                    new_target: Some(new_target(link, &repair.target)),
+                }),
+                LinkAction::Report | LinkAction::Retarget => None,
+            })
+            .collect()
+    }
+}
+
+/// One change to a broken link
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkFix {
+    pub link: PathBuf,
+    /// `None` deletes the link
+    pub new_target: Option<PathBuf>,
+}
+
+/// Result of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct LinkSummary {
+    pub deleted: Vec<PathBuf>,
+    /// Links and their new targets
+    pub relinked: Vec<(PathBuf, PathBuf)>,
+    /// Broken links that were left alone
+    pub left: usize,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    pub shortcuts: usize,
+    pub junk: usize,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&LinkSummary> for Summary {
+    fn from(summary: &LinkSummary) -> Self {
+        Summary {
+            files: summary.deleted.len() + summary.relinked.len(),
+            bytes: 0,
+            skipped: summary.left + summary.shortcuts,
+            junk: summary.junk,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// The target `link` gets to reach `file`: relative from the link's
+/// directory if the old target was relative, otherwise absolute
+fn new_target(link: &BrokenLink, file: &Path) -> PathBuf {
+    let Ok(file) = links::absolute(file) else {
+        return file.to_path_buf();
+    };
+    match links::absolute_parent(&link.path) {
+        Ok(dir) if link.target.is_relative() => links::relative_to(&file, &dir),
+        _ => file,
+    }
+}
+
+/// Walks the input directory for symlinks and checks where each leads.
+/// For a dangling link, a file under the input directory with the
+/// target's name is offered as a repair when the hash cache remembers the
+/// old target and the file has its size and digest, or when the cache
+/// knows nothing and the name is unique.
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use file_organizer_rust::modules::broken_links::{self, LinkOptions, LinkProblem, MatchKind};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir(dir.path().join("music"))?;
+/// std::fs::write(dir.path().join("music/song.mp3"), "la la")?;
+/// std::os::unix::fs::symlink("old/song.mp3", dir.path().join("favourite.mp3"))?;
+///
+/// let plan = broken_links::plan(&LinkOptions::new(dir.path().to_path_buf()), ())?;
+/// assert_eq!(plan.links[0].problem, LinkProblem::Dangling);
+/// let repair = plan.links[0].repair.as_ref().unwrap();
+/// assert_eq!(repair.target, dir.path().join("music/song.mp3"));
+/// assert_eq!(repair.matched_by, MatchKind::Name);
+/// # }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn plan(options: &LinkOptions, progress: impl ProgressSink) -> Result<LinkPlan> {
+    let root = &options.input_dir;
+    let allowed = options
+        .allowed_roots
+        .iter()
+        .map(|dir| fs::canonicalize(dir).map_err(|e| anyhow::anyhow!("Allowed root {}: {}", dir.display(), e)))
+        .collect::<Result<Vec<_>>>()?;
+
+    progress.scan_started(root);
+    let walker = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .sorted(true);
+    let walk = walker.links(root);
+    let mut plan = LinkPlan {
+        checked: walk.files.len(),
+        repositories: walk.repositories,
+        junk: walk.junk.len(),
+        inaccessible: walk.inaccessible,
+        ..LinkPlan::default()
+    };
+
+    // Every regular file, for shortcuts and for finding where a target
+    // went; repairs are searched for in the whole tree
+    let files = Walker::new(true)
+        .include_repos(options.include_repos)
+        .junk(Some(options.junk.clone()))
+        .sorted(true)
+        .files(root);
+    plan.shortcuts = files
+        .files
+        .iter()
+        .filter(|entry| {
+            entry
+                .path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
+                && options.filter.matches(&entry.path, root)
+        })
+        .map(|entry| entry.path.clone())
+        .collect();
+    let mut by_name: HashMap<OsString, Vec<&WalkEntry>> = HashMap::new();
+    if options.find_repairs {
+        for entry in &files.files {
+            if let Some(name) = entry.path.file_name() {
+                by_name.entry(name.to_os_string()).or_default().push(entry);
+            }
+        }
+    }
+
+    progress.phase_changed("checking", walk.files.len() as u64);
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    let errors = ErrorLog::new(options.fail_fast);
+    for entry in walk.files {
+        progress.item_started(&entry.path);
+        let checked = errors.check(&entry.path, "check", check_link(&entry.path, &allowed));
+        let outcome = match checked? {
+            Some(Some((target, resolved, problem))) => {
+                let repair = if problem == LinkProblem::Dangling {
+                    find_repair(&resolved, &by_name, &cache)
+                } else {
+                    None
+                };
+                plan.links.push(BrokenLink {
+                    path: entry.path.clone(),
+                    target,
+                    problem,
+                    repair,
+                });
+                Outcome::Done
+            }
+            Some(None) => Outcome::Skipped,
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&entry.path, 0, outcome);
+    }
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+    plan.errors = errors.into_errors();
+    Ok(plan)
+}
+
+/// The stored and resolved target of the link at `path` and what is wrong
+/// with it, or `None` for a link that is fine
+fn check_link(path: &Path, allowed: &[PathBuf]) -> Result<Option<(PathBuf, PathBuf, LinkProblem)>> {
+    let target = fs::read_link(path)?;
+    let resolved = links::resolve(path)?;
+    let problem = match fs::metadata(path) {
+        Ok(_) if allowed.is_empty() => return Ok(None),
+        Ok(_) => {
+            let real = fs::canonicalize(path)?;
+            if allowed.iter().any(|dir| real.starts_with(dir)) {
+                return Ok(None);
+            }
+            LinkProblem::Outside
+        }
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => LinkProblem::Dangling,
+        Err(_) if loops(path) => LinkProblem::Loop,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some((target, resolved, problem)))
+}
+
+/// Whether following the link at `path` from link to link comes back
+/// around, or goes on longer than the operating system would follow it
+fn loops(path: &Path) -> bool {
+    let mut seen = HashSet::new();
+    let mut current = path.to_path_buf();
+    for _ in 0..40 {
+        if !links::is_symlink(&current) {
+            return false;
+        }
+        match links::resolve(&current) {
+            Ok(next) if seen.insert(next.clone()) => current = next,
+            Ok(_) => return true,
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+/// A file under the root that is most likely what `missing` was
+fn find_repair(missing: &Path, by_name: &HashMap<OsString, Vec<&WalkEntry>>, cache: &HashCache) -> Option<Repair> {
+    let candidates = by_name.get(missing.file_name()?)?;
+    match cache.lookup(missing) {
+        Some(known) => {
+            let hashing = Hashing::new(known.algorithm);
+            candidates
+                .iter()
+                .filter(|entry| entry.metadata.len() == known.size)
+                .find(|entry| {
+                    cache
+                        .digest(&hashing, &entry.path, &entry.metadata)
+                        .is_ok_and(|(digest, _)| digest == known.digest)
+                })
+                .map(|entry| Repair {
+                    target: entry.path.clone(),
+                    matched_by: MatchKind::Digest,
+                })
+        }
+        None => match candidates.as_slice() {
+            [only] => Some(Repair {
+                target: only.path.clone(),
+                matched_by: MatchKind::Name,
+            }),
+            _ => None,
+        },
+    }
+}
+
+/// Deletes or retargets every link in `fixes`, recording each in an undo
+/// manifest so deleted links can be recreated and retargeted ones pointed
+/// back
+pub fn apply(options: &LinkOptions, plan: LinkPlan, fixes: Vec<LinkFix>, progress: impl ProgressSink) -> Result<LinkSummary> {
+    progress.phase_changed("relinking", fixes.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    errors.extend(plan.errors);
+    let mut journal = Journal::new("relink", TransferMode::Move);
+    let mut summary = LinkSummary {
+        left: plan.links.len().saturating_sub(fixes.len()),
+        shortcuts: plan.shortcuts.len(),
+        junk: plan.junk,
+        inaccessible: plan.inaccessible,
+        ..LinkSummary::default()
+    };
+    let mut result = Ok(());
+    for fix in fixes {
+        progress.item_started(&fix.link);
+        let rule = if fix.new_target.is_some() { "retarget" } else { "delete broken link" };
+        let changed = errors.check(&fix.link, rule, journal.relink(&fix.link, fix.new_target.as_deref(), Some(rule)));
+        let outcome = match changed {
+            Ok(Some(())) => {
+                match fix.new_target {
+                    Some(target) => summary.relinked.push((fix.link.clone(), target)),
+                    None => summary.deleted.push(fix.link.clone()),
+                }
+                Outcome::Done
+            }
+            Ok(None) => Outcome::Failed,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        progress.item_finished(&fix.link, 0, outcome);
+    }
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // changed can be undone
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            summary.manifest = Some(path);
+        }
+    }
+    result?;
+
+    summary.errors = errors.into_errors();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for BrokenLinkFinder {
+    type Plan = LinkPlan;
+    const NAME: &'static str = "broken-links";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            allowed_roots: Vec::new(),
+            action: None,
+            find_repairs: true,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            dry_run: false,
+            hash_cache: true,
+            report: None,
+            report_path: None,
+            fail_fast: false,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to check for broken links")?);
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<LinkPlan> {
+        let spinner = create_spinner("Checking symlinks...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Lists every broken link with its target and any repair found. A dry
+    /// run stops here, after showing what the preset action would do.
+    fn review(&self, plan: &LinkPlan) -> Result<bool> {
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print(input_dir);
+        if !plan.shortcuts.is_empty() {
+            println!(
+                "{} Windows shortcuts (.lnk) were not checked; only symlinks are",
+                plan.shortcuts.len()
+            );
+        }
+        if plan.links.is_empty() {
+            println!("No broken links among {} symlinks", plan.checked);
+            print_inaccessible(&plan.inaccessible, "links inside them were not checked");
+            print_errors(&plan.errors);
+            return Ok(false);
+        }
+        if self.dry_run {
+            if let Some(action) = self.action {
+                for fix in plan.fixes(action) {
+                    match fix.new_target {
+                        Some(target) => println!("Would point {} → {}", fix.link.display(), target.display()),
+                        None => println!("Would delete {}", fix.link.display()),
+                    }
+                }
+            }
+            let planned = self.action.map(|action| plan.fixes(action)).unwrap_or_default();
+            self.write_report(&plan.links, &planned)?;
+            println!("Dry run finished, no links were changed");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn execute(&self, plan: LinkPlan) -> Result<Summary> {
+        let action = match self.action {
+            Some(action) => action,
+            None => {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("What should happen to the {} broken links?", plan.links.len()))
+                    .items(&[
+                        "Repair those a match was found for, leave the rest",
+                        "Delete them",
+                        "Decide one by one",
+                        "Leave them",
+                    ])
+                    .default(0)
+                    .interact()?;
+                [LinkAction::Repair, LinkAction::Delete, LinkAction::Retarget, LinkAction::Report][choice]
+            }
+        };
+        let fixes = match action {
+            LinkAction::Retarget => ask_fixes(&plan)?,
+            action => plan.fixes(action),
+        };
+
+        let options = self.options()?;
+        let spinner = create_spinner("Fixing links...");
+        self.metrics.phase("relinking");
+        let links = plan.links.clone();
+        let summary = apply(&options, plan, fixes, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!(
+            "{} links repaired, {} deleted, {} left alone",
+            summary.relinked.len(),
+            summary.deleted.len(),
+            summary.left
+        ));
+        print_inaccessible(&summary.inaccessible, "links inside them were not checked");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+        self.write_report(&links, &summary_fixes(&summary))?;
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+/// The changes a finished run made, for the report
+fn summary_fixes(summary: &LinkSummary) -> Vec<LinkFix> {
+    let deleted = summary.deleted.iter().map(|link| LinkFix {
+        link: link.clone(),
+        new_target: None,
+    });
+    let relinked = summary.relinked.iter().map(|(link, target)| LinkFix {
+        link: link.clone(),
+        new_target: Some(target.clone()),
+    });
+    deleted.chain(relinked).collect()
+}
+
+/// Asks what to do with each broken link in turn
+fn ask_fixes(plan: &LinkPlan) -> Result<Vec<LinkFix>> {
+    let mut fixes = Vec::new();
+    for link in &plan.links {
+        let mut items = Vec::new();
+        if let Some(repair) = &link.repair {
+            items.push(format!("Point it to {}", repair.target.display()));
+        }
+        items.extend(["Enter a new target".to_string(), "Delete it".to_string(), "Leave it".to_string()]);
+        let choice = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} → {} ({})", link.path.display(), link.target.display(), link.problem.label()))
+            .items(&items)
+            .default(0)
+            .interact()?;
+        let choice = if link.repair.is_some() { choice } else { choice + 1 };
+        let new_target = match choice {
+            0 => Some(new_target(link, &link.repair.as_ref().unwrap().target)),
+            1 => {
+                let typed: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("New target")
+                    .interact_text()?;
+                let target = expand_path(typed.trim())?;
+                if !link.path.parent().unwrap_or(Path::new("")).join(&target).exists() {
+                    println!("Note: {} does not exist either", target.display());
+                }
+                Some(target)
+            }
+            2 => None,
+            _ => continue,
+        };
+        fixes.push(LinkFix {
+            link: link.path.clone(),
+            new_target,
+        });
+    }
+    Ok(fixes)
+}
+
+impl BrokenLinkFinder {
+    /// Presets the directory to check so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Also counts links as broken when they resolve outside all of `dirs`
+    pub fn with_allowed_roots(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.allowed_roots = dirs;
+        self
+    }
+
+    /// Presets what happens to the broken links so the prompt is skipped
+    pub fn with_action(mut self, action: LinkAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Whether to look for files dangling links' targets moved to
+    pub fn with_find_repairs(mut self, find: bool) -> Self {
+        self.find_repairs = find;
+        self
+    }
+
+    /// Checks inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only checks links whose path relative to the input matches one of
+    /// `patterns`, e.g. "music/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves links whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Lists the broken links and what would happen to them without
+    /// changing anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Whether repairs may be confirmed with digests from the hash cache
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Writes the report as CSV or JSON, to `path` or next to the input,
+    /// or with `None` writes none, without asking
+    pub fn with_report(mut self, format: Option<ReportFormat>, path: Option<PathBuf>) -> Self {
+        self.report = Some(format);
+        self.report_path = path;
+        self
+    }
+
+    /// Aborts on the first link that cannot be checked or changed
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Writes the report in the preset format, or asks whether to write one
+    /// and in which format; the path defaults to one in the input directory
+    fn write_report(&self, links: &[BrokenLink], fixes: &[LinkFix]) -> Result<()> {
+        let format = match self.report {
+            Some(format) => format,
+            None => {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Write a report?")
+                    .items(&["No", "CSV", "JSON"])
+                    .default(0)
+                    .interact()?;
+                [None, Some(ReportFormat::Csv), Some(ReportFormat::Json)][choice]
+            }
+        };
+        let Some(format) = format else {
+            return Ok(());
+        };
+
+        let input_dir = self.input_dir.clone().unwrap_or_default();
+        let path = self
+            .report_path
+            .clone()
+            .unwrap_or_else(|| report::default_path(&input_dir, format));
+        report::write(links, fixes, &path, format)?;
+        println!("Report written to {}", path.display());
+        Ok(())
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<LinkOptions> {
+        let input_dir = self
+            .input_dir
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        Ok(LinkOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            junk: self.junk.clone(),
+            allowed_roots: self.allowed_roots.clone(),
+            find_repairs: self.find_repairs,
+            hash_cache: if self.hash_cache { Some(HashCache::default_path()?) } else { None },
+            manifest_dir: if self.dry_run { None } else { Some(Journal::default_dir()?) },
+            fail_fast: self.fail_fast,
+            ..LinkOptions::new(input_dir)
+        })
+    }
+}
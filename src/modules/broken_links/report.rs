@@ -0,0 +1,71 @@
+use anyhow::Result;
+use chrono::Local;
+use serde::Serialize;
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{BrokenLink, LinkFix};
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    atomic::{write_atomic, AtomicFile},
+    csv_field,
+};
+
+/// `<input>/broken-links-<timestamp>.<ext>`
+pub fn default_path(input_dir: &Path, format: ReportFormat) -> PathBuf {
+    input_dir.join(format!(
+        "broken-links-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    links: &'a [BrokenLink],
+    fixes: &'a [LinkFix],
+}
+
+/// Writes every broken link found and what was done to it, as JSON or as
+/// CSV with the columns link, target, problem, repair, action (delete,
+/// retarget, or none), and new target
+pub fn write(links: &[BrokenLink], fixes: &[LinkFix], path: &Path, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Json => write_atomic(path, serde_json::to_string_pretty(&JsonReport { links, fixes })?)?,
+        ReportFormat::Csv => {
+            let mut file = BufWriter::new(AtomicFile::create(path)?);
+            writeln!(file, "link,target,problem,repair,action,new_target")?;
+            for link in links {
+                let fix = fixes.iter().find(|fix| fix.link == link.path);
+                let action = match fix {
+                    Some(LinkFix { new_target: Some(_), .. }) => "retarget",
+                    Some(LinkFix { new_target: None, .. }) => "delete",
+                    None => "none",
+                };
+                let new_target = fix
+                    .and_then(|fix| fix.new_target.as_ref())
+                    .map(|target| target.display().to_string())
+                    .unwrap_or_default();
+                let repair = link
+                    .repair
+                    .as_ref()
+                    .map(|repair| repair.target.display().to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{}",
+                    csv_field(&link.path.display().to_string()),
+                    csv_field(&link.target.display().to_string()),
+                    link.problem.label(),
+                    csv_field(&repair),
+                    action,
+                    csv_field(&new_target)
+                )?;
+            }
+            file.into_inner()?.commit()?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use std::path::{Component, Path};
+
+use crate::utils::hashing::Algorithm;
+
+/// One line of a manifest: a digest and the path it was taken of, relative
+/// to the manifest's root and separated by `/`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub digest: String,
+    pub path: String,
+}
+
+/// Default manifest file name for `algorithm`, e.g. `SHA256SUMS`
+pub fn file_name(algorithm: Algorithm) -> String {
+    format!("{}SUMS", algorithm.name().to_uppercase())
+}
+
+/// `path` relative to `root` in manifest form, or `None` when a component
+/// is not valid UTF-8 and so cannot be written as text
+pub fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// A line as `sha256sum` writes it: the digest, two spaces, and the path.
+/// A path containing a backslash or line break gets the same escaping GNU
+/// coreutils uses, marked by a leading backslash.
+pub fn format_line(digest: &str, path: &str) -> String {
+    if path.contains(['\\', '\n', '\r']) {
+        let escaped = path
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        format!("\\{}  {}\n", digest, escaped)
+    } else {
+        format!("{}  {}\n", digest, path)
+    }
+}
+
+/// Reads the lines of a manifest in `sha256sum` format. Both the text
+/// (`digest  path`) and binary (`digest *path`) markers are accepted,
+/// blank lines and `#` comments are ignored, and a leading `./` is dropped
+/// so manifests made with `find . | xargs sha256sum` match too.
+pub fn parse(text: &str) -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let parsed = line.split_once(' ').and_then(|(digest, rest)| {
+            let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+            let valid = !digest.is_empty() && digest.chars().all(|c| c.is_ascii_hexdigit()) && !path.is_empty();
+            valid.then_some((digest, path))
+        });
+        let Some((digest, path)) = parsed else {
+            anyhow::bail!("Line {} is not a checksum line: {}", number + 1, line);
+        };
+        let path = if escaped { unescape(path, number + 1)? } else { path.to_string() };
+        let path = path.strip_prefix("./").map(str::to_string).unwrap_or(path);
+        entries.push(ManifestEntry {
+            digest: digest.to_ascii_lowercase(),
+            path,
+        });
+    }
+    Ok(entries)
+}
+
+fn unescape(path: &str, line: usize) -> Result<String> {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            _ => anyhow::bail!("Line {} has an invalid escape in its path", line),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// The algorithm a manifest was most likely made with: named in its file
+/// name as `SHA256SUMS`, `BLAKE3SUMS`, `b3sums`, or `XXH3SUMS` are, or
+/// failing that told apart by digest length, XXH3 being the only 32-digit
+/// one. SHA-256 and BLAKE3 digests are both 64 digits, so SHA-256 is
+/// assumed, as the more common.
+pub fn detect_algorithm(manifest: &Path, entries: &[ManifestEntry]) -> Algorithm {
+    let name = manifest
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name.contains("blake3") || name.contains("b3") {
+        Algorithm::Blake3
+    } else if name.contains("xxh") {
+        Algorithm::Xxh3
+    } else if name.contains("sha256") {
+        Algorithm::Sha256
+    } else if entries.first().is_some_and(|entry| entry.digest.len() == 32) {
+        Algorithm::Xxh3
+    } else {
+        Algorithm::Sha256
+    }
+}
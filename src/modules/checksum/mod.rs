@@ -0,0 +1,812 @@
+pub mod manifest;
+mod resume;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::{Input, Select};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::utils::{
+    atomic::AtomicFile,
+    create_spinner,
+    expand::expand_path,
+    filter::PathFilter,
+    get_directory_from_user,
+    hash_cache::HashCache,
+    hashing::{Algorithm, Hashing},
+    junk::JunkList,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    walk::{is_access_denied, Inaccessible, Walker},
+};
+
+use manifest::ManifestEntry;
+use resume::VerifiedSet;
+
+/// Writes `SHA256SUMS`-style manifests of a directory tree and checks a
+/// tree against one, so copies and archives can be shown to be intact
+pub struct ChecksumManager {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// `None` asks whether to generate or verify
+    operation: Option<ChecksumOperation>,
+    /// For generating, `None` asks; for verifying, `None` goes by the
+    /// manifest
+    algorithm: Option<Algorithm>,
+    /// Manifest to write, or to verify against
+    manifest: Option<PathBuf>,
+    /// Check every entry again instead of resuming an interrupted run
+    fresh: bool,
+    /// Cover git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be covered
+    include: Vec<String>,
+    /// Glob patterns that leave a file out
+    exclude: Vec<String>,
+    /// Reuse and keep digests of unchanged files between runs
+    hash_cache: bool,
+    /// Abort on the first file that cannot be read
+    fail_fast: bool,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ChecksumOperation {
+    Generate,
+    Verify,
+}
+
+/// Everything writing a manifest needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerateOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Cover git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Known junk, left out of the manifest
+    #[serde(skip)]
+    pub junk: JunkList,
+    pub algorithm: Algorithm,
+    /// Where the manifest goes; `None` writes `<ALGORITHM>SUMS` into the
+    /// input directory
+    pub output: Option<PathBuf>,
+    /// Where digests of unchanged files are kept between runs, shared with
+    /// deduplication; `None` reads every file
+    pub hash_cache: Option<PathBuf>,
+    /// Abort on the first file that cannot be read instead of leaving it
+    /// out and carrying on
+    pub fail_fast: bool,
+}
+
+impl GenerateOptions {
+    /// A recursive SHA-256 manifest of `input_dir`, written into it
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            algorithm: Algorithm::Sha256,
+            output: None,
+            hash_cache: None,
+            fail_fast: false,
+        }
+    }
+
+    /// The manifest path this run writes
+    pub fn manifest_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.input_dir.join(manifest::file_name(self.algorithm)))
+    }
+}
+
+/// Everything checking a tree against a manifest needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyOptions {
+    pub manifest: PathBuf,
+    /// Directory the manifest's paths are relative to; `None` is the
+    /// directory the manifest is in
+    pub root: Option<PathBuf>,
+    /// `None` goes by the manifest's file name and digest length
+    pub algorithm: Option<Algorithm>,
+    /// Whether files in subdirectories not in the manifest count as extra
+    pub recursive: bool,
+    /// Look for extra files in working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs limiting where extra files are looked for
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Known junk, never reported as extra
+    #[serde(skip)]
+    pub junk: JunkList,
+    /// Skip entries an interrupted run already verified, as long as the
+    /// manifest and those files are unchanged since
+    pub resume: bool,
+    /// Where digests are kept for later runs, shared with deduplication.
+    /// Verification itself always reads the files.
+    pub hash_cache: Option<PathBuf>,
+    /// Abort on the first file that cannot be read instead of recording it
+    /// and carrying on
+    pub fail_fast: bool,
+}
+
+impl VerifyOptions {
+    /// Checks the tree around `manifest`, resuming an interrupted run
+    pub fn new(manifest: PathBuf) -> Self {
+        Self {
+            manifest,
+            root: None,
+            algorithm: None,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            resume: true,
+            hash_cache: None,
+            fail_fast: false,
+        }
+    }
+
+    /// The directory the manifest's paths are resolved against
+    pub fn root(&self) -> PathBuf {
+        self.root.clone().unwrap_or_else(|| match self.manifest.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        })
+    }
+}
+
+/// What a [`ChecksumManager`] run does
+#[derive(Debug, Clone, Serialize)]
+pub enum ChecksumJob {
+    Generate(GenerateOptions),
+    Verify(VerifyOptions),
+}
+
+/// Result of [`generate`]
+#[derive(Debug, Default, Serialize)]
+pub struct GenerateSummary {
+    pub manifest: PathBuf,
+    /// Files listed in the manifest
+    pub files: usize,
+    pub bytes: u64,
+    /// Files whose digest came from the hash cache instead of being read
+    pub cached: usize,
+    /// Files left out because their path is not valid UTF-8
+    pub unlisted: Vec<PathBuf>,
+    pub junk: usize,
+    /// Working copies that were left out
+    pub repositories: Vec<PathBuf>,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&GenerateSummary> for Summary {
+    fn from(summary: &GenerateSummary) -> Self {
+        Summary {
+            files: summary.files,
+            bytes: summary.bytes,
+            skipped: summary.unlisted.len(),
+            junk: summary.junk,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// A file whose contents no longer match its manifest entry
+#[derive(Debug, Clone, Serialize)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Result of [`verify`]
+#[derive(Debug, Default, Serialize)]
+pub struct VerifySummary {
+    pub algorithm: Option<Algorithm>,
+    /// Entries read and found intact in this run
+    pub verified: usize,
+    pub bytes: u64,
+    /// Entries an interrupted run had already found intact, skipped
+    pub resumed: usize,
+    /// Listed in the manifest but not on disk
+    pub missing: Vec<PathBuf>,
+    pub mismatched: Vec<Mismatch>,
+    /// On disk but not in the manifest
+    pub extra: Vec<PathBuf>,
+    /// Working copies not searched for extra files
+    pub repositories: Vec<PathBuf>,
+    /// Entries that could not be read
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl VerifySummary {
+    /// Whether every entry is present and intact, and nothing was added
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty()
+            && self.mismatched.is_empty()
+            && self.extra.is_empty()
+            && self.errors.is_empty()
+            && self.inaccessible.is_empty()
+    }
+}
+
+/// Every problem counts as failed, so a run that finds any exits non-zero
+impl From<&VerifySummary> for Summary {
+    fn from(summary: &VerifySummary) -> Self {
+        Summary {
+            files: summary.verified + summary.resumed,
+            bytes: summary.bytes,
+            skipped: summary.resumed,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.missing.len()
+                + summary.mismatched.len()
+                + summary.extra.len()
+                + summary.errors.len()
+                + summary.inaccessible.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Hashes every file under the input directory in parallel and writes a
+/// manifest that `sha256sum -c` (or `b3sum -c`) can check, with paths
+/// relative to the input directory and sorted. The manifest itself, and
+/// any progress file of an interrupted verification, are left out.
+///
+/// ```
+/// use file_organizer_rust::modules::checksum::{self, GenerateOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir(dir.path().join("docs"))?;
+/// std::fs::write(dir.path().join("docs/notes.txt"), "hello")?;
+///
+/// let summary = checksum::generate(&GenerateOptions::new(dir.path().to_path_buf()), ())?;
+/// assert_eq!(summary.files, 1);
+/// assert_eq!(
+///     std::fs::read_to_string(dir.path().join("SHA256SUMS"))?,
+///     "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  docs/notes.txt\n"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn generate(options: &GenerateOptions, progress: impl ProgressSink) -> Result<GenerateSummary> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    let manifest_path = options.manifest_path();
+    let own_files = own_files(&manifest_path);
+
+    progress.scan_started(root);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .sorted(true)
+        .files(root);
+    let mut summary = GenerateSummary {
+        manifest: manifest_path.clone(),
+        junk: walk.junk.len(),
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..GenerateSummary::default()
+    };
+
+    let mut files = Vec::new();
+    for entry in walk.files {
+        if own_files.iter().any(|own| same_path(own, &entry.path)) {
+            continue;
+        }
+        match manifest::relative_path(root, &entry.path) {
+            Some(relative) => files.push((relative, entry)),
+            None => summary.unlisted.push(entry.path),
+        }
+    }
+
+    progress.phase_changed("hashing", files.len() as u64);
+    let hashing = Hashing::new(options.algorithm);
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    let errors = ErrorLog::new(options.fail_fast);
+    let inaccessible = std::sync::Mutex::new(Vec::new());
+    let digests = files
+        .par_iter()
+        .map(|(relative, entry)| {
+            progress.item_started(&entry.path);
+            let (digest, outcome) = match cache.digest(&hashing, &entry.path, &entry.metadata) {
+                Err(e) if is_access_denied(&e) => {
+                    inaccessible.lock().unwrap().push(Inaccessible::new(&entry.path, &e));
+                    (None, Outcome::Skipped)
+                }
+                result => match errors.check(&entry.path, "hash", result)? {
+                    Some(digest) => (Some(digest), Outcome::Done),
+                    None => (None, Outcome::Failed),
+                },
+            };
+            progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+            Ok(digest.map(|digest| (relative, digest, entry.metadata.len())))
+        })
+        .collect::<Result<Vec<_>>>();
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+    let digests = digests?;
+
+    let file = AtomicFile::create(&manifest_path)
+        .with_context(|| format!("Failed to create {}", manifest_path.display()))?;
+    let mut writer = BufWriter::new(file);
+    for (relative, (digest, from_cache), bytes) in digests.into_iter().flatten() {
+        writer.write_all(manifest::format_line(&digest, relative).as_bytes())?;
+        summary.files += 1;
+        summary.bytes += bytes;
+        summary.cached += usize::from(from_cache);
+    }
+    writer.into_inner()?.commit()?;
+
+    summary.errors = errors.into_errors();
+    summary.inaccessible.extend(inaccessible.into_inner().unwrap());
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+/// Reads every file listed in the manifest in parallel and compares its
+/// digest, then walks the tree for files the manifest does not list.
+/// Entries that pass are noted in a progress file next to the manifest
+/// while the run lasts, so a run that is interrupted skips them when
+/// started again, and the file is removed once every entry was checked.
+///
+/// ```
+/// use file_organizer_rust::modules::checksum::{self, GenerateOptions, VerifyOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("a.txt"), "one")?;
+/// std::fs::write(dir.path().join("b.txt"), "two")?;
+/// let manifest = checksum::generate(&GenerateOptions::new(dir.path().to_path_buf()), ())?.manifest;
+///
+/// std::fs::write(dir.path().join("a.txt"), "changed")?;
+/// std::fs::remove_file(dir.path().join("b.txt"))?;
+/// std::fs::write(dir.path().join("c.txt"), "three")?;
+///
+/// let summary = checksum::verify(&VerifyOptions::new(manifest), ())?;
+/// assert_eq!(summary.mismatched[0].path, dir.path().join("a.txt"));
+/// assert_eq!(summary.missing, [dir.path().join("b.txt")]);
+/// assert_eq!(summary.extra, [dir.path().join("c.txt")]);
+/// assert!(!summary.passed());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn verify(options: &VerifyOptions, progress: impl ProgressSink) -> Result<VerifySummary> {
+    let text = fs::read_to_string(&options.manifest)
+        .with_context(|| format!("Failed to read {}", options.manifest.display()))?;
+    let entries = manifest::parse(&text).with_context(|| format!("Invalid manifest {}", options.manifest.display()))?;
+    let algorithm = options
+        .algorithm
+        .unwrap_or_else(|| manifest::detect_algorithm(&options.manifest, &entries));
+    let expected_len = if algorithm == Algorithm::Xxh3 { 32 } else { 64 };
+    if let Some(entry) = entries.iter().find(|entry| entry.digest.len() != expected_len) {
+        anyhow::bail!(
+            "{} is not a {} digest ({} in {})",
+            entry.digest,
+            algorithm.name(),
+            entry.path,
+            options.manifest.display()
+        );
+    }
+    let root = options.root();
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+
+    // A path listed twice is checked once
+    let mut listed = HashSet::new();
+    let entries: Vec<ManifestEntry> = entries
+        .into_iter()
+        .filter(|entry| listed.insert(entry.path.clone()))
+        .collect();
+
+    let verified = VerifiedSet::open(&options.manifest, &fs::metadata(&options.manifest)?, options.resume);
+    let hashing = Hashing::new(algorithm);
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    let errors = ErrorLog::new(options.fail_fast);
+
+    progress.phase_changed("verifying", entries.len() as u64);
+    let checks = entries
+        .par_iter()
+        .map(|entry| {
+            let path = root.join(&entry.path);
+            progress.item_started(&path);
+            let check = check_entry(entry, &path, &hashing, &cache, &verified);
+            let (check, outcome) = match check {
+                Err(e) if is_access_denied(&e) => (Check::Inaccessible(Inaccessible::new(&path, &e)), Outcome::Skipped),
+                result => match errors.check(&path, "verify", result)? {
+                    Some(Check::Mismatch(actual)) => (Check::Mismatch(actual), Outcome::Failed),
+                    Some(Check::Missing) => (Check::Missing, Outcome::Failed),
+                    Some(check) => (check, Outcome::Done),
+                    None => (Check::Failed, Outcome::Failed),
+                },
+            };
+            let bytes = match check {
+                Check::Intact(bytes) => bytes,
+                _ => 0,
+            };
+            progress.item_finished(&path, bytes, outcome);
+            Ok((path, entry, check))
+        })
+        .collect::<Result<Vec<_>>>();
+    let _ = cache.save();
+    let checks = match checks {
+        Ok(checks) => checks,
+        Err(e) => {
+            let _ = verified.save();
+            return Err(e);
+        }
+    };
+
+    let mut summary = VerifySummary {
+        algorithm: Some(algorithm),
+        ..VerifySummary::default()
+    };
+    for (path, entry, check) in checks {
+        match check {
+            Check::Intact(bytes) => {
+                summary.verified += 1;
+                summary.bytes += bytes;
+            }
+            Check::Resumed => summary.resumed += 1,
+            Check::Missing => summary.missing.push(path),
+            Check::Mismatch(actual) => summary.mismatched.push(Mismatch {
+                path,
+                expected: entry.digest.clone(),
+                actual,
+            }),
+            Check::Inaccessible(inaccessible) => summary.inaccessible.push(inaccessible),
+            Check::Failed => {}
+        }
+    }
+
+    progress.scan_started(&root);
+    let own_files = own_files(&options.manifest);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .sorted(true)
+        .files(&root);
+    summary.repositories = walk.repositories;
+    summary.inaccessible.extend(walk.inaccessible);
+    summary.extra = walk
+        .files
+        .into_iter()
+        .map(|entry| entry.path)
+        .filter(|path| !own_files.iter().any(|own| same_path(own, path)))
+        .filter(|path| manifest::relative_path(&root, path).is_none_or(|relative| !listed.contains(&relative)))
+        .collect();
+
+    summary.errors = errors.into_errors();
+    verified.finish()?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+/// How one manifest entry fared
+enum Check {
+    Intact(u64),
+    Resumed,
+    Missing,
+    Mismatch(String),
+    Inaccessible(Inaccessible),
+    /// Recorded in the error log
+    Failed,
+}
+
+fn check_entry(
+    entry: &ManifestEntry,
+    path: &Path,
+    hashing: &Hashing,
+    cache: &HashCache,
+    verified: &VerifiedSet,
+) -> Result<Check> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Check::Missing),
+        Ok(_) => return Ok(Check::Missing),
+        Err(e) => return Err(e.into()),
+    };
+    if verified.contains(&entry.path, &metadata) {
+        return Ok(Check::Resumed);
+    }
+    // Read in full even when the cache knows the file: what is checked is
+    // the bytes on disk, not what they were when last hashed
+    let digest = hashing.digest_file(path)?;
+    cache.record(hashing.algorithm, path, &metadata, &digest);
+    if digest != entry.digest {
+        return Ok(Check::Mismatch(digest));
+    }
+    verified.insert(&entry.path, &metadata);
+    Ok(Check::Intact(metadata.len()))
+}
+
+/// The manifest and its verification progress file, which never list
+/// themselves
+fn own_files(manifest: &Path) -> [PathBuf; 2] {
+    [manifest.to_path_buf(), VerifiedSet::path_for(manifest)]
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    a == b || std::path::absolute(a).ok().zip(std::path::absolute(b).ok()).is_some_and(|(a, b)| a == b)
+}
+
+#[async_trait]
+impl FileOrganizer for ChecksumManager {
+    type Plan = ChecksumJob;
+    const NAME: &'static str = "checksum";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            operation: None,
+            algorithm: None,
+            manifest: None,
+            fresh: false,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            hash_cache: true,
+            fail_fast: false,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.operation.is_none() {
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select operation")
+                .items(&["Generate a checksum manifest", "Verify against a checksum manifest"])
+                .default(0)
+                .interact()?;
+            self.operation = Some([ChecksumOperation::Generate, ChecksumOperation::Verify][choice]);
+        }
+        match self.operation {
+            Some(ChecksumOperation::Generate) => {
+                if self.input_dir.is_none() {
+                    self.input_dir = Some(get_directory_from_user("Enter directory to checksum")?);
+                }
+                if self.algorithm.is_none() {
+                    let choice = Select::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Select checksum algorithm")
+                        .items(&["SHA-256 (checkable with sha256sum)", "BLAKE3 (faster, checkable with b3sum)", "XXH3 (fastest, not tamper-proof)"])
+                        .default(0)
+                        .interact()?;
+                    self.algorithm = Some([Algorithm::Sha256, Algorithm::Blake3, Algorithm::Xxh3][choice]);
+                }
+            }
+            _ => {
+                if self.manifest.is_none() {
+                    let typed: String = Input::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Enter manifest to verify")
+                        .interact_text()?;
+                    let manifest = expand_path(typed.trim())?;
+                    if !manifest.is_file() {
+                        anyhow::bail!("Manifest does not exist: {}", manifest.display());
+                    }
+                    self.manifest = Some(manifest);
+                }
+            }
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<ChecksumJob> {
+        self.job()
+    }
+
+    fn execute(&self, job: ChecksumJob) -> Result<Summary> {
+        match job {
+            ChecksumJob::Generate(options) => self.generate(&options),
+            ChecksumJob::Verify(options) => self.verify(&options),
+        }
+    }
+}
+
+impl ChecksumManager {
+    /// Presets the directory to checksum, or for verifying the directory
+    /// the manifest's paths are relative to, so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Writes a manifest without asking, to `output` or into the input
+    /// directory
+    pub fn with_generate(mut self, output: Option<PathBuf>) -> Self {
+        self.operation = Some(ChecksumOperation::Generate);
+        self.manifest = output;
+        self
+    }
+
+    /// Verifies against `manifest` without asking
+    pub fn with_verify(mut self, manifest: PathBuf) -> Self {
+        self.operation = Some(ChecksumOperation::Verify);
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Presets the algorithm so the prompt is skipped; for verifying, it
+    /// overrides the one guessed from the manifest
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Checks every entry again, ignoring what an interrupted run verified
+    pub fn with_fresh(mut self, fresh: bool) -> Self {
+        self.fresh = fresh;
+        self
+    }
+
+    /// Covers working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only covers files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.mkv" or "photos/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` out
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Whether digests of unchanged files are reused from earlier runs and
+    /// kept for later ones
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Aborts on the first file that cannot be read
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    fn generate(&self, options: &GenerateOptions) -> Result<Summary> {
+        let spinner = create_spinner("Hashing files...");
+        self.metrics.phase("hashing");
+        let summary = generate(options, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!(
+            "Wrote {} ({} files, {} from the hash cache)",
+            summary.manifest.display(),
+            summary.files,
+            summary.cached
+        ));
+        if !summary.unlisted.is_empty() {
+            println!("{} files were left out as their names are not valid UTF-8:", summary.unlisted.len());
+            for path in &summary.unlisted {
+                println!("  {}", path.display());
+            }
+        }
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to cover them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "the manifest leaves them out");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+
+    fn verify(&self, options: &VerifyOptions) -> Result<Summary> {
+        let spinner = create_spinner("Verifying files...");
+        self.metrics.phase("verifying");
+        let summary = verify(options, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+
+        let root = options.root();
+        let relative = |path: &Path| path.strip_prefix(&root).unwrap_or(path).display().to_string();
+        for mismatch in &summary.mismatched {
+            println!("MISMATCH {}", relative(&mismatch.path));
+            println!("  expected {}", mismatch.expected);
+            println!("  actual   {}", mismatch.actual);
+        }
+        for path in &summary.missing {
+            println!("MISSING  {}", relative(path));
+        }
+        for path in &summary.extra {
+            println!("EXTRA    {}", relative(path));
+        }
+        print_inaccessible(&summary.inaccessible, "they were not verified");
+        print_errors(&summary.errors);
+
+        let algorithm = summary.algorithm.map(Algorithm::name).unwrap_or_default();
+        if summary.resumed > 0 {
+            println!("{} files verified earlier were skipped (use --fresh to check them again)", summary.resumed);
+        }
+        if summary.passed() {
+            println!("OK: {} files intact ({})", summary.verified + summary.resumed, algorithm);
+        } else {
+            println!(
+                "FAILED: {} intact, {} mismatched, {} missing, {} extra, {} unreadable ({})",
+                summary.verified + summary.resumed,
+                summary.mismatched.len(),
+                summary.missing.len(),
+                summary.extra.len(),
+                summary.errors.len() + summary.inaccessible.len(),
+                algorithm
+            );
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+
+    /// The configured settings as a library job
+    fn job(&self) -> Result<ChecksumJob> {
+        let filter = PathFilter::new(&self.include, &self.exclude)?;
+        let hash_cache = if self.hash_cache { Some(HashCache::default_path()?) } else { None };
+        match self.operation {
+            Some(ChecksumOperation::Verify) => {
+                let manifest = self.manifest.clone().ok_or_else(|| anyhow::anyhow!("Manifest not set"))?;
+                Ok(ChecksumJob::Verify(VerifyOptions {
+                    root: self.input_dir.clone(),
+                    algorithm: self.algorithm,
+                    recursive: self.recursive,
+                    include_repos: self.include_repos,
+                    filter,
+                    junk: self.junk.clone(),
+                    resume: !self.fresh,
+                    hash_cache,
+                    fail_fast: self.fail_fast,
+                    ..VerifyOptions::new(manifest)
+                }))
+            }
+            _ => {
+                let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+                Ok(ChecksumJob::Generate(GenerateOptions {
+                    recursive: self.recursive,
+                    include_repos: self.include_repos,
+                    filter,
+                    junk: self.junk.clone(),
+                    algorithm: self.algorithm.unwrap_or_default(),
+                    output: self.manifest.clone(),
+                    hash_cache,
+                    fail_fast: self.fail_fast,
+                    ..GenerateOptions::new(input_dir)
+                }))
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::utils::atomic::write_atomic;
+
+/// How often an ongoing verification saves which entries have passed
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Entries of a manifest that already passed verification, kept next to
+/// the manifest as `<manifest>.verified` while a run is underway so an
+/// interrupted run can pick up where it stopped. Only valid for the exact
+/// manifest it was made for, and an entry only for a file that has kept
+/// its size and modification time since.
+#[derive(Debug)]
+pub struct VerifiedSet {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    manifest: Stamp,
+    entries: HashMap<String, Stamp>,
+    #[serde(skip)]
+    unsaved: usize,
+    #[serde(skip)]
+    saved_at: Option<Instant>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Stamp {
+    size: u64,
+    mtime: i64,
+    mtime_nanos: u32,
+}
+
+impl Stamp {
+    fn of(metadata: &Metadata) -> Self {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        Self {
+            size: metadata.len(),
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+        }
+    }
+}
+
+impl VerifiedSet {
+    /// Progress file kept for `manifest`
+    pub fn path_for(manifest: &Path) -> PathBuf {
+        let mut name = manifest.file_name().unwrap_or_default().to_os_string();
+        name.push(".verified");
+        manifest.with_file_name(name)
+    }
+
+    /// Picks up the progress saved for `manifest`, whose current metadata
+    /// is `manifest_metadata`, when `resume` is set. Progress made for an
+    /// earlier version of the manifest is dropped.
+    pub fn open(manifest: &Path, manifest_metadata: &Metadata, resume: bool) -> Self {
+        let path = Self::path_for(manifest);
+        let stamp = Stamp::of(manifest_metadata);
+        let state = resume
+            .then(|| fs::read_to_string(&path).ok())
+            .flatten()
+            .and_then(|text| serde_json::from_str::<State>(&text).ok())
+            .filter(|state| state.manifest == stamp)
+            .unwrap_or_else(|| State {
+                manifest: stamp,
+                ..State::default()
+            });
+        Self {
+            path,
+            state: Mutex::new(State {
+                saved_at: Some(Instant::now()),
+                ..state
+            }),
+        }
+    }
+
+    /// Whether the entry for `path` passed in an earlier run and the file,
+    /// with current metadata `metadata`, is unchanged since
+    pub fn contains(&self, path: &str, metadata: &Metadata) -> bool {
+        self.state.lock().unwrap().entries.get(path) == Some(&Stamp::of(metadata))
+    }
+
+    /// Remembers that the entry for `path` passed, saving every so often
+    pub fn insert(&self, path: &str, metadata: &Metadata) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.insert(path.to_string(), Stamp::of(metadata));
+        state.unsaved += 1;
+        if state.saved_at.is_none_or(|at| at.elapsed() >= SAVE_INTERVAL) {
+            // Losing progress only costs a re-read, so a failed save is
+            // left for the next interval
+            if write_atomic(&self.path, serde_json::to_string(&*state).unwrap_or_default()).is_ok() {
+                state.unsaved = 0;
+                state.saved_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Saves what has passed so far, for a run that stops before checking
+    /// every entry
+    pub fn save(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.unsaved > 0 {
+            write_atomic(&self.path, serde_json::to_string(&*state)?)?;
+            state.unsaved = 0;
+            state.saved_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Removes the progress file once every entry has been checked
+    pub fn finish(self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
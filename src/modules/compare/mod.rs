@@ -0,0 +1,696 @@
+pub mod report;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::utils::prompt::Select;
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::directory_sync::same_mtime;
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    hash_cache::HashCache,
+    hashing::{Algorithm, Hashing},
+    junk::JunkList,
+    links,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    walk::{is_access_denied, Inaccessible, WalkEntry, Walker},
+};
+
+/// Compares two directory trees and reports the files only one of them
+/// has and the files both have that differ, e.g. to check a sync, an
+/// extraction, or a categorization in copy mode
+pub struct TreeComparer {
+    recursive: bool,
+    left: Option<PathBuf>,
+    right: Option<PathBuf>,
+    /// Hash files of equal size instead of comparing modification times;
+    /// `None` asks
+    deep: Option<bool>,
+    algorithm: Algorithm,
+    /// Count files of equal size as the same in a quick comparison
+    ignore_mtime: bool,
+    /// Walk into symlinked directories and compare linked files
+    follow_links: bool,
+    /// Compare git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be compared
+    include: Vec<String>,
+    /// Glob patterns that leave a file out on both sides
+    exclude: Vec<String>,
+    /// Reuse and keep digests of unchanged files between runs
+    hash_cache: bool,
+    /// Abort on the first file that cannot be read
+    fail_fast: bool,
+    /// `None` asks after the run; `Some(None)` only prints the tree
+    report: Option<Option<ReportFormat>>,
+    /// Where a CSV or JSON report is written; without one a preset report
+    /// is printed instead of the tree
+    report_path: Option<PathBuf>,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// Everything a comparison needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct CompareOptions {
+    /// The first tree, A
+    pub left: PathBuf,
+    /// The second tree, B
+    pub right: PathBuf,
+    pub recursive: bool,
+    /// Compare working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to each root
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Known junk, left out on both sides
+    #[serde(skip)]
+    pub junk: JunkList,
+    /// Hash files of equal size instead of comparing modification times
+    pub deep: bool,
+    pub algorithm: Algorithm,
+    /// In a quick comparison, count files of equal size as the same
+    /// whatever their modification times
+    pub ignore_mtime: bool,
+    /// Walk into symlinked directories and compare the files links point
+    /// to; otherwise links are left out
+    pub follow_links: bool,
+    /// Where digests of unchanged files are kept between runs, shared with
+    /// deduplication and checksums; `None` reads every file
+    pub hash_cache: Option<PathBuf>,
+    /// Abort on the first file that cannot be read instead of recording it
+    /// and carrying on
+    pub fail_fast: bool,
+}
+
+impl CompareOptions {
+    /// A recursive quick comparison of `left` and `right`
+    pub fn new(left: PathBuf, right: PathBuf) -> Self {
+        Self {
+            left,
+            right,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            deep: false,
+            algorithm: Algorithm::Sha256,
+            ignore_mtime: false,
+            follow_links: false,
+            hash_cache: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// How a file differs between the two trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    OnlyLeft,
+    OnlyRight,
+    Size,
+    /// Same size, modification times more than two seconds apart
+    Modified,
+    /// Same size, different digests
+    Content,
+}
+
+impl DiffKind {
+    /// As written in CSV reports
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::OnlyLeft => "only_left",
+            Self::OnlyRight => "only_right",
+            Self::Size => "size",
+            Self::Modified => "modified",
+            Self::Content => "content",
+        }
+    }
+
+    /// `-` for files only A has, `+` for files only B has, `~` for files
+    /// that differ
+    fn marker(self) -> char {
+        match self {
+            Self::OnlyLeft => '-',
+            Self::OnlyRight => '+',
+            _ => '~',
+        }
+    }
+}
+
+/// One side of a file as the comparison saw it
+#[derive(Debug, Clone, Serialize)]
+pub struct FileState {
+    pub size: u64,
+    /// Local time, `YYYY-MM-DD HH:MM:SS`
+    pub modified: String,
+    /// Only known for files a deep comparison found to differ
+    pub digest: Option<String>,
+}
+
+impl FileState {
+    fn new(metadata: &Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .map(|time| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            digest: None,
+        }
+    }
+}
+
+/// A file one tree lacks or both have with differences
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeDiff {
+    /// Relative to both roots
+    pub path: PathBuf,
+    pub kind: DiffKind,
+    pub left: Option<FileState>,
+    pub right: Option<FileState>,
+}
+
+impl TreeDiff {
+    fn new(path: PathBuf, kind: DiffKind, left: Option<&Metadata>, right: Option<&Metadata>) -> Self {
+        Self {
+            path,
+            kind,
+            left: left.map(FileState::new),
+            right: right.map(FileState::new),
+        }
+    }
+
+    /// What changed between the sides, e.g. ` (12 KiB → 14 KiB)`
+    fn detail(&self) -> String {
+        let (Some(left), Some(right)) = (&self.left, &self.right) else {
+            return String::new();
+        };
+        match self.kind {
+            DiffKind::Size => format!(" ({} → {})", format_size(left.size, BINARY), format_size(right.size, BINARY)),
+            DiffKind::Modified => format!(" (modified {} → {})", left.modified, right.modified),
+            DiffKind::Content => " (contents differ)".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Result of [`compare`]
+#[derive(Debug, Default, Serialize)]
+pub struct CompareReport {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub deep: bool,
+    /// Sorted by path
+    pub differences: Vec<TreeDiff>,
+    /// Files both trees have and that match
+    pub identical: usize,
+    /// Bytes in the identical files, counted once
+    pub bytes: u64,
+    /// Digests that came from the hash cache instead of being read
+    pub cached: usize,
+    pub junk: usize,
+    /// Working copies left out on either side
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be hashed
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl CompareReport {
+    /// Whether the trees hold the same files and every one could be
+    /// compared
+    pub fn identical_trees(&self) -> bool {
+        self.differences.is_empty() && self.errors.is_empty() && self.inaccessible.is_empty()
+    }
+
+    /// Differences of the given kinds
+    pub fn count(&self, kinds: &[DiffKind]) -> usize {
+        self.differences.iter().filter(|diff| kinds.contains(&diff.kind)).count()
+    }
+
+    /// Prints the differences as an indented tree, each directory once
+    pub fn print_tree(&self) {
+        println!("- only in {}", self.left.display());
+        println!("+ only in {}", self.right.display());
+        println!("~ in both, but different");
+        println!();
+        let mut open: Vec<&OsStr> = Vec::new();
+        for diff in &self.differences {
+            let dirs: Vec<&OsStr> = diff.path.parent().map(|dir| dir.iter().collect()).unwrap_or_default();
+            let common = open.iter().zip(&dirs).take_while(|(a, b)| a == b).count();
+            for (depth, dir) in dirs.iter().enumerate().skip(common) {
+                println!("{}{}/", "  ".repeat(depth), dir.to_string_lossy());
+            }
+            println!(
+                "{}{} {}{}",
+                "  ".repeat(dirs.len()),
+                diff.kind.marker(),
+                diff.path.file_name().unwrap_or_default().to_string_lossy(),
+                diff.detail()
+            );
+            open = dirs;
+        }
+    }
+}
+
+/// Every difference and every file that could not be compared counts as
+/// failed, so a run that finds any exits non-zero
+impl From<&CompareReport> for Summary {
+    fn from(report: &CompareReport) -> Self {
+        Summary {
+            files: report.identical + report.differences.len(),
+            bytes: report.bytes,
+            skipped: 0,
+            junk: report.junk,
+            inaccessible: report.inaccessible.len(),
+            failed: report.differences.len() + report.errors.len() + report.inaccessible.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Walks both trees and matches their files by relative path. Files of
+/// different sizes differ. Files of equal size differ when their
+/// modification times are more than two seconds apart, unless
+/// `ignore_mtime` is set, or in a deep comparison when their digests
+/// differ; those are hashed in parallel, through the hash cache. When one
+/// root is inside the other, it is left out of the outer walk.
+///
+/// ```
+/// use file_organizer_rust::modules::compare::{self, CompareOptions, DiffKind};
+///
+/// let a = tempfile::tempdir()?;
+/// let b = tempfile::tempdir()?;
+/// std::fs::create_dir(a.path().join("docs"))?;
+/// std::fs::create_dir(b.path().join("docs"))?;
+/// std::fs::write(a.path().join("docs/same.txt"), "same")?;
+/// std::fs::write(b.path().join("docs/same.txt"), "same")?;
+/// std::fs::write(a.path().join("docs/edited.txt"), "draft")?;
+/// std::fs::write(b.path().join("docs/edited.txt"), "final")?;
+/// std::fs::write(a.path().join("old.txt"), "old")?;
+/// std::fs::write(b.path().join("new.txt"), "new")?;
+///
+/// let options = CompareOptions {
+///     deep: true,
+///     ..CompareOptions::new(a.path().to_path_buf(), b.path().to_path_buf())
+/// };
+/// let report = compare::compare(&options, ())?;
+/// let found: Vec<_> = report.differences.iter().map(|diff| (diff.path.to_str().unwrap(), diff.kind)).collect();
+/// assert_eq!(
+///     found,
+///     [("docs/edited.txt", DiffKind::Content), ("new.txt", DiffKind::OnlyRight), ("old.txt", DiffKind::OnlyLeft)]
+/// );
+/// assert_eq!(report.identical, 1);
+/// assert!(!report.identical_trees());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn compare(options: &CompareOptions, progress: impl ProgressSink) -> Result<CompareReport> {
+    for root in [&options.left, &options.right] {
+        if !root.is_dir() {
+            anyhow::bail!("Not a directory: {}", root.display());
+        }
+    }
+    let (left_abs, right_abs) = (links::absolute(&options.left)?, links::absolute(&options.right)?);
+    if left_abs == right_abs {
+        anyhow::bail!("Both sides are the same directory: {}", left_abs.display());
+    }
+
+    let walker = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .follow_links(options.follow_links)
+        .symlinks(options.follow_links)
+        .sorted(true);
+    let mut report = CompareReport {
+        left: options.left.clone(),
+        right: options.right.clone(),
+        deep: options.deep,
+        ..CompareReport::default()
+    };
+    let left = scan(&walker, &options.left, &left_abs, &right_abs, &progress, &mut report);
+    let mut right = scan(&walker, &options.right, &right_abs, &left_abs, &progress, &mut report);
+
+    let mut pairs = Vec::new();
+    for (relative, left) in left {
+        match right.remove(&relative) {
+            None => report
+                .differences
+                .push(TreeDiff::new(relative, DiffKind::OnlyLeft, Some(&left.metadata), None)),
+            Some(right) if left.metadata.len() != right.metadata.len() => report.differences.push(TreeDiff::new(
+                relative,
+                DiffKind::Size,
+                Some(&left.metadata),
+                Some(&right.metadata),
+            )),
+            Some(right) if options.deep => pairs.push((relative, left, right)),
+            Some(right) if !options.ignore_mtime && !same_mtime(&left.metadata, &right.metadata) => {
+                report.differences.push(TreeDiff::new(
+                    relative,
+                    DiffKind::Modified,
+                    Some(&left.metadata),
+                    Some(&right.metadata),
+                ))
+            }
+            Some(_) => {
+                report.identical += 1;
+                report.bytes += left.metadata.len();
+            }
+        }
+    }
+    for (relative, right) in right {
+        report
+            .differences
+            .push(TreeDiff::new(relative, DiffKind::OnlyRight, None, Some(&right.metadata)));
+    }
+
+    if !pairs.is_empty() {
+        hash_pairs(options, pairs, &progress, &mut report)?;
+    }
+    report.differences.sort_by(|a, b| a.path.cmp(&b.path));
+    progress.finished(&Summary::from(&report));
+    Ok(report)
+}
+
+/// The files under `root` by relative path, leaving out `other` if it is
+/// inside `root`
+fn scan(
+    walker: &Walker,
+    root: &Path,
+    root_abs: &Path,
+    other_abs: &Path,
+    progress: &impl ProgressSink,
+    report: &mut CompareReport,
+) -> BTreeMap<PathBuf, WalkEntry> {
+    let nested = other_abs.strip_prefix(root_abs).ok().map(|relative| root.join(relative));
+    progress.scan_started(root);
+    let walk = walker.clone().exclude(nested.into_iter().collect()).files(root);
+    report.junk += walk.junk.len();
+    report.repositories.extend(walk.repositories);
+    report.inaccessible.extend(walk.inaccessible);
+    walk.files
+        .into_iter()
+        .filter_map(|entry| Some((entry.path.strip_prefix(root).ok()?.to_path_buf(), entry)))
+        .collect()
+}
+
+/// Hashes both sides of each pair of equally sized files in parallel and
+/// records whether they match
+fn hash_pairs(
+    options: &CompareOptions,
+    pairs: Vec<(PathBuf, WalkEntry, WalkEntry)>,
+    progress: &impl ProgressSink,
+    report: &mut CompareReport,
+) -> Result<()> {
+    progress.phase_changed("hashing", pairs.len() as u64);
+    let hashing = Hashing::new(options.algorithm);
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    let errors = ErrorLog::new(options.fail_fast);
+    let inaccessible = Mutex::new(Vec::new());
+    let digest = |entry: &WalkEntry| match cache.digest(&hashing, &entry.path, &entry.metadata) {
+        Err(e) if is_access_denied(&e) => {
+            inaccessible.lock().unwrap().push(Inaccessible::new(&entry.path, &e));
+            Ok(None)
+        }
+        result => errors.check(&entry.path, "hash", result),
+    };
+    let hashed = pairs
+        .into_par_iter()
+        .map(|(relative, left, right)| {
+            progress.item_started(&left.path);
+            let digests = match (digest(&left)?, digest(&right)?) {
+                (Some(a), Some(b)) => Some((a, b)),
+                _ => None,
+            };
+            let outcome = if digests.is_some() { Outcome::Done } else { Outcome::Failed };
+            progress.item_finished(&left.path, left.metadata.len(), outcome);
+            Ok(digests.map(|digests| (relative, left, right, digests)))
+        })
+        .collect::<Result<Vec<_>>>();
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+
+    for (relative, left, right, ((a, a_cached), (b, b_cached))) in hashed?.into_iter().flatten() {
+        report.cached += usize::from(a_cached) + usize::from(b_cached);
+        if a == b {
+            report.identical += 1;
+            report.bytes += left.metadata.len();
+        } else {
+            let mut diff = TreeDiff::new(relative, DiffKind::Content, Some(&left.metadata), Some(&right.metadata));
+            diff.left.as_mut().unwrap().digest = Some(a);
+            diff.right.as_mut().unwrap().digest = Some(b);
+            report.differences.push(diff);
+        }
+    }
+    report.errors = errors.into_errors();
+    report.inaccessible.extend(inaccessible.into_inner().unwrap());
+    Ok(())
+}
+
+#[async_trait]
+impl FileOrganizer for TreeComparer {
+    type Plan = CompareReport;
+    const NAME: &'static str = "compare";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            left: None,
+            right: None,
+            deep: None,
+            algorithm: Algorithm::Sha256,
+            ignore_mtime: false,
+            follow_links: false,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            hash_cache: true,
+            fail_fast: false,
+            report: None,
+            report_path: None,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.left.is_none() {
+            self.left = Some(get_directory_from_user("Enter the first directory (A)")?);
+        }
+        if self.right.is_none() {
+            self.right = Some(get_directory_from_user("Enter the second directory (B)")?);
+        }
+        if self.deep.is_none() {
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("How should files in both be compared?")
+                .items(&["Quick: size and modification time", "Deep: hash the contents"])
+                .default(0)
+                .interact()?;
+            self.deep = Some(choice == 1);
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<CompareReport> {
+        let spinner = create_spinner("Comparing directories...");
+        self.metrics.phase("scanning");
+        let report = compare(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(report)
+    }
+
+    /// Prints the differences, or a preset report when it has nowhere
+    /// else to go, then writes the report if asked
+    fn execute(&self, report: CompareReport) -> Result<Summary> {
+        if let (Some(Some(format)), None) = (self.report, &self.report_path) {
+            report::write(&report, std::io::stdout().lock(), format)?;
+            if !report.errors.is_empty() || !report.inaccessible.is_empty() {
+                eprintln!(
+                    "{} files could not be compared",
+                    report.errors.len() + report.inaccessible.len()
+                );
+            }
+            // Without phases nothing else reaches stdout
+            return Ok(Summary::from(&report));
+        }
+
+        if !report.differences.is_empty() {
+            report.print_tree();
+            println!();
+        }
+        if !report.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to compare them)",
+                report.repositories.len()
+            );
+        }
+        print_inaccessible(&report.inaccessible, "they were not compared");
+        print_errors(&report.errors);
+        if report.identical_trees() {
+            println!("Identical: {} files", report.identical);
+        } else {
+            println!(
+                "Different: {} only in A, {} only in B, {} changed, {} identical",
+                report.count(&[DiffKind::OnlyLeft]),
+                report.count(&[DiffKind::OnlyRight]),
+                report.count(&[DiffKind::Size, DiffKind::Modified, DiffKind::Content]),
+                report.identical
+            );
+        }
+        if report.cached > 0 {
+            println!("{} digests came from the hash cache", report.cached);
+        }
+        self.write_report(&report)?;
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&report)
+        })
+    }
+}
+
+impl TreeComparer {
+    /// Presets the first tree, A, so the prompt is skipped
+    pub fn with_left(mut self, dir: PathBuf) -> Self {
+        self.left = Some(dir);
+        self
+    }
+
+    /// Presets the second tree, B, so the prompt is skipped
+    pub fn with_right(mut self, dir: PathBuf) -> Self {
+        self.right = Some(dir);
+        self
+    }
+
+    /// Hashes files of equal size instead of comparing modification times,
+    /// without asking
+    pub fn with_deep(mut self, deep: bool) -> Self {
+        self.deep = Some(deep);
+        self
+    }
+
+    /// Hashes with `algorithm` in a deep comparison instead of SHA-256
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Counts files of equal size as the same in a quick comparison
+    pub fn with_ignore_mtime(mut self, ignore: bool) -> Self {
+        self.ignore_mtime = ignore;
+        self
+    }
+
+    /// Walks into symlinked directories and compares linked files
+    pub fn with_follow_links(mut self, follow: bool) -> Self {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Compares working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only compares files whose relative path matches one of `patterns`,
+    /// e.g. "*.mkv" or "photos/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` out on
+    /// both sides
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Whether digests of unchanged files are reused from earlier runs and
+    /// kept for later ones
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Aborts on the first file that cannot be read
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Writes the report as CSV or JSON to `path`, or prints it instead of
+    /// the tree without one; `None` only prints the tree. Either way
+    /// nothing is asked.
+    pub fn with_report(mut self, format: Option<ReportFormat>, path: Option<PathBuf>) -> Self {
+        self.report = Some(format);
+        self.report_path = path;
+        self
+    }
+
+    /// Writes the report in the preset format, or asks whether to write one
+    /// and in which format; the path defaults to one in the working directory
+    fn write_report(&self, report: &CompareReport) -> Result<()> {
+        let format = match self.report {
+            Some(format) => format,
+            None => {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Write a report?")
+                    .items(&["No", "CSV", "JSON"])
+                    .default(0)
+                    .interact()?;
+                [None, Some(ReportFormat::Csv), Some(ReportFormat::Json)][choice]
+            }
+        };
+        let Some(format) = format else {
+            return Ok(());
+        };
+
+        let path = self
+            .report_path
+            .clone()
+            .unwrap_or_else(|| report::default_path(format));
+        report::save(report, &path, format)?;
+        println!("Report written to {}", path.display());
+        Ok(())
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<CompareOptions> {
+        let left = self.left.clone().ok_or_else(|| anyhow::anyhow!("First directory not set"))?;
+        let right = self.right.clone().ok_or_else(|| anyhow::anyhow!("Second directory not set"))?;
+        Ok(CompareOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            junk: self.junk.clone(),
+            deep: self.deep.unwrap_or(false),
+            algorithm: self.algorithm,
+            ignore_mtime: self.ignore_mtime,
+            follow_links: self.follow_links,
+            hash_cache: if self.hash_cache { Some(HashCache::default_path()?) } else { None },
+            fail_fast: self.fail_fast,
+            ..CompareOptions::new(left, right)
+        })
+    }
+}
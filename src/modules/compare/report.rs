@@ -0,0 +1,73 @@
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{CompareReport, FileState};
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{atomic::AtomicFile, csv_field};
+
+/// `compare-<timestamp>.<ext>` in the current directory, so the report
+/// does not land in either tree
+pub fn default_path(format: ReportFormat) -> PathBuf {
+    PathBuf::from(format!(
+        "compare-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Writes the report as JSON, or as CSV with one row per difference:
+/// path, difference (only_left, only_right, size, modified, or content),
+/// then the size, modification time, and digest on each side, empty where
+/// unknown
+pub fn write(report: &CompareReport, mut out: impl Write, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, report)?;
+            writeln!(out)?;
+        }
+        ReportFormat::Csv => {
+            writeln!(
+                out,
+                "path,difference,left_size,right_size,left_modified,right_modified,left_digest,right_digest"
+            )?;
+            let fields = |state: &Option<FileState>| match state {
+                Some(state) => (
+                    state.size.to_string(),
+                    state.modified.clone(),
+                    state.digest.clone().unwrap_or_default(),
+                ),
+                None => Default::default(),
+            };
+            for diff in &report.differences {
+                let (left_size, left_modified, left_digest) = fields(&diff.left);
+                let (right_size, right_modified, right_digest) = fields(&diff.right);
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(&diff.path.display().to_string()),
+                    diff.kind.label(),
+                    left_size,
+                    right_size,
+                    left_modified,
+                    right_modified,
+                    left_digest,
+                    right_digest
+                )?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// [`write`] into a file at `path`, replacing it only once complete
+pub fn save(report: &CompareReport, path: &Path, format: ReportFormat) -> Result<()> {
+    let mut file = BufWriter::new(AtomicFile::create(path)?);
+    write(report, &mut file, format)?;
+    file.into_inner()?.commit()?;
+    Ok(())
+}
@@ -0,0 +1,238 @@
+mod pipeline;
+mod rules;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::modules::file_categorizer::grace;
+use crate::utils::{
+    atomic::write_atomic,
+    filter::PathFilter,
+    junk::JunkList,
+    stamp::FileStamp,
+    transfer::Verify,
+    walk::Walker,
+};
+use pipeline::{Journals, Step};
+pub use rules::{Action, CategorizeAction, DaemonRules, DedupeAction, OptimizeAction, QuietHours, WatchRule};
+
+/// Long-running counterpart of the one-shot modules: watches directories
+/// and runs each file that settles in them through the actions of its
+/// rules file, until told to stop
+pub struct Daemon {
+    interval: Duration,
+    watches: Vec<Watch>,
+    junk: JunkList,
+    /// Where `seen` is kept between runs
+    state_path: PathBuf,
+    seen: SeenFiles,
+}
+
+struct Watch {
+    dir: PathBuf,
+    recursive: bool,
+    debounce: Duration,
+    quiet_hours: Option<QuietHours>,
+    filter: PathFilter,
+    steps: Vec<Step>,
+}
+
+/// Files a pipeline has finished with that are still in a watched
+/// directory, with the size and modification time they had then. They are
+/// only picked up again once they change, including after a failure.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SeenFiles {
+    files: HashMap<PathBuf, FileStamp>,
+}
+
+impl Daemon {
+    /// Resolves the rules against the filesystem and the config: watched
+    /// directories and libraries must exist and rule sets must be saved
+    pub fn new(rules: DaemonRules, verify: Verify) -> Result<Self> {
+        let watches = rules
+            .watches
+            .iter()
+            .map(|watch| {
+                Watch::new(watch, verify).with_context(|| format!("Watch {}", watch.dir.display()))
+            })
+            .collect::<Result<_>>()?;
+        let state_path = SeenFiles::default_path()?;
+        Ok(Self {
+            interval: Duration::from_secs(rules.interval),
+            watches,
+            junk: JunkList::load()?,
+            seen: SeenFiles::load(&state_path),
+            state_path,
+        })
+    }
+
+    /// What each watch does, for `daemon check`
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for watch in &self.watches {
+            let mut settings = vec![format!("settled for {}s", watch.debounce.as_secs())];
+            if watch.recursive {
+                settings.push("recursive".to_string());
+            }
+            if let Some(quiet) = watch.quiet_hours {
+                settings.push(format!("quiet {}", quiet));
+            }
+            lines.push(format!("{} ({})", watch.dir.display(), settings.join(", ")));
+            for (index, step) in watch.steps.iter().enumerate() {
+                lines.push(format!("  {}. {}", index + 1, step.describe()));
+            }
+        }
+        lines
+    }
+
+    /// Scans every `interval` until `stop` is set. A file already in its
+    /// pipeline is seen through to the end, and the journals of the last
+    /// scan are written, before returning.
+    pub fn run(&mut self, stop: &AtomicBool) -> Result<()> {
+        log(format!(
+            "Watching {} directories every {}s",
+            self.watches.len(),
+            self.interval.as_secs()
+        ));
+        while !stop.load(Ordering::SeqCst) {
+            self.scan(stop)?;
+            wait(self.interval, stop);
+        }
+        log("Stopped");
+        Ok(())
+    }
+
+    /// Runs the pipelines on every new, settled file outside quiet hours
+    fn scan(&mut self, stop: &AtomicBool) -> Result<()> {
+        let now = Local::now().time();
+        let mut journals = Journals::default();
+        for watch in &mut self.watches {
+            if watch.quiet_hours.is_some_and(|quiet| quiet.contains(now)) {
+                continue;
+            }
+            let outputs = watch.steps.iter().filter_map(Step::output_dir).map(Path::to_path_buf).collect();
+            let walk = Walker::new(watch.recursive)
+                .exclude(outputs)
+                .filter(watch.filter.clone())
+                .junk(Some(self.junk.clone()))
+                .sorted(true)
+                .files(&watch.dir);
+            for entry in walk.files {
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if self.seen.files.get(&entry.path) == Some(&FileStamp::of(&entry.metadata)) {
+                    continue;
+                }
+                // Still being written; picked up on a later scan
+                if grace::is_in_progress(&entry.path, watch.debounce).unwrap_or(true) {
+                    continue;
+                }
+                if let Err(e) = watch.process(&entry.path, &mut journals) {
+                    log(format!("{}: {:#}", entry.path.display(), e));
+                }
+                if let Ok(metadata) = fs::metadata(&entry.path) {
+                    self.seen.files.insert(entry.path, FileStamp::of(&metadata));
+                }
+            }
+            for step in &watch.steps {
+                step.finish();
+            }
+        }
+        // A failure to write them is reported but does not stop the daemon
+        if let Err(e) = journals.save() {
+            log(format!("{:#}", e));
+        }
+        self.seen.files.retain(|path, _| path.exists());
+        if let Err(e) = self.seen.save(&self.state_path) {
+            log(format!("{:#}", e));
+        }
+        Ok(())
+    }
+}
+
+impl Watch {
+    fn new(rule: &WatchRule, verify: Verify) -> Result<Self> {
+        if !rule.dir.is_dir() {
+            anyhow::bail!("{} is not a directory", rule.dir.display());
+        }
+        let dir = rule.dir.canonicalize()?;
+        let steps = rule
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(index, action)| Step::new(action, &dir, verify).with_context(|| format!("Action {}", index + 1)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            recursive: rule.recursive,
+            debounce: Duration::from_secs(rule.debounce),
+            quiet_hours: rule.quiet_hours,
+            filter: PathFilter::new(&rule.include, &rule.exclude)?,
+            steps,
+            dir,
+        })
+    }
+
+    /// Runs `file` through every step, following it wherever a step puts it
+    fn process(&mut self, file: &Path, journals: &mut Journals) -> Result<()> {
+        let mut current = file.to_path_buf();
+        for step in &mut self.steps {
+            match step.apply(&current, &self.dir, journals)? {
+                Some(path) => current = path,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SeenFiles {
+    /// `<data dir>/file-organizer-rust/daemon-state.json`
+    fn default_path() -> Result<PathBuf> {
+        let dir = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("No data directory on this platform"))?;
+        Ok(dir.join("file-organizer-rust").join("daemon-state.json"))
+    }
+
+    /// A missing or unreadable state starts empty, so every file present
+    /// is treated as new
+    fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write daemon state {}", path.display()))
+    }
+}
+
+/// Prints a line with the local time, the daemon's only output
+fn log(message: impl fmt::Display) {
+    println!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+/// Sleeps for `duration`, returning early once `stop` is set
+fn wait(duration: Duration, stop: &AtomicBool) {
+    let until = Instant::now() + duration;
+    while !stop.load(Ordering::SeqCst) {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return;
+        }
+        thread::sleep(left.min(Duration::from_millis(200)));
+    }
+}
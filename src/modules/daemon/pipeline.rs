@@ -0,0 +1,287 @@
+use anyhow::{Context, Result};
+use image::ImageFormat;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    log,
+    rules::{Action, DedupeAction},
+};
+use crate::modules::{
+    base::FileOrganizer,
+    file_categorizer::route::{Routed, RulesetRouter},
+    file_deduplicator::DuplicateAction,
+    image_optimizer::ImageOptimizer,
+};
+use crate::utils::{
+    delete::{safe_delete, DeleteMode},
+    hash_cache::HashCache,
+    journal::Journal,
+    junk::JunkList,
+    names::{is_same_file, numbered_name},
+    transfer::{move_file, TransferMode, Verify},
+    walk::Walker,
+};
+
+/// Dedupe action with the directory duplicates are moved to, and the
+/// digests of library files kept between scans
+pub struct Dedupe {
+    action: DedupeAction,
+    duplicates_dir: PathBuf,
+    cache: HashCache,
+    junk: JunkList,
+}
+
+/// An action of a watch, checked and ready to run
+pub enum Step {
+    Categorize {
+        router: RulesetRouter,
+        ruleset: String,
+        mode: TransferMode,
+    },
+    Dedupe(Box<Dedupe>),
+    Optimize {
+        optimizer: Box<ImageOptimizer>,
+        format: ImageFormat,
+        output_dir: PathBuf,
+    },
+}
+
+impl Step {
+    /// Resolves `action` for files arriving in `dir`: loads rule sets and
+    /// checks that the directories it reads exist
+    pub fn new(action: &Action, dir: &Path, verify: Verify) -> Result<Self> {
+        Ok(match action {
+            Action::Categorize(categorize) => {
+                let mode = if categorize.copy { TransferMode::Copy } else { TransferMode::Move };
+                let base_dir = categorize.output_dir.as_deref().unwrap_or(dir);
+                let router = RulesetRouter::new(&categorize.ruleset, base_dir, mode, categorize.on_conflict)?
+                    .with_verify(verify);
+                Self::Categorize { router, ruleset: categorize.ruleset.clone(), mode }
+            }
+            Action::Dedupe(dedupe) => {
+                if !dedupe.library.is_dir() {
+                    anyhow::bail!("Library {} is not a directory", dedupe.library.display());
+                }
+                Self::Dedupe(Box::new(Dedupe {
+                    action: DedupeAction {
+                        library: dedupe.library.canonicalize()?,
+                        ..dedupe.clone()
+                    },
+                    duplicates_dir: dedupe.duplicates_dir.clone().unwrap_or_else(|| dir.join("duplicates")),
+                    cache: HashCache::open(Some(&HashCache::default_path()?)),
+                    junk: JunkList::load()?,
+                }))
+            }
+            Action::OptimizeImages(optimize) => {
+                let format = optimize.target_format()?;
+                let output_dir = optimize
+                    .output_dir
+                    .clone()
+                    .unwrap_or_else(|| dir.join(format.extensions_str()[0]));
+                let optimizer = ImageOptimizer::new(false)
+                    .with_target_format(format)?
+                    .with_output_dir(output_dir.clone());
+                Self::Optimize { optimizer: Box::new(optimizer), format, output_dir }
+            }
+        })
+    }
+
+    /// Directory the step writes into, which the watch must not pick up
+    /// files from
+    pub fn output_dir(&self) -> Option<&Path> {
+        match self {
+            Self::Categorize { router, .. } => Some(router.root()),
+            Self::Dedupe(dedupe) => {
+                (dedupe.action.on_duplicate == DuplicateAction::Move).then_some(dedupe.duplicates_dir.as_path())
+            }
+            Self::Optimize { output_dir, .. } => Some(output_dir),
+        }
+    }
+
+    /// One line for `daemon check`
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Categorize { router, ruleset, mode } => format!(
+                "categorize by rule set '{}', {} into {}",
+                ruleset,
+                mode.verb(),
+                router.root().display()
+            ),
+            Self::Dedupe(dedupe) => {
+                let Dedupe { action, duplicates_dir, .. } = &**dedupe;
+                let outcome = match action.on_duplicate {
+                    DuplicateAction::Delete => action.delete_mode.verb().to_string(),
+                    DuplicateAction::Move => format!("moved to {}", duplicates_dir.display()),
+                    DuplicateAction::Report => "reported".to_string(),
+                };
+                format!(
+                    "dedupe against {} by {:?}, copies it already has {}",
+                    action.library.display(),
+                    action.hash_method,
+                    outcome
+                )
+            }
+            Self::Optimize { format, output_dir, .. } => format!(
+                "convert images to {} into {}",
+                format.extensions_str()[0],
+                output_dir.display()
+            ),
+        }
+    }
+
+    /// Runs the step on `file`, found under the watched `dir`, recording
+    /// what it changes in `journals`. Returns where the file is now, or
+    /// `None` when the pipeline ends here.
+    pub fn apply(&mut self, file: &Path, dir: &Path, journals: &mut Journals) -> Result<Option<PathBuf>> {
+        match self {
+            Self::Categorize { router, mode, .. } => {
+                match router.route(file, dir, journals.get("daemon-categorize", *mode))? {
+                    Routed::Transferred { destination, category } => {
+                        log(format!("{} {} to {} ({})", file.display(), mode.verb(), destination.display(), category));
+                        Ok(Some(destination))
+                    }
+                    Routed::Skipped(reason) => {
+                        log(format!("{} left in place ({})", file.display(), reason));
+                        Ok(Some(file.to_path_buf()))
+                    }
+                    Routed::Unmatched => Ok(Some(file.to_path_buf())),
+                }
+            }
+            Self::Dedupe(dedupe) => {
+                let Dedupe { action, duplicates_dir, cache, junk } = &**dedupe;
+                let Some(original) = find_copy(file, action, cache, junk)? else {
+                    return Ok(Some(file.to_path_buf()));
+                };
+                match action.on_duplicate {
+                    DuplicateAction::Report => {
+                        log(format!("{} is a copy of {}", file.display(), original.display()));
+                        Ok(Some(file.to_path_buf()))
+                    }
+                    DuplicateAction::Delete => {
+                        let metadata = fs::metadata(file)?;
+                        safe_delete(file, action.delete_mode)?;
+                        if action.delete_mode != DeleteMode::DryRun {
+                            journals
+                                .get("daemon-dedupe", TransferMode::Move)
+                                .record_deletion(file, &metadata, Some("duplicate"));
+                        }
+                        log(format!(
+                            "{} {} (copy of {})",
+                            file.display(),
+                            action.delete_mode.verb(),
+                            original.display()
+                        ));
+                        Ok(None)
+                    }
+                    DuplicateAction::Move => {
+                        fs::create_dir_all(duplicates_dir)?;
+                        let target = free_path(&duplicates_dir.join(file.file_name().unwrap()));
+                        move_file(file, &target, Verify::default())?;
+                        journals
+                            .get("daemon-dedupe", TransferMode::Move)
+                            .record(file, &target, Some("duplicate"))?;
+                        log(format!(
+                            "{} moved to {} (copy of {})",
+                            file.display(),
+                            target.display(),
+                            original.display()
+                        ));
+                        Ok(None)
+                    }
+                }
+            }
+            Self::Optimize { optimizer, .. } => {
+                if let Some(output) = optimizer.convert_one(file)? {
+                    journals
+                        .get("daemon-optimize-images", TransferMode::Copy)
+                        .record(file, &output, Some("optimize images"))?;
+                    log(format!("{} converted to {}", file.display(), output.display()));
+                }
+                Ok(Some(file.to_path_buf()))
+            }
+        }
+    }
+
+    /// Called after every scan
+    pub fn finish(&self) {
+        if let Self::Dedupe(dedupe) = self {
+            // The cache only saves time, so failing to write it fails nothing
+            let _ = dedupe.cache.save();
+        }
+    }
+}
+
+/// A file in the library with the same contents as `file`, if any.
+/// Only files of the same size are hashed.
+fn find_copy(file: &Path, action: &DedupeAction, cache: &HashCache, junk: &JunkList) -> Result<Option<PathBuf>> {
+    let metadata = fs::metadata(file)?;
+    let size = metadata.len();
+    let candidates = Walker::new(true)
+        .junk(Some(junk.clone()))
+        .size_range(Some(size), Some(size))
+        .files(&action.library)
+        .files;
+    if candidates.iter().all(|entry| is_same_file(&entry.path, file)) {
+        return Ok(None);
+    }
+
+    let digest = action.hash_method.hash(file, &metadata, cache)?;
+    for entry in candidates {
+        if is_same_file(&entry.path, file) {
+            continue;
+        }
+        // A library file that cannot be read cannot be a known copy
+        if action.hash_method.hash(&entry.path, &entry.metadata, cache).ok().as_ref() == Some(&digest) {
+            return Ok(Some(entry.path));
+        }
+    }
+    Ok(None)
+}
+
+/// `path`, or the first `name-<n>.ext` next to it that is free
+fn free_path(path: &Path) -> PathBuf {
+    let mut candidate = path.to_path_buf();
+    let mut counter = 1;
+    while candidate.exists() {
+        candidate = path.with_file_name(numbered_name(path, counter));
+        counter += 1;
+    }
+    candidate
+}
+
+/// The journals of one scan, one per operation and transfer mode, so each
+/// can be undone on its own
+#[derive(Default)]
+pub struct Journals(Vec<Journal>);
+
+impl Journals {
+    fn get(&mut self, operation: &str, mode: TransferMode) -> &mut Journal {
+        let index = match self.0.iter().position(|journal| journal.operation == operation && journal.mode == mode) {
+            Some(index) => index,
+            None => {
+                self.0.push(Journal::new(operation, mode));
+                self.0.len() - 1
+            }
+        };
+        &mut self.0[index]
+    }
+
+    /// Writes every journal with entries to the data directory
+    pub fn save(self) -> Result<()> {
+        for journal in self.0.into_iter().filter(|journal| !journal.entries.is_empty()) {
+            let dir = Journal::default_dir()?;
+            // Two journals of one operation can be written in the same second
+            let path = free_path(&dir.join(journal.file_name()));
+            journal.save(&path).context("Failed to write the daemon's journal")?;
+            log(format!(
+                "Journal of {} files written; undo with: file-organizer-rust undo --manifest {}",
+                journal.entries.len(),
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+}
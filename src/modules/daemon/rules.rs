@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use chrono::NaiveTime;
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::modules::{
+    file_categorizer::{grace::DEFAULT_GRACE_SECS, ConflictPolicy},
+    file_deduplicator::{DuplicateAction, HashMethod},
+};
+use crate::utils::{delete::DeleteMode, expand::expand_path};
+
+/// A rules file for `daemon run`: the directories watched and the actions
+/// run, in order, on every file that settles in them. Paths may use `~`
+/// and environment variables.
+///
+/// ```
+/// use file_organizer_rust::modules::daemon::{Action, DaemonRules};
+/// use file_organizer_rust::modules::file_deduplicator::DuplicateAction;
+///
+/// let rules = DaemonRules::parse(r#"
+///     [[watch]]
+///     dir = "/srv/inbox"
+///     quiet_hours = "22:00-07:00"
+///
+///     [[watch.actions]]
+///     action = "categorize"
+///     ruleset = "inbox"
+///
+///     [[watch.actions]]
+///     action = "dedupe"
+///     library = "/srv/photos"
+///     on_duplicate = "move"
+/// "#)?;
+/// let watch = &rules.watches[0];
+/// assert_eq!((rules.interval, watch.debounce), (30, 60));
+/// let quiet = watch.quiet_hours.unwrap();
+/// assert!(quiet.contains(chrono::NaiveTime::from_hms_opt(23, 30, 0).unwrap()));
+/// assert!(!quiet.contains(chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+/// assert!(matches!(&watch.actions[1], Action::Dedupe(dedupe) if dedupe.on_duplicate == DuplicateAction::Move));
+///
+/// // Misspelled settings are errors rather than silently ignored
+/// let rules_text = "[[watch]]\ndir = \"/srv/inbox\"\nrecursive = true\n\
+///     [[watch.actions]]\naction = \"optimize-images\"\nformat = \"webp\"".to_string();
+/// let misspelled = rules_text.replace("recursive", "recursve");
+/// assert!(DaemonRules::parse(&rules_text).is_ok());
+/// assert!(DaemonRules::parse(&misspelled).is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonRules {
+    /// Seconds between scans of the watched directories
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(rename = "watch")]
+    pub watches: Vec<WatchRule>,
+}
+
+/// `[[watch]]`: a directory and the pipeline its new files go through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchRule {
+    pub dir: PathBuf,
+    /// Also pick up files in subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+    /// Seconds a file must go unmodified before it is picked up, so
+    /// downloads and copies still being written are left to finish
+    #[serde(default = "default_debounce")]
+    pub debounce: u64,
+    /// Daily window, e.g. "22:00-07:00", in which new files are left
+    /// waiting until it ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+    /// Only pick up files whose path relative to `dir` matches one of
+    /// these globs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Leave files whose relative path matches one of these globs alone
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Run in order on every new file. Later actions see the file where an
+    /// earlier one moved or copied it; one that deletes or moves it aside
+    /// as a duplicate ends the pipeline.
+    pub actions: Vec<Action>,
+}
+
+/// `[[watch.actions]]`, selected by its `action` key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    Categorize(CategorizeAction),
+    Dedupe(DedupeAction),
+    OptimizeImages(OptimizeAction),
+}
+
+/// Sorts the file by a saved rule set, as `categorize --ruleset` does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CategorizeAction {
+    /// Name of a rule set in the config
+    pub ruleset: String,
+    /// Root the `custom/<category>` folders are created under; the watched
+    /// directory by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    /// Copy into the category instead of moving
+    #[serde(default)]
+    pub copy: bool,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+/// Compares the file against a library and deals with it when the
+/// library already holds a copy; the library's copy is always kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DedupeAction {
+    /// Directory searched, recursively, for an existing copy
+    pub library: PathBuf,
+    #[serde(default = "default_hash_method")]
+    pub hash_method: HashMethod,
+    /// What happens to a file the library already has
+    #[serde(default = "default_duplicate_action")]
+    pub on_duplicate: DuplicateAction,
+    /// How `on_duplicate = "delete"` gets rid of the file
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+    /// Where `on_duplicate = "move"` puts the file; `duplicates/` in the
+    /// watched directory by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicates_dir: Option<PathBuf>,
+}
+
+/// Writes a converted copy of the file when it is a JPEG, PNG, WebP, or
+/// GIF not already in the target format; the original is kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptimizeAction {
+    /// "jpg", "png", or "webp"
+    pub format: String,
+    /// Where converted images go; a folder named after the format in the
+    /// watched directory by default, as for `image-optimize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+}
+
+impl OptimizeAction {
+    pub fn target_format(&self) -> Result<ImageFormat> {
+        match ImageFormat::from_extension(&self.format) {
+            Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => Ok(format),
+            _ => anyhow::bail!("Unsupported image format '{}' (expected jpg, png, or webp)", self.format),
+        }
+    }
+}
+
+/// A daily window that may run past midnight, such as 22:00 to 07:00
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Whether `time` falls in the window, counting its start but not its
+    /// end
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("Invalid quiet hours '{}', use e.g. \"22:00-07:00\"", value))
+        };
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("Invalid quiet hours '{}', use e.g. \"22:00-07:00\"", value))?;
+        let hours = Self { start: parse(start)?, end: parse(end)? };
+        if hours.start == hours.end {
+            anyhow::bail!("Quiet hours '{}' start and end at the same time", value);
+        }
+        Ok(hours)
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(hours: QuietHours) -> Self {
+        hours.to_string()
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+impl DaemonRules {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid rules file {}", path.display()))
+    }
+
+    /// Parses a rules file, expanding its paths and checking everything
+    /// that does not depend on the filesystem or the config
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut rules: Self = toml::from_str(text)?;
+        if rules.interval == 0 {
+            anyhow::bail!("interval must be at least 1 second");
+        }
+        if rules.watches.is_empty() {
+            anyhow::bail!("No [[watch]] directories");
+        }
+        let mut dirs = HashSet::new();
+        for watch in &mut rules.watches {
+            expand(&mut watch.dir)?;
+            if !dirs.insert(watch.dir.clone()) {
+                anyhow::bail!("{} is watched twice; give it one pipeline", watch.dir.display());
+            }
+            if watch.actions.is_empty() {
+                anyhow::bail!("{}: no actions", watch.dir.display());
+            }
+            for action in &mut watch.actions {
+                match action {
+                    Action::Categorize(categorize) => {
+                        if categorize.ruleset.trim().is_empty() {
+                            anyhow::bail!("{}: categorize needs a ruleset", watch.dir.display());
+                        }
+                        if let Some(dir) = &mut categorize.output_dir {
+                            expand(dir)?;
+                        }
+                    }
+                    Action::Dedupe(dedupe) => {
+                        expand(&mut dedupe.library)?;
+                        if let Some(dir) = &mut dedupe.duplicates_dir {
+                            expand(dir)?;
+                        }
+                    }
+                    Action::OptimizeImages(optimize) => {
+                        optimize.target_format().with_context(|| watch.dir.display().to_string())?;
+                        if let Some(dir) = &mut optimize.output_dir {
+                            expand(dir)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(rules)
+    }
+}
+
+fn expand(path: &mut PathBuf) -> Result<()> {
+    *path = expand_path(&path.to_string_lossy())?;
+    Ok(())
+}
+
+fn default_interval() -> u64 {
+    30
+}
+
+fn default_debounce() -> u64 {
+    DEFAULT_GRACE_SECS
+}
+
+fn default_hash_method() -> HashMethod {
+    HashMethod::Sha256
+}
+
+fn default_duplicate_action() -> DuplicateAction {
+    DuplicateAction::Delete
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_in_the_rules_file_are_expanded() {
+        let rules = DaemonRules::parse(
+            "[[watch]]\ndir = \"~/inbox\"\n[[watch.actions]]\naction = \"dedupe\"\nlibrary = \"~/photos\"",
+        )
+        .unwrap();
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(rules.watches[0].dir, home.join("inbox"));
+        assert!(matches!(&rules.watches[0].actions[0], Action::Dedupe(dedupe) if dedupe.library == home.join("photos")));
+    }
+}
@@ -1,38 +1,480 @@
+mod plan;
+mod progress;
+mod summary;
+mod symlink;
+
 use anyhow::Result;
 use async_trait::async_trait;
-use dialoguer::{theme::ColorfulTheme, Select};
-use std::{collections::HashMap, fs, path::PathBuf};
-use walkdir::WalkDir;
+use chrono::{DateTime, Local};
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use dialoguer::theme::ColorfulTheme;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::utils::prompt::{Confirm, Input, MultiSelect, Select};
+use crate::utils::{
+    create_spinner,
+    delete::DeleteMode,
+    expand::expand_path,
+    filter::PathFilter,
+    get_directory_from_user,
+    journal::Journal,
+    junk::JunkList,
+    metrics::Metrics,
+    names::Normalization,
+    progress::{Outcome, ProgressSink},
+    sanitize::folder_name,
+    space::{self, preflight, print_estimates, SpaceEstimate},
+    transfer::{TransferMode, Verify},
+    walk::{Inaccessible, WalkResult, Walker},
+};
+use crate::modules::base::{print_inaccessible, FileOrganizer, Summary};
+use plan::{Discard, FlattenPlan, Resolution};
+use progress::FlattenProgress;
+use crate::utils::links::is_symlink;
 
-use crate::utils::{create_spinner, get_directory_from_user};
-use crate::modules::base::FileOrganizer;
+pub use plan::FlattenMove;
+pub use summary::FlattenSummary;
+pub use symlink::SymlinkPolicy;
 
 pub struct DirectoryFlattener {
+    /// Without recursion only files in immediate subfolders are pulled up
     recursive: bool,
+    /// Deepest level of files to flatten, the root's own files being level 1
+    max_depth: Option<usize>,
     input_dir: Option<PathBuf>,
     handle_duplicates: Option<DuplicateHandling>,
+    /// Flatten git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Walk in name order, so collisions are numbered the same every run
+    sorted: bool,
+    /// Leave the emptied directory tree in place after flattening
+    keep_empty_dirs: bool,
+    /// Print the planned moves and ask before executing them
+    dry_run: bool,
+    /// Move within the tree, or copy and leave the original intact
+    mode: Option<TransferMode>,
+    /// Flatten into this directory instead of the input directory
+    output_dir: Option<PathBuf>,
+    /// Only flatten files with these extensions (lowercase, no dot)
+    extensions: Vec<String>,
+    /// Glob patterns a file's relative path must match to be flattened
+    include: Vec<String>,
+    /// Glob patterns that leave a file in place
+    exclude: Vec<String>,
+    /// Joins path components in the prefix naming scheme
+    prefix_separator: String,
+    /// Prefix every file rather than only colliding names
+    prefix_all: Option<bool>,
+    /// Delete identical extra copies instead of leaving them in place
+    delete_identical: Option<bool>,
+    /// Print a log line every 5% instead of a progress bar
+    quiet: bool,
+    /// Where older versions and replaced files go; asked for newest-wins
+    superseded_dir: Option<PathBuf>,
+    /// Delete older versions and replaced files instead of keeping them
+    delete_superseded: bool,
+    /// How losing files and identical copies are deleted
+    delete_mode: DeleteMode,
+    /// Break newest-wins mtime ties by keeping the larger file
+    size_tiebreak: bool,
+    /// How to handle symlinks to files; asked when some are found
+    symlinks: Option<SymlinkPolicy>,
+    /// Walk into symlinked directories, which may lie outside the input
+    follow_symlinks: bool,
+    /// How moves to another filesystem check the copy
+    verify: Verify,
+    /// Copy even when the output filesystem looks too small
+    force: bool,
+    /// Ask about every name collision regardless of their number or the
+    /// duplicate-handling policy
+    interactive_conflicts: bool,
+    /// Ask about each name collision, chosen instead of a policy; only
+    /// when there are at most `conflict_prompt_limit`, renaming otherwise
+    decide_conflicts: bool,
+    conflict_prompt_limit: usize,
+    /// Write a CSV report of the moves; asked after the run when unset
+    report: Option<bool>,
+    report_path: Option<PathBuf>,
+    metrics: Metrics,
 }
 
-#[derive(Clone, Copy)]
-enum DuplicateHandling {
+/// Collision counts up to this are resolved one by one at the prompt
+const DEFAULT_CONFLICT_PROMPT_LIMIT: usize = 10;
+
+/// How files with the same name are brought into the root
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateHandling {
+    /// Number the later files
     Rename,
+    /// Leave the later files where they are
     Skip,
+    /// Skip extra copies with the same contents, rename only when they differ
+    SkipIdentical,
+    /// Keep the file with the newest mtime and discard the others
+    NewestWins { size_tiebreak: bool },
+    /// Prefix the original directory path into the name, for colliding
+    /// names only or for every file
+    Prefix { all: bool },
+}
+
+/// Everything a flatten run needs, with no prompting
+pub struct FlattenOptions {
+    pub input_dir: PathBuf,
+    /// Directory the files are flattened into; `None` flattens into the
+    /// input directory itself
+    pub output_dir: Option<PathBuf>,
+    pub mode: TransferMode,
+    /// Deepest level of files to flatten, the input's own files being
+    /// level 1; `None` has no limit
+    pub max_depth: Option<usize>,
+    pub duplicates: DuplicateHandling,
+    /// Joins path components in the prefix naming scheme
+    pub prefix_separator: String,
+    /// Only flatten files with these extensions, in lower case without the
+    /// dot; empty flattens every file
+    pub extensions: Vec<String>,
+    /// Include/exclude globs, matched relative to the input directory
+    pub filter: PathFilter,
+    pub symlinks: SymlinkPolicy,
+    /// Walk into symlinked directories, which may lie outside the input
+    pub follow_symlinks: bool,
+    /// Flatten inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Walk in name order, so collisions are numbered the same every run
+    pub sorted: bool,
+    /// Where older versions and replaced files go; `None` deletes them
+    pub superseded_dir: Option<PathBuf>,
+    /// Delete identical extra copies when moving, instead of leaving them
+    /// in place
+    pub delete_identical: bool,
+    /// How discarded files and identical copies are deleted
+    pub delete_mode: DeleteMode,
+    /// Leave the emptied directory tree in place
+    pub keep_empty_dirs: bool,
+    /// What counts as junk, which stays where it is
+    pub junk: JunkList,
+    /// Unicode form of the names given to flattened files
+    pub names: Normalization,
+    /// How moves to another filesystem check the copy
+    pub verify: Verify,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+}
+
+impl FlattenOptions {
+    /// Moves every file under `input_dir` into it, numbering repeated
+    /// names and removing the emptied directories
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            output_dir: None,
+            mode: TransferMode::Move,
+            max_depth: None,
+            duplicates: DuplicateHandling::Rename,
+            prefix_separator: "_".to_string(),
+            extensions: Vec::new(),
+            filter: PathFilter::default(),
+            symlinks: SymlinkPolicy::Skip,
+            follow_symlinks: false,
+            include_repos: false,
+            sorted: false,
+            superseded_dir: None,
+            delete_identical: false,
+            delete_mode: DeleteMode::Trash,
+            keep_empty_dirs: false,
+            junk: JunkList::default(),
+            names: Normalization::default(),
+            verify: Verify::default(),
+            manifest_dir: None,
+        }
+    }
+
+    /// Directory the files are flattened into
+    fn root(&self) -> &Path {
+        self.output_dir.as_deref().unwrap_or(&self.input_dir)
+    }
+
+    fn discard(&self) -> Discard {
+        match &self.superseded_dir {
+            Some(dir) => Discard::MoveTo(dir.clone()),
+            None => Discard::Delete(self.delete_mode),
+        }
+    }
+}
+
+/// A flatten run decided in full, before any file is touched
+#[derive(Serialize)]
+pub struct FlattenJob {
+    input_dir: PathBuf,
+    /// Directory the files are flattened into
+    root: PathBuf,
+    mode: TransferMode,
+    symlinks: SymlinkPolicy,
+    discard: Discard,
+    /// Symlinks left in place by the skip policy
+    symlinks_skipped: usize,
+    /// Working copies that were not walked into
+    repositories: Vec<PathBuf>,
+    /// Known junk files and directories, left where they are
+    junk: Vec<PathBuf>,
+    /// Space the copies need in the root; empty when moving
+    space: Vec<SpaceEstimate>,
+    /// Files and directories that could not be read, left where they are
+    inaccessible: Vec<Inaccessible>,
+    /// Delete the identical extra copies after moving
+    delete_identical: bool,
+    /// Where to write the CSV report of the moves, if one is wanted
+    report: Option<PathBuf>,
+    plan: FlattenPlan,
+}
+
+impl FlattenJob {
+    /// The moves planned, in walk order
+    pub fn moves(&self) -> &[FlattenMove] {
+        &self.plan.moves
+    }
+
+    /// What carrying out the job did, given the bytes it transferred
+    fn summary(&self, bytes: u64, removed_dirs: usize, elapsed: Duration) -> FlattenSummary {
+        FlattenSummary {
+            mode: self.mode,
+            files: self.plan.moves.len(),
+            bytes,
+            renamed: self.plan.renamed_count(),
+            skipped: self.plan.skipped.len(),
+            identical: self.plan.identical.len(),
+            identical_deleted: self.delete_identical,
+            replaced: self.plan.replaced().count(),
+            superseded: self.plan.superseded().count(),
+            symlinks_skipped: self.symlinks_skipped,
+            removed_dirs,
+            repositories: self.repositories.len(),
+            junk: self.junk.len(),
+            inaccessible: self.inaccessible.len(),
+            elapsed,
+            manifest: None,
+        }
+    }
+}
+
+/// Walks the input directory for the files to flatten, leaving out the
+/// output and superseded directories when they lie inside it
+fn scan(options: &FlattenOptions, progress: impl ProgressSink) -> WalkResult {
+    let input_dir = &options.input_dir;
+    let root = options.root();
+    let mut excluded = Vec::new();
+    if root != input_dir && root.starts_with(input_dir) {
+        excluded.push(root.to_path_buf());
+    }
+    if let Some(dir) = &options.superseded_dir {
+        if dir.starts_with(input_dir) {
+            excluded.push(dir.clone());
+        }
+    }
+    progress.scan_started(input_dir);
+    Walker::new(true)
+        .max_depth(options.max_depth)
+        .symlinks(true)
+        .follow_links(options.follow_symlinks)
+        .include_repos(options.include_repos)
+        .sorted(options.sorted)
+        .exclude(excluded)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .files(input_dir)
+}
+
+/// Plans the job for the files `scan` found, with `build` resolving their
+/// names into a plan
+fn build_job(
+    options: &FlattenOptions,
+    mut walk: WalkResult,
+    build: impl FnOnce(&[PathBuf]) -> Result<FlattenPlan>,
+) -> Result<FlattenJob> {
+    let repositories = std::mem::take(&mut walk.repositories);
+    let junk = std::mem::take(&mut walk.junk);
+    let inaccessible = std::mem::take(&mut walk.inaccessible);
+    let mut files = walk.into_paths();
+    // Everything else, and the directories holding it, stays untouched
+    files.retain(|file| matches_extension(file, &options.extensions));
+    let links = files.iter().filter(|file| is_symlink(file)).count();
+    let symlinks_skipped = if options.symlinks == SymlinkPolicy::Skip { links } else { 0 };
+    if options.symlinks == SymlinkPolicy::Skip {
+        files.retain(|file| !is_symlink(file));
+    }
+
+    let plan = build(&files)?;
+    let root = options.root().to_path_buf();
+    let space = match options.mode {
+        TransferMode::Copy => {
+            let required = plan.moves.iter().filter_map(|m| fs::metadata(&m.source).ok()).map(|m| m.len()).sum();
+            vec![SpaceEstimate::new(&root, required)?]
+        }
+        TransferMode::Move => Vec::new(),
+    };
+    Ok(FlattenJob {
+        input_dir: options.input_dir.clone(),
+        root,
+        mode: options.mode,
+        symlinks: options.symlinks,
+        discard: options.discard(),
+        symlinks_skipped,
+        repositories,
+        junk,
+        space,
+        inaccessible,
+        // Copying leaves the extra copies where they are anyway
+        delete_identical: options.delete_identical && options.mode == TransferMode::Move,
+        report: None,
+        plan,
+    })
+}
+
+/// Carries out `job`, calling `moved` after each move. The journal is
+/// returned even when the run failed part way, with whatever was moved.
+fn run(options: &FlattenOptions, job: &FlattenJob, moved: impl FnMut(&FlattenMove)) -> (Journal, Result<u64>) {
+    let mut journal = Journal::new("flatten", job.mode);
+    let result = fs::create_dir_all(&job.root).map_err(anyhow::Error::from).and_then(|_| {
+        let bytes = job.plan.execute(job.mode, options.verify, job.symlinks, &job.discard, &mut journal, moved)?;
+        if job.delete_identical {
+            job.plan.delete_identical(options.delete_mode, &mut journal)?;
+        }
+        Ok(bytes)
+    });
+    // Copying leaves the source tree as it was. Only directories files
+    // actually left are considered, so a failed run keeps the rest.
+    if !options.keep_empty_dirs && job.mode == TransferMode::Move {
+        let emptied = journal.entries.iter().filter_map(|entry| entry.source.parent()).map(Path::to_path_buf).collect();
+        journal.removed_dirs = remove_empty_dirs(&job.input_dir, &emptied);
+    }
+    (journal, result)
+}
+
+/// Walks the input directory and plans where every file goes in the root
+pub fn plan(options: &FlattenOptions, progress: impl ProgressSink) -> Result<FlattenJob> {
+    let walk = scan(options, &progress);
+    let root = options.root();
+    build_job(options, walk, |files| {
+        Ok(FlattenPlan::build(
+            &options.input_dir,
+            root,
+            files,
+            options.duplicates,
+            &options.prefix_separator,
+            options.names,
+        ))
+    })
+}
+
+/// Moves or copies every file in `job` into the root, recording each in an
+/// undo manifest
+pub fn apply(options: &FlattenOptions, job: FlattenJob, progress: impl ProgressSink) -> Result<FlattenSummary> {
+    space::check(&job.space)?;
+    let started = Instant::now();
+    let phase = match job.mode {
+        TransferMode::Move => "Moving",
+        TransferMode::Copy => "Copying",
+    };
+    progress.phase_changed(phase, job.plan.moves.len() as u64);
+    let (journal, result) = run(options, &job, |m| progress.item_finished(&m.source, 0, Outcome::Done));
+
+    // Saved even when the run failed part way, so what was moved can be
+    // undone
+    let mut manifest = None;
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            manifest = Some(path);
+        }
+    }
+    let bytes = result?;
+    Ok(FlattenSummary { manifest, ..job.summary(bytes, journal.removed_dirs.len(), started.elapsed()) })
+}
+
+/// Flattens the input directory
+///
+/// ```
+/// use file_organizer_rust::modules::directory_flattener::{self, FlattenOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir_all(dir.path().join("2023/trip"))?;
+/// std::fs::create_dir_all(dir.path().join("2024"))?;
+/// std::fs::write(dir.path().join("2023/trip/beach.jpg"), "jpeg")?;
+/// std::fs::write(dir.path().join("2024/beach.jpg"), "other jpeg")?;
+///
+/// let summary = directory_flattener::execute(&FlattenOptions::new(dir.path().to_path_buf()), ())?;
+/// assert_eq!((summary.files, summary.renamed), (2, 1));
+/// assert!(dir.path().join("beach.jpg").exists());
+/// assert!(dir.path().join("beach-2.jpg").exists());
+/// assert!(!dir.path().join("2023").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &FlattenOptions, progress: impl ProgressSink) -> Result<FlattenSummary> {
+    let job = plan(options, &progress)?;
+    let summary = apply(options, job, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
 }
 
 #[async_trait]
 impl FileOrganizer for DirectoryFlattener {
-    fn new(_recursive: bool) -> Self {
+    type Plan = FlattenJob;
+    const NAME: &'static str = "directory-flatten";
+
+    fn new(recursive: bool) -> Self {
         Self {
-            recursive: true,  // Directory flattener is always recursive
+            recursive,
+            max_depth: None,
             input_dir: None,
             handle_duplicates: Some(DuplicateHandling::Rename),
+            include_repos: false,
+            keep_empty_dirs: false,
+            dry_run: false,
+            mode: None,
+            output_dir: None,
+            extensions: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            prefix_separator: "_".to_string(),
+            prefix_all: None,
+            delete_identical: None,
+            quiet: false,
+            superseded_dir: None,
+            delete_superseded: false,
+            delete_mode: DeleteMode::default(),
+            size_tiebreak: false,
+            symlinks: None,
+            follow_symlinks: false,
+            verify: Verify::default(),
+            force: false,
+            sorted: false,
+            interactive_conflicts: false,
+            decide_conflicts: false,
+            conflict_prompt_limit: DEFAULT_CONFLICT_PROMPT_LIMIT,
+            report: None,
+            report_path: None,
+            metrics: Metrics::default(),
         }
     }
 
-    async fn run(&self) -> Result<()> {
+    fn configure(&mut self) -> Result<()> {
         let input_dir = get_directory_from_user("Enter directory to flatten")?;
         
-        let options = vec!["Rename duplicates", "Skip duplicates"];
+        let options = vec![
+            "Rename duplicates",
+            "Skip duplicates",
+            "Prefix with original path",
+            "Skip identical copies, rename different files",
+            "Keep the newest, discard older versions",
+            "Decide each collision myself",
+        ];
         let handle_duplicates = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("How to handle duplicate filenames?")
             .items(&options)
@@ -42,134 +484,745 @@ impl FileOrganizer for DirectoryFlattener {
         let handle_duplicates = match handle_duplicates {
             0 => DuplicateHandling::Rename,
             1 => DuplicateHandling::Skip,
+            2 => DuplicateHandling::Prefix {
+                all: match self.prefix_all {
+                    Some(all) => all,
+                    None => Confirm::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Prefix every file, not only those with colliding names?")
+                        .default(false)
+                        .interact()?,
+                },
+            },
+            3 => DuplicateHandling::SkipIdentical,
+            4 => DuplicateHandling::NewestWins {
+                size_tiebreak: self.size_tiebreak,
+            },
+            5 => {
+                self.decide_conflicts = true;
+                DuplicateHandling::Rename
+            }
             _ => unreachable!(),
         };
 
-        let spinner = create_spinner("Flattening directory...");
-        
-        match handle_duplicates {
-            DuplicateHandling::Rename => self.flatten_with_rename(&input_dir)?,
-            DuplicateHandling::Skip => self.flatten_with_skip(&input_dir)?,
+        // Only an output directory given without `--copy` leaves the mode
+        // open; otherwise files move within the tree as before
+        let mode = match self.mode {
+            Some(mode) => mode,
+            None if self.output_dir.is_none() => TransferMode::Move,
+            None => {
+                let mode_options = vec![
+                    "Move files into the output directory",
+                    "Copy files into the output directory",
+                ];
+                let mode_selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select operation mode")
+                    .items(&mode_options)
+                    .default(0)
+                    .interact()?;
+                match mode_selection {
+                    0 => TransferMode::Move,
+                    1 => TransferMode::Copy,
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        let root = match &self.output_dir {
+            Some(dir) => dir.clone(),
+            None if mode == TransferMode::Copy => {
+                let dir: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Output directory for the flat copy")
+                    .interact_text()?;
+                expand_path(dir.trim())?
+            }
+            None => input_dir.clone(),
+        };
+        if root.exists() && !root.is_dir() {
+            anyhow::bail!("Output {} is not a directory", root.display());
         }
 
-        spinner.finish_with_message("Directory flattening completed!");
+        // Files losing a collision: older versions, or root files the user
+        // chose to replace
+        let newest_wins = matches!(handle_duplicates, DuplicateHandling::NewestWins { .. });
+        if newest_wins && self.superseded_dir.is_none() && !self.delete_superseded {
+            let discard_options = vec!["Move them into superseded/ for review", "Delete them"];
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("What to do with the older versions?")
+                .items(&discard_options)
+                .default(0)
+                .interact()?;
+            match selection {
+                0 => self.superseded_dir = Some(root.join("superseded")),
+                1 => self.delete_superseded = true,
+                _ => unreachable!(),
+            }
+        }
+
+        self.input_dir = Some(input_dir);
+        self.handle_duplicates = Some(handle_duplicates);
+        self.mode = Some(mode);
+        self.output_dir = Some(root);
         Ok(())
     }
 
-    fn is_recursive(&self) -> bool {
-        self.recursive
+    fn plan(&mut self) -> Result<FlattenJob> {
+        let mut options = self.options()?;
+        let spinner = create_spinner("Scanning for files to flatten...");
+        let walk = scan(&options, &self.metrics);
+        spinner.finish_and_clear();
+
+        if self.extensions.is_empty() && self.include.is_empty() && self.exclude.is_empty() {
+            let files: Vec<PathBuf> = walk.files.iter().map(|entry| entry.path.clone()).collect();
+            options.extensions = prompt_extensions(&extension_counts(&files, options.root()))?;
+        }
+        let links = walk
+            .files
+            .iter()
+            .filter(|entry| matches_extension(&entry.path, &options.extensions) && is_symlink(&entry.path))
+            .count();
+        options.symlinks = match self.symlinks {
+            _ if links == 0 => SymlinkPolicy::Skip,
+            Some(policy) => policy,
+            None => {
+                let symlink_options = vec![
+                    "Skip symlinks",
+                    "Move symlinks, rewriting relative targets",
+                    "Replace symlinks with copies of their targets",
+                ];
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("Found {} symlinks to files. How to handle them?", links))
+                    .items(&symlink_options)
+                    .default(0)
+                    .interact()?;
+                match selection {
+                    0 => SymlinkPolicy::Skip,
+                    1 => SymlinkPolicy::Move,
+                    2 => SymlinkPolicy::Copy,
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        let root = options.root().to_path_buf();
+        let mut job = build_job(&options, walk, |files| {
+            let collisions = FlattenPlan::collision_count(&root, files, options.names);
+            // A policy the user picked is applied as is
+            let interactive = collisions > 0
+                && (self.interactive_conflicts
+                    || (self.decide_conflicts && collisions <= self.conflict_prompt_limit));
+            if !interactive {
+                return Ok(FlattenPlan::build(
+                    &options.input_dir,
+                    &root,
+                    files,
+                    options.duplicates,
+                    &options.prefix_separator,
+                    options.names,
+                ));
+            }
+            println!("{} name collisions to resolve", collisions);
+            let mut remembered = None;
+            FlattenPlan::build_interactive(&root, files, options.names, |incoming, existing| match remembered {
+                Some(resolution) => Ok(resolution),
+                None => {
+                    let (resolution, apply_to_all) = prompt_resolution(incoming, existing)?;
+                    if apply_to_all {
+                        remembered = Some(resolution);
+                    }
+                    Ok(resolution)
+                }
+            })
+        })?;
+        // Copying leaves the extra copies where they are anyway
+        if self.delete_identical.is_none() && options.mode == TransferMode::Move && !job.plan.identical.is_empty() {
+            job.delete_identical = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Delete {} identical extra copies?", job.plan.identical.len()))
+                .default(false)
+                .interact()?;
+        }
+        if !job.plan.moves.is_empty() {
+            job.report = self.report_path(&options.input_dir)?;
+        }
+        Ok(job)
+    }
+
+    fn review(&self, job: &FlattenJob) -> Result<bool> {
+        if !self.dry_run {
+            return Ok(true);
+        }
+        job.plan.print_table(&job.input_dir, &job.root, job.mode);
+        print_estimates(&job.space);
+        let proceed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Flatten as shown?")
+            .default(false)
+            .interact()?;
+        if !proceed {
+            println!("Dry run finished, no files were moved");
+        }
+        Ok(proceed)
+    }
+
+    fn execute(&self, job: FlattenJob) -> Result<Summary> {
+        preflight(&job.space, self.force)?;
+        let started = Instant::now();
+        let mut progress = FlattenProgress::new(job.plan.moves.len(), self.quiet);
+        self.metrics.phase(match job.mode {
+            TransferMode::Move => "moving",
+            TransferMode::Copy => "copying",
+        });
+        let (journal, result) = run(&self.options()?, &job, |m| {
+            self.metrics.add(1, 0);
+            progress.advance(m.source.strip_prefix(&job.input_dir).unwrap_or(&m.source))
+        });
+        progress.finish();
+        write_journal(&journal)?;
+        let bytes = result?;
+        self.metrics.add(0, bytes);
+
+        let summary = job.summary(bytes, journal.removed_dirs.len(), started.elapsed());
+        summary.print();
+        if !job.repositories.is_empty() {
+            println!("Use --include-repos to flatten the skipped repositories");
+        }
+        print_inaccessible(&job.inaccessible, "they were left where they are");
+        if let Some(path) = &job.report {
+            journal.write_csv(path)?;
+            println!("Report written to {}", path.display());
+        }
+        Ok(Summary { phases: self.metrics.finish(), ..Summary::from(&summary) })
     }
+}
 
-    fn get_input_dir(&self) -> Option<&PathBuf> {
-        self.input_dir.as_ref()
+impl DirectoryFlattener {
+    /// Flattens working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
     }
 
-    fn set_input_dir(&mut self, dir: PathBuf) {
-        self.input_dir = Some(dir);
+    /// Shows the planned moves and asks before flattening
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 
-    fn process_file(&self, file: &PathBuf) -> Result<()> {
-        if let Some(input_dir) = &self.input_dir {
-            if file.parent() == Some(input_dir.as_path()) {
-                return Ok(()); // Skip files already in root
-            }
+    /// Copies instead of moving, leaving the input tree intact
+    pub fn with_mode(mut self, mode: TransferMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
 
-            let filename = file.file_name().unwrap().to_string_lossy().to_string();
-            match self.handle_duplicates {
-                Some(DuplicateHandling::Rename) => {
-                    let mut counter = 1;
-                    let mut new_path = input_dir.join(&filename);
-                    
-                    while new_path.exists() {
-                        let stem = file.file_stem().unwrap().to_string_lossy();
-                        let ext = file.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
-                        let new_filename = if ext.is_empty() {
-                            format!("{}-{}", stem, counter)
-                        } else {
-                            format!("{}-{}.{}", stem, counter, ext)
-                        };
-                        new_path = input_dir.join(new_filename);
-                        counter += 1;
-                    }
-                    
-                    fs::rename(file, new_path)?;
-                }
-                Some(DuplicateHandling::Skip) => {
-                    let new_path = input_dir.join(&filename);
-                    if !new_path.exists() {
-                        fs::rename(file, new_path)?;
-                    }
-                }
-                None => {}
+    /// Only flattens files at most `depth` levels below the input, so 2
+    /// pulls files out of the immediate subfolders and leaves deeper ones
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// The configured settings as library options, with the extension
+    /// filter, symlink policy, and deletion of identical copies as preset;
+    /// `plan` asks for those that are not
+    fn options(&self) -> Result<FlattenOptions> {
+        let (Some(input_dir), Some(mode), Some(duplicates)) = (&self.input_dir, self.mode, self.handle_duplicates)
+        else {
+            anyhow::bail!("Flattening not configured");
+        };
+        Ok(FlattenOptions {
+            output_dir: self.output_dir.clone(),
+            mode,
+            max_depth: self.depth_limit(),
+            duplicates,
+            prefix_separator: self.prefix_separator.clone(),
+            extensions: self.extensions.clone(),
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            symlinks: self.symlinks.unwrap_or(SymlinkPolicy::Skip),
+            follow_symlinks: self.follow_symlinks,
+            include_repos: self.include_repos,
+            sorted: self.sorted,
+            // Older versions and replaced files go where `configure` settled
+            superseded_dir: self.superseded_dir.clone().filter(|_| !self.delete_superseded),
+            delete_identical: self.delete_identical.unwrap_or(false),
+            delete_mode: self.delete_mode,
+            keep_empty_dirs: self.keep_empty_dirs,
+            junk: JunkList::load()?,
+            names: Normalization::load()?,
+            verify: self.verify,
+            manifest_dir: None,
+            ..FlattenOptions::new(input_dir.clone())
+        })
+    }
+
+    fn depth_limit(&self) -> Option<usize> {
+        match self.max_depth {
+            Some(depth) => Some(depth),
+            None if !self.recursive => Some(2),
+            None => None,
+        }
+    }
+
+    /// Flattens into `dir` instead of the input directory
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    /// Restricts flattening to files with the given extensions, e.g. "mp3"
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        self
+    }
+
+    /// Only flattens files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.pdf" or "scans/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` in place
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Joins path components with `separator` in the prefix naming scheme
+    pub fn with_prefix_separator(mut self, separator: &str) -> Result<Self> {
+        if separator.is_empty() || folder_name(separator).as_deref() != Some(separator) {
+            anyhow::bail!("Separator {:?} cannot be used in file names", separator);
+        }
+        self.prefix_separator = separator.to_string();
+        Ok(self)
+    }
+
+    /// Prefixes every file with its original path, not only colliding names
+    pub fn with_prefix_all(mut self, all: bool) -> Self {
+        self.prefix_all = Some(all);
+        self
+    }
+
+    /// Deletes identical extra copies, or leaves them in place
+    pub fn with_delete_identical(mut self, delete: bool) -> Self {
+        self.delete_identical = Some(delete);
+        self
+    }
+
+    /// Asks about every name collision, however many there are
+    pub fn with_interactive_conflicts(mut self, interactive: bool) -> Self {
+        self.interactive_conflicts = interactive;
+        self
+    }
+
+    /// Resolves collisions at the prompt, when deciding each one was
+    /// chosen, only if there are at most `limit` of them; 0 always renames
+    pub fn with_conflict_prompt_limit(mut self, limit: usize) -> Self {
+        self.conflict_prompt_limit = limit;
+        self
+    }
+
+    /// Moves older versions and replaced files into `dir` without asking
+    pub fn with_superseded_dir(mut self, dir: PathBuf) -> Self {
+        self.superseded_dir = Some(dir);
+        self
+    }
+
+    /// Deletes older versions and replaced files without asking
+    pub fn with_delete_superseded(mut self, delete: bool) -> Self {
+        self.delete_superseded = delete;
+        self
+    }
+
+    /// Trashes, permanently deletes, or only lists the files the run
+    /// deletes
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Keeps the larger file when two versions have the same mtime
+    pub fn with_size_tiebreak(mut self, tiebreak: bool) -> Self {
+        self.size_tiebreak = tiebreak;
+        self
+    }
+
+    /// Skips, moves, or replaces symlinks to files without asking
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = Some(policy);
+        self
+    }
+
+    /// Flattens the contents of symlinked directories too
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Sets how moves to another filesystem check the copy before deleting
+    /// the source
+    pub fn with_verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Walks in name order, so the file that keeps a contested name and the
+    /// numbering of the others are the same on every run
+    pub fn with_sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Copies even when the output filesystem looks too small, instead of
+    /// refusing
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Logs progress every 5% instead of drawing a progress bar
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Writes a CSV report of the moves, to `path` or next to the input,
+    /// without asking
+    pub fn with_report(mut self, path: Option<PathBuf>) -> Self {
+        self.report = Some(true);
+        self.report_path = path;
+        self
+    }
+
+    /// Where to write the CSV move report: the preset choice, or asked
+    /// for, defaulting to a timestamped file in the input directory
+    fn report_path(&self, input_dir: &Path) -> Result<Option<PathBuf>> {
+        let write = match self.report {
+            Some(write) => write,
+            None => Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Write a CSV report of the moves?")
+                .default(false)
+                .interact()?,
+        };
+        if !write {
+            return Ok(None);
+        }
+        Ok(Some(self.report_path.clone().unwrap_or_else(|| {
+            input_dir.join(format!("flatten-report-{}.csv", Local::now().format("%Y%m%d-%H%M%S")))
+        })))
+    }
+
+    /// Leaves the directories emptied by flattening in place
+    pub fn with_keep_empty_dirs(mut self, keep: bool) -> Self {
+        self.keep_empty_dirs = keep;
+        self
+    }
+}
+
+/// Shows both sides of a name collision and asks how to resolve it,
+/// returning the choice and whether it applies to all remaining ones
+fn prompt_resolution(incoming: &Path, existing: &Path) -> Result<(Resolution, bool)> {
+    let describe = |path: &Path| -> String {
+        match fs::metadata(path) {
+            Ok(metadata) => {
+                let modified = metadata
+                    .modified()
+                    .map(|time| DateTime::<Local>::from(time).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                format!("{:>10}  {}", format_size(metadata.len(), BINARY), modified)
             }
+            Err(_) => "unreadable".to_string(),
         }
-        Ok(())
+    };
+    println!();
+    println!("Name collision: {}", incoming.file_name().unwrap_or_default().to_string_lossy());
+    println!("  incoming  {}  {}", describe(incoming), incoming.display());
+    println!("  existing  {}  {}", describe(existing), existing.display());
+
+    const RESOLUTIONS: [Resolution; 4] = [
+        Resolution::KeepBoth,
+        Resolution::KeepNewest,
+        Resolution::KeepLargest,
+        Resolution::Skip,
+    ];
+    let options = vec![
+        "Keep both (rename the incoming file)",
+        "Keep the newest",
+        "Keep the largest",
+        "Skip the incoming file",
+        "Keep both for all remaining collisions",
+        "Keep the newest for all remaining collisions",
+        "Keep the largest for all remaining collisions",
+        "Skip all remaining collisions",
+    ];
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("How to resolve this collision?")
+        .items(&options)
+        .default(0)
+        .interact()?;
+    Ok((RESOLUTIONS[selection % RESOLUTIONS.len()], selection >= RESOLUTIONS.len()))
+}
+
+fn matches_extension(file: &Path, extensions: &[String]) -> bool {
+    extensions.is_empty()
+        || file
+            .extension()
+            .is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+}
+
+/// Asks whether to flatten only some extensions, listing those among the
+/// files to flatten with their counts, when there are several. An empty
+/// result flattens everything.
+fn prompt_extensions(counts: &[(String, usize)]) -> Result<Vec<String>> {
+    if counts.len() < 2 {
+        return Ok(Vec::new());
     }
 
-    fn create_directories(&self, _base_dir: &PathBuf) -> Result<()> {
-        // No additional directories needed for flattening
-        Ok(())
+    let filter = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Filter by extension?")
+        .default(false)
+        .interact()?;
+    if !filter {
+        return Ok(Vec::new());
     }
+
+    let items: Vec<String> = counts
+        .iter()
+        .map(|(ext, count)| format!("{} ({})", ext, count))
+        .collect();
+    let selected = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Extensions to flatten")
+        .items(&items)
+        .interact()?;
+    Ok(selected.into_iter().map(|i| counts[i].0.clone()).collect())
 }
 
-impl DirectoryFlattener {
-    fn flatten_with_rename(&self, dir: &PathBuf) -> Result<()> {
-        let mut filename_count: HashMap<String, u32> = HashMap::new();
+/// How many files of each extension would be flattened, most common first.
+/// Files already directly in `root` stay put, so they are not counted.
+fn extension_counts(files: &[PathBuf], root: &Path) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for file in files.iter().filter(|file| file.parent() != Some(root)) {
+        if let Some(ext) = file.extension() {
+            *counts.entry(ext.to_string_lossy().to_lowercase()).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
 
-        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-            if !entry.file_type().is_file() {
-                continue;
-            }
+/// Saves the journal to the data directory and prints how to undo the run
+fn write_journal(journal: &Journal) -> Result<()> {
+    if journal.entries.is_empty() {
+        return Ok(());
+    }
 
-            let path = entry.path();
-            if path.parent() == Some(dir.as_path()) {
-                continue; // Skip files already in root
-            }
+    let path = Journal::default_dir()?.join(journal.file_name());
+    journal.save(&path)?;
+    println!("Undo with: file-organizer-rust undo --manifest {}", path.display());
+    Ok(())
+}
 
-            let filename = path.file_name().unwrap().to_string_lossy().to_string();
-            let count = filename_count.entry(filename.clone()).or_insert(0);
-            *count += 1;
-
-            let new_filename = if *count > 1 {
-                let stem = path.file_stem().unwrap().to_string_lossy();
-                let ext = path.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
-                if ext.is_empty() {
-                    format!("{}-{}", stem, count)
-                } else {
-                    format!("{}-{}.{}", stem, count, ext)
-                }
-            } else {
-                filename
-            };
+/// Removes the directories in `emptied`, which this run took files out of,
+/// once they are empty, deepest first, then their parents below `root`
+/// left empty by that. Directories still holding skipped duplicates or
+/// hidden files are kept, and others are never looked at. Returns the
+/// removed directories.
+fn remove_empty_dirs(root: &Path, emptied: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+    let mut candidates: Vec<&Path> = emptied
+        .iter()
+        .flat_map(|dir| dir.ancestors().take_while(|ancestor| *ancestor != root && ancestor.starts_with(root)))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    candidates.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+
+    // remove_dir refuses directories that are not empty
+    candidates
+        .into_iter()
+        .filter(|dir| fs::remove_dir(dir).is_ok())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::{OsStr, OsString};
 
-            let new_path = dir.join(&new_filename);
-            fs::rename(path, new_path)?;
+    #[test]
+    fn only_files_to_flatten_are_counted_by_extension() {
+        let root = Path::new("/photos");
+        let files: Vec<PathBuf> =
+            ["/photos/top.pdf", "/photos/a/x.JPG", "/photos/a/b/y.jpg", "/photos/a/z.pdf", "/photos/a/README"]
+                .iter()
+                .map(PathBuf::from)
+                .collect();
+        assert_eq!(extension_counts(&files, root), [("jpg".to_string(), 2), ("pdf".to_string(), 1)]);
+        // A flat copy takes root files too
+        assert_eq!(extension_counts(&files, Path::new("/out"))[1], ("pdf".to_string(), 2));
+    }
+
+    #[test]
+    fn only_directories_the_run_emptied_are_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        for dir in ["a/b", "a/c", "kept", "untouched"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
         }
-        Ok(())
+        fs::write(root.join("kept/.hidden"), "").unwrap();
+
+        let emptied: BTreeSet<PathBuf> = [root.join("a/b"), root.join("a/c"), root.join("kept")].into();
+        let removed = remove_empty_dirs(root, &emptied);
+        assert_eq!(removed.len(), 3);
+        assert!(!root.join("a").exists());
+        assert!(root.join("kept/.hidden").exists());
+        // Empty before the run and never touched by it
+        assert!(root.join("untouched").is_dir());
     }
 
-    fn flatten_with_skip(&self, dir: &PathBuf) -> Result<()> {
-        let mut existing_files: HashMap<String, bool> = HashMap::new();
+    #[test]
+    fn a_root_file_with_the_same_name_is_never_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("report.pdf"), "root").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/report.pdf"), "sub").unwrap();
 
-        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-            if !entry.file_type().is_file() {
-                continue;
-            }
+        let summary = execute(&FlattenOptions::new(dir.path().to_path_buf()), ()).unwrap();
+        assert_eq!((summary.files, summary.renamed), (1, 1));
+        assert_eq!(fs::read_to_string(dir.path().join("report.pdf")).unwrap(), "root");
+        assert_eq!(fs::read_to_string(dir.path().join("report-2.pdf")).unwrap(), "sub");
+    }
 
-            let path = entry.path();
-            if path.parent() == Some(dir.as_path()) {
-                let filename = path.file_name().unwrap().to_string_lossy().to_string();
-                existing_files.insert(filename, true);
-                continue;
-            }
+    #[test]
+    fn files_below_the_depth_limit_stay_where_they_are() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        for file in ["top.txt", "a/two.txt", "a/b/three.txt", "a/b/c/four.txt"] {
+            fs::write(root.join(file), file).unwrap();
+        }
 
-            let filename = path.file_name().unwrap().to_string_lossy().to_string();
-            if !existing_files.contains_key(&filename) {
-                let new_path = dir.join(&filename);
-                fs::rename(path, new_path)?;
-                existing_files.insert(filename, true);
-            }
+        let options = FlattenOptions { max_depth: Some(2), ..FlattenOptions::new(root.to_path_buf()) };
+        let summary = execute(&options, ()).unwrap();
+        assert_eq!(summary.files, 1);
+        assert_eq!(fs::read_to_string(root.join("two.txt")).unwrap(), "a/two.txt");
+        assert!(root.join("top.txt").exists());
+        assert!(root.join("a/b/three.txt").exists());
+        assert!(root.join("a/b/c/four.txt").exists());
+        assert!(!root.join("three.txt").exists() && !root.join("four.txt").exists());
+    }
+
+    /// `input/docs/guide.txt` with links to it and to `outside.txt` next to
+    /// the input, both relative, under `input/links`
+    #[cfg(unix)]
+    fn linked_tree(dir: &Path) -> PathBuf {
+        let input = dir.join("input");
+        fs::create_dir_all(input.join("docs")).unwrap();
+        fs::create_dir(input.join("links")).unwrap();
+        fs::write(input.join("docs/guide.txt"), "guide").unwrap();
+        fs::write(dir.join("outside.txt"), "outside").unwrap();
+        std::os::unix::fs::symlink("../docs/guide.txt", input.join("links/inside")).unwrap();
+        std::os::unix::fs::symlink("../../outside.txt", input.join("links/outside")).unwrap();
+        input
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn moved_relative_links_still_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = linked_tree(dir.path());
+
+        let options = FlattenOptions { symlinks: SymlinkPolicy::Move, ..FlattenOptions::new(input.clone()) };
+        execute(&options, ()).unwrap();
+        for (link, contents) in [("inside", "guide"), ("outside", "outside")] {
+            assert!(is_symlink(&input.join(link)), "{} is not a link", link);
+            assert_eq!(fs::read_to_string(input.join(link)).unwrap(), contents);
         }
-        Ok(())
+        assert_eq!(fs::read_link(input.join("inside")).unwrap(), Path::new("guide.txt"));
+        assert_eq!(fs::read_link(input.join("outside")).unwrap(), Path::new("../outside.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copied_links_become_their_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = linked_tree(dir.path());
+
+        let options = FlattenOptions { symlinks: SymlinkPolicy::Copy, ..FlattenOptions::new(input.clone()) };
+        execute(&options, ()).unwrap();
+        for (link, contents) in [("inside", "guide"), ("outside", "outside")] {
+            assert!(!is_symlink(&input.join(link)));
+            assert_eq!(fs::read_to_string(input.join(link)).unwrap(), contents);
+        }
+        assert_eq!(fs::read_to_string(dir.path().join("outside.txt")).unwrap(), "outside");
     }
-} 
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn skipped_links_stay_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = linked_tree(dir.path());
+
+        let summary = execute(&FlattenOptions::new(input.clone()), ()).unwrap();
+        assert_eq!((summary.files, summary.symlinks_skipped), (1, 2));
+        assert!(is_symlink(&input.join("links/inside")) && is_symlink(&input.join("links/outside")));
+        assert!(input.join("guide.txt").exists());
+    }
+
+    /// Names of the files directly in `dir`, as stored
+    fn names(dir: &Path) -> BTreeSet<OsString> {
+        fs::read_dir(dir).unwrap().flatten().filter(|e| e.path().is_file()).map(|e| e.file_name()).collect()
+    }
+
+    #[test]
+    fn composed_and_decomposed_spellings_of_a_name_collide() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("mac")).unwrap();
+        fs::write(root.join("caf\u{e9}.txt"), "linux").unwrap();
+        fs::write(root.join("mac/cafe\u{301}.txt"), "mac").unwrap();
+
+        let summary = execute(&FlattenOptions::new(root.to_path_buf()), ()).unwrap();
+        assert_eq!((summary.files, summary.renamed), (1, 1));
+        // The renamed file is written composed, like the name it clashed with
+        let expected: BTreeSet<OsString> = ["caf\u{e9}.txt", "caf\u{e9}-2.txt"].map(OsString::from).into();
+        assert_eq!(names(root), expected);
+        assert_eq!(fs::read_to_string(root.join("caf\u{e9}-2.txt")).unwrap(), "mac");
+    }
+
+    #[test]
+    fn decomposed_names_are_kept_when_normalization_is_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("mac")).unwrap();
+        fs::write(root.join("mac/cafe\u{301}.txt"), "mac").unwrap();
+
+        let options = FlattenOptions { names: Normalization::None, ..FlattenOptions::new(root.to_path_buf()) };
+        execute(&options, ()).unwrap();
+        assert_eq!(names(root), [OsString::from("cafe\u{301}.txt")].into());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_are_not_utf8_keep_their_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let latin1 = OsStr::from_bytes(b"r\xe9sum\xe9.txt");
+        let other = OsStr::from_bytes(b"r\xe8sum\xe8.txt");
+        for (sub, name) in [("a", latin1), ("b", other)] {
+            fs::create_dir(root.join(sub)).unwrap();
+            fs::write(root.join(sub).join(name), sub).unwrap();
+        }
+
+        let summary = execute(&FlattenOptions::new(root.to_path_buf()), ()).unwrap();
+        // Lossy conversion would have made both the same name
+        assert_eq!((summary.files, summary.renamed), (2, 0));
+        assert_eq!(names(root), [latin1.to_os_string(), other.to_os_string()].into());
+        assert_eq!(fs::read_to_string(root.join(latin1)).unwrap(), "a");
+    }
+
+}
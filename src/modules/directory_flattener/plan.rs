@@ -0,0 +1,713 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    symlink::{transfer_symlink, SymlinkPolicy},
+    DuplicateHandling,
+};
+use crate::utils::{
+    delete::{safe_delete, safe_delete_all, DeleteMode},
+    hashing::same_contents,
+    journal::Journal,
+    links::{absolute, is_symlink},
+    names::{escaped_name, is_same_file, numbered_name, NameSet, Normalization},
+    sanitize::{folder_name, safe_os_name},
+    transfer::{move_file, replace_with, transfer_file, TransferMode, Verify},
+};
+
+/// One file moved or copied into the flatten root
+#[derive(Serialize)]
+pub struct FlattenMove {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    /// Renamed because another file has the same name
+    pub renamed: bool,
+    /// File already in the root this one takes the place of, discarded
+    /// once the move succeeds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaces: Option<PathBuf>,
+    /// Older versions from subfolders this one won over, discarded once
+    /// the move succeeds
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub supersedes: Vec<PathBuf>,
+}
+
+/// Every move of a flatten run, computed before anything is touched. The
+/// dry run prints it and the real run executes it, so the two cannot
+/// drift apart.
+#[derive(Default, Serialize)]
+pub struct FlattenPlan {
+    pub moves: Vec<FlattenMove>,
+    /// Files left in place because their name is already taken
+    pub skipped: Vec<PathBuf>,
+    /// Extra copies of a file with the same name and contents
+    pub identical: Vec<PathBuf>,
+    /// Older versions from subfolders that lost to a newer file already
+    /// in the root, which stays
+    pub superseded_by_root: Vec<PathBuf>,
+    /// Unicode form of the names given to moved and discarded files
+    #[serde(skip)]
+    pub names: Normalization,
+}
+
+/// Where files that lose a collision go
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Discard {
+    /// Delete the way the `--delete-mode` asks
+    Delete(DeleteMode),
+    /// Move into this folder for review
+    MoveTo(PathBuf),
+}
+
+/// How the user resolved one name collision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Move both, renaming the incoming file
+    KeepBoth,
+    /// Keep the file with the newer mtime, leaving the other out
+    KeepNewest,
+    /// Keep the larger file, leaving the other out
+    KeepLargest,
+    /// Leave the incoming file where it is
+    Skip,
+}
+
+/// Current owner of a name in the root
+enum Holder {
+    Existing(PathBuf),
+    /// Index into the planned moves
+    Planned(usize),
+}
+
+impl FlattenPlan {
+    /// Plans flattening `files` into `root`, which is the walked directory
+    /// itself or a separate output directory, resolving repeated names
+    /// according to `handling`. Files already directly in `root` stay put.
+    /// Path prefixes are relative to `input` and joined with `separator`.
+    /// New names are written in the Unicode form `names`.
+    pub fn build(
+        input: &Path,
+        root: &Path,
+        files: &[PathBuf],
+        handling: DuplicateHandling,
+        separator: &str,
+        names: Normalization,
+    ) -> Self {
+        let mut plan = Self { names, ..Self::default() };
+        let flat_name = |path: &Path| flat_name(path, names);
+        let candidates = files.iter().filter(|path| path.parent() != Some(root));
+        // Names already in the root or planned, so nothing is overwritten
+        let mut used = NameSet::existing(root);
+        match handling {
+            DuplicateHandling::Rename => {
+                for path in candidates {
+                    let filename = flat_name(path);
+                    let target_name = free_name(&filename, &mut used);
+                    plan.moves.push(FlattenMove {
+                        source: path.clone(),
+                        target: root.join(&target_name),
+                        renamed: target_name != filename,
+                        replaces: None,
+                        supersedes: Vec::new(),
+                    });
+                }
+            }
+            DuplicateHandling::Skip => {
+                // Files already in the root keep their names
+                for path in candidates {
+                    let filename = flat_name(path);
+                    if used.insert(&filename) {
+                        plan.moves.push(FlattenMove {
+                            source: path.clone(),
+                            target: root.join(filename),
+                            renamed: false,
+                            replaces: None,
+                            supersedes: Vec::new(),
+                        });
+                    } else {
+                        plan.skipped.push(path.clone());
+                    }
+                }
+            }
+            DuplicateHandling::NewestWins { size_tiebreak } => {
+                let newer = |a: &Path, b: &Path| {
+                    let (a_time, b_time) = (modified(a), modified(b));
+                    a_time > b_time || (size_tiebreak && a_time == b_time && file_size(a) > file_size(b))
+                };
+                let mut holders: HashMap<String, Holder> = used
+                    .names()
+                    .map(|name| (used.key(name), Holder::Existing(root.join(name))))
+                    .collect();
+                let mut dropped = HashSet::new();
+                for path in candidates {
+                    let filename = flat_name(path);
+                    let mut supersedes = Vec::new();
+                    let replaces = match holders.get(&used.key(&filename)) {
+                        None => None,
+                        Some(Holder::Existing(existing)) if newer(path, existing) => Some(existing.clone()),
+                        Some(Holder::Existing(_)) => {
+                            plan.superseded_by_root.push(path.clone());
+                            continue;
+                        }
+                        Some(Holder::Planned(index)) => {
+                            let beaten = &mut plan.moves[*index];
+                            if !newer(path, &beaten.source) {
+                                beaten.supersedes.push(path.clone());
+                                continue;
+                            }
+                            dropped.insert(*index);
+                            // What the beaten file would have replaced or
+                            // superseded goes to the new winner
+                            supersedes = std::mem::take(&mut beaten.supersedes);
+                            supersedes.push(beaten.source.clone());
+                            beaten.replaces.take()
+                        }
+                    };
+                    holders.insert(used.key(&filename), Holder::Planned(plan.moves.len()));
+                    plan.moves.push(FlattenMove {
+                        source: path.clone(),
+                        target: root.join(filename),
+                        renamed: false,
+                        replaces,
+                        supersedes,
+                    });
+                }
+                plan.moves = plan
+                    .moves
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| !dropped.contains(index))
+                    .map(|(_, m)| m)
+                    .collect();
+            }
+            DuplicateHandling::SkipIdentical => {
+                // Every file holding or planned for a name, the existing
+                // root file first
+                let mut holders: HashMap<String, Vec<PathBuf>> = used
+                    .names()
+                    .map(|name| (used.key(name), vec![root.join(name)]))
+                    .collect();
+                for path in candidates {
+                    let filename = flat_name(path);
+                    let group = holders.entry(used.key(&filename)).or_default();
+                    // A file that cannot be read is renamed rather than dropped
+                    if group.iter().any(|other| same_contents(path, other).unwrap_or(false)) {
+                        plan.identical.push(path.clone());
+                        continue;
+                    }
+
+                    group.push(path.clone());
+                    let target_name = free_name(&filename, &mut used);
+                    plan.moves.push(FlattenMove {
+                        source: path.clone(),
+                        target: root.join(&target_name),
+                        renamed: target_name != filename,
+                        replaces: None,
+                        supersedes: Vec::new(),
+                    });
+                }
+            }
+            DuplicateHandling::Prefix { all } => {
+                let mut filename_count: HashMap<String, u32> = HashMap::new();
+                for path in candidates.clone() {
+                    *filename_count.entry(used.key(&flat_name(path))).or_insert(0) += 1;
+                }
+
+                let existing = used.clone();
+                for path in candidates {
+                    let filename = flat_name(path);
+                    let collides = filename_count[&used.key(&filename)] > 1 || existing.contains(&filename);
+                    let name = if all || collides {
+                        prefixed_name(input, path, separator, names)
+                    } else {
+                        filename.clone()
+                    };
+
+                    // Different paths can still collapse into the same name,
+                    // e.g. a_b/c.jpg and a/b_c.jpg
+                    let target_name = free_name(&name, &mut used);
+
+                    plan.moves.push(FlattenMove {
+                        source: path.clone(),
+                        target: root.join(&target_name),
+                        renamed: collides || target_name != name,
+                        replaces: None,
+                        supersedes: Vec::new(),
+                    });
+                }
+            }
+        }
+        plan
+    }
+
+    /// Plans flattening `files` into `root` like [`FlattenPlan::build`], but
+    /// lets `resolve` decide each name collision given the incoming file and
+    /// the one currently holding the name. A losing file already in the
+    /// root is replaced; one from a subfolder stays where it is.
+    pub fn build_interactive(
+        root: &Path,
+        files: &[PathBuf],
+        names: Normalization,
+        mut resolve: impl FnMut(&Path, &Path) -> Result<Resolution>,
+    ) -> Result<Self> {
+        let mut plan = Self { names, ..Self::default() };
+        let flat_name = |path: &Path| flat_name(path, names);
+        let mut used = NameSet::existing(root);
+        let mut holders: HashMap<String, Holder> = used
+            .names()
+            .map(|name| (used.key(name), Holder::Existing(root.join(name))))
+            .collect();
+        let mut dropped = HashSet::new();
+
+        for path in files.iter().filter(|path| path.parent() != Some(root)) {
+            let filename = flat_name(path);
+            let Some(holder) = holders.get(&used.key(&filename)) else {
+                used.insert(&filename);
+                holders.insert(used.key(&filename), Holder::Planned(plan.moves.len()));
+                plan.moves.push(FlattenMove {
+                    source: path.clone(),
+                    target: root.join(filename),
+                    renamed: false,
+                    replaces: None,
+                    supersedes: Vec::new(),
+                });
+                continue;
+            };
+            let holder_path = match holder {
+                Holder::Existing(existing) => existing.clone(),
+                Holder::Planned(index) => plan.moves[*index].source.clone(),
+            };
+
+            let incoming_wins = match resolve(path, &holder_path)? {
+                Resolution::KeepBoth => {
+                    let target_name = free_name(&filename, &mut used);
+                    plan.moves.push(FlattenMove {
+                        source: path.clone(),
+                        target: root.join(target_name),
+                        renamed: true,
+                        replaces: None,
+                        supersedes: Vec::new(),
+                    });
+                    continue;
+                }
+                Resolution::Skip => false,
+                Resolution::KeepNewest => modified(path) > modified(&holder_path),
+                Resolution::KeepLargest => file_size(path) > file_size(&holder_path),
+            };
+            if !incoming_wins {
+                plan.skipped.push(path.clone());
+                continue;
+            }
+
+            let replaces = match holder {
+                Holder::Existing(existing) => Some(existing.clone()),
+                Holder::Planned(index) => {
+                    let index = *index;
+                    dropped.insert(index);
+                    plan.skipped.push(holder_path);
+                    plan.moves[index].replaces.take()
+                }
+            };
+            holders.insert(used.key(&filename), Holder::Planned(plan.moves.len()));
+            plan.moves.push(FlattenMove {
+                source: path.clone(),
+                target: root.join(filename),
+                renamed: false,
+                replaces,
+                supersedes: Vec::new(),
+            });
+        }
+
+        plan.moves = plan
+            .moves
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !dropped.contains(index))
+            .map(|(_, m)| m)
+            .collect();
+        Ok(plan)
+    }
+
+    /// Number of files whose name is already in the root or taken by an
+    /// earlier file, before any policy is applied
+    pub fn collision_count(root: &Path, files: &[PathBuf], names: Normalization) -> usize {
+        let mut used = NameSet::existing(root);
+        files
+            .iter()
+            .filter(|path| path.parent() != Some(root))
+            .filter(|path| !used.insert(&flat_name(path, names)))
+            .count()
+    }
+
+    /// Files already in the root that lose a collision to a moved file
+    pub fn replaced(&self) -> impl Iterator<Item = &PathBuf> {
+        self.moves.iter().filter_map(|m| m.replaces.as_ref())
+    }
+
+    /// Older versions that lose a collision to a newer file
+    pub fn superseded(&self) -> impl Iterator<Item = &PathBuf> {
+        self.moves.iter().flat_map(|m| &m.supersedes).chain(&self.superseded_by_root)
+    }
+
+    pub fn renamed_count(&self) -> usize {
+        self.moves.iter().filter(|m| m.renamed).count()
+    }
+
+    /// Prints `source → target` for every move, relative to the walked
+    /// `input` and the flatten `root`, then the skipped files and the totals
+    pub fn print_table(&self, input: &Path, root: &Path, mode: TransferMode) {
+        let relative = |path: &Path| {
+            let base = if path.parent() == Some(root) { root } else { input };
+            path.strip_prefix(base).unwrap_or(path).display().to_string()
+        };
+        let width = self
+            .moves
+            .iter()
+            .map(|m| relative(&m.source).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(60);
+
+        println!();
+        for m in &self.moves {
+            println!(
+                "{:<width$}  →  {}{}",
+                relative(&m.source),
+                relative(&m.target),
+                if m.renamed { "  (renamed)" } else { "" }
+            );
+        }
+        for path in &self.skipped {
+            println!("{:<width$}     skipped, name taken", relative(path));
+        }
+        for path in &self.identical {
+            println!("{:<width$}     identical copy, skipped", relative(path));
+        }
+        for path in self.replaced() {
+            println!("{:<width$}     replaced by the kept file", relative(path));
+        }
+        for path in self.superseded() {
+            println!("{:<width$}     superseded by a newer file", relative(path));
+        }
+        println!();
+        println!(
+            "{} files would be {} ({} renamed because of a name collision), {} skipped",
+            self.moves.len(),
+            mode.verb(),
+            self.renamed_count(),
+            self.skipped.len()
+        );
+        if !self.identical.is_empty() {
+            println!("{} identical copies would be skipped", self.identical.len());
+        }
+        let discarded = self.superseded().count() + self.replaced().count();
+        if discarded > 0 {
+            println!("{} older or replaced files would be discarded", discarded);
+        }
+    }
+
+    /// Performs the moves or copies, recording each in `journal`, and
+    /// returns the bytes transferred. Symlinks are handled according to
+    /// `symlinks`. `on_move` is called before each one.
+    pub fn execute(
+        &self,
+        mode: TransferMode,
+        verify: Verify,
+        symlinks: SymlinkPolicy,
+        discard: &Discard,
+        journal: &mut Journal,
+        mut on_move: impl FnMut(&FlattenMove),
+    ) -> Result<u64> {
+        // Copying leaves the source tree, older versions included, alone
+        let discard_superseded = mode == TransferMode::Move;
+        if discard_superseded {
+            for path in &self.superseded_by_root {
+                discard_file(path, path, discard, "superseded", verify, self.names, journal)?;
+            }
+        }
+        // Links go last, once the files they may point at have moved
+        let (links, files): (Vec<&FlattenMove>, Vec<&FlattenMove>) =
+            self.moves.iter().partition(|m| is_symlink(&m.source));
+        let mut relocated = HashMap::new();
+        let mut bytes = 0;
+        for m in files.into_iter().chain(links) {
+            on_move(m);
+            // The tree may have changed since planning
+            let replacing = m.replaces.as_deref().is_some_and(|replaced| is_same_file(replaced, &m.target));
+            if m.target.exists() && !replacing {
+                anyhow::bail!("{} already exists, not overwriting it", m.target.display());
+            }
+            let link = is_symlink(&m.source);
+            let transfer = || {
+                if link {
+                    transfer_symlink(&m.source, &m.target, mode, symlinks, &relocated)
+                } else {
+                    // Moves across filesystems fall back to copying; copies keep the source mtime
+                    transfer_file(&m.source, &m.target, mode, verify)
+                }
+            };
+            // A replaced root file is only discarded once its successor is in
+            bytes += match &m.replaces {
+                Some(replaced) => replace_with(replaced, &m.target, transfer, |aside| {
+                    discard_file(aside, replaced, discard, "replaced by the kept file", verify, self.names, journal)
+                })?,
+                None => transfer()?,
+            };
+            if mode == TransferMode::Move && !link {
+                relocated.insert(absolute(&m.source)?, absolute(&m.target)?);
+            }
+            let rule = if m.renamed { "renamed after collision" } else { "flatten" };
+            journal.record(&m.source, &m.target, Some(rule))?;
+            // Older versions go only once the newest is in place
+            if discard_superseded {
+                for path in &m.supersedes {
+                    discard_file(path, path, discard, "superseded", verify, self.names, journal)?;
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Deletes the identical extra copies the way `mode` asks, recording
+    /// them in `journal`
+    pub fn delete_identical(&self, mode: DeleteMode, journal: &mut Journal) -> Result<usize> {
+        let present: Vec<(&PathBuf, fs::Metadata)> =
+            self.identical.iter().filter_map(|path| Some((path, fs::metadata(path).ok()?))).collect();
+        let paths: Vec<&Path> = present.iter().map(|(path, _)| path.as_path()).collect();
+        let results = safe_delete_all(&paths, mode);
+        let mut first_error = None;
+        for ((path, metadata), result) in present.iter().zip(results) {
+            match result {
+                Ok(()) if mode != DeleteMode::DryRun => journal.record_deletion(path, metadata, Some("identical copy")),
+                Ok(()) => {}
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(present.len()),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Deletes `path` or moves it into the discard folder under a free name,
+/// recording either in `journal` as done to `original`, where the file was
+/// before being moved aside (usually `path` itself)
+fn discard_file(
+    path: &Path,
+    original: &Path,
+    discard: &Discard,
+    rule: &str,
+    verify: Verify,
+    names: Normalization,
+    journal: &mut Journal,
+) -> Result<()> {
+    match discard {
+        Discard::Delete(mode) => {
+            let metadata = fs::metadata(path)?;
+            safe_delete(path, *mode)?;
+            if *mode != DeleteMode::DryRun {
+                journal.record_deletion(original, &metadata, Some(rule));
+            }
+            Ok(())
+        }
+        Discard::MoveTo(dir) => {
+            fs::create_dir_all(dir)?;
+            let filename = flat_name(original, names);
+            let target = dir.join(free_name(&filename, &mut NameSet::existing(dir)));
+            move_file(path, &target, verify)?;
+            journal.record(original, &target, Some(rule))
+        }
+    }
+}
+
+/// `name` if it is still free, otherwise the first free `name-<n>`
+/// counting from 2; the result is marked as used
+fn free_name(name: &OsStr, used: &mut NameSet) -> OsString {
+    let mut candidate = name.to_os_string();
+    let mut n = 1;
+    while !used.insert(&candidate) {
+        n += 1;
+        candidate = numbered_name(Path::new(name), n);
+    }
+    candidate
+}
+
+/// The name `path` gets in the flatten root: in the Unicode form `names`
+/// and safe for the platform, but otherwise byte for byte, so names that
+/// are not valid UTF-8 survive
+fn flat_name(path: &Path, names: Normalization) -> OsString {
+    safe_os_name(&names.apply(path.file_name().unwrap())).into_owned()
+}
+
+/// The file name prefixed with its directory path relative to `input`,
+/// e.g. `2021/vacation/IMG_1.jpg` becomes `2021_vacation_IMG_1.jpg`
+pub fn prefixed_name(input: &Path, path: &Path, separator: &str, names: Normalization) -> OsString {
+    let relative = path.strip_prefix(input).unwrap_or(path);
+    let mut name = OsString::new();
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        let folder = names.apply(component.as_os_str());
+        if let Some(folder) = folder_name(&escaped_name(&folder)) {
+            name.push(folder);
+            name.push(separator);
+        }
+    }
+    name.push(flat_name(path, names));
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::transfer::aside_path;
+
+    /// `root/report.txt` holding "old", and `root/sub/report.txt` holding
+    /// "new", which wins any collision
+    fn collision(root: &Path) -> (PathBuf, PathBuf) {
+        let (existing, incoming) = (root.join("report.txt"), root.join("sub").join("report.txt"));
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(&existing, "old").unwrap();
+        fs::write(&incoming, "newer").unwrap();
+        (existing, incoming)
+    }
+
+    /// The plan for moving `incoming` into `root`, keeping the larger file
+    /// on a collision
+    fn keep_largest(root: &Path, incoming: &Path) -> FlattenPlan {
+        let files = [incoming.to_path_buf()];
+        FlattenPlan::build_interactive(root, &files, Normalization::None, |_, _| Ok(Resolution::KeepLargest)).unwrap()
+    }
+
+    fn execute(plan: &FlattenPlan, discard: &Discard, journal: &mut Journal) -> Result<u64> {
+        plan.execute(TransferMode::Move, Verify::Size, SymlinkPolicy::Skip, discard, journal, |_| {})
+    }
+
+    #[test]
+    fn a_replaced_root_file_is_discarded_once_the_winner_is_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let (existing, incoming) = collision(dir.path());
+        let plan = keep_largest(dir.path(), &incoming);
+        assert_eq!(plan.replaced().collect::<Vec<_>>(), [&existing]);
+
+        let superseded = dir.path().join("superseded");
+        let mut journal = Journal::new("flatten", TransferMode::Move);
+        execute(&plan, &Discard::MoveTo(superseded.clone()), &mut journal).unwrap();
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "newer");
+        assert_eq!(fs::read_to_string(superseded.join("report.txt")).unwrap(), "old");
+        assert!(!aside_path(&existing).exists());
+        // The discard is journaled under the name the file had
+        assert_eq!(journal.entries[0].source, existing);
+        assert_eq!(journal.entries[1].source, incoming);
+    }
+
+    #[test]
+    fn a_failed_move_keeps_the_root_file_it_would_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let (existing, incoming) = collision(dir.path());
+        let plan = keep_largest(dir.path(), &incoming);
+        // The incoming file disappears between planning and executing
+        fs::remove_file(&incoming).unwrap();
+
+        let mut journal = Journal::new("flatten", TransferMode::Move);
+        assert!(execute(&plan, &Discard::Delete(DeleteMode::Permanent), &mut journal).is_err());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+        assert!(!aside_path(&existing).exists());
+        assert!(journal.entries.is_empty());
+    }
+
+    #[test]
+    fn identical_copies_are_skipped_and_different_ones_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let files: Vec<PathBuf> = [("a", "same"), ("b", "same"), ("c", "different")]
+            .iter()
+            .map(|(folder, contents)| {
+                let path = root.join(folder).join("IMG_1.jpg");
+                fs::create_dir(path.parent().unwrap()).unwrap();
+                fs::write(&path, contents).unwrap();
+                path
+            })
+            .collect();
+
+        let plan = FlattenPlan::build(root, root, &files, DuplicateHandling::SkipIdentical, "_", Normalization::None);
+        assert_eq!(plan.identical, [files[1].clone()]);
+        let targets: Vec<_> = plan.moves.iter().map(|m| (&m.source, m.target.clone(), m.renamed)).collect();
+        assert_eq!(
+            targets,
+            [(&files[0], root.join("IMG_1.jpg"), false), (&files[2], root.join("IMG_1-2.jpg"), true)]
+        );
+        assert_eq!(plan.renamed_count(), 1);
+    }
+
+    #[test]
+    fn a_copy_identical_to_a_root_file_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("IMG_1.jpg"), "same").unwrap();
+        fs::create_dir(root.join("a")).unwrap();
+        fs::write(root.join("a/IMG_1.jpg"), "same").unwrap();
+
+        let files = [root.join("IMG_1.jpg"), root.join("a/IMG_1.jpg")];
+        let plan = FlattenPlan::build(root, root, &files, DuplicateHandling::SkipIdentical, "_", Normalization::None);
+        assert!(plan.moves.is_empty());
+        assert_eq!(plan.identical, [files[1].clone()]);
+    }
+
+    /// `a/notes.txt` and the newer `b/notes.txt`, planned newest-wins
+    fn versions(root: &Path) -> (PathBuf, PathBuf, FlattenPlan) {
+        let (older, newer) = (root.join("a").join("notes.txt"), root.join("b").join("notes.txt"));
+        for (path, age) in [(&older, 3600), (&newer, 60)] {
+            fs::create_dir(path.parent().unwrap()).unwrap();
+            fs::write(path, "notes").unwrap();
+            let mtime = filetime::FileTime::from_unix_time(1_700_000_000 - age, 0);
+            filetime::set_file_mtime(path, mtime).unwrap();
+        }
+        let files = [older.clone(), newer.clone()];
+        let handling = DuplicateHandling::NewestWins { size_tiebreak: false };
+        let plan = FlattenPlan::build(root, root, &files, handling, "_", Normalization::None);
+        (older, newer, plan)
+    }
+
+    #[test]
+    fn older_versions_are_discarded_after_the_newest_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let (older, newer, plan) = versions(dir.path());
+        assert_eq!(plan.moves.len(), 1);
+        assert_eq!(plan.superseded().collect::<Vec<_>>(), [&older]);
+
+        let mut journal = Journal::new("flatten", TransferMode::Move);
+        execute(&plan, &Discard::Delete(DeleteMode::Permanent), &mut journal).unwrap();
+        assert!(dir.path().join("notes.txt").exists());
+        assert!(!older.exists());
+        assert_eq!(journal.entries[0].source, newer);
+        assert!(journal.entries[1].deleted);
+    }
+
+    #[test]
+    fn older_versions_stay_when_the_newest_cannot_move() {
+        let dir = tempfile::tempdir().unwrap();
+        let (older, newer, plan) = versions(dir.path());
+        fs::remove_file(&newer).unwrap();
+
+        let mut journal = Journal::new("flatten", TransferMode::Move);
+        assert!(execute(&plan, &Discard::Delete(DeleteMode::Permanent), &mut journal).is_err());
+        assert!(older.exists());
+        assert!(journal.entries.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{path::Path, time::Duration};
+
+/// Progress of the moves: a bar showing the file being moved, or with
+/// `--quiet` a log line every 5% for logs and scripts
+pub enum FlattenProgress {
+    Bar(ProgressBar),
+    Log { total: usize, done: usize, step: usize },
+}
+
+impl FlattenProgress {
+    pub fn new(total: usize, quiet: bool) -> Self {
+        if quiet {
+            return Self::Log {
+                total,
+                done: 0,
+                step: (total / 20).max(1),
+            };
+        }
+
+        let pb = ProgressBar::new(total as u64);
+        pb.set_style(ProgressStyle::default_spinner()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] ({pos}/{len}) {msg}")
+            .unwrap()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
+            .progress_chars("#>-"));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        Self::Bar(pb)
+    }
+
+    /// Called once per file, with its path relative to the input
+    pub fn advance(&mut self, relative: &Path) {
+        match self {
+            Self::Bar(pb) => {
+                pb.set_message(relative.display().to_string());
+                pb.inc(1);
+            }
+            Self::Log { total, done, step } => {
+                *done += 1;
+                if (*done).is_multiple_of(*step) || *done == *total {
+                    println!("Flattened {}/{} files", done, total);
+                }
+            }
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Self::Bar(pb) = self {
+            pb.finish_with_message("Directory flattening completed!");
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{path::PathBuf, time::Duration};
+
+use crate::modules::base::Summary;
+use crate::utils::transfer::TransferMode;
+
+/// What a flatten run did, printed at the end
+#[derive(Debug, Serialize)]
+pub struct FlattenSummary {
+    pub mode: TransferMode,
+    pub files: usize,
+    pub bytes: u64,
+    /// Renamed because another file had the same name
+    pub renamed: usize,
+    /// Left in place because the name was taken
+    pub skipped: usize,
+    /// Extra copies with the same name and contents
+    pub identical: usize,
+    pub identical_deleted: bool,
+    /// Root files replaced by the file kept in a collision
+    pub replaced: usize,
+    pub symlinks_skipped: usize,
+    /// Older versions discarded by newest-wins
+    pub superseded: usize,
+    pub removed_dirs: usize,
+    pub repositories: usize,
+    /// Known junk files and directories, left in place
+    pub junk: usize,
+    /// Files and directories that could not be read
+    pub inaccessible: usize,
+    pub elapsed: Duration,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+}
+
+impl From<&FlattenSummary> for Summary {
+    fn from(summary: &FlattenSummary) -> Self {
+        Summary {
+            files: summary.files,
+            bytes: summary.bytes,
+            skipped: summary.skipped + summary.identical + summary.symlinks_skipped,
+            junk: summary.junk,
+            inaccessible: summary.inaccessible,
+            failed: 0,
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl FlattenSummary {
+    pub fn print(&self) {
+        println!();
+        println!("{:<28} {:>10}", format!("Files {}", self.mode.verb()), self.files);
+        println!("{:<28} {:>10}", "Size", format_size(self.bytes, BINARY));
+        println!("{:<28} {:>10}", "Renamed after a collision", self.renamed);
+        println!("{:<28} {:>10}", "Duplicates skipped", self.skipped);
+        if self.identical > 0 {
+            let label = if self.identical_deleted { "Identical copies deleted" } else { "Identical copies skipped" };
+            println!("{:<28} {:>10}", label, self.identical);
+        }
+        if self.replaced > 0 {
+            println!("{:<28} {:>10}", "Replaced in the root", self.replaced);
+        }
+        if self.superseded > 0 {
+            println!("{:<28} {:>10}", "Older versions superseded", self.superseded);
+        }
+        if self.symlinks_skipped > 0 {
+            println!("{:<28} {:>10}", "Symlinks skipped", self.symlinks_skipped);
+        }
+        println!("{:<28} {:>10}", "Directories removed", self.removed_dirs);
+        if self.repositories > 0 {
+            println!("{:<28} {:>10}", "Repositories skipped", self.repositories);
+        }
+        if self.junk > 0 {
+            println!("{:<28} {:>10}", "Junk entries skipped", self.junk);
+        }
+        if self.inaccessible > 0 {
+            println!("{:<28} {:>10}", "Unreadable entries skipped", self.inaccessible);
+        }
+        println!("{:<28} {:>10.1?}", "Elapsed", self.elapsed);
+    }
+}
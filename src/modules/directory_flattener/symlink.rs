@@ -0,0 +1,67 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{
+    links::{absolute_parent, create_symlink, relative_to, resolve},
+    metadata::copy_file_metadata,
+    transfer::TransferMode,
+};
+
+/// What to do with symlinks to files found while flattening
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SymlinkPolicy {
+    /// Leave them where they are
+    Skip,
+    /// Move the link itself, rewriting a relative target so it still
+    /// resolves from the new location
+    Move,
+    /// Put a copy of the target in the root instead of the link
+    Copy,
+}
+
+/// Transfers the symlink `source` to `target` according to `policy`,
+/// returning the bytes written. `relocated` maps files already moved by
+/// this run to their new paths (absolute and normalized), so links into
+/// the flattened tree keep pointing at their file. Moving removes the
+/// original link; copying leaves it.
+pub fn transfer_symlink(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    policy: SymlinkPolicy,
+    relocated: &HashMap<PathBuf, PathBuf>,
+) -> Result<u64> {
+    let link = fs::read_link(source)?;
+    let resolved = resolve(source)?;
+    let current = relocated.get(&resolved).cloned();
+
+    match policy {
+        SymlinkPolicy::Skip => anyhow::bail!("{} is a symlink and symlinks are skipped", source.display()),
+        SymlinkPolicy::Move => {
+            let new_link = match (link.is_relative(), current) {
+                (true, current) => relative_to(&current.unwrap_or(resolved), &absolute_parent(target)?),
+                (false, Some(current)) => current,
+                (false, None) => link,
+            };
+            create_symlink(&new_link, target)?;
+            if mode == TransferMode::Move {
+                fs::remove_file(source)?;
+            }
+            Ok(0)
+        }
+        SymlinkPolicy::Copy => {
+            let data = current.unwrap_or(resolved);
+            let size = fs::copy(&data, target)?;
+            copy_file_metadata(&data, target)?;
+            if mode == TransferMode::Move {
+                fs::remove_file(source)?;
+            }
+            Ok(size)
+        }
+    }
+}
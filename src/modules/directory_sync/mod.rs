@@ -0,0 +1,646 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use filetime::FileTime;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::utils::prompt::{Confirm, Input};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::utils::{
+    create_byte_bar, create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    expand::expand_path,
+    filter::PathFilter,
+    get_directory_from_user,
+    journal::Journal,
+    junk::JunkList,
+    links,
+    metrics::Metrics,
+    progress::{ByteProgress, Outcome, ProgressSink},
+    space::{preflight, print_estimates, SpaceEstimate},
+    transfer::{copy_atomic, partial_path, TransferMode, Verify},
+    walk::{Inaccessible, Walker},
+};
+
+/// Modification times this close count as equal. FAT keeps them to two
+/// seconds and other filesystems to a second, so a copy on one of those
+/// would otherwise never match its source.
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Makes a destination directory look like a source directory: copies
+/// new and changed files across and, when asked, deletes destination
+/// files the source no longer has. Files count as changed when their size
+/// or modification time differs.
+pub struct DirectorySync {
+    recursive: bool,
+    source: Option<PathBuf>,
+    destination: Option<PathBuf>,
+    /// `None` asks whether to delete files missing from the source
+    delete: Option<bool>,
+    /// Sync working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be synced
+    include: Vec<String>,
+    /// Glob patterns that leave a file out of the sync, on both sides
+    exclude: Vec<String>,
+    /// Show the differences without changing anything
+    dry_run: bool,
+    /// Abort on the first file that cannot be copied or deleted
+    fail_fast: bool,
+    delete_mode: DeleteMode,
+    /// How copies are checked before they replace anything
+    verify: Verify,
+    /// Copy even when the destination filesystem looks too small
+    force: bool,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// Everything a sync needs, with no prompting
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub source: PathBuf,
+    /// Created if it does not exist
+    pub destination: PathBuf,
+    pub recursive: bool,
+    /// Sync git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to either root; files they
+    /// leave out are neither copied nor deleted
+    pub filter: PathFilter,
+    /// Known junk, neither copied nor deleted
+    pub junk: JunkList,
+    /// Delete destination files the source does not have
+    pub delete: bool,
+    pub delete_mode: DeleteMode,
+    /// How each copy is checked before it replaces anything
+    pub verify: Verify,
+    /// Where the undo manifest goes; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl SyncOptions {
+    /// A recursive sync from `source` to `destination` that deletes
+    /// nothing
+    pub fn new(source: PathBuf, destination: PathBuf) -> Self {
+        Self {
+            source,
+            destination,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            delete: false,
+            delete_mode: DeleteMode::Trash,
+            verify: Verify::Size,
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A file to copy across
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncCopy {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub bytes: u64,
+}
+
+/// What [`plan`] found to do
+#[derive(Debug, Default, Serialize)]
+pub struct SyncPlan {
+    /// Files the destination does not have yet
+    pub adds: Vec<SyncCopy>,
+    /// Files whose size or modification time differs between the two
+    pub updates: Vec<SyncCopy>,
+    /// Destination files the source does not have, only listed when
+    /// deleting was asked for
+    pub deletes: Vec<PathBuf>,
+    /// Destination files the source does not have, left alone because
+    /// deleting was not asked for
+    pub extra: usize,
+    /// Files already the same on both sides
+    pub unchanged: usize,
+    /// Copies an interrupted run left behind, removed before copying
+    pub partials: Vec<PathBuf>,
+    pub space: Vec<SpaceEstimate>,
+    /// Working copies that were not synced
+    pub repositories: Vec<PathBuf>,
+    pub junk: usize,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl SyncPlan {
+    /// Bytes the adds and updates copy
+    pub fn bytes(&self) -> u64 {
+        self.adds.iter().chain(&self.updates).map(|copy| copy.bytes).sum()
+    }
+
+    /// Prints the differences like a diff: `+` for adds, `~` for updates,
+    /// and `-` for deletes, paths relative to the destination
+    pub fn print_diff(&self, destination: &Path) {
+        let relative = |path: &Path| path.strip_prefix(destination).unwrap_or(path).display().to_string();
+        for copy in &self.adds {
+            println!("+ {} ({})", relative(&copy.target), format_size(copy.bytes, BINARY));
+        }
+        for copy in &self.updates {
+            println!("~ {} ({})", relative(&copy.target), format_size(copy.bytes, BINARY));
+        }
+        for path in &self.deletes {
+            println!("- {}", relative(path));
+        }
+    }
+}
+
+/// Result of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct SyncSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub bytes: u64,
+    pub unchanged: usize,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    pub repositories: Vec<PathBuf>,
+    pub junk: usize,
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&SyncSummary> for Summary {
+    fn from(summary: &SyncSummary) -> Self {
+        Summary {
+            files: summary.added + summary.updated + summary.deleted,
+            bytes: summary.bytes,
+            skipped: summary.unchanged,
+            junk: summary.junk,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Compares the two trees. A file is copied when the destination lacks
+/// it or has it with another size or modification time; with `delete`,
+/// destination files the source lacks are listed for deletion. Copies an
+/// interrupted run left behind are listed for removal.
+pub fn plan(options: &SyncOptions, progress: impl ProgressSink) -> Result<SyncPlan> {
+    let source = &options.source;
+    let destination = &options.destination;
+    if !source.is_dir() {
+        anyhow::bail!("Source is not a directory: {}", source.display());
+    }
+    let (source_abs, destination_abs) = (links::absolute(source)?, links::absolute(destination)?);
+    if destination_abs.starts_with(&source_abs) || source_abs.starts_with(&destination_abs) {
+        anyhow::bail!("Source and destination must not contain each other");
+    }
+
+    let walker = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .sorted(true);
+    progress.scan_started(source);
+    let from = walker.files(source);
+    let mut plan = SyncPlan {
+        repositories: from.repositories,
+        junk: from.junk.len(),
+        inaccessible: from.inaccessible,
+        ..SyncPlan::default()
+    };
+    let mut existing: HashMap<PathBuf, Metadata> = HashMap::new();
+    if destination.is_dir() {
+        progress.scan_started(destination);
+        let to = walker.files(destination);
+        plan.inaccessible.extend(to.inaccessible);
+        for entry in to.files {
+            if let Ok(relative) = entry.path.strip_prefix(destination) {
+                existing.insert(relative.to_path_buf(), entry.metadata);
+            }
+        }
+    }
+
+    for entry in from.files {
+        let Ok(relative) = entry.path.strip_prefix(source) else {
+            continue;
+        };
+        let target = destination.join(relative);
+        let bytes = entry.metadata.len();
+        let partial = partial_path(relative);
+        if existing.remove(&partial).is_some() {
+            plan.partials.push(destination.join(&partial));
+        }
+        match existing.remove(relative) {
+            None => plan.adds.push(SyncCopy { source: entry.path, target, bytes }),
+            Some(metadata) if !same_file(&entry.metadata, &metadata) => {
+                plan.updates.push(SyncCopy { source: entry.path, target, bytes })
+            }
+            Some(_) => plan.unchanged += 1,
+        }
+    }
+    if options.delete {
+        plan.deletes = existing.into_keys().map(|relative| destination.join(relative)).collect();
+        plan.deletes.sort();
+    } else {
+        plan.extra = existing.len();
+    }
+
+    let added: u64 = plan.adds.iter().map(|copy| copy.bytes).sum();
+    // An update is written beside the file it replaces, so both take up
+    // space for a moment; the largest one is what needs to fit on top
+    let largest_update = plan.updates.iter().map(|copy| copy.bytes).max().unwrap_or(0);
+    plan.space = vec![SpaceEstimate::new(destination, added + largest_update)?];
+    Ok(plan)
+}
+
+/// Whether a destination file with `target` metadata already matches a
+/// source file with `source` metadata
+fn same_file(source: &Metadata, target: &Metadata) -> bool {
+    source.len() == target.len() && same_mtime(source, target)
+}
+
+/// Whether two files' modification times are within [`MTIME_TOLERANCE`]
+/// of each other
+pub(crate) fn same_mtime(a: &Metadata, b: &Metadata) -> bool {
+    let (a, b) = (
+        FileTime::from_last_modification_time(a),
+        FileTime::from_last_modification_time(b),
+    );
+    let nanos = |time: FileTime| time.unix_seconds() as i128 * 1_000_000_000 + time.nanoseconds() as i128;
+    (nanos(a) - nanos(b)).unsigned_abs() <= MTIME_TOLERANCE.as_nanos()
+}
+
+/// Carries out `plan`: removes stale partial copies, copies adds and
+/// updates (each written under a temporary name and renamed into place,
+/// keeping mtime and permissions), then deletes what is listed for
+/// deletion and the directories that leaves empty where the source has
+/// none. Added files are recorded in an undo manifest.
+///
+/// ```
+/// use file_organizer_rust::modules::directory_sync::{self, SyncOptions};
+///
+/// let source = tempfile::tempdir()?;
+/// let destination = tempfile::tempdir()?;
+/// std::fs::create_dir(source.path().join("docs"))?;
+/// std::fs::write(source.path().join("docs/notes.txt"), "hello")?;
+/// std::fs::write(destination.path().join("old.txt"), "bye")?;
+///
+/// let options = SyncOptions {
+///     delete: true,
+///     delete_mode: file_organizer_rust::utils::delete::DeleteMode::Permanent,
+///     ..SyncOptions::new(source.path().to_path_buf(), destination.path().to_path_buf())
+/// };
+/// let plan = directory_sync::plan(&options, ())?;
+/// let summary = directory_sync::apply(&options, plan, ())?;
+/// assert_eq!((summary.added, summary.deleted), (1, 1));
+/// assert_eq!(std::fs::read_to_string(destination.path().join("docs/notes.txt"))?, "hello");
+/// assert!(!destination.path().join("old.txt").exists());
+///
+/// // A second run finds nothing to do
+/// let plan = directory_sync::plan(&options, ())?;
+/// assert_eq!((plan.adds.len(), plan.updates.len(), plan.unchanged), (0, 0, 1));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &SyncOptions, plan: SyncPlan, progress: impl ProgressSink) -> Result<SyncSummary> {
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut summary = SyncSummary {
+        unchanged: plan.unchanged,
+        repositories: plan.repositories,
+        junk: plan.junk,
+        inaccessible: plan.inaccessible,
+        ..SyncSummary::default()
+    };
+    for partial in &plan.partials {
+        let _ = fs::remove_file(partial);
+    }
+
+    let mut journal = Journal::new("sync", TransferMode::Copy);
+    let copies = plan.adds.len() + plan.updates.len();
+    progress.phase_changed("copying", copies as u64);
+    let result = copy_all(options, plan.adds, plan.updates, &errors, &mut journal, &mut summary, &progress);
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // added can be undone
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            summary.manifest = Some(path);
+        }
+    }
+    result?;
+
+    if !plan.deletes.is_empty() {
+        progress.phase_changed("deleting", plan.deletes.len() as u64);
+        let results = safe_delete_all(&plan.deletes, options.delete_mode);
+        for (path, result) in plan.deletes.iter().zip(results) {
+            let outcome = match errors.check(path, "delete", result)? {
+                Some(()) => {
+                    summary.deleted += 1;
+                    Outcome::Done
+                }
+                None => Outcome::Failed,
+            };
+            progress.item_finished(path, 0, outcome);
+        }
+        if options.delete_mode != DeleteMode::DryRun {
+            remove_emptied_dirs(&plan.deletes, &options.source, &options.destination);
+        }
+    }
+
+    summary.errors = errors.into_errors();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+fn copy_all(
+    options: &SyncOptions,
+    adds: Vec<SyncCopy>,
+    updates: Vec<SyncCopy>,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    summary: &mut SyncSummary,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    let copies = adds.into_iter().map(|copy| (copy, true)).chain(updates.into_iter().map(|copy| (copy, false)));
+    for (copy, new) in copies {
+        progress.item_started(&copy.source);
+        let copied = (|| {
+            if let Some(dir) = copy.target.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            copy_atomic(&copy.source, &copy.target, options.verify, |bytes| progress.bytes_written(bytes))
+        })();
+        let outcome = match errors.check(&copy.source, "copy", copied)? {
+            Some(bytes) => {
+                summary.bytes += bytes;
+                if new {
+                    summary.added += 1;
+                    // Undo removes added files; an update replaced the
+                    // old contents, which undo cannot bring back
+                    journal.record(&copy.source, &copy.target, Some("add"))?;
+                } else {
+                    summary.updated += 1;
+                }
+                Outcome::Done
+            }
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&copy.source, copy.bytes, outcome);
+    }
+    Ok(())
+}
+
+/// Removes the destination directories that deleting `deleted` left
+/// empty, unless the source has the same directory
+fn remove_emptied_dirs(deleted: &[PathBuf], source: &Path, destination: &Path) {
+    let mut dirs: Vec<&Path> = deleted
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .filter(|dir| dir.starts_with(destination) && *dir != destination)
+        .collect();
+    // Deepest first, so a parent is only tried once its children are gone
+    dirs.sort_by(|a, b| b.components().count().cmp(&a.components().count()).then(a.cmp(b)));
+    dirs.dedup();
+    for dir in dirs {
+        let kept_in_source = dir
+            .strip_prefix(destination)
+            .is_ok_and(|relative| source.join(relative).is_dir());
+        if !kept_in_source {
+            // Fails, as it should, for directories that still hold something
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+#[async_trait]
+impl FileOrganizer for DirectorySync {
+    type Plan = SyncPlan;
+    const NAME: &'static str = "sync";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            source: None,
+            destination: None,
+            delete: None,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            dry_run: false,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            verify: Verify::default(),
+            force: false,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.source.is_none() {
+            self.source = Some(get_directory_from_user("Enter source directory")?);
+        }
+        if self.destination.is_none() {
+            let typed: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter destination directory (created if missing)")
+                .interact_text()?;
+            self.destination = Some(expand_path(typed.trim())?);
+        }
+        if self.delete.is_none() {
+            self.delete = Some(
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Also delete destination files the source no longer has?")
+                    .default(false)
+                    .interact()?,
+            );
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<SyncPlan> {
+        let spinner = create_spinner("Comparing directories...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Shows the differences, with every file to be deleted listed, and
+    /// asks before changing anything. A dry run stops after the list.
+    fn review(&self, plan: &SyncPlan) -> Result<bool> {
+        let destination = self.destination.as_deref().unwrap_or(Path::new(""));
+        if self.dry_run || !plan.deletes.is_empty() {
+            plan.print_diff(destination);
+        }
+        println!(
+            "{} files to add, {} to update ({}), {} to delete, {} unchanged",
+            plan.adds.len(),
+            plan.updates.len(),
+            format_size(plan.bytes(), BINARY),
+            plan.deletes.len(),
+            plan.unchanged
+        );
+        if plan.extra > 0 {
+            println!(
+                "{} destination files are not in the source and are kept (use --delete to remove them)",
+                plan.extra
+            );
+        }
+        if !plan.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to sync them)",
+                plan.repositories.len()
+            );
+        }
+        if self.dry_run {
+            print_estimates(&plan.space);
+            println!("Dry run finished, nothing was copied or deleted");
+            return Ok(false);
+        }
+        if plan.adds.is_empty() && plan.updates.is_empty() && plan.deletes.is_empty() {
+            println!("Destination is already in sync");
+            return Ok(false);
+        }
+        preflight(&plan.space, self.force)?;
+        let prompt = if plan.deletes.is_empty() {
+            "Sync now?".to_string()
+        } else {
+            format!("Sync now, deleting the {} files listed with -?", plan.deletes.len())
+        };
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(plan.deletes.is_empty())
+            .interact()
+    }
+
+    fn execute(&self, plan: SyncPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let bar = create_byte_bar(plan.bytes());
+        self.metrics.phase("copying");
+        let summary = apply(&options, plan, (ByteProgress(bar.clone()), &self.metrics))?;
+        bar.finish_and_clear();
+        println!(
+            "{} files added, {} updated, {} deleted ({} copied)",
+            summary.added,
+            summary.updated,
+            summary.deleted,
+            format_size(summary.bytes, BINARY)
+        );
+        print_inaccessible(&summary.inaccessible, "they were not synced");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl DirectorySync {
+    /// Presets the source so the prompt is skipped
+    pub fn with_source(mut self, dir: PathBuf) -> Self {
+        self.source = Some(dir);
+        self
+    }
+
+    /// Presets the destination so the prompt is skipped
+    pub fn with_destination(mut self, dir: PathBuf) -> Self {
+        self.destination = Some(dir);
+        self
+    }
+
+    /// Whether destination files the source does not have are deleted,
+    /// without asking
+    pub fn with_delete(mut self, delete: bool) -> Self {
+        self.delete = Some(delete);
+        self
+    }
+
+    /// Syncs working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only syncs files whose relative path matches one of `patterns`
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` out, on
+    /// both sides
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Lists the differences without copying or deleting anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first file that cannot be copied or deleted
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Sets how deleted files are disposed of
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Sets how copies are checked before they replace anything
+    pub fn with_verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Copies even when the destination filesystem looks too small
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<SyncOptions> {
+        let source = self.source.clone().ok_or_else(|| anyhow::anyhow!("Source directory not set"))?;
+        let destination = self
+            .destination
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Destination directory not set"))?;
+        Ok(SyncOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            junk: self.junk.clone(),
+            delete: self.delete.unwrap_or(false),
+            delete_mode: self.delete_mode,
+            verify: self.verify,
+            manifest_dir: Some(Journal::default_dir()?),
+            fail_fast: self.fail_fast,
+            ..SyncOptions::new(source, destination)
+        })
+    }
+}
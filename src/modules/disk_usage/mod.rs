@@ -0,0 +1,480 @@
+mod report;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::Select;
+use crate::config::Config;
+use crate::modules::base::{print_inaccessible, FileOrganizer, Summary};
+use crate::modules::file_categorizer::{preset, types::TypeNaming, ReportFormat};
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    metrics::Metrics,
+    progress::ProgressSink,
+    walk::{Inaccessible, Walker},
+};
+
+/// Reports what takes up space under a directory: the largest files, the
+/// heaviest directories, and the split by extension and category. Reads
+/// metadata only, never file contents.
+pub struct UsageReporter {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Count git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be counted
+    include: Vec<String>,
+    /// Glob patterns that leave a file out of the report
+    exclude: Vec<String>,
+    /// Rows in the largest-files and heaviest-directories tables
+    top: usize,
+    /// `None` asks after the report; `Some(None)` exports nothing
+    export: Option<Option<ReportFormat>>,
+    export_path: Option<PathBuf>,
+    metrics: Metrics,
+}
+
+/// Everything a usage scan needs, with no prompting
+pub struct UsageOptions {
+    pub input_dirs: Vec<PathBuf>,
+    pub recursive: bool,
+    /// Count git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to each input directory
+    pub filter: PathFilter,
+    /// Rows in the largest-files and heaviest-directories tables
+    pub top: usize,
+}
+
+impl UsageOptions {
+    /// A recursive scan of `input_dirs` listing the top 20 files and
+    /// directories
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            top: 20,
+        }
+    }
+}
+
+/// A file or directory and the bytes it holds
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEntry {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Files counted, 1 for a file
+    pub files: usize,
+}
+
+/// Files sharing an extension or category
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageGroup {
+    pub name: String,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+/// Result of [`scan`]. Sizes are apparent sizes, as `ls` shows them.
+#[derive(Debug, Default, Serialize)]
+pub struct UsageReport {
+    pub total_bytes: u64,
+    pub total_files: usize,
+    /// Largest first
+    pub largest_files: Vec<UsageEntry>,
+    /// Directories below the input directories with everything under them,
+    /// heaviest first
+    pub heaviest_dirs: Vec<UsageEntry>,
+    /// Canonical lower-case extension, heaviest first
+    pub extensions: Vec<UsageGroup>,
+    /// The categorizer's preset categories, heaviest first
+    pub categories: Vec<UsageGroup>,
+    /// Further hardlinks to files already counted, left out of every total
+    pub hardlinks: usize,
+    /// Bytes those hardlinks would have added
+    pub hardlink_bytes: u64,
+    /// Working copies that were not counted
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be read, so were not counted
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl UsageReport {
+    /// Share of the total `bytes` make up, in percent
+    pub fn share(&self, bytes: u64) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        bytes as f64 * 100.0 / self.total_bytes as f64
+    }
+
+    /// Prints every table, paths relative to `input`
+    pub fn print_tables(&self, input: &Path) {
+        let relative = |path: &Path| path.strip_prefix(input).unwrap_or(path).display().to_string();
+        let row = |bytes: u64, files: usize, name: &str| {
+            println!(
+                "{:>12} {:>6.1}% {:>9}  {}",
+                format_size(bytes, BINARY),
+                self.share(bytes),
+                files,
+                name
+            );
+        };
+        let header = |title: &str| {
+            println!();
+            println!("{}", title);
+            println!("{:>12} {:>7} {:>9}  Name", "Size", "Share", "Files");
+        };
+
+        header("Largest files");
+        for entry in &self.largest_files {
+            row(entry.bytes, entry.files, &relative(&entry.path));
+        }
+        if !self.heaviest_dirs.is_empty() {
+            header("Heaviest directories");
+            for entry in &self.heaviest_dirs {
+                row(entry.bytes, entry.files, &format!("{}/", relative(&entry.path)));
+            }
+        }
+        header("By extension");
+        for group in &self.extensions {
+            row(group.bytes, group.files, &group.name);
+        }
+        header("By category");
+        for group in &self.categories {
+            row(group.bytes, group.files, &group.name);
+        }
+        println!();
+        row(self.total_bytes, self.total_files, "Total");
+    }
+
+    /// Observations worth acting on, such as how much the largest files
+    /// account for
+    pub fn suggestions(&self, input: &Path) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        if self.total_bytes == 0 {
+            return suggestions;
+        }
+        if self.largest_files.len() < self.total_files {
+            let bytes: u64 = self.largest_files.iter().map(|entry| entry.bytes).sum();
+            suggestions.push(format!(
+                "Top {} files account for {:.0}% of usage",
+                self.largest_files.len(),
+                self.share(bytes)
+            ));
+        }
+        if let Some(dir) = self.heaviest_dirs.first() {
+            suggestions.push(format!(
+                "{}/ holds {:.0}% of usage",
+                dir.path.strip_prefix(input).unwrap_or(&dir.path).display(),
+                self.share(dir.bytes)
+            ));
+        }
+        if let Some(category) = self.categories.first() {
+            suggestions.push(format!(
+                "{} take {:.0}% of usage ({} files)",
+                category.name,
+                self.share(category.bytes),
+                category.files
+            ));
+        }
+        if self.hardlinks > 0 {
+            suggestions.push(format!(
+                "{} hardlinks to files already counted were left out ({})",
+                self.hardlinks,
+                format_size(self.hardlink_bytes, BINARY)
+            ));
+        }
+        suggestions
+    }
+}
+
+/// Walks the input directories and totals every file's size by file,
+/// directory, extension, and category. A file with several hardlinks is
+/// counted once, at the first path the walk finds.
+///
+/// ```
+/// use file_organizer_rust::modules::disk_usage::{self, UsageOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir(dir.path().join("videos"))?;
+/// std::fs::write(dir.path().join("videos/clip.mp4"), vec![0; 4096])?;
+/// std::fs::write(dir.path().join("notes.txt"), "hello")?;
+///
+/// let report = disk_usage::scan(&UsageOptions::new(vec![dir.path().to_path_buf()]), ())?;
+/// assert_eq!(report.total_bytes, 4101);
+/// assert_eq!(report.largest_files[0].path, dir.path().join("videos/clip.mp4"));
+/// assert_eq!(report.heaviest_dirs[0].bytes, 4096);
+/// assert_eq!(report.categories[0].name, "Videos");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn scan(options: &UsageOptions, progress: impl ProgressSink) -> Result<UsageReport> {
+    if options.input_dirs.is_empty() {
+        anyhow::bail!("No input directory given");
+    }
+    let config = Config::load()?;
+    let naming = TypeNaming::new(&config.categorize.extension_aliases, false);
+    let categories = preset::table(&config.categorize.preset_overrides);
+
+    let mut report = UsageReport::default();
+    let mut files = Vec::new();
+    let mut dirs: HashMap<PathBuf, (u64, usize)> = HashMap::new();
+    let mut extensions: HashMap<String, (u64, usize)> = HashMap::new();
+    let mut by_category: HashMap<String, (u64, usize)> = HashMap::new();
+    let mut seen = HashSet::new();
+    for root in &options.input_dirs {
+        progress.scan_started(root);
+        // Sorted, so the path a hardlinked file is counted at does not
+        // change between runs
+        let walk = Walker::new(options.recursive)
+            .include_repos(options.include_repos)
+            .filter(options.filter.clone())
+            .sorted(true)
+            .files(root);
+        report.repositories.extend(walk.repositories);
+        report.inaccessible.extend(walk.inaccessible);
+
+        for entry in walk.files {
+            let bytes = entry.metadata.len();
+            if hardlink_id(&entry.metadata).is_some_and(|id| !seen.insert(id)) {
+                report.hardlinks += 1;
+                report.hardlink_bytes += bytes;
+                continue;
+            }
+            report.total_bytes += bytes;
+            report.total_files += 1;
+
+            for dir in entry.path.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root)) {
+                let total = dir_total(&mut dirs, dir);
+                total.0 += bytes;
+                total.1 += 1;
+            }
+            let extension = entry
+                .path
+                .extension()
+                .map(|ext| naming.canonical(&ext.to_string_lossy()));
+            let category = extension
+                .as_ref()
+                .and_then(|ext| categories.get(ext).cloned())
+                .unwrap_or_else(|| "Other".to_string());
+            let extension = extension.unwrap_or_else(|| "(none)".to_string());
+            for (groups, name) in [(&mut extensions, extension), (&mut by_category, category)] {
+                let total = groups.entry(name).or_default();
+                total.0 += bytes;
+                total.1 += 1;
+            }
+            files.push(UsageEntry { path: entry.path, bytes, files: 1 });
+        }
+    }
+
+    files.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    files.truncate(options.top);
+    report.largest_files = files;
+
+    let mut dirs: Vec<UsageEntry> = dirs
+        .into_iter()
+        .map(|(path, (bytes, files))| UsageEntry { path, bytes, files })
+        .collect();
+    dirs.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    dirs.truncate(options.top);
+    report.heaviest_dirs = dirs;
+
+    report.extensions = ranked(extensions);
+    report.categories = ranked(by_category);
+    Ok(report)
+}
+
+/// The running total for `dir`, looked up without allocating when it
+/// already exists, which it does for all but the first file in a directory
+fn dir_total<'a>(dirs: &'a mut HashMap<PathBuf, (u64, usize)>, dir: &Path) -> &'a mut (u64, usize) {
+    if !dirs.contains_key(dir) {
+        dirs.insert(dir.to_path_buf(), (0, 0));
+    }
+    dirs.get_mut(dir).unwrap()
+}
+
+fn ranked(groups: HashMap<String, (u64, usize)>) -> Vec<UsageGroup> {
+    let mut groups: Vec<UsageGroup> = groups
+        .into_iter()
+        .map(|(name, (bytes, files))| UsageGroup { name, bytes, files })
+        .collect();
+    groups.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+/// Device and inode of a file with more than one hardlink, so its other
+/// paths can be recognized; `None` for every other file
+#[cfg(unix)]
+fn hardlink_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hardlink_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[async_trait]
+impl FileOrganizer for UsageReporter {
+    type Plan = UsageReport;
+    const NAME: &'static str = "usage";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            top: 20,
+            export: None,
+            export_path: None,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to report disk usage for")?);
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<UsageReport> {
+        let spinner = create_spinner("Measuring disk usage...");
+        self.metrics.phase("scanning");
+        let report = scan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(report)
+    }
+
+    /// Prints the tables and suggestions, then exports the report if asked
+    fn execute(&self, report: UsageReport) -> Result<Summary> {
+        let input_dir = self.input_dir.clone().unwrap_or_default();
+        if report.total_files == 0 {
+            println!("No files found");
+        } else {
+            report.print_tables(&input_dir);
+            let suggestions = report.suggestions(&input_dir);
+            if !suggestions.is_empty() {
+                println!();
+                for suggestion in suggestions {
+                    println!("- {}", suggestion);
+                }
+            }
+        }
+        if !report.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to count them)",
+                report.repositories.len()
+            );
+        }
+        print_inaccessible(&report.inaccessible, "the totals leave them out");
+        if report.total_files > 0 {
+            self.export(&report, &input_dir)?;
+        }
+
+        Ok(Summary {
+            files: report.total_files,
+            bytes: report.total_bytes,
+            inaccessible: report.inaccessible.len(),
+            phases: self.metrics.finish(),
+            ..Summary::default()
+        })
+    }
+}
+
+impl UsageReporter {
+    /// Presets the directory to measure so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Counts working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only counts files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.mkv" or "photos/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` out
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Lists `top` files and directories instead of 20
+    pub fn with_top(mut self, top: usize) -> Self {
+        self.top = top;
+        self
+    }
+
+    /// Exports the report as CSV or JSON, to `path` or next to the input,
+    /// or with `None` exports nothing, without asking
+    pub fn with_export(mut self, format: Option<ReportFormat>, path: Option<PathBuf>) -> Self {
+        self.export = Some(format);
+        self.export_path = path;
+        self
+    }
+
+    /// Writes the export in the preset format, or asks whether to export
+    /// and in which format; the path defaults to one in the input directory
+    fn export(&self, report: &UsageReport, input_dir: &Path) -> Result<()> {
+        let format = match self.export {
+            Some(format) => format,
+            None => {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Export the report?")
+                    .items(&["No", "CSV", "JSON"])
+                    .default(0)
+                    .interact()?;
+                [None, Some(ReportFormat::Csv), Some(ReportFormat::Json)][choice]
+            }
+        };
+        let Some(format) = format else {
+            return Ok(());
+        };
+
+        let path = self
+            .export_path
+            .clone()
+            .unwrap_or_else(|| report::default_path(input_dir, format));
+        report::write(report, &path, format)?;
+        println!("Report written to {}", path.display());
+        Ok(())
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<UsageOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| {
+            anyhow::anyhow!("Input directory not set")
+        })?;
+        Ok(UsageOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            top: self.top,
+            ..UsageOptions::new(vec![input_dir])
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::UsageReport;
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    atomic::{write_atomic, AtomicFile},
+    csv_field,
+};
+
+/// `<input>/usage-report-<timestamp>.<ext>`
+pub fn default_path(input_dir: &Path, format: ReportFormat) -> PathBuf {
+    input_dir.join(format!(
+        "usage-report-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Writes the report as JSON, or as CSV with one row per table line:
+/// section (file, directory, extension, category, or total), name, files,
+/// bytes, and share of the total in percent
+pub fn write(report: &UsageReport, path: &Path, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Json => write_atomic(path, serde_json::to_string_pretty(report)?)?,
+        ReportFormat::Csv => {
+            let mut file = BufWriter::new(AtomicFile::create(path)?);
+            writeln!(file, "section,name,files,bytes,share")?;
+            for (section, name, files, bytes) in rows(report) {
+                writeln!(
+                    file,
+                    "{},{},{},{},{:.2}",
+                    section,
+                    csv_field(&name),
+                    files,
+                    bytes,
+                    report.share(bytes)
+                )?;
+            }
+            file.into_inner()?.commit()?;
+        }
+    }
+    Ok(())
+}
+
+fn rows(report: &UsageReport) -> Vec<(&'static str, String, usize, u64)> {
+    let mut rows = Vec::new();
+    for entry in &report.largest_files {
+        rows.push(("file", entry.path.display().to_string(), entry.files, entry.bytes));
+    }
+    for entry in &report.heaviest_dirs {
+        rows.push(("directory", entry.path.display().to_string(), entry.files, entry.bytes));
+    }
+    for group in &report.extensions {
+        rows.push(("extension", group.name.clone(), group.files, group.bytes));
+    }
+    for group in &report.categories {
+        rows.push(("category", group.name.clone(), group.files, group.bytes));
+    }
+    rows.push(("total", String::new(), report.total_files, report.total_bytes));
+    rows
+}
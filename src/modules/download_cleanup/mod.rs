@@ -0,0 +1,683 @@
+mod patterns;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use dialoguer::theme::ColorfulTheme;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::Confirm;
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_deduplicator::{Duplicate, HashMethod};
+use crate::modules::file_renamer::Rename;
+use crate::utils::{
+    create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    exif,
+    filter::PathFilter,
+    get_directory_from_user,
+    hash_cache::HashCache,
+    journal::Journal,
+    metrics::Metrics,
+    names::{is_same_file, numbered_name, NameSet},
+    progress::{Outcome, ProgressSink},
+    stamp::FileStamp,
+    transfer::{move_file, TransferMode, Verify},
+    walk::{is_access_denied, Inaccessible, WalkEntry, Walker},
+};
+pub use patterns::{copy_of, parse_screenshot, screenshot_name, ScreenshotName};
+
+/// Tidies a downloads or desktop folder: removes numbered copies a browser
+/// or file manager left of a file already there, and gathers screenshots
+/// under one naming scheme in one folder
+pub struct DownloadCleaner {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Glob patterns a file's relative path must match to be looked at
+    include: Vec<String>,
+    /// Glob patterns that leave a file alone
+    exclude: Vec<String>,
+    /// Look inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Where screenshots go; `Screenshots/` in the input by default
+    screenshots_dir: Option<PathBuf>,
+    copies: bool,
+    screenshots: bool,
+    delete_mode: DeleteMode,
+    hash_cache: bool,
+    /// Show what would change and stop there
+    dry_run: bool,
+    /// Abort on the first file that cannot be removed or moved
+    fail_fast: bool,
+    metrics: Metrics,
+}
+
+/// Everything a cleanup run needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Look inside working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Remove numbered copies with the same contents as a lower-numbered
+    /// name next to them
+    pub collapse_copies: bool,
+    /// Rename screenshots and move them into `screenshots_dir`
+    pub screenshots: bool,
+    pub screenshots_dir: PathBuf,
+    /// How copies are removed
+    pub delete_mode: DeleteMode,
+    /// Hash cache file, so unchanged files are not read again
+    pub hash_cache: Option<PathBuf>,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl CleanupOptions {
+    /// Both cleanups on the files directly in `input_dir`, screenshots
+    /// going to `Screenshots/` in it, copies to the trash, writing no undo
+    /// manifest
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            screenshots_dir: input_dir.join("Screenshots"),
+            input_dir,
+            recursive: false,
+            include_repos: false,
+            filter: PathFilter::default(),
+            collapse_copies: true,
+            screenshots: true,
+            delete_mode: DeleteMode::default(),
+            hash_cache: None,
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// What [`plan`] found, before anything is removed or moved
+#[derive(Debug, Default, Serialize)]
+pub struct CleanupPlan {
+    /// Numbered copies to remove, each with the lowest-numbered name
+    /// holding the same contents, which is kept
+    pub duplicates: Vec<Duplicate>,
+    /// Numbered copies whose contents differ from every lower-numbered
+    /// name, left alone
+    pub distinct: usize,
+    /// Screenshots and the names they get in the screenshots folder
+    pub screenshots: Vec<Rename>,
+    /// Screenshots that already have their name and folder
+    pub unchanged: usize,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be hashed, left alone
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+    /// Size and modification time of every copy and the file it copies
+    /// when they were hashed, checked again before a copy is removed
+    #[serde(skip)]
+    pub stamps: HashMap<PathBuf, FileStamp>,
+}
+
+impl CleanupPlan {
+    /// Prints every copy to remove and every screenshot to move, paths
+    /// relative to `input`, then the totals
+    pub fn print_preview(&self, input: &Path) {
+        let relative = |path: &Path| path.strip_prefix(input).unwrap_or(path).display().to_string();
+        if !self.duplicates.is_empty() {
+            println!();
+            println!("Copies to remove:");
+            for duplicate in &self.duplicates {
+                println!("  {}  (same as {})", relative(&duplicate.path), relative(&duplicate.original));
+            }
+        }
+        if !self.screenshots.is_empty() {
+            println!();
+            println!("Screenshots to move:");
+            for rename in &self.screenshots {
+                println!("  {}  →  {}", relative(&rename.source), relative(&rename.target));
+            }
+        }
+        println!();
+        println!(
+            "{} copies to remove, {} screenshots to move",
+            self.duplicates.len(),
+            self.screenshots.len()
+        );
+        if self.distinct > 0 {
+            println!("{} numbered copies differ from the file they are named after and stay", self.distinct);
+        }
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct CleanupSummary {
+    pub removed: Vec<Duplicate>,
+    pub moved: Vec<Rename>,
+    /// Copies left alone because they, or the file they copy, changed
+    /// since they were hashed
+    pub changed: Vec<PathBuf>,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be hashed, removed, or moved
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&CleanupSummary> for Summary {
+    fn from(summary: &CleanupSummary) -> Self {
+        Summary {
+            files: summary.removed.len() + summary.moved.len(),
+            bytes: summary.removed.iter().map(|duplicate| duplicate.size).sum(),
+            skipped: summary.changed.len(),
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Numbered copies of one name in one directory, with the file of that
+/// name if there is one, as `(number, entry)`; the file itself is 0
+type CopyGroups = IndexMap<(PathBuf, String, Option<OsString>), Vec<(u32, WalkEntry)>>;
+
+/// Finds the numbered copies to remove and the screenshots to move. Copies
+/// are only compared with files of the same name in the same directory,
+/// and only hashed when their size matches one of them.
+pub fn plan(options: &CleanupOptions, progress: impl ProgressSink) -> Result<CleanupPlan> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    progress.scan_started(root);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .sorted(true)
+        .skip_hidden(true)
+        .filter(options.filter.clone())
+        .files(root);
+
+    let mut plan = CleanupPlan {
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..CleanupPlan::default()
+    };
+    let mut groups = CopyGroups::new();
+    let mut files = Vec::new();
+    for entry in walk.files {
+        let Some(stem) = entry.path.file_stem().and_then(OsStr::to_str) else {
+            continue;
+        };
+        let (base, number) = copy_of(stem).unwrap_or_else(|| (stem.to_string(), 0));
+        let key = (
+            entry.path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            base,
+            entry.path.extension().map(OsStr::to_os_string),
+        );
+        files.push(entry.path.clone());
+        groups.entry(key).or_default().push((number, entry));
+    }
+
+    if options.collapse_copies {
+        find_duplicates(options, groups, &mut plan, &progress)?;
+    }
+    if options.screenshots {
+        let duplicates: HashSet<&Path> = plan.duplicates.iter().map(|duplicate| duplicate.path.as_path()).collect();
+        let screenshots: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| !duplicates.contains(path.as_path()))
+            .collect();
+        place_screenshots(&options.screenshots_dir, screenshots, &mut plan);
+    }
+    Ok(plan)
+}
+
+/// Hashes the members of every group that holds a copy and shares its size
+/// with another member, and marks each copy whose contents a
+/// lower-numbered member also has
+fn find_duplicates(
+    options: &CleanupOptions,
+    groups: CopyGroups,
+    plan: &mut CleanupPlan,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    let mut candidates = Vec::new();
+    for mut members in groups.into_values() {
+        if members.len() < 2 || members.iter().all(|(number, _)| *number == 0) {
+            continue;
+        }
+        plan.distinct += members.iter().filter(|(number, _)| *number > 0).count();
+        // Lowest number first, so the first of each contents is kept
+        members.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        let mut by_size: IndexMap<u64, Vec<(u32, WalkEntry)>> = IndexMap::new();
+        for (number, entry) in members {
+            by_size.entry(entry.metadata.len()).or_default().push((number, entry));
+        }
+        candidates.extend(by_size.into_values().filter(|same_size| same_size.len() > 1));
+    }
+
+    progress.phase_changed("Hashing", candidates.iter().map(|same_size| same_size.len() as u64).sum());
+    let errors = ErrorLog::new(options.fail_fast);
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    for same_size in candidates {
+        let mut originals: IndexMap<String, (PathBuf, FileStamp)> = IndexMap::new();
+        for (_, entry) in same_size {
+            progress.item_started(&entry.path);
+            let result = HashMethod::Sha256.hash(&entry.path, &entry.metadata, &cache);
+            let (digest, outcome) = match result {
+                Err(e) if is_access_denied(&e) => {
+                    plan.inaccessible.push(Inaccessible::new(&entry.path, &e));
+                    (None, Outcome::Skipped)
+                }
+                result => match errors.check(&entry.path, "hash", result)? {
+                    Some(digest) => (Some(digest), Outcome::Done),
+                    None => (None, Outcome::Failed),
+                },
+            };
+            progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+            let Some(digest) = digest else {
+                continue;
+            };
+            match originals.get(&digest) {
+                // Members come lowest number first, and only the file
+                // itself has none, so this is always a numbered copy
+                Some((original, stamp)) => {
+                    plan.stamps.insert(original.clone(), *stamp);
+                    plan.stamps.insert(entry.path.clone(), FileStamp::of(&entry.metadata));
+                    plan.distinct -= 1;
+                    plan.duplicates.push(Duplicate {
+                        original: original.clone(),
+                        size: entry.metadata.len(),
+                        path: entry.path,
+                    });
+                }
+                None => {
+                    originals.insert(digest, (entry.path, FileStamp::of(&entry.metadata)));
+                }
+            }
+        }
+    }
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+    plan.errors = errors.into_errors();
+    Ok(())
+}
+
+/// Plans a move into `dir` under a dated name for every screenshot among
+/// `files`. The time comes from the EXIF capture date, then the time in
+/// the name, then the modification time. A name already taken in `dir`
+/// gets a number, `name-2.ext`.
+fn place_screenshots(dir: &Path, files: Vec<PathBuf>, plan: &mut CleanupPlan) {
+    let mut taken = NameSet::existing(dir);
+    for source in files {
+        let Some(stem) = source.file_stem().and_then(OsStr::to_str) else {
+            continue;
+        };
+        let Some(name) = parse_screenshot(stem) else {
+            continue;
+        };
+        let from_name = match name {
+            ScreenshotName::Dated(time) => Some(time),
+            ScreenshotName::Undated => None,
+        };
+        let modified = || {
+            let modified = fs::metadata(&source).and_then(|metadata| metadata.modified()).ok()?;
+            Some(DateTime::<Local>::from(modified).naive_local())
+        };
+        // Every platform this runs on records modification times
+        let Some(time) = exif::capture_date(&source).or(from_name).or_else(modified) else {
+            continue;
+        };
+        let extension = source.extension().and_then(OsStr::to_str);
+        let target = dir.join(screenshot_name(time, extension));
+
+        // A screenshot already in the folder keeps its name when it is
+        // the one it would get
+        let in_dir = source.parent().is_some_and(|parent| is_same_file(parent, dir));
+        let own = in_dir.then(|| taken.key(source.file_name().unwrap_or_default()));
+        let mut candidate = target.clone();
+        let mut n = 2;
+        while Some(taken.key(candidate.file_name().unwrap())) != own && !taken.insert(candidate.file_name().unwrap()) {
+            candidate = dir.join(numbered_name(&target, n));
+            n += 1;
+        }
+        if in_dir && candidate.file_name() == source.file_name() {
+            plan.unchanged += 1;
+            continue;
+        }
+        plan.screenshots.push(Rename { source, target: candidate });
+    }
+}
+
+/// Removes the copies in `plan`, then moves the screenshots, recording
+/// both in an undo manifest. Removed copies are listed in it but cannot be
+/// brought back by it; the trash can. A copy that changed since it was
+/// hashed is left alone, and a screenshot's new name taken since planning
+/// is not overwritten.
+///
+/// ```
+/// use file_organizer_rust::modules::download_cleanup::{self, CleanupOptions};
+/// use file_organizer_rust::utils::delete::DeleteMode;
+///
+/// let dir = tempfile::tempdir()?;
+/// let path = |name: &str| dir.path().join(name);
+/// std::fs::write(path("report.pdf"), "final")?;
+/// std::fs::write(path("report (1).pdf"), "final")?;
+/// std::fs::write(path("report (2).pdf"), "draft")?;
+/// std::fs::write(path("Screenshot from 2024-03-05 14-22-10.png"), "pixels")?;
+///
+/// let mut options = CleanupOptions::new(dir.path().to_path_buf());
+/// options.delete_mode = DeleteMode::Permanent;
+/// let plan = download_cleanup::plan(&options, ())?;
+/// assert_eq!((plan.duplicates.len(), plan.distinct), (1, 1));
+/// let summary = download_cleanup::apply(&options, plan, ())?;
+/// assert_eq!((summary.removed.len(), summary.moved.len()), (1, 1));
+/// assert!(!path("report (1).pdf").exists() && path("report (2).pdf").exists());
+/// assert!(path("Screenshots/Screenshot_2024-03-05_14-22-10.png").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &CleanupOptions, plan: CleanupPlan, progress: impl ProgressSink) -> Result<CleanupSummary> {
+    let errors = ErrorLog::new(options.fail_fast);
+    errors.extend(plan.errors);
+    let mut journal = Journal::new("clean-downloads", TransferMode::Move);
+    let mut summary = CleanupSummary {
+        repositories: plan.repositories,
+        inaccessible: plan.inaccessible,
+        ..CleanupSummary::default()
+    };
+    let result = remove_copies(plan.duplicates, &plan.stamps, options, &errors, &mut journal, &mut summary, &progress)
+        .and_then(|_| move_screenshots(plan.screenshots, options, &errors, &mut journal, &mut summary, &progress));
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // moved can be undone
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            summary.manifest = Some(path);
+        }
+    }
+    result?;
+
+    summary.errors = errors.into_errors();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+fn remove_copies(
+    duplicates: Vec<Duplicate>,
+    stamps: &HashMap<PathBuf, FileStamp>,
+    options: &CleanupOptions,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    summary: &mut CleanupSummary,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    progress.phase_changed("Removing copies", duplicates.len() as u64);
+    // A file that changed since it was hashed may no longer be a copy
+    let unchanged = |path: &Path| stamps.get(path).is_some_and(|stamp| stamp.matches(path));
+    let mut removable = Vec::new();
+    for duplicate in duplicates {
+        if !unchanged(&duplicate.path) || !unchanged(&duplicate.original) {
+            progress.item_finished(&duplicate.path, 0, Outcome::Skipped);
+            summary.changed.push(duplicate.path);
+            continue;
+        }
+        let metadata = fs::metadata(&duplicate.path).map_err(anyhow::Error::from);
+        if let Some(metadata) = errors.check(&duplicate.path, "read", metadata)? {
+            removable.push((duplicate, metadata));
+        }
+    }
+
+    // Removed in one batch, which the trash handles much faster
+    let paths: Vec<&Path> = removable.iter().map(|(duplicate, _)| duplicate.path.as_path()).collect();
+    let results = safe_delete_all(&paths, options.delete_mode);
+    for ((duplicate, metadata), result) in removable.into_iter().zip(results) {
+        let outcome = match errors.check(&duplicate.path, "delete", result)? {
+            Some(()) => Outcome::Done,
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&duplicate.path, duplicate.size, outcome);
+        if outcome == Outcome::Done {
+            if options.delete_mode != DeleteMode::DryRun {
+                journal.record_deletion(&duplicate.path, &metadata, Some("duplicate"));
+            }
+            summary.removed.push(duplicate);
+        }
+    }
+    Ok(())
+}
+
+fn move_screenshots(
+    screenshots: Vec<Rename>,
+    options: &CleanupOptions,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    summary: &mut CleanupSummary,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    if screenshots.is_empty() {
+        return Ok(());
+    }
+    progress.phase_changed("Moving screenshots", screenshots.len() as u64);
+    fs::create_dir_all(&options.screenshots_dir)
+        .with_context(|| format!("Failed to create {}", options.screenshots_dir.display()))?;
+    for rename in screenshots {
+        progress.item_started(&rename.source);
+        let result = if rename.target.exists() && !is_same_file(&rename.source, &rename.target) {
+            Err(anyhow::anyhow!("{} already exists, not overwriting it", rename.target.display()))
+        } else {
+            move_file(&rename.source, &rename.target, Verify::Size)
+                .with_context(|| format!("Failed to move to {}", rename.target.display()))
+                .and_then(|_| journal.record(&rename.source, &rename.target, Some("screenshot")))
+        };
+        let outcome = match errors.check(&rename.source, "move", result)? {
+            Some(()) => Outcome::Done,
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&rename.source, 0, outcome);
+        if outcome == Outcome::Done {
+            summary.moved.push(rename);
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl FileOrganizer for DownloadCleaner {
+    type Plan = CleanupPlan;
+    const NAME: &'static str = "clean-downloads";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            screenshots_dir: None,
+            copies: true,
+            screenshots: true,
+            delete_mode: DeleteMode::default(),
+            hash_cache: true,
+            dry_run: false,
+            fail_fast: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter downloads directory to clean up")?);
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<CleanupPlan> {
+        let spinner = create_spinner("Looking for copies and screenshots...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Always shows every change first; in a dry run stops there,
+    /// otherwise asks before making them
+    fn review(&self, plan: &CleanupPlan) -> Result<bool> {
+        if plan.duplicates.is_empty() && plan.screenshots.is_empty() {
+            println!("No copies to remove or screenshots to move");
+            return Ok(false);
+        }
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print_preview(input_dir);
+        if self.dry_run {
+            println!("Dry run finished, nothing was changed");
+            return Ok(false);
+        }
+        let mut prompt = Vec::new();
+        if !plan.duplicates.is_empty() {
+            prompt.push(format!("remove {} copies ({})", plan.duplicates.len(), self.delete_mode.verb()));
+        }
+        if !plan.screenshots.is_empty() {
+            prompt.push(format!("move {} screenshots", plan.screenshots.len()));
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Go ahead and {}?", prompt.join(" and ")))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: CleanupPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Cleaning up...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!(
+            "{} copies {}, {} screenshots moved",
+            summary.removed.len(),
+            options.delete_mode.verb(),
+            summary.moved.len()
+        ));
+        if !summary.changed.is_empty() {
+            println!("{} copies changed since they were checked and were left alone", summary.changed.len());
+        }
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to look inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "files inside them were not checked");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl DownloadCleaner {
+    /// Presets the directory to clean up so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Only looks at files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.pdf"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Looks inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Moves screenshots into `dir` instead of `Screenshots/` in the input
+    pub fn with_screenshots_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.screenshots_dir = dir;
+        self
+    }
+
+    /// Whether numbered copies are collapsed
+    pub fn with_copies(mut self, copies: bool) -> Self {
+        self.copies = copies;
+        self
+    }
+
+    /// Whether screenshots are renamed and moved
+    pub fn with_screenshots(mut self, screenshots: bool) -> Self {
+        self.screenshots = screenshots;
+        self
+    }
+
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Whether digests are kept between runs
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Shows what would change without changing anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first file that cannot be removed or moved
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<CleanupOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let defaults = CleanupOptions::new(input_dir);
+        Ok(CleanupOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            collapse_copies: self.copies,
+            screenshots: self.screenshots,
+            screenshots_dir: self.screenshots_dir.clone().unwrap_or_else(|| defaults.screenshots_dir.clone()),
+            delete_mode: self.delete_mode,
+            hash_cache: if self.hash_cache { Some(HashCache::default_path()?) } else { None },
+            manifest_dir: Some(Journal::default_dir()?),
+            fail_fast: self.fail_fast,
+            ..defaults
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// What a screenshot's name says about when it was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotName {
+    Dated(NaiveDateTime),
+    /// Windows' `Screenshot (12)` and other names without a time
+    Undated,
+}
+
+/// The name a file had before a browser or file manager numbered a second
+/// download or copy of it, and its number: `report (2)` is copy 2 of
+/// `report`, `photo-copy` and `photo copy` copy 1 of `photo`, `notes -
+/// Copy (3)` copy 3 of `notes`. Takes the name without its extension.
+///
+/// ```
+/// use file_organizer_rust::modules::download_cleanup::copy_of;
+///
+/// assert_eq!(copy_of("report (2)"), Some(("report".to_string(), 2)));
+/// assert_eq!(copy_of("image-copy"), Some(("image".to_string(), 1)));
+/// assert_eq!(copy_of("notes - Copy (3)"), Some(("notes".to_string(), 3)));
+/// assert_eq!(copy_of("report"), None);
+/// // Windows numbers every screenshot this way; they are not copies
+/// assert_eq!(copy_of("Screenshot (12)"), None);
+/// ```
+pub fn copy_of(stem: &str) -> Option<(String, u32)> {
+    static COPY: OnceLock<Regex> = OnceLock::new();
+    static NUMBERED: OnceLock<Regex> = OnceLock::new();
+    let copy = COPY.get_or_init(|| {
+        Regex::new(r"(?i)^(?P<base>.+?)(?: - copy| copy|[-_]copy)(?: ?\((?P<paren>\d+)\)| (?P<n>\d+))?$").unwrap()
+    });
+    let numbered = NUMBERED.get_or_init(|| Regex::new(r"^(?P<base>.+?) ?\((?P<n>\d+)\)$").unwrap());
+
+    if parse_screenshot(stem) == Some(ScreenshotName::Undated) {
+        return None;
+    }
+    let captures = copy.captures(stem).or_else(|| numbered.captures(stem))?;
+    let n = match captures.name("paren").or_else(|| captures.name("n")) {
+        Some(n) => n.as_str().parse().ok()?,
+        None => 1,
+    };
+    Some((captures["base"].to_string(), n))
+}
+
+/// Whether `stem`, a name without its extension, is one that macOS,
+/// Windows, GNOME, KDE, or Android gives screenshots, or the name
+/// [`screenshot_name`] gives them, and the time in it if there is one
+///
+/// ```
+/// use file_organizer_rust::modules::download_cleanup::{parse_screenshot, ScreenshotName};
+///
+/// let taken = chrono::NaiveDate::from_ymd_opt(2024, 3, 5).unwrap().and_hms_opt(14, 22, 10).unwrap();
+/// for stem in [
+///     "Screenshot 2024-03-05 at 14.22.10",
+///     "Screen Shot 2024-03-05 at 2.22.10\u{202f}PM",
+///     "Screenshot from 2024-03-05 14-22-10",
+///     "Screenshot 2024-03-05 142210",
+///     "Screenshot_20240305_142210",
+///     "Screenshot_20240305-142210_Chrome",
+///     "Screenshot_2024-03-05_14-22-10",
+/// ] {
+///     assert_eq!(parse_screenshot(stem), Some(ScreenshotName::Dated(taken)), "{}", stem);
+/// }
+/// assert_eq!(parse_screenshot("Screenshot (12)"), Some(ScreenshotName::Undated));
+/// assert_eq!(parse_screenshot("Screenshot ideas"), None);
+/// ```
+pub fn parse_screenshot(stem: &str) -> Option<ScreenshotName> {
+    static DATED: OnceLock<Vec<Regex>> = OnceLock::new();
+    static UNDATED: OnceLock<Regex> = OnceLock::new();
+    let dated = DATED.get_or_init(|| {
+        [
+            // macOS; before Mojave "Screen Shot", 12-hour clocks add AM/PM
+            // after a narrow space, a second display adds " (2)"
+            r"^(?:screenshot|screen shot) (?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2}) at (?P<H>\d{1,2})\.(?P<M>\d{2})\.(?P<S>\d{2})(?:[ \x{202f}](?P<p>[ap]m))?(?: \(\d+\))?$",
+            // GNOME
+            r"^screenshot from (?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2}) (?P<H>\d{2})-(?P<M>\d{2})-(?P<S>\d{2})(?:-\d+)?$",
+            // Windows Snipping Tool
+            r"^screenshot (?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2}) (?P<H>\d{2})(?P<M>\d{2})(?P<S>\d{2})(?: \(\d+\))?$",
+            // KDE Spectacle and Android, which may add the app's name
+            r"^screenshot_(?P<y>\d{4})(?P<m>\d{2})(?P<d>\d{2})[_-](?P<H>\d{2})(?P<M>\d{2})(?P<S>\d{2})(?:[_-].*)?$",
+            // Older Android, and names already cleaned up, maybe numbered
+            r"^screenshot_(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})[_-](?P<H>\d{2})-(?P<M>\d{2})-(?P<S>\d{2})(?:[_-].*)?$",
+        ]
+        .iter()
+        .map(|pattern| Regex::new(&format!("(?i){}", pattern)).unwrap())
+        .collect()
+    });
+    let undated = UNDATED.get_or_init(|| Regex::new(r"(?i)^(?:screenshot|screen shot)(?: ?\(\d+\))?$").unwrap());
+
+    if let Some(captures) = dated.iter().find_map(|pattern| pattern.captures(stem)) {
+        // A name that looks right but holds an impossible time is not one
+        return time_in(&captures).map(ScreenshotName::Dated);
+    }
+    undated.is_match(stem).then_some(ScreenshotName::Undated)
+}
+
+/// `Screenshot_YYYY-MM-DD_HH-MM-SS.<extension>`
+pub fn screenshot_name(taken: NaiveDateTime, extension: Option<&str>) -> String {
+    let name = format!("Screenshot_{}", taken.format("%Y-%m-%d_%H-%M-%S"));
+    match extension {
+        Some(extension) => format!("{}.{}", name, extension),
+        None => name,
+    }
+}
+
+fn time_in(captures: &Captures) -> Option<NaiveDateTime> {
+    let number = |name: &str| captures[name].parse::<u32>().ok();
+    let mut hour = number("H")?;
+    if let Some(period) = captures.name("p") {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+        let pm = period.as_str().eq_ignore_ascii_case("pm");
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    }
+    NaiveDate::from_ymd_opt(number("y")? as i32, number("m")?, number("d")?)?.and_hms_opt(
+        hour,
+        number("M")?,
+        number("S")?,
+    )
+}
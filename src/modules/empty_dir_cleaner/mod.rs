@@ -0,0 +1,522 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::utils::prompt::Confirm;
+use crate::utils::{
+    create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    get_directory_from_user,
+    junk::JunkList,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    walk::{is_repository, Inaccessible},
+};
+use crate::modules::base::{
+    print_changed, print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary,
+};
+
+/// Removes empty directories, deepest first, so a tree of nested empty
+/// folders collapses in one pass. Directories holding only junk files can
+/// go too, junk and all.
+pub struct EmptyDirCleaner {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Look inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Count directories holding only empty directories as empty
+    nested: bool,
+    /// Delete the junk in junk-only directories and remove them; `None`
+    /// until preset or asked
+    delete_junk: Option<bool>,
+    /// Keep directories modified more recently than this
+    min_age: Option<Duration>,
+    /// Keep the input directory itself even when it ends up empty
+    protect_root: bool,
+    /// Abort on the first entry that cannot be removed
+    fail_fast: bool,
+    delete_mode: DeleteMode,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// Everything a cleaning run needs, with no prompting
+pub struct EmptyDirOptions {
+    pub input_dirs: Vec<PathBuf>,
+    /// Look at the whole tree; otherwise only the input directories' own
+    /// subdirectories are considered, and one holding any directory is kept
+    pub recursive: bool,
+    /// Look inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Count directories holding only empty directories as empty
+    pub nested: bool,
+    /// Delete the junk in directories that hold nothing else and remove
+    /// them; otherwise they are listed and kept
+    pub delete_junk: bool,
+    /// What counts as junk
+    pub junk: JunkList,
+    /// Keep directories modified more recently than this, so a folder
+    /// another program just created is not pulled out from under it
+    pub min_age: Option<Duration>,
+    /// Keep the input directories themselves
+    pub protect_root: bool,
+    /// How junk files are deleted. Empty directories hold nothing to
+    /// restore, so they are removed outright unless this is a dry run.
+    pub delete_mode: DeleteMode,
+    /// Abort on the first per-entry error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl EmptyDirOptions {
+    /// A recursive clean of `input_dirs` that collapses nested empty
+    /// directories, keeps junk-only ones and the input directories, and
+    /// moves junk to the trash
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            recursive: true,
+            include_repos: false,
+            nested: true,
+            delete_junk: false,
+            junk: JunkList::default(),
+            min_age: None,
+            protect_root: true,
+            delete_mode: DeleteMode::Trash,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A directory to remove
+#[derive(Debug, Clone, Serialize)]
+pub struct EmptyDir {
+    pub path: PathBuf,
+    /// Junk files and junk directories directly inside, deleted first
+    pub junk: Vec<PathBuf>,
+}
+
+/// Directories found by [`plan`], deepest first, before anything is removed
+#[derive(Debug, Default, Serialize)]
+pub struct EmptyDirPlan {
+    pub dirs: Vec<EmptyDir>,
+    /// Directories holding nothing but junk, kept because deleting junk
+    /// was not asked for
+    pub junk_only: Vec<PathBuf>,
+    /// Empty directories kept because they were modified too recently
+    pub too_recent: Vec<PathBuf>,
+    /// Working copies that were not looked into
+    pub repositories: Vec<PathBuf>,
+    /// Directories that could not be read, and so are kept
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl EmptyDirPlan {
+    pub fn junk_count(&self) -> usize {
+        self.dirs.iter().map(|dir| dir.junk.len()).sum()
+    }
+
+    /// Lists the directories to remove, relative to `input`, with the junk
+    /// each holds
+    pub fn print_report(&self, input: &Path) {
+        let relative = |path: &Path| match path.strip_prefix(input) {
+            Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => path.display().to_string(),
+        };
+        println!();
+        for dir in &self.dirs {
+            if dir.junk.is_empty() {
+                println!("  {}/", relative(&dir.path));
+            } else {
+                println!("  {}/ ({} junk files)", relative(&dir.path), dir.junk.len());
+            }
+        }
+        println!(
+            "{} empty directories, holding {} junk files",
+            self.dirs.len(),
+            self.junk_count()
+        );
+        if !self.junk_only.is_empty() {
+            println!(
+                "{} directories holding only junk files are kept (use --delete-junk to remove them)",
+                self.junk_only.len()
+            );
+        }
+        if !self.too_recent.is_empty() {
+            println!("{} empty directories are kept as they were modified too recently", self.too_recent.len());
+        }
+    }
+}
+
+/// Outcome of [`execute`]
+#[derive(Debug, Default, Serialize)]
+pub struct EmptyDirSummary {
+    /// Directories removed, or under `DeleteMode::DryRun` the ones that
+    /// would be
+    pub removed: Vec<PathBuf>,
+    /// Junk files and directories deleted from them, or the ones that
+    /// would be
+    pub junk_deleted: Vec<PathBuf>,
+    /// Directories something appeared in since the scan, kept
+    pub changed: Vec<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Entries that could not be deleted
+    pub errors: Vec<FileError>,
+    /// Directories that could not be read
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&EmptyDirSummary> for Summary {
+    fn from(summary: &EmptyDirSummary) -> Self {
+        Summary {
+            files: summary.removed.len(),
+            bytes: 0,
+            skipped: summary.changed.len(),
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Whether a scanned directory can go
+enum Verdict {
+    Keep,
+    Remove,
+}
+
+/// Finds every directory under the input directories that holds nothing,
+/// or only junk, or (with `nested`) only directories that go as well.
+/// Listed children first, so removing them in order collapses whole trees.
+pub fn plan(options: &EmptyDirOptions, progress: impl ProgressSink) -> Result<EmptyDirPlan> {
+    if options.input_dirs.is_empty() {
+        anyhow::bail!("No input directory given");
+    }
+    let mut plan = EmptyDirPlan::default();
+    let now = SystemTime::now();
+    for root in &options.input_dirs {
+        if !root.is_dir() {
+            anyhow::bail!("{} is not a directory", root.display());
+        }
+        progress.scan_started(root);
+        scan(options, root, true, now, &mut plan);
+    }
+    Ok(plan)
+}
+
+fn scan(options: &EmptyDirOptions, dir: &Path, is_root: bool, now: SystemTime, plan: &mut EmptyDirPlan) -> Verdict {
+    let mut entries: Vec<fs::DirEntry> = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().collect(),
+        Err(e) => {
+            plan.inaccessible.push(Inaccessible::new(dir, e));
+            return Verdict::Keep;
+        }
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut keep = false;
+    let mut junk = Vec::new();
+    for entry in entries {
+        let path = entry.path();
+        // Symlinks are content, never followed
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        if !is_dir {
+            if options.junk.is_junk_file(&path) {
+                junk.push(path);
+            } else {
+                keep = true;
+            }
+        } else if options.junk.is_junk_dir(&path) {
+            junk.push(path);
+        } else if !options.include_repos && is_repository(&path) {
+            plan.repositories.push(path);
+            keep = true;
+        } else if !options.recursive && !is_root {
+            keep = true;
+        } else {
+            let verdict = scan(options, &path, false, now, plan);
+            keep |= matches!(verdict, Verdict::Keep) || !options.nested;
+        }
+    }
+
+    if keep {
+        return Verdict::Keep;
+    }
+    if !junk.is_empty() && !options.delete_junk {
+        plan.junk_only.push(dir.to_path_buf());
+        return Verdict::Keep;
+    }
+    if let Some(min_age) = options.min_age {
+        let modified = fs::metadata(dir).and_then(|metadata| metadata.modified());
+        let old_enough = modified.is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age >= min_age));
+        if !old_enough {
+            plan.too_recent.push(dir.to_path_buf());
+            return Verdict::Keep;
+        }
+    }
+    if is_root && options.protect_root {
+        return Verdict::Keep;
+    }
+    plan.dirs.push(EmptyDir { path: dir.to_path_buf(), junk });
+    Verdict::Remove
+}
+
+/// Deletes the junk in each planned directory the way
+/// `options.delete_mode` asks, then removes the directory. A directory
+/// that is no longer empty is kept and reported as changed.
+pub fn apply(options: &EmptyDirOptions, plan: EmptyDirPlan, progress: impl ProgressSink) -> Result<EmptyDirSummary> {
+    progress.phase_changed("Removing", plan.dirs.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut summary = EmptyDirSummary::default();
+    for dir in plan.dirs {
+        let mut junk_left = false;
+        for (path, result) in dir.junk.iter().zip(safe_delete_all(&dir.junk, options.delete_mode)) {
+            match errors.check(path, "delete", result)? {
+                Some(()) => summary.junk_deleted.push(path.clone()),
+                None => junk_left = true,
+            }
+        }
+        if junk_left {
+            progress.item_finished(&dir.path, 0, Outcome::Failed);
+            continue;
+        }
+
+        let result = match options.delete_mode {
+            DeleteMode::DryRun => Ok(()),
+            _ => fs::remove_dir(&dir.path),
+        };
+        let outcome = match result {
+            Ok(()) => {
+                summary.removed.push(dir.path.clone());
+                Outcome::Done
+            }
+            Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => {
+                summary.changed.push(dir.path.clone());
+                Outcome::Skipped
+            }
+            Err(e) => match errors.check::<()>(&dir.path, "remove", Err(e.into()))? {
+                Some(()) => Outcome::Done,
+                None => Outcome::Failed,
+            },
+        };
+        progress.item_finished(&dir.path, 0, outcome);
+    }
+
+    summary.repositories = plan.repositories;
+    summary.errors = errors.into_errors();
+    summary.inaccessible = plan.inaccessible;
+    Ok(summary)
+}
+
+/// Finds the empty directories under the input directories and removes them
+///
+/// ```
+/// use file_organizer_rust::modules::empty_dir_cleaner::{self, EmptyDirOptions};
+/// use file_organizer_rust::utils::delete::DeleteMode;
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir_all(dir.path().join("a/b/c"))?;
+/// std::fs::create_dir(dir.path().join("junk"))?;
+/// std::fs::write(dir.path().join("junk/.DS_Store"), "")?;
+/// std::fs::create_dir(dir.path().join("photos"))?;
+/// std::fs::write(dir.path().join("photos/photo.jpg"), "jpeg")?;
+///
+/// let options = EmptyDirOptions {
+///     delete_junk: true,
+///     delete_mode: DeleteMode::Permanent,
+///     ..EmptyDirOptions::new(vec![dir.path().to_path_buf()])
+/// };
+/// let summary = empty_dir_cleaner::execute(&options, ())?;
+/// assert_eq!(summary.removed.len(), 4);
+/// assert_eq!(summary.junk_deleted.len(), 1);
+/// assert!(!dir.path().join("a").exists());
+/// assert!(dir.path().join("photos").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &EmptyDirOptions, progress: impl ProgressSink) -> Result<EmptyDirSummary> {
+    let plan = plan(options, &progress)?;
+    let summary = apply(options, plan, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for EmptyDirCleaner {
+    type Plan = EmptyDirPlan;
+    const NAME: &'static str = "clean-empty-dirs";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include_repos: false,
+            nested: true,
+            delete_junk: None,
+            min_age: None,
+            protect_root: true,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to clean of empty directories")?);
+        }
+        if self.delete_junk.is_none() {
+            self.delete_junk = Some(
+                Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Also remove directories holding only junk files (.DS_Store, Thumbs.db, ...)?")
+                    .default(false)
+                    .interact()?,
+            );
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<EmptyDirPlan> {
+        let spinner = create_spinner("Scanning for empty directories...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Lists what was found and asks before removing it, except in a dry
+    /// run, which removes nothing anyway
+    fn review(&self, plan: &EmptyDirPlan) -> Result<bool> {
+        if plan.dirs.is_empty() {
+            println!("No empty directories found");
+            if !plan.junk_only.is_empty() {
+                println!(
+                    "{} directories hold only junk files; use --delete-junk to remove them",
+                    plan.junk_only.len()
+                );
+            }
+            return Ok(false);
+        }
+        plan.print_report(self.input_dir.as_deref().unwrap_or(Path::new("")));
+        if self.delete_mode == DeleteMode::DryRun {
+            return Ok(true);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Remove {} directories?", plan.dirs.len()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: EmptyDirPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Removing empty directories...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        let removed = match self.delete_mode {
+            DeleteMode::DryRun => "left in place (dry run)",
+            _ => "removed",
+        };
+        spinner.finish_with_message(format!(
+            "{} directories {}, {} junk files {}",
+            summary.removed.len(),
+            removed,
+            summary.junk_deleted.len(),
+            options.delete_mode.verb()
+        ));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to clean them)",
+                summary.repositories.len()
+            );
+        }
+        print_changed(&summary.changed, "they are no longer empty and were kept");
+        print_inaccessible(&summary.inaccessible, "they and the directories above them were kept");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl EmptyDirCleaner {
+    /// Presets the directory to clean so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Looks inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Whether directories holding only empty directories count as empty
+    pub fn with_nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+
+    /// Presets whether junk-only directories are removed, junk and all, so
+    /// the prompt is skipped
+    pub fn with_delete_junk(mut self, delete_junk: bool) -> Self {
+        self.delete_junk = Some(delete_junk);
+        self
+    }
+
+    /// Keeps directories modified less than `age` ago
+    pub fn with_min_age(mut self, age: Duration) -> Self {
+        self.min_age = Some(age);
+        self
+    }
+
+    /// Whether the input directory itself is kept when it ends up empty
+    pub fn with_protect_root(mut self, protect: bool) -> Self {
+        self.protect_root = protect;
+        self
+    }
+
+    /// Trashes, permanently deletes, or only lists the junk; empty
+    /// directories are removed unless this is a dry run
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Aborts on the first entry that cannot be removed instead of
+    /// recording it and carrying on
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<EmptyDirOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| {
+            anyhow::anyhow!("Input directory not set")
+        })?;
+        Ok(EmptyDirOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            nested: self.nested,
+            delete_junk: self.delete_junk.unwrap_or(false),
+            junk: self.junk.clone(),
+            min_age: self.min_age,
+            protect_root: self.protect_root,
+            delete_mode: self.delete_mode,
+            fail_fast: self.fail_fast,
+            ..EmptyDirOptions::new(vec![input_dir])
+        })
+    }
+}
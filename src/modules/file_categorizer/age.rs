@@ -0,0 +1,73 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+
+/// Buckets used when the user keeps the defaults
+pub const DEFAULT_BUCKETS: &str = "recent:30d,this year:365d,1-3 years:3y,ancient";
+
+/// One age range of the age rule
+#[derive(Debug, Clone)]
+pub struct AgeBucket {
+    /// Folder under `by_age/`
+    pub folder: String,
+    /// Exclusive upper bound in days; `None` for the last bucket
+    pub below_days: Option<i64>,
+}
+
+/// Parses `label:age,...,label` where ages are `<n>d`, `<n>w`, `<n>m`
+/// (30 days) or `<n>y` (365 days). Every label but the last needs an age,
+/// and ages must increase.
+pub fn parse_buckets(spec: &str) -> Result<Vec<AgeBucket>> {
+    let parts: Vec<&str> = spec.split(',').map(str::trim).collect();
+    if parts.len() < 2 {
+        anyhow::bail!("Age buckets need at least two labels, e.g. \"recent:30d,old\"");
+    }
+
+    let mut buckets = Vec::new();
+    let mut previous: Option<i64> = None;
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        let (label, days) = match part.rsplit_once(':') {
+            Some((label, age)) if !last => (label.trim(), Some(parse_days(age.trim())?)),
+            None if last => (*part, None),
+            _ if last => anyhow::bail!("The last age bucket '{}' takes no age", part),
+            _ => anyhow::bail!("Age bucket '{}' needs an age, e.g. \"recent:30d\"", part),
+        };
+        if label.is_empty() || label.contains(['/', '\\']) {
+            anyhow::bail!("Invalid age bucket label '{}'", label);
+        }
+        if let (Some(lower), Some(upper)) = (previous, days) {
+            if upper <= lower {
+                anyhow::bail!("Age bucket '{}' must be older than the one before", label);
+            }
+        }
+        buckets.push(AgeBucket { folder: label.to_string(), below_days: days });
+        previous = days;
+    }
+    Ok(buckets)
+}
+
+/// Folder of the bucket a file dated `date` falls into, measured from `now`
+pub fn bucket_folder<'a>(buckets: &'a [AgeBucket], date: &NaiveDateTime, now: &NaiveDateTime) -> &'a str {
+    let age = (*now - *date).num_days().max(0);
+    buckets
+        .iter()
+        .find(|bucket| bucket.below_days.is_none_or(|below| age < below))
+        .map(|bucket| bucket.folder.as_str())
+        .unwrap_or_default()
+}
+
+fn parse_days(age: &str) -> Result<i64> {
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age '{}', use e.g. 30d, 12w, 6m, or 3y", age))?;
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "d" => 1,
+        "w" => 7,
+        "m" => 30,
+        "y" => 365,
+        _ => anyhow::bail!("Invalid age unit in '{}', use d, w, m, or y", age),
+    };
+    Ok(number * multiplier)
+}
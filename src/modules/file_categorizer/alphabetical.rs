@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+/// Bucket for names starting with an ASCII digit
+const DIGITS: &str = "0-9";
+/// Bucket for everything that is neither a letter nor a digit
+const SYMBOLS: &str = "#";
+
+/// Options for the first-letter rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlphaOptions {
+    /// Bucket `apple` and `Apple` together under `A/`
+    pub fold_case: bool,
+    /// Collect names starting with a digit in `0-9/` rather than `#/`
+    pub digit_bucket: bool,
+    /// Collect names starting with anything else in `#/`; without it such
+    /// files are left to the next rule or the unmatched policy
+    pub symbol_bucket: bool,
+    /// Add a second level from the first two letters, e.g. `A/AB/`
+    pub two_letter: bool,
+}
+
+impl Default for AlphaOptions {
+    fn default() -> Self {
+        Self {
+            fold_case: true,
+            digit_bucket: true,
+            symbol_bucket: true,
+            two_letter: false,
+        }
+    }
+}
+
+/// Parses `--alphabetical` options: a comma-separated subset of
+/// `case-sensitive`, `no-digits`, `no-symbols`, and `two-letter`, applied
+/// on top of the defaults. An empty spec keeps the defaults.
+pub fn parse_options(spec: &str) -> Result<AlphaOptions> {
+    let mut options = AlphaOptions::default();
+    for option in spec.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+        match option {
+            "case-sensitive" => options.fold_case = false,
+            "no-digits" => options.digit_bucket = false,
+            "no-symbols" => options.symbol_bucket = false,
+            "two-letter" => options.two_letter = true,
+            _ => anyhow::bail!(
+                "Unknown alphabetical option '{}' (use case-sensitive, no-digits, no-symbols, two-letter)",
+                option
+            ),
+        }
+    }
+    Ok(options)
+}
+
+/// Folder for `file` by the first character of its name: `A/`, `0-9/`, or
+/// `#/`. Letters of any script bucket by their uppercase form when folding
+/// case. The second level only applies to names whose first two characters
+/// are both letters.
+pub fn folder(file: &Path, options: &AlphaOptions) -> Option<PathBuf> {
+    let name = file.file_name()?.to_string_lossy();
+    let mut chars = name.chars();
+    let first = chars.next()?;
+
+    if first.is_alphabetic() {
+        let letter = letter(first, options);
+        let mut folder = PathBuf::from(&letter);
+        if options.two_letter {
+            if let Some(second) = chars.next().filter(|c| c.is_alphabetic()) {
+                folder.push(format!("{}{}", letter, self::letter(second, options)));
+            }
+        }
+        Some(folder)
+    } else if first.is_ascii_digit() && options.digit_bucket {
+        Some(PathBuf::from(DIGITS))
+    } else if options.symbol_bucket {
+        Some(PathBuf::from(SYMBOLS))
+    } else {
+        None
+    }
+}
+
+fn letter(c: char, options: &AlphaOptions) -> String {
+    if options.fold_case {
+        c.to_uppercase().collect()
+    } else {
+        c.to_string()
+    }
+}
@@ -0,0 +1,38 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::utils::sanitize::folder_name;
+use crate::utils::exif;
+
+/// Extensions the camera and location rules read EXIF from; other files are
+/// left alone
+pub(super) const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tif", "tiff", "heic", "heif", "dng", "webp", "png"];
+
+const UNKNOWN_CAMERA: &str = "Unknown Camera";
+
+/// `<Make Model>` folder for a photo, with `aliases` (keyed by the combined
+/// name) applied first. Photos without camera tags, such as scans and
+/// screenshots, go to `Unknown Camera`. `None` for non-photo files.
+pub fn folder(file: &Path, aliases: &BTreeMap<String, String>) -> Option<String> {
+    let ext = file.extension()?.to_string_lossy().to_lowercase();
+    if !PHOTO_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+
+    let name = exif::camera(file).and_then(|(make, model)| combine(make, model));
+    let folder = name
+        .map(|name| aliases.get(&name).cloned().unwrap_or(name))
+        .and_then(|name| folder_name(&name));
+    Some(folder.unwrap_or_else(|| UNKNOWN_CAMERA.to_string()))
+}
+
+/// Joins make and model with collapsed whitespace, dropping the make when
+/// the model already starts with it ("Canon" + "Canon EOS R5")
+fn combine(make: Option<String>, model: Option<String>) -> Option<String> {
+    let collapse = |s: String| s.split_whitespace().collect::<Vec<_>>().join(" ");
+    match (make.map(collapse), model.map(collapse)) {
+        (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (Some(only), None) | (None, Some(only)) => Some(only),
+        (None, None) => None,
+    }
+}
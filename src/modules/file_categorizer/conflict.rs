@@ -0,0 +1,161 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{hashing::same_contents, names::numbered_name, transfer};
+
+/// What to do when a file's category target already exists
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Keep both, adding a numeric suffix to the incoming file
+    #[default]
+    Rename,
+    Skip,
+    Overwrite,
+    /// Skip when the existing file has the same contents, rename otherwise
+    SkipIdentical,
+}
+
+/// How a single conflict was resolved
+pub enum Resolution {
+    /// Transfer to this path (the original target or a renamed one)
+    Transfer(PathBuf),
+    /// Transfer to the target in place of the file there, through
+    /// [`replace_with`]
+    Replace,
+    Skip(&'static str),
+}
+
+impl Resolution {
+    pub fn describe(&self) -> String {
+        match self {
+            Resolution::Transfer(path) => format!("written to {}", path.display()),
+            Resolution::Replace => "replaces it".to_string(),
+            Resolution::Skip(reason) => format!("skipped ({})", reason),
+        }
+    }
+}
+
+/// Decides where `source` goes when `target` is already taken
+pub fn resolve(source: &Path, target: &Path, policy: ConflictPolicy) -> Result<Resolution> {
+    // A previous run may already have sorted the file into its own category
+    if fs::canonicalize(source)? == fs::canonicalize(target)? {
+        return Ok(Resolution::Skip("already in place"));
+    }
+
+    match policy {
+        ConflictPolicy::Rename => Ok(Resolution::Transfer(unique_path(target))),
+        ConflictPolicy::Skip => Ok(Resolution::Skip("target exists")),
+        ConflictPolicy::Overwrite => Ok(Resolution::Replace),
+        ConflictPolicy::SkipIdentical => {
+            if same_contents(source, target)? {
+                Ok(Resolution::Skip("identical file exists"))
+            } else {
+                Ok(Resolution::Transfer(unique_path(target)))
+            }
+        }
+    }
+}
+
+/// Puts a new file at `target` with `write`. The file already there is
+/// moved aside first, then deleted once `write` succeeds, or put back when
+/// it fails, so a failed transfer never costs the existing file.
+pub fn replace_with<T>(target: &Path, write: impl FnOnce() -> Result<T>) -> Result<T> {
+    transfer::replace_with(target, target, write, |aside| Ok(fs::remove_file(aside)?))
+}
+
+/// `name-1.ext`, `name-2.ext`, ... next to `target`
+fn unique_path(target: &Path) -> PathBuf {
+    let mut counter = 1;
+    loop {
+        let candidate = target.with_file_name(numbered_name(target, counter));
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::transfer::{aside_path, transfer_file, TransferMode, Verify};
+
+    fn conflict(dir: &Path) -> (PathBuf, PathBuf) {
+        let (source, target) = (dir.join("incoming.txt"), dir.join("sorted").join("incoming.txt"));
+        fs::create_dir(dir.join("sorted")).unwrap();
+        fs::write(&source, "new").unwrap();
+        fs::write(&target, "old").unwrap();
+        (source, target)
+    }
+
+    #[test]
+    fn overwrite_replaces_the_target_once_the_transfer_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = conflict(dir.path());
+
+        assert!(matches!(resolve(&source, &target, ConflictPolicy::Overwrite).unwrap(), Resolution::Replace));
+        // Resolving alone changes nothing
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+
+        replace_with(&target, || transfer_file(&source, &target, TransferMode::Move, Verify::Size)).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(!source.exists());
+        assert!(!aside_path(&target).exists());
+    }
+
+    #[test]
+    fn a_failed_overwrite_keeps_the_original_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = conflict(dir.path());
+        fs::remove_file(&source).unwrap();
+
+        let result = replace_with(&target, || transfer_file(&source, &target, TransferMode::Copy, Verify::Size));
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert!(!aside_path(&target).exists());
+    }
+
+    #[test]
+    fn a_partly_written_replacement_gives_way_to_the_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, target) = conflict(dir.path());
+
+        let result: Result<()> = replace_with(&target, || {
+            fs::write(&target, "half")?;
+            anyhow::bail!("disk full")
+        });
+        assert_eq!(result.unwrap_err().to_string(), "disk full");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+
+    #[test]
+    fn rename_and_skip_leave_the_target_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = conflict(dir.path());
+
+        match resolve(&source, &target, ConflictPolicy::Rename).unwrap() {
+            Resolution::Transfer(path) => assert_eq!(path, target.with_file_name("incoming-1.txt")),
+            _ => panic!("expected a renamed transfer"),
+        }
+        assert!(matches!(resolve(&source, &target, ConflictPolicy::Skip).unwrap(), Resolution::Skip(_)));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+
+    #[test]
+    fn identical_files_are_skipped_and_different_ones_renamed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = conflict(dir.path());
+        assert!(matches!(
+            resolve(&source, &target, ConflictPolicy::SkipIdentical).unwrap(),
+            Resolution::Transfer(_)
+        ));
+
+        fs::write(&target, "new").unwrap();
+        assert!(matches!(resolve(&source, &target, ConflictPolicy::SkipIdentical).unwrap(), Resolution::Skip(_)));
+    }
+}
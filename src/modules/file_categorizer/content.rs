@@ -0,0 +1,206 @@
+use std::{fs::File, io::Read, path::Path};
+
+/// Broad content groups used by the content-type rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentGroup {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Executable,
+    Text,
+    Other,
+}
+
+impl ContentGroup {
+    /// Folder name under `by_content/`
+    pub fn folder(&self) -> &'static str {
+        match self {
+            ContentGroup::Image => "image",
+            ContentGroup::Video => "video",
+            ContentGroup::Audio => "audio",
+            ContentGroup::Document => "document",
+            ContentGroup::Archive => "archive",
+            ContentGroup::Executable => "executable",
+            ContentGroup::Text => "text",
+            ContentGroup::Other => "other",
+        }
+    }
+}
+
+/// Bytes read from the start of each file
+const SNIFF_LEN: usize = 8192;
+
+/// Classifies a file by its leading bytes, ignoring the extension
+pub fn sniff(file: &Path) -> std::io::Result<ContentGroup> {
+    Ok(identify(file)?.0)
+}
+
+/// The MIME type of a file by its leading bytes. The extension only
+/// tells apart formats that share a container, such as Office files and
+/// ZIPs, and kinds of plain text; anything unrecognized is
+/// `application/octet-stream`.
+pub fn mime_type(file: &Path) -> std::io::Result<&'static str> {
+    Ok(identify(file)?.1)
+}
+
+fn identify(file: &Path) -> std::io::Result<(ContentGroup, &'static str)> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(file)?.take(SNIFF_LEN as u64).read_to_end(&mut header)?;
+
+    let (group, mime) = classify(&header);
+    let ext = extension(file);
+    // Office and ebook files are ZIPs on the inside; the extension decides
+    if group == ContentGroup::Archive && header.starts_with(b"PK\x03\x04") {
+        if let Some(mime) = ext.as_deref().and_then(zip_document) {
+            return Ok((ContentGroup::Document, mime));
+        }
+    }
+    if group == ContentGroup::Text {
+        if let Some(mime) = ext.as_deref().and_then(text_subtype) {
+            return Ok((group, mime));
+        }
+    }
+    // Matroska and WebM share a header
+    if mime == "video/x-matroska" && ext.as_deref() == Some("webm") {
+        return Ok((group, "video/webm"));
+    }
+    Ok((group, mime))
+}
+
+/// MIME types of formats stored as ZIP containers that are not archives
+fn zip_document(ext: &str) -> Option<&'static str> {
+    let mime = match ext {
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "odp" => "application/vnd.oasis.opendocument.presentation",
+        "epub" => "application/epub+zip",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// MIME types of text formats only the extension tells apart
+fn text_subtype(ext: &str) -> Option<&'static str> {
+    let mime = match ext {
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// The group a file's extension claims, for extensions common enough to
+/// be worth checking against the content
+pub fn expected_group(file: &Path) -> Option<ContentGroup> {
+    let group = match extension(file)?.as_str() {
+        "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "ico" | "heic" => ContentGroup::Image,
+        "mp4" | "m4v" | "mkv" | "webm" | "avi" | "mov" | "flv" => ContentGroup::Video,
+        "mp3" | "flac" | "ogg" | "wav" | "m4a" => ContentGroup::Audio,
+        "pdf" | "doc" | "xls" | "ppt" | "rtf" | "docx" | "xlsx" | "pptx" | "odt" | "ods" | "odp" | "epub" => {
+            ContentGroup::Document
+        }
+        "zip" | "gz" | "tgz" | "bz2" | "xz" | "7z" | "rar" | "zst" | "tar" => ContentGroup::Archive,
+        "exe" | "dll" | "so" | "dylib" => ContentGroup::Executable,
+        "txt" | "md" | "csv" | "json" | "xml" | "html" | "htm" | "yaml" | "yml" | "toml" | "log" => ContentGroup::Text,
+        _ => return None,
+    };
+    Some(group)
+}
+
+fn extension(file: &Path) -> Option<String> {
+    file.extension().map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+fn classify(header: &[u8]) -> (ContentGroup, &'static str) {
+    let starts = |magic: &[u8]| header.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+
+    // ISO base media files (MP4, MOV, M4A, HEIC) share the `ftyp` box
+    if at(4, b"ftyp") {
+        return match header.get(8..12) {
+            Some(b"M4A ") | Some(b"M4B ") => (ContentGroup::Audio, "audio/mp4"),
+            Some(b"heic") | Some(b"heix") => (ContentGroup::Image, "image/heic"),
+            Some(b"mif1") => (ContentGroup::Image, "image/heif"),
+            Some(b"avif") => (ContentGroup::Image, "image/avif"),
+            Some(b"qt  ") => (ContentGroup::Video, "video/quicktime"),
+            _ => (ContentGroup::Video, "video/mp4"),
+        };
+    }
+    if starts(b"RIFF") {
+        return match header.get(8..12) {
+            Some(b"WEBP") => (ContentGroup::Image, "image/webp"),
+            Some(b"WAVE") => (ContentGroup::Audio, "audio/wav"),
+            Some(b"AVI ") => (ContentGroup::Video, "video/x-msvideo"),
+            _ => (ContentGroup::Other, "application/octet-stream"),
+        };
+    }
+
+    const SIGNATURES: &[(&[u8], ContentGroup, &str)] = &[
+        (b"\xFF\xD8\xFF", ContentGroup::Image, "image/jpeg"),
+        (b"\x89PNG\r\n\x1A\n", ContentGroup::Image, "image/png"),
+        (b"GIF87a", ContentGroup::Image, "image/gif"),
+        (b"GIF89a", ContentGroup::Image, "image/gif"),
+        (b"BM", ContentGroup::Image, "image/bmp"),
+        (b"II*\x00", ContentGroup::Image, "image/tiff"),
+        (b"MM\x00*", ContentGroup::Image, "image/tiff"),
+        (b"\x00\x00\x01\x00", ContentGroup::Image, "image/vnd.microsoft.icon"),
+        (b"\x1A\x45\xDF\xA3", ContentGroup::Video, "video/x-matroska"),
+        (b"FLV", ContentGroup::Video, "video/x-flv"),
+        (b"ID3", ContentGroup::Audio, "audio/mpeg"),
+        (b"\xFF\xFB", ContentGroup::Audio, "audio/mpeg"),
+        (b"\xFF\xF3", ContentGroup::Audio, "audio/mpeg"),
+        (b"fLaC", ContentGroup::Audio, "audio/flac"),
+        (b"OggS", ContentGroup::Audio, "audio/ogg"),
+        (b"%PDF", ContentGroup::Document, "application/pdf"),
+        (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", ContentGroup::Document, "application/x-ole-storage"),
+        (b"{\\rtf", ContentGroup::Document, "application/rtf"),
+        (b"PK\x03\x04", ContentGroup::Archive, "application/zip"),
+        (b"PK\x05\x06", ContentGroup::Archive, "application/zip"),
+        (b"\x1F\x8B", ContentGroup::Archive, "application/gzip"),
+        (b"BZh", ContentGroup::Archive, "application/x-bzip2"),
+        (b"\xFD7zXZ\x00", ContentGroup::Archive, "application/x-xz"),
+        (b"7z\xBC\xAF\x27\x1C", ContentGroup::Archive, "application/x-7z-compressed"),
+        (b"Rar!\x1A\x07", ContentGroup::Archive, "application/vnd.rar"),
+        (b"\x28\xB5\x2F\xFD", ContentGroup::Archive, "application/zstd"),
+        (b"\x7FELF", ContentGroup::Executable, "application/x-executable"),
+        (b"MZ", ContentGroup::Executable, "application/vnd.microsoft.portable-executable"),
+        (b"\xFE\xED\xFA\xCE", ContentGroup::Executable, "application/x-mach-binary"),
+        (b"\xFE\xED\xFA\xCF", ContentGroup::Executable, "application/x-mach-binary"),
+        (b"\xCE\xFA\xED\xFE", ContentGroup::Executable, "application/x-mach-binary"),
+        (b"\xCF\xFA\xED\xFE", ContentGroup::Executable, "application/x-mach-binary"),
+        (b"#!", ContentGroup::Executable, "text/x-shellscript"),
+    ];
+    if let Some((_, group, mime)) = SIGNATURES.iter().find(|(magic, _, _)| starts(magic)) {
+        return (*group, mime);
+    }
+    if at(257, b"ustar") {
+        return (ContentGroup::Archive, "application/x-tar");
+    }
+
+    if !header.is_empty() && !header.contains(&0) && looks_like_utf8(header) {
+        (ContentGroup::Text, "text/plain")
+    } else {
+        (ContentGroup::Other, "application/octet-stream")
+    }
+}
+
+/// UTF-8 check that tolerates a multi-byte sequence cut off by the read limit
+fn looks_like_utf8(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && bytes.len() - e.valid_up_to() < 4,
+    }
+}
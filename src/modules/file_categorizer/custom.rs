@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+use crate::config::Ruleset;
+
+/// A custom rule compiled from a rule set entry. Rules are tried in
+/// declaration order and the first match wins; within a category, entries
+/// are tried in the order they are listed.
+#[derive(Debug, Clone)]
+pub struct CustomRule {
+    pub category: String,
+    matchers: Vec<Matcher>,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Extension(String),
+    /// `/pattern/flags` against the file name, or the path relative to the
+    /// input directory with the `p` flag
+    Pattern { regex: Regex, relative_path: bool },
+}
+
+/// Compiles every entry of a rule set up front so invalid patterns are
+/// reported before any file is touched
+pub fn compile(ruleset: &Ruleset) -> Result<Vec<CustomRule>> {
+    ruleset
+        .iter()
+        .map(|(category, entries)| {
+            let matchers = entries
+                .iter()
+                .map(|entry| parse_entry(entry))
+                .collect::<Result<_>>()
+                .with_context(|| format!("Category '{}'", category))?;
+            Ok(CustomRule { category: category.clone(), matchers })
+        })
+        .collect()
+}
+
+/// Splits a prompt line (`Category:pdf,doc` or `Category:/^invoice/i`) into
+/// its category and entries, checking that patterns compile
+pub fn parse_line(line: &str) -> Result<(String, Vec<String>)> {
+    let (category, rest) = line
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Use 'Category:ext1,ext2,...' or 'Category:/pattern/flags'"))?;
+    let category = category.trim().to_string();
+    let rest = rest.trim();
+    if category.is_empty() || rest.is_empty() {
+        anyhow::bail!("Both a category and extensions or a pattern are required");
+    }
+
+    // Patterns may contain commas, so they are never split
+    let entries: Vec<String> = if rest.starts_with('/') {
+        vec![rest.to_string()]
+    } else {
+        rest.split(',').map(|ext| ext.trim().to_lowercase()).filter(|ext| !ext.is_empty()).collect()
+    };
+    for entry in &entries {
+        parse_entry(entry)?;
+    }
+    Ok((category, entries))
+}
+
+/// The first rule matching `file`, if any
+pub fn first_match<'a>(rules: &'a [CustomRule], file: &Path, input_dir: Option<&Path>) -> Option<&'a CustomRule> {
+    let name = file.file_name()?.to_string_lossy();
+    let ext = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    let relative = input_dir
+        .and_then(|dir| file.strip_prefix(dir).ok())
+        .unwrap_or(file)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    rules.iter().find(|rule| {
+        rule.matchers.iter().any(|matcher| match matcher {
+            Matcher::Extension(wanted) => ext.as_deref() == Some(wanted.as_str()),
+            Matcher::Pattern { regex, relative_path: true } => regex.is_match(&relative),
+            Matcher::Pattern { regex, relative_path: false } => regex.is_match(&name),
+        })
+    })
+}
+
+fn parse_entry(entry: &str) -> Result<Matcher> {
+    let Some(body) = entry.strip_prefix('/') else {
+        return Ok(Matcher::Extension(entry.trim_start_matches('.').to_lowercase()));
+    };
+
+    let end = body
+        .rfind('/')
+        .ok_or_else(|| anyhow::anyhow!("Pattern '{}' is missing its closing '/'", entry))?;
+    let (pattern, flags) = (&body[..end], &body[end + 1..]);
+    let mut case_insensitive = false;
+    let mut relative_path = false;
+    for flag in flags.chars() {
+        match flag {
+            'i' => case_insensitive = true,
+            'p' => relative_path = true,
+            _ => anyhow::bail!("Pattern '{}': unknown flag '{}' (use i or p)", entry, flag),
+        }
+    }
+
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .with_context(|| format!("Invalid pattern '{}'", entry))?;
+    Ok(Matcher::Pattern { regex, relative_path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(entries: &[(&str, &[&str])]) -> Vec<CustomRule> {
+        let ruleset: Ruleset = entries
+            .iter()
+            .map(|(category, entries)| (category.to_string(), entries.iter().map(|e| e.to_string()).collect()))
+            .collect();
+        compile(&ruleset).unwrap()
+    }
+
+    fn category<'a>(rules: &'a [CustomRule], file: &str) -> Option<&'a str> {
+        first_match(rules, Path::new(file), None).map(|rule| rule.category.as_str())
+    }
+
+    #[test]
+    fn the_first_declared_rule_wins() {
+        let pattern_first = rules(&[("Finance", &["/^invoice/i"]), ("Documents", &["pdf"])]);
+        assert_eq!(category(&pattern_first, "Invoice-2024.pdf"), Some("Finance"));
+        assert_eq!(category(&pattern_first, "manual.pdf"), Some("Documents"));
+
+        let extension_first = rules(&[("Documents", &["pdf"]), ("Finance", &["/^invoice/i"])]);
+        assert_eq!(category(&extension_first, "Invoice-2024.pdf"), Some("Documents"));
+        assert_eq!(category(&extension_first, "invoice.txt"), Some("Finance"));
+    }
+
+    #[test]
+    fn extensions_match_case_insensitively_with_or_without_a_dot() {
+        let rules = rules(&[("Images", &[".JPG", "png"])]);
+        assert_eq!(category(&rules, "photo.jpg"), Some("Images"));
+        assert_eq!(category(&rules, "scan.PNG"), Some("Images"));
+        assert_eq!(category(&rules, "jpg"), None);
+    }
+
+    #[test]
+    fn patterns_match_the_name_unless_asked_for_the_relative_path() {
+        let rules = rules(&[("Screenshots", &["/^Screen Shot/"]), ("Work", &["/^clients/acme//p"])]);
+        assert_eq!(category(&rules, "Screen Shot 2024.png"), Some("Screenshots"));
+        assert_eq!(category(&rules, "screen shot 2024.png"), None);
+
+        let input = Path::new("/downloads");
+        let matched = |file: &str| first_match(&rules, &input.join(file), Some(input)).map(|r| r.category.as_str());
+        assert_eq!(matched("clients/acme/brief.pdf"), Some("Work"));
+        assert_eq!(matched("acme/brief.pdf"), None);
+    }
+
+    #[test]
+    fn prompt_lines_keep_patterns_whole() {
+        assert_eq!(
+            parse_line("Docs: PDF, .doc ,").unwrap(),
+            ("Docs".to_string(), vec!["pdf".to_string(), ".doc".to_string()])
+        );
+        assert_eq!(
+            parse_line("Finance:/^invoice,receipt/i").unwrap(),
+            ("Finance".to_string(), vec!["/^invoice,receipt/i".to_string()])
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_reported_up_front() {
+        for line in ["no colon", "Docs:", ":pdf", "Finance:/^invoice", "Finance:/^invoice/x", "Finance:/(/"] {
+            assert!(parse_line(line).is_err(), "{:?}", line);
+        }
+        let ruleset: Ruleset = [("Broken".to_string(), vec!["/[/".to_string()])].into_iter().collect();
+        let error = compile(&ruleset).unwrap_err();
+        assert!(format!("{:#}", error).contains("Category 'Broken'"));
+    }
+}
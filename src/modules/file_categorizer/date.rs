@@ -0,0 +1,261 @@
+use anyhow::Result;
+use chrono::{
+    format::{Item, StrftimeItems},
+    Datelike, NaiveDateTime,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::utils::{exif, video};
+
+/// Which timestamp the date rule prefers; the others are used as fallbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSource {
+    Created,
+    Modified,
+    /// The EXIF capture date of photos and the container creation time of
+    /// videos
+    ExifFirst,
+}
+
+/// The timestamp that was actually used for a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolvedSource {
+    Created,
+    Modified,
+    Exif,
+    /// Creation time in a video's container metadata
+    Video,
+}
+
+impl ResolvedSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResolvedSource::Created => "creation time",
+            ResolvedSource::Modified => "modification time",
+            ResolvedSource::Exif => "EXIF capture date",
+            ResolvedSource::Video => "video creation time",
+        }
+    }
+}
+
+/// Folder layout used before the format became configurable
+pub const DEFAULT_FORMAT: &str = "%Y/%m-%B";
+
+/// Folder templates offered for each granularity
+pub const GRANULARITY_FORMATS: &[(&str, &str)] = &[
+    ("Year", "%Y"),
+    ("Year / month", DEFAULT_FORMAT),
+    ("Year / month / day", "%Y/%m/%d"),
+    ("Year / quarter", "%Y/Q%q"),
+];
+
+/// Extensions worth probing for an EXIF capture date
+const EXIF_EXTENSIONS: &[&str] = &["jpg", "jpeg", "tif", "tiff", "heic", "heif", "png", "webp"];
+
+/// Resolves the date of `file`, trying the preferred source first and
+/// falling back to birth time, then mtime, then the embedded date: EXIF for
+/// images, the container's creation time for videos.
+///
+/// Fails only when the file has neither a creation nor a modification
+/// time and no capture date.
+pub fn resolve(file: &Path, preference: DateSource) -> Result<(NaiveDateTime, ResolvedSource)> {
+    let metadata = fs::metadata(file)?;
+    resolve_with(file, preference, metadata.created(), metadata.modified())
+}
+
+/// [`resolve`] with the file's timestamps given, as the platform reported
+/// them
+fn resolve_with(
+    file: &Path,
+    preference: DateSource,
+    created: io::Result<SystemTime>,
+    modified: io::Result<SystemTime>,
+) -> Result<(NaiveDateTime, ResolvedSource)> {
+    let order: &[ResolvedSource] = match preference {
+        DateSource::Created => &[ResolvedSource::Created, ResolvedSource::Modified, ResolvedSource::Exif],
+        DateSource::Modified => &[ResolvedSource::Modified, ResolvedSource::Created, ResolvedSource::Exif],
+        DateSource::ExifFirst => &[ResolvedSource::Exif, ResolvedSource::Created, ResolvedSource::Modified],
+    };
+
+    for source in order {
+        let date = match source {
+            ResolvedSource::Created => created.as_ref().ok().copied().map(local_time),
+            ResolvedSource::Modified => modified.as_ref().ok().copied().map(local_time),
+            ResolvedSource::Exif if has_exif_extension(file) => exif::capture_date(file),
+            ResolvedSource::Exif if video::is_video(file) => {
+                if let Some(date) = video::probe(file).and_then(|info| info.created) {
+                    return Ok((date, ResolvedSource::Video));
+                }
+                None
+            }
+            ResolvedSource::Exif | ResolvedSource::Video => None,
+        };
+        if let Some(date) = date {
+            return Ok((date, *source));
+        }
+    }
+
+    anyhow::bail!("No usable date for {}", file.display())
+}
+
+fn local_time(time: SystemTime) -> NaiveDateTime {
+    chrono::DateTime::<chrono::Local>::from(time).naive_local()
+}
+
+fn has_exif_extension(file: &Path) -> bool {
+    file.extension()
+        .map(|ext| EXIF_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Checks a folder template: chrono specifiers plus `%q` for the quarter,
+/// with `/` separating nested folders
+pub fn validate_format(template: &str) -> Result<()> {
+    if template.trim().is_empty() {
+        anyhow::bail!("Date format must not be empty");
+    }
+    if template.contains('\\') {
+        anyhow::bail!("Date format '{}': use '/' to separate folders", template);
+    }
+    let expanded = expand_quarter(template, 1);
+    if StrftimeItems::new(&expanded).any(|item| matches!(item, Item::Error)) {
+        anyhow::bail!("Date format '{}' contains an invalid format specifier", template);
+    }
+    if template.split('/').any(|segment| segment.is_empty() || segment == "." || segment == "..") {
+        anyhow::bail!("Date format '{}' has an empty or relative folder segment", template);
+    }
+    Ok(())
+}
+
+/// Nested folder path for `date` under a validated template
+pub fn folder(date: &NaiveDateTime, template: &str) -> PathBuf {
+    let expanded = expand_quarter(template, quarter(date));
+    date.format(&expanded).to_string().split('/').collect()
+}
+
+/// Calendar quarter, 1 to 4
+pub fn quarter(date: &NaiveDateTime) -> u32 {
+    (date.month() - 1) / 3 + 1
+}
+
+/// Replaces `%q` with the quarter, leaving escaped `%%` for chrono
+fn expand_quarter(template: &str, quarter: u32) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('q') => expanded.push_str(&quarter.to_string()),
+            Some(next) => {
+                expanded.push('%');
+                expanded.push(next);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn unsupported() -> io::Result<SystemTime> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "creation time is not available on this filesystem"))
+    }
+
+    fn at(seconds: u64) -> io::Result<SystemTime> {
+        Ok(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    fn date(seconds: u64) -> NaiveDateTime {
+        local_time(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    const MODIFIED: u64 = 1_500_000_000;
+    const CREATED: u64 = 1_609_502_400;
+
+    #[test]
+    fn files_without_a_creation_time_are_filed_under_their_mtime() {
+        let file = Path::new("notes.txt");
+        let (resolved, source) = resolve_with(file, DateSource::Created, unsupported(), at(MODIFIED)).unwrap();
+        assert_eq!(source, ResolvedSource::Modified);
+        assert_eq!(resolved, date(MODIFIED));
+    }
+
+    #[test]
+    fn the_preferred_source_wins_when_available() {
+        let file = Path::new("notes.txt");
+        let resolve = |preference| resolve_with(file, preference, at(CREATED), at(MODIFIED)).unwrap();
+        assert_eq!(resolve(DateSource::Created), (date(CREATED), ResolvedSource::Created));
+        assert_eq!(resolve(DateSource::Modified), (date(MODIFIED), ResolvedSource::Modified));
+        // Files with no embedded date fall through to the next source
+        assert_eq!(resolve(DateSource::ExifFirst), (date(CREATED), ResolvedSource::Created));
+    }
+
+    #[test]
+    fn photos_without_exif_fall_back_to_file_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("photo.jpg");
+        image::RgbImage::new(4, 4).save(&photo).unwrap();
+
+        let (_, source) = resolve_with(&photo, DateSource::ExifFirst, unsupported(), at(MODIFIED)).unwrap();
+        assert_eq!(source, ResolvedSource::Modified);
+    }
+
+    #[test]
+    fn files_without_any_date_are_an_error() {
+        let result = resolve_with(Path::new("notes.txt"), DateSource::Created, unsupported(), unsupported());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn resolve_reads_the_timestamps_of_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "x").unwrap();
+        filetime::set_file_mtime(&file, filetime::FileTime::from_unix_time(MODIFIED as i64, 0)).unwrap();
+
+        assert_eq!(resolve(&file, DateSource::Modified).unwrap(), (date(MODIFIED), ResolvedSource::Modified));
+    }
+
+    #[test]
+    fn folder_templates_expand_the_quarter() {
+        let resolved = chrono::NaiveDate::from_ymd_opt(2017, 7, 14).unwrap().and_hms_opt(2, 40, 0).unwrap();
+        assert_eq!(folder(&resolved, DEFAULT_FORMAT), Path::new("2017/07-July"));
+        assert_eq!(folder(&resolved, "%Y/Q%q"), Path::new("2017/Q3"));
+        assert_eq!(folder(&resolved, "%Y/%m/%d"), Path::new("2017/07/14"));
+        assert_eq!(folder(&resolved, "100%%/%Y"), Path::new("100%/2017"));
+    }
+
+    #[test]
+    fn quarters_group_three_months() {
+        let first_of = |month| chrono::NaiveDate::from_ymd_opt(2024, month, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let quarters: Vec<u32> = (1..=12).map(|month| quarter(&first_of(month))).collect();
+        assert_eq!(quarters, [1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 4]);
+    }
+
+    #[test]
+    fn single_digit_months_and_days_are_zero_padded() {
+        let resolved = chrono::NaiveDate::from_ymd_opt(2024, 3, 5).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(folder(&resolved, "%Y-%m"), Path::new("2024-03"));
+        assert_eq!(folder(&resolved, "%Y/%m/%d"), Path::new("2024/03/05"));
+        assert_eq!(folder(&resolved, DEFAULT_FORMAT), Path::new("2024/03-March"));
+    }
+
+    #[test]
+    fn invalid_folder_templates_are_rejected() {
+        for template in ["", "%Y\\%m", "%Y/%!", "%Y//%m", "../%Y"] {
+            assert!(validate_format(template).is_err(), "{:?}", template);
+        }
+        assert!(validate_format("%Y/Q%q").is_ok());
+    }
+}
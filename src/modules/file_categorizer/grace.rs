@@ -0,0 +1,29 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// Files modified this recently may still be written to
+pub const DEFAULT_GRACE_SECS: u64 = 60;
+
+/// Extensions browsers and download managers use for unfinished downloads
+const IN_PROGRESS_EXTENSIONS: &[&str] = &["part", "crdownload", "download", "tmp", "partial", "opdownload"];
+
+/// Whether `file` looks like it is still being written: an unfinished
+/// download, or modified within the last `grace`
+pub fn is_in_progress(file: &Path, grace: Duration) -> Result<bool> {
+    let ext = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    if ext.is_some_and(|ext| IN_PROGRESS_EXTENSIONS.contains(&ext.as_str())) {
+        return Ok(true);
+    }
+    if grace.is_zero() {
+        return Ok(false);
+    }
+
+    let modified = fs::metadata(file)?.modified()?;
+    // A modification time in the future counts as recent
+    Ok(SystemTime::now().duration_since(modified).is_ok_and(|age| age < grace)
+        || modified > SystemTime::now())
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use super::camera::PHOTO_EXTENSIONS;
+use crate::utils::exif;
+use crate::utils::geocode::Geocoder;
+use crate::utils::sanitize::folder_name;
+
+/// Photos without GPS coordinates
+const NO_LOCATION: &str = "No Location";
+
+/// Photos whose coordinates are far from any known place, such as at sea
+const UNKNOWN_LOCATION: &str = "Unknown Location";
+
+/// `<Country>/<Region>` folder for a photo from its EXIF GPS position,
+/// reverse-geocoded offline; just `<Country>` where the table has no
+/// regions. Photos without a position go to `No Location`. `None` for
+/// non-photo files.
+pub fn folder(file: &Path, geocoder: &Geocoder) -> Option<PathBuf> {
+    let ext = file.extension()?.to_string_lossy().to_lowercase();
+    if !PHOTO_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+
+    let Some((latitude, longitude)) = exif::gps(file) else {
+        return Some(PathBuf::from(NO_LOCATION));
+    };
+    let Some(place) = geocoder.lookup(latitude, longitude) else {
+        return Some(PathBuf::from(UNKNOWN_LOCATION));
+    };
+    let mut folder = PathBuf::from(folder_name(&place.country)?);
+    if let Some(region) = place.region.as_deref().and_then(folder_name) {
+        folder.push(region);
+    }
+    Some(folder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::exif::tests::{gps_jpeg, PARIS, SANTIAGO};
+    use std::fs;
+
+    #[test]
+    fn photos_go_under_the_country_and_region_they_were_taken_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let geocoder = Geocoder::new();
+        let photo = |name: &str, bytes: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, bytes).unwrap();
+            folder(&path, &geocoder)
+        };
+        assert_eq!(photo("paris.jpg", &gps_jpeg(PARIS.0, PARIS.1)), Some(Path::new("France").join("Île-de-France")));
+        assert_eq!(
+            photo("santiago.JPEG", &gps_jpeg(SANTIAGO.0, SANTIAGO.1)),
+            Some(Path::new("Chile").join("Santiago Metropolitan"))
+        );
+        // Mid-Atlantic
+        assert_eq!(
+            photo("sea.jpg", &gps_jpeg((30, 0, 0, b'N'), (50, 0, 0, b'W'))),
+            Some(PathBuf::from(UNKNOWN_LOCATION))
+        );
+        assert_eq!(photo("plain.jpg", &[0xFF, 0xD8, 0xFF, 0xD9]), Some(PathBuf::from(NO_LOCATION)));
+        assert_eq!(photo("notes.txt", b"text"), None);
+    }
+}
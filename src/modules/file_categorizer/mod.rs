@@ -1,122 +1,1186 @@
-use anyhow::Result;
+mod camera;
+mod conflict;
+mod age;
+mod alphabetical;
+pub(crate) mod content;
+mod custom;
+mod date;
+pub(crate) mod grace;
+mod location;
+mod music;
+mod plan;
+pub(crate) mod preset;
+mod report;
+pub(crate) mod route;
+mod size;
+mod summary;
+pub(crate) mod types;
+mod video;
+
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use dialoguer::{theme::ColorfulTheme, MultiSelect};
-use std::{collections::HashMap, fs, path::PathBuf};
-use walkdir::WalkDir;
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use crate::utils::{create_spinner, get_directory_from_user};
-use crate::modules::base::FileOrganizer;
+use crate::utils::prompt::{Confirm, Input, MultiSelect, Select};
+use crate::utils::{
+    create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    expand::expand_path,
+    geocode::Geocoder,
+    get_directories_from_user,
+    journal::Journal,
+    junk::{is_hidden, JunkList},
+    metrics::Metrics,
+    names::Normalization,
+    sanitize::{long_path, safe_os_name, safe_relative_path},
+    space::{self, preflight, print_estimates, SpaceEstimate},
+    transfer::{is_in_use, transfer_file, TransferMode, Verify},
+    progress::{Outcome, ProgressSink},
+    walk::{is_access_denied, Inaccessible, Walker},
+};
+use conflict::Resolution;
+use age::AgeBucket;
+use alphabetical::AlphaOptions;
+use custom::CustomRule;
+use plan::Plan;
+use size::SizeBucket;
+use summary::{RootLayout, TransferSummary};
+use types::TypeNaming;
+use crate::config::{validate_ruleset, CategorizeConfig, Config, Ruleset, RulesetFile};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+pub use conflict::ConflictPolicy;
+pub use date::DateSource;
+pub use plan::{PlannedTransfer, RuleMatch};
+pub use report::ReportFormat;
 
 pub struct FileCategorizer {
     recursive: bool,
     input_dir: Option<PathBuf>,
+    /// Further source roots swept into the same organized tree
+    extra_input_dirs: Vec<PathBuf>,
     rules: Vec<CategoryRule>,
+    mode: Option<TransferMode>,
+    /// Root the categorized tree is created under; defaults to the input
+    output_dir: Option<PathBuf>,
+    /// Destination replacing a rule's `by_*` folder, keyed by rule label
+    rule_dests: BTreeMap<&'static str, PathBuf>,
+    date_source: Option<DateSource>,
+    /// Folder template for the date rule, e.g. `%Y/%m` or `%Y/Q%q`
+    date_format: Option<String>,
+    size_buckets: Option<Vec<SizeBucket>>,
+    age_buckets: Option<Vec<AgeBucket>>,
+    alphabetical: Option<AlphaOptions>,
+    /// Group type folders by kind (`by_type/images/jpg`)
+    type_grouping: Option<bool>,
+    /// Only merge alias folders from earlier runs, then exit
+    merge_aliases: bool,
+    unmatched: Option<UnmatchedPolicy>,
+    hidden: Option<HiddenPolicy>,
+    /// Known junk file names, from the built-in list and the config
+    junk: JunkList,
+    /// Unicode form of the file and folder names created, from the config
+    names: Normalization,
+    /// Move report written after the run; prompted for when unset
+    report: Option<ReportFormat>,
+    report_path: Option<PathBuf>,
+    composition: Option<Composition>,
+    /// Print per-file details such as which date source was used
+    verbose: bool,
+    /// Saved rule set to apply without prompting for rules
+    ruleset: Option<String>,
+    on_conflict: Option<ConflictPolicy>,
+    /// Show the planned layout and ask before transferring anything
+    dry_run: bool,
+    /// Also write the undo journal into the input directory
+    journal_in_input: bool,
+    /// Files modified more recently than this are deferred
+    grace_period: Duration,
+    /// Categorize inside git, Mercurial, and Subversion working copies
+    include_repos: bool,
+    /// Walk the inputs in name order
+    sorted: bool,
+    /// Abort on the first file that cannot be transferred
+    fail_fast: bool,
+    /// How junk files are deleted
+    delete_mode: DeleteMode,
+    /// How moves to another filesystem check the copy
+    verify: Verify,
+    /// Copy even when a destination filesystem looks too small
+    force: bool,
+    metrics: Metrics,
 }
 
 #[derive(Debug, Clone)]
 enum CategoryRule {
-    FileType,
+    /// Folders per canonical extension, optionally grouped by kind
+    FileType(TypeNaming),
     DateBased,
-    Custom(HashMap<String, Vec<String>>),
+    /// Broad groups detected from magic bytes, ignoring the extension
+    ContentType,
+    /// User rules in declaration order; the first matching rule wins
+    Custom(Vec<CustomRule>),
+    /// Built-in friendly folders (Documents, Images, ...) keyed by extension
+    Preset(HashMap<String, String>),
+    SizeBased(Vec<SizeBucket>),
+    /// Staleness buckets measured from the file's resolved date
+    Age(Vec<AgeBucket>),
+    /// `<artist>/<album>` from embedded tags of audio files
+    MusicTags,
+    /// `<Make Model>` from EXIF, with aliases from the config
+    Camera(BTreeMap<String, String>),
+    /// `<Country>/<Region>` from EXIF GPS, geocoded offline
+    Location(Arc<Geocoder>),
+    /// `4K`, `1080p`, ... from video container metadata
+    Video,
+    /// `A/`, `0-9/`, `#/` by the first character of the name
+    Alphabetical(AlphaOptions),
+}
+
+impl CategoryRule {
+    fn label(&self) -> &'static str {
+        match self {
+            CategoryRule::FileType(_) => "file type",
+            CategoryRule::DateBased => "date",
+            CategoryRule::ContentType => "content type",
+            CategoryRule::Custom(_) => "custom",
+            CategoryRule::Preset(_) => "preset",
+            CategoryRule::SizeBased(_) => "size",
+            CategoryRule::Age(_) => "age",
+            CategoryRule::MusicTags => "music tags",
+            CategoryRule::Camera(_) => "camera",
+            CategoryRule::Location(_) => "location",
+            CategoryRule::Video => "video",
+            CategoryRule::Alphabetical(_) => "alphabetical",
+        }
+    }
+
+    /// Directory the rule's categories are created under; nested rules
+    /// after the first contribute only their category
+    fn root(&self) -> &'static str {
+        match self {
+            CategoryRule::FileType(_) => "by_type",
+            CategoryRule::DateBased => "by_date",
+            CategoryRule::ContentType => "by_content",
+            CategoryRule::Custom(_) => "custom",
+            CategoryRule::Preset(_) => "",
+            CategoryRule::SizeBased(_) => "by_size",
+            CategoryRule::Age(_) => "by_age",
+            CategoryRule::MusicTags => "by_music",
+            CategoryRule::Camera(_) => "by_camera",
+            CategoryRule::Location(_) => "by_location",
+            CategoryRule::Video => "by_video",
+            CategoryRule::Alphabetical(_) => "by_letter",
+        }
+    }
+}
+
+/// Roots of every rule that creates one, see `CategoryRule::root`
+const OUTPUT_ROOTS: &[&str] = &[
+    "by_type",
+    "by_date",
+    "by_content",
+    "custom",
+    "by_size",
+    "by_age",
+    "by_music",
+    "by_camera",
+    "by_location",
+    "by_video",
+    "by_letter",
+    UNCATEGORIZED,
+];
+
+/// Folder for files no rule matched
+const UNCATEGORIZED: &str = "Uncategorized";
+
+/// What happens to files no rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmatchedPolicy {
+    Uncategorized,
+    /// `Uncategorized/<content group>` from magic bytes
+    UncategorizedByContent,
+    LeaveInPlace,
+    /// Ask once, after planning, when there are unmatched files
+    Prompt,
+}
+
+/// What happens to dotfiles, files in hidden directories, and known junk
+/// such as `.DS_Store` or `Thumbs.db`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HiddenPolicy {
+    Skip,
+    /// Treat them like any other file
+    Categorize,
+    /// Delete known junk and skip other hidden files
+    DeleteJunk,
+}
+
+/// How several selected rules combine into one destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Composition {
+    /// `by_date/2024/03-March/jpg/photo.jpg`, in selection order
+    Nested,
+    /// The first rule that matches claims the file
+    Priority,
+}
+
+/// A categorization rule of [`CategorizeOptions`]
+#[derive(Debug, Clone)]
+pub enum Rule {
+    /// Friendly folders (Documents, Images, ...) by extension
+    Preset,
+    /// `by_type/jpg`, or `by_type/images/jpg` when grouped by kind
+    FileType { grouped: bool },
+    /// `by_date/` folders from the options' date format
+    Date,
+    /// Broad groups detected from magic bytes
+    ContentType,
+    /// `by_size/` buckets, e.g. `tiny:1MB,small:10MB,big`
+    Size(String),
+    /// `by_age/` buckets, e.g. `recent:30d,old:1y,ancient`
+    Age(String),
+    MusicTags,
+    Camera,
+    Location,
+    Video,
+    /// `by_letter/` folders, with options such as `two-letter,no-symbols`
+    Alphabetical(String),
+    /// Categories by extension or name pattern; the first match wins
+    Custom(Ruleset),
+}
+
+impl Rule {
+    fn compile(&self, config: &CategorizeConfig) -> Result<CategoryRule> {
+        Ok(match self {
+            Rule::Preset => CategoryRule::Preset(preset::table(&config.preset_overrides)),
+            Rule::FileType { grouped } => CategoryRule::FileType(TypeNaming::new(&config.extension_aliases, *grouped)),
+            Rule::Date => CategoryRule::DateBased,
+            Rule::ContentType => CategoryRule::ContentType,
+            Rule::Size(spec) => CategoryRule::SizeBased(size::parse_buckets(spec)?),
+            Rule::Age(spec) => CategoryRule::Age(age::parse_buckets(spec)?),
+            Rule::MusicTags => CategoryRule::MusicTags,
+            Rule::Camera => CategoryRule::Camera(config.camera_aliases.clone()),
+            Rule::Location => CategoryRule::Location(Arc::new(Geocoder::new())),
+            Rule::Video => CategoryRule::Video,
+            Rule::Alphabetical(spec) => CategoryRule::Alphabetical(alphabetical::parse_options(spec)?),
+            Rule::Custom(ruleset) => CategoryRule::Custom(custom::compile(ruleset)?),
+        })
+    }
+}
+
+/// Everything a categorization run needs, with no prompting
+pub struct CategorizeOptions {
+    /// Source roots swept into one organized tree
+    pub input_dirs: Vec<PathBuf>,
+    /// Root the categorized tree is built under; `None` builds it inside
+    /// the first input directory
+    pub output_dir: Option<PathBuf>,
+    pub rules: Vec<Rule>,
+    /// How several rules combine into one destination
+    pub composition: Composition,
+    pub mode: TransferMode,
+    pub recursive: bool,
+    /// Categorize inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Timestamp the date and age rules prefer; `None` is the creation
+    /// date for the date rule and the modification date for the age rule
+    pub date_source: Option<DateSource>,
+    /// Folder template of the date rule, e.g. `%Y/%m` or `%Y/Q%q`
+    pub date_format: String,
+    pub on_conflict: ConflictPolicy,
+    /// `UnmatchedPolicy::Prompt` leaves unmatched files in place
+    pub unmatched: UnmatchedPolicy,
+    pub hidden: HiddenPolicy,
+    /// Files modified more recently than this are left for a later run
+    pub grace_period: Duration,
+    /// Preset overrides and extension and camera aliases, as in the
+    /// config's `[categorize]` table
+    pub config: CategorizeConfig,
+    /// What counts as junk
+    pub junk: JunkList,
+    /// Unicode form of the file and folder names created
+    pub names: Normalization,
+    /// How junk files are deleted under `HiddenPolicy::DeleteJunk`
+    pub delete_mode: DeleteMode,
+    /// How moves to another filesystem check the copy
+    pub verify: Verify,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl CategorizeOptions {
+    /// A recursive move of `input_dirs` into the preset categories inside
+    /// the first of them, renaming on conflicts and leaving unmatched and
+    /// hidden files where they are
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            output_dir: None,
+            rules: vec![Rule::Preset],
+            composition: Composition::Nested,
+            mode: TransferMode::Move,
+            recursive: true,
+            include_repos: false,
+            date_source: None,
+            date_format: date::DEFAULT_FORMAT.to_string(),
+            on_conflict: ConflictPolicy::Rename,
+            unmatched: UnmatchedPolicy::LeaveInPlace,
+            hidden: HiddenPolicy::Skip,
+            grace_period: Duration::from_secs(grace::DEFAULT_GRACE_SECS),
+            config: CategorizeConfig::default(),
+            junk: JunkList::default(),
+            names: Normalization::default(),
+            delete_mode: DeleteMode::Trash,
+            verify: Verify::default(),
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A categorization run decided in full, before any file is transferred
+#[derive(Serialize)]
+pub struct CategorizeJob {
+    /// Root the categorized tree is built under
+    base_dir: PathBuf,
+    /// Entries directly inside each input before the run
+    entries_before: Vec<usize>,
+    unmatched: usize,
+    /// Whether unmatched files stay where they are
+    unmatched_left_in_place: bool,
+    hidden_skipped: usize,
+    /// Known junk files among `hidden_skipped`
+    junk_skipped: usize,
+    /// Space the copies need on each destination; empty when moving
+    space: Vec<SpaceEstimate>,
+    plan: Plan,
+}
+
+impl CategorizeJob {
+    fn new(base_dir: PathBuf, entries_before: Vec<usize>, plan: Plan) -> Self {
+        Self {
+            base_dir,
+            entries_before,
+            unmatched: 0,
+            unmatched_left_in_place: true,
+            hidden_skipped: 0,
+            junk_skipped: 0,
+            space: Vec::new(),
+            plan,
+        }
+    }
+
+    /// The transfers planned, in walk order
+    pub fn transfers(&self) -> &[PlannedTransfer] {
+        &self.plan.entries
+    }
+
+    /// Known junk files to delete
+    pub fn deletions(&self) -> &[PathBuf] {
+        &self.plan.deletions
+    }
+
+    /// Files left where they are: unmatched ones left in place, deferred,
+    /// and skipped hidden files
+    pub fn skipped(&self) -> usize {
+        let unmatched = if self.unmatched_left_in_place { self.unmatched } else { 0 };
+        unmatched + self.plan.deferred.len() + self.hidden_skipped
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct CategorizeSummary {
+    pub files: usize,
+    pub bytes: u64,
+    /// Known junk files deleted, or under `DeleteMode::DryRun` the ones
+    /// that would be
+    pub deleted: usize,
+    /// Files left where they are, see [`CategorizeJob::skipped`], and files
+    /// another process held open
+    pub skipped: usize,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be transferred or deleted
+    pub errors: Vec<FileError>,
+    /// Files and directories that could not be read
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&CategorizeSummary> for Summary {
+    fn from(summary: &CategorizeSummary) -> Self {
+        Summary {
+            files: summary.files,
+            bytes: summary.bytes,
+            skipped: summary.skipped,
+            junk: summary.deleted,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Walks the input directories and plans one transfer for every file the
+/// rules match
+pub fn plan(options: &CategorizeOptions, progress: impl ProgressSink) -> Result<CategorizeJob> {
+    let categorizer = FileCategorizer::from_options(options)?;
+    let (base_dir, entries_before, plan) = categorizer.scan(&progress)?;
+    categorizer.route(base_dir, entries_before, plan, options.hidden, |_| Ok(options.unmatched))
+}
+
+/// Transfers every file in `job` and deletes its junk, recording both in an
+/// undo manifest
+pub fn apply(
+    options: &CategorizeOptions,
+    job: CategorizeJob,
+    progress: impl ProgressSink,
+) -> Result<CategorizeSummary> {
+    let categorizer = FileCategorizer::from_options(options)?;
+    space::check(&job.space)?;
+    if let Some(dir) = &options.output_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let mut journal = Journal::new("categorize", options.mode);
+    let (summary, result) = categorizer.execute_plan(&job.plan, &mut journal, progress, |_| {});
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // transferred can be undone
+    let mut manifest = None;
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            manifest = Some(path);
+        }
+    }
+    result?;
+
+    Ok(CategorizeSummary {
+        files: summary.files,
+        bytes: summary.bytes,
+        deleted: summary.deleted,
+        skipped: job.skipped() + summary.in_use.len(),
+        manifest,
+        repositories: job.plan.repositories,
+        errors: summary.errors,
+        inaccessible: job.plan.inaccessible,
+    })
+}
+
+/// Sorts the files under the input directories into category folders
+///
+/// ```
+/// use file_organizer_rust::modules::file_categorizer::{self, CategorizeOptions, Rule};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("report.pdf"), "pdf")?;
+/// std::fs::write(dir.path().join("photo.JPG"), "jpeg")?;
+/// std::fs::write(dir.path().join("notes"), "no extension")?;
+///
+/// let options = CategorizeOptions {
+///     rules: vec![Rule::FileType { grouped: false }],
+///     grace_period: std::time::Duration::ZERO,
+///     ..CategorizeOptions::new(vec![dir.path().to_path_buf()])
+/// };
+/// let summary = file_categorizer::execute(&options, ())?;
+/// assert_eq!(summary.files, 2);
+/// assert!(dir.path().join("by_type/pdf/report.pdf").exists());
+/// assert!(dir.path().join("by_type/jpg/photo.JPG").exists());
+/// assert!(dir.path().join("notes").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &CategorizeOptions, progress: impl ProgressSink) -> Result<CategorizeSummary> {
+    let job = plan(options, &progress)?;
+    let summary = apply(options, job, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
 }
 
 #[async_trait]
 impl FileOrganizer for FileCategorizer {
+    type Plan = CategorizeJob;
+    const NAME: &'static str = "categorize";
+
     fn new(recursive: bool) -> Self {
         Self {
             recursive,
             input_dir: None,
+            extra_input_dirs: Vec::new(),
             rules: Vec::new(),
+            mode: None,
+            output_dir: None,
+            rule_dests: BTreeMap::new(),
+            date_source: None,
+            date_format: None,
+            size_buckets: None,
+            age_buckets: None,
+            alphabetical: None,
+            type_grouping: None,
+            merge_aliases: false,
+            unmatched: None,
+            hidden: None,
+            junk: JunkList::default(),
+            names: Normalization::default(),
+            report: None,
+            report_path: None,
+            composition: None,
+            verbose: false,
+            ruleset: None,
+            on_conflict: None,
+            dry_run: false,
+            journal_in_input: false,
+            grace_period: Duration::from_secs(grace::DEFAULT_GRACE_SECS),
+            include_repos: false,
+            sorted: false,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            verify: Verify::default(),
+            force: false,
+            metrics: Metrics::default(),
         }
     }
 
-    async fn run(&self) -> Result<()> {
-        let input_dir = get_directory_from_user("Enter directory to categorize")?;
+    fn configure(&mut self) -> Result<()> {
+        let mut input_dirs = match &self.input_dir {
+            Some(dir) => std::iter::once(dir.clone()).chain(self.extra_input_dirs.iter().cloned()).collect(),
+            None => get_directories_from_user("Enter directories to categorize (comma-separated)")?,
+        };
+        check_input_dirs(&input_dirs)?;
+        let input_dir = input_dirs.remove(0);
+        self.input_dir = Some(input_dir.clone());
+        self.extra_input_dirs = input_dirs;
+        if self.merge_aliases {
+            return Ok(());
+        }
         
-        let rule_options = vec!["File Type", "Date Based", "Custom Rules"];
+        let rules = match &self.ruleset {
+            Some(name) => vec![CategoryRule::Custom(load_ruleset(name)?)],
+            None => self.prompt_rules()?,
+        };
+
+        let mode = match self.mode {
+            Some(mode) => mode,
+            None => {
+                let mode_options = vec!["Move files", "Copy files"];
+                let mode_selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select operation mode")
+                    .items(&mode_options)
+                    .default(0)
+                    .interact()?;
+                match mode_selection {
+                    0 => TransferMode::Move,
+                    1 => TransferMode::Copy,
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        if self.output_dir.is_none() && (mode == TransferMode::Copy || !self.extra_input_dirs.is_empty()) {
+            let dir: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Output directory (leave empty to categorize inside {})",
+                    input_dir.display()
+                ))
+                .allow_empty(true)
+                .interact_text()?;
+            self.output_dir = (!dir.trim().is_empty()).then(|| expand_path(dir.trim())).transpose()?;
+        }
+
+        // A single rule's folders are placed with the output directory
+        if self.rule_dests.is_empty() && rules.len() > 1 {
+            self.rule_dests = prompt_rule_dests(&rules)?;
+        }
+        for dest in self.rule_dests.values() {
+            if dest.exists() && !dest.is_dir() {
+                anyhow::bail!("Destination {} is not a directory", dest.display());
+            }
+        }
+
+        if self.on_conflict.is_none() {
+            self.on_conflict = Some(prompt_conflict_policy()?);
+        }
+
+        let dated = rules.iter().any(|r| matches!(r, CategoryRule::DateBased));
+        if self.date_source.is_none() && dated {
+            self.date_source = Some(prompt_date_source()?);
+        }
+
+        if self.composition.is_none() && rules.len() > 1 {
+            self.composition = Some(prompt_composition()?);
+        }
+
+        if self.date_format.is_none() && dated {
+            self.date_format = Some(prompt_date_format()?);
+        }
+
+        if rules.iter().any(|r| matches!(r, CategoryRule::Video)) && !crate::utils::video::ffprobe_available() {
+            println!("ffprobe not found on PATH; only MP4, MOV, and Matroska videos get a resolution");
+        }
+
+        self.rules = rules;
+        self.mode = Some(mode);
+        self.junk = JunkList::load()?;
+        self.names = Normalization::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<CategorizeJob> {
+        if self.merge_aliases {
+            let base_dir = self.base_dir()?.to_path_buf();
+            let entries_before = self.input_dirs().map(summary::top_level_entries).collect();
+            return Ok(CategorizeJob::new(base_dir, entries_before, Plan::default()));
+        }
+
+        let spinner = create_spinner("Planning categorization...");
+        let (base_dir, entries_before, plan) = self.scan(&self.metrics)?;
+        spinner.finish_and_clear();
+
+        let hidden = plan.hidden.len();
+        let hidden_policy = match self.hidden {
+            Some(policy) => policy,
+            None if hidden > 0 => prompt_hidden_policy(hidden)?,
+            None => HiddenPolicy::Skip,
+        };
+        let job = self.route(base_dir, entries_before, plan, hidden_policy, |plan| {
+            println!(
+                "{} of {} files matched no rule; many unmatched files usually means the rules need work",
+                plan.unmatched.len(),
+                plan.unmatched.len() + plan.entries.len()
+            );
+            match self.unmatched {
+                Some(UnmatchedPolicy::Prompt) | None => prompt_unmatched_policy(),
+                Some(policy) => Ok(policy),
+            }
+        })?;
+        for rule in self.rules.iter().filter(|r| {
+            matches!(r, CategoryRule::Age(_) | CategoryRule::Camera(_) | CategoryRule::Location(_) | CategoryRule::Video)
+        }) {
+            job.plan.print_rule_report(rule.label());
+        }
+        Ok(job)
+    }
+
+    fn review(&self, job: &CategorizeJob) -> Result<bool> {
+        if !self.dry_run || self.merge_aliases {
+            return Ok(true);
+        }
+        let roots: Vec<&Path> = std::iter::once(job.base_dir.as_path())
+            .chain(self.rule_dests.values().map(PathBuf::as_path))
+            .collect();
+        job.plan.print_tree(&roots, self.verbose);
+        print_estimates(&job.space);
+        let proceed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Proceed with categorization?")
+            .default(false)
+            .interact()?;
+        if !proceed {
+            println!("Dry run finished, no files were {}", self.mode.unwrap_or(TransferMode::Move).verb());
+        }
+        Ok(proceed)
+    }
+
+    fn execute(&self, job: CategorizeJob) -> Result<Summary> {
+        let input_dir = self.input_dir.as_deref().ok_or_else(|| {
+            anyhow::anyhow!("No input directory set for categorization")
+        })?;
+        if self.merge_aliases {
+            let files = self.merge_type_aliases(input_dir)?;
+            return Ok(Summary { files, ..Summary::default() });
+        }
+        let CategorizeJob { base_dir, entries_before, plan, .. } = &job;
+        preflight(&job.space, self.force)?;
+        let mode = self.mode.unwrap_or(TransferMode::Move);
+        for dir in self.output_dir.iter().chain(self.rule_dests.values()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut journal = Journal::new("categorize", mode);
+        // The journal is written even when the run fails part way, so the
+        // transfers that did happen can still be undone
+        let pb = ProgressBar::new(0);
+        pb.set_style(ProgressStyle::default_spinner()
+            .template("{spinner:.green} [{elapsed_precise}] ({pos}/{len}) {msg}")
+            .unwrap()
+            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        let (mut summary, result) = self.execute_plan(plan, &mut journal, (&pb, &self.metrics), |summary| {
+            pb.set_message(summary.progress_message())
+        });
+        pb.finish_and_clear();
+        self.write_journal(&journal, &self.input_dirs().collect::<Vec<_>>())?;
+        summary.unmatched = job.unmatched;
+        summary.unmatched_left_in_place = job.unmatched_left_in_place;
+        summary.deferred = plan.deferred.len();
+        summary.hidden_skipped = job.hidden_skipped;
+        summary.junk_dirs_skipped = plan.junk_dirs.len();
+        summary.repositories_skipped = plan.repositories.len();
+        summary.inaccessible = plan.inaccessible.clone();
+        summary.phases = self.metrics.finish();
+        summary.layout = self
+            .input_dirs()
+            .zip(entries_before.iter().copied())
+            .map(|(root, before)| RootLayout::measure(root, before, &self.output_roots(root, base_dir)))
+            .collect();
+        // Like the journal, the report lists what a failed run did move
+        self.write_report(&journal, &summary, self.report_dir(base_dir))?;
+        result?;
+
+        println!(
+            "File categorization completed: {} files {} ({})",
+            summary.files,
+            mode.verb(),
+            format_size(summary.bytes, BINARY)
+        );
+        summary.print_table();
+        print_inaccessible(&summary.inaccessible, "they were left where they are");
+        print_errors(&summary.errors);
+        if !plan.repositories.is_empty() {
+            println!("Use --include-repos to categorize inside the skipped repositories");
+            if self.verbose {
+                for repo in &plan.repositories {
+                    println!("  {}", repo.display());
+                }
+            }
+        }
+        let deferred: Vec<&PathBuf> = plan.deferred.iter().chain(&summary.in_use).collect();
+        if !deferred.is_empty() {
+            println!(
+                "{} files deferred (unfinished, modified in the last {}s, or in use); run again later:",
+                deferred.len(),
+                self.grace_period.as_secs()
+            );
+            for path in &deferred {
+                println!("  {}", path.display());
+            }
+        }
+
+        Ok(Summary {
+            files: summary.files,
+            bytes: summary.bytes,
+            skipped: job.unmatched + deferred.len() + job.hidden_skipped - job.junk_skipped,
+            junk: job.junk_skipped + plan.junk_dirs.len(),
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: summary.phases,
+        })
+    }
+}
+
+impl FileCategorizer {
+    /// Presets the operation mode so the prompt is skipped
+    pub fn with_mode(mut self, mode: TransferMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Presets which timestamp the date rule prefers
+    pub fn with_date_source(mut self, source: DateSource) -> Self {
+        self.date_source = Some(source);
+        self
+    }
+
+    /// Presets the date rule's folder template, checking its specifiers
+    pub fn with_date_format(mut self, format: String) -> Result<Self> {
+        date::validate_format(&format)?;
+        self.date_format = Some(format);
+        Ok(self)
+    }
+
+    /// Presets the size rule's buckets, e.g. `tiny:1MB,small:10MB,big`
+    pub fn with_size_buckets(mut self, spec: &str) -> Result<Self> {
+        self.size_buckets = Some(size::parse_buckets(spec)?);
+        Ok(self)
+    }
+
+    /// Presets the alphabetical rule's options, e.g. `two-letter,no-symbols`
+    pub fn with_alphabetical(mut self, spec: &str) -> Result<Self> {
+        self.alphabetical = Some(alphabetical::parse_options(spec)?);
+        Ok(self)
+    }
+
+    /// Sends the file type rule's folders to `dir` instead of `by_type/`
+    pub fn with_type_dest(mut self, dir: PathBuf) -> Self {
+        self.rule_dests.insert("file type", dir);
+        self
+    }
+
+    /// Sends the date rule's folders to `dir` instead of `by_date/`
+    pub fn with_date_dest(mut self, dir: PathBuf) -> Self {
+        self.rule_dests.insert("date", dir);
+        self
+    }
+
+    /// Presets how multiple rules combine
+    pub fn with_composition(mut self, composition: Composition) -> Self {
+        self.composition = Some(composition);
+        self
+    }
+
+    /// Presets whether type folders are grouped by kind
+    pub fn with_type_grouping(mut self, grouped: bool) -> Self {
+        self.type_grouping = Some(grouped);
+        self
+    }
+
+    /// Runs a one-off merge of `by_type/` alias folders instead of
+    /// categorizing
+    pub fn with_merge_aliases(mut self, merge: bool) -> Self {
+        self.merge_aliases = merge;
+        self
+    }
+
+    /// Writes a CSV or JSON move report after the run, by default to
+    /// `<input>/categorize-report-<timestamp>.<ext>`
+    pub fn with_report(mut self, format: ReportFormat, path: Option<PathBuf>) -> Self {
+        self.report = Some(format);
+        self.report_path = path;
+        self
+    }
+
+    /// Sets every source root at once; the first one doubles as the input
+    /// directory of single-root runs
+    pub fn with_input_dirs(mut self, mut dirs: Vec<PathBuf>) -> Self {
+        if !dirs.is_empty() {
+            self.input_dir = Some(dirs.remove(0));
+            self.extra_input_dirs = dirs;
+        }
+        self
+    }
+
+    /// Defers files modified less than `secs` seconds ago; 0 only defers
+    /// unfinished downloads
+    pub fn with_grace_period(mut self, secs: u64) -> Self {
+        self.grace_period = Duration::from_secs(secs);
+        self
+    }
+
+    /// Walks into version-controlled working copies instead of skipping
+    /// them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Presets what happens to hidden and junk files instead of asking
+    pub fn with_hidden_policy(mut self, policy: HiddenPolicy) -> Self {
+        self.hidden = Some(policy);
+        self
+    }
+
+    /// Presets what happens to files no rule matches
+    pub fn with_unmatched(mut self, policy: UnmatchedPolicy) -> Self {
+        self.unmatched = Some(policy);
+        self
+    }
+
+    /// Presets the age rule's buckets, e.g. `recent:30d,old:1y,ancient`
+    pub fn with_age_buckets(mut self, spec: &str) -> Result<Self> {
+        self.age_buckets = Some(age::parse_buckets(spec)?);
+        Ok(self)
+    }
+
+    /// Categorizes with a saved rule set instead of prompting for rules
+    pub fn with_ruleset(mut self, name: String) -> Self {
+        self.ruleset = Some(name);
+        self
+    }
+
+    /// Presets how existing targets are handled
+    pub fn with_on_conflict(mut self, policy: ConflictPolicy) -> Self {
+        self.on_conflict = Some(policy);
+        self
+    }
+
+    /// Previews the categorized layout and asks before transferring
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets how moves to another filesystem check the copy before deleting
+    /// the source
+    pub fn with_verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Walks the inputs in name order, so repeated runs over the same tree
+    /// plan the same transfers and collision suffixes in the same order
+    pub fn with_sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Copies even when a destination filesystem looks too small, instead
+    /// of refusing
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Aborts on the first file that cannot be transferred instead of
+    /// recording it and carrying on
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Trashes, permanently deletes, or only lists the junk files deleted
+    /// under the delete-junk hidden-file policy
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Writes a copy of the undo journal into the input directory
+    pub fn with_journal_in_input(mut self, enabled: bool) -> Self {
+        self.journal_in_input = enabled;
+        self
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Builds the categorized tree under `dir` instead of the input directory
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    fn prompt_rules(&self) -> Result<Vec<CategoryRule>> {
+        let rule_options = vec![
+            "Preset Categories (Documents, Images, Videos, ...)",
+            "File Type",
+            "Date Based",
+            "Content Type",
+            "Size Based",
+            "Age Based",
+            "Music Tags (Artist/Album)",
+            "Camera Model (photos)",
+            "Location (photos, from GPS)",
+            "Video Resolution (4K, 1080p, ...)",
+            "Alphabetical (A/, B/, ..., 0-9/, #/)",
+            "Custom Rules",
+        ];
         let selected_rules = MultiSelect::with_theme(&ColorfulTheme::default())
             .with_prompt("Select categorization rules")
             .items(&rule_options)
-            .defaults(&[true, false, false])
+            .defaults(&[true, false, false, false, false, false, false, false, false, false, false, false])
             .interact()?;
 
         if selected_rules.is_empty() {
-            anyhow::bail!("No categorization rules selected");
+            println!("No rules selected, using the preset categories");
+            return Ok(vec![preset_rule()?]);
         }
 
         let mut rules = Vec::new();
         for &idx in selected_rules.iter() {
             match idx {
-                0 => rules.push(CategoryRule::FileType),
-                1 => rules.push(CategoryRule::DateBased),
-                2 => {
-                    let custom_rules = self.configure_custom_rules()?;
-                    rules.push(CategoryRule::Custom(custom_rules));
+                0 => rules.push(preset_rule()?),
+                1 => rules.push(CategoryRule::FileType(self.type_naming()?)),
+                2 => rules.push(CategoryRule::DateBased),
+                3 => rules.push(CategoryRule::ContentType),
+                4 => rules.push(CategoryRule::SizeBased(self.size_buckets()?)),
+                5 => rules.push(CategoryRule::Age(self.age_buckets()?)),
+                6 => rules.push(CategoryRule::MusicTags),
+                7 => rules.push(CategoryRule::Camera(Config::load()?.categorize.camera_aliases)),
+                8 => rules.push(CategoryRule::Location(Arc::new(Geocoder::new()))),
+                9 => rules.push(CategoryRule::Video),
+                10 => rules.push(CategoryRule::Alphabetical(self.alpha_options()?)),
+                11 => {
+                    let custom_rules = self.choose_custom_rules()?;
+                    rules.push(CategoryRule::Custom(custom::compile(&custom_rules)?));
                 }
                 _ => unreachable!(),
             }
         }
+        Ok(rules)
+    }
 
-        let spinner = create_spinner("Categorizing files...");
-        self.categorize_files(&input_dir, &rules)?;
-        spinner.finish_with_message("File categorization completed!");
+    /// Size buckets from `--size-buckets`, or prompted with the defaults
+    /// prefilled
+    fn size_buckets(&self) -> Result<Vec<SizeBucket>> {
+        if let Some(buckets) = &self.size_buckets {
+            return Ok(buckets.clone());
+        }
+        let spec: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Size buckets (label:threshold,..., last label is open-ended)")
+            .default(size::DEFAULT_BUCKETS.to_string())
+            .validate_with(|input: &String| size::parse_buckets(input).map(|_| ()).map_err(|e| e.to_string()))
+            .interact_text()?;
+        size::parse_buckets(&spec)
+    }
 
-        Ok(())
+    /// Alphabetical rule options from `--alphabetical`, or prompted
+    fn alpha_options(&self) -> Result<AlphaOptions> {
+        if let Some(options) = self.alphabetical {
+            return Ok(options);
+        }
+        let defaults = AlphaOptions::default();
+        let confirm = |prompt: &str, default: bool| {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(default)
+                .interact()
+        };
+        Ok(AlphaOptions {
+            fold_case: confirm("Fold case (apple and Apple both go to A/)?", defaults.fold_case)?,
+            digit_bucket: confirm("Collect names starting with a digit in 0-9/?", defaults.digit_bucket)?,
+            symbol_bucket: confirm("Collect names starting with a symbol in #/?", defaults.symbol_bucket)?,
+            two_letter: confirm("Add two-letter subfolders (A/AB/) for very large sets?", defaults.two_letter)?,
+        })
     }
 
-    fn is_recursive(&self) -> bool {
-        self.recursive
+    /// Type folder naming from the config aliases and `--type-grouping`,
+    /// or the grouping prompt
+    fn type_naming(&self) -> Result<TypeNaming> {
+        let grouped = match self.type_grouping {
+            Some(grouped) => grouped,
+            None => {
+                let grouping_options = vec![
+                    "By extension (by_type/jpg)",
+                    "By kind, then extension (by_type/images/jpg)",
+                ];
+                let grouping_selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Select file type grouping")
+                    .items(&grouping_options)
+                    .default(0)
+                    .interact()?;
+                grouping_selection == 1
+            }
+        };
+        Ok(TypeNaming::new(&Config::load()?.categorize.extension_aliases, grouped))
     }
 
-    fn get_input_dir(&self) -> Option<&PathBuf> {
-        self.input_dir.as_ref()
+    /// Merges alias folders such as `by_type/jpeg` into `by_type/jpg`,
+    /// journaling the moves so the merge can be undone
+    fn merge_type_aliases(&self, input_dir: &Path) -> Result<usize> {
+        let base_dir = self.output_dir.as_deref().unwrap_or(input_dir);
+        let type_root = base_dir.join("by_type");
+        if !type_root.is_dir() {
+            anyhow::bail!("No by_type directory in {}", base_dir.display());
+        }
+
+        // The merge follows the layout it finds, grouped or not
+        let naming = TypeNaming::new(&Config::load()?.categorize.extension_aliases, false);
+        let mut journal = Journal::new("categorize", TransferMode::Move);
+        let policy = self.on_conflict.unwrap_or(ConflictPolicy::Rename);
+        let merged = naming.merge_aliases(&type_root, policy, &mut journal);
+        self.write_journal(&journal, &[input_dir])?;
+        let merged = merged?;
+        println!("Merged {} files into canonical type folders", merged);
+        Ok(merged)
     }
 
-    fn set_input_dir(&mut self, dir: PathBuf) {
-        self.input_dir = Some(dir);
+    /// Age buckets from `--age-buckets`, or prompted with the defaults
+    /// prefilled
+    fn age_buckets(&self) -> Result<Vec<AgeBucket>> {
+        if let Some(buckets) = &self.age_buckets {
+            return Ok(buckets.clone());
+        }
+        let spec: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Age buckets (label:age with d/w/m/y,..., last label is open-ended)")
+            .default(age::DEFAULT_BUCKETS.to_string())
+            .validate_with(|input: &String| age::parse_buckets(input).map(|_| ()).map_err(|e| e.to_string()))
+            .interact_text()?;
+        age::parse_buckets(&spec)
     }
 
-    fn process_file(&self, file: &PathBuf) -> Result<()> {
-        if let Some(input_dir) = &self.input_dir {
-            for rule in &self.rules {
-                match rule {
-                    CategoryRule::FileType => self.categorize_by_type(file, input_dir)?,
-                    CategoryRule::DateBased => self.categorize_by_date(file, input_dir)?,
-                    CategoryRule::Custom(rules) => self.categorize_by_custom_rules(file, input_dir, rules)?,
-                }
+    /// Offers saved rule sets for reuse, or takes new rules and offers to
+    /// save them
+    fn choose_custom_rules(&self) -> Result<Ruleset> {
+        let mut config = Config::load()?;
+
+        if !config.categorize.rulesets.is_empty() {
+            let mut options = vec!["Enter rules manually".to_string()];
+            options.extend(
+                config
+                    .categorize
+                    .rulesets
+                    .iter()
+                    .map(|(name, ruleset)| format!("Saved rule set: {} ({} categories)", name, ruleset.len())),
+            );
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select custom rules")
+                .items(&options)
+                .default(0)
+                .interact()?;
+            if selection > 0 {
+                let (_, ruleset) = config.categorize.rulesets.iter().nth(selection - 1).unwrap();
+                return Ok(ruleset.clone());
             }
         }
-        Ok(())
+
+        let rules = self.configure_custom_rules()?;
+        if rules.is_empty() {
+            return Ok(rules);
+        }
+
+        let save = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Save these rules as a named rule set?")
+            .default(false)
+            .interact()?;
+        if save {
+            let name: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Rule set name")
+                .interact_text()?;
+            validate_ruleset(&name, &rules)?;
+            config.categorize.rulesets.insert(name.clone(), rules.clone());
+            config.save()?;
+            println!("Saved rule set '{}' to {}", name, Config::path()?.display());
+        }
+
+        Ok(rules)
     }
 
-    fn create_directories(&self, base_dir: &PathBuf) -> Result<()> {
-        for rule in &self.rules {
-            match rule {
-                CategoryRule::FileType => {
-                    fs::create_dir_all(base_dir.join("by_type"))?;
-                }
-                CategoryRule::DateBased => {
-                    fs::create_dir_all(base_dir.join("by_date"))?;
-                }
-                CategoryRule::Custom(rules) => {
-                    for category in rules.keys() {
-                        fs::create_dir_all(base_dir.join("custom").join(category))?;
-                    }
-                }
-            }
+    /// Merges the rule sets from a shared TOML file into the config
+    pub fn import_rulesets(path: &Path) -> Result<()> {
+        let file = RulesetFile::load(path)?;
+        let mut config = Config::load()?;
+        for (name, ruleset) in file.rulesets {
+            println!("Imported rule set '{}'", name);
+            config.categorize.rulesets.insert(name, ruleset);
         }
+        config.save()
+    }
+
+    /// Writes every saved rule set to a standalone TOML file
+    pub fn export_rulesets(path: &Path) -> Result<()> {
+        let config = Config::load()?;
+        let file = RulesetFile { rulesets: config.categorize.rulesets };
+        file.save(path)?;
+        println!("Exported {} rule sets to {}", file.rulesets.len(), path.display());
         Ok(())
     }
-}
 
-impl FileCategorizer {
-    fn configure_custom_rules(&self) -> Result<HashMap<String, Vec<String>>> {
-        let mut rules = HashMap::new();
+    fn configure_custom_rules(&self) -> Result<Ruleset> {
+        let mut rules = Ruleset::new();
         println!("Configure custom rules (category:extension, e.g., 'Documents:pdf,doc,docx')");
+        println!("or match file names with a regex, e.g., 'Finance:/^invoice/i' (flag p matches the relative path)");
+        println!("Rules are checked in the order entered and the first match wins");
         println!("Enter an empty line to finish");
 
         loop {
-            let input: String = dialoguer::Input::new()
+            let input: String = Input::new()
                 .with_prompt("Enter rule")
                 .allow_empty(true)
                 .interact_text()?;
@@ -125,97 +1189,930 @@ impl FileCategorizer {
                 break;
             }
 
-            let parts: Vec<&str> = input.split(':').collect();
-            if parts.len() != 2 {
-                println!("Invalid format. Use 'Category:ext1,ext2,...'");
+            match custom::parse_line(&input) {
+                Ok((category, entries)) => rules.entry(category).or_default().extend(entries),
+                Err(e) => println!("Invalid rule: {:#}", e),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Evaluates the selected rules against the original path of `file` and
+    /// computes its single destination. Nested composition stacks every
+    /// matching rule's category below the first rule's root; priority
+    /// composition stops at the first matching rule.
+    fn plan_file(&self, file: &Path, base_dir: &Path) -> Result<Option<PlannedTransfer>> {
+        let size = fs::metadata(file)?.len();
+        let composition = self.composition.unwrap_or(Composition::Nested);
+
+        let mut target_dir = base_dir.to_path_buf();
+        let mut matches = Vec::new();
+        for rule in &self.rules {
+            let category = match rule {
+                CategoryRule::FileType(naming) => naming.category(file),
+                CategoryRule::DateBased => self.date_category(file)?,
+                CategoryRule::ContentType => self.content_category(file)?,
+                CategoryRule::Custom(rules) => self.custom_category(file, rules),
+                CategoryRule::Preset(table) => self.preset_category(file, table),
+                CategoryRule::SizeBased(buckets) => Some(PathBuf::from(size::bucket_folder(buckets, size))),
+                CategoryRule::Age(buckets) => self.age_category(file, buckets)?,
+                CategoryRule::MusicTags => music::artist_album(file),
+                CategoryRule::Camera(aliases) => camera::folder(file, aliases).map(PathBuf::from),
+                CategoryRule::Location(geocoder) => location::folder(file, geocoder),
+                CategoryRule::Video => self.video_category(file),
+                CategoryRule::Alphabetical(options) => alphabetical::folder(file, options),
+            };
+            let Some(category) = category else {
                 continue;
+            };
+
+            if matches.is_empty() {
+                target_dir = match self.rule_dests.get(rule.label()) {
+                    Some(dest) => dest.clone(),
+                    None => base_dir.join(rule.root()),
+                };
+            }
+            target_dir.push(safe_relative_path(Path::new(&self.names.apply(category.as_os_str()))));
+            matches.push(RuleMatch {
+                rule: rule.label(),
+                category: category.to_string_lossy().into_owned(),
+            });
+            if composition == Composition::Priority {
+                break;
             }
+        }
 
-            let category = parts[0].trim().to_string();
-            let extensions: Vec<String> = parts[1]
-                .split(',')
-                .map(|s| s.trim().to_lowercase())
-                .collect();
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(PlannedTransfer {
+            source: file.to_path_buf(),
+            destination: target_dir.join(self.file_name(file)),
+            matches,
+            size,
+        }))
+    }
+
+    /// The name `file` gets in its category folder: in the configured
+    /// Unicode form and safe for the platform, otherwise byte for byte
+    fn file_name(&self, file: &Path) -> OsString {
+        safe_os_name(&self.names.apply(file.file_name().unwrap())).into_owned()
+    }
 
-            rules.insert(category, extensions);
+    /// Root the categorized tree is built under: the output directory, or
+    /// the input directory
+    fn base_dir(&self) -> Result<&Path> {
+        match (&self.output_dir, &self.input_dir) {
+            (Some(dir), _) | (None, Some(dir)) => Ok(dir),
+            (None, None) => anyhow::bail!("No input directory set for categorization"),
         }
+    }
 
-        Ok(rules)
+    /// Transfers `file`, returning where it ended up and its size
+    fn transfer(&self, file: &Path, target: &Path) -> Result<Option<(PathBuf, u64)>> {
+        let mut target = target.to_path_buf();
+        let mut replace = false;
+        if target.exists() {
+            let policy = self.on_conflict.unwrap_or(ConflictPolicy::Rename);
+            let resolution = conflict::resolve(file, &target, policy)?;
+            println!(
+                "Conflict: {} already exists, {} {}",
+                target.display(),
+                file.display(),
+                resolution.describe()
+            );
+            match resolution {
+                Resolution::Transfer(path) => target = path,
+                Resolution::Replace => replace = true,
+                Resolution::Skip(_) => return Ok(None),
+            }
+        }
+        let mode = self.mode.unwrap_or(TransferMode::Move);
+        let transfer = || transfer_file(file, &target, mode, self.verify);
+        let bytes = if replace { conflict::replace_with(&target, transfer)? } else { transfer()? };
+        Ok(Some((target, bytes)))
     }
 
-    fn categorize_files(&self, dir: &PathBuf, _rules: &[CategoryRule]) -> Result<()> {
-        let walker = if self.recursive {
-            WalkDir::new(dir)
-        } else {
-            WalkDir::new(dir).max_depth(1)
+    /// Every source root, the input directory first
+    fn input_dirs(&self) -> impl Iterator<Item = &Path> {
+        self.input_dir.iter().chain(&self.extra_input_dirs).map(PathBuf::as_path)
+    }
+
+    /// The source root `file` was found under
+    fn source_root(&self, file: &Path) -> Option<&Path> {
+        self.input_dirs().find(|root| file.starts_with(root))
+    }
+
+    /// Where the move report goes by default: the input directory, or the
+    /// organized root when several inputs were swept
+    fn report_dir<'a>(&'a self, base_dir: &'a Path) -> &'a Path {
+        match &self.input_dir {
+            Some(dir) if self.extra_input_dirs.is_empty() => dir,
+            _ => base_dir,
+        }
+    }
+
+    /// Bytes the planned copies write under each destination root: the
+    /// categorized tree and any rule folders placed elsewhere
+    fn estimate_space(&self, plan: &Plan, base_dir: &Path) -> Result<Vec<SpaceEstimate>> {
+        let mut required: BTreeMap<&Path, u64> = BTreeMap::new();
+        for entry in &plan.entries {
+            let root = self
+                .rule_dests
+                .values()
+                .map(PathBuf::as_path)
+                .find(|dir| entry.destination.starts_with(dir))
+                .unwrap_or(base_dir);
+            *required.entry(root).or_default() += entry.size;
+        }
+        required
+            .into_iter()
+            .map(|(root, bytes)| SpaceEstimate::new(root, bytes))
+            .collect()
+    }
+
+    /// The categorizer `options` describe, with nothing left to ask
+    fn from_options(options: &CategorizeOptions) -> Result<Self> {
+        let Some((input_dir, extra_input_dirs)) = options.input_dirs.split_first() else {
+            anyhow::bail!("No input directory given");
         };
+        check_input_dirs(&options.input_dirs)?;
+        if options.rules.is_empty() {
+            anyhow::bail!("No categorization rules given");
+        }
+        date::validate_format(&options.date_format)?;
+        let rules = options
+            .rules
+            .iter()
+            .map(|rule| rule.compile(&options.config))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            input_dir: Some(input_dir.clone()),
+            extra_input_dirs: extra_input_dirs.to_vec(),
+            rules,
+            mode: Some(options.mode),
+            output_dir: options.output_dir.clone(),
+            date_source: options.date_source,
+            date_format: Some(options.date_format.clone()),
+            unmatched: Some(options.unmatched),
+            hidden: Some(options.hidden),
+            junk: options.junk.clone(),
+            names: options.names,
+            composition: Some(options.composition),
+            on_conflict: Some(options.on_conflict),
+            grace_period: options.grace_period,
+            include_repos: options.include_repos,
+            fail_fast: options.fail_fast,
+            delete_mode: options.delete_mode,
+            verify: options.verify,
+            ..Self::new(options.recursive)
+        })
+    }
 
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
-            if !entry.file_type().is_file() {
-                continue;
+    /// Walks every input directory, returning the root the tree is built
+    /// under, the entries each input held, and the plan before hidden and
+    /// unmatched files are routed
+    fn scan(&self, progress: impl ProgressSink) -> Result<(PathBuf, Vec<usize>, Plan)> {
+        let base_dir = self.base_dir()?.to_path_buf();
+        let entries_before = self.input_dirs().map(summary::top_level_entries).collect();
+        let mut plan = Plan::default();
+        for root in self.input_dirs() {
+            progress.scan_started(root);
+            self.build_plan(&mut plan, root, &base_dir)?;
+        }
+        Ok((base_dir, entries_before, plan))
+    }
+
+    /// Routes the hidden files set aside by [`scan`](Self::scan) by
+    /// `hidden`, then the unmatched ones by the policy `unmatched` picks,
+    /// which is only asked when some file matched no rule
+    fn route(
+        &self,
+        base_dir: PathBuf,
+        entries_before: Vec<usize>,
+        mut plan: Plan,
+        hidden: HiddenPolicy,
+        unmatched: impl FnOnce(&Plan) -> Result<UnmatchedPolicy>,
+    ) -> Result<CategorizeJob> {
+        let hidden_count = plan.hidden.len();
+        let junk = plan.hidden.iter().filter(|file| self.junk.is_junk_file(file)).count();
+        self.route_hidden(&mut plan, &base_dir, hidden)?;
+
+        let unmatched_count = plan.unmatched.len();
+        let unmatched_policy = if unmatched_count == 0 { UnmatchedPolicy::LeaveInPlace } else { unmatched(&plan)? };
+        self.route_unmatched(&mut plan, &base_dir, unmatched_policy)?;
+        let space = match self.mode {
+            Some(TransferMode::Copy) => self.estimate_space(&plan, &base_dir)?,
+            _ => Vec::new(),
+        };
+
+        Ok(CategorizeJob {
+            unmatched: unmatched_count,
+            unmatched_left_in_place: matches!(
+                unmatched_policy,
+                UnmatchedPolicy::LeaveInPlace | UnmatchedPolicy::Prompt
+            ),
+            hidden_skipped: if hidden == HiddenPolicy::Skip { hidden_count } else { 0 },
+            junk_skipped: if hidden == HiddenPolicy::Skip { junk } else { 0 },
+            space,
+            ..CategorizeJob::new(base_dir, entries_before, plan)
+        })
+    }
+
+    /// Walks `dir` and adds a transfer for every file a rule matches to
+    /// `plan`, skipping the categorizer's own output directories
+    fn build_plan(&self, plan: &mut Plan, dir: &Path, base_dir: &Path) -> Result<()> {
+        let mut walk = Walker::new(self.recursive)
+            .include_repos(self.include_repos)
+            .sorted(self.sorted)
+            .exclude(self.output_roots(dir, base_dir))
+            .junk(Some(self.junk.clone()))
+            .files(dir);
+        plan.repositories.append(&mut walk.repositories);
+        plan.inaccessible.append(&mut walk.inaccessible);
+        // Junk files wait for the hidden-file policy like dotfiles; junk
+        // directories are never looked into
+        for path in std::mem::take(&mut walk.junk) {
+            if path.is_dir() {
+                plan.junk_dirs.push(path);
+            } else {
+                plan.hidden.push(path);
             }
+        }
 
-            let path = entry.path().to_path_buf();
-            self.process_file(&path)?;
+        for file in walk.into_paths() {
+            if report::is_run_output(&file, dir) || self.report_path.as_deref() == Some(file.as_path()) {
+                continue;
+            }
+            // A file that cannot be read for its date or contents is left
+            // where it is
+            let result = grace::is_in_progress(&file, self.grace_period).and_then(|in_progress| {
+                if in_progress {
+                    plan.deferred.push(file.clone());
+                } else if is_hidden(&file, dir) {
+                    plan.hidden.push(file.clone());
+                } else {
+                    self.plan_into(plan, &file, base_dir)?;
+                }
+                Ok(())
+            });
+            match result {
+                Err(e) if is_access_denied(&e) => plan.inaccessible.push(Inaccessible::new(&file, &e)),
+                result => result?,
+            }
         }
 
         Ok(())
     }
 
-    fn categorize_by_type(&self, file: &PathBuf, base_dir: &PathBuf) -> Result<()> {
-        if let Some(ext) = file.extension() {
-            let category = ext.to_string_lossy().to_lowercase();
-            let target_dir = base_dir.join("by_type").join(&category);
-            fs::create_dir_all(&target_dir)?;
-            
-            let target_path = target_dir.join(file.file_name().unwrap());
-            if !target_path.exists() {
-                fs::rename(file, target_path)?;
+    fn plan_into(&self, plan: &mut Plan, file: &Path, base_dir: &Path) -> Result<()> {
+        match self.plan_file(file, base_dir)? {
+            // Already where the rules would put it
+            Some(planned) if planned.destination == planned.source => {}
+            Some(planned) => plan.entries.push(planned),
+            None => plan.unmatched.push(file.to_path_buf()),
+        }
+        Ok(())
+    }
+
+    /// Plans the hidden and junk files set aside while walking: drops them,
+    /// plans them like other files, or queues known junk for deletion
+    fn route_hidden(&self, plan: &mut Plan, base_dir: &Path, policy: HiddenPolicy) -> Result<()> {
+        for file in std::mem::take(&mut plan.hidden) {
+            match policy {
+                HiddenPolicy::Skip => {}
+                HiddenPolicy::Categorize => self.plan_into(plan, &file, base_dir)?,
+                HiddenPolicy::DeleteJunk if self.junk.is_junk_file(&file) => plan.deletions.push(file),
+                HiddenPolicy::DeleteJunk => {}
             }
         }
         Ok(())
     }
 
-    fn categorize_by_date(&self, file: &PathBuf, base_dir: &PathBuf) -> Result<()> {
-        let metadata = fs::metadata(file)?;
-        let created = metadata.created()?;
-        let datetime = chrono::DateTime::<chrono::Local>::from(created);
-        
-        let year = datetime.format("%Y").to_string();
-        let month = datetime.format("%m-%B").to_string();
-        
-        let target_dir = base_dir.join("by_date").join(&year).join(&month);
-        fs::create_dir_all(&target_dir)?;
-        
-        let target_path = target_dir.join(file.file_name().unwrap());
-        if !target_path.exists() {
-            fs::rename(file, target_path)?;
+    /// Plans unmatched files into `Uncategorized/` (by sniffed content
+    /// group if requested), or leaves them out of the plan
+    fn route_unmatched(&self, plan: &mut Plan, base_dir: &Path, policy: UnmatchedPolicy) -> Result<()> {
+        let sniff = match policy {
+            UnmatchedPolicy::Uncategorized => false,
+            UnmatchedPolicy::UncategorizedByContent => true,
+            UnmatchedPolicy::LeaveInPlace | UnmatchedPolicy::Prompt => return Ok(()),
+        };
+
+        for file in std::mem::take(&mut plan.unmatched) {
+            let mut target_dir = base_dir.join(UNCATEGORIZED);
+            let mut category = UNCATEGORIZED.to_string();
+            if sniff {
+                let group = content::sniff(&file)?.folder();
+                target_dir.push(group);
+                category = format!("{}/{}", UNCATEGORIZED, group);
+            }
+            plan.entries.push(PlannedTransfer {
+                destination: target_dir.join(self.file_name(&file)),
+                matches: vec![RuleMatch { rule: "unmatched", category }],
+                size: fs::metadata(&file)?.len(),
+                source: file,
+            });
         }
         Ok(())
     }
 
-    fn categorize_by_custom_rules(
+    /// Directories under the input that hold categorized files: every rule
+    /// root (whether or not the rule is selected this run, so trees from
+    /// earlier runs are recognized), the preset folders, and the output
+    /// directory itself when it lies inside the input
+    fn output_roots(&self, input_dir: &Path, base_dir: &Path) -> Vec<PathBuf> {
+        let mut roots = Vec::new();
+        if base_dir != input_dir {
+            if base_dir.starts_with(input_dir) {
+                roots.push(base_dir.to_path_buf());
+            }
+        } else {
+            roots.extend(OUTPUT_ROOTS.iter().map(|root| base_dir.join(root)));
+            for rule in &self.rules {
+                if let CategoryRule::Preset(table) = rule {
+                    roots.extend(table.values().map(|category| base_dir.join(category)));
+                }
+            }
+        }
+        // Per-rule destinations inside the input, but not the input itself
+        roots.extend(
+            self.rule_dests
+                .values()
+                .filter(|dest| dest.starts_with(input_dir) && dest.as_path() != input_dir)
+                .cloned(),
+        );
+        roots.sort();
+        roots.dedup();
+        roots
+    }
+
+    /// Carries out `plan`, returning the tally of what was done even when
+    /// the run stopped part way. `transferred` sees the tally after each
+    /// file that was transferred.
+    fn execute_plan(
         &self,
-        file: &PathBuf,
-        base_dir: &PathBuf,
-        rules: &HashMap<String, Vec<String>>,
-    ) -> Result<()> {
-        if let Some(ext) = file.extension() {
-            let ext = ext.to_string_lossy().to_lowercase();
-            
-            for (category, extensions) in rules {
-                if extensions.contains(&ext) {
-                    let target_dir = base_dir.join("custom").join(category);
-                    fs::create_dir_all(&target_dir)?;
-                    
-                    let target_path = target_dir.join(file.file_name().unwrap());
-                    if !target_path.exists() {
-                        fs::rename(file, target_path)?;
+        plan: &Plan,
+        journal: &mut Journal,
+        progress: impl ProgressSink,
+        transferred: impl Fn(&TransferSummary),
+    ) -> (TransferSummary, Result<()>) {
+        let mut summary = TransferSummary::default();
+        let errors = ErrorLog::new(self.fail_fast);
+        let phase = match self.mode.unwrap_or(TransferMode::Move) {
+            TransferMode::Move => "Moving",
+            TransferMode::Copy => "Copying",
+        };
+        progress.phase_changed(phase, plan.entries.len() as u64);
+        let result = plan.entries.iter().try_for_each(|entry| {
+            if entry.destination.exists() {
+                summary.conflicts += 1;
+            }
+            let done = match self.execute_entry(entry) {
+                Err(e) if is_in_use(&e) => {
+                    summary.in_use.push(entry.source.clone());
+                    None
+                }
+                result => errors.check(&entry.source, "transfer", result)?.flatten(),
+            };
+            match done {
+                Some((destination, bytes, metadata)) => {
+                    journal.record_transfer(&entry.source, &destination, &metadata, Some(&entry.rules()));
+                    let root = self
+                        .source_root(&entry.source)
+                        .unwrap_or_else(|| entry.source.parent().unwrap_or(&entry.source));
+                    summary.add(root, entry.category(), bytes);
+                    progress.item_finished(&entry.source, bytes, Outcome::Done);
+                    transferred(&summary);
+                }
+                None => progress.item_finished(&entry.source, 0, Outcome::Skipped),
+            }
+            anyhow::Ok(())
+        });
+        let result = result.and_then(|_| {
+            if plan.deletions.is_empty() {
+                return Ok(());
+            }
+            progress.phase_changed("Deleting", plan.deletions.len() as u64);
+            // Files gone since planning are left out; the rest go in one
+            // batch, which the trash handles much faster
+            let present: Vec<(&PathBuf, fs::Metadata)> = plan
+                .deletions
+                .iter()
+                .filter_map(|path| Some((path, fs::metadata(path).ok()?)))
+                .collect();
+            let paths: Vec<&Path> = present.iter().map(|(path, _)| path.as_path()).collect();
+            let results = safe_delete_all(&paths, self.delete_mode);
+            for ((path, metadata), result) in present.iter().zip(results) {
+                if errors.check(path, "delete", result)?.is_some() {
+                    if self.delete_mode != DeleteMode::DryRun {
+                        journal.record_deletion(path, metadata, Some("junk"));
                     }
-                    break;
+                    summary.deleted += 1;
+                    progress.item_finished(path, metadata.len(), Outcome::Done);
+                } else {
+                    progress.item_finished(path, metadata.len(), Outcome::Failed);
                 }
             }
+            anyhow::Ok(())
+        });
+        summary.errors = errors.into_errors();
+        (summary, result)
+    }
+
+    /// Transfers one planned file, returning where it went, its size, and
+    /// its metadata from before the transfer for the journal
+    fn execute_entry(&self, entry: &PlannedTransfer) -> Result<Option<(PathBuf, u64, fs::Metadata)>> {
+        // The file may have been moved or deleted since it was planned
+        let Ok(metadata) = fs::metadata(&entry.source) else {
+            return Ok(None);
+        };
+        if let Some(parent) = entry.destination.parent() {
+            fs::create_dir_all(long_path(parent))?;
+        }
+        let transferred = self.transfer(&entry.source, &entry.destination)?;
+        Ok(transferred.map(|(destination, bytes)| (destination, bytes, metadata)))
+    }
+
+    /// Saves the journal to the data directory (and every input directory
+    /// when requested) and tells the user how to undo the run
+    fn write_journal(&self, journal: &Journal, input_dirs: &[&Path]) -> Result<()> {
+        if journal.entries.is_empty() {
+            return Ok(());
+        }
+
+        let path = Journal::default_dir()?.join(journal.file_name());
+        journal.save(&path)?;
+        if self.journal_in_input {
+            for input_dir in input_dirs {
+                journal.save(&input_dir.join(journal.file_name()))?;
+            }
         }
+        println!("Undo with: file-organizer-rust undo --manifest {}", path.display());
+        Ok(())
+    }
+
+    /// Writes the move report in the preset format, or asks whether to
+    /// write one and in which format
+    fn write_report(&self, journal: &Journal, summary: &TransferSummary, report_dir: &Path) -> Result<()> {
+        let format = match self.report {
+            Some(format) => format,
+            None => {
+                let report_options = vec!["No report", "CSV", "JSON"];
+                let report_selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Write a move report?")
+                    .items(&report_options)
+                    .default(0)
+                    .interact()?;
+                match report_selection {
+                    0 => return Ok(()),
+                    1 => ReportFormat::Csv,
+                    2 => ReportFormat::Json,
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        let path = self
+            .report_path
+            .clone()
+            .unwrap_or_else(|| report::default_path(report_dir, format));
+        report::write(journal, summary, &path, format)?;
+        println!("Move report written to {}", path.display());
         Ok(())
     }
-} 
\ No newline at end of file
+
+    fn date_category(&self, file: &Path) -> Result<Option<PathBuf>> {
+        let (datetime, source) = date::resolve(file, self.date_source.unwrap_or(DateSource::Created))?;
+        if self.verbose {
+            println!("{}: dated by {}", file.display(), source.label());
+        }
+        
+        let format = self.date_format.as_deref().unwrap_or(date::DEFAULT_FORMAT);
+        Ok(Some(date::folder(&datetime, format)))
+    }
+
+    fn video_category(&self, file: &Path) -> Option<PathBuf> {
+        let (folder, info) = video::folder(file)?;
+        if self.verbose {
+            let details = info.as_ref().map_or_else(|| "no readable metadata".to_string(), |info| info.describe());
+            println!("{}: {}", file.display(), details);
+        }
+        Some(folder)
+    }
+
+    fn content_category(&self, file: &Path) -> Result<Option<PathBuf>> {
+        let group = content::sniff(file)?;
+        if let Some(expected) = content::expected_group(file) {
+            if expected != group {
+                println!(
+                    "Suspicious: {} has a {} extension but {} content",
+                    file.display(),
+                    expected.folder(),
+                    group.folder()
+                );
+            }
+        }
+
+        Ok(Some(PathBuf::from(group.folder())))
+    }
+
+    fn custom_category(&self, file: &Path, rules: &[CustomRule]) -> Option<PathBuf> {
+        let rule = custom::first_match(rules, file, self.source_root(file))?;
+        Some(PathBuf::from(&rule.category))
+    }
+
+    fn age_category(&self, file: &Path, buckets: &[AgeBucket]) -> Result<Option<PathBuf>> {
+        let (datetime, source) = date::resolve(file, self.date_source.unwrap_or(DateSource::Modified))?;
+        if self.verbose {
+            println!("{}: aged by {}", file.display(), source.label());
+        }
+
+        let now = chrono::Local::now().naive_local();
+        Ok(Some(PathBuf::from(age::bucket_folder(buckets, &datetime, &now))))
+    }
+
+    fn preset_category(&self, file: &Path, table: &HashMap<String, String>) -> Option<PathBuf> {
+        let ext = file.extension()?.to_string_lossy().to_lowercase();
+        table.get(&ext).map(PathBuf::from)
+    }
+}
+
+fn preset_rule() -> Result<CategoryRule> {
+    let config = Config::load()?;
+    Ok(CategoryRule::Preset(preset::table(&config.categorize.preset_overrides)))
+}
+
+/// Asks whether some rules should write to their own destination root, and
+/// where; rules left empty keep their folder under the output root
+fn prompt_rule_dests(rules: &[CategoryRule]) -> Result<BTreeMap<&'static str, PathBuf>> {
+    let mut dests = BTreeMap::new();
+    let separate = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Send any rule's folders to a different destination root?")
+        .default(false)
+        .interact()?;
+    if !separate {
+        return Ok(dests);
+    }
+
+    for rule in rules {
+        let dest: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Destination for the {} rule (leave empty for the default)", rule.label()))
+            .allow_empty(true)
+            .interact_text()?;
+        if !dest.trim().is_empty() {
+            dests.insert(rule.label(), expand_path(dest.trim())?);
+        }
+    }
+    Ok(dests)
+}
+
+fn prompt_conflict_policy() -> Result<ConflictPolicy> {
+    let policy_options = vec![
+        "Rename (add numeric suffix)",
+        "Skip",
+        "Overwrite",
+        "Skip if identical (compare hashes), rename otherwise",
+    ];
+    let policy_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("When a file already exists in its category")
+        .items(&policy_options)
+        .default(0)
+        .interact()?;
+
+    Ok(match policy_selection {
+        0 => ConflictPolicy::Rename,
+        1 => ConflictPolicy::Skip,
+        2 => ConflictPolicy::Overwrite,
+        3 => ConflictPolicy::SkipIdentical,
+        _ => unreachable!(),
+    })
+}
+
+fn prompt_date_source() -> Result<DateSource> {
+    let source_options = vec![
+        "Creation date (falls back to modification date)",
+        "Modification date",
+        "Embedded date first (EXIF for photos, container metadata for videos)",
+    ];
+    let source_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select date source")
+        .items(&source_options)
+        .default(0)
+        .interact()?;
+
+    Ok(match source_selection {
+        0 => DateSource::Created,
+        1 => DateSource::Modified,
+        2 => DateSource::ExifFirst,
+        _ => unreachable!(),
+    })
+}
+
+/// Rejects duplicate or nested source roots, whose files would otherwise be
+/// planned twice
+fn check_input_dirs(dirs: &[PathBuf]) -> Result<()> {
+    for (i, dir) in dirs.iter().enumerate() {
+        if !dir.is_dir() {
+            anyhow::bail!("Not a directory: {}", dir.display());
+        }
+        if let Some(other) = dirs[i + 1..].iter().find(|other| other.starts_with(dir) || dir.starts_with(other)) {
+            anyhow::bail!("Input directories overlap: {} and {}", dir.display(), other.display());
+        }
+    }
+    Ok(())
+}
+
+fn prompt_hidden_policy(count: usize) -> Result<HiddenPolicy> {
+    let policy_options = vec![
+        "Skip them",
+        "Categorize them like other files",
+        "Delete known junk files (.DS_Store, Thumbs.db, ...), skip the rest",
+    ];
+    let policy_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Found {} hidden or junk files. What should happen to them?", count))
+        .items(&policy_options)
+        .default(0)
+        .interact()?;
+
+    Ok(match policy_selection {
+        0 => HiddenPolicy::Skip,
+        1 => HiddenPolicy::Categorize,
+        2 => HiddenPolicy::DeleteJunk,
+        _ => unreachable!(),
+    })
+}
+
+fn prompt_unmatched_policy() -> Result<UnmatchedPolicy> {
+    let policy_options = vec![
+        "Move them to Uncategorized/",
+        "Move them to Uncategorized/, sorted by content type",
+        "Leave them in place",
+    ];
+    let policy_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("What should happen to files no rule matched?")
+        .items(&policy_options)
+        .default(2)
+        .interact()?;
+
+    Ok(match policy_selection {
+        0 => UnmatchedPolicy::Uncategorized,
+        1 => UnmatchedPolicy::UncategorizedByContent,
+        2 => UnmatchedPolicy::LeaveInPlace,
+        _ => unreachable!(),
+    })
+}
+
+fn prompt_composition() -> Result<Composition> {
+    let composition_options = vec![
+        "Nested (e.g. by_date/2024/03-March/jpg/photo.jpg, in selection order)",
+        "Priority (first matching rule claims the file)",
+    ];
+    let composition_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Multiple rules selected, how should they combine?")
+        .items(&composition_options)
+        .default(0)
+        .interact()?;
+
+    Ok(match composition_selection {
+        0 => Composition::Nested,
+        1 => Composition::Priority,
+        _ => unreachable!(),
+    })
+}
+
+fn prompt_date_format() -> Result<String> {
+    let mut format_options: Vec<String> = date::GRANULARITY_FORMATS
+        .iter()
+        .map(|(label, format)| format!("{} ({})", label, format))
+        .collect();
+    format_options.push("Custom template".to_string());
+    let format_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select date folder granularity")
+        .items(&format_options)
+        .default(1)
+        .interact()?;
+
+    if let Some((_, format)) = date::GRANULARITY_FORMATS.get(format_selection) {
+        return Ok(format.to_string());
+    }
+    let format: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Folder template (chrono specifiers, %q for quarter, '/' for subfolders)")
+        .validate_with(|input: &String| date::validate_format(input).map_err(|e| e.to_string()))
+        .interact_text()?;
+    Ok(format)
+}
+
+fn load_ruleset(name: &str) -> Result<Vec<CustomRule>> {
+    let config = Config::load()?;
+    match config.categorize.rulesets.get(name) {
+        Some(ruleset) => custom::compile(ruleset).with_context(|| format!("Rule set '{}'", name)),
+        None => anyhow::bail!(
+            "No saved rule set named '{}' in {}",
+            name,
+            Config::path()?.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(dirs: &[&Path], rules: Vec<Rule>) -> CategorizeOptions {
+        CategorizeOptions {
+            rules,
+            grace_period: Duration::ZERO,
+            ..CategorizeOptions::new(dirs.iter().map(|dir| dir.to_path_buf()).collect())
+        }
+    }
+
+    /// Every file under `root` with its contents
+    fn snapshot(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| (entry.path().strip_prefix(root).unwrap().to_path_buf(), fs::read(entry.path()).unwrap()))
+            .collect()
+    }
+
+    /// Runs `options` twice, checking the second run finds nothing to do,
+    /// and returns the tree after the first
+    fn categorize_twice(root: &Path, options: &CategorizeOptions) -> BTreeMap<PathBuf, Vec<u8>> {
+        let first = execute(options, ()).unwrap();
+        assert!(first.files > 0);
+        let once = snapshot(root);
+        let second = execute(options, ()).unwrap();
+        assert_eq!(second.files, 0);
+        assert_eq!(snapshot(root), once);
+        once
+    }
+
+    #[test]
+    fn categorizing_twice_gives_the_tree_categorizing_once_does() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("nested/deeper")).unwrap();
+        for file in ["photo.jpg", "nested/report.pdf", "nested/deeper/notes.txt", "nested/deeper/other.jpg"] {
+            fs::write(dir.path().join(file), file).unwrap();
+        }
+
+        let options = options(&[dir.path()], vec![Rule::Date, Rule::FileType { grouped: false }]);
+        let tree = categorize_twice(dir.path(), &options);
+        assert_eq!(tree.len(), 4);
+        assert!(tree.keys().all(|path| path.starts_with("by_date")), "{:?}", tree.keys());
+    }
+
+    #[test]
+    fn files_sorted_by_earlier_runs_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let sorted = ["by_type/jpg/old.jpg", "by_date/2020/01-January/old.pdf", "custom/Docs/old.txt"];
+        for file in sorted.iter().chain(&["new.jpg", "sub/new.txt", "sub/new.pdf"]) {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), file).unwrap();
+        }
+
+        let ruleset = [("Docs".to_string(), vec!["txt".to_string(), "pdf".to_string()])].into_iter().collect();
+        let options = CategorizeOptions {
+            composition: Composition::Priority,
+            ..options(&[root], vec![Rule::Custom(ruleset), Rule::FileType { grouped: false }])
+        };
+        let tree = categorize_twice(root, &options);
+        for file in sorted {
+            assert_eq!(tree[Path::new(file)], file.as_bytes(), "{} moved", file);
+        }
+        assert!(tree.contains_key(Path::new("by_type/jpg/new.jpg")));
+        assert!(tree.contains_key(Path::new("custom/Docs/new.txt")));
+        assert!(tree.contains_key(Path::new("custom/Docs/new.pdf")));
+        assert_eq!(tree.len(), 6);
+    }
+
+    #[test]
+    fn the_location_rule_sorts_photos_by_place() {
+        use crate::utils::exif::tests::{gps_jpeg, PARIS, SANTIAGO};
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("paris.jpg"), gps_jpeg(PARIS.0, PARIS.1)).unwrap();
+        fs::write(dir.path().join("santiago.jpg"), gps_jpeg(SANTIAGO.0, SANTIAGO.1)).unwrap();
+        fs::write(dir.path().join("scan.jpg"), [0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+
+        let summary = execute(&options(&[dir.path()], vec![Rule::Location]), ()).unwrap();
+        assert_eq!(summary.files, 3);
+        let root = dir.path().join("by_location");
+        assert!(root.join("France/Île-de-France/paris.jpg").exists());
+        assert!(root.join("Chile/Santiago Metropolitan/santiago.jpg").exists());
+        assert!(root.join("No Location/scan.jpg").exists());
+    }
+
+    #[test]
+    fn only_size_buckets_that_receive_files_are_created() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("note.txt"), "short").unwrap();
+
+        let summary = execute(&options(&[dir.path()], vec![Rule::Size("tiny:1KB,small:1MB,big".into())]), ()).unwrap();
+        assert_eq!(summary.files, 1);
+        let buckets: Vec<_> = fs::read_dir(dir.path().join("by_size"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(buckets.len(), 1, "{:?}", buckets);
+        assert!(buckets[0].starts_with("tiny"));
+        assert!(dir.path().join("by_size").join(&buckets[0]).join("note.txt").exists());
+    }
+
+    #[test]
+    fn the_selected_rules_sort_every_input_directory() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        fs::create_dir(first.path().join("nested")).unwrap();
+        fs::write(first.path().join("nested/photo.jpg"), "jpeg").unwrap();
+        fs::write(second.path().join("report.pdf"), "pdf").unwrap();
+
+        let summary = execute(&options(&[first.path(), second.path()], vec![Rule::FileType { grouped: false }]), ())
+            .unwrap();
+        assert_eq!(summary.files, 2);
+        assert!(summary.errors.is_empty());
+        assert!(first.path().join("by_type/jpg/photo.jpg").exists());
+        assert!(first.path().join("by_type/pdf/report.pdf").exists());
+        assert!(!first.path().join("nested/photo.jpg").exists());
+        assert!(!second.path().join("report.pdf").exists());
+    }
+
+    #[test]
+    fn decomposed_names_are_sorted_under_their_composed_spelling() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("mac")).unwrap();
+        fs::write(dir.path().join("caf\u{e9}.txt"), "linux").unwrap();
+        fs::write(dir.path().join("mac/cafe\u{301}.txt"), "mac").unwrap();
+
+        let summary = execute(&options(&[dir.path()], vec![Rule::FileType { grouped: false }]), ()).unwrap();
+        assert_eq!(summary.files, 2);
+        let mut names: Vec<String> = fs::read_dir(dir.path().join("by_type/txt"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        // The second café.txt clashes with the first rather than sitting
+        // beside it under the other spelling
+        assert_eq!(names, ["caf\u{e9}-1.txt", "caf\u{e9}.txt"]);
+    }
+
+    #[test]
+    fn a_file_whose_folder_cannot_be_created_is_recorded_and_the_rest_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "jpeg").unwrap();
+        fs::write(dir.path().join("report.pdf"), "pdf").unwrap();
+        // A file where the category folder should go
+        fs::create_dir(dir.path().join("by_type")).unwrap();
+        fs::write(dir.path().join("by_type/pdf"), "in the way").unwrap();
+
+        let summary = execute(&options(&[dir.path()], vec![Rule::FileType { grouped: false }]), ()).unwrap();
+        assert_eq!(summary.files, 1);
+        assert!(dir.path().join("by_type/jpg/photo.jpg").exists());
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].path, dir.path().join("report.pdf"));
+        assert_eq!(summary.errors[0].phase, "transfer");
+        assert!(dir.path().join("report.pdf").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_unreadable_file_is_recorded_and_the_rest_sorted() {
+        use std::os::unix::fs::PermissionsExt;
+        // Permissions do not stop root
+        if crate::utils::permissions::is_root() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("photo.jpg"), "jpeg").unwrap();
+        let locked = dir.path().join("locked.pdf");
+        fs::write(&locked, "pdf").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let options = CategorizeOptions {
+            mode: TransferMode::Copy,
+            ..options(&[dir.path()], vec![Rule::FileType { grouped: false }])
+        };
+        let summary = execute(&options, ()).unwrap();
+        assert_eq!(summary.files, 1);
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].path, locked);
+        assert!(!dir.path().join("by_type/pdf/locked.pdf").exists());
+    }
+
+    #[test]
+    fn fail_fast_stops_at_the_first_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("report.pdf"), "pdf").unwrap();
+        fs::create_dir(dir.path().join("by_type")).unwrap();
+        fs::write(dir.path().join("by_type/pdf"), "in the way").unwrap();
+
+        let options = CategorizeOptions {
+            fail_fast: true,
+            ..options(&[dir.path()], vec![Rule::FileType { grouped: false }])
+        };
+        assert!(execute(&options, ()).is_err());
+        assert!(dir.path().join("report.pdf").exists());
+    }
+}
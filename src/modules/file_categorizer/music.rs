@@ -0,0 +1,105 @@
+use lofty::{
+    file::TaggedFileExt,
+    tag::{Accessor, ItemKey},
+};
+use std::path::{Path, PathBuf};
+
+use crate::utils::sanitize::folder_name;
+
+/// Extensions the music rule reads tags from; other files are left alone
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "flac", "m4a", "ogg"];
+
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+const UNKNOWN_ALBUM: &str = "Unknown Album";
+
+/// `<artist>/<album>` from the file's tags, preferring the album artist so
+/// compilations stay together. `None` for non-audio files.
+pub fn artist_album(file: &Path) -> Option<PathBuf> {
+    let ext = file.extension()?.to_string_lossy().to_lowercase();
+    if !AUDIO_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+
+    // Unreadable or untagged audio still goes to the Unknown buckets
+    let tagged = lofty::read_from_path(file).ok();
+    let tag = tagged.as_ref().and_then(|t| t.primary_tag().or_else(|| t.first_tag()));
+
+    let artist = tag
+        .and_then(|tag| {
+            tag.get_string(&ItemKey::AlbumArtist)
+                .and_then(folder_name)
+                .or_else(|| tag.artist().and_then(|artist| folder_name(&artist)))
+        })
+        .unwrap_or_else(|| UNKNOWN_ARTIST.to_string());
+    let album = tag
+        .and_then(|tag| tag.album().and_then(|album| folder_name(&album)))
+        .unwrap_or_else(|| UNKNOWN_ALBUM.to_string());
+
+    Some(PathBuf::from(artist).join(album))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// An MP3 of three silent MPEG-1 Layer III frames (128 kbit/s, 44.1 kHz)
+    /// behind an ID3v2.3 tag holding `frames` as Latin-1 text frames
+    fn tagged_mp3(frames: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (id, text) in frames {
+            body.extend(id.as_bytes());
+            body.extend((text.len() as u32 + 1).to_be_bytes());
+            body.extend([0, 0, 0]);
+            body.extend(text.as_bytes());
+        }
+
+        let mut mp3 = b"ID3\x03\x00\x00".to_vec();
+        // The tag size is stored 7 bits per byte
+        let size = body.len() as u32;
+        mp3.extend([21, 14, 7, 0].map(|shift| (size >> shift) as u8 & 0x7F));
+        mp3.extend(body);
+        for _ in 0..3 {
+            let mut frame = vec![0; 417];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            mp3.extend(frame);
+        }
+        mp3
+    }
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn tagged_files_go_under_artist_and_album() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(dir.path(), "track.mp3", &tagged_mp3(&[("TPE1", "AC/DC"), ("TALB", "Back in Black ")]));
+        assert_eq!(artist_album(&file), Some(Path::new("AC_DC").join("Back in Black")));
+    }
+
+    #[test]
+    fn the_album_artist_keeps_compilations_together() {
+        let dir = tempfile::tempdir().unwrap();
+        let tags = [("TPE1", "Someone"), ("TPE2", "Various Artists"), ("TALB", "Hits")];
+        let file = write(dir.path(), "track.MP3", &tagged_mp3(&tags));
+        assert_eq!(artist_album(&file), Some(Path::new("Various Artists").join("Hits")));
+    }
+
+    #[test]
+    fn untagged_and_unreadable_audio_goes_to_the_unknown_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let unknown = Some(Path::new(UNKNOWN_ARTIST).join(UNKNOWN_ALBUM));
+        assert_eq!(artist_album(&write(dir.path(), "untagged.mp3", &tagged_mp3(&[]))), unknown);
+        assert_eq!(artist_album(&write(dir.path(), "broken.flac", b"not audio")), unknown);
+    }
+
+    #[test]
+    fn other_files_are_left_to_other_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(artist_album(&write(dir.path(), "cover.jpg", b"jpeg")), None);
+        assert_eq!(artist_album(&write(dir.path(), "README", b"text")), None);
+    }
+}
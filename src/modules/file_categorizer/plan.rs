@@ -0,0 +1,163 @@
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::walk::Inaccessible;
+
+/// A rule that contributed to a file's destination
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    /// Label of the rule, e.g. "date"
+    pub rule: &'static str,
+    /// Category path the rule chose, e.g. "2024/03-March"
+    pub category: String,
+}
+
+/// The single transfer planned for one file
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedTransfer {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Matching rules in the order they were applied
+    pub matches: Vec<RuleMatch>,
+    pub size: u64,
+}
+
+impl PlannedTransfer {
+    /// Top-level category the file lands in, used for counts
+    pub fn category(&self) -> &str {
+        self.matches.first().map(|m| m.category.as_str()).unwrap_or_default()
+    }
+
+    /// Rules that placed the file, e.g. "date > file type"
+    pub fn rules(&self) -> String {
+        self.matches.iter().map(|m| m.rule).collect::<Vec<_>>().join(" > ")
+    }
+}
+
+/// Every transfer of a run, computed before anything is moved. The dry run
+/// renders it and the real run executes it.
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub entries: Vec<PlannedTransfer>,
+    /// Files no rule matched, including extensionless ones
+    pub unmatched: Vec<PathBuf>,
+    /// Dotfiles, files in hidden directories, and known junk, set aside
+    /// until the hidden-file policy is chosen
+    pub hidden: Vec<PathBuf>,
+    /// Known junk files to delete
+    pub deletions: Vec<PathBuf>,
+    /// Known junk directories such as `@eaDir`, left alone along with
+    /// everything in them
+    pub junk_dirs: Vec<PathBuf>,
+    /// Unfinished downloads and files modified within the grace period,
+    /// left for a later run
+    pub deferred: Vec<PathBuf>,
+    /// Working copies that were not walked into
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be read, left where they are
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+#[derive(Default)]
+struct DirNode {
+    children: BTreeMap<String, DirNode>,
+    files: Vec<String>,
+}
+
+impl DirNode {
+    fn total(&self) -> usize {
+        self.files.len() + self.children.values().map(DirNode::total).sum::<usize>()
+    }
+
+    fn print(&self, depth: usize, verbose: bool) {
+        let indent = "  ".repeat(depth);
+        for (name, child) in &self.children {
+            println!("{}{}/ ({})", indent, name, child.total());
+            child.print(depth + 1, verbose);
+        }
+        if verbose {
+            for file in &self.files {
+                println!("{}{}", indent, file);
+            }
+        }
+    }
+}
+
+impl Plan {
+    /// Prints the would-be target directories under each of `roots` (the
+    /// output root and any per-rule destinations) with file counts;
+    /// `verbose` also lists the files in each directory
+    pub fn print_tree(&self, roots: &[&Path], verbose: bool) {
+        let mut trees: BTreeMap<&Path, DirNode> = BTreeMap::new();
+        for entry in &self.entries {
+            // The most specific root, as a destination may lie inside the output root
+            let base = roots
+                .iter()
+                .filter(|root| entry.destination.starts_with(root))
+                .max_by_key(|root| root.components().count())
+                .copied()
+                .unwrap_or(Path::new("/"));
+            let relative = entry.destination.strip_prefix(base).unwrap_or(&entry.destination);
+            let mut node = trees.entry(base).or_default();
+            if let Some(parent) = relative.parent() {
+                for component in parent.components() {
+                    let name = component.as_os_str().to_string_lossy().into_owned();
+                    node = node.children.entry(name).or_default();
+                }
+            }
+            let name = relative.file_name().unwrap_or_default().to_string_lossy();
+            node.files.push(format!("{} [{}]", name, entry.rules()));
+        }
+
+        for (base, tree) in &trees {
+            println!();
+            println!("{}/", base.display());
+            tree.print(1, verbose);
+        }
+        println!("{} files would be transferred", self.entries.len());
+        if !self.repositories.is_empty() {
+            println!("{} repositories skipped", self.repositories.len());
+        }
+        if !self.deferred.is_empty() {
+            println!("{} files deferred as still being written", self.deferred.len());
+        }
+        if !self.junk_dirs.is_empty() {
+            println!("{} junk folders skipped", self.junk_dirs.len());
+        }
+        if !self.deletions.is_empty() {
+            println!("{} junk files would be deleted", self.deletions.len());
+            if verbose {
+                for path in &self.deletions {
+                    println!("  {}", path.display());
+                }
+            }
+        }
+    }
+
+    /// Files and bytes per bucket of a rule, e.g. the age buckets, so the
+    /// run doubles as a report of what could be archived
+    pub fn print_rule_report(&self, rule: &str) {
+        let mut buckets: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        for entry in &self.entries {
+            let Some(matched) = entry.matches.iter().find(|m| m.rule == rule) else {
+                continue;
+            };
+            let totals = buckets.entry(matched.category.clone()).or_default();
+            totals.0 += 1;
+            totals.1 += entry.size;
+        }
+        if buckets.is_empty() {
+            return;
+        }
+
+        println!();
+        println!("{:<24} {:>8} {:>12}", format!("By {}", rule), "Files", "Size");
+        for (bucket, (files, bytes)) in &buckets {
+            println!("{:<24} {:>8} {:>12}", bucket, files, format_size(*bytes, BINARY));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Built-in category folders for the usual Downloads-folder cleanup.
+/// Each extension belongs to exactly one category.
+const CATEGORIES: &[(&str, &[&str])] = &[
+    ("Documents", &["pdf", "doc", "docx", "odt", "rtf", "txt", "md", "tex", "pages"]),
+    ("Spreadsheets", &["xls", "xlsx", "ods", "csv", "tsv", "numbers"]),
+    ("Presentations", &["ppt", "pptx", "odp", "key"]),
+    (
+        "Images",
+        &[
+            "jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff", "svg", "heic", "heif", "ico", "raw", "cr2",
+            "nef", "arw", "dng", "psd",
+        ],
+    ),
+    ("Videos", &["mp4", "m4v", "mkv", "mov", "avi", "webm", "flv", "wmv", "mpg", "mpeg", "3gp"]),
+    ("Audio", &["mp3", "flac", "ogg", "wav", "m4a", "aac", "opus", "wma", "aiff"]),
+    ("Archives", &["zip", "tar", "gz", "tgz", "bz2", "xz", "7z", "rar", "zst"]),
+    (
+        "Code",
+        &[
+            "rs", "py", "js", "ts", "jsx", "tsx", "c", "h", "cpp", "hpp", "java", "kt", "go", "rb", "php", "swift",
+            "sh", "html", "css", "json", "yaml", "yml", "toml", "xml", "sql",
+        ],
+    ),
+    ("Installers", &["exe", "msi", "dmg", "pkg", "deb", "rpm", "appimage", "apk"]),
+    ("Fonts", &["ttf", "otf", "woff", "woff2"]),
+    ("Ebooks", &["epub", "mobi", "azw", "azw3", "fb2", "djvu"]),
+];
+
+/// Extension -> category lookup for the preset, with the user's overrides
+/// from the config applied on top
+pub fn table(overrides: &BTreeMap<String, String>) -> HashMap<String, String> {
+    let mut table = HashMap::new();
+    for (category, extensions) in CATEGORIES {
+        for ext in *extensions {
+            table.insert(ext.to_string(), category.to_string());
+        }
+    }
+    for (ext, category) in overrides {
+        table.insert(ext.to_lowercase(), category.clone());
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_extension_maps_to_two_categories() {
+        let mut seen = HashMap::new();
+        for (category, extensions) in CATEGORIES {
+            for ext in *extensions {
+                if let Some(other) = seen.insert(*ext, *category) {
+                    panic!(".{} is in both {} and {}", ext, other, category);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extensions_are_lowercase_without_a_dot() {
+        for ext in CATEGORIES.iter().flat_map(|(_, extensions)| *extensions) {
+            assert!(!ext.is_empty() && !ext.starts_with('.'), "{:?}", ext);
+            assert_eq!(*ext, ext.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn overrides_replace_and_extend_the_preset() {
+        let overrides = BTreeMap::from([
+            ("CSV".to_string(), "Data".to_string()),
+            ("blend".to_string(), "3D".to_string()),
+        ]);
+        let table = table(&overrides);
+        assert_eq!(table["csv"], "Data");
+        assert_eq!(table["blend"], "3D");
+        assert_eq!(table["pdf"], "Documents");
+        assert!(!table.contains_key("CSV"));
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use chrono::Local;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::summary::TransferSummary;
+use crate::utils::{atomic::write_atomic, journal::Journal};
+
+/// File format of the move report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+/// `<input>/categorize-report-<timestamp>.<ext>`
+pub fn default_path(input_dir: &Path, format: ReportFormat) -> PathBuf {
+    input_dir.join(format!(
+        "categorize-report-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Whether `file` is a report or undo journal that a run wrote into
+/// `input_dir`: `categorize-report-<timestamp>.<ext>` or
+/// `categorize-<timestamp>.json` directly inside it. Later runs leave these
+/// where they are.
+pub fn is_run_output(file: &Path, input_dir: &Path) -> bool {
+    if file.parent() != Some(input_dir) {
+        return false;
+    }
+    let Some(name) = file.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let (stamp, extensions): (&str, &[&str]) = match name.strip_prefix("categorize-report-") {
+        Some(rest) => (rest, &["csv", "json"]),
+        None => match name.strip_prefix("categorize-") {
+            Some(rest) => (rest, &["json"]),
+            None => return false,
+        },
+    };
+    let Some((stamp, ext)) = stamp.split_once('.') else {
+        return false;
+    };
+    extensions.contains(&ext)
+        && stamp.len() == 15
+        && stamp.char_indices().all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() })
+}
+
+/// JSON report: the journal's fields plus the run summary
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    #[serde(flatten)]
+    journal: &'a Journal,
+    summary: &'a TransferSummary,
+}
+
+/// Writes every transfer recorded in the run's journal, so the report and
+/// the undo manifest cannot disagree. The JSON format also carries the
+/// summary statistics.
+pub fn write(journal: &Journal, summary: &TransferSummary, path: &Path, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Json => write_atomic(path, serde_json::to_string_pretty(&JsonReport { journal, summary })?)?,
+        ReportFormat::Csv => journal.write_csv(path)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_and_journals_in_the_input_are_recognized() {
+        let input = Path::new("/downloads");
+        let report = default_path(input, ReportFormat::Csv);
+        assert!(is_run_output(&report, input));
+        assert!(is_run_output(&default_path(input, ReportFormat::Json), input));
+        assert!(is_run_output(&input.join("categorize-20240102-030405.json"), input));
+    }
+
+    #[test]
+    fn other_files_are_not_run_output() {
+        let input = Path::new("/downloads");
+        for name in [
+            "categorize-report-20240102-030405.txt",
+            "categorize-20240102-030405.csv",
+            "categorize-report-2024-01-02.csv",
+            "categorize-report-20240102-0304.csv",
+            "my-categorize-report-20240102-030405.csv",
+            "report.csv",
+        ] {
+            assert!(!is_run_output(&input.join(name), input), "{}", name);
+        }
+        // Only the reports the run wrote at the top of the input
+        assert!(!is_run_output(&input.join("sub/categorize-report-20240102-030405.csv"), input));
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    conflict::{self, ConflictPolicy, Resolution},
+    custom::{self, CustomRule},
+    load_ruleset, CategoryRule,
+};
+use crate::utils::{
+    journal::Journal,
+    names::Normalization,
+    sanitize::{long_path, safe_os_name, safe_relative_path},
+    transfer::{transfer_file, TransferMode, Verify},
+};
+
+/// Sorts files one at a time by a saved rule set, for callers that are
+/// handed files rather than walking a tree. Files land where
+/// `categorize --ruleset` would put them: `<base>/custom/<category>/`.
+pub struct RulesetRouter {
+    rules: Vec<CustomRule>,
+    /// `custom/` under the base directory
+    root: PathBuf,
+    mode: TransferMode,
+    on_conflict: ConflictPolicy,
+    verify: Verify,
+    names: Normalization,
+}
+
+/// What [`RulesetRouter::route`] did with a file
+pub enum Routed {
+    /// Transferred into the folder of the first matching rule
+    Transferred { destination: PathBuf, category: String },
+    /// Matched, but the conflict policy left it where it is
+    Skipped(&'static str),
+    Unmatched,
+}
+
+impl RulesetRouter {
+    /// Loads the rule set `name` from the config, to sort files into
+    /// categories under `base_dir`
+    pub fn new(name: &str, base_dir: &Path, mode: TransferMode, on_conflict: ConflictPolicy) -> Result<Self> {
+        Ok(Self {
+            rules: load_ruleset(name)?,
+            root: base_dir.join(CategoryRule::Custom(Vec::new()).root()),
+            mode,
+            on_conflict,
+            verify: Verify::default(),
+            names: Normalization::load()?,
+        })
+    }
+
+    pub fn with_verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Where the category folders are created; a walk over the input
+    /// should leave it out
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Transfers `file`, found under `input_dir`, into the folder of the
+    /// first rule it matches and records it in `journal`, which must have
+    /// the router's transfer mode
+    pub fn route(&self, file: &Path, input_dir: &Path, journal: &mut Journal) -> Result<Routed> {
+        let Some(rule) = custom::first_match(&self.rules, file, Some(input_dir)) else {
+            return Ok(Routed::Unmatched);
+        };
+        let dir = self
+            .root
+            .join(safe_relative_path(Path::new(&self.names.apply(rule.category.as_ref()))));
+        let mut target = dir.join(safe_os_name(&self.names.apply(file.file_name().unwrap())));
+        let mut replace = false;
+        if target.exists() {
+            match conflict::resolve(file, &target, self.on_conflict)? {
+                Resolution::Transfer(path) => target = path,
+                Resolution::Replace => replace = true,
+                Resolution::Skip(reason) => return Ok(Routed::Skipped(reason)),
+            }
+        }
+        fs::create_dir_all(long_path(&dir))?;
+        let metadata = fs::metadata(file)?;
+        let transfer = || transfer_file(file, &target, self.mode, self.verify);
+        if replace {
+            conflict::replace_with(&target, transfer)?;
+        } else {
+            transfer()?;
+        }
+        journal.record_transfer(file, &target, &metadata, Some(CategoryRule::Custom(Vec::new()).label()));
+        Ok(Routed::Transferred {
+            destination: target,
+            category: rule.category.clone(),
+        })
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use humansize::{format_size, BINARY};
+
+use crate::utils::parse_size;
+
+/// Buckets used when the user keeps the defaults
+pub const DEFAULT_BUCKETS: &str = "tiny:1MB,small:10MB,medium:100MB,large:1GB,huge";
+
+/// One size range of the size rule
+#[derive(Debug, Clone)]
+pub struct SizeBucket {
+    /// Folder under `by_size/`, e.g. `small (1 MiB-10 MiB)`
+    pub folder: String,
+    /// Exclusive upper bound; `None` for the last bucket
+    pub below: Option<u64>,
+}
+
+/// Parses `label:threshold,...,label` into buckets. Every label but the
+/// last needs a threshold, and thresholds must increase.
+pub fn parse_buckets(spec: &str) -> Result<Vec<SizeBucket>> {
+    let parts: Vec<&str> = spec.split(',').map(str::trim).collect();
+    if parts.len() < 2 {
+        anyhow::bail!("Size buckets need at least two labels, e.g. \"small:10MB,large\"");
+    }
+
+    let mut buckets = Vec::new();
+    let mut previous: Option<u64> = None;
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        let (label, threshold) = match part.split_once(':') {
+            Some((label, size)) if !last => (label.trim(), Some(parse_size(size.trim())?)),
+            None if last => (*part, None),
+            _ if last => anyhow::bail!("The last size bucket '{}' takes no threshold", part),
+            _ => anyhow::bail!("Size bucket '{}' needs a threshold, e.g. \"small:10MB\"", part),
+        };
+        if label.is_empty() || label.contains(['/', '\\']) {
+            anyhow::bail!("Invalid size bucket label '{}'", label);
+        }
+
+        let folder = match (previous, threshold) {
+            (None, Some(upper)) => format!("{} (<{})", label, format_size(upper, BINARY)),
+            (Some(lower), Some(upper)) => {
+                if upper <= lower {
+                    anyhow::bail!("Size bucket '{}' must have a larger threshold than the one before", label);
+                }
+                format!("{} ({}-{})", label, format_size(lower, BINARY), format_size(upper, BINARY))
+            }
+            (Some(lower), None) => format!("{} (>{})", label, format_size(lower, BINARY)),
+            (None, None) => unreachable!(),
+        };
+        buckets.push(SizeBucket { folder, below: threshold });
+        previous = threshold;
+    }
+    Ok(buckets)
+}
+
+/// Folder of the bucket `size` falls into
+pub fn bucket_folder(buckets: &[SizeBucket], size: u64) -> &str {
+    buckets
+        .iter()
+        .find(|bucket| bucket.below.is_none_or(|below| size < below))
+        .map(|bucket| bucket.folder.as_str())
+        .unwrap_or_default()
+}
@@ -0,0 +1,214 @@
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::modules::base::FileError;
+use crate::utils::{
+    metrics::PhaseTiming,
+    walk::{is_repository, Inaccessible},
+};
+
+/// Categories shown in the progress bar message
+const PROGRESS_CATEGORIES: usize = 4;
+
+/// Everything a run did, collected during planning and execution. Printed
+/// after the run and included in the JSON report.
+#[derive(Default, Serialize)]
+pub struct TransferSummary {
+    pub files: usize,
+    pub bytes: u64,
+    /// Transfers whose target already existed, whatever the resolution
+    pub conflicts: usize,
+    pub unmatched: usize,
+    /// Whether unmatched files stayed where they were rather than going to
+    /// `Uncategorized/`
+    pub unmatched_left_in_place: bool,
+    /// Files planned but left for later: unfinished or recently modified
+    pub deferred: usize,
+    /// Files another process held open, skipped
+    pub in_use: Vec<PathBuf>,
+    pub hidden_skipped: usize,
+    /// Known junk directories left alone with everything in them
+    pub junk_dirs_skipped: usize,
+    /// Junk files deleted under `HiddenPolicy::DeleteJunk`
+    pub deleted: usize,
+    pub repositories_skipped: usize,
+    /// Files and directories that could not be read, left where they are
+    pub inaccessible: Vec<Inaccessible>,
+    pub by_category: BTreeMap<String, CategoryCount>,
+    /// Counts per input directory, shown when there are several
+    pub by_source: BTreeMap<PathBuf, CategoryCount>,
+    /// Shape of each input directory before and after the run
+    pub layout: Vec<RootLayout>,
+    /// Files that could not be transferred or deleted
+    pub errors: Vec<FileError>,
+    /// How long scanning, transferring, and deleting took
+    pub phases: Vec<PhaseTiming>,
+}
+
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct CategoryCount {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Entries directly inside an input directory before and after a run, and
+/// the deepest directory left outside the categorized folders
+#[derive(Serialize)]
+pub struct RootLayout {
+    pub root: PathBuf,
+    pub entries_before: usize,
+    pub entries_after: usize,
+    pub deepest_dir: Option<PathBuf>,
+    pub deepest_depth: usize,
+}
+
+impl RootLayout {
+    /// Measures `root` after the run, skipping `excluded` (the categorizer's
+    /// own output) and working copies when looking for the deepest directory
+    pub fn measure(root: &Path, entries_before: usize, excluded: &[PathBuf]) -> Self {
+        let deepest = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0
+                    || !(excluded.iter().any(|dir| dir == e.path())
+                        || e.file_type().is_dir() && is_repository(e.path()))
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.depth() > 0 && e.file_type().is_dir())
+            .max_by_key(|e| e.depth());
+
+        Self {
+            root: root.to_path_buf(),
+            entries_before,
+            entries_after: top_level_entries(root),
+            deepest_depth: deepest.as_ref().map_or(0, |e| e.depth()),
+            deepest_dir: deepest.map(|e| e.into_path()),
+        }
+    }
+}
+
+/// Number of files and directories directly inside `dir`
+pub fn top_level_entries(dir: &Path) -> usize {
+    fs::read_dir(dir).map(|entries| entries.count()).unwrap_or(0)
+}
+
+impl TransferSummary {
+    pub fn add(&mut self, source_root: &Path, category: &str, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        for count in [
+            self.by_category.entry(category.to_string()).or_default(),
+            self.by_source.entry(source_root.to_path_buf()).or_default(),
+        ] {
+            count.files += 1;
+            count.bytes += bytes;
+        }
+    }
+
+    /// Busiest categories for the progress message, e.g.
+    /// "Images 4,210 · Documents 912 · Other 3,004"
+    pub fn progress_message(&self) -> String {
+        let mut counts: Vec<_> = self.by_category.iter().collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(count.files));
+        counts
+            .iter()
+            .take(PROGRESS_CATEGORIES)
+            .map(|(category, count)| format!("{} {}", category, format_count(count.files)))
+            .collect::<Vec<_>>()
+            .join(" · ")
+    }
+
+    /// Prints the per-category, per-source, layout, and counter tables,
+    /// leaving out the empty ones
+    pub fn print_table(&self) {
+        if !self.by_category.is_empty() {
+            println!();
+            println!("{:<32} {:>8} {:>12}", "Category", "Files", "Size");
+            for (category, count) in &self.by_category {
+                println!(
+                    "{:<32} {:>8} {:>12}",
+                    category,
+                    format_count(count.files),
+                    format_size(count.bytes, BINARY)
+                );
+            }
+        }
+
+        if self.by_source.len() > 1 {
+            println!();
+            println!("{:<32} {:>8} {:>12}", "Source", "Files", "Size");
+            for (source, count) in &self.by_source {
+                println!(
+                    "{:<32} {:>8} {:>12}",
+                    source.display(),
+                    format_count(count.files),
+                    format_size(count.bytes, BINARY)
+                );
+            }
+        }
+
+        if !self.layout.is_empty() {
+            println!();
+            println!("{:<32} {:>8} {:>8}  Deepest remaining", "Directory", "Before", "After");
+            for layout in &self.layout {
+                let deepest = match &layout.deepest_dir {
+                    Some(dir) => format!(
+                        "{} ({} levels)",
+                        dir.strip_prefix(&layout.root).unwrap_or(dir).display(),
+                        layout.deepest_depth
+                    ),
+                    None => "-".to_string(),
+                };
+                println!(
+                    "{:<32} {:>8} {:>8}  {}",
+                    layout.root.display(),
+                    format_count(layout.entries_before),
+                    format_count(layout.entries_after),
+                    deepest
+                );
+            }
+        }
+
+        let unmatched = if self.unmatched_left_in_place {
+            "Unmatched, left in place"
+        } else {
+            "Unmatched, sent to Uncategorized/"
+        };
+        let counters = [
+            ("Conflicts", self.conflicts),
+            (unmatched, self.unmatched),
+            ("Deferred", self.deferred + self.in_use.len()),
+            ("Hidden or junk, skipped", self.hidden_skipped),
+            ("Junk folders, skipped", self.junk_dirs_skipped),
+            ("Junk deleted", self.deleted),
+            ("Repositories skipped", self.repositories_skipped),
+            ("Unreadable, skipped", self.inaccessible.len()),
+            ("Failed", self.errors.len()),
+        ];
+        if counters.iter().any(|(_, count)| *count > 0) {
+            println!();
+            for (label, count) in counters.iter().filter(|(_, count)| *count > 0) {
+                println!("{:<32} {:>8}", label, format_count(*count));
+            }
+        }
+    }
+}
+
+/// `4210` -> `4,210`
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    formatted
+}
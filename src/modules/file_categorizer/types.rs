@@ -0,0 +1,210 @@
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use super::{
+    conflict::{self, ConflictPolicy, Resolution},
+    preset,
+};
+use crate::utils::{journal::Journal, transfer::{transfer_file, TransferMode, Verify}};
+
+/// Extension spellings that share a folder with their canonical form
+const DEFAULT_ALIASES: &[(&str, &str)] = &[
+    ("jpeg", "jpg"),
+    ("jpe", "jpg"),
+    ("tif", "tiff"),
+    ("htm", "html"),
+    ("mpeg", "mpg"),
+    ("yml", "yaml"),
+    ("markdown", "md"),
+    ("aif", "aiff"),
+    ("mid", "midi"),
+];
+
+/// How the file type rule names its folders
+#[derive(Debug, Clone)]
+pub struct TypeNaming {
+    aliases: HashMap<String, String>,
+    /// Kind folder of each preset extension, e.g. `jpg` -> `images`
+    kinds: HashMap<String, String>,
+    /// Put each extension under its kind, e.g. `images/jpg`
+    grouped: bool,
+}
+
+/// Kind folder for extensions the preset does not know
+const OTHER_KIND: &str = "other";
+
+impl TypeNaming {
+    /// The default aliases with the config's `extension_aliases` on top
+    pub fn new(overrides: &BTreeMap<String, String>, grouped: bool) -> Self {
+        let mut aliases: HashMap<String, String> = DEFAULT_ALIASES
+            .iter()
+            .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+            .collect();
+        for (alias, canonical) in overrides {
+            aliases.insert(alias.to_lowercase(), canonical.to_lowercase());
+        }
+        let kinds = preset::table(&BTreeMap::new())
+            .into_iter()
+            .map(|(ext, category)| (ext, category.to_lowercase()))
+            .collect();
+        Self { aliases, kinds, grouped }
+    }
+
+    pub fn canonical(&self, ext: &str) -> String {
+        let ext = ext.to_lowercase();
+        self.aliases.get(&ext).cloned().unwrap_or(ext)
+    }
+
+    /// Folder below `by_type/` for `file`
+    pub fn category(&self, file: &Path) -> Option<PathBuf> {
+        let ext = self.canonical(&file.extension()?.to_string_lossy());
+        if !self.grouped {
+            return Some(PathBuf::from(ext));
+        }
+        Some(PathBuf::from(self.kind(&ext)).join(ext))
+    }
+
+    fn kind(&self, ext: &str) -> &str {
+        self.kinds.get(ext).map_or(OTHER_KIND, String::as_str)
+    }
+
+    fn is_kind_folder(&self, name: &str) -> bool {
+        name == OTHER_KIND || self.kinds.values().any(|kind| kind == name)
+    }
+
+    /// Moves the contents of alias folders left by earlier runs, such as
+    /// `by_type/jpeg`, `by_type/JPG` or `by_type/images/jpeg`, into their
+    /// canonical folder and removes the emptied folders. Grouped folders
+    /// are merged within the canonical extension's kind, ungrouped ones
+    /// next to each other, whatever the naming's own grouping. Returns the
+    /// number of files moved.
+    pub fn merge_aliases(&self, type_root: &Path, policy: ConflictPolicy, journal: &mut Journal) -> Result<usize> {
+        let mut folders = Vec::new();
+        for entry in fs::read_dir(type_root)? {
+            let dir = entry?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+            if self.is_kind_folder(&dir.file_name().unwrap_or_default().to_string_lossy()) {
+                for entry in fs::read_dir(&dir)? {
+                    let sub = entry?.path();
+                    if sub.is_dir() {
+                        folders.push(sub);
+                    }
+                }
+            } else {
+                folders.push(dir);
+            }
+        }
+
+        let mut moved = 0;
+        for dir in folders {
+            let name = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            let canonical = self.canonical(&name);
+            if canonical == name {
+                continue;
+            }
+
+            let target_root = if dir.parent() == Some(type_root) {
+                type_root.join(&canonical)
+            } else {
+                type_root.join(self.kind(&canonical)).join(&canonical)
+            };
+            for file in WalkDir::new(&dir).into_iter().filter_map(|e| e.ok()) {
+                if !file.file_type().is_file() {
+                    continue;
+                }
+                let mut target = target_root.join(file.path().strip_prefix(&dir)?);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut replace = false;
+                if target.exists() {
+                    match conflict::resolve(file.path(), &target, policy)? {
+                        Resolution::Transfer(path) => target = path,
+                        Resolution::Replace => replace = true,
+                        Resolution::Skip(reason) => {
+                            println!("Left {} in place ({})", file.path().display(), reason);
+                            continue;
+                        }
+                    }
+                }
+                let metadata = fs::metadata(file.path())?;
+                // Alias folders sit in the same tree as the canonical one,
+                // so this is a rename and never needs the copy to be verified
+                let transfer = || transfer_file(file.path(), &target, TransferMode::Move, Verify::Size);
+                if replace {
+                    conflict::replace_with(&target, transfer)?;
+                } else {
+                    transfer()?;
+                }
+                journal.record_transfer(file.path(), &target, &metadata, Some("merge aliases"));
+                moved += 1;
+            }
+
+            // Remove the emptied folders bottom-up; any file left behind
+            // keeps its folder
+            for sub in WalkDir::new(&dir).contents_first(true).into_iter().filter_map(|e| e.ok()) {
+                if sub.file_type().is_dir() {
+                    let _ = fs::remove_dir(sub.path());
+                }
+            }
+            if !dir.exists() {
+                let relative = |path: &Path| path.strip_prefix(type_root).unwrap_or(path).display().to_string();
+                println!("Merged {}/ into {}/", relative(&dir), relative(&target_root));
+            }
+        }
+        Ok(moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, path.to_string_lossy().as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn folders_use_the_canonical_extension_and_optionally_its_kind() {
+        let flat = TypeNaming::new(&BTreeMap::new(), false);
+        assert_eq!(flat.category(Path::new("a.JPEG")), Some(PathBuf::from("jpg")));
+        assert_eq!(flat.category(Path::new("README")), None);
+
+        let grouped = TypeNaming::new(&BTreeMap::from([("blend".to_string(), "BLENDER".to_string())]), true);
+        assert_eq!(grouped.category(Path::new("a.jpeg")), Some(Path::new("images").join("jpg")));
+        assert_eq!(grouped.category(Path::new("notes.markdown")), Some(Path::new("documents").join("md")));
+        assert_eq!(grouped.category(Path::new("scene.blend")), Some(Path::new("other").join("blender")));
+    }
+
+    #[test]
+    fn aliases_merge_next_to_their_canonical_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("jpeg/a.jpeg"));
+        write(&root.join("jpg/b.jpg"));
+        write(&root.join("images/jpeg/c.jpeg"));
+        write(&root.join("images/JPG/d.jpg"));
+        write(&root.join("documents/md/e.md"));
+
+        let naming = TypeNaming::new(&BTreeMap::new(), false);
+        let mut journal = Journal::new("categorize", TransferMode::Move);
+        let moved = naming.merge_aliases(root, ConflictPolicy::Rename, &mut journal).unwrap();
+
+        assert_eq!(moved, 3);
+        assert_eq!(journal.entries.len(), 3);
+        for file in ["jpg/a.jpeg", "jpg/b.jpg", "images/jpg/c.jpeg", "images/jpg/d.jpg", "documents/md/e.md"] {
+            assert!(root.join(file).is_file(), "{} is missing", file);
+        }
+        for gone in ["jpeg", "images/jpeg", "images/JPG"] {
+            assert!(!root.join(gone).exists(), "{} was not removed", gone);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::{Path, PathBuf};
+
+use crate::utils::video::{self, VideoInfo};
+
+/// `<resolution class>` folder for a video from its container metadata,
+/// with the metadata for reporting. Videos whose size cannot be read, such
+/// as AVIs without `ffprobe`, go to `Unknown Resolution`. `None` for
+/// non-video files.
+pub fn folder(file: &Path) -> Option<(PathBuf, Option<VideoInfo>)> {
+    if !video::is_video(file) {
+        return None;
+    }
+    let info = video::probe(file);
+    let class = info
+        .as_ref()
+        .map_or(video::UNKNOWN_RESOLUTION, VideoInfo::resolution_class);
+    Some((PathBuf::from(class), info))
+}
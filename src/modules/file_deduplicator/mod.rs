@@ -1,64 +1,425 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use dialoguer::{theme::ColorfulTheme, Select};
-use sha2::{Sha256, Digest};
-use std::{collections::HashMap, fs, path::PathBuf, io::Read};
-use walkdir::WalkDir;
-
-use crate::utils::{create_spinner, get_directory_from_user};
-use crate::modules::base::FileOrganizer;
+use dialoguer::theme::ColorfulTheme;
+use serde::{Deserialize, Serialize};
+use indexmap::IndexMap;
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+
+use crate::utils::prompt::Select;
+use crate::utils::{
+    create_spinner, Spinner, delete::{safe_delete_all, DeleteMode}, filter::PathFilter, get_directory_from_user, hash_cache::HashCache, hashing::{Algorithm, Hashing}, junk::JunkList, metrics::Metrics, secure_delete::print_caveat,
+    progress::{Outcome, ProgressSink},
+    stamp::FileStamp,
+    transfer::{move_file, Verify},
+    walk::{is_access_denied, Inaccessible, Walker},
+};
+use crate::modules::base::{print_changed, print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
 
 pub struct FileDeduplicator {
     recursive: bool,
     input_dir: Option<PathBuf>,
     duplicate_action: Option<DuplicateAction>,
     hash_method: Option<HashMethod>,
-    duplicates_dir: Option<PathBuf>,
-    file_hashes: HashMap<String, Vec<PathBuf>>,
+    /// Scan git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Walk in name order, so the same copy is kept on every run
+    sorted: bool,
+    /// Trust that nothing else writes to the tree during the run
+    assume_static: bool,
+    /// Reuse and keep digests of unchanged files between runs
+    hash_cache: bool,
+    /// Abort on the first file that cannot be hashed, deleted, or moved
+    fail_fast: bool,
+    delete_mode: DeleteMode,
+    verify: Verify,
+    /// Shown while hashing and finished with the result
+    spinner: Option<Spinner>,
+    metrics: Metrics,
 }
 
-#[derive(Clone, Copy)]
-enum DuplicateAction {
+/// What happens to every copy after the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateAction {
     Delete,
+    /// Move into `duplicates_dir`
     Move,
+    /// Leave the files alone and only list them
     Report,
 }
 
-#[derive(Clone, Copy)]
-enum HashMethod {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashMethod {
     Sha256,
-    QuickHash,  // First 1MB + file size
+    QuickHash,  // First and last 1MB + file size
+    Blake3,
+    /// Fastest; not cryptographic, which does not matter for duplicates
+    Xxh3,
+}
+
+impl HashMethod {
+    /// Whole-file digests come from `cache` when the file is unchanged;
+    /// quick hashes are cheap enough to always compute
+    pub(crate) fn hash(self, path: &Path, metadata: &fs::Metadata, cache: &HashCache) -> Result<String> {
+        let algorithm = match self {
+            Self::QuickHash => return Hashing::new(Algorithm::Sha256).quick_hash(path),
+            Self::Sha256 => Algorithm::Sha256,
+            Self::Blake3 => Algorithm::Blake3,
+            Self::Xxh3 => Algorithm::Xxh3,
+        };
+        Ok(cache.digest(&Hashing::new(algorithm), path, metadata)?.0)
+    }
+}
+
+/// Everything a deduplication run needs, with no prompting
+pub struct DedupeOptions {
+    /// Directories scanned together, so a file in one can duplicate a file
+    /// in another
+    pub input_dirs: Vec<PathBuf>,
+    pub recursive: bool,
+    /// Scan git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Walk each input in name order rather than filesystem order, so the
+    /// copy kept and the order of the groups are the same on every run
+    pub sorted: bool,
+    pub hash_method: HashMethod,
+    pub action: DuplicateAction,
+    /// Where `DuplicateAction::Move` puts duplicates; defaults to
+    /// `duplicates/` in the first input directory
+    pub duplicates_dir: Option<PathBuf>,
+    /// Include/exclude globs, matched relative to each input directory
+    pub filter: PathFilter,
+    /// Known junk, never hashed; `None` treats it like any other file
+    pub junk: Option<JunkList>,
+    /// How `DuplicateAction::Delete` gets rid of duplicates
+    pub delete_mode: DeleteMode,
+    /// How `DuplicateAction::Move` checks a copy to another filesystem
+    pub verify: Verify,
+    /// Skip checking that a duplicate and its original still have the
+    /// size and modification time they were hashed with before deleting
+    /// or moving it; only safe when nothing else writes to the tree
+    pub assume_static: bool,
+    /// Where digests of unchanged files are kept between runs, shared with
+    /// checksum manifests; `None` hashes every file afresh
+    pub hash_cache: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl DedupeOptions {
+    /// A recursive SHA-256 scan of `input_dirs` that moves duplicates to
+    /// the trash
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            recursive: true,
+            include_repos: false,
+            sorted: false,
+            hash_method: HashMethod::Sha256,
+            action: DuplicateAction::Delete,
+            duplicates_dir: None,
+            filter: PathFilter::default(),
+            junk: Some(JunkList::default()),
+            delete_mode: DeleteMode::Trash,
+            verify: Verify::Size,
+            assume_static: false,
+            hash_cache: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A copy that was deleted, moved, or reported
+#[derive(Debug, Clone, Serialize)]
+pub struct Duplicate {
+    pub path: PathBuf,
+    /// The first file found with the same contents, which is kept
+    pub original: PathBuf,
+    pub size: u64,
+}
+
+/// Outcome of [`execute`]
+#[derive(Debug, Default, Serialize)]
+pub struct DedupeSummary {
+    pub duplicates: Vec<Duplicate>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Known junk files and directories that were never hashed
+    pub junk: Vec<PathBuf>,
+    /// Duplicates left alone because they or their original changed
+    /// after hashing
+    pub changed: Vec<PathBuf>,
+    /// Files that could not be hashed, deleted, or moved
+    pub errors: Vec<FileError>,
+    /// Files and directories that could not be read; copies of what they
+    /// hold were not looked for
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&DedupeSummary> for Summary {
+    fn from(summary: &DedupeSummary) -> Self {
+        Summary {
+            files: summary.duplicates.len(),
+            bytes: summary.bytes(),
+            skipped: summary.changed.len(),
+            junk: summary.junk.len(),
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl DedupeSummary {
+    /// Bytes held by the duplicates, i.e. the space freed by removing them
+    pub fn bytes(&self) -> u64 {
+        self.duplicates.iter().map(|duplicate| duplicate.size).sum()
+    }
+}
+
+/// Copies sharing one hash, in walk order. Groups come in the walk order of
+/// their originals.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    /// The first file found, which is kept
+    pub original: PathBuf,
+    pub duplicates: Vec<PathBuf>,
+}
+
+/// Duplicates found by [`plan`], before anything is deleted or moved
+#[derive(Debug, Default, Serialize)]
+pub struct DedupePlan {
+    pub groups: Vec<DuplicateGroup>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Known junk files and directories, left out of every group
+    pub junk: Vec<PathBuf>,
+    /// Files that could not be hashed, left out of every group
+    pub errors: Vec<FileError>,
+    /// Files and directories that could not be read, left out of every
+    /// group
+    pub inaccessible: Vec<Inaccessible>,
+    /// Size and modification time of every grouped file when it was
+    /// scanned, checked again before it is deleted or moved
+    #[serde(skip)]
+    pub stamps: HashMap<PathBuf, FileStamp>,
+}
+
+/// Hashes every file under the input directories and groups the copies
+pub fn plan(options: &DedupeOptions, progress: impl ProgressSink) -> Result<DedupePlan> {
+    if options.input_dirs.is_empty() {
+        anyhow::bail!("No input directory given");
+    }
+    for dir in &options.input_dirs {
+        progress.scan_started(dir);
+    }
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .sorted(options.sorted)
+        .filter(options.filter.clone())
+        .junk(options.junk.clone())
+        .files_in(&options.input_dirs);
+
+    progress.phase_changed("Hashing", walk.files.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut inaccessible = walk.inaccessible;
+    let mut file_hashes: IndexMap<String, Vec<PathBuf>> = IndexMap::new();
+    let mut stamps = HashMap::new();
+    let cache = HashCache::open(options.hash_cache.as_deref());
+    for entry in walk.files {
+        progress.item_started(&entry.path);
+        let (hash, outcome) = match options.hash_method.hash(&entry.path, &entry.metadata, &cache) {
+            Err(e) if is_access_denied(&e) => {
+                inaccessible.push(Inaccessible::new(&entry.path, &e));
+                (None, Outcome::Skipped)
+            }
+            result => match errors.check(&entry.path, "hash", result)? {
+                Some(hash) => (Some(hash), Outcome::Done),
+                None => (None, Outcome::Failed),
+            },
+        };
+        progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+        if let Some(hash) = hash {
+            stamps.insert(entry.path.clone(), FileStamp::of(&entry.metadata));
+            file_hashes.entry(hash).or_default().push(entry.path);
+        }
+    }
+    // The cache only saves time, so failing to write it fails nothing
+    let _ = cache.save();
+
+    let mut plan = DedupePlan {
+        groups: Vec::new(),
+        repositories: walk.repositories,
+        junk: walk.junk,
+        errors: errors.into_errors(),
+        inaccessible,
+        stamps: HashMap::new(),
+    };
+    plan.groups = file_hashes
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|mut paths| {
+            let original = paths.remove(0);
+            DuplicateGroup { original, duplicates: paths }
+        })
+        .collect();
+    // Only files that may be acted on need their stamp kept
+    plan.stamps = plan
+        .groups
+        .iter()
+        .flat_map(|group| std::iter::once(&group.original).chain(&group.duplicates))
+        .filter_map(|path| stamps.remove_entry(path))
+        .collect();
+    Ok(plan)
+}
+
+/// Applies `options.action` to every duplicate in `plan`
+pub fn apply(options: &DedupeOptions, plan: DedupePlan, progress: impl ProgressSink) -> Result<DedupeSummary> {
+    let duplicates_dir = match (options.action, options.input_dirs.first()) {
+        (DuplicateAction::Move, Some(first_dir)) => {
+            let dir = options
+                .duplicates_dir
+                .clone()
+                .unwrap_or_else(|| first_dir.join("duplicates"));
+            fs::create_dir_all(&dir)?;
+            Some(dir)
+        }
+        _ => None,
+    };
+
+    let errors = ErrorLog::new(options.fail_fast);
+    errors.extend(plan.errors);
+    let phase = match options.action {
+        DuplicateAction::Move => "move",
+        DuplicateAction::Delete | DuplicateAction::Report => "read",
+    };
+    let total = plan.groups.iter().map(|group| group.duplicates.len() as u64).sum();
+    match options.action {
+        DuplicateAction::Move => progress.phase_changed("Moving", total),
+        DuplicateAction::Delete => progress.phase_changed("Deleting", total),
+        DuplicateAction::Report => {}
+    }
+    // Whether a file still looks as it did when hashed; a file that changed
+    // since, or whose original did, may no longer be a duplicate
+    let unchanged = |path: &Path| {
+        options.assume_static
+            || options.action == DuplicateAction::Report
+            || plan.stamps.get(path).is_some_and(|stamp| stamp.matches(path))
+    };
+    let mut duplicates = Vec::new();
+    let mut changed = Vec::new();
+    for group in plan.groups {
+        for duplicate in group.duplicates {
+            if !unchanged(&duplicate) || !unchanged(&group.original) {
+                progress.item_finished(&duplicate, 0, Outcome::Skipped);
+                changed.push(duplicate);
+                continue;
+            }
+            let result = handle_duplicate(&duplicate, options, duplicates_dir.as_deref());
+            if let Some(size) = errors.check(&duplicate, phase, result)? {
+                if options.action == DuplicateAction::Move {
+                    progress.item_finished(&duplicate, size, Outcome::Done);
+                }
+                duplicates.push(Duplicate {
+                    path: duplicate,
+                    original: group.original.clone(),
+                    size,
+                });
+            }
+        }
+    }
+
+    // Deleted in one batch at the end, which the trash handles much faster
+    if options.action == DuplicateAction::Delete {
+        let paths: Vec<&Path> = duplicates.iter().map(|duplicate| duplicate.path.as_path()).collect();
+        let results = safe_delete_all(&paths, options.delete_mode);
+        let mut deleted = Vec::new();
+        for (duplicate, result) in duplicates.into_iter().zip(results) {
+            if errors.check(&duplicate.path, "delete", result)?.is_some() {
+                progress.item_finished(&duplicate.path, duplicate.size, Outcome::Done);
+                deleted.push(duplicate);
+            }
+        }
+        duplicates = deleted;
+    }
+
+    Ok(DedupeSummary {
+        duplicates,
+        repositories: plan.repositories,
+        junk: plan.junk,
+        changed,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    })
+}
+
+/// Moves one duplicate when that is the action, and returns its size.
+/// Deletion is left to the caller, which batches it.
+fn handle_duplicate(duplicate: &Path, options: &DedupeOptions, duplicates_dir: Option<&Path>) -> Result<u64> {
+    let size = fs::metadata(duplicate)?.len();
+    if let (DuplicateAction::Move, Some(dir)) = (options.action, duplicates_dir) {
+        move_file(duplicate, &dir.join(duplicate.file_name().unwrap()), options.verify)?;
+    }
+    Ok(size)
+}
+
+/// Hashes every file under the input directories and applies
+/// `options.action` to each copy after the first
+pub fn execute(options: &DedupeOptions, progress: impl ProgressSink) -> Result<DedupeSummary> {
+    let plan = plan(options, &progress)?;
+    let summary = apply(options, plan, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
 }
 
 #[async_trait]
 impl FileOrganizer for FileDeduplicator {
+    type Plan = DedupePlan;
+    const NAME: &'static str = "deduplicate";
+
     fn new(recursive: bool) -> Self {
         Self {
             recursive,
             input_dir: None,
             duplicate_action: None,
             hash_method: None,
-            duplicates_dir: None,
-            file_hashes: HashMap::new(),
+            include_repos: false,
+            sorted: false,
+            assume_static: false,
+            hash_cache: true,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            verify: Verify::default(),
+            spinner: None,
+            metrics: Metrics::default(),
         }
     }
 
-    async fn run(&self) -> Result<()> {
-        let input_dir = get_directory_from_user("Enter directory to scan for duplicates")?;
-        
+    fn configure(&mut self) -> Result<()> {
+        self.input_dir = Some(get_directory_from_user("Enter directory to scan for duplicates")?);
+
         // Select hash method
-        let hash_options = vec!["SHA-256 (Accurate)", "Quick Hash (Fast)"];
+        let hash_options = vec![
+            "SHA-256 (Accurate)",
+            "Quick Hash (Fast)",
+            "BLAKE3 (Accurate, faster than SHA-256)",
+            "XXH3 (Accurate for duplicates, fastest)",
+        ];
         let hash_selection = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Select hash method")
             .items(&hash_options)
             .default(0)
             .interact()?;
 
-        let hash_method = match hash_selection {
+        self.hash_method = Some(match hash_selection {
             0 => HashMethod::Sha256,
             1 => HashMethod::QuickHash,
+            2 => HashMethod::Blake3,
+            3 => HashMethod::Xxh3,
             _ => unreachable!(),
-        };
+        });
 
         // Select action for duplicates
         let action_options = vec!["Delete duplicates", "Move to separate directory", "Generate report only"];
@@ -68,190 +429,146 @@ impl FileOrganizer for FileDeduplicator {
             .default(0)
             .interact()?;
 
-        let duplicate_action = match action_selection {
+        self.duplicate_action = Some(match action_selection {
             0 => DuplicateAction::Delete,
             1 => DuplicateAction::Move,
             2 => DuplicateAction::Report,
             _ => unreachable!(),
-        };
-
-        // Create duplicates directory if needed
-        let duplicates_dir = if matches!(duplicate_action, DuplicateAction::Move) {
-            let dir = input_dir.join("duplicates");
-            fs::create_dir_all(&dir)?;
-            Some(dir)
-        } else {
-            None
-        };
-
-        // Set up state
-        let mut this = Self {
-            recursive: self.recursive,
-            input_dir: Some(input_dir.clone()),
-            duplicate_action: Some(duplicate_action),
-            hash_method: Some(hash_method),
-            duplicates_dir,
-            file_hashes: HashMap::new(),
-        };
-
-        let spinner = create_spinner("Scanning for duplicates...");
-        
-        // First pass: collect all file hashes
-        this.collect_file_hashes()?;
-
-        // Second pass: handle duplicates
-        let mut total_duplicates = 0;
-        let mut total_space_saved = 0;
-        
-        for (_hash, paths) in this.file_hashes.iter() {
-            if paths.len() > 1 {
-                let duplicates = &paths[1..]; // Keep the first occurrence
-                total_duplicates += duplicates.len();
-                
-                for duplicate in duplicates {
-                    let file_size = fs::metadata(duplicate)?.len();
-                    total_space_saved += file_size;
-
-                    match this.duplicate_action.unwrap() {
-                        DuplicateAction::Delete => {
-                            fs::remove_file(duplicate)?;
-                        }
-                        DuplicateAction::Move => {
-                            if let Some(ref dup_dir) = this.duplicates_dir {
-                                let new_path = dup_dir.join(duplicate.file_name().unwrap());
-                                fs::rename(duplicate, new_path)?;
-                            }
-                        }
-                        DuplicateAction::Report => {
-                            println!("Duplicate found: {}", duplicate.display());
-                            println!("  Original: {}", paths[0].display());
-                            println!("  Size: {} bytes", file_size);
-                        }
-                    }
-                }
+        });
+        if self.duplicate_action == Some(DuplicateAction::Delete) && self.delete_mode == DeleteMode::Secure {
+            if let Some(dir) = &self.input_dir {
+                print_caveat(dir);
             }
         }
-
-        let action_msg = match this.duplicate_action.unwrap() {
-            DuplicateAction::Delete => "deleted",
-            DuplicateAction::Move => "moved",
-            DuplicateAction::Report => "found",
-        };
-
-        spinner.finish_with_message(format!(
-            "Found and {} {} duplicate files (total {} bytes)",
-            action_msg,
-            total_duplicates,
-            total_space_saved
-        ));
-
         Ok(())
     }
 
-    fn is_recursive(&self) -> bool {
-        self.recursive
-    }
-
-    fn get_input_dir(&self) -> Option<&PathBuf> {
-        self.input_dir.as_ref()
-    }
-
-    fn set_input_dir(&mut self, dir: PathBuf) {
-        self.input_dir = Some(dir);
+    fn plan(&mut self) -> Result<DedupePlan> {
+        let spinner = create_spinner("Scanning for duplicates...");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        self.spinner = Some(spinner);
+        Ok(plan)
     }
 
-    fn process_file(&self, file: &PathBuf) -> Result<()> {
-        let hash = match self.hash_method.unwrap() {
-            HashMethod::Sha256 => self.calculate_sha256(file)?,
-            HashMethod::QuickHash => self.calculate_quick_hash(file)?,
-        };
+    fn execute(&self, plan: DedupePlan) -> Result<Summary> {
+        let options = self.options()?;
+        let summary = apply(&options, plan, (self.spinner.as_deref(), &self.metrics))?;
 
-        if let Some(paths) = self.file_hashes.get(&hash) {
-            match self.duplicate_action.unwrap() {
-                DuplicateAction::Delete => {
-                    fs::remove_file(file)?;
-                }
-                DuplicateAction::Move => {
-                    if let Some(ref dup_dir) = self.duplicates_dir {
-                        let new_path = dup_dir.join(file.file_name().unwrap());
-                        fs::rename(file, new_path)?;
-                    }
-                }
-                DuplicateAction::Report => {
-                    println!("Duplicate found: {}", file.display());
-                    println!("  Original: {}", paths[0].display());
-                }
+        if options.action == DuplicateAction::Report {
+            for duplicate in &summary.duplicates {
+                println!("Duplicate found: {}", duplicate.path.display());
+                println!("  Original: {}", duplicate.original.display());
+                println!("  Size: {} bytes", duplicate.size);
             }
         }
-        Ok(())
-    }
 
-    fn create_directories(&self, base_dir: &PathBuf) -> Result<()> {
-        if matches!(self.duplicate_action, Some(DuplicateAction::Move)) {
-            fs::create_dir_all(base_dir.join("duplicates"))?;
+        let outcome = match options.action {
+            DuplicateAction::Delete => format!(", {}", options.delete_mode.verb()),
+            DuplicateAction::Move => ", moved".to_string(),
+            DuplicateAction::Report => String::new(),
+        };
+        let mut message = format!(
+            "Found {} duplicate files (total {} bytes){}",
+            summary.duplicates.len(),
+            summary.bytes(),
+            outcome
+        );
+        if !summary.junk.is_empty() {
+            message.push_str(&format!(", {} junk entries skipped", summary.junk.len()));
         }
-        Ok(())
+        if !summary.inaccessible.is_empty() {
+            message.push_str(&format!(", {} unreadable entries skipped", summary.inaccessible.len()));
+        }
+        if !summary.changed.is_empty() {
+            message.push_str(&format!(", {} changed files skipped", summary.changed.len()));
+        }
+        match &self.spinner {
+            Some(spinner) => spinner.finish_with_message(message),
+            None => println!("{}", message),
+        }
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to scan them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "duplicates of their contents may have been missed");
+        print_changed(&summary.changed, "they, or the original they matched, no longer look as they did when hashed");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
     }
 }
 
 impl FileDeduplicator {
-    fn collect_file_hashes(&mut self) -> Result<()> {
-        let input_dir = self.input_dir.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Input directory not set")
-        })?;
-
-        let walker = if self.recursive {
-            WalkDir::new(input_dir)
-        } else {
-            WalkDir::new(input_dir).max_depth(1)
-        };
-
-        for entry in walker.into_iter().filter_map(|e| e.ok()) {
-            if !entry.file_type().is_file() {
-                continue;
-            }
-
-            let path = entry.path().to_path_buf();
-            let hash = match self.hash_method.unwrap() {
-                HashMethod::Sha256 => self.calculate_sha256(&path)?,
-                HashMethod::QuickHash => self.calculate_quick_hash(&path)?,
-            };
-
-            self.file_hashes.entry(hash).or_insert_with(Vec::new).push(path);
-        }
-
-        Ok(())
+    /// Scans working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
     }
 
-    fn calculate_sha256(&self, file: &PathBuf) -> Result<String> {
-        let mut file = fs::File::open(file)?;
-        let mut hasher = Sha256::new();
-        let mut buffer = [0; 1024];
+    /// Walks in name order, so repeated runs keep the same copy
+    pub fn with_sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
 
-        loop {
-            let count = file.read(&mut buffer)?;
-            if count == 0 {
-                break;
-            }
-            hasher.update(&buffer[..count]);
-        }
+    /// Deletes or moves duplicates without first checking that they and
+    /// their originals are unchanged since hashing
+    pub fn with_assume_static(mut self, assume_static: bool) -> Self {
+        self.assume_static = assume_static;
+        self
+    }
 
-        Ok(format!("{:x}", hasher.finalize()))
+    /// Whether digests of unchanged files are reused from earlier runs and
+    /// kept for later ones
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
     }
 
-    fn calculate_quick_hash(&self, file: &PathBuf) -> Result<String> {
-        let mut file = fs::File::open(file)?;
-        let metadata = file.metadata()?;
-        let mut hasher = Sha256::new();
-        let mut buffer = [0; 1024 * 1024]; // 1MB buffer
+    /// Sets how moved duplicates are checked when they cross filesystems
+    pub fn with_verify(mut self, verify: Verify) -> Self {
+        self.verify = verify;
+        self
+    }
 
-        // Hash file size
-        hasher.update(metadata.len().to_string().as_bytes());
+    /// Trashes, permanently deletes (overwriting them first under
+    /// `DeleteMode::Secure`), or only lists duplicates under the delete
+    /// action
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
 
-        // Hash first 1MB
-        let count = file.read(&mut buffer)?;
-        hasher.update(&buffer[..count]);
+    /// Aborts on the first file that cannot be processed instead of
+    /// recording it and carrying on
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
 
-        Ok(format!("{:x}", hasher.finalize()))
+    /// The configured settings as library options
+    fn options(&self) -> Result<DedupeOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| {
+            anyhow::anyhow!("Input directory not set")
+        })?;
+        Ok(DedupeOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            sorted: self.sorted,
+            hash_method: self.hash_method.unwrap_or(HashMethod::Sha256),
+            action: self.duplicate_action.unwrap_or(DuplicateAction::Delete),
+            fail_fast: self.fail_fast,
+            delete_mode: self.delete_mode,
+            verify: self.verify,
+            assume_static: self.assume_static,
+            hash_cache: if self.hash_cache { Some(HashCache::default_path()?) } else { None },
+            junk: Some(JunkList::load()?),
+            ..DedupeOptions::new(vec![input_dir])
+        })
     }
-} 
\ No newline at end of file
+}
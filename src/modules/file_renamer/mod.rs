@@ -0,0 +1,796 @@
+mod rule;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::{Confirm, Input, MultiSelect, Select};
+use crate::config::Config;
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_categorizer::types::TypeNaming;
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    journal::Journal,
+    metrics::Metrics,
+    names::{escaped_name, is_case_insensitive, is_same_file, numbered_name, NameSet, Normalization},
+    progress::{Outcome, ProgressSink},
+    sanitize::safe_file_name,
+    transfer::{move_file, TransferMode, Verify},
+    walk::{Inaccessible, WalkEntry, Walker},
+};
+pub use rule::{CaseTransform, DatePrefix, DateSource, ExtensionStyle, NamePattern, Replacement};
+
+/// Renames files in bulk by find/replace, case, numbering, date prefixes,
+/// and extension rules, previewing every new name first
+pub struct FileRenamer {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Rename inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Glob patterns a file's relative path must match to be renamed
+    include: Vec<String>,
+    /// Glob patterns that leave a file's name alone
+    exclude: Vec<String>,
+    replacements: Vec<Replacement>,
+    case: Option<CaseTransform>,
+    pattern: Option<NamePattern>,
+    /// First number `{n}` takes in each directory
+    start: usize,
+    date_prefix: Option<DatePrefix>,
+    extension: Option<ExtensionStyle>,
+    /// Show the new names and stop without renaming anything
+    dry_run: bool,
+    /// Abort on the first file that cannot be renamed
+    fail_fast: bool,
+    names: Normalization,
+    metrics: Metrics,
+}
+
+/// Everything a renaming run needs, with no prompting. The rules apply to
+/// the name without its extension in this order: replacements, case,
+/// pattern, date prefix. The extension only changes under `extension`.
+pub struct RenameOptions {
+    pub input_dirs: Vec<PathBuf>,
+    pub recursive: bool,
+    /// Rename inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to each input directory
+    pub filter: PathFilter,
+    pub replacements: Vec<Replacement>,
+    pub case: Option<CaseTransform>,
+    pub pattern: Option<NamePattern>,
+    /// First number `{n}` takes in each directory
+    pub start: usize,
+    pub date_prefix: Option<DatePrefix>,
+    pub extension: Option<ExtensionStyle>,
+    /// Unicode form of the new names
+    pub names: Normalization,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl RenameOptions {
+    /// A recursive run over `input_dirs` with no rules yet, numbering from
+    /// 1 and writing no undo manifest
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            replacements: Vec::new(),
+            case: None,
+            pattern: None,
+            start: 1,
+            date_prefix: None,
+            extension: None,
+            names: Normalization::default(),
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+
+    fn has_rules(&self) -> bool {
+        !self.replacements.is_empty()
+            || self.case.is_some()
+            || self.pattern.is_some()
+            || self.date_prefix.is_some()
+            || self.extension.is_some()
+    }
+}
+
+/// One file's old and new path, always in the same directory
+#[derive(Debug, Clone, Serialize)]
+pub struct Rename {
+    pub source: PathBuf,
+    pub target: PathBuf,
+}
+
+/// A new name that cannot be used
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameConflict {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub reason: String,
+}
+
+/// New names decided by [`plan`], before anything is renamed
+#[derive(Debug, Default, Serialize)]
+pub struct RenamePlan {
+    pub renames: Vec<Rename>,
+    /// Files the rules leave with the name they have
+    pub unchanged: usize,
+    /// New names that clash with each other or with a file that stays, or
+    /// that are not usable at all. While there are any, nothing is renamed.
+    pub conflicts: Vec<RenameConflict>,
+    /// Files whose names are not valid UTF-8, which the rules cannot be
+    /// applied to; left alone
+    pub undecodable: Vec<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be read, left alone
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl RenamePlan {
+    /// Prints `old → new` for every rename, paths relative to `input`,
+    /// then the conflicts and the totals
+    pub fn print_preview(&self, input: &Path) {
+        let relative = |path: &Path| path.strip_prefix(input).unwrap_or(path).display().to_string();
+        let new_name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let width = self
+            .renames
+            .iter()
+            .map(|rename| relative(&rename.source).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(60);
+
+        println!();
+        for rename in &self.renames {
+            println!("{:<width$}  →  {}", relative(&rename.source), new_name(&rename.target));
+        }
+        if !self.conflicts.is_empty() {
+            println!();
+            println!("{} new names cannot be used:", self.conflicts.len());
+            for conflict in &self.conflicts {
+                println!(
+                    "  {}  →  {}: {}",
+                    relative(&conflict.source),
+                    new_name(&conflict.target),
+                    conflict.reason
+                );
+            }
+        }
+        println!();
+        println!("{} files would be renamed, {} keep their name", self.renames.len(), self.unchanged);
+        if !self.undecodable.is_empty() {
+            println!("{} files with names that are not valid UTF-8 are left alone", self.undecodable.len());
+        }
+    }
+}
+
+/// Outcome of [`execute`]
+#[derive(Debug, Default, Serialize)]
+pub struct RenameSummary {
+    pub renamed: Vec<Rename>,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be renamed
+    pub errors: Vec<FileError>,
+    /// Files and directories that could not be read
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&RenameSummary> for Summary {
+    fn from(summary: &RenameSummary) -> Self {
+        Summary {
+            files: summary.renamed.len(),
+            bytes: 0,
+            skipped: 0,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Works out the new name of every file under the input directories and
+/// checks them for conflicts. Counters run per directory, in name order.
+pub fn plan(options: &RenameOptions, progress: impl ProgressSink) -> Result<RenamePlan> {
+    if options.input_dirs.is_empty() {
+        anyhow::bail!("No input directory given");
+    }
+    if !options.has_rules() {
+        anyhow::bail!("No renaming rule given");
+    }
+    for dir in &options.input_dirs {
+        progress.scan_started(dir);
+    }
+    // Hidden files are left alone: renaming `.gitignore` breaks it
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .sorted(true)
+        .skip_hidden(true)
+        .filter(options.filter.clone())
+        .files_in(&options.input_dirs);
+    let naming = match options.extension {
+        Some(ExtensionStyle::Canonical) => Some(TypeNaming::new(&Config::load()?.categorize.extension_aliases, false)),
+        _ => None,
+    };
+
+    let mut plan = RenamePlan {
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..RenamePlan::default()
+    };
+    let mut counters: HashMap<PathBuf, usize> = HashMap::new();
+    let mut by_dir: IndexMap<PathBuf, Vec<Rename>> = IndexMap::new();
+    for entry in walk.files {
+        let dir = entry.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let counter = counters.entry(dir.clone()).or_insert(options.start);
+        let sequence = *counter;
+        *counter += 1;
+
+        let Some((stem, extension)) = split_name(&entry.path) else {
+            plan.undecodable.push(entry.path);
+            continue;
+        };
+        let stem = new_stem(options, &entry, stem, sequence);
+        let extension = extension.map(|ext| match (options.extension, &naming) {
+            (Some(ExtensionStyle::Lower), _) => ext.to_lowercase(),
+            (Some(ExtensionStyle::Upper), _) => ext.to_uppercase(),
+            (Some(ExtensionStyle::Canonical), Some(naming)) => naming.canonical(ext),
+            _ => ext.to_string(),
+        });
+        let name = match extension {
+            Some(ext) => format!("{}.{}", stem, ext),
+            None => stem.clone(),
+        };
+        let problem = if stem.trim().is_empty() {
+            Some("the name would be empty")
+        } else if name.contains(['/', '\\']) {
+            Some("the name would contain a path separator")
+        } else {
+            None
+        };
+        let name = names_form(&name, options.names);
+        let target = dir.join(&name);
+        if let Some(reason) = problem {
+            plan.conflicts.push(RenameConflict {
+                source: entry.path,
+                target,
+                reason: reason.to_string(),
+            });
+        } else if target == entry.path {
+            plan.unchanged += 1;
+        } else {
+            by_dir.entry(dir).or_default().push(Rename { source: entry.path, target });
+        }
+    }
+
+    for (dir, renames) in by_dir {
+        check_conflicts(&dir, renames, &mut plan)?;
+    }
+    Ok(plan)
+}
+
+/// The name without its extension, and the extension, as text; `None` for
+/// names that are not valid UTF-8
+fn split_name(path: &Path) -> Option<(&str, Option<&str>)> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = match path.extension() {
+        Some(ext) => Some(ext.to_str()?),
+        None => None,
+    };
+    Some((stem, extension))
+}
+
+/// `stem` after the replacements, case transform, pattern, and date
+/// prefix, in that order
+fn new_stem(options: &RenameOptions, entry: &WalkEntry, stem: &str, sequence: usize) -> String {
+    let mut stem = stem.to_string();
+    for replacement in &options.replacements {
+        stem = replacement.apply(&stem);
+    }
+    if let Some(case) = options.case {
+        stem = case.apply(&stem);
+    }
+    if let Some(pattern) = &options.pattern {
+        stem = pattern.render(&stem, sequence);
+    }
+    if let Some(prefix) = &options.date_prefix {
+        stem.insert_str(0, &prefix.render(&entry.path, &entry.metadata).unwrap_or_default());
+    }
+    stem
+}
+
+/// `name` in the Unicode form `names` and made safe for the platform
+fn names_form(name: &str, names: Normalization) -> String {
+    let name = names.apply(OsStr::new(name));
+    safe_file_name(&escaped_name(&name)).into_owned()
+}
+
+/// Moves the renames in `dir` into the plan unless a new name is taken: by
+/// a file in `dir` that keeps its name, or by an earlier rename. Names are
+/// compared the way the filesystem compares them.
+fn check_conflicts(dir: &Path, renames: Vec<Rename>, plan: &mut RenamePlan) -> Result<()> {
+    let keys = NameSet::new(is_case_insensitive(dir));
+    let sources: HashSet<&OsStr> = renames.iter().filter_map(|rename| rename.source.file_name()).collect();
+    // Who holds each name, and whether it is another rename
+    let mut holders: HashMap<String, (PathBuf, bool)> = HashMap::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name();
+        if !sources.contains(name.as_os_str()) {
+            holders.insert(keys.key(&name), (entry.path(), false));
+        }
+    }
+
+    for rename in renames {
+        let key = keys.key(rename.target.file_name().unwrap_or_default());
+        match holders.get(&key) {
+            Some((holder, renamed)) => {
+                let holder = Path::new(holder.file_name().unwrap_or_default());
+                let reason = if *renamed {
+                    format!("same new name as {}", holder.display())
+                } else {
+                    format!("name taken by {}", holder.display())
+                };
+                plan.conflicts.push(RenameConflict {
+                    source: rename.source,
+                    target: rename.target,
+                    reason,
+                });
+            }
+            None => {
+                holders.insert(key, (rename.source.clone(), true));
+                plan.renames.push(rename);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Renames every file in `plan`, recording each in an undo manifest. A
+/// rename whose new name another file in the plan still holds waits for
+/// that file to move; when every remaining rename waits, as in a swap,
+/// one file steps aside under a temporary name first.
+pub fn apply(options: &RenameOptions, plan: RenamePlan, progress: impl ProgressSink) -> Result<RenameSummary> {
+    if !plan.conflicts.is_empty() {
+        anyhow::bail!("{} new names cannot be used, nothing was renamed", plan.conflicts.len());
+    }
+    progress.phase_changed("Renaming", plan.renames.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut journal = Journal::new("rename", TransferMode::Move);
+    let mut renamed = Vec::new();
+    let result = rename_all(plan.renames, &errors, &mut journal, &mut renamed, &progress);
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // renamed can be undone
+    let mut manifest = None;
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            manifest = Some(path);
+        }
+    }
+    result?;
+
+    Ok(RenameSummary {
+        renamed,
+        manifest,
+        repositories: plan.repositories,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    })
+}
+
+fn rename_all(
+    renames: Vec<Rename>,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    renamed: &mut Vec<Rename>,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    // Where each file is now, and where it goes
+    let mut pending: Vec<(PathBuf, Rename)> = renames
+        .into_iter()
+        .map(|rename| (rename.source.clone(), rename))
+        .collect();
+    while !pending.is_empty() {
+        let before = pending.len();
+        let mut waiting = Vec::new();
+        for (current, rename) in std::mem::take(&mut pending) {
+            // A new name differing only in case or Unicode form can be the
+            // file's own name
+            if rename.target.exists() && !is_same_file(&current, &rename.target) {
+                waiting.push((current, rename));
+                continue;
+            }
+            progress.item_started(&rename.source);
+            let result = move_file(&current, &rename.target, Verify::Size)
+                .map_err(|e| e.context(format!("Failed to rename to {}", rename.target.display())))
+                .and_then(|_| journal.record(&current, &rename.target, Some("rename")));
+            let outcome = match errors.check(&rename.source, "rename", result)? {
+                Some(()) => {
+                    renamed.push(rename);
+                    Outcome::Done
+                }
+                None => Outcome::Failed,
+            };
+            progress.item_finished(&current, 0, outcome);
+        }
+        if waiting.len() < before {
+            pending = waiting;
+            continue;
+        }
+
+        // Nothing moved. A name may only be waited for while another file
+        // of the plan holds it; anything else took it since planning.
+        let held: Vec<bool> = waiting
+            .iter()
+            .map(|(_, rename)| waiting.iter().any(|(current, _)| is_same_file(current, &rename.target)))
+            .collect();
+        for ((current, rename), held) in waiting.into_iter().zip(held) {
+            if held {
+                pending.push((current, rename));
+                continue;
+            }
+            let error = Err(anyhow::anyhow!("{} already exists, not overwriting it", rename.target.display()));
+            errors.check::<()>(&rename.source, "rename", error)?;
+            progress.item_finished(&current, 0, Outcome::Failed);
+        }
+
+        // Every remaining rename waits on another, as in a swap: one file
+        // steps aside under a temporary name to break the cycle
+        if let Some((current, rename)) = pending.first_mut() {
+            let temporary = temporary_name(current);
+            let result = move_file(current, &temporary, Verify::Size)
+                .and_then(|_| journal.record(current, &temporary, Some("temporary name")));
+            match errors.check(&rename.source, "rename", result)? {
+                Some(()) => *current = temporary,
+                None => {
+                    pending.remove(0);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An unused hidden name next to `path`, such as `.photo.jpg.renaming`
+fn temporary_name(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".renaming");
+    let temporary = path.with_file_name(name);
+    if !temporary.exists() {
+        return temporary;
+    }
+    (2..)
+        .map(|n| path.with_file_name(numbered_name(&temporary, n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(temporary)
+}
+
+/// Works out the new names under the input directories and renames the
+/// files, unless a new name cannot be used
+///
+/// ```
+/// use file_organizer_rust::modules::file_renamer::{self, NamePattern, RenameOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("b.jpg"), "jpeg")?;
+/// std::fs::write(dir.path().join("a.jpg"), "jpeg")?;
+///
+/// let options = RenameOptions {
+///     pattern: Some(NamePattern::parse("holiday_{n:02}")?),
+///     ..RenameOptions::new(vec![dir.path().to_path_buf()])
+/// };
+/// let summary = file_renamer::execute(&options, ())?;
+/// assert_eq!(summary.renamed.len(), 2);
+/// assert!(dir.path().join("holiday_01.jpg").exists());
+/// assert!(dir.path().join("holiday_02.jpg").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &RenameOptions, progress: impl ProgressSink) -> Result<RenameSummary> {
+    let plan = plan(options, &progress)?;
+    let summary = apply(options, plan, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for FileRenamer {
+    type Plan = RenamePlan;
+    const NAME: &'static str = "rename";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include_repos: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            replacements: Vec::new(),
+            case: None,
+            pattern: None,
+            start: 1,
+            date_prefix: None,
+            extension: None,
+            dry_run: false,
+            fail_fast: false,
+            names: Normalization::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory with files to rename")?);
+        }
+        if !self.has_rules() {
+            self.prompt_rules()?;
+        }
+        self.names = Normalization::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<RenamePlan> {
+        let spinner = create_spinner("Working out new names...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Always shows every new name first. Nothing is renamed while a new
+    /// name cannot be used, or in a dry run; otherwise asks before renaming.
+    fn review(&self, plan: &RenamePlan) -> Result<bool> {
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print_preview(input_dir);
+        if !plan.conflicts.is_empty() {
+            println!("Change the rules so every new name is unique; nothing was renamed");
+            return Ok(false);
+        }
+        if plan.renames.is_empty() {
+            println!("No file names to change");
+            return Ok(false);
+        }
+        if self.dry_run {
+            println!("Dry run finished, no files were renamed");
+            return Ok(false);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Rename {} files?", plan.renames.len()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: RenamePlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Renaming files...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!("{} files renamed", summary.renamed.len()));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to rename inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "files inside them were not renamed");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl FileRenamer {
+    /// Presets the directory to rename files in so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Renames inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Only renames files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.jpg" or "scans/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Adds a find/replace, applied after the ones added before it. Any
+    /// preset rule skips the rule prompts.
+    pub fn with_replacement(mut self, replacement: Replacement) -> Self {
+        self.replacements.push(replacement);
+        self
+    }
+
+    pub fn with_case(mut self, case: CaseTransform) -> Self {
+        self.case = Some(case);
+        self
+    }
+
+    /// Builds every new name from `pattern`, e.g. `photo_{n:04}`
+    pub fn with_pattern(mut self, pattern: NamePattern) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Starts the `{n}` counter of each directory at `start` instead of 1
+    pub fn with_start(mut self, start: usize) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn with_date_prefix(mut self, prefix: DatePrefix) -> Self {
+        self.date_prefix = Some(prefix);
+        self
+    }
+
+    pub fn with_extension_style(mut self, style: ExtensionStyle) -> Self {
+        self.extension = Some(style);
+        self
+    }
+
+    /// Shows the new names without renaming anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first file that cannot be renamed instead of recording
+    /// it and carrying on
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    fn has_rules(&self) -> bool {
+        !self.replacements.is_empty()
+            || self.case.is_some()
+            || self.pattern.is_some()
+            || self.date_prefix.is_some()
+            || self.extension.is_some()
+    }
+
+    /// Asks which rules to apply, then for the settings of each
+    fn prompt_rules(&mut self) -> Result<()> {
+        let theme = ColorfulTheme::default();
+        let rules = [
+            "Find and replace",
+            "Change case",
+            "Name pattern with a counter",
+            "Date prefix",
+            "Change extensions",
+        ];
+        let selected = MultiSelect::with_theme(&theme)
+            .with_prompt("Renaming rules (space to select)")
+            .items(&rules)
+            .interact()?;
+        if selected.is_empty() {
+            anyhow::bail!("No renaming rule selected");
+        }
+
+        for rule in selected {
+            match rule {
+                0 => {
+                    let find: String = Input::with_theme(&theme).with_prompt("Find").interact_text()?;
+                    let replace: String = Input::with_theme(&theme)
+                        .with_prompt("Replace with")
+                        .allow_empty(true)
+                        .interact_text()?;
+                    let regex = Confirm::with_theme(&theme)
+                        .with_prompt("Treat the text to find as a regular expression?")
+                        .default(false)
+                        .interact()?;
+                    self.replacements.push(if regex {
+                        Replacement::regex(&find, &replace)?
+                    } else {
+                        Replacement::literal(&find, &replace)?
+                    });
+                }
+                1 => {
+                    let cases = [CaseTransform::Lower, CaseTransform::Upper, CaseTransform::Title];
+                    let choice = Select::with_theme(&theme)
+                        .with_prompt("Case")
+                        .items(&["lower case", "UPPER CASE", "Title Case"])
+                        .default(0)
+                        .interact()?;
+                    self.case = Some(cases[choice]);
+                }
+                2 => {
+                    let pattern: String = Input::with_theme(&theme)
+                        .with_prompt("Pattern ({name} is the current name, {n} or {n:04} the counter)")
+                        .default("{name}_{n:03}".to_string())
+                        .interact_text()?;
+                    self.pattern = Some(NamePattern::parse(&pattern)?);
+                    self.start = Input::with_theme(&theme)
+                        .with_prompt("First number")
+                        .default(self.start)
+                        .interact_text()?;
+                }
+                3 => {
+                    let choice = Select::with_theme(&theme)
+                        .with_prompt("Date to use")
+                        .items(&["Modification time", "EXIF capture date, else modification time"])
+                        .default(0)
+                        .interact()?;
+                    let source = if choice == 0 { DateSource::Modified } else { DateSource::Exif };
+                    let format: String = Input::with_theme(&theme)
+                        .with_prompt("Date format")
+                        .default("%Y-%m-%d".to_string())
+                        .interact_text()?;
+                    self.date_prefix = Some(DatePrefix::new(source).with_format(&format)?);
+                }
+                _ => {
+                    let styles = [ExtensionStyle::Lower, ExtensionStyle::Upper, ExtensionStyle::Canonical];
+                    let choice = Select::with_theme(&theme)
+                        .with_prompt("Extensions")
+                        .items(&["lower case", "UPPER CASE", "lower case with aliases merged (jpeg → jpg)"])
+                        .default(0)
+                        .interact()?;
+                    self.extension = Some(styles[choice]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<RenameOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| {
+            anyhow::anyhow!("Input directory not set")
+        })?;
+        Ok(RenameOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            replacements: self.replacements.clone(),
+            case: self.case,
+            pattern: self.pattern.clone(),
+            start: self.start,
+            date_prefix: self.date_prefix.clone(),
+            extension: self.extension,
+            names: self.names,
+            manifest_dir: Some(Journal::default_dir()?),
+            fail_fast: self.fail_fast,
+            ..RenameOptions::new(vec![input_dir])
+        })
+    }
+}
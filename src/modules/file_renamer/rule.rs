@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use regex::{NoExpand, Regex};
+use serde::Serialize;
+use std::{fs::Metadata, path::Path};
+
+use crate::utils::exif;
+
+/// A find/replace applied to every file name stem
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pattern: Regex,
+    replacement: String,
+    /// Take `replacement` as is rather than expanding `$1` and `${name}`
+    literal: bool,
+}
+
+impl Replacement {
+    /// Replaces every occurrence of the substring `find`
+    pub fn literal(find: &str, replacement: &str) -> Result<Self> {
+        if find.is_empty() {
+            anyhow::bail!("Text to find must not be empty");
+        }
+        Ok(Self {
+            pattern: Regex::new(&regex::escape(find))?,
+            replacement: replacement.to_string(),
+            literal: true,
+        })
+    }
+
+    /// Replaces every match of the regular expression `pattern`; the
+    /// replacement may refer to capture groups as `$1` or `${name}`
+    pub fn regex(pattern: &str, replacement: &str) -> Result<Self> {
+        Ok(Self {
+            pattern: Regex::new(pattern).with_context(|| format!("Invalid regex '{}'", pattern))?,
+            replacement: replacement.to_string(),
+            literal: false,
+        })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        if self.literal {
+            self.pattern.replace_all(text, NoExpand(&self.replacement)).into_owned()
+        } else {
+            self.pattern.replace_all(text, self.replacement.as_str()).into_owned()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CaseTransform {
+    Lower,
+    Upper,
+    /// First letter of every word upper case, the rest lower case. Words
+    /// are separated by spaces, `_`, `-`, and `.`.
+    Title,
+}
+
+impl CaseTransform {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::Lower => text.to_lowercase(),
+            Self::Upper => text.to_uppercase(),
+            Self::Title => {
+                let mut title = String::with_capacity(text.len());
+                let mut word_start = true;
+                for c in text.chars() {
+                    if word_start {
+                        title.extend(c.to_uppercase());
+                    } else {
+                        title.extend(c.to_lowercase());
+                    }
+                    word_start = c.is_whitespace() || matches!(c, '_' | '-' | '.');
+                }
+                title
+            }
+        }
+    }
+}
+
+/// A new stem such as `photo_{n:04}`. `{name}` is the stem after the
+/// replacements and case transform, `{n}` a counter, and `{n:04}` the
+/// counter padded with zeros to four digits.
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Name,
+    Sequence { width: usize },
+}
+
+impl NamePattern {
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => anyhow::bail!("Unclosed '{{' in pattern \"{}\"", pattern),
+                            Some(c) => token.push(c),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(match token.as_str() {
+                        "name" => Segment::Name,
+                        "n" => Segment::Sequence { width: 0 },
+                        _ => match token.strip_prefix("n:") {
+                            Some(padding) => Segment::Sequence {
+                                width: padding
+                                    .parse()
+                                    .map_err(|_| anyhow::anyhow!("Invalid counter padding in {{{}}}", token))?,
+                            },
+                            None => anyhow::bail!("Unknown pattern token {{{}}} (expected name or n)", token),
+                        },
+                    });
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in pattern \"{}\"", pattern),
+                '/' | '\\' => anyhow::bail!("Pattern must not contain path separators"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if segments.is_empty() {
+            anyhow::bail!("Pattern must not be empty");
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn render(&self, name: &str, sequence: usize) -> String {
+        let mut rendered = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => rendered.push_str(text),
+                Segment::Name => rendered.push_str(name),
+                Segment::Sequence { width } => rendered.push_str(&format!("{:0width$}", sequence, width = *width)),
+            }
+        }
+        rendered
+    }
+}
+
+/// Where a date prefix is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DateSource {
+    Modified,
+    /// The EXIF capture date, or the modification time for files without
+    /// one
+    Exif,
+}
+
+/// Puts the file's date in front of the name, e.g. `2024-06-01_IMG_1.jpg`
+#[derive(Debug, Clone)]
+pub struct DatePrefix {
+    pub source: DateSource,
+    /// chrono format of the date, `%Y-%m-%d` by default
+    format: String,
+}
+
+impl DatePrefix {
+    pub fn new(source: DateSource) -> Self {
+        Self {
+            source,
+            format: "%Y-%m-%d".to_string(),
+        }
+    }
+
+    /// Formats the date with `format` instead, e.g. `%Y%m%d`
+    pub fn with_format(mut self, format: &str) -> Result<Self> {
+        if format.is_empty() || StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            anyhow::bail!("Invalid date format \"{}\"", format);
+        }
+        if format.contains(['/', '\\']) {
+            anyhow::bail!("Date format must not contain path separators");
+        }
+        self.format = format.to_string();
+        Ok(self)
+    }
+
+    /// The prefix for `path`, separator included, or `None` when the
+    /// date cannot be read
+    pub fn render(&self, path: &Path, metadata: &Metadata) -> Option<String> {
+        let exif = match self.source {
+            DateSource::Exif => exif::capture_date(path),
+            DateSource::Modified => None,
+        };
+        let date = exif.or_else(|| {
+            let modified: DateTime<Local> = metadata.modified().ok()?.into();
+            Some(modified.naive_local())
+        })?;
+        Some(format!("{}_", date.format(&self.format)))
+    }
+}
+
+/// How extensions are rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ExtensionStyle {
+    Lower,
+    Upper,
+    /// Lower case with alias spellings merged, e.g. `JPEG` becomes `jpg`,
+    /// using the same aliases as the categorizer's file type rule
+    Canonical,
+}
@@ -0,0 +1,367 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::utils::prompt::Input;
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    secure_delete::{shred, storage_warnings, Pass, ShredOptions, CAVEAT},
+    walk::{Inaccessible, Walker},
+};
+
+/// What has to be typed to go ahead, as there is no getting the files back
+const CONFIRMATION: &str = "shred";
+
+/// Overwrites files under a directory before deleting them, for data that
+/// must not be recoverable from the disk afterwards
+pub struct FileShredder {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Glob patterns a file's relative path must match to be shredded
+    include: Vec<String>,
+    /// Glob patterns that keep a file
+    exclude: Vec<String>,
+    /// Shred inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    shred: ShredOptions,
+    /// List the files and stop
+    dry_run: bool,
+    /// Abort on the first file that cannot be shredded
+    fail_fast: bool,
+    metrics: Metrics,
+}
+
+/// Everything a shredding run needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct ShredderOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Shred inside working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Passes, truncation, and renaming
+    pub shred: ShredOptions,
+    /// Abort on the first file that cannot be shredded instead of
+    /// recording it and carrying on
+    pub fail_fast: bool,
+}
+
+impl ShredderOptions {
+    /// Every file under `input_dir`, recursively, with the default passes
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            shred: ShredOptions::default(),
+            fail_fast: false,
+        }
+    }
+}
+
+/// Files found by [`plan`], before anything is overwritten
+#[derive(Debug, Default, Serialize)]
+pub struct ShredPlan {
+    /// With their sizes, in name order
+    pub files: Vec<(PathBuf, u64)>,
+    /// Why overwriting may not reach the old contents here, where that
+    /// could be detected
+    pub warnings: Vec<String>,
+    /// Working copies that were not walked into
+    pub repositories: Vec<PathBuf>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl ShredPlan {
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct ShredSummary {
+    pub shredded: Vec<PathBuf>,
+    pub bytes: u64,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be shredded, and may be partly overwritten
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&ShredSummary> for Summary {
+    fn from(summary: &ShredSummary) -> Self {
+        Summary {
+            files: summary.shredded.len(),
+            bytes: summary.bytes,
+            skipped: 0,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Finds the files to shred. Known junk is included rather than skipped,
+/// since thumbnail caches such as `Thumbs.db` hold previews of the files
+/// next to them.
+pub fn plan(options: &ShredderOptions, progress: impl ProgressSink) -> Result<ShredPlan> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    if options.shred.passes.is_empty() {
+        anyhow::bail!("At least one overwrite pass is needed");
+    }
+    progress.scan_started(root);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(None)
+        .sorted(true)
+        .files(root);
+    Ok(ShredPlan {
+        files: walk
+            .files
+            .into_iter()
+            .map(|entry| (entry.path, entry.metadata.len()))
+            .collect(),
+        warnings: storage_warnings(root),
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+    })
+}
+
+/// Overwrites and deletes every file in `plan`, one at a time
+///
+/// ```
+/// use file_organizer_rust::modules::file_shredder::{self, ShredderOptions};
+/// use file_organizer_rust::utils::filter::PathFilter;
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("contract.pdf"), "confidential")?;
+/// std::fs::write(dir.path().join("notes.txt"), "keep me")?;
+///
+/// let options = ShredderOptions {
+///     filter: PathFilter::new(&["*.pdf".to_string()], &[])?,
+///     ..ShredderOptions::new(dir.path().to_path_buf())
+/// };
+/// let plan = file_shredder::plan(&options, ())?;
+/// let summary = file_shredder::apply(&options, plan, ())?;
+/// assert_eq!(summary.shredded, [dir.path().join("contract.pdf")]);
+/// assert!(!dir.path().join("contract.pdf").exists());
+/// assert!(dir.path().join("notes.txt").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &ShredderOptions, plan: ShredPlan, progress: impl ProgressSink) -> Result<ShredSummary> {
+    progress.phase_changed("shredding", plan.files.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut summary = ShredSummary {
+        repositories: plan.repositories,
+        inaccessible: plan.inaccessible,
+        ..ShredSummary::default()
+    };
+    for (path, size) in plan.files {
+        progress.item_started(&path);
+        match errors.check(&path, "shred", shred(&path, &options.shred))? {
+            Some(()) => {
+                progress.item_finished(&path, size, Outcome::Done);
+                summary.bytes += size;
+                summary.shredded.push(path);
+            }
+            None => progress.item_finished(&path, size, Outcome::Failed),
+        }
+    }
+    summary.errors = errors.into_errors();
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for FileShredder {
+    type Plan = ShredPlan;
+    const NAME: &'static str = "shred";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            shred: ShredOptions::default(),
+            dry_run: false,
+            fail_fast: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to securely delete files from")?);
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<ShredPlan> {
+        let spinner = create_spinner("Looking for files to shred...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Lists every file and the storage caveats, then, unless this is a
+    /// dry run, asks for the confirmation word to be typed
+    fn review(&self, plan: &ShredPlan) -> Result<bool> {
+        if plan.files.is_empty() {
+            println!("No files to shred");
+            return Ok(false);
+        }
+        let input_dir = self.input_dir.clone().unwrap_or_default();
+        for (path, size) in &plan.files {
+            println!("  {} ({})", path.strip_prefix(&input_dir).unwrap_or(path).display(), format_size(*size, BINARY));
+        }
+        println!(
+            "{} files ({}), {} overwrite passes: {}",
+            plan.files.len(),
+            format_size(plan.bytes(), BINARY),
+            self.shred.passes.len(),
+            self.shred
+                .passes
+                .iter()
+                .map(|pass| pass.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        println!("Note: {}", CAVEAT);
+        for warning in &plan.warnings {
+            println!("Warning: {}", warning);
+        }
+        if self.dry_run {
+            println!("Dry run: nothing was overwritten or deleted");
+            return Ok(false);
+        }
+
+        let typed: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "These files cannot be recovered. Type \"{}\" to overwrite and delete them",
+                CONFIRMATION
+            ))
+            .allow_empty(true)
+            .interact_text()?;
+        if typed.trim() != CONFIRMATION {
+            println!("Cancelled; nothing was deleted");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn execute(&self, plan: ShredPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Shredding files...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!(
+            "{} files ({}) overwritten and deleted",
+            summary.shredded.len(),
+            format_size(summary.bytes, BINARY)
+        ));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to shred inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "they were not shredded");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl FileShredder {
+    /// Presets the directory to shred files in so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Only shreds files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.pdf" or "clients/acme/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Keeps files whose relative path matches one of `patterns`
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Shreds inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Overwrites with `passes` in order instead of random data then zeros
+    pub fn with_passes(mut self, passes: Vec<Pass>) -> Self {
+        self.shred.passes = passes;
+        self
+    }
+
+    /// Whether files are cut to nothing after the last pass
+    pub fn with_truncate(mut self, truncate: bool) -> Self {
+        self.shred.truncate = truncate;
+        self
+    }
+
+    /// Whether files get a random name before being unlinked
+    pub fn with_rename(mut self, rename: bool) -> Self {
+        self.shred.rename = rename;
+        self
+    }
+
+    /// Lists the files and caveats, then stops
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first file that cannot be shredded
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<ShredderOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        Ok(ShredderOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            shred: self.shred.clone(),
+            fail_fast: self.fail_fast,
+            ..ShredderOptions::new(input_dir)
+        })
+    }
+}
+
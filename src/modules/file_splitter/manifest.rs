@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::atomic::write_atomic;
+
+/// Suffix of the manifest written next to the chunks
+pub const MANIFEST_SUFFIX: &str = ".split.json";
+
+/// What a split wrote, enough to put the file back together and prove it
+/// is the same file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitManifest {
+    /// Name of the original file, without its directory
+    pub file: String,
+    /// Size of the original file in bytes
+    pub size: u64,
+    /// Size of every chunk but the last
+    pub chunk_size: u64,
+    /// SHA-256 of the original file
+    pub sha256: String,
+    pub chunks: Vec<Chunk>,
+}
+
+/// One piece of the original file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    /// File name, in the manifest's directory
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl SplitManifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let manifest: Self =
+            serde_json::from_str(&text).with_context(|| format!("{} is not a split manifest", path.display()))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// `<dir>/<file>.split.json`
+    pub fn path_for(dir: &Path, file: &str) -> PathBuf {
+        dir.join(format!("{}{}", file, MANIFEST_SUFFIX))
+    }
+
+    /// Checks that the manifest describes the whole file and only names
+    /// files in its own directory, so a tampered manifest cannot make a
+    /// join read or write elsewhere
+    fn validate(&self) -> Result<()> {
+        let plain = |name: &str| !name.is_empty() && Path::new(name).file_name().is_some_and(|n| n == name);
+        if !plain(&self.file) {
+            anyhow::bail!("Manifest names an invalid file: {}", self.file);
+        }
+        if let Some(chunk) = self.chunks.iter().find(|chunk| !plain(&chunk.name)) {
+            anyhow::bail!("Manifest names an invalid chunk: {}", chunk.name);
+        }
+        let total: u64 = self.chunks.iter().map(|chunk| chunk.size).sum();
+        if total != self.size {
+            anyhow::bail!("Manifest chunks add up to {} bytes, not the {} of the file", total, self.size);
+        }
+        let last = self.chunks.len().saturating_sub(1);
+        if self.chunks[..last].iter().any(|chunk| chunk.size != self.chunk_size) {
+            anyhow::bail!("Manifest chunks are not all {} bytes", self.chunk_size);
+        }
+        Ok(())
+    }
+}
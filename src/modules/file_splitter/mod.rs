@@ -0,0 +1,664 @@
+mod manifest;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::{Input, Select};
+use crate::modules::base::{FileOrganizer, Summary};
+use crate::utils::{
+    atomic::AtomicFile,
+    create_byte_bar,
+    expand::expand_path,
+    hashing::{Algorithm, Hasher},
+    metrics::Metrics,
+    parse_size,
+    progress::{ByteProgress, Outcome, ProgressSink},
+    space::{preflight, SpaceEstimate},
+};
+
+pub use manifest::{Chunk, SplitManifest, MANIFEST_SUFFIX};
+
+/// Bytes read and written at a time, which bounds the memory a split or
+/// join needs whatever the file size
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Splits one large file into numbered chunks with a manifest of their
+/// SHA-256 digests, and joins them back, checking every chunk first
+pub struct FileSplitter {
+    /// `None` asks whether to split or join
+    job: Option<Job>,
+    /// File to split
+    file: Option<PathBuf>,
+    chunk_size: Option<u64>,
+    /// Where chunks go; defaults to the file's directory
+    output_dir: Option<PathBuf>,
+    /// Manifest of the chunks to join
+    manifest: Option<PathBuf>,
+    /// Where the joined file goes; defaults to its name next to the chunks
+    output: Option<PathBuf>,
+    /// Replace an existing file when joining
+    overwrite: bool,
+    /// Write even when the destination filesystem looks too small
+    force: bool,
+    metrics: Metrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Job {
+    Split,
+    Join,
+}
+
+/// Everything a split needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct SplitOptions {
+    pub file: PathBuf,
+    /// Bytes in every chunk but the last
+    pub chunk_size: u64,
+    /// Where the chunks and manifest go
+    pub output_dir: PathBuf,
+}
+
+impl SplitOptions {
+    /// Splits `file` into `chunk_size` chunks next to it
+    pub fn new(file: PathBuf, chunk_size: u64) -> Self {
+        let output_dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+        Self {
+            file,
+            chunk_size,
+            output_dir,
+        }
+    }
+}
+
+/// Everything a join needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct JoinOptions {
+    /// The `.split.json` manifest; the chunks are read from its directory
+    pub manifest: PathBuf,
+    /// Where the joined file goes; `None` uses its original name in the
+    /// manifest's directory
+    pub output: Option<PathBuf>,
+    /// Replace an existing file at the output path
+    pub overwrite: bool,
+}
+
+/// A split or join, planned
+#[derive(Debug, Clone, Serialize)]
+pub enum SplitPlan {
+    Split {
+        options: SplitOptions,
+        size: u64,
+        chunks: u64,
+        space: Vec<SpaceEstimate>,
+    },
+    Join {
+        options: JoinOptions,
+        manifest: SplitManifest,
+        output: PathBuf,
+        space: Vec<SpaceEstimate>,
+    },
+}
+
+impl SplitPlan {
+    /// Bytes the run reads: the file once to split, the chunks twice to
+    /// join (checked first, then copied)
+    pub fn bytes(&self) -> u64 {
+        match self {
+            Self::Split { size, .. } => *size,
+            Self::Join { manifest, .. } => 2 * manifest.size,
+        }
+    }
+}
+
+/// Outcome of [`split`]
+#[derive(Debug, Default, Serialize)]
+pub struct SplitSummary {
+    pub chunks: Vec<PathBuf>,
+    pub manifest: PathBuf,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Outcome of [`join`]
+#[derive(Debug, Default, Serialize)]
+pub struct JoinSummary {
+    /// The joined file; `None` when chunks were missing or corrupt and
+    /// nothing was written
+    pub output: Option<PathBuf>,
+    pub bytes: u64,
+    /// Chunks the manifest lists that do not exist
+    pub missing: Vec<PathBuf>,
+    /// Chunks with the wrong size or digest
+    pub corrupt: Vec<PathBuf>,
+}
+
+/// Splits `options.file` into chunks of `options.chunk_size` bytes named
+/// `<file>.000`, `<file>.001`, ..., reading it once. Each chunk is hashed
+/// as it is written, and the manifest `<file>.split.json` lists them with
+/// the digest of the whole file. Numbers are padded to the same width, so
+/// the chunks sort in order.
+///
+/// ```
+/// use file_organizer_rust::modules::file_splitter::{self, JoinOptions, SplitOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// let image = dir.path().join("disk.img");
+/// let contents: Vec<u8> = (0..10_000u32).map(|n| (n % 251) as u8).collect();
+/// std::fs::write(&image, &contents)?;
+///
+/// let parts = dir.path().join("parts");
+/// let split = file_splitter::split(
+///     &SplitOptions { output_dir: parts.clone(), ..SplitOptions::new(image, 4096) },
+///     (),
+/// )?;
+/// assert_eq!(split.chunks.len(), 3);
+/// assert!(parts.join("disk.img.002").exists());
+///
+/// let joined = file_splitter::join(
+///     &JoinOptions { manifest: split.manifest, output: None, overwrite: false },
+///     (),
+/// )?;
+/// assert_eq!(std::fs::read(joined.output.unwrap())?, contents);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn split(options: &SplitOptions, progress: impl ProgressSink) -> Result<SplitSummary> {
+    let source = &options.file;
+    let Some(name) = source.file_name().and_then(|name| name.to_str()) else {
+        anyhow::bail!("Cannot split {}: its name is not valid text", source.display());
+    };
+    if options.chunk_size == 0 {
+        anyhow::bail!("Chunk size must be more than zero");
+    }
+    let size = fs::metadata(source)?.len();
+    let count = size.div_ceil(options.chunk_size);
+    let width = count.saturating_sub(1).to_string().len().max(3);
+    fs::create_dir_all(&options.output_dir)?;
+
+    let mut reader = File::open(source)?;
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut whole = Hasher::new(Algorithm::Sha256);
+    let mut summary = SplitSummary::default();
+    let mut chunks = Vec::new();
+    progress.phase_changed("splitting", count);
+    for index in 0..count {
+        let chunk_name = format!("{}.{:0width$}", name, index, width = width);
+        let path = options.output_dir.join(&chunk_name);
+        progress.item_started(&path);
+        let chunk_size = options.chunk_size.min(size - index * options.chunk_size);
+        let mut file = AtomicFile::create(&path)?;
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        let mut written = 0;
+        while written < chunk_size {
+            let limit = (chunk_size - written).min(BUFFER_SIZE as u64) as usize;
+            let read = match reader.read(&mut buffer[..limit]) {
+                Ok(0) => anyhow::bail!("{} shrank while it was split", source.display()),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            file.write_all(&buffer[..read])?;
+            hasher.update(&buffer[..read]);
+            whole.update(&buffer[..read]);
+            written += read as u64;
+            progress.bytes_written(read as u64);
+        }
+        file.commit()?;
+        chunks.push(Chunk {
+            name: chunk_name,
+            size: chunk_size,
+            sha256: hasher.finish(),
+        });
+        summary.chunks.push(path.clone());
+        progress.item_finished(&path, chunk_size, Outcome::Done);
+    }
+    if reader.read(&mut buffer[..1])? != 0 {
+        anyhow::bail!("{} grew while it was split", source.display());
+    }
+
+    let manifest = SplitManifest {
+        file: name.to_string(),
+        size,
+        chunk_size: options.chunk_size,
+        sha256: whole.finish(),
+        chunks,
+    };
+    summary.manifest = SplitManifest::path_for(&options.output_dir, name);
+    manifest.save(&summary.manifest)?;
+    summary.bytes = size;
+    summary.sha256 = manifest.sha256;
+    progress.finished(&Summary {
+        files: summary.chunks.len(),
+        bytes: size,
+        ..Summary::default()
+    });
+    Ok(summary)
+}
+
+/// Joins the chunks a manifest lists back into the original file. Every
+/// chunk is checked against its size and digest first, and if any is
+/// missing or corrupt nothing is written. The chunks are then streamed into
+/// a temporary file that only replaces the output once the whole file's
+/// digest matches.
+pub fn join(options: &JoinOptions, progress: impl ProgressSink) -> Result<JoinSummary> {
+    let manifest = SplitManifest::load(&options.manifest)?;
+    let dir = chunk_dir(&options.manifest);
+    let output = output_path(options, &manifest);
+    if output.exists() && !options.overwrite {
+        anyhow::bail!("{} already exists", output.display());
+    }
+
+    let mut summary = JoinSummary::default();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    progress.phase_changed("verifying", manifest.chunks.len() as u64);
+    for chunk in &manifest.chunks {
+        let path = dir.join(&chunk.name);
+        progress.item_started(&path);
+        let outcome = match fs::metadata(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                summary.missing.push(path.clone());
+                Outcome::Failed
+            }
+            Ok(metadata) if metadata.len() != chunk.size => {
+                summary.corrupt.push(path.clone());
+                Outcome::Failed
+            }
+            Err(e) => return Err(e.into()),
+            Ok(_) => {
+                let mut hasher = Hasher::new(Algorithm::Sha256);
+                copy_chunk(&path, &mut buffer, &progress, |bytes| {
+                    hasher.update(bytes);
+                    Ok(())
+                })?;
+                if hasher.finish() == chunk.sha256 {
+                    Outcome::Done
+                } else {
+                    summary.corrupt.push(path.clone());
+                    Outcome::Failed
+                }
+            }
+        };
+        progress.item_finished(&path, chunk.size, outcome);
+    }
+    if !summary.missing.is_empty() || !summary.corrupt.is_empty() {
+        return Ok(summary);
+    }
+
+    if let Some(parent) = output.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = AtomicFile::create(&output)?;
+    let mut whole = Hasher::new(Algorithm::Sha256);
+    progress.phase_changed("joining", manifest.chunks.len() as u64);
+    for chunk in &manifest.chunks {
+        let path = dir.join(&chunk.name);
+        progress.item_started(&path);
+        copy_chunk(&path, &mut buffer, &progress, |bytes| {
+            whole.update(bytes);
+            file.write_all(bytes)
+        })?;
+        progress.item_finished(&path, chunk.size, Outcome::Done);
+    }
+    // A chunk replaced between checking and copying shows up here
+    if whole.finish() != manifest.sha256 {
+        anyhow::bail!("Joined file does not match the manifest's digest; nothing was written");
+    }
+    file.commit()?;
+
+    summary.output = Some(output);
+    summary.bytes = manifest.size;
+    progress.finished(&Summary {
+        files: 1,
+        bytes: manifest.size,
+        ..Summary::default()
+    });
+    Ok(summary)
+}
+
+/// Directory the chunks of the manifest at `manifest` are in
+fn chunk_dir(manifest: &Path) -> &Path {
+    manifest.parent().unwrap_or(Path::new(""))
+}
+
+fn output_path(options: &JoinOptions, manifest: &SplitManifest) -> PathBuf {
+    options
+        .output
+        .clone()
+        .unwrap_or_else(|| chunk_dir(&options.manifest).join(&manifest.file))
+}
+
+/// Reads the chunk at `path` through `buffer`, handing each piece to
+/// `consume` and reporting it as written
+fn copy_chunk(
+    path: &Path,
+    buffer: &mut [u8],
+    progress: &impl ProgressSink,
+    mut consume: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<()> {
+    let mut reader = File::open(path)?;
+    loop {
+        let read = match reader.read(buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        consume(&buffer[..read])?;
+        progress.bytes_written(read as u64);
+    }
+}
+
+#[async_trait]
+impl FileOrganizer for FileSplitter {
+    type Plan = SplitPlan;
+    const NAME: &'static str = "split-file";
+
+    /// Works on a single file, so `recursive` does not apply
+    fn new(_recursive: bool) -> Self {
+        Self {
+            job: None,
+            file: None,
+            chunk_size: None,
+            output_dir: None,
+            manifest: None,
+            output: None,
+            overwrite: false,
+            force: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.job.is_none() {
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select operation")
+                .items(&["Split a file into chunks", "Join chunks back into a file"])
+                .default(0)
+                .interact()?;
+            self.job = Some(if selection == 0 { Job::Split } else { Job::Join });
+        }
+        match self.job {
+            Some(Job::Split) => {
+                if self.file.is_none() {
+                    let typed: String = Input::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Enter file to split")
+                        .interact_text()?;
+                    self.file = Some(expand_path(typed.trim())?);
+                }
+                if self.chunk_size.is_none() {
+                    let typed: String = Input::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Enter chunk size (e.g., 100MB, 4GB)")
+                        .interact_text()?;
+                    self.chunk_size = Some(parse_size(&typed)?);
+                }
+            }
+            _ => {
+                if self.manifest.is_none() {
+                    let typed: String = Input::with_theme(&ColorfulTheme::default())
+                        .with_prompt(format!("Enter manifest of the chunks (<file>{})", MANIFEST_SUFFIX))
+                        .interact_text()?;
+                    self.manifest = Some(expand_path(typed.trim())?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<SplitPlan> {
+        match self.job {
+            Some(Job::Split) => {
+                let (Some(file), Some(chunk_size)) = (self.file.clone(), self.chunk_size) else {
+                    anyhow::bail!("File and chunk size not set");
+                };
+                if !file.is_file() {
+                    anyhow::bail!("File does not exist: {}", file.display());
+                }
+                let mut options = SplitOptions::new(file, chunk_size);
+                if let Some(dir) = &self.output_dir {
+                    options.output_dir = dir.clone();
+                }
+                let size = fs::metadata(&options.file)?.len();
+                let space = vec![SpaceEstimate::new(&options.output_dir, size)?];
+                Ok(SplitPlan::Split {
+                    chunks: size.div_ceil(chunk_size.max(1)),
+                    size,
+                    space,
+                    options,
+                })
+            }
+            _ => {
+                let manifest = self.manifest.clone().ok_or_else(|| anyhow::anyhow!("Manifest not set"))?;
+                let options = JoinOptions {
+                    manifest,
+                    output: self.output.clone(),
+                    overwrite: self.overwrite,
+                };
+                let manifest = SplitManifest::load(&options.manifest)?;
+                let output = output_path(&options, &manifest);
+                let dir = output.parent().unwrap_or(Path::new(""));
+                let space = vec![SpaceEstimate::new(dir, manifest.size)?];
+                Ok(SplitPlan::Join {
+                    options,
+                    manifest,
+                    output,
+                    space,
+                })
+            }
+        }
+    }
+
+    fn review(&self, plan: &SplitPlan) -> Result<bool> {
+        let space = match plan {
+            SplitPlan::Split {
+                options,
+                size,
+                chunks,
+                space,
+            } => {
+                println!(
+                    "Splitting {} ({}) into {} chunks of {} in {}",
+                    options.file.display(),
+                    format_size(*size, BINARY),
+                    chunks,
+                    format_size(options.chunk_size, BINARY),
+                    options.output_dir.display()
+                );
+                space
+            }
+            SplitPlan::Join {
+                manifest,
+                output,
+                space,
+                ..
+            } => {
+                println!(
+                    "Joining {} chunks into {} ({})",
+                    manifest.chunks.len(),
+                    output.display(),
+                    format_size(manifest.size, BINARY)
+                );
+                space
+            }
+        };
+        preflight(space, self.force)?;
+        Ok(true)
+    }
+
+    fn execute(&self, plan: SplitPlan) -> Result<Summary> {
+        let bar = create_byte_bar(plan.bytes());
+        let progress = (ByteProgress(bar.clone()), &self.metrics);
+        let summary = match plan {
+            SplitPlan::Split { options, .. } => {
+                self.metrics.phase("splitting");
+                let summary = split(&options, progress)?;
+                bar.finish_and_clear();
+                println!(
+                    "Split into {} chunks, manifest written to {}",
+                    summary.chunks.len(),
+                    summary.manifest.display()
+                );
+                println!("SHA-256: {}", summary.sha256);
+                Summary {
+                    files: summary.chunks.len(),
+                    bytes: summary.bytes,
+                    ..Summary::default()
+                }
+            }
+            SplitPlan::Join { options, .. } => {
+                self.metrics.phase("verifying");
+                let summary = join(&options, progress)?;
+                bar.finish_and_clear();
+                for path in &summary.missing {
+                    println!("Missing: {}", path.display());
+                }
+                for path in &summary.corrupt {
+                    println!("Corrupt: {}", path.display());
+                }
+                match &summary.output {
+                    Some(output) => println!("Joined and verified {}", output.display()),
+                    None => println!("Nothing was written, as some chunks are missing or corrupt"),
+                }
+                Summary {
+                    files: usize::from(summary.output.is_some()),
+                    bytes: summary.bytes,
+                    failed: summary.missing.len() + summary.corrupt.len(),
+                    ..Summary::default()
+                }
+            }
+        };
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..summary
+        })
+    }
+}
+
+impl FileSplitter {
+    /// Splits a file, without asking whether to split or join
+    pub fn with_split(mut self) -> Self {
+        self.job = Some(Job::Split);
+        self
+    }
+
+    /// Joins chunks, without asking whether to split or join
+    pub fn with_join(mut self) -> Self {
+        self.job = Some(Job::Join);
+        self
+    }
+
+    /// Presets the file to split so the prompt is skipped
+    pub fn with_file(mut self, file: PathBuf) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Presets the chunk size so the prompt is skipped
+    pub fn with_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Presets the manifest of the chunks to join so the prompt is skipped
+    pub fn with_manifest(mut self, manifest: PathBuf) -> Self {
+        self.manifest = Some(manifest);
+        self
+    }
+
+    /// Writes chunks here instead of next to the file
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    /// Writes the joined file here instead of next to the chunks
+    pub fn with_output(mut self, path: PathBuf) -> Self {
+        self.output = Some(path);
+        self
+    }
+
+    /// Replaces an existing file when joining
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Writes even when the destination filesystem looks too small
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::hashing::same_contents;
+    use std::io::{Seek, SeekFrom};
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn join_options(manifest: PathBuf, output: PathBuf) -> JoinOptions {
+        JoinOptions {
+            manifest,
+            output: Some(output),
+            overwrite: false,
+        }
+    }
+
+    #[test]
+    fn missing_and_corrupt_chunks_are_reported_before_anything_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("disk.img");
+        fs::write(&file, vec![7u8; 10_000]).unwrap();
+        let split = split(&SplitOptions::new(file, 2048), ()).unwrap();
+        assert_eq!(split.chunks.len(), 5);
+
+        fs::remove_file(&split.chunks[1]).unwrap();
+        fs::write(&split.chunks[3], vec![8u8; 2048]).unwrap();
+        let output = dir.path().join("joined.img");
+        let summary = join(&join_options(split.manifest, output.clone()), ()).unwrap();
+        assert_eq!(summary.missing, [split.chunks[1].clone()]);
+        assert_eq!(summary.corrupt, [split.chunks[3].clone()]);
+        assert_eq!(summary.output, None);
+        assert!(!output.exists());
+    }
+
+    /// Writes 640 MiB of chunks and as much again joined, so it only runs
+    /// when asked for with `--ignored`
+    #[test]
+    #[ignore]
+    fn a_large_sparse_file_is_rebuilt_bit_for_bit() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("vm.img");
+        let mut file = File::create(&image).unwrap();
+        file.set_len(640 * MIB).unwrap();
+        // Data in a sea of holes, including across chunk boundaries and in
+        // the last bytes
+        for offset in [0, 64 * MIB - 3, 100 * MIB, 320 * MIB + 1, 640 * MIB - 3] {
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&offset.to_le_bytes()[..3]).unwrap();
+        }
+        drop(file);
+        let size = 640 * MIB;
+
+        let parts = dir.path().join("parts");
+        let options = SplitOptions { output_dir: parts, ..SplitOptions::new(image.clone(), 64 * MIB) };
+        let split = split(&options, ()).unwrap();
+        assert_eq!(split.chunks.len(), 10);
+        assert_eq!(split.bytes, size);
+
+        let output = dir.path().join("joined.img");
+        let joined = join(&join_options(split.manifest, output.clone()), ()).unwrap();
+        assert_eq!(joined.output, Some(output.clone()));
+        assert_eq!(joined.bytes, size);
+        assert!(same_contents(&image, &output).unwrap());
+    }
+}
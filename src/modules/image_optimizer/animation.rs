@@ -0,0 +1,73 @@
+use anyhow::Result;
+use image::{
+    codecs::{gif::GifDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frame, ImageFormat,
+};
+use std::{fs::File, io::BufReader, path::Path};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Leave animated inputs alone and report them
+    Skip,
+    /// Convert only the first frame into a static image
+    FirstFrame,
+    /// Keep the animation when the target format can store it
+    Animate,
+}
+
+/// Returns true when the file holds more than one frame
+pub fn is_animated(file: &Path, format: ImageFormat) -> Result<bool> {
+    let reader = BufReader::new(File::open(file)?);
+    match format {
+        ImageFormat::Gif => Ok(GifDecoder::new(reader)?.into_frames().take(2).count() > 1),
+        ImageFormat::WebP => Ok(WebPDecoder::new(reader)?.has_animation()),
+        _ => Ok(false),
+    }
+}
+
+/// Whether a target format can hold an animation produced by this tool
+pub fn supports_animation(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::WebP)
+}
+
+/// Decodes every frame of an animated GIF or WebP
+pub fn decode_frames(file: &Path, format: ImageFormat) -> Result<Vec<Frame>> {
+    let reader = BufReader::new(File::open(file)?);
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(reader)?.into_frames().collect_frames()?,
+        ImageFormat::WebP => WebPDecoder::new(reader)?.into_frames().collect_frames()?,
+        _ => anyhow::bail!("{:?} inputs cannot be animated", format),
+    };
+    Ok(frames)
+}
+
+/// Encodes decoded frames into an animated WebP, keeping the frame timing
+pub fn encode_animated_webp(frames: &[Frame]) -> Result<Vec<u8>> {
+    let first = frames
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("Animation has no frames"))?;
+    let (width, height) = first.buffer().dimensions();
+
+    let mut config = webp::WebPConfig::new()
+        .map_err(|_| anyhow::anyhow!("Failed to initialize WebP encoder"))?;
+    config.lossless = 1;
+
+    let mut encoder = webp::AnimEncoder::new(width, height, &config);
+    let mut timestamp = 0i32;
+    for frame in frames {
+        let buffer = frame.buffer();
+        encoder.add_frame(webp::AnimFrame::from_rgba(
+            buffer.as_raw(),
+            buffer.width(),
+            buffer.height(),
+            timestamp,
+        ));
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        timestamp += (numer / denom.max(1)) as i32;
+    }
+
+    let data = encoder
+        .try_encode()
+        .map_err(|e| anyhow::anyhow!("Failed to encode animated WebP: {:?}", e))?;
+    Ok(data.to_vec())
+}
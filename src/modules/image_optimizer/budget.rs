@@ -0,0 +1,81 @@
+use anyhow::Result;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+
+use super::encode;
+
+const MIN_QUALITY: u8 = 30;
+const MAX_QUALITY: u8 = 95;
+const SEARCH_STEPS: usize = 4;
+/// Each shrink step keeps this fraction of the width and height
+const SHRINK_FACTOR: f32 = 0.8;
+const MIN_DIMENSION: u32 = 16;
+
+/// An encoding that fits the size budget, with the settings that produced it
+pub struct FittedImage {
+    pub data: Vec<u8>,
+    pub quality: u8,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Whether a target format can be squeezed into a size budget
+pub fn supports_target_size(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Jpeg | ImageFormat::WebP)
+}
+
+/// Encodes `img` at the highest quality that fits in `max_bytes`.
+///
+/// Quality is binary-searched between 30 and 95; when even the lowest
+/// quality is too large, the image is shrunk step by step and searched again.
+pub fn fit_to_size(img: &DynamicImage, format: ImageFormat, max_bytes: u64) -> Result<FittedImage> {
+    let mut current = img.clone();
+
+    loop {
+        if let Some((quality, data)) = search_quality(&current, format, max_bytes)? {
+            return Ok(FittedImage {
+                data,
+                quality,
+                width: current.width(),
+                height: current.height(),
+            });
+        }
+
+        let width = (current.width() as f32 * SHRINK_FACTOR) as u32;
+        let height = (current.height() as f32 * SHRINK_FACTOR) as u32;
+        if width < MIN_DIMENSION || height < MIN_DIMENSION {
+            anyhow::bail!("Cannot fit image into {} bytes", max_bytes);
+        }
+        current = img.resize_exact(width, height, FilterType::Triangle);
+    }
+}
+
+/// Returns the best quality within the budget, or `None` when the floor
+/// quality is still too large
+fn search_quality(img: &DynamicImage, format: ImageFormat, max_bytes: u64) -> Result<Option<(u8, Vec<u8>)>> {
+    let mut low = MIN_QUALITY;
+    let mut high = MAX_QUALITY;
+    let mut best = None;
+
+    for _ in 0..SEARCH_STEPS {
+        if low > high {
+            break;
+        }
+        let quality = low + (high - low) / 2;
+        let data = encode::encode_lossy(img, format, quality)?;
+        if data.len() as u64 <= max_bytes {
+            best = Some((quality, data));
+            low = quality + 1;
+        } else {
+            high = quality.saturating_sub(1);
+        }
+    }
+
+    if best.is_none() {
+        let data = encode::encode_lossy(img, format, MIN_QUALITY)?;
+        if data.len() as u64 <= max_bytes {
+            best = Some((MIN_QUALITY, data));
+        }
+    }
+
+    Ok(best)
+}
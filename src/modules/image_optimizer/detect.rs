@@ -0,0 +1,28 @@
+use image::ImageFormat;
+use std::{fs::File, io::Read, path::Path};
+
+/// Formats the optimizer can decode
+const SUPPORTED_FORMATS: [ImageFormat; 4] = [
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::WebP,
+    ImageFormat::Gif,
+];
+
+/// Identifies a supported image format from the file's magic bytes,
+/// ignoring its extension
+pub fn content_format(file: &Path) -> Option<ImageFormat> {
+    let mut header = [0u8; 16];
+    let mut reader = File::open(file).ok()?;
+    let read = reader.read(&mut header).ok()?;
+
+    image::guess_format(&header[..read])
+        .ok()
+        .filter(|format| SUPPORTED_FORMATS.contains(format))
+}
+
+/// Whether the extension is missing or names a different format than the
+/// content
+pub fn is_mislabeled(file: &Path, content: ImageFormat) -> bool {
+    ImageFormat::from_path(file).map(|format| format != content).unwrap_or(true)
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use humansize::{format_size, BINARY};
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{atomic::AtomicFile, csv_field};
+
+/// Number of files converted into a temporary directory to estimate savings
+pub const SAMPLE_SIZE: usize = 20;
+
+/// A file that would be converted, as listed by a dry run
+pub struct PlannedFile {
+    pub source: PathBuf,
+    pub size: u64,
+    pub dimensions: Option<(u32, u32)>,
+    pub output: PathBuf,
+}
+
+/// Savings measured on a random sample and extrapolated to the whole batch
+pub struct SavingsEstimate {
+    pub sample_size: usize,
+    pub sample_original: u64,
+    pub sample_converted: u64,
+    pub total_original: u64,
+}
+
+impl SavingsEstimate {
+    pub fn estimated_output(&self) -> u64 {
+        if self.sample_original == 0 {
+            return self.total_original;
+        }
+        let ratio = self.sample_converted as f64 / self.sample_original as f64;
+        (self.total_original as f64 * ratio) as u64
+    }
+
+    pub fn print(&self, total_files: usize) {
+        let estimated = self.estimated_output();
+        let saved = self.total_original as i64 - estimated as i64;
+        println!();
+        println!(
+            "Estimated output: {} -> {} ({} {})",
+            format_size(self.total_original, BINARY),
+            format_size(estimated, BINARY),
+            if saved >= 0 { "saving" } else { "growing by" },
+            format_size(saved.unsigned_abs(), BINARY),
+        );
+        println!(
+            "Based on converting {} of {} files; results vary with content, so treat this as a rough guide.",
+            self.sample_size, total_files
+        );
+    }
+}
+
+pub fn print_plan(files: &[PlannedFile]) {
+    println!();
+    println!("{:>12}  {:>11}  Source -> Output", "Size", "Dimensions");
+    for file in files {
+        let dimensions = file
+            .dimensions
+            .map(|(w, h)| format!("{}x{}", w, h))
+            .unwrap_or_else(|| "?".to_string());
+        println!(
+            "{:>12}  {:>11}  {} -> {}",
+            format_size(file.size, BINARY),
+            dimensions,
+            file.source.display(),
+            file.output.display()
+        );
+    }
+    println!("{} files would be converted", files.len());
+}
+
+pub fn write_plan_csv(files: &[PlannedFile], path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(AtomicFile::create(path)?);
+    writeln!(writer, "source,size,width,height,output")?;
+    for file in files {
+        let (width, height) = file
+            .dimensions
+            .map(|(w, h)| (w.to_string(), h.to_string()))
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            csv_field(&file.source.display().to_string()),
+            file.size,
+            width,
+            height,
+            csv_field(&file.output.display().to_string()),
+        )?;
+    }
+    writer.into_inner()?.commit()
+}
@@ -0,0 +1,326 @@
+use anyhow::Result;
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    ColorType, DynamicImage, GrayImage, ImageEncoder, ImageFormat, ImageOutputFormat,
+};
+use std::{collections::HashMap, io::Cursor};
+
+#[derive(Clone, Copy)]
+pub enum CompressionLevel {
+    None,
+    Fast,
+    Balanced,
+    Best,
+}
+
+/// Encoder settings shared by every worker for the duration of a run
+#[derive(Clone, Copy)]
+pub struct EncodeSettings {
+    pub jpeg_quality: u8,
+    pub png_compression: CompressionLevel,
+    pub palette_reduction: bool,
+    /// Bits per sample of grayscale PNGs without alpha; below 8 the
+    /// samples are packed, so 1-bit scans take an eighth of the space
+    pub gray_bit_depth: u8,
+}
+
+impl Default for EncodeSettings {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 85,
+            png_compression: CompressionLevel::Fast,
+            palette_reduction: false,
+            gray_bit_depth: 8,
+        }
+    }
+}
+
+/// Encodes `img` into an in-memory buffer in the target format
+pub fn encode(img: &DynamicImage, format: ImageFormat, settings: &EncodeSettings) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    match format {
+        ImageFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut buffer, settings.jpeg_quality);
+            encoder.write_image(img.as_bytes(), img.width(), img.height(), img.color())?;
+        }
+        ImageFormat::Png => {
+            buffer = encode_png(img, settings)?;
+        }
+        ImageFormat::WebP => {
+            img.write_to(&mut Cursor::new(&mut buffer), ImageOutputFormat::WebP)?;
+        }
+        _ => anyhow::bail!("Unsupported target format: {:?}", format),
+    }
+
+    Ok(buffer)
+}
+
+/// Encodes `img` lossily at the given quality (1-100) for JPEG or WebP
+pub fn encode_lossy(img: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    match format {
+        ImageFormat::Jpeg => {
+            let rgb = img.to_rgb8();
+            let encoder = JpegEncoder::new_with_quality(&mut buffer, quality);
+            encoder.write_image(rgb.as_raw(), rgb.width(), rgb.height(), ColorType::Rgb8)?;
+        }
+        ImageFormat::WebP => {
+            // The WebP encoder only accepts 8-bit RGB and RGBA buffers
+            let source = if img.color().has_alpha() {
+                DynamicImage::ImageRgba8(img.to_rgba8())
+            } else {
+                DynamicImage::ImageRgb8(img.to_rgb8())
+            };
+            let encoder = webp::Encoder::from_image(&source)
+                .map_err(|e| anyhow::anyhow!("Failed to initialize WebP encoder: {}", e))?;
+            buffer = encoder.encode(quality as f32).to_vec();
+        }
+        _ => anyhow::bail!("{:?} does not support lossy encoding", format),
+    }
+
+    Ok(buffer)
+}
+
+/// Whether `img` is written with fewer than 8 bits per sample when
+/// `settings` ask for it: only grayscale PNGs without alpha can be
+pub fn packs_gray(img: &DynamicImage, format: ImageFormat) -> bool {
+    format == ImageFormat::Png && matches!(img, DynamicImage::ImageLuma8(_))
+}
+
+fn encode_png(img: &DynamicImage, settings: &EncodeSettings) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    let indexed = match img {
+        DynamicImage::ImageLuma8(gray) if settings.gray_bit_depth < 8 => {
+            Some(encode_gray_png(gray, settings.gray_bit_depth, settings.png_compression)?)
+        }
+        _ if settings.palette_reduction => encode_indexed_png(img, settings.png_compression)?,
+        _ => None,
+    };
+
+    match indexed {
+        Some(data) => buffer = data,
+        None => {
+            let (compression, filter) = match settings.png_compression {
+                CompressionLevel::None => (CompressionType::Fast, FilterType::NoFilter),
+                CompressionLevel::Fast => (CompressionType::Fast, FilterType::Sub),
+                CompressionLevel::Balanced => (CompressionType::Default, FilterType::Adaptive),
+                CompressionLevel::Best => (CompressionType::Best, FilterType::Adaptive),
+            };
+            let encoder = PngEncoder::new_with_quality(&mut buffer, compression, filter);
+            encoder.write_image(img.as_bytes(), img.width(), img.height(), img.color())?;
+        }
+    }
+
+    #[cfg(feature = "oxipng")]
+    if matches!(settings.png_compression, CompressionLevel::Best) {
+        buffer = oxipng::optimize_from_memory(&buffer, &oxipng::Options::from_preset(4))?;
+    }
+
+    Ok(buffer)
+}
+
+/// Writes an indexed PNG when the image has at most 256 distinct colors.
+///
+/// Returns `None` when the image has too many colors for a palette.
+fn encode_indexed_png(img: &DynamicImage, level: CompressionLevel) -> Result<Option<Vec<u8>>> {
+    let rgba = img.to_rgba8();
+    let mut palette: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut entries: Vec<[u8; 4]> = Vec::new();
+    let mut indices = Vec::with_capacity((rgba.width() * rgba.height()) as usize);
+
+    for pixel in rgba.pixels() {
+        let index = match palette.get(&pixel.0) {
+            Some(&index) => index,
+            None => {
+                if entries.len() == 256 {
+                    return Ok(None);
+                }
+                let index = entries.len() as u8;
+                palette.insert(pixel.0, index);
+                entries.push(pixel.0);
+                index
+            }
+        };
+        indices.push(index);
+    }
+
+    let bit_depth = match entries.len() {
+        0..=2 => png::BitDepth::One,
+        3..=4 => png::BitDepth::Two,
+        5..=16 => png::BitDepth::Four,
+        _ => png::BitDepth::Eight,
+    };
+    let data = pack_samples(&indices, rgba.width(), bit_depth as usize);
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, rgba.width(), rgba.height());
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(bit_depth);
+        encoder.set_palette(entries.iter().flat_map(|c| [c[0], c[1], c[2]]).collect::<Vec<_>>());
+        if entries.iter().any(|c| c[3] != 255) {
+            encoder.set_trns(entries.iter().map(|c| c[3]).collect::<Vec<_>>());
+        }
+        encoder.set_compression(png_compression(level));
+        encoder.set_filter(png::FilterType::NoFilter);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+    }
+
+    Ok(Some(buffer))
+}
+
+/// Writes a grayscale PNG at `bits` (1, 2, or 4) per sample. The samples
+/// are expected to be quantized to that many levels already, as the
+/// grayscale color mode does; anything between levels is rounded.
+fn encode_gray_png(gray: &GrayImage, bits: u8, level: CompressionLevel) -> Result<Vec<u8>> {
+    let bit_depth = match bits {
+        1 => png::BitDepth::One,
+        2 => png::BitDepth::Two,
+        4 => png::BitDepth::Four,
+        _ => anyhow::bail!("Unsupported grayscale bit depth: {}", bits),
+    };
+    let max_level = (1u16 << bits) - 1;
+    let levels: Vec<u8> = gray
+        .as_raw()
+        .iter()
+        .map(|&value| ((value as u16 * max_level + 127) / 255) as u8)
+        .collect();
+    let data = pack_samples(&levels, gray.width(), bits as usize);
+
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, gray.width(), gray.height());
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(bit_depth);
+        encoder.set_compression(png_compression(level));
+        encoder.set_filter(png::FilterType::NoFilter);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+    }
+    Ok(buffer)
+}
+
+/// Packs samples of `bits` each into scanlines `width` samples wide, the
+/// layout PNG uses below 8 bits per sample
+fn pack_samples(samples: &[u8], width: u32, bits: usize) -> Vec<u8> {
+    let width = width as usize;
+    let row_bytes = (width * bits).div_ceil(8);
+    let rows = samples.len().checked_div(width).unwrap_or(0);
+    let mut data = vec![0u8; row_bytes * rows];
+    for (row, row_samples) in samples.chunks(width).enumerate() {
+        let line = &mut data[row * row_bytes..(row + 1) * row_bytes];
+        for (col, &sample) in row_samples.iter().enumerate() {
+            let bit_offset = col * bits;
+            line[bit_offset / 8] |= sample << (8 - bits - bit_offset % 8);
+        }
+    }
+    data
+}
+
+fn png_compression(level: CompressionLevel) -> png::Compression {
+    match level {
+        CompressionLevel::None | CompressionLevel::Fast => png::Compression::Fast,
+        CompressionLevel::Balanced => png::Compression::Default,
+        CompressionLevel::Best => png::Compression::Best,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, RgbImage};
+
+    /// Smooth gradients with sensor-like noise, like a downscaled photo
+    fn photographic() -> DynamicImage {
+        let mut seed = 0x2545_f491_u32;
+        DynamicImage::ImageRgb8(RgbImage::from_fn(256, 192, |x, y| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let noise = (seed % 7) as u8;
+            Rgb([(x as u8).saturating_add(noise), ((x + y) / 2) as u8, (y as u8).saturating_add(noise)])
+        }))
+    }
+
+    /// A handful of solid blocks, like a chart or screenshot
+    fn flat_color() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(256, 192, |x, y| match (x / 64, y / 64) {
+            (0, _) => Rgb([230, 57, 70]),
+            (1, 0) | (1, 1) => Rgb([29, 53, 87]),
+            (2, _) => Rgb([241, 250, 238]),
+            _ => Rgb([69, 123, 157]),
+        }))
+    }
+
+    fn png_size(img: &DynamicImage, png_compression: CompressionLevel, palette_reduction: bool) -> usize {
+        let settings = EncodeSettings { png_compression, palette_reduction, ..EncodeSettings::default() };
+        encode(img, ImageFormat::Png, &settings).unwrap().len()
+    }
+
+    #[test]
+    fn best_png_is_smaller_than_fast_for_photos() {
+        let photo = photographic();
+        assert!(png_size(&photo, CompressionLevel::Best, false) < png_size(&photo, CompressionLevel::Fast, false));
+    }
+
+    #[test]
+    fn best_png_is_smaller_than_fast_for_flat_colors() {
+        let flat = flat_color();
+        let fast = png_size(&flat, CompressionLevel::Fast, false);
+        assert!(png_size(&flat, CompressionLevel::Best, false) < fast);
+        assert!(png_size(&flat, CompressionLevel::Best, true) < fast);
+    }
+
+    #[test]
+    fn palette_reduction_keeps_every_pixel() {
+        let flat = flat_color();
+        let settings = EncodeSettings {
+            png_compression: CompressionLevel::Best,
+            palette_reduction: true,
+            ..EncodeSettings::default()
+        };
+        let decoded = image::load_from_memory(&encode(&flat, ImageFormat::Png, &settings).unwrap()).unwrap();
+        assert_eq!(decoded.to_rgb8(), flat.to_rgb8());
+    }
+
+    #[test]
+    fn palette_reduction_skips_images_with_many_colors() {
+        assert!(encode_indexed_png(&photographic(), CompressionLevel::Best).unwrap().is_none());
+    }
+
+    fn gray_png(bits: u8) -> Vec<u8> {
+        // Quantized the way the grayscale color mode leaves it
+        let levels = (1u32 << bits) - 1;
+        let gray = GrayImage::from_fn(64, 64, |x, _| Luma([((x * levels / 63) * 255 / levels) as u8]));
+        let settings = EncodeSettings { gray_bit_depth: bits, ..EncodeSettings::default() };
+        encode(&DynamicImage::ImageLuma8(gray), ImageFormat::Png, &settings).unwrap()
+    }
+
+    #[test]
+    fn gray_png_is_stored_at_the_chosen_bit_depth() {
+        for (bits, depth) in [(1, png::BitDepth::One), (2, png::BitDepth::Two), (4, png::BitDepth::Four)] {
+            let data = gray_png(bits);
+            let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+            assert_eq!(reader.info().bit_depth, depth);
+            assert_eq!(reader.info().color_type, png::ColorType::Grayscale);
+        }
+        assert!(gray_png(1).len() < gray_png(8).len());
+    }
+
+    #[test]
+    fn packed_gray_png_decodes_to_the_same_levels() {
+        let gray = GrayImage::from_fn(13, 5, |x, y| Luma([if (x + y) % 3 == 0 { 255 } else { 0 }]));
+        let settings = EncodeSettings { gray_bit_depth: 1, ..EncodeSettings::default() };
+        let data = encode(&DynamicImage::ImageLuma8(gray.clone()), ImageFormat::Png, &settings).unwrap();
+        assert_eq!(image::load_from_memory(&data).unwrap().to_luma8(), gray);
+    }
+}
@@ -0,0 +1,73 @@
+use image::{io::Reader, GenericImageView};
+use std::{fs, path::Path};
+
+/// Size and dimension limits a file must meet to be converted. Every limit
+/// that is set has to pass; unset limits are ignored.
+#[derive(Clone, Default)]
+pub struct CandidateFilter {
+    pub min_file_size: Option<u64>,
+    pub max_file_size: Option<u64>,
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+}
+
+impl CandidateFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_file_size.is_none()
+            && self.max_file_size.is_none()
+            && self.min_width.is_none()
+            && self.min_height.is_none()
+    }
+
+    /// Checks the file against the limits. Sizes come from metadata and
+    /// dimensions from the image header, so no pixels are decoded unless
+    /// the header cannot be read.
+    pub fn matches(&self, file: &Path) -> bool {
+        if self.min_file_size.is_some() || self.max_file_size.is_some() {
+            let size = match fs::metadata(file) {
+                Ok(metadata) => metadata.len(),
+                // Leave unreadable files to the converter so they get reported
+                Err(_) => return true,
+            };
+            if self.min_file_size.is_some_and(|min| size < min)
+                || self.max_file_size.is_some_and(|max| size > max)
+            {
+                return false;
+            }
+        }
+
+        if self.min_width.is_some() || self.min_height.is_some() {
+            let (width, height) = match read_dimensions(file) {
+                Some(dimensions) => dimensions,
+                None => return true,
+            };
+            if self.min_width.is_some_and(|min| width < min)
+                || self.min_height.is_some_and(|min| height < min)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Reads dimensions from the header, falling back to a full decode for
+/// files whose header the fast path cannot parse
+fn read_dimensions(file: &Path) -> Option<(u32, u32)> {
+    header_dimensions(file).or_else(|| {
+        Reader::open(file)
+            .and_then(|reader| reader.with_guessed_format())
+            .ok()
+            .and_then(|reader| reader.decode().ok())
+            .map(|img| img.dimensions())
+    })
+}
+
+/// Reads dimensions from the image header without decoding any pixels
+pub fn header_dimensions(file: &Path) -> Option<(u32, u32)> {
+    Reader::open(file)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+}
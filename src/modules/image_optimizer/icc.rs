@@ -0,0 +1,529 @@
+use anyhow::Result;
+use flate2::{write::ZlibEncoder, Compression, Crc};
+use image::{
+    codecs::{jpeg::JpegDecoder, png::PngDecoder},
+    DynamicImage, ImageDecoder, ImageFormat,
+};
+use std::{
+    fs::{self, File},
+    io::{BufReader, Write},
+    path::Path,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IccMode {
+    /// Copy the source profile into the output unchanged
+    Preserve,
+    /// Convert pixels from the source profile to sRGB and drop the profile
+    NormalizeSrgb,
+}
+
+/// Reads the embedded ICC profile of a JPEG (APP2), PNG (iCCP) or WebP (ICCP)
+pub fn read_profile(file: &Path, format: ImageFormat) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => {
+            let reader = BufReader::new(File::open(file).ok()?);
+            JpegDecoder::new(reader).ok()?.icc_profile()
+        }
+        ImageFormat::Png => {
+            let reader = BufReader::new(File::open(file).ok()?);
+            PngDecoder::new(reader).ok()?.icc_profile()
+        }
+        ImageFormat::WebP => {
+            let data = fs::read(file).ok()?;
+            riff_chunks(&data)?
+                .into_iter()
+                .find(|(id, _)| id == b"ICCP")
+                .map(|(_, body)| body.to_vec())
+        }
+        _ => None,
+    }
+}
+
+/// Whether the encoded target format can carry an ICC profile
+pub fn supports_embedding(format: ImageFormat) -> bool {
+    matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)
+}
+
+/// Inserts `profile` into already-encoded image bytes
+pub fn embed(data: Vec<u8>, format: ImageFormat, profile: &[u8], width: u32, height: u32, alpha: bool) -> Result<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => embed_jpeg(data, profile),
+        ImageFormat::Png => {
+            let mut body = b"ICC Profile\0\0".to_vec();
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(profile)?;
+            body.extend(encoder.finish()?);
+            insert_png_chunk(data, b"iCCP", &body)
+        }
+        ImageFormat::WebP => embed_webp(data, profile, width, height, alpha),
+        _ => anyhow::bail!("{:?} cannot carry an ICC profile", format),
+    }
+}
+
+/// Marks a PNG as sRGB with the perceptual rendering intent
+pub fn tag_png_srgb(data: Vec<u8>) -> Result<Vec<u8>> {
+    insert_png_chunk(data, b"sRGB", &[0])
+}
+
+fn embed_jpeg(data: Vec<u8>, profile: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 2 || data[..2] != [0xFF, 0xD8] {
+        anyhow::bail!("Output is not a JPEG");
+    }
+
+    // Profiles larger than one segment are split into numbered APP2 chunks
+    const CHUNK: usize = 65519;
+    let chunks: Vec<&[u8]> = profile.chunks(CHUNK).collect();
+    if chunks.len() > 255 {
+        anyhow::bail!("ICC profile is too large for JPEG");
+    }
+
+    let mut segments = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let length = (2 + 14 + chunk.len()) as u16;
+        segments.extend([0xFF, 0xE2]);
+        segments.extend(length.to_be_bytes());
+        segments.extend(b"ICC_PROFILE\0");
+        segments.push(index as u8 + 1);
+        segments.push(chunks.len() as u8);
+        segments.extend(*chunk);
+    }
+
+    // Keep a leading JFIF APP0 segment first, as readers expect
+    let mut insert_at = 2;
+    if data.len() > 4 && data[2..4] == [0xFF, 0xE0] {
+        insert_at = 4 + u16::from_be_bytes([data[4], data[5]]) as usize;
+    }
+
+    let mut output = Vec::with_capacity(data.len() + segments.len());
+    output.extend(&data[..insert_at]);
+    output.extend(segments);
+    output.extend(&data[insert_at..]);
+    Ok(output)
+}
+
+/// Inserts a chunk right after IHDR, where iCCP and sRGB have to appear
+fn insert_png_chunk(data: Vec<u8>, kind: &[u8; 4], body: &[u8]) -> Result<Vec<u8>> {
+    const IHDR_END: usize = 8 + 8 + 13 + 4;
+    if data.len() < IHDR_END || &data[12..16] != b"IHDR" {
+        anyhow::bail!("Output is not a PNG");
+    }
+
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(body);
+
+    let mut output = Vec::with_capacity(data.len() + body.len() + 12);
+    output.extend(&data[..IHDR_END]);
+    output.extend((body.len() as u32).to_be_bytes());
+    output.extend(kind);
+    output.extend(body);
+    output.extend(crc.sum().to_be_bytes());
+    output.extend(&data[IHDR_END..]);
+    Ok(output)
+}
+
+fn embed_webp(data: Vec<u8>, profile: &[u8], width: u32, height: u32, alpha: bool) -> Result<Vec<u8>> {
+    let chunks = riff_chunks(&data).ok_or_else(|| anyhow::anyhow!("Output is not a WebP"))?;
+
+    let mut body = Vec::new();
+    match chunks.first().map(|(id, body)| (*id, *body)) {
+        // Extended files already have a header; flag the profile in it
+        Some((id, header)) if &id == b"VP8X" => {
+            let mut header = header.to_vec();
+            header[0] |= 0x20;
+            push_chunk(&mut body, b"VP8X", &header);
+            push_chunk(&mut body, b"ICCP", profile);
+            for (id, chunk) in &chunks[1..] {
+                push_chunk(&mut body, id, chunk);
+            }
+        }
+        // Simple files need a VP8X header before they may carry a profile
+        Some(_) => {
+            let mut header = vec![0x20 | if alpha { 0x10 } else { 0 }, 0, 0, 0];
+            header.extend(&(width - 1).to_le_bytes()[..3]);
+            header.extend(&(height - 1).to_le_bytes()[..3]);
+            push_chunk(&mut body, b"VP8X", &header);
+            push_chunk(&mut body, b"ICCP", profile);
+            for (id, chunk) in &chunks {
+                push_chunk(&mut body, id, chunk);
+            }
+        }
+        None => anyhow::bail!("WebP output has no image data"),
+    }
+
+    let mut output = Vec::with_capacity(body.len() + 12);
+    output.extend(b"RIFF");
+    output.extend(((body.len() + 4) as u32).to_le_bytes());
+    output.extend(b"WEBP");
+    output.extend(body);
+    Ok(output)
+}
+
+/// Splits a RIFF/WEBP file into its top-level chunks
+fn riff_chunks(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id: [u8; 4] = data[offset..offset + 4].try_into().ok()?;
+        let size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = data.get(offset + 8..offset + 8 + size)?;
+        chunks.push((id, body));
+        offset += 8 + size + size % 2;
+    }
+    Some(chunks)
+}
+
+fn push_chunk(output: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    output.extend(id);
+    output.extend((body.len() as u32).to_le_bytes());
+    output.extend(body);
+    if body.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// Converts RGB pixels from a matrix/TRC profile (Display P3, Adobe RGB
+/// and most camera profiles) to sRGB.
+///
+/// Returns `None` when the profile is not an RGB matrix/TRC profile, in
+/// which case the caller should keep the profile instead.
+pub fn convert_to_srgb(img: &DynamicImage, profile: &[u8]) -> Option<DynamicImage> {
+    let source = MatrixProfile::parse(profile)?;
+
+    // Source RGB -> XYZ (D50) -> linear sRGB
+    let matrix: [[f32; 3]; 3] = std::array::from_fn(|row| {
+        std::array::from_fn(|col| {
+            (0..3).map(|k| XYZ_D50_TO_SRGB[row][k] * source.to_xyz[k][col]).sum()
+        })
+    });
+
+    let curves: Vec<[f32; 256]> = source
+        .curves
+        .iter()
+        .map(|curve| std::array::from_fn(|i| curve.eval(i as f32 / 255.0)))
+        .collect();
+    let encode: Vec<u8> = (0..4096)
+        .map(|i| (srgb_encode(i as f32 / 4095.0) * 255.0).round() as u8)
+        .collect();
+
+    let convert = |pixel: &mut [u8]| {
+        let linear = [
+            curves[0][pixel[0] as usize],
+            curves[1][pixel[1] as usize],
+            curves[2][pixel[2] as usize],
+        ];
+        for (channel, row) in matrix.iter().enumerate() {
+            let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+            pixel[channel] = encode[(value.clamp(0.0, 1.0) * 4095.0).round() as usize];
+        }
+    };
+
+    if img.color().has_alpha() {
+        let mut rgba = img.to_rgba8();
+        rgba.pixels_mut().for_each(|p| convert(&mut p.0));
+        Some(DynamicImage::ImageRgba8(rgba))
+    } else {
+        let mut rgb = img.to_rgb8();
+        rgb.pixels_mut().for_each(|p| convert(&mut p.0));
+        Some(DynamicImage::ImageRgb8(rgb))
+    }
+}
+
+/// Inverse of the sRGB primaries adapted to D50, the ICC connection space
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+struct MatrixProfile {
+    /// Columns are the red, green and blue colorants in XYZ (D50)
+    to_xyz: [[f32; 3]; 3],
+    curves: [Curve; 3],
+}
+
+enum Curve {
+    Table(Vec<f32>),
+    /// ICC parametric curve: g, a, b, c, d, e, f
+    Parametric([f32; 7]),
+}
+
+impl MatrixProfile {
+    fn parse(profile: &[u8]) -> Option<Self> {
+        if profile.get(16..20)? != b"RGB " {
+            return None;
+        }
+
+        let count = read_u32(profile, 128)? as usize;
+        let mut tags = Vec::with_capacity(count);
+        for index in 0..count {
+            let entry = 132 + index * 12;
+            let signature = profile.get(entry..entry + 4)?;
+            let offset = read_u32(profile, entry + 4)? as usize;
+            let size = read_u32(profile, entry + 8)? as usize;
+            tags.push((signature, profile.get(offset..offset + size)?));
+        }
+        let tag = |name: &[u8]| tags.iter().find(|(sig, _)| *sig == name).map(|(_, data)| *data);
+
+        let mut to_xyz = [[0f32; 3]; 3];
+        for (col, name) in [b"rXYZ", b"gXYZ", b"bXYZ"].iter().enumerate() {
+            let data = tag(*name)?;
+            if data.get(..4)? != b"XYZ " {
+                return None;
+            }
+            for (row, xyz_row) in to_xyz.iter_mut().enumerate() {
+                xyz_row[col] = read_s15_fixed16(data, 8 + row * 4)?;
+            }
+        }
+
+        let curves = [
+            Curve::parse(tag(b"rTRC")?)?,
+            Curve::parse(tag(b"gTRC")?)?,
+            Curve::parse(tag(b"bTRC")?)?,
+        ];
+
+        Some(Self { to_xyz, curves })
+    }
+}
+
+impl Curve {
+    fn parse(data: &[u8]) -> Option<Self> {
+        match data.get(..4)? {
+            b"curv" => {
+                let count = read_u32(data, 8)? as usize;
+                match count {
+                    0 => Some(Curve::Parametric([1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+                    1 => {
+                        let gamma = u16::from_be_bytes(data.get(12..14)?.try_into().ok()?) as f32 / 256.0;
+                        Some(Curve::Parametric([gamma, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]))
+                    }
+                    _ => {
+                        let table = (0..count)
+                            .map(|i| {
+                                let at = 12 + i * 2;
+                                data.get(at..at + 2)
+                                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.0)
+                            })
+                            .collect::<Option<Vec<f32>>>()?;
+                        Some(Curve::Table(table))
+                    }
+                }
+            }
+            b"para" => {
+                let function = u16::from_be_bytes(data.get(8..10)?.try_into().ok()?);
+                let param_count = match function {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => return None,
+                };
+                let mut values = Vec::with_capacity(param_count);
+                for i in 0..param_count {
+                    values.push(read_s15_fixed16(data, 12 + i * 4)?);
+                }
+                // Normalize every function type to g, a, b, c, d, e, f
+                let params = match function {
+                    0 => [values[0], 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                    1 => {
+                        let (g, a, b) = (values[0], values[1], values[2]);
+                        [g, a, b, 0.0, -b / a, 0.0, 0.0]
+                    }
+                    2 => {
+                        let (g, a, b, c) = (values[0], values[1], values[2], values[3]);
+                        [g, a, b, 0.0, -b / a, c, c]
+                    }
+                    3 => [values[0], values[1], values[2], values[3], values[4], 0.0, 0.0],
+                    _ => [values[0], values[1], values[2], values[3], values[4], values[5], values[6]],
+                };
+                Some(Curve::Parametric(params))
+            }
+            _ => None,
+        }
+    }
+
+    /// Maps an encoded value in 0..=1 to linear light
+    fn eval(&self, x: f32) -> f32 {
+        match self {
+            Curve::Table(table) => {
+                let position = x * (table.len() - 1) as f32;
+                let index = position.floor() as usize;
+                let next = (index + 1).min(table.len() - 1);
+                let fraction = position - index as f32;
+                table[index] * (1.0 - fraction) + table[next] * fraction
+            }
+            Curve::Parametric([g, a, b, c, d, e, f]) => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_s15_fixed16(data: &[u8], offset: usize) -> Option<f32> {
+    Some(i32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as f32 / 65536.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{
+        codecs::{jpeg::JpegEncoder, png::PngEncoder},
+        ImageEncoder, Rgb, RgbImage,
+    };
+
+    /// sRGB's transfer function as an ICC parametric curve (function 3)
+    const SRGB_CURVE: [f32; 5] = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+
+    /// Colorants of sRGB and Display P3, adapted to D50, as the red, green
+    /// and blue XYZ columns of a profile
+    const SRGB_COLORANTS: [[f32; 3]; 3] =
+        [[0.4361, 0.2225, 0.0139], [0.3851, 0.7169, 0.0971], [0.1431, 0.0606, 0.7141]];
+    const P3_COLORANTS: [[f32; 3]; 3] =
+        [[0.5151, 0.2412, -0.0011], [0.2920, 0.6922, 0.0419], [0.1571, 0.0666, 0.7841]];
+
+    fn fixed(value: f32) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    /// A minimal matrix/TRC display profile with the given colorants and
+    /// sRGB's curve on every channel
+    fn matrix_profile(colorants: [[f32; 3]; 3]) -> Vec<u8> {
+        let mut tags: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+        for (name, xyz) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().zip(colorants) {
+            let mut data = b"XYZ \0\0\0\0".to_vec();
+            xyz.iter().for_each(|value| data.extend(fixed(*value)));
+            tags.push((name, data));
+        }
+        for name in [b"rTRC", b"gTRC", b"bTRC"] {
+            let mut data = b"para\0\0\0\0\0\x03\0\0".to_vec();
+            SRGB_CURVE.iter().for_each(|value| data.extend(fixed(*value)));
+            tags.push((name, data));
+        }
+
+        let mut profile = vec![0; 128];
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        profile.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 128 + 4 + 12 * tags.len();
+        for (name, data) in &tags {
+            profile.extend(*name);
+            profile.extend((offset as u32).to_be_bytes());
+            profile.extend((data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        tags.iter().for_each(|(_, data)| profile.extend(data));
+        let size = profile.len() as u32;
+        profile[..4].copy_from_slice(&size.to_be_bytes());
+        profile
+    }
+
+    fn encoded(img: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        match format {
+            ImageFormat::Png => PngEncoder::new(&mut data)
+                .write_image(img.as_raw(), img.width(), img.height(), image::ColorType::Rgb8)
+                .unwrap(),
+            ImageFormat::Jpeg => JpegEncoder::new(&mut data).encode_image(img).unwrap(),
+            ImageFormat::WebP => {
+                data = webp::Encoder::from_rgb(img.as_raw(), img.width(), img.height()).encode(90.0).to_vec()
+            }
+            _ => unreachable!(),
+        }
+        data
+    }
+
+    fn round_trip(format: ImageFormat, profile: &[u8]) -> Option<Vec<u8>> {
+        let img = RgbImage::from_pixel(8, 8, Rgb([200, 50, 50]));
+        let data = embed(encoded(&img, format), format, profile, 8, 8, false).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("image");
+        fs::write(&file, data).unwrap();
+        read_profile(&file, format)
+    }
+
+    #[test]
+    fn embedded_profiles_round_trip_unchanged() {
+        let profile = matrix_profile(P3_COLORANTS);
+        for format in [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP] {
+            assert_eq!(round_trip(format, &profile).as_deref(), Some(&profile[..]), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn profiles_larger_than_a_jpeg_segment_are_split_and_joined() {
+        let mut profile = matrix_profile(P3_COLORANTS);
+        profile.resize(150_000, 0xAB);
+        assert_eq!(round_trip(ImageFormat::Jpeg, &profile), Some(profile));
+    }
+
+    #[test]
+    fn tagged_pngs_carry_an_srgb_chunk() {
+        let img = RgbImage::from_pixel(2, 2, Rgb([1, 2, 3]));
+        let data = tag_png_srgb(encoded(&img, ImageFormat::Png)).unwrap();
+        assert_eq!(&data[37..41], b"sRGB");
+        assert!(image::load_from_memory(&data).is_ok());
+        assert!(tag_png_srgb(b"not a png".to_vec()).is_err());
+    }
+
+    #[test]
+    fn an_srgb_profile_leaves_pixels_alone() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| Rgb([x as u8 * 16, y as u8 * 16, 128])));
+        let converted = convert_to_srgb(&img, &matrix_profile(SRGB_COLORANTS)).unwrap();
+        for (before, after) in img.to_rgb8().pixels().zip(converted.to_rgb8().pixels()) {
+            for channel in 0..3 {
+                assert!(before[channel].abs_diff(after[channel]) <= 1, "{:?} became {:?}", before, after);
+            }
+        }
+    }
+
+    #[test]
+    fn display_p3_colors_are_mapped_into_srgb() {
+        let profile = matrix_profile(P3_COLORANTS);
+        let convert = |rgb: [u8; 3]| {
+            let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(rgb)));
+            convert_to_srgb(&img, &profile).unwrap().to_rgb8().get_pixel(0, 0).0
+        };
+
+        // Both spaces share the D65 white, so grays stay gray
+        let gray = convert([128, 128, 128]);
+        assert!(gray.iter().all(|channel| channel.abs_diff(128) <= 1), "{:?}", gray);
+        // The wider P3 red is more saturated once written as sRGB
+        let [r, g, b] = convert([200, 50, 50]);
+        assert!(r > 200 && g < 50 && b < 50, "{:?}", [r, g, b]);
+        // Pure P3 red lies outside sRGB and is clipped to its red
+        assert_eq!(convert([255, 0, 0]), [255, 0, 0]);
+    }
+
+    #[test]
+    fn profiles_other_than_rgb_matrices_are_not_converted() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(1, 1));
+        let mut cmyk = matrix_profile(P3_COLORANTS);
+        cmyk[16..20].copy_from_slice(b"CMYK");
+        assert!(convert_to_srgb(&img, &cmyk).is_none());
+        assert!(convert_to_srgb(&img, b"truncated").is_none());
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{Context, Result};
+use image::{DynamicImage, RgbImage};
+use jpeg_decoder::{Decoder, PixelFormat};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// Coding details from a JPEG frame header (SOFn marker)
+pub struct JpegInfo {
+    pub precision: u8,
+    pub components: u8,
+    pub arithmetic: bool,
+    pub hierarchical: bool,
+    pub lossless: bool,
+}
+
+impl JpegInfo {
+    /// Whether the pixels are stored as CMYK (or YCCK), and have to be
+    /// decoded with [`decode_cmyk`]
+    pub fn is_cmyk(&self) -> bool {
+        self.components == 4
+    }
+
+    /// Names the feature the decoder cannot handle, if any
+    pub fn unsupported_variant(&self) -> Option<String> {
+        if self.arithmetic {
+            Some("arithmetic coding".to_string())
+        } else if self.hierarchical {
+            Some("hierarchical coding".to_string())
+        } else if !self.lossless && self.precision != 8 {
+            Some(format!("{}-bit samples", self.precision))
+        } else if !matches!(self.components, 1 | 3 | 4) {
+            Some(format!("{} color components", self.components))
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the first frame header of a JPEG file without decoding the scan data
+pub fn inspect(file: &Path) -> Option<JpegInfo> {
+    let mut reader = BufReader::new(File::open(file).ok()?);
+
+    let mut soi = [0u8; 2];
+    reader.read_exact(&mut soi).ok()?;
+    if soi != [0xFF, 0xD8] {
+        return None;
+    }
+
+    loop {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte).ok()?;
+        if byte[0] != 0xFF {
+            return None;
+        }
+        // Markers may be preceded by any number of 0xFF fill bytes
+        let mut marker = 0xFF;
+        while marker == 0xFF {
+            reader.read_exact(&mut byte).ok()?;
+            marker = byte[0];
+        }
+
+        // Standalone markers carry no length
+        if matches!(marker, 0x01 | 0xD0..=0xD7) {
+            continue;
+        }
+        // Start of scan: no frame header before the entropy-coded data
+        if matches!(marker, 0xDA | 0xD9) {
+            return None;
+        }
+
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length).ok()?;
+        let length = u16::from_be_bytes(length) as usize;
+        if length < 2 {
+            return None;
+        }
+
+        // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            let mut header = [0u8; 6];
+            reader.read_exact(&mut header).ok()?;
+            let kind = marker & 0x0F;
+            return Some(JpegInfo {
+                precision: header[0],
+                components: header[5],
+                arithmetic: kind >= 0x8,
+                hierarchical: matches!(kind, 0x5 | 0x6 | 0x7 | 0xD | 0xE | 0xF),
+                lossless: matches!(kind, 0x3 | 0x7 | 0xB | 0xF),
+            });
+        }
+
+        reader.seek_relative(length as i64 - 2).ok()?;
+    }
+}
+
+/// Decodes a CMYK or YCCK JPEG to RGB. The decoder hands back ink amounts,
+/// which [`cmyk_to_rgb`] turns into light.
+pub fn decode_cmyk(file: &Path) -> Result<DynamicImage> {
+    let mut decoder = Decoder::new(BufReader::new(File::open(file)?));
+    let pixels = decoder.decode()?;
+    let info = decoder.info().context("JPEG has no frame header")?;
+    if info.pixel_format != PixelFormat::CMYK32 {
+        anyhow::bail!("not a CMYK JPEG");
+    }
+    RgbImage::from_raw(info.width.into(), info.height.into(), cmyk_to_rgb(&pixels))
+        .map(DynamicImage::ImageRgb8)
+        .context("JPEG has fewer pixels than its size")
+}
+
+/// Converts CMYK pixels, 0 meaning no ink, to RGB with the naive transform
+/// `255 × (1 − C) × (1 − K)` per channel. No press profile is applied, so
+/// colors are close rather than exact.
+pub fn cmyk_to_rgb(cmyk: &[u8]) -> Vec<u8> {
+    cmyk.chunks_exact(4)
+        .flat_map(|pixel| {
+            let light = |ink: u8| 255 - u32::from(ink);
+            let k = light(pixel[3]);
+            [0, 1, 2].map(|channel| ((light(pixel[channel]) * k + 127) / 255) as u8)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8×8 baseline JPEG with 4 components, every sample 128: one
+    /// quantization table of ones, Huffman tables that only code a zero DC
+    /// difference and end-of-block, and a scan of four empty blocks
+    fn uniform_cmyk_jpeg() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend([0xFF, 0xDB, 0x00, 67, 0x00]);
+        jpeg.extend([1; 64]);
+        jpeg.extend([0xFF, 0xC0, 0x00, 20, 8, 0x00, 8, 0x00, 8, 4]);
+        for id in 1..=4 {
+            jpeg.extend([id, 0x11, 0]);
+        }
+        for class in [0x00, 0x10] {
+            jpeg.extend([0xFF, 0xC4, 0x00, 20, class, 1]);
+            jpeg.extend([0; 15]);
+            jpeg.push(0x00);
+        }
+        jpeg.extend([0xFF, 0xDA, 0x00, 14, 4]);
+        for id in 1..=4 {
+            jpeg.extend([id, 0x00]);
+        }
+        jpeg.extend([0, 63, 0]);
+        jpeg.extend([0x00, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    /// A JPEG that ends after a frame header of the given SOF `marker`
+    fn frame_header(marker: u8, precision: u8, components: u8) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0, 0];
+        jpeg.extend([0xFF, marker, 0x00, 8 + 3 * components, precision, 0x00, 8, 0x00, 8, components]);
+        for id in 1..=components {
+            jpeg.extend([id, 0x11, 0]);
+        }
+        jpeg
+    }
+
+    fn write(dir: &Path, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn cmyk_to_rgb_subtracts_ink_from_white() {
+        assert_eq!(cmyk_to_rgb(&[0, 0, 0, 0]), [255, 255, 255]);
+        assert_eq!(cmyk_to_rgb(&[0, 0, 0, 255]), [0, 0, 0]);
+        assert_eq!(cmyk_to_rgb(&[255, 0, 0, 0]), [0, 255, 255]);
+        assert_eq!(cmyk_to_rgb(&[0, 255, 255, 0]), [255, 0, 0]);
+        assert_eq!(cmyk_to_rgb(&[128, 0, 0, 128]), [63, 127, 127]);
+    }
+
+    #[test]
+    fn cmyk_jpegs_are_decoded_to_rgb() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(dir.path(), "print.jpg", &uniform_cmyk_jpeg());
+
+        let info = inspect(&file).unwrap();
+        assert!(info.is_cmyk());
+        assert_eq!(info.unsupported_variant(), None);
+
+        // Samples of 128 are stored inverted, so each ink is 127
+        let img = decode_cmyk(&file).unwrap();
+        assert_eq!(img.color(), image::ColorType::Rgb8);
+        assert_eq!((img.width(), img.height()), (8, 8));
+        assert!(img.to_rgb8().pixels().all(|pixel| pixel.0 == [64, 64, 64]));
+    }
+
+    #[test]
+    fn rgb_jpegs_are_not_decoded_as_cmyk() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("photo.jpg");
+        RgbImage::from_pixel(8, 8, image::Rgb([10, 200, 30])).save(&file).unwrap();
+
+        assert!(!inspect(&file).unwrap().is_cmyk());
+        assert!(decode_cmyk(&file).is_err());
+    }
+
+    #[test]
+    fn frame_headers_name_the_unsupported_variant() {
+        let dir = tempfile::tempdir().unwrap();
+        let variant = |marker, precision, components| {
+            let file = write(dir.path(), "variant.jpg", &frame_header(marker, precision, components));
+            inspect(&file).unwrap().unsupported_variant()
+        };
+
+        assert_eq!(variant(0xC0, 8, 3), None);
+        // Progressive files decode
+        assert_eq!(variant(0xC2, 8, 3), None);
+        assert_eq!(variant(0xC9, 8, 3).as_deref(), Some("arithmetic coding"));
+        assert_eq!(variant(0xC5, 8, 3).as_deref(), Some("hierarchical coding"));
+        assert_eq!(variant(0xC1, 12, 3).as_deref(), Some("12-bit samples"));
+        assert_eq!(variant(0xC0, 8, 2).as_deref(), Some("2 color components"));
+    }
+
+    #[test]
+    fn files_that_are_not_jpegs_have_no_frame_header() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(inspect(&write(dir.path(), "text.jpg", b"not an image")).is_none());
+        assert!(inspect(&dir.path().join("missing.jpg")).is_none());
+    }
+}
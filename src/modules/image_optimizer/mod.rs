@@ -1,36 +1,260 @@
-use anyhow::Result;
+mod animation;
+mod budget;
+mod detect;
+mod dry_run;
+mod encode;
+mod filter;
+mod icc;
+mod jpeg;
+mod report;
+mod template;
+mod transform;
+mod watermark;
+
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use dialoguer::{theme::ColorfulTheme, Select};
-use image::{ImageFormat, ImageEncoder};
+use dialoguer::theme::ColorfulTheme;
+use image::ImageFormat;
 use rayon::prelude::*;
-use std::{path::PathBuf, fs, sync::Arc, time::Duration, io::BufWriter};
-use walkdir::WalkDir;
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::{Path, PathBuf}, fs, sync::Arc, time::Duration};
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use crate::utils::prompt::{Confirm, Input, MultiSelect, Select};
+use crate::utils::{
+    create_spinner, exif, expand::expand_path, get_directory_from_user, junk::JunkList, metadata,
+    metrics::Metrics,
+    names::{escaped_name, numbered_name},
+    progress::{Outcome, ProgressSink},
+    space::{self, preflight, print_estimates, SpaceEstimate},
+    walk::{is_access_denied, Inaccessible, Walker},
+};
+use crate::modules::base::{FileOrganizer, Summary};
+pub use animation::AnimationMode;
+use encode::{CompressionLevel, EncodeSettings};
+pub use icc::IccMode;
+pub use filter::CandidateFilter;
+use dry_run::{PlannedFile, SavingsEstimate};
+use rand::seq::SliceRandom;
+use report::{format_megabytes, ConvertedFile};
+pub use report::{ConversionReport, FailureStage, FileOutcome, FileReport};
+use template::{NameTemplate, TemplateContext, DEFAULT_TEMPLATE};
+pub use transform::ColorMode;
+use watermark::{Anchor, Watermark};
 
-use crate::utils::get_directory_from_user;
-use crate::modules::base::FileOrganizer;
+/// Images above this many pixels (about 400 MB as RGBA) are decoded one at a time
+const DEFAULT_LARGE_IMAGE_PIXELS: u64 = 100_000_000;
 
+#[derive(Clone)]
 pub struct ImageOptimizer {
     recursive: bool,
     input_dir: Option<PathBuf>,
     target_format: Option<ImageFormat>,
     output_dir: Option<PathBuf>,
-    progress_bar: Option<Arc<ProgressBar>>,
+    encode_settings: EncodeSettings,
+    animation_mode: AnimationMode,
+    color_mode: Option<ColorMode>,
+    watermark: Option<Arc<Watermark>>,
+    name_template: Option<NameTemplate>,
+    filter: CandidateFilter,
+    detect_by_content: bool,
+    target_size: Option<u64>,
+    source_formats: Option<Vec<ImageFormat>>,
+    recompress: bool,
+    preserve_metadata: bool,
+    large_image_pixels: u64,
+    ignore_errors: bool,
+    /// Convert even when the output filesystem looks too small
+    force: bool,
+    dry_run: bool,
+    /// Where to write a CSV report of the run; asked in `configure` when
+    /// unset
+    report: Option<Option<PathBuf>>,
+    icc_mode: IccMode,
+    output_paths: HashMap<PathBuf, PathBuf>,
+    /// Phase timings, with decoding and encoding timed across workers
+    metrics: Arc<Metrics>,
+}
+
+/// Output path of every source, planned before any conversion starts
+struct OutputPlan {
+    paths: HashMap<PathBuf, PathBuf>,
+    /// Sources whose rendered name clashed, with the name they got instead
+    renames: Vec<(PathBuf, String)>,
+}
+
+/// Images a run converts, decided before anything is written
+#[derive(Serialize)]
+pub struct ImagePlan {
+    pub input_dir: PathBuf,
+    /// Sources in path order
+    pub files: Vec<PathBuf>,
+    /// Output path of every source
+    pub outputs: BTreeMap<PathBuf, PathBuf>,
+    /// Sources whose rendered name clashed, with the name they got instead
+    pub renames: Vec<(PathBuf, String)>,
+    /// Sources whose extension does not match their content, when content
+    /// detection is on
+    pub mislabeled: usize,
+    /// Images excluded by the size and dimension filters
+    pub filtered_out: usize,
+    /// Images already in the target format
+    pub same_format: usize,
+    /// Known junk files and directories the scan left out
+    pub junk: usize,
+    /// Space the outputs need, counting each as large as its source
+    pub space: Option<SpaceEstimate>,
+    /// Files and directories the scan could not read
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+/// Everything a conversion run needs, with no prompting
+pub struct ImageOptions {
+    pub input_dir: PathBuf,
+    /// JPEG, PNG, or WebP
+    pub target_format: ImageFormat,
+    /// Where outputs go; `None` uses a folder named after the target format
+    /// in the input directory
+    pub output_dir: Option<PathBuf>,
+    pub recursive: bool,
+    /// Input formats to convert; `None` converts JPEG, PNG, WebP, and GIF
+    pub source_formats: Option<Vec<ImageFormat>>,
+    pub color_mode: ColorMode,
+    /// Output filename template; `None` keeps the source name
+    pub name_template: Option<String>,
+    pub filter: CandidateFilter,
+    /// Collect files by their magic bytes instead of their extension
+    pub detect_by_content: bool,
+    /// Encode every image at the highest quality that fits in this many
+    /// bytes; needs a lossy target format
+    pub target_size: Option<u64>,
+    /// Re-encode files already in the target format instead of skipping them
+    pub recompress: bool,
+    /// Keep the source modification time and permissions on outputs
+    pub preserve_metadata: bool,
+    pub animation: AnimationMode,
+    pub icc_mode: IccMode,
+}
+
+impl ImageOptions {
+    pub fn new(input_dir: PathBuf, target_format: ImageFormat) -> Self {
+        Self {
+            input_dir,
+            target_format,
+            output_dir: None,
+            recursive: true,
+            source_formats: None,
+            color_mode: ColorMode::Keep,
+            name_template: None,
+            filter: CandidateFilter::default(),
+            detect_by_content: false,
+            target_size: None,
+            recompress: false,
+            preserve_metadata: true,
+            animation: AnimationMode::Skip,
+            icc_mode: IccMode::Preserve,
+        }
+    }
+}
+
+/// What a conversion run did
+pub struct ImageSummary {
+    /// Every converted, skipped, and failed image
+    pub report: ConversionReport,
+    /// Images excluded by the size and dimension filters
+    pub filtered_out: usize,
+    /// Images already in the target format
+    pub same_format: usize,
+    /// Known junk files and directories the scan left out
+    pub junk: usize,
+}
+
+impl From<&ImageSummary> for Summary {
+    fn from(summary: &ImageSummary) -> Self {
+        let report = &summary.report;
+        Summary {
+            files: report.converted_count(),
+            bytes: report.total_saved().max(0) as u64,
+            skipped: report.skipped_animated_count() + summary.filtered_out + summary.same_format,
+            junk: summary.junk,
+            inaccessible: report.unreadable_count(),
+            failed: report.failed_count(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Finds the images to convert and settles every output name, without
+/// writing anything
+pub fn plan(options: &ImageOptions, progress: impl ProgressSink) -> Result<ImagePlan> {
+    ImageOptimizer::from_options(options)?.scan(progress)
+}
+
+/// Converts every image in `plan`. Images that fail are recorded in the
+/// report rather than stopping the run.
+pub fn apply(options: &ImageOptions, plan: ImagePlan, progress: impl ProgressSink) -> Result<ImageSummary> {
+    let mut optimizer = ImageOptimizer::from_options(options)?;
+    space::check(plan.space.as_slice())?;
+    optimizer.output_paths = plan.outputs.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    optimizer.convert(&plan, progress, |_| {})
+}
+
+/// Plans and converts in one go.
+///
+/// ```
+/// use file_organizer_rust::modules::image_optimizer::{self, ImageOptions};
+/// use image::ImageFormat;
+///
+/// let dir = tempfile::tempdir()?;
+/// image::RgbImage::new(4, 4).save(dir.path().join("pixel.png"))?;
+///
+/// let options = ImageOptions::new(dir.path().to_path_buf(), ImageFormat::Jpeg);
+/// let summary = image_optimizer::execute(&options, ())?;
+/// assert_eq!(summary.report.converted_count(), 1);
+/// assert!(dir.path().join("jpg/pixel.jpg").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &ImageOptions, progress: impl ProgressSink) -> Result<ImageSummary> {
+    let plan = plan(options, &progress)?;
+    let summary = apply(options, plan, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
 }
 
 #[async_trait]
 impl FileOrganizer for ImageOptimizer {
+    type Plan = ImagePlan;
+    const NAME: &'static str = "image-optimize";
+
     fn new(recursive: bool) -> Self {
         Self {
             recursive,
             input_dir: None,
             target_format: None,
             output_dir: None,
-            progress_bar: None,
+            encode_settings: EncodeSettings::default(),
+            animation_mode: AnimationMode::Skip,
+            color_mode: None,
+            watermark: None,
+            name_template: None,
+            filter: CandidateFilter::default(),
+            detect_by_content: false,
+            target_size: None,
+            source_formats: None,
+            recompress: false,
+            preserve_metadata: true,
+            large_image_pixels: DEFAULT_LARGE_IMAGE_PIXELS,
+            ignore_errors: false,
+            force: false,
+            dry_run: false,
+            report: None,
+            icc_mode: IccMode::Preserve,
+            output_paths: HashMap::new(),
+            metrics: Arc::default(),
         }
     }
 
-    async fn run(&self) -> Result<()> {
+    fn configure(&mut self) -> Result<()> {
         let formats = vec!["JPEG", "PNG", "WebP"];
         let format_selection = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Select target format")
@@ -45,90 +269,634 @@ impl FileOrganizer for ImageOptimizer {
             _ => unreachable!(),
         };
 
+        if self.target_size.is_some() && !budget::supports_target_size(target_format) {
+            anyhow::bail!("A target file size needs a lossy format (JPEG or WebP)");
+        }
+
+        let source_formats = match &self.source_formats {
+            Some(formats) => formats.clone(),
+            None => prompt_source_formats()?,
+        };
+
+        let mut encode_settings = EncodeSettings::default();
+        if target_format == ImageFormat::Png {
+            let compression_options = vec!["None", "Fast", "Balanced", "Best"];
+            let compression_selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select PNG compression level")
+                .items(&compression_options)
+                .default(2)
+                .interact()?;
+
+            encode_settings.png_compression = match compression_selection {
+                0 => CompressionLevel::None,
+                1 => CompressionLevel::Fast,
+                2 => CompressionLevel::Balanced,
+                3 => CompressionLevel::Best,
+                _ => unreachable!(),
+            };
+
+            encode_settings.palette_reduction = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Reduce images with 256 colors or fewer to a palette?")
+                .default(true)
+                .interact()?;
+        }
+
+        let color_mode = match self.color_mode {
+            Some(mode) => mode,
+            None => prompt_color_mode()?,
+        };
+        if let ColorMode::Grayscale { bit_depth } = color_mode {
+            encode_settings.gray_bit_depth = bit_depth;
+        }
+
+        let watermark = prompt_watermark()?.map(Arc::new);
+
+        let name_template = match &self.name_template {
+            Some(template) => template.clone(),
+            None => prompt_name_template()?,
+        };
+
         let input_dir = get_directory_from_user("Enter input directory path")?;
         
-        // Create output directory
-        let format_dir_name = match target_format {
-            ImageFormat::Jpeg => "jpg",
-            ImageFormat::Png => "png",
-            ImageFormat::WebP => "webp",
-            _ => unreachable!(),
+        let output_dir = input_dir.join(format_dir_name(target_format));
+
+        // A dry run writes nothing, so has no report to ask about
+        if self.report.is_none() && !self.dry_run {
+            let export = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Export report to CSV?")
+                .default(false)
+                .interact()?;
+            self.report = Some(if export {
+                let csv_path: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Report file")
+                    .default(input_dir.join("image-optimization-report.csv").display().to_string())
+                    .interact_text()?;
+                Some(expand_path(csv_path.trim())?)
+            } else {
+                None
+            });
+        }
+
+        self.target_format = Some(target_format);
+        self.source_formats = Some(source_formats);
+        self.encode_settings = encode_settings;
+        self.color_mode = Some(color_mode);
+        self.watermark = watermark;
+        self.name_template = Some(name_template);
+        self.input_dir = Some(input_dir);
+        self.output_dir = Some(output_dir);
+        Ok(())
+    }
+
+    /// Also settles the output name of every file and how animated inputs
+    /// are handled, which `execute` then relies on
+    fn plan(&mut self) -> Result<ImagePlan> {
+        self.metrics.phase("scanning");
+        let plan = self.scan(())?;
+        if plan.same_format > 0 {
+            println!(
+                "Skipping {} images already in the target format (use --recompress to re-encode them)",
+                plan.same_format
+            );
+        }
+        if plan.mislabeled > 0 {
+            println!(
+                "Found {} images whose extension does not match their content; output names use the detected format",
+                plan.mislabeled
+            );
+        }
+        if !plan.renames.is_empty() {
+            println!("Renamed {} outputs to avoid name collisions:", plan.renames.len());
+            for (file, name) in &plan.renames {
+                println!("  {} -> {}", file.display(), name);
+            }
+        }
+
+        let may_be_animated = plan
+            .files
+            .iter()
+            .any(|f| matches!(self.source_format(f), Some(ImageFormat::Gif | ImageFormat::WebP)));
+        if may_be_animated {
+            let animation_options = vec![
+                "Skip animated images",
+                "Convert the first frame only",
+                "Keep animation (WebP target only)",
+            ];
+            let animation_selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("How to handle animated GIF/WebP inputs?")
+                .items(&animation_options)
+                .default(0)
+                .interact()?;
+
+            self.animation_mode = match animation_selection {
+                0 => AnimationMode::Skip,
+                1 => AnimationMode::FirstFrame,
+                2 => AnimationMode::Animate,
+                _ => unreachable!(),
+            };
+        }
+
+        Ok(plan)
+    }
+
+    fn review(&self, plan: &ImagePlan) -> Result<bool> {
+        if plan.files.is_empty() {
+            if plan.filtered_out > 0 {
+                println!("No image files matched the filters ({} excluded).", plan.filtered_out);
+            } else if plan.same_format > 0 {
+                println!("No images left to convert.");
+            } else {
+                println!("No image files found in the directory.");
+            }
+            return Ok(false);
+        }
+
+        println!("Sample output names:");
+        for file in plan.files.iter().take(5) {
+            println!("  {} -> {}", file.display(), plan.outputs[file].display());
+        }
+        // A dry run converts nothing, and the default names need no second look
+        let custom_names = self.name_template.as_ref().is_some_and(|t| !t.is_default());
+        if custom_names && !self.dry_run {
+            let proceed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Proceed with these names?")
+                .default(true)
+                .interact()?;
+            if !proceed {
+                return Ok(false);
+            }
+        }
+
+        if self.dry_run {
+            self.dry_run(&plan.files, &plan.input_dir)?;
+            print_estimates(plan.space.as_slice());
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn execute(&self, plan: ImagePlan) -> Result<Summary> {
+        preflight(plan.space.as_slice(), self.force)?;
+
+        let pb = conversion_bar(plan.files.len() as u64);
+        let summary = self.convert(&plan, (&pb, self.metrics.as_ref()), |line| pb.println(line))?;
+        let report = &summary.report;
+        pb.finish_with_message(format!(
+            "Converted {} images, saved {} ({} failed, {} animated skipped, {} unreadable skipped, {} junk skipped, {} excluded by filters)",
+            report.converted_count(),
+            format_megabytes(report.total_saved()),
+            report.failed_count(),
+            report.skipped_animated_count(),
+            report.unreadable_count(),
+            plan.junk,
+            plan.filtered_out
+        ));
+
+        report.print_table();
+
+        let mut by_format: BTreeMap<String, usize> = BTreeMap::new();
+        for file in report.converted_sources() {
+            let name = self
+                .source_format(file)
+                .map(|f| f.extensions_str()[0].to_uppercase())
+                .unwrap_or_else(|| "unknown".to_string());
+            *by_format.entry(name).or_default() += 1;
+        }
+        if !by_format.is_empty() {
+            let breakdown: Vec<String> = by_format
+                .iter()
+                .map(|(format, count)| format!("{} {}", format, count))
+                .collect();
+            println!("Converted by source format: {}", breakdown.join(", "));
+        }
+
+        if let Some(Some(csv_path)) = &self.report {
+            report.write_csv(csv_path)?;
+            println!("Report written to {}", csv_path.display());
+        }
+
+        let failed = report.failed_count();
+        if failed > 0 {
+            let log_path = plan.input_dir.join("conversion-errors.log");
+            report.write_error_log(&log_path)?;
+            println!("{} failures written to {}", failed, log_path.display());
+            if !self.ignore_errors {
+                anyhow::bail!("{} images failed to convert", failed);
+            }
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+/// Folder interactive runs write outputs to, inside the input directory
+fn format_dir_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::WebP => "webp",
+        _ => unreachable!(),
+    }
+}
+
+/// The bar shown while converting, ticking on its own
+fn conversion_bar(len: u64) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    pb.set_style(ProgressStyle::default_spinner()
+        .template("{spinner:.green} [{elapsed_precise}] {msg} ({pos}/{len})")
+        .unwrap()
+        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "));
+
+    // Let indicatif drive the ticks from its own thread; it stops as soon
+    // as the bar is finished, so nothing keeps redrawing after this stage.
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+impl ImageOptimizer {
+    /// Sets the format [`convert_one`](Self::convert_one) converts to:
+    /// JPEG, PNG, or WebP. Interactive runs ask for it.
+    pub fn with_target_format(mut self, format: ImageFormat) -> Result<Self> {
+        if !matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP) {
+            anyhow::bail!("Unsupported target format {:?} (expected JPEG, PNG, or WebP)", format);
+        }
+        self.target_format = Some(format);
+        Ok(self)
+    }
+
+    /// Sets where [`convert_one`](Self::convert_one) writes its outputs.
+    /// Interactive runs use a folder named after the format in the input.
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    /// Converts a single image outside of a run, into the output directory
+    /// under its own stem with the target format's extension, numbered when
+    /// that name is taken. Returns the output, or `None` when the file was
+    /// left alone: not a JPEG, PNG, WebP, or GIF, already in the target
+    /// format, or animated.
+    pub fn convert_one(&mut self, file: &Path) -> Result<Option<PathBuf>> {
+        let (Some(output_dir), Some(target_format)) = (self.output_dir.clone(), self.target_format) else {
+            anyhow::bail!("Image optimization not configured");
         };
-        let output_dir = input_dir.join(format_dir_name);
+        match self.source_format(file) {
+            Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif))
+                if self.recompress || format != target_format => {}
+            _ => return Ok(None),
+        }
+
         fs::create_dir_all(&output_dir)?;
+        let mut name = file.file_stem().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(target_format.extensions_str()[0]);
+        let mut output = output_dir.join(&name);
+        let mut counter = 1;
+        while output.exists() {
+            output = output_dir.join(numbered_name(Path::new(&name), counter));
+            counter += 1;
+        }
+        self.output_paths.insert(file.to_path_buf(), output);
+        let converted = self.convert_file(file);
+        self.output_paths.remove(file);
+        Ok(converted?.map(|converted| converted.output))
+    }
+
+    /// Preselects the output filename template so the prompt is skipped
+    pub fn with_name_template(mut self, template: &str) -> Result<Self> {
+        self.name_template = Some(NameTemplate::parse(template)?);
+        Ok(self)
+    }
+
+    /// Preselects the color transform so the interactive prompt is skipped
+    pub fn with_color_mode(mut self, mode: ColorMode) -> Self {
+        self.color_mode = Some(mode);
+        if let ColorMode::Grayscale { bit_depth } = mode {
+            self.encode_settings.gray_bit_depth = bit_depth;
+        }
+        self
+    }
+
+    /// Restricts conversion to files that pass the size and dimension limits
+    pub fn with_filter(mut self, filter: CandidateFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Collects files by their magic bytes instead of their extension
+    pub fn with_content_detection(mut self, enabled: bool) -> Self {
+        self.detect_by_content = enabled;
+        self
+    }
+
+    /// Encodes every image at the highest quality that fits in `max_bytes`
+    pub fn with_target_size(mut self, max_bytes: u64) -> Self {
+        self.target_size = Some(max_bytes);
+        self
+    }
+
+    /// Preselects which input formats are converted so the prompt is skipped
+    pub fn with_source_formats(mut self, formats: Vec<ImageFormat>) -> Self {
+        self.source_formats = Some(formats);
+        self
+    }
+
+    /// Re-encodes files that are already in the target format instead of
+    /// skipping them
+    pub fn with_recompress(mut self, recompress: bool) -> Self {
+        self.recompress = recompress;
+        self
+    }
+
+    /// Whether outputs keep the source modification time and permissions
+    pub fn with_preserve_metadata(mut self, preserve: bool) -> Self {
+        self.preserve_metadata = preserve;
+        self
+    }
+
+    /// Sets the pixel count above which images skip the parallel workers
+    pub fn with_large_image_pixels(mut self, pixels: u64) -> Self {
+        self.large_image_pixels = pixels;
+        self
+    }
+
+    /// Whether decoding the image would allocate more than the parallel
+    /// lane allows, judged from the header alone
+    fn is_large_image(&self, file: &Path) -> bool {
+        filter::header_dimensions(file)
+            .map(|(width, height)| width as u64 * height as u64 > self.large_image_pixels)
+            .unwrap_or(false)
+    }
+
+    /// Exits successfully even when some images failed to convert
+    pub fn with_ignore_errors(mut self, ignore: bool) -> Self {
+        self.ignore_errors = ignore;
+        self
+    }
+
+    /// Converts even when the output filesystem looks too small, instead of
+    /// refusing
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Chooses whether embedded color profiles are kept or converted to sRGB
+    pub fn with_icc_mode(mut self, mode: IccMode) -> Self {
+        self.icc_mode = mode;
+        self
+    }
+
+    /// Lists what would be converted and estimates the savings without
+    /// writing any output
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Writes a CSV report of the run to `path`, or none, without asking
+    pub fn with_report(mut self, path: Option<PathBuf>) -> Self {
+        self.report = Some(path);
+        self
+    }
+
+    fn from_options(options: &ImageOptions) -> Result<Self> {
+        if options.target_size.is_some() && !budget::supports_target_size(options.target_format) {
+            anyhow::bail!("A target file size needs a lossy format (JPEG or WebP)");
+        }
+        let output_dir = options
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| options.input_dir.join(format_dir_name(options.target_format)));
+        let mut optimizer = Self::new(options.recursive)
+            .with_target_format(options.target_format)?
+            .with_output_dir(output_dir)
+            .with_color_mode(options.color_mode)
+            .with_filter(options.filter.clone())
+            .with_content_detection(options.detect_by_content)
+            .with_recompress(options.recompress)
+            .with_preserve_metadata(options.preserve_metadata)
+            .with_icc_mode(options.icc_mode);
+        if let Some(template) = &options.name_template {
+            optimizer = optimizer.with_name_template(template)?;
+        }
+        optimizer.target_size = options.target_size;
+        optimizer.source_formats = options.source_formats.clone();
+        optimizer.animation_mode = options.animation;
+        optimizer.input_dir = Some(options.input_dir.clone());
+        Ok(optimizer)
+    }
 
-        // Set up state
-        let mut this = Self {
-            recursive: self.recursive,
-            input_dir: Some(input_dir.clone()),
-            target_format: Some(target_format),
-            output_dir: Some(output_dir),
-            progress_bar: None,
+    /// Collects and filters the images and settles the output name of
+    /// every one, with no prompting
+    fn scan(&mut self, progress: impl ProgressSink) -> Result<ImagePlan> {
+        let (Some(input_dir), Some(target_format)) = (self.input_dir.clone(), self.target_format) else {
+            anyhow::bail!("Image optimization not configured");
         };
+        progress.scan_started(&input_dir);
 
-        // Collect all files first
-        let files: Vec<_> = this.collect_image_files()?;
-        let total_files = files.len();
-        
-        if total_files == 0 {
-            println!("No image files found in the directory.");
-            return Ok(());
+        let (files, junk, inaccessible) = self.collect_image_files()?;
+        let found_files = files.len();
+        let files = self.apply_filter(files);
+        let filtered_out = found_files - files.len();
+
+        // Re-encoding a file into its own format is opt-in
+        let (files, same_format): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|f| self.recompress || self.source_format(f) != Some(target_format));
+        let mut plan = ImagePlan {
+            input_dir,
+            files,
+            outputs: BTreeMap::new(),
+            renames: Vec::new(),
+            mislabeled: 0,
+            filtered_out,
+            same_format: same_format.len(),
+            junk,
+            space: None,
+            inaccessible,
+        };
+        if plan.files.is_empty() {
+            return Ok(plan);
         }
 
-        // Create a progress bar
-        let pb = ProgressBar::new(total_files as u64);
-        pb.set_style(ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {msg} ({pos}/{len})")
-            .unwrap()
-            .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ "));
-        
-        let pb = Arc::new(pb);
-        this.progress_bar = Some(Arc::clone(&pb));
-        let pb_clone = Arc::clone(&pb);
+        if self.detect_by_content {
+            plan.mislabeled = plan
+                .files
+                .iter()
+                .filter(|f| {
+                    detect::content_format(f)
+                        .map(|format| detect::is_mislabeled(f, format))
+                        .unwrap_or(false)
+                })
+                .count();
+        }
 
-        // Start the progress bar update thread
-        tokio::spawn(async move {
-            loop {
-                pb_clone.tick();
-                tokio::time::sleep(Duration::from_millis(100)).await;
-            }
-        });
+        let output_plan = self.plan_output_paths(&plan.files)?;
+        plan.outputs = output_plan.paths.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        plan.renames = output_plan.renames;
+        self.output_paths = output_plan.paths;
+        let required = plan.files.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+        let output_dir = self.output_dir.as_ref().unwrap_or(&plan.input_dir);
+        plan.space = Some(SpaceEstimate::new(output_dir, required)?);
+        Ok(plan)
+    }
 
-        // Process files in parallel with chunking for better memory management
-        files.par_chunks(8)
-            .try_for_each(|chunk| -> Result<()> {
-                for path in chunk {
-                    if let Err(e) = this.process_file(path) {
-                        pb.println(format!("Error converting {}: {}", path.display(), e));
-                    }
-                    pb.inc(1);
-                    pb.set_message(format!("Converting images..."));
+    /// Converts every image in `plan`, passing each file's outcome to
+    /// `progress` and each line worth showing to `log`
+    fn convert(
+        &self,
+        plan: &ImagePlan,
+        progress: impl ProgressSink,
+        log: impl Fn(String) + Sync,
+    ) -> Result<ImageSummary> {
+        if let Some(output_dir) = &self.output_dir {
+            fs::create_dir_all(output_dir)?;
+        }
+        progress.phase_changed("Converting images", plan.files.len() as u64);
+
+        let convert = |path: &PathBuf| {
+            progress.item_started(path);
+            let report = self.convert_with_report(path);
+            let outcome = match &report.outcome {
+                FileOutcome::Failed { stage, error } => {
+                    log(format!("Error converting {} ({}): {}", path.display(), stage, error));
+                    Outcome::Failed
                 }
-                Ok(())
-            })?;
+                FileOutcome::SkippedAnimated => {
+                    log(format!("Skipping animated image {}", path.display()));
+                    Outcome::Skipped
+                }
+                FileOutcome::Unreadable { .. } => Outcome::Skipped,
+                FileOutcome::Converted { .. } => Outcome::Done,
+            };
+            progress.item_finished(path, report.original_size, outcome);
+            report
+        };
 
-        pb.finish_with_message(format!("Successfully converted {} images!", total_files));
-        Ok(())
+        // Huge images would multiply their decode buffers across every worker,
+        // so they go through a sequential lane after the parallel pass
+        let (large, regular): (Vec<_>, Vec<_>) =
+            plan.files.iter().partition(|f| self.is_large_image(f));
+
+        let mut reports: Vec<FileReport> = regular.into_par_iter().map(convert).collect();
+        if !large.is_empty() {
+            log(format!("Converting {} large images one at a time", large.len()));
+            reports.extend(large.into_iter().map(convert));
+        }
+
+        // Entries the scan could not read are reported with the rest
+        reports.extend(plan.inaccessible.iter().map(|entry| FileReport {
+            source: entry.path.clone(),
+            original_size: 0,
+            outcome: FileOutcome::Unreadable { reason: entry.reason.clone() },
+        }));
+
+        Ok(ImageSummary {
+            report: ConversionReport::new(reports),
+            filtered_out: plan.filtered_out,
+            same_format: plan.same_format,
+            junk: plan.junk,
+        })
     }
 
-    fn is_recursive(&self) -> bool {
-        self.recursive
+    fn dry_run(&self, files: &[PathBuf], input_dir: &Path) -> Result<()> {
+        let planned: Vec<PlannedFile> = files
+            .par_iter()
+            .map(|file| PlannedFile {
+                source: file.clone(),
+                size: fs::metadata(file).map(|m| m.len()).unwrap_or(0),
+                dimensions: filter::header_dimensions(file),
+                output: self.output_paths[file].clone(),
+            })
+            .collect();
+        dry_run::print_plan(&planned);
+
+        let mut estimate = self.estimate_savings(files)?;
+        estimate.total_original = planned.iter().map(|f| f.size).sum();
+        estimate.print(files.len());
+
+        let export = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Export plan to CSV?")
+            .default(false)
+            .interact()?;
+        if export {
+            let csv_path: String = Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Plan file")
+                .default(input_dir.join("image-optimization-plan.csv").display().to_string())
+                .interact_text()?;
+            let csv_path = expand_path(csv_path.trim())?;
+            dry_run::write_plan_csv(&planned, &csv_path)?;
+            println!("Plan written to {}", csv_path.display());
+        }
+
+        Ok(())
     }
 
-    fn get_input_dir(&self) -> Option<&PathBuf> {
-        self.input_dir.as_ref()
+    /// Converts a random sample into a temporary directory, which is removed
+    /// again once the sizes are measured
+    fn estimate_savings(&self, files: &[PathBuf]) -> Result<SavingsEstimate> {
+        let temp_dir = tempfile::tempdir()?;
+        let sample: Vec<&PathBuf> = files
+            .choose_multiple(&mut rand::thread_rng(), dry_run::SAMPLE_SIZE)
+            .collect();
+
+        let mut sampler = self.clone();
+        sampler.output_dir = Some(temp_dir.path().to_path_buf());
+        sampler.preserve_metadata = false;
+        sampler.output_paths = sample
+            .iter()
+            .map(|file| {
+                let name = self.output_paths[*file].file_name().unwrap_or_default();
+                ((*file).clone(), temp_dir.path().join(name))
+            })
+            .collect();
+
+        let spinner = create_spinner("Converting a sample to estimate savings...");
+        let reports: Vec<FileReport> = sample
+            .par_iter()
+            .map(|file| sampler.convert_with_report(file))
+            .collect();
+        spinner.finish_and_clear();
+
+        let mut estimate = SavingsEstimate {
+            sample_size: 0,
+            sample_original: 0,
+            sample_converted: 0,
+            total_original: 0,
+        };
+        for report in &reports {
+            if let FileOutcome::Converted { new_size, .. } = report.outcome {
+                estimate.sample_size += 1;
+                estimate.sample_original += report.original_size;
+                estimate.sample_converted += new_size;
+            }
+        }
+
+        Ok(estimate)
     }
 
-    fn set_input_dir(&mut self, dir: PathBuf) {
-        self.input_dir = Some(dir);
+    /// Format of a collected file, from its content when content detection
+    /// is enabled and from its extension otherwise
+    fn source_format(&self, file: &Path) -> Option<ImageFormat> {
+        if self.detect_by_content {
+            detect::content_format(file)
+        } else {
+            ImageFormat::from_path(file).ok()
+        }
     }
 
-    fn process_file(&self, file: &PathBuf) -> Result<()> {
-        // Open and decode the image with faster nearest-neighbor sampling
-        let img = image::io::Reader::open(file)?
-            .with_guessed_format()?
-            .decode()?;
-        
+    /// Converts a single image and returns the written output, or `None`
+    /// when the file was left alone because it is animated
+    fn convert_file(&self, file: &Path) -> Result<Option<ConvertedFile>> {
+        let reader = image::io::Reader::open(file)
+            .and_then(|r| r.with_guessed_format())
+            .context(FailureStage::Io)?;
+        let source_format = reader.format();
+
         let output_dir = self.output_dir.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Output directory not set")
         })?;
@@ -137,93 +905,590 @@ impl FileOrganizer for ImageOptimizer {
             anyhow::anyhow!("Target format not set")
         })?;
 
-        let stem = file.file_stem().unwrap().to_string_lossy().to_string();
-        let new_filename = format!("{}.{}", stem, target_format.extensions_str()[0]);
-        let output_path = output_dir.join(new_filename);
-
-        // Create a buffered writer for better performance
-        let output = fs::File::create(&output_path)?;
-        let mut writer = BufWriter::new(output);
-
-        // Optimize based on format
-        match target_format {
-            ImageFormat::Jpeg => {
-                // Use a lower quality setting for JPEG to improve speed
-                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut writer, 85);
-                encoder.write_image(
-                    img.as_bytes(),
-                    img.width(),
-                    img.height(),
-                    img.color(),
-                )?;
-            }
-            ImageFormat::Png => {
-                // Use fast compression for PNG
-                let encoder = image::codecs::png::PngEncoder::new_with_quality(
-                    &mut writer,
-                    image::codecs::png::CompressionType::Fast,
-                    image::codecs::png::FilterType::Sub,
-                );
-                encoder.write_image(
-                    img.as_bytes(),
-                    img.width(),
-                    img.height(),
-                    img.color(),
-                )?;
-            }
-            ImageFormat::WebP => {
-                // For WebP, save directly using the image crate's save function
-                img.save_with_format(&output_path, target_format)?;
+        let output_path = match self.output_paths.get(file) {
+            Some(path) => path.clone(),
+            None => {
+                let mut name = file.file_stem().unwrap().to_os_string();
+                name.push(".");
+                name.push(target_format.extensions_str()[0]);
+                output_dir.join(name)
+            }
+        };
+
+        if let Some(format) = source_format {
+            if animation::is_animated(file, format).context(FailureStage::Decode)? {
+                match self.animation_mode {
+                    AnimationMode::FirstFrame => {}
+                    AnimationMode::Animate if animation::supports_animation(target_format) => {
+                        let frames = animation::decode_frames(file, format).context(FailureStage::Decode)?;
+                        let data = animation::encode_animated_webp(&frames).context(FailureStage::Encode)?;
+                        fs::write(&output_path, data).context(FailureStage::Io)?;
+                        self.finish_output(file, &output_path).context(FailureStage::Io)?;
+                        return Ok(Some(ConvertedFile {
+                            output: output_path,
+                            notes: vec!["animated".to_string()],
+                        }));
+                    }
+                    AnimationMode::Skip | AnimationMode::Animate => return Ok(None),
+                }
             }
-            _ => unreachable!(),
         }
-        
+
+        let mut notes = Vec::new();
+        if let Some(format) = source_format {
+            if detect::is_mislabeled(file, format) {
+                notes.push(format!("content is {}", format.extensions_str()[0]));
+            }
+        }
+
+        let jpeg_info = match source_format {
+            Some(ImageFormat::Jpeg) => jpeg::inspect(file),
+            _ => None,
+        };
+
+        let cmyk = jpeg_info.as_ref().is_some_and(|info| info.is_cmyk());
+        // CMYK is converted here rather than left to the decoder, so the
+        // transform is known and tested
+        let decoded = self.metrics.time("decode", || {
+            if cmyk {
+                jpeg::decode_cmyk(file)
+            } else {
+                reader.decode().map_err(Into::into)
+            }
+        });
+        let img = match decoded {
+            Ok(img) => img,
+            Err(e) => match jpeg_info.as_ref().and_then(|info| info.unsupported_variant()) {
+                Some(variant) => {
+                    return Err(anyhow::anyhow!("unsupported JPEG variant: {}", variant))
+                        .context(FailureStage::Decode);
+                }
+                None => return Err(e).context(FailureStage::Decode),
+            },
+        };
+        // A CMYK profile does not describe the converted RGB pixels
+        let profile = if cmyk {
+            notes.push("CMYK converted to RGB".to_string());
+            None
+        } else {
+            source_format.and_then(|format| icc::read_profile(file, format))
+        };
+        let mut embed_profile = None;
+        let mut tag_srgb = false;
+        let img = match (&profile, self.icc_mode) {
+            (Some(profile), IccMode::NormalizeSrgb) => match icc::convert_to_srgb(&img, profile) {
+                Some(converted) => {
+                    notes.push("converted to sRGB".to_string());
+                    tag_srgb = true;
+                    converted
+                }
+                None => {
+                    notes.push("ICC profile not convertible, kept".to_string());
+                    embed_profile = Some(profile);
+                    img
+                }
+            },
+            (Some(profile), IccMode::Preserve) => {
+                embed_profile = Some(profile);
+                img
+            }
+            (None, _) => img,
+        };
+
+        let (img, color_note) =
+            transform::apply_color_mode(img, self.color_mode.unwrap_or(ColorMode::Keep));
+        // A color profile no longer describes pixels that were made gray
+        if color_note.is_some() && embed_profile.take().is_some() {
+            notes.push("ICC profile dropped".to_string());
+        }
+        let made_gray = color_note.is_some();
+        notes.extend(color_note);
+
+        let img = match &self.watermark {
+            Some(watermark) => {
+                notes.push("watermark".to_string());
+                watermark.apply(img)
+            }
+            None => img,
+        };
+        // Only grayscale PNGs can be stored below 8 bits; elsewhere the
+        // fewer levels just compress better
+        if made_gray
+            && self.encode_settings.gray_bit_depth < 8
+            && (self.target_size.is_some() || !encode::packs_gray(&img, target_format))
+        {
+            notes.push("stored at 8 bits".to_string());
+        }
+
+        let (data, width, height) = match self.target_size {
+            Some(max_bytes) => {
+                let fitted = self
+                    .metrics
+                    .time("encode", || budget::fit_to_size(&img, target_format, max_bytes))
+                    .context(FailureStage::Encode)?;
+                notes.push(format!("quality {}", fitted.quality));
+                if (fitted.width, fitted.height) != (img.width(), img.height()) {
+                    notes.push(format!("resized to {}x{}", fitted.width, fitted.height));
+                }
+                (fitted.data, fitted.width, fitted.height)
+            }
+            None => {
+                let data = self
+                    .metrics
+                    .time("encode", || encode::encode(&img, target_format, &self.encode_settings))
+                    .context(FailureStage::Encode)?;
+                (data, img.width(), img.height())
+            }
+        };
+
+        let data = match embed_profile {
+            Some(profile) if icc::supports_embedding(target_format) => {
+                notes.push("ICC profile preserved".to_string());
+                icc::embed(data, target_format, profile, width, height, img.color().has_alpha())
+                    .context(FailureStage::Encode)?
+            }
+            _ if tag_srgb && target_format == ImageFormat::Png => {
+                icc::tag_png_srgb(data).context(FailureStage::Encode)?
+            }
+            _ => data,
+        };
+        fs::write(&output_path, data).context(FailureStage::Io)?;
+        self.finish_output(file, &output_path).context(FailureStage::Io)?;
+
+        Ok(Some(ConvertedFile { output: output_path, notes }))
+    }
+
+    /// Carries the source's mtime and permissions over to a written output
+    fn finish_output(&self, source: &Path, output: &Path) -> Result<()> {
+        if self.preserve_metadata {
+            metadata::copy_file_metadata(source, output)?;
+        }
         Ok(())
     }
 
-    fn create_directories(&self, base_dir: &PathBuf) -> Result<()> {
-        if let Some(target_format) = self.target_format {
-            let format_dir_name = match target_format {
-                ImageFormat::Jpeg => "jpg",
-                ImageFormat::Png => "png",
-                ImageFormat::WebP => "webp",
-                _ => unreachable!(),
+    /// Renders the output name of every file up front so that collisions
+    /// between sources are resolved before anything is written
+    fn plan_output_paths(&self, files: &[PathBuf]) -> Result<OutputPlan> {
+        let output_dir = self.output_dir.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Output directory not set")
+        })?;
+        let target_format = self.target_format.ok_or_else(|| {
+            anyhow::anyhow!("Target format not set")
+        })?;
+        let template = match &self.name_template {
+            Some(template) => template.clone(),
+            None => NameTemplate::parse(DEFAULT_TEMPLATE)?,
+        };
+
+        let quality = match target_format {
+            ImageFormat::Jpeg => self.encode_settings.jpeg_quality,
+            _ => 100,
+        };
+
+        let mut planned = Vec::with_capacity(files.len());
+        for (index, file) in files.iter().enumerate() {
+            let (width, height) = if template.needs_dimensions() {
+                filter::header_dimensions(file).unwrap_or((0, 0))
+            } else {
+                (0, 0)
             };
-            fs::create_dir_all(base_dir.join(format_dir_name))?;
+            let date = if template.needs_date() {
+                exif::capture_date(file)
+                    .or_else(|| fs::metadata(file).and_then(|m| m.modified()).ok().map(|t| {
+                        chrono::DateTime::<chrono::Local>::from(t).naive_local()
+                    }))
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
+
+            // Mislabeled files are named after the format their content is in
+            let detected = if self.detect_by_content { detect::content_format(file) } else { None };
+            let source_extension = match detected {
+                Some(format) if detect::is_mislabeled(file, format) => {
+                    format.extensions_str()[0].to_string()
+                }
+                _ => file.extension().unwrap_or_default().to_string_lossy().to_string(),
+            };
+
+            let name = template.render(&TemplateContext {
+                stem: &escaped_name(file.file_stem().unwrap_or_default()),
+                source_extension: &source_extension,
+                extension: target_format.extensions_str()[0],
+                width,
+                height,
+                quality,
+                date: &date,
+                sequence: index + 1,
+            });
+
+            planned.push((file, name, source_extension.to_lowercase()));
         }
-        Ok(())
+
+        // Names that clash (ignoring case, for case-insensitive filesystems)
+        // keep the first source in path order; later ones get a `.from-<ext>`
+        // marker and, if that still clashes, a numeric suffix
+        let mut name_counts: HashMap<String, usize> = HashMap::new();
+        for (_, name, _) in &planned {
+            *name_counts.entry(name.to_lowercase()).or_default() += 1;
+        }
+
+        let mut used_names = HashSet::new();
+        let mut output_paths = HashMap::new();
+        let mut renames = Vec::new();
+        for (file, name, source_extension) in planned {
+            let taken = name_counts[&name.to_lowercase()] > 1 && used_names.contains(&name.to_lowercase());
+            let candidate = if taken && !source_extension.is_empty() {
+                with_source_marker(&name, &source_extension)
+            } else {
+                name.clone()
+            };
+            let resolved = unique_name(candidate, &mut used_names);
+            if resolved != name {
+                renames.push((file.clone(), resolved.clone()));
+            }
+            output_paths.insert(file.clone(), output_dir.join(resolved));
+        }
+
+        Ok(OutputPlan { paths: output_paths, renames })
     }
-}
 
-impl ImageOptimizer {
-    fn collect_image_files(&self) -> Result<Vec<PathBuf>> {
+    fn convert_with_report(&self, file: &Path) -> FileReport {
+        let original_size = fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        let outcome = match self.convert_file(file) {
+            Ok(Some(ConvertedFile { output, notes })) => {
+                let new_size = fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+                FileOutcome::Converted { output, new_size, notes }
+            }
+            Ok(None) => FileOutcome::SkippedAnimated,
+            Err(e) if is_access_denied(&e) => FileOutcome::Unreadable {
+                reason: e.root_cause().to_string(),
+            },
+            Err(e) => FileOutcome::failed(e),
+        };
+
+        FileReport {
+            source: file.to_path_buf(),
+            original_size,
+            outcome,
+        }
+    }
+
+    /// Image files to convert, how many junk entries the walk left out,
+    /// and the entries it could not read
+    fn collect_image_files(&self) -> Result<(Vec<PathBuf>, usize, Vec<Inaccessible>)> {
         let input_dir = self.input_dir.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Input directory not set")
         })?;
 
-        let walker = if self.recursive {
-            WalkDir::new(input_dir)
-        } else {
-            WalkDir::new(input_dir).max_depth(1)
-        };
+        // Earlier runs' output would otherwise be converted again
+        let mut walk = Walker::new(self.recursive)
+            .include_repos(true)
+            .exclude(self.output_dir.iter().cloned().collect())
+            .junk(Some(JunkList::load()?))
+            .files(input_dir);
 
-        let files: Vec<PathBuf> = walker
+        let junk = walk.junk.len();
+        let inaccessible = std::mem::take(&mut walk.inaccessible);
+        let mut files: Vec<PathBuf> = walk
+            .into_paths()
             .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| {
-                if let Some(ext) = e.path().extension() {
+            .filter(|file| {
+                if self.detect_by_content {
+                    return detect::content_format(file).is_some();
+                }
+                if let Some(ext) = file.extension() {
                     matches!(
                         ext.to_str().unwrap_or(""),
-                        "jpg" | "jpeg" | "png" | "webp"
+                        "jpg" | "jpeg" | "png" | "webp" | "gif"
                     )
                 } else {
                     false
                 }
             })
-            .map(|e| e.path().to_path_buf())
+            .filter(|file| match &self.source_formats {
+                Some(formats) => self
+                    .source_format(file)
+                    .map(|format| formats.contains(&format))
+                    .unwrap_or(false),
+                None => true,
+            })
             .collect();
+        // Path order keeps sequence numbers and collision handling deterministic
+        files.sort();
+
+        Ok((files, junk, inaccessible))
+    }
+
+    /// Drops files excluded by the size and dimension filters
+    fn apply_filter(&self, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.filter.is_empty() {
+            return files;
+        }
+        files
+            .into_par_iter()
+            .filter(|file| self.filter.matches(file))
+            .collect()
+    }
+}
+
+fn prompt_source_formats() -> Result<Vec<ImageFormat>> {
+    let format_options = vec!["JPEG", "PNG", "WebP", "GIF"];
+    let selected = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select source formats to convert")
+        .items(&format_options)
+        .defaults(&[true, true, true, true])
+        .interact()?;
+
+    if selected.is_empty() {
+        anyhow::bail!("No source formats selected");
+    }
+
+    Ok(selected
+        .into_iter()
+        .map(|idx| match idx {
+            0 => ImageFormat::Jpeg,
+            1 => ImageFormat::Png,
+            2 => ImageFormat::WebP,
+            3 => ImageFormat::Gif,
+            _ => unreachable!(),
+        })
+        .collect())
+}
+
+fn prompt_color_mode() -> Result<ColorMode> {
+    let color_options = vec!["Keep colors", "Grayscale"];
+    let color_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select color mode")
+        .items(&color_options)
+        .default(0)
+        .interact()?;
+
+    if color_selection == 0 {
+        return Ok(ColorMode::Keep);
+    }
+
+    let depth_options = vec!["8-bit (256 levels)", "4-bit (16 levels)", "2-bit (4 levels)", "1-bit (black & white)"];
+    let depth_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select grayscale bit depth")
+        .items(&depth_options)
+        .default(0)
+        .interact()?;
+
+    let bit_depth = match depth_selection {
+        0 => 8,
+        1 => 4,
+        2 => 2,
+        3 => 1,
+        _ => unreachable!(),
+    };
+
+    Ok(ColorMode::Grayscale { bit_depth })
+}
+
+fn prompt_watermark() -> Result<Option<Watermark>> {
+    let watermark_options = vec!["No watermark", "Text", "Image overlay (PNG)"];
+    let watermark_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Add a watermark?")
+        .items(&watermark_options)
+        .default(0)
+        .interact()?;
+
+    if watermark_selection == 0 {
+        return Ok(None);
+    }
+
+    let anchor_options = vec!["Bottom right", "Bottom left", "Top right", "Top left", "Center"];
+    let anchor_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Watermark position")
+        .items(&anchor_options)
+        .default(0)
+        .interact()?;
+
+    let anchor = match anchor_selection {
+        0 => Anchor::BottomRight,
+        1 => Anchor::BottomLeft,
+        2 => Anchor::TopRight,
+        3 => Anchor::TopLeft,
+        4 => Anchor::Center,
+        _ => unreachable!(),
+    };
+
+    let opacity: u8 = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Opacity in percent (0-100)")
+        .default(50)
+        .validate_with(|v: &u8| if *v <= 100 { Ok(()) } else { Err("Enter a value between 0 and 100") })
+        .interact_text()?;
+
+    let padding: u32 = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Padding from the edge in pixels")
+        .default(16)
+        .interact_text()?;
+
+    let opacity = opacity as f32 / 100.0;
+    let watermark = if watermark_selection == 1 {
+        let text: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Watermark text")
+            .interact_text()?;
+        let size_percent: f32 = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Text height as percent of image height")
+            .default(5.0)
+            .interact_text()?;
+        Watermark::text(text, size_percent, anchor, opacity, padding)
+    } else {
+        let path: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Path to the watermark image")
+            .interact_text()?;
+        Watermark::image(&expand_path(path.trim())?, anchor, opacity, padding)?
+    };
+
+    Ok(Some(watermark))
+}
+
+fn prompt_name_template() -> Result<NameTemplate> {
+    let template: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Output filename template ({stem}, {src_ext}, {ext}, {width}, {height}, {quality}, {date}, {n:04})")
+        .default(DEFAULT_TEMPLATE.to_string())
+        .validate_with(|t: &String| NameTemplate::parse(t).map(|_| ()).map_err(|e| e.to_string()))
+        .interact_text()?;
+    NameTemplate::parse(&template)
+}
 
-        Ok(files)
+/// Turns `photo.webp` into `photo.from-png.webp`
+fn with_source_marker(name: &str, source_extension: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.from-{}.{}", stem, source_extension, ext),
+        None => format!("{}.from-{}", name, source_extension),
     }
-} 
\ No newline at end of file
+}
+
+/// Returns `name`, or the first free `name-N` variant; names are compared
+/// case-insensitively
+fn unique_name(name: String, used_names: &mut HashSet<String>) -> String {
+    if used_names.insert(name.to_lowercase()) {
+        return name;
+    }
+
+    let path = Path::new(&name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = path.extension().map(|e| e.to_string_lossy().to_string());
+    let mut counter = 1;
+    loop {
+        let candidate = match &ext {
+            Some(ext) => format!("{}-{}.{}", stem, counter, ext),
+            None => format!("{}-{}", stem, counter),
+        };
+        if used_names.insert(candidate.to_lowercase()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::{ProgressDrawTarget, TermLike};
+    use std::{io, sync::Mutex, thread};
+
+    /// A terminal that keeps every line drawn on it
+    #[derive(Debug, Clone, Default)]
+    struct Screen(Arc<Mutex<Vec<String>>>);
+
+    impl Screen {
+        fn lines(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl TermLike for Screen {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push(s.to_string());
+            Ok(())
+        }
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            self.write_line(s)
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            Ok(())
+        }
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn write_png(path: &Path) {
+        image::RgbImage::from_pixel(8, 8, image::Rgb([10, 200, 30])).save(path).unwrap();
+    }
+
+    #[test]
+    fn converted_images_keep_the_source_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("photo.png");
+        write_png(&source);
+        filetime::set_file_mtime(&source, filetime::FileTime::from_unix_time(1_500_000_000, 0)).unwrap();
+
+        let mut optimizer = ImageOptimizer::new(false)
+            .with_target_format(ImageFormat::Jpeg)
+            .unwrap()
+            .with_output_dir(dir.path().join("jpg"));
+        let output = optimizer.convert_one(&source).unwrap().unwrap();
+
+        let mtime = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+        let seconds = |path: &Path| mtime(path).duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(seconds(&output), seconds(&source));
+    }
+
+    #[test]
+    fn converted_images_get_a_fresh_mtime_when_not_preserving() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("photo.png");
+        write_png(&source);
+        filetime::set_file_mtime(&source, filetime::FileTime::from_unix_time(1_500_000_000, 0)).unwrap();
+
+        let mut optimizer = ImageOptimizer::new(false)
+            .with_target_format(ImageFormat::Jpeg)
+            .unwrap()
+            .with_output_dir(dir.path().join("jpg"))
+            .with_preserve_metadata(false);
+        let output = optimizer.convert_one(&source).unwrap().unwrap();
+
+        let modified = fs::metadata(&output).unwrap().modified().unwrap();
+        assert!(modified.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() > 1_500_000_000);
+    }
+
+    #[test]
+    fn conversion_bar_stops_drawing_once_finished() {
+        let screen = Screen::default();
+        let pb = conversion_bar(2);
+        pb.set_draw_target(ProgressDrawTarget::term_like(Box::new(screen.clone())));
+        pb.inc(2);
+        thread::sleep(Duration::from_millis(300));
+        assert!(!screen.lines().is_empty(), "the bar ticks while converting");
+
+        pb.finish_with_message("Converted 2 images");
+        let finished = screen.lines();
+        assert!(finished.iter().any(|line| line.contains("Converted 2 images")));
+
+        // Later stages of a longer run print below the finished bar, which
+        // must not be drawn over again
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(screen.lines(), finished);
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::Result;
+use humansize::{format_size, BINARY};
+use std::{
+    fmt,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{atomic::AtomicFile, csv_field};
+
+/// Result of converting a single image, collected from the rayon workers
+pub struct FileReport {
+    pub source: PathBuf,
+    pub original_size: u64,
+    pub outcome: FileOutcome,
+}
+
+/// Output of a successful conversion, before its size is measured
+pub struct ConvertedFile {
+    pub output: PathBuf,
+    /// Transforms applied along the way, e.g. "grayscale"
+    pub notes: Vec<String>,
+}
+
+pub enum FileOutcome {
+    Converted { output: PathBuf, new_size: u64, notes: Vec<String> },
+    SkippedAnimated,
+    /// The source could not be read, e.g. for lack of permission
+    Unreadable { reason: String },
+    Failed { stage: FailureStage, error: String },
+}
+
+/// Pipeline step a conversion failed in, so corrupt sources can be told
+/// apart from permission or disk problems
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureStage {
+    Io,
+    Decode,
+    Encode,
+    Other,
+}
+
+impl fmt::Display for FailureStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FailureStage::Io => "io",
+            FailureStage::Decode => "decode",
+            FailureStage::Encode => "encode",
+            FailureStage::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FileOutcome {
+    /// Builds a failure from a conversion error, using the stage attached
+    /// with `.context(stage)` or falling back to I/O for bare `io::Error`s
+    pub fn failed(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<FailureStage>() {
+            Some(stage) => FileOutcome::Failed {
+                stage: *stage,
+                error: err.chain().skip(1).map(|e| e.to_string()).collect::<Vec<_>>().join(": "),
+            },
+            None => FileOutcome::Failed {
+                stage: if err.chain().any(|e| e.is::<std::io::Error>()) {
+                    FailureStage::Io
+                } else {
+                    FailureStage::Other
+                },
+                error: format!("{:#}", err),
+            },
+        }
+    }
+}
+
+impl FileReport {
+    /// Bytes saved by the conversion, negative when the output grew
+    pub fn saved(&self) -> i64 {
+        match self.outcome {
+            FileOutcome::Converted { new_size, .. } => self.original_size as i64 - new_size as i64,
+            FileOutcome::SkippedAnimated | FileOutcome::Unreadable { .. } | FileOutcome::Failed { .. } => 0,
+        }
+    }
+
+    fn saved_percent(&self) -> Option<f64> {
+        match self.outcome {
+            FileOutcome::Converted { .. } if self.original_size > 0 => {
+                Some(self.saved() as f64 / self.original_size as f64 * 100.0)
+            }
+            _ => None,
+        }
+    }
+
+    fn display_name(&self) -> String {
+        self.source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.source.display().to_string())
+    }
+}
+
+impl FileOutcome {
+    fn sort_rank(&self) -> u8 {
+        match self {
+            FileOutcome::Converted { .. } => 0,
+            FileOutcome::SkippedAnimated => 1,
+            FileOutcome::Unreadable { .. } => 2,
+            FileOutcome::Failed { .. } => 3,
+        }
+    }
+}
+
+pub struct ConversionReport {
+    files: Vec<FileReport>,
+}
+
+impl ConversionReport {
+    /// Builds the report, ordering converted files by bytes saved with
+    /// skipped and failed files last
+    pub fn new(mut files: Vec<FileReport>) -> Self {
+        files.sort_by(|a, b| {
+            a.outcome
+                .sort_rank()
+                .cmp(&b.outcome.sort_rank())
+                .then(b.saved().cmp(&a.saved()))
+        });
+        Self { files }
+    }
+
+    pub fn converted_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Converted { .. }))
+            .count()
+    }
+
+    pub fn skipped_animated_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::SkippedAnimated))
+            .count()
+    }
+
+    pub fn unreadable_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Unreadable { .. }))
+            .count()
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Failed { .. }))
+            .count()
+    }
+
+    /// Source paths of the files that were converted
+    pub fn converted_sources(&self) -> impl Iterator<Item = &Path> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Converted { .. }))
+            .map(|f| f.source.as_path())
+    }
+
+    pub fn total_saved(&self) -> i64 {
+        self.files.iter().map(FileReport::saved).sum()
+    }
+
+    pub fn print_table(&self) {
+        let name_width = self
+            .files
+            .iter()
+            .map(|f| f.display_name().chars().count())
+            .max()
+            .unwrap_or(0)
+            .clamp(8, 48);
+
+        println!();
+        println!(
+            "{:<name_width$}  {:>12}  {:>12}  {:>8}",
+            "File", "Original", "New", "Saved"
+        );
+        println!("{}", "-".repeat(name_width + 38));
+
+        for file in &self.files {
+            let name: String = file.display_name().chars().take(name_width).collect();
+            match &file.outcome {
+                FileOutcome::Converted { new_size, notes, .. } => println!(
+                    "{:<name_width$}  {:>12}  {:>12}  {:>7.1}%  {}",
+                    name,
+                    format_size(file.original_size, BINARY),
+                    format_size(*new_size, BINARY),
+                    file.saved_percent().unwrap_or(0.0),
+                    notes.join(", "),
+                ),
+                FileOutcome::SkippedAnimated => println!(
+                    "{:<name_width$}  {:>12}  {:>12}  {:>8}  animated",
+                    name,
+                    format_size(file.original_size, BINARY),
+                    "-",
+                    "skipped",
+                ),
+                FileOutcome::Unreadable { reason } => println!(
+                    "{:<name_width$}  {:>12}  {:>12}  {:>8}  unreadable: {}",
+                    name,
+                    "-",
+                    "-",
+                    "skipped",
+                    reason,
+                ),
+                FileOutcome::Failed { stage, error } => println!(
+                    "{:<name_width$}  {:>12}  {:>12}  {:>8}  {} error: {}",
+                    name,
+                    format_size(file.original_size, BINARY),
+                    "-",
+                    "failed",
+                    stage,
+                    error,
+                ),
+            }
+        }
+
+        let (original, new) = self.converted_totals();
+        println!("{}", "-".repeat(name_width + 38));
+        println!(
+            "{:<name_width$}  {:>12}  {:>12}  {:>7.1}%",
+            format!(
+                "Total ({} converted, {} animated skipped, {} unreadable, {} failed)",
+                self.converted_count(),
+                self.skipped_animated_count(),
+                self.unreadable_count(),
+                self.failed_count()
+            ),
+            format_size(original, BINARY),
+            format_size(new, BINARY),
+            if original > 0 { self.total_saved() as f64 / original as f64 * 100.0 } else { 0.0 },
+        );
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(AtomicFile::create(path)?);
+        writeln!(writer, "source,output,original_size,new_size,saved_bytes,saved_percent,notes,error_stage,error")?;
+
+        for file in &self.files {
+            match &file.outcome {
+                FileOutcome::Converted { output, new_size, notes } => writeln!(
+                    writer,
+                    "{},{},{},{},{},{:.2},{},,",
+                    csv_field(&file.source.display().to_string()),
+                    csv_field(&output.display().to_string()),
+                    file.original_size,
+                    new_size,
+                    file.saved(),
+                    file.saved_percent().unwrap_or(0.0),
+                    csv_field(&notes.join("; ")),
+                )?,
+                FileOutcome::SkippedAnimated => writeln!(
+                    writer,
+                    "{},,{},,,,,,skipped: animated",
+                    csv_field(&file.source.display().to_string()),
+                    file.original_size,
+                )?,
+                FileOutcome::Unreadable { reason } => writeln!(
+                    writer,
+                    "{},,,,,,,,{}",
+                    csv_field(&file.source.display().to_string()),
+                    csv_field(&format!("skipped: unreadable: {}", reason)),
+                )?,
+                FileOutcome::Failed { stage, error } => writeln!(
+                    writer,
+                    "{},,{},,,,,{},{}",
+                    csv_field(&file.source.display().to_string()),
+                    file.original_size,
+                    stage,
+                    csv_field(error),
+                )?,
+            }
+        }
+
+        writer.into_inner()?.commit()
+    }
+
+    /// Writes one tab-separated line per failed file: path, stage, error
+    pub fn write_error_log(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(AtomicFile::create(path)?);
+        for file in &self.files {
+            if let FileOutcome::Failed { stage, error } = &file.outcome {
+                writeln!(writer, "{}\t{}\t{}", file.source.display(), stage, error)?;
+            }
+        }
+        writer.into_inner()?.commit()
+    }
+
+    fn converted_totals(&self) -> (u64, u64) {
+        self.files
+            .iter()
+            .filter_map(|f| match f.outcome {
+                FileOutcome::Converted { new_size, .. } => Some((f.original_size, new_size)),
+                FileOutcome::SkippedAnimated | FileOutcome::Unreadable { .. } | FileOutcome::Failed { .. } => None,
+            })
+            .fold((0, 0), |(o, n), (fo, fn_)| (o + fo, n + fn_))
+    }
+}
+
+/// Formats a signed byte delta as megabytes for the final progress message
+pub fn format_megabytes(bytes: i64) -> String {
+    format!("{:.2} MB", bytes as f64 / (1024.0 * 1024.0))
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+
+pub const DEFAULT_TEMPLATE: &str = "{stem}.{ext}";
+
+/// A parsed output filename template such as `{stem}_{width}x{height}.{ext}`.
+///
+/// Supported tokens: `{stem}`, `{src_ext}` (original extension), `{ext}`
+/// (target extension), `{width}`, `{height}`, `{quality}`, `{date}`
+/// (EXIF capture date or mtime, `YYYY-MM-DD`), and `{n}` / `{n:04}` for a
+/// sequence counter with optional zero padding.
+#[derive(Clone)]
+pub struct NameTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Stem,
+    SourceExtension,
+    Extension,
+    Width,
+    Height,
+    Quality,
+    Date,
+    Sequence { width: usize },
+}
+
+/// Per-file values substituted into the template
+pub struct TemplateContext<'a> {
+    pub stem: &'a str,
+    pub source_extension: &'a str,
+    pub extension: &'a str,
+    pub width: u32,
+    pub height: u32,
+    pub quality: u8,
+    pub date: &'a str,
+    pub sequence: usize,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => {
+                                anyhow::bail!("Unclosed '{{' in template \"{}\"", template)
+                            }
+                            Some(c) => token.push(c),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_token(&token)?);
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in template \"{}\"", template),
+                '/' | '\\' => anyhow::bail!("Template must not contain path separators"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if segments.is_empty() {
+            anyhow::bail!("Template must not be empty");
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Whether this is [`DEFAULT_TEMPLATE`], which keeps each file's stem
+    pub fn is_default(&self) -> bool {
+        self.segments == [Segment::Stem, Segment::Literal(".".to_string()), Segment::Extension]
+    }
+
+    /// Whether rendering needs the image dimensions from the file header
+    pub fn needs_dimensions(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s, Segment::Width | Segment::Height))
+    }
+
+    /// Whether rendering needs the capture or modification date
+    pub fn needs_date(&self) -> bool {
+        self.segments.iter().any(|s| matches!(s, Segment::Date))
+    }
+
+    pub fn render(&self, ctx: &TemplateContext) -> String {
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => name.push_str(text),
+                Segment::Stem => name.push_str(ctx.stem),
+                Segment::SourceExtension => name.push_str(ctx.source_extension),
+                Segment::Extension => name.push_str(ctx.extension),
+                Segment::Width => name.push_str(&ctx.width.to_string()),
+                Segment::Height => name.push_str(&ctx.height.to_string()),
+                Segment::Quality => name.push_str(&ctx.quality.to_string()),
+                Segment::Date => name.push_str(ctx.date),
+                Segment::Sequence { width } => {
+                    name.push_str(&format!("{:0width$}", ctx.sequence, width = *width))
+                }
+            }
+        }
+        name
+    }
+}
+
+fn parse_token(token: &str) -> Result<Segment> {
+    let segment = match token {
+        "stem" => Segment::Stem,
+        "src_ext" => Segment::SourceExtension,
+        "ext" => Segment::Extension,
+        "width" => Segment::Width,
+        "height" => Segment::Height,
+        "quality" => Segment::Quality,
+        "date" => Segment::Date,
+        "n" => Segment::Sequence { width: 0 },
+        _ => match token.strip_prefix("n:") {
+            Some(padding) => Segment::Sequence {
+                width: padding
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid sequence padding in {{{}}}", token))?,
+            },
+            None => anyhow::bail!(
+                "Unknown template token {{{}}} (expected stem, src_ext, ext, width, height, quality, date, n)",
+                token
+            ),
+        },
+    };
+    Ok(segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext<'static> {
+        TemplateContext {
+            stem: "photo",
+            source_extension: "png",
+            extension: "webp",
+            width: 640,
+            height: 480,
+            quality: 80,
+            date: "2024-05-01",
+            sequence: 7,
+        }
+    }
+
+    #[test]
+    fn every_token_renders() {
+        let template = NameTemplate::parse("{stem}_{width}x{height}-q{quality}-{date}-{src_ext}-{n:03}.{ext}").unwrap();
+        assert_eq!(template.render(&context()), "photo_640x480-q80-2024-05-01-png-007.webp");
+        assert!(template.needs_dimensions() && template.needs_date() && !template.is_default());
+    }
+
+    #[test]
+    fn only_the_default_template_is_default() {
+        assert!(NameTemplate::parse(DEFAULT_TEMPLATE).unwrap().is_default());
+        assert!(!NameTemplate::parse("{stem}-{n}.{ext}").unwrap().is_default());
+    }
+
+    #[test]
+    fn typos_fail_up_front() {
+        for bad in ["{stme}.{ext}", "{stem.{ext}", "{stem}}.{ext}", "a/{stem}.{ext}", "{n:x}", ""] {
+            assert!(NameTemplate::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use image::{ColorType, DynamicImage};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Keep,
+    /// Convert to grayscale, quantized to `bit_depth` bits per sample
+    Grayscale { bit_depth: u8 },
+}
+
+/// Applies the color transform, returning a note for the report when the
+/// image was actually changed
+pub fn apply_color_mode(img: DynamicImage, mode: ColorMode) -> (DynamicImage, Option<String>) {
+    let bit_depth = match mode {
+        ColorMode::Keep => return (img, None),
+        ColorMode::Grayscale { bit_depth } => bit_depth,
+    };
+
+    let already_gray = matches!(
+        img.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+    );
+
+    let gray = if already_gray && bit_depth >= 8 {
+        return (img, None);
+    } else if img.color().has_alpha() {
+        DynamicImage::ImageLumaA8(img.to_luma_alpha8())
+    } else {
+        DynamicImage::ImageLuma8(img.to_luma8())
+    };
+
+    if bit_depth >= 8 {
+        return (gray, Some("grayscale".to_string()));
+    }
+
+    let quantized = match gray {
+        DynamicImage::ImageLuma8(mut buffer) => {
+            buffer.pixels_mut().for_each(|p| p.0[0] = quantize(p.0[0], bit_depth));
+            DynamicImage::ImageLuma8(buffer)
+        }
+        DynamicImage::ImageLumaA8(mut buffer) => {
+            buffer.pixels_mut().for_each(|p| p.0[0] = quantize(p.0[0], bit_depth));
+            DynamicImage::ImageLumaA8(buffer)
+        }
+        other => other,
+    };
+
+    (quantized, Some(format!("grayscale {}-bit", bit_depth)))
+}
+
+/// Snaps an 8-bit sample to the nearest of `2^bits` evenly spaced levels
+fn quantize(value: u8, bits: u8) -> u8 {
+    let max_level = (1u16 << bits) - 1;
+    let level = (value as u16 * max_level + 127) / 255;
+    (level * 255 / max_level) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    #[test]
+    fn quantize_snaps_to_evenly_spaced_levels() {
+        assert_eq!((0..=255).map(|v| quantize(v, 1)).collect::<std::collections::BTreeSet<_>>().len(), 2);
+        assert_eq!(quantize(100, 1), 0);
+        assert_eq!(quantize(200, 1), 255);
+        assert_eq!(quantize(90, 2), 85);
+        assert_eq!(quantize(255, 4), 255);
+    }
+
+    #[test]
+    fn gray_images_pass_through_at_8_bits() {
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 4, Luma([77])));
+        let (out, note) = apply_color_mode(gray.clone(), ColorMode::Grayscale { bit_depth: 8 });
+        assert_eq!(out, gray);
+        assert!(note.is_none());
+    }
+
+    #[test]
+    fn color_images_are_made_gray_and_noted() {
+        let color = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([200, 30, 30])));
+        let (out, note) = apply_color_mode(color, ColorMode::Grayscale { bit_depth: 2 });
+        assert_eq!(out.color(), ColorType::L8);
+        assert_eq!(note.as_deref(), Some("grayscale 2-bit"));
+        assert!(out.to_luma8().pixels().all(|p| [0, 85, 170, 255].contains(&p.0[0])));
+    }
+
+    #[test]
+    fn keep_leaves_the_image_alone() {
+        let color = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([1, 2, 3])));
+        let (out, note) = apply_color_mode(color.clone(), ColorMode::Keep);
+        assert_eq!(out, color);
+        assert!(note.is_none());
+    }
+}
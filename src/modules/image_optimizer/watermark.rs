@@ -0,0 +1,304 @@
+use anyhow::Result;
+use image::{imageops, ColorType, DynamicImage, Rgba, RgbaImage};
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+pub enum WatermarkKind {
+    /// Text rendered with the built-in bitmap font; `size_percent` is the
+    /// glyph height relative to the image height
+    Text { text: String, size_percent: f32 },
+    /// A pre-decoded overlay image
+    Image(RgbaImage),
+}
+
+/// A watermark decoded once up front and shared by every worker
+pub struct Watermark {
+    pub kind: WatermarkKind,
+    pub anchor: Anchor,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    pub opacity: f32,
+    pub padding: u32,
+}
+
+impl Watermark {
+    pub fn text(text: String, size_percent: f32, anchor: Anchor, opacity: f32, padding: u32) -> Self {
+        Self {
+            kind: WatermarkKind::Text { text, size_percent },
+            anchor,
+            opacity,
+            padding,
+        }
+    }
+
+    pub fn image(path: &Path, anchor: Anchor, opacity: f32, padding: u32) -> Result<Self> {
+        let overlay = image::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to load watermark {}: {}", path.display(), e))?
+            .to_rgba8();
+        Ok(Self {
+            kind: WatermarkKind::Image(overlay),
+            anchor,
+            opacity,
+            padding,
+        })
+    }
+
+    /// Composites the watermark onto `img`, keeping its original color type
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let color = img.color();
+        let mut canvas = img.to_rgba8();
+        let (width, height) = canvas.dimensions();
+        let padding = self.padding.min(width / 4).min(height / 4);
+        let max_width = width.saturating_sub(padding * 2).max(1);
+        let max_height = height.saturating_sub(padding * 2).max(1);
+
+        let overlay = match &self.kind {
+            WatermarkKind::Text { text, size_percent } => {
+                let glyph_height = (height as f32 * size_percent / 100.0).max(GLYPH_ROWS as f32);
+                let scale = (glyph_height / GLYPH_ROWS as f32).round() as u32;
+                render_text(text, scale.max(1))
+            }
+            WatermarkKind::Image(overlay) => overlay.clone(),
+        };
+
+        // Inputs smaller than the watermark get a proportionally shrunk one
+        let overlay = if overlay.width() > max_width || overlay.height() > max_height {
+            let ratio = (max_width as f32 / overlay.width() as f32)
+                .min(max_height as f32 / overlay.height() as f32);
+            let new_width = ((overlay.width() as f32 * ratio) as u32).max(1);
+            let new_height = ((overlay.height() as f32 * ratio) as u32).max(1);
+            imageops::resize(&overlay, new_width, new_height, imageops::FilterType::Triangle)
+        } else {
+            overlay
+        };
+
+        let (x, y) = self.position(width, height, overlay.width(), overlay.height(), padding);
+        blend(&mut canvas, &overlay, x, y, self.opacity);
+
+        let result = DynamicImage::ImageRgba8(canvas);
+        match color {
+            ColorType::L8 | ColorType::L16 => DynamicImage::ImageLuma8(result.to_luma8()),
+            ColorType::La8 | ColorType::La16 => DynamicImage::ImageLumaA8(result.to_luma_alpha8()),
+            ColorType::Rgb8 | ColorType::Rgb16 | ColorType::Rgb32F => {
+                DynamicImage::ImageRgb8(result.to_rgb8())
+            }
+            _ => result,
+        }
+    }
+
+    fn position(&self, width: u32, height: u32, w: u32, h: u32, padding: u32) -> (u32, u32) {
+        let right = width.saturating_sub(w + padding);
+        let bottom = height.saturating_sub(h + padding);
+        match self.anchor {
+            Anchor::TopLeft => (padding, padding),
+            Anchor::TopRight => (right, padding),
+            Anchor::BottomLeft => (padding, bottom),
+            Anchor::BottomRight => (right, bottom),
+            Anchor::Center => (width.saturating_sub(w) / 2, height.saturating_sub(h) / 2),
+        }
+    }
+}
+
+fn blend(canvas: &mut RgbaImage, overlay: &RgbaImage, x: u32, y: u32, opacity: f32) {
+    for (ox, oy, pixel) in overlay.enumerate_pixels() {
+        let (cx, cy) = (x + ox, y + oy);
+        if cx >= canvas.width() || cy >= canvas.height() {
+            continue;
+        }
+
+        let alpha = pixel[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            continue;
+        }
+
+        let base = canvas.get_pixel_mut(cx, cy);
+        for channel in 0..3 {
+            let blended = pixel[channel] as f32 * alpha + base[channel] as f32 * (1.0 - alpha);
+            base[channel] = blended.round() as u8;
+        }
+        base[3] = base[3].max((alpha * 255.0) as u8);
+    }
+}
+
+const GLYPH_COLS: u32 = 5;
+const GLYPH_ROWS: u32 = 7;
+
+/// Renders `text` in white using the built-in 5x7 font, with one blank
+/// column between glyphs. Lowercase letters are drawn as uppercase.
+fn render_text(text: &str, scale: u32) -> RgbaImage {
+    let chars: Vec<char> = text.chars().collect();
+    let advance = (GLYPH_COLS + 1) * scale;
+    let width = (advance * chars.len() as u32).max(1);
+    let mut canvas = RgbaImage::new(width, GLYPH_ROWS * scale);
+
+    for (index, c) in chars.iter().enumerate() {
+        let rows = glyph(*c);
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_COLS {
+                if bits & (1 << (GLYPH_COLS - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        canvas.put_pixel(
+                            index as u32 * advance + col * scale + dx,
+                            row as u32 * scale + dy,
+                            Rgba([255, 255, 255, 255]),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    canvas
+}
+
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0; 7],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '@' => [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '&' => [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        '\'' => [0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '©' => [0b01110, 0b10001, 0b10111, 0b10101, 0b10111, 0b10001, 0b01110],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    const BACKGROUND: Rgb<u8> = Rgb([100, 100, 100]);
+
+    fn background(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, BACKGROUND))
+    }
+
+    fn overlay(width: u32, height: u32, color: [u8; 4], anchor: Anchor, opacity: f32, padding: u32) -> Watermark {
+        Watermark {
+            kind: WatermarkKind::Image(RgbaImage::from_pixel(width, height, Rgba(color))),
+            anchor,
+            opacity,
+            padding,
+        }
+    }
+
+    fn assert_close(actual: Rgb<u8>, expected: [u8; 3]) {
+        let off = actual.0.iter().zip(expected).any(|(&a, e)| a.abs_diff(e) > 1);
+        assert!(!off, "expected {:?}, got {:?}", expected, actual.0);
+    }
+
+    #[test]
+    fn overlay_is_blended_at_its_anchor() {
+        let watermark = overlay(10, 10, [255, 0, 0, 255], Anchor::BottomRight, 0.5, 5);
+        let out = watermark.apply(background(100, 80)).to_rgb8();
+        // 5 px in from the bottom right corner
+        for (x, y) in [(85, 65), (94, 74), (90, 70)] {
+            assert_close(*out.get_pixel(x, y), [178, 50, 50]);
+        }
+        for (x, y) in [(84, 65), (95, 74), (94, 75), (0, 0), (10, 70)] {
+            assert_eq!(*out.get_pixel(x, y), BACKGROUND, "({}, {}) lies outside the watermark", x, y);
+        }
+    }
+
+    #[test]
+    fn overlay_alpha_scales_with_opacity() {
+        let watermark = overlay(4, 4, [0, 0, 255, 128], Anchor::TopLeft, 1.0, 0);
+        let out = watermark.apply(background(20, 20)).to_rgb8();
+        assert_close(*out.get_pixel(0, 0), [50, 50, 178]);
+        assert_eq!(*out.get_pixel(4, 4), BACKGROUND);
+    }
+
+    #[test]
+    fn overlay_larger_than_the_input_is_scaled_down() {
+        let watermark = overlay(40, 40, [255, 255, 255, 255], Anchor::Center, 1.0, 0);
+        let out = watermark.apply(background(20, 10));
+        assert_eq!((out.width(), out.height()), (20, 10));
+        let out = out.to_rgb8();
+        // Shrunk to 10x10 and centered
+        assert_close(*out.get_pixel(5, 0), [255, 255, 255]);
+        assert_close(*out.get_pixel(14, 9), [255, 255, 255]);
+        assert_eq!(*out.get_pixel(4, 5), BACKGROUND);
+        assert_eq!(*out.get_pixel(15, 5), BACKGROUND);
+    }
+
+    #[test]
+    fn text_is_drawn_with_the_bitmap_font() {
+        // 10% of 70 px is one glyph pixel per font pixel
+        let watermark = Watermark::text("I".to_string(), 10.0, Anchor::TopLeft, 1.0, 2);
+        let out = watermark.apply(background(100, 70)).to_rgb8();
+        let glyph_rows = glyph('I');
+        for (row, bits) in glyph_rows.iter().enumerate() {
+            for col in 0..GLYPH_COLS {
+                let pixel = *out.get_pixel(2 + col, 2 + row as u32);
+                if bits & (1 << (GLYPH_COLS - 1 - col)) != 0 {
+                    assert_close(pixel, [255, 255, 255]);
+                } else {
+                    assert_eq!(pixel, BACKGROUND);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn color_type_is_kept() {
+        let watermark = overlay(2, 2, [255, 0, 0, 255], Anchor::TopLeft, 1.0, 0);
+        assert_eq!(watermark.apply(background(8, 8)).color(), ColorType::Rgb8);
+        let gray = DynamicImage::ImageLuma8(image::GrayImage::new(8, 8));
+        assert_eq!(watermark.apply(gray).color(), ColorType::L8);
+    }
+}
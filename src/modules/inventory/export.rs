@@ -0,0 +1,96 @@
+use anyhow::Result;
+use chrono::Local;
+use serde_json::Value;
+use std::{
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{Column, InventoryFormat, InventoryRecord};
+use crate::utils::{atomic::AtomicFile, csv_field};
+
+/// `inventory-<timestamp>.<ext>` in the current directory
+pub fn default_path(format: InventoryFormat) -> PathBuf {
+    PathBuf::from(format!(
+        "inventory-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Whether `path` stands for standard output
+pub fn is_stdout(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Writes `columns` of every record, as CSV with a header line or as one
+/// JSON object per line. Missing values are empty in CSV and `null` in
+/// JSON.
+pub fn write(records: &[InventoryRecord], columns: &[Column], mut out: impl Write, format: InventoryFormat) -> Result<()> {
+    match format {
+        InventoryFormat::Csv => {
+            let header: Vec<&str> = columns.iter().map(|column| column.name()).collect();
+            writeln!(out, "{}", header.join(","))?;
+            for record in records {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .map(|&column| match value(record, column) {
+                        Value::Null => String::new(),
+                        Value::String(text) => csv_field(&text),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+        InventoryFormat::JsonLines => {
+            // Written by hand so the keys keep the order the columns were asked in
+            for record in records {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .map(|&column| format!("\"{}\":{}", column.name(), value(record, column)))
+                    .collect();
+                writeln!(out, "{{{}}}", fields.join(","))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// [`write`] into a file at `path`, replacing it only once complete, or to
+/// standard output for `-`
+pub fn save(records: &[InventoryRecord], columns: &[Column], path: &Path, format: InventoryFormat) -> Result<()> {
+    if is_stdout(path) {
+        return write(records, columns, io::stdout().lock(), format);
+    }
+    let mut file = BufWriter::new(AtomicFile::create(path)?);
+    write(records, columns, &mut file, format)?;
+    file.into_inner()?.commit()?;
+    Ok(())
+}
+
+fn value(record: &InventoryRecord, column: Column) -> Value {
+    let text = |text: &Option<String>| text.clone().map_or(Value::Null, Value::String);
+    match column {
+        // Forward slashes on every platform, so inventories compare equal
+        Column::Path => Value::String(
+            record
+                .path
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+        ),
+        Column::Size => record.size.into(),
+        Column::Modified => text(&record.modified),
+        Column::Changed => text(&record.changed),
+        Column::Extension => text(&record.extension),
+        Column::Mime => record.mime.map_or(Value::Null, Value::from),
+        Column::Hash => text(&record.hash),
+        Column::Width => record.width.map_or(Value::Null, Value::from),
+        Column::Height => record.height.map_or(Value::Null, Value::from),
+        Column::ExifDate => text(&record.exif_date),
+        Column::Duration => record.duration.map_or(Value::Null, Value::from),
+    }
+}
@@ -0,0 +1,678 @@
+pub mod export;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, SecondsFormat};
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use crate::utils::prompt::{Confirm, Select};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_categorizer::content;
+use crate::utils::{
+    exif,
+    filter::PathFilter,
+    get_directory_from_user,
+    hash_cache::HashCache,
+    hashing::{Algorithm, Hashing},
+    junk::JunkList,
+    links,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    video,
+    walk::{is_access_denied, Inaccessible, WalkEntry, Walker},
+};
+
+/// Exports one row per file under a directory with its size, times, type,
+/// and optionally digest and image details, for dashboards or as a
+/// snapshot to diff against a later one
+pub struct InventoryExporter {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// `None` asks
+    format: Option<InventoryFormat>,
+    /// `-` writes to standard output; `None` writes
+    /// `inventory-<timestamp>.<ext>` in the current directory
+    output: Option<PathBuf>,
+    /// Exactly these columns, in this order; `None` takes the defaults
+    /// plus whatever hashing and media ask for
+    columns: Option<Vec<Column>>,
+    /// `None` asks; `Some(None)` does not hash
+    algorithm: Option<Option<Algorithm>>,
+    /// Add image and video dimensions, EXIF dates, and video durations;
+    /// `None` asks
+    media: Option<bool>,
+    /// Glob patterns a file's relative path must match to be listed
+    include: Vec<String>,
+    /// Glob patterns that leave a file out
+    exclude: Vec<String>,
+    /// List git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Walk into symlinked directories and list the files links point to
+    follow_links: bool,
+    /// Reuse and keep digests of unchanged files between runs
+    hash_cache: bool,
+    /// Abort on the first file that cannot be read
+    fail_fast: bool,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// A field of an inventory row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Column {
+    /// Relative to the input directory
+    Path,
+    Size,
+    /// Last modification
+    Modified,
+    /// Last status change on Unix, creation elsewhere
+    Changed,
+    /// Lowercase, without the dot
+    Extension,
+    /// Detected from the contents, not the extension
+    Mime,
+    Hash,
+    /// Of images and videos
+    Width,
+    Height,
+    /// EXIF capture date of photos
+    ExifDate,
+    /// Length of videos, in seconds
+    Duration,
+}
+
+impl Column {
+    /// Every column, in the default order
+    pub const ALL: [Column; 11] = [
+        Column::Path,
+        Column::Size,
+        Column::Modified,
+        Column::Changed,
+        Column::Extension,
+        Column::Mime,
+        Column::Hash,
+        Column::Width,
+        Column::Height,
+        Column::ExifDate,
+        Column::Duration,
+    ];
+
+    /// Columns that need nothing but the walk and a peek at the contents
+    pub const BASIC: [Column; 6] = [
+        Column::Path,
+        Column::Size,
+        Column::Modified,
+        Column::Changed,
+        Column::Extension,
+        Column::Mime,
+    ];
+
+    /// Columns read from images and videos
+    pub const MEDIA: [Column; 4] = [Column::Width, Column::Height, Column::ExifDate, Column::Duration];
+
+    /// As written in CSV headers and JSON keys
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Path => "path",
+            Self::Size => "size",
+            Self::Modified => "mtime",
+            Self::Changed => "ctime",
+            Self::Extension => "extension",
+            Self::Mime => "mime",
+            Self::Hash => "hash",
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::ExifDate => "exif_date",
+            Self::Duration => "duration",
+        }
+    }
+
+    /// Parses a column by its [`name`](Self::name)
+    pub fn from_name(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|column| column.name() == name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown column: {} (expected {})",
+                name,
+                Self::ALL.map(Column::name).join(", ")
+            )
+        })
+    }
+}
+
+/// How an inventory is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InventoryFormat {
+    /// A header line, then one row per file
+    Csv,
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl InventoryFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// Everything an export needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// List working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Known junk, left out
+    #[serde(skip)]
+    pub junk: JunkList,
+    /// Walk into symlinked directories and list the files links point
+    /// to; otherwise links are left out
+    pub follow_links: bool,
+    /// In order; only what they need is read from each file
+    pub columns: Vec<Column>,
+    /// For the hash column
+    pub algorithm: Algorithm,
+    /// Where digests of unchanged files are kept between runs, shared with
+    /// deduplication and checksums; `None` reads every file
+    pub hash_cache: Option<PathBuf>,
+    pub format: InventoryFormat,
+    /// Where the inventory is written; `-` is standard output. A file
+    /// inside the tree is not listed in itself.
+    pub output: PathBuf,
+    /// Abort on the first file that cannot be read instead of recording it
+    /// and carrying on
+    pub fail_fast: bool,
+}
+
+impl InventoryOptions {
+    /// The basic columns of every file under `input_dir`, recursively, as
+    /// CSV into `output`
+    pub fn new(input_dir: PathBuf, output: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            junk: JunkList::default(),
+            follow_links: false,
+            columns: Column::BASIC.to_vec(),
+            algorithm: Algorithm::Sha256,
+            hash_cache: None,
+            format: InventoryFormat::Csv,
+            output,
+            fail_fast: false,
+        }
+    }
+
+    fn wants(&self, columns: &[Column]) -> bool {
+        self.columns.iter().any(|column| columns.contains(column))
+    }
+}
+
+/// What is known about one file; fields whose column was not asked for,
+/// or that could not be read, are `None`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InventoryRecord {
+    pub path: PathBuf,
+    pub size: u64,
+    /// RFC 3339, local time
+    pub modified: Option<String>,
+    /// RFC 3339, local time
+    pub changed: Option<String>,
+    pub extension: Option<String>,
+    pub mime: Option<&'static str>,
+    pub hash: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// `YYYY-MM-DDTHH:MM:SS`, in the camera's time
+    pub exif_date: Option<String>,
+    /// Seconds, to the millisecond
+    pub duration: Option<f64>,
+}
+
+/// Result of [`export`]
+#[derive(Debug, Default, Serialize)]
+pub struct InventorySummary {
+    pub output: PathBuf,
+    /// Rows written
+    pub files: usize,
+    pub bytes: u64,
+    /// Digests that came from the hash cache instead of being read
+    pub cached: usize,
+    pub junk: usize,
+    /// Working copies that were left out
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be read, and have no row
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&InventorySummary> for Summary {
+    fn from(summary: &InventorySummary) -> Self {
+        Summary {
+            files: summary.files,
+            bytes: summary.bytes,
+            skipped: 0,
+            junk: summary.junk,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Walks the input directory and writes a row for every file, sorted by
+/// path so two inventories of the same tree can be diffed line by line.
+/// Files are read in parallel, and only as far as the columns need: the
+/// first few KiB for the MIME type, image headers and EXIF or video
+/// container metadata for media, the whole file for the hash, through the
+/// hash cache.
+///
+/// ```
+/// use file_organizer_rust::modules::inventory::{self, Column, InventoryOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir(dir.path().join("docs"))?;
+/// std::fs::write(dir.path().join("docs/notes.txt"), "hello")?;
+/// std::fs::write(dir.path().join("logo.png"), b"\x89PNG\r\n\x1a\nrest")?;
+/// let output = dir.path().join("inventory.csv");
+///
+/// let options = InventoryOptions {
+///     columns: vec![Column::Path, Column::Size, Column::Mime, Column::Hash],
+///     ..InventoryOptions::new(dir.path().to_path_buf(), output.clone())
+/// };
+/// let summary = inventory::export(&options, ())?;
+/// assert_eq!(summary.files, 2);
+/// assert_eq!(
+///     std::fs::read_to_string(&output)?,
+///     "path,size,mime,hash\n\
+///      docs/notes.txt,5,text/plain,2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824\n\
+///      logo.png,12,image/png,f02a830cf03a1cf756c0461481db682bc9254a5a399f847494d33850c3ff7156\n"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn export(options: &InventoryOptions, progress: impl ProgressSink) -> Result<InventorySummary> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    if options.columns.is_empty() {
+        anyhow::bail!("At least one column is needed");
+    }
+
+    progress.scan_started(root);
+    let output = (!export::is_stdout(&options.output)).then(|| links::absolute(&options.output)).transpose()?;
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .junk(Some(options.junk.clone()))
+        .follow_links(options.follow_links)
+        .symlinks(options.follow_links)
+        .sorted(true)
+        .files(root);
+    let files: Vec<WalkEntry> = walk
+        .files
+        .into_iter()
+        .filter(|entry| output.is_none() || links::absolute(&entry.path).ok() != output)
+        .collect();
+    let mut summary = InventorySummary {
+        output: options.output.clone(),
+        junk: walk.junk.len(),
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..InventorySummary::default()
+    };
+
+    progress.phase_changed("reading", files.len() as u64);
+    let hashing = Hashing::new(options.algorithm);
+    let cache = options
+        .wants(&[Column::Hash])
+        .then(|| HashCache::open(options.hash_cache.as_deref()));
+    let errors = ErrorLog::new(options.fail_fast);
+    let inaccessible = Mutex::new(Vec::new());
+    let records = files
+        .par_iter()
+        .map(|entry| {
+            progress.item_started(&entry.path);
+            let (record, outcome) = match record(options, root, entry, &hashing, cache.as_ref()) {
+                Err(e) if is_access_denied(&e) => {
+                    inaccessible.lock().unwrap().push(Inaccessible::new(&entry.path, &e));
+                    (None, Outcome::Skipped)
+                }
+                result => match errors.check(&entry.path, "read", result)? {
+                    Some(record) => (Some(record), Outcome::Done),
+                    None => (None, Outcome::Failed),
+                },
+            };
+            progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+            Ok(record)
+        })
+        .collect::<Result<Vec<_>>>();
+    if let Some(cache) = &cache {
+        // The cache only saves time, so failing to write it fails nothing
+        let _ = cache.save();
+    }
+    let mut rows = Vec::new();
+    for (record, cached) in records?.into_iter().flatten() {
+        summary.cached += usize::from(cached);
+        rows.push(record);
+    }
+    let records = rows;
+
+    export::save(&records, &options.columns, &options.output, options.format)?;
+    summary.files = records.len();
+    summary.bytes = records.iter().map(|record| record.size).sum();
+    summary.errors = errors.into_errors();
+    summary.inaccessible.extend(inaccessible.into_inner().unwrap());
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+/// The row for one file, and whether its digest came from the hash cache
+fn record(
+    options: &InventoryOptions,
+    root: &Path,
+    entry: &WalkEntry,
+    hashing: &Hashing,
+    cache: Option<&HashCache>,
+) -> Result<(InventoryRecord, bool)> {
+    let wants = |column| options.columns.contains(&column);
+    let metadata = &entry.metadata;
+    let mut record = InventoryRecord {
+        path: entry.path.strip_prefix(root).unwrap_or(&entry.path).to_path_buf(),
+        size: metadata.len(),
+        ..InventoryRecord::default()
+    };
+    if wants(Column::Modified) {
+        record.modified = metadata.modified().ok().map(timestamp);
+    }
+    if wants(Column::Changed) {
+        record.changed = changed(metadata).map(timestamp);
+    }
+    if wants(Column::Extension) {
+        record.extension = entry.path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    }
+    if options.wants(&[Column::Mime, Column::Width, Column::Height, Column::ExifDate, Column::Duration]) {
+        let mime = content::mime_type(&entry.path)?;
+        record.mime = wants(Column::Mime).then_some(mime);
+        // Only images and videos are opened for media columns; anything
+        // the decoders or EXIF reader do not understand is left empty
+        if mime.starts_with("image/") && options.wants(&Column::MEDIA) {
+            if let Ok((width, height)) = image::image_dimensions(&entry.path) {
+                record.width = wants(Column::Width).then_some(width);
+                record.height = wants(Column::Height).then_some(height);
+            }
+            if wants(Column::ExifDate) {
+                record.exif_date = exif::capture_date(&entry.path).map(|date| date.format("%Y-%m-%dT%H:%M:%S").to_string());
+            }
+        }
+        // Videos are read from their container, or through ffprobe when
+        // it is installed
+        if mime.starts_with("video/") && options.wants(&[Column::Width, Column::Height, Column::Duration]) {
+            if let Some(info) = video::probe(&entry.path) {
+                record.width = info.width.filter(|_| wants(Column::Width));
+                record.height = info.height.filter(|_| wants(Column::Height));
+                record.duration = info
+                    .duration
+                    .filter(|_| wants(Column::Duration))
+                    .map(|duration| (duration.as_secs_f64() * 1000.0).round() / 1000.0);
+            }
+        }
+    }
+    let mut cached = false;
+    if let Some(cache) = cache {
+        let (digest, from_cache) = cache.digest(hashing, &entry.path, metadata)?;
+        record.hash = Some(digest);
+        cached = from_cache;
+    }
+    Ok((record, cached))
+}
+
+fn timestamp(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+/// When the inode last changed: contents, permissions, owner, or links
+#[cfg(unix)]
+fn changed(metadata: &Metadata) -> Option<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let since_epoch = Duration::new(u64::try_from(metadata.ctime()).ok()?, metadata.ctime_nsec() as u32);
+    Some(SystemTime::UNIX_EPOCH + since_epoch)
+}
+
+/// Windows has no status change time; creation is the closest there is
+#[cfg(not(unix))]
+fn changed(metadata: &Metadata) -> Option<SystemTime> {
+    metadata.created().ok()
+}
+
+#[async_trait]
+impl FileOrganizer for InventoryExporter {
+    type Plan = InventoryOptions;
+    const NAME: &'static str = "inventory";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            format: None,
+            output: None,
+            columns: None,
+            algorithm: None,
+            media: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            follow_links: false,
+            hash_cache: true,
+            fail_fast: false,
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to take an inventory of")?);
+        }
+        if self.format.is_none() {
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select output format")
+                .items(&["CSV", "JSON Lines"])
+                .default(0)
+                .interact()?;
+            self.format = Some([InventoryFormat::Csv, InventoryFormat::JsonLines][choice]);
+        }
+        // Chosen columns already say whether to hash and read images
+        if self.columns.is_none() {
+            if self.algorithm.is_none() {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Hash every file?")
+                    .items(&["No", "SHA-256", "BLAKE3 (faster)", "XXH3 (fastest, not tamper-proof)"])
+                    .default(0)
+                    .interact()?;
+                self.algorithm = Some([None, Some(Algorithm::Sha256), Some(Algorithm::Blake3), Some(Algorithm::Xxh3)][choice]);
+            }
+            if self.media.is_none() {
+                self.media = Some(
+                    Confirm::with_theme(&ColorfulTheme::default())
+                        .with_prompt("Add image and video dimensions, EXIF dates, and durations?")
+                        .default(false)
+                        .interact()?,
+                );
+            }
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<InventoryOptions> {
+        self.options()
+    }
+
+    /// Shows a bar while files are read, unless the inventory goes to
+    /// standard output, which then holds nothing else
+    fn execute(&self, options: InventoryOptions) -> Result<Summary> {
+        if export::is_stdout(&options.output) {
+            return Ok(Summary::from(&export(&options, ())?));
+        }
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        bar.set_message("scanning...");
+        bar.enable_steady_tick(Duration::from_millis(100));
+        self.metrics.phase("reading");
+        let summary = export(&options, (&bar, &self.metrics))?;
+        bar.finish_with_message(format!(
+            "Wrote {} ({} files, {}, {} digests from the hash cache)",
+            summary.output.display(),
+            summary.files,
+            format_size(summary.bytes, BINARY),
+            summary.cached
+        ));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to list them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "the inventory leaves them out");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl InventoryExporter {
+    /// Presets the directory to take an inventory of so the prompt is
+    /// skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    pub fn with_format(mut self, format: InventoryFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Writes the inventory to `path`, or to standard output for `-`,
+    /// instead of `inventory-<timestamp>.<ext>` in the current directory
+    pub fn with_output(mut self, path: Option<PathBuf>) -> Self {
+        self.output = path;
+        self
+    }
+
+    /// Writes exactly `columns`, in this order, whatever hashing and media
+    /// are set to; empty keeps the defaults
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        self.columns = (!columns.is_empty()).then_some(columns);
+        self
+    }
+
+    /// Adds a hash column with `algorithm`, or with `None` leaves it out.
+    /// With chosen columns, only picks the algorithm.
+    pub fn with_algorithm(mut self, algorithm: Option<Algorithm>) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
+
+    /// Whether to add width, height, EXIF date, and duration columns
+    pub fn with_media(mut self, media: bool) -> Self {
+        self.media = Some(media);
+        self
+    }
+
+    /// Only lists files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.jpg" or "projects/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves out files whose relative path matches one of `patterns`
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Lists files inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Walks into symlinked directories and lists the files links point to
+    pub fn with_follow_links(mut self, follow: bool) -> Self {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Whether digests of unchanged files are reused from earlier runs
+    pub fn with_hash_cache(mut self, hash_cache: bool) -> Self {
+        self.hash_cache = hash_cache;
+        self
+    }
+
+    /// Aborts on the first file that cannot be read
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<InventoryOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let format = self.format.unwrap_or(InventoryFormat::Csv);
+        let algorithm = self.algorithm.flatten();
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => {
+                let mut columns = Column::BASIC.to_vec();
+                if algorithm.is_some() {
+                    columns.push(Column::Hash);
+                }
+                if self.media == Some(true) {
+                    columns.extend(Column::MEDIA);
+                }
+                columns
+            }
+        };
+        Ok(InventoryOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            junk: self.junk.clone(),
+            follow_links: self.follow_links,
+            columns,
+            algorithm: algorithm.unwrap_or(Algorithm::Sha256),
+            hash_cache: if self.hash_cache { Some(HashCache::default_path()?) } else { None },
+            format,
+            fail_fast: self.fail_fast,
+            ..InventoryOptions::new(input_dir, self.output.clone().unwrap_or_else(|| export::default_path(format)))
+        })
+    }
+}
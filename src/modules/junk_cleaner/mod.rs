@@ -0,0 +1,377 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::utils::prompt::Confirm;
+use crate::utils::{
+    create_spinner,
+    delete::{safe_delete_all, DeleteMode},
+    get_directory_from_user,
+    junk::JunkList,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    walk::{Inaccessible, Walker},
+};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+
+/// Deletes the files and directories operating systems and file managers
+/// leave behind (`.DS_Store`, `Thumbs.db`, `@eaDir`, ...), as listed by
+/// [`JunkList`]
+pub struct JunkCleaner {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Clean inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Abort on the first entry that cannot be deleted
+    fail_fast: bool,
+    delete_mode: DeleteMode,
+    junk: JunkList,
+    metrics: Metrics,
+}
+
+/// Everything a cleaning run needs, with no prompting
+pub struct CleanOptions {
+    pub input_dirs: Vec<PathBuf>,
+    pub recursive: bool,
+    /// Clean inside git, Mercurial, and Subversion working copies too
+    pub include_repos: bool,
+    /// What counts as junk
+    pub junk: JunkList,
+    pub delete_mode: DeleteMode,
+    /// Abort on the first per-entry error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl CleanOptions {
+    /// A recursive clean of `input_dirs` with the built-in junk list that
+    /// moves junk to the trash
+    pub fn new(input_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            input_dirs,
+            recursive: true,
+            include_repos: false,
+            junk: JunkList::default(),
+            delete_mode: DeleteMode::Trash,
+            fail_fast: false,
+        }
+    }
+}
+
+/// A junk file, or a junk directory with everything in it
+#[derive(Debug, Clone, Serialize)]
+pub struct JunkEntry {
+    pub path: PathBuf,
+    /// Bytes held, counting every file inside a directory
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Junk found by [`plan`], before anything is deleted
+#[derive(Debug, Default, Serialize)]
+pub struct JunkPlan {
+    pub entries: Vec<JunkEntry>,
+    /// Working copies that were not walked into
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be read; junk in them was not
+    /// looked for
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl JunkPlan {
+    pub fn bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+
+    /// Lists how many entries of each name were found, most frequent first
+    pub fn print_report(&self) {
+        let mut by_name: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        for entry in &self.entries {
+            let mut name = entry.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if name.starts_with("._") {
+                name = "._* (resource forks)".to_string();
+            } else if entry.is_dir {
+                name.push('/');
+            }
+            let count = by_name.entry(name).or_default();
+            count.0 += 1;
+            count.1 += entry.size;
+        }
+        let mut rows: Vec<_> = by_name.into_iter().collect();
+        rows.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+
+        println!();
+        for (name, (count, bytes)) in &rows {
+            println!("{:<32} {:>8} {:>12}", name, count, format_size(*bytes, BINARY));
+        }
+        println!(
+            "{:<32} {:>8} {:>12}",
+            "Total",
+            self.entries.len(),
+            format_size(self.bytes(), BINARY)
+        );
+    }
+}
+
+/// Outcome of [`execute`]
+#[derive(Debug, Default, Serialize)]
+pub struct CleanSummary {
+    /// Entries deleted, or under `DeleteMode::DryRun` the ones that would be
+    pub removed: Vec<JunkEntry>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Entries that could not be deleted
+    pub errors: Vec<FileError>,
+    /// Files and directories that could not be read
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl CleanSummary {
+    pub fn bytes(&self) -> u64 {
+        self.removed.iter().map(|entry| entry.size).sum()
+    }
+}
+
+impl From<&CleanSummary> for Summary {
+    fn from(summary: &CleanSummary) -> Self {
+        Summary {
+            files: summary.removed.len(),
+            bytes: summary.bytes(),
+            skipped: 0,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Finds every junk file and junk directory under the input directories.
+/// Nothing inside a junk directory is listed on its own.
+pub fn plan(options: &CleanOptions, progress: impl ProgressSink) -> Result<JunkPlan> {
+    if options.input_dirs.is_empty() {
+        anyhow::bail!("No input directory given");
+    }
+    for dir in &options.input_dirs {
+        progress.scan_started(dir);
+    }
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .symlinks(true)
+        .junk(Some(options.junk.clone()))
+        .files_in(&options.input_dirs);
+
+    let entries = walk
+        .junk
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            let is_dir = metadata.is_dir();
+            let size = if is_dir { dir_size(&path) } else { metadata.len() };
+            Some(JunkEntry { path, size, is_dir })
+        })
+        .collect();
+    Ok(JunkPlan {
+        entries,
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+    })
+}
+
+/// Bytes held by the files under `dir`, not following symlinks
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Deletes every entry in `plan` the way `options.delete_mode` asks
+pub fn apply(options: &CleanOptions, plan: JunkPlan, progress: impl ProgressSink) -> Result<CleanSummary> {
+    progress.phase_changed("Deleting", plan.entries.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let results = safe_delete_all(
+        &plan.entries.iter().map(|entry| entry.path.as_path()).collect::<Vec<_>>(),
+        options.delete_mode,
+    );
+    let mut removed = Vec::new();
+    for (entry, result) in plan.entries.into_iter().zip(results) {
+        match errors.check(&entry.path, "delete", result)? {
+            Some(()) => {
+                progress.item_finished(&entry.path, entry.size, Outcome::Done);
+                removed.push(entry);
+            }
+            None => progress.item_finished(&entry.path, entry.size, Outcome::Failed),
+        }
+    }
+
+    Ok(CleanSummary {
+        removed,
+        repositories: plan.repositories,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    })
+}
+
+/// Finds the junk under the input directories and deletes it
+///
+/// ```
+/// use file_organizer_rust::modules::junk_cleaner::{self, CleanOptions};
+/// use file_organizer_rust::utils::delete::DeleteMode;
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join(".DS_Store"), "")?;
+/// std::fs::create_dir(dir.path().join("@eaDir"))?;
+/// std::fs::write(dir.path().join("@eaDir").join("thumb.jpg"), "jpeg")?;
+/// std::fs::write(dir.path().join("photo.jpg"), "jpeg")?;
+///
+/// let options = CleanOptions {
+///     delete_mode: DeleteMode::Permanent,
+///     ..CleanOptions::new(vec![dir.path().to_path_buf()])
+/// };
+/// let summary = junk_cleaner::execute(&options, ())?;
+/// assert_eq!(summary.removed.len(), 2);
+/// assert!(dir.path().join("photo.jpg").exists());
+/// assert!(!dir.path().join("@eaDir").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute(options: &CleanOptions, progress: impl ProgressSink) -> Result<CleanSummary> {
+    let plan = plan(options, &progress)?;
+    let summary = apply(options, plan, &progress)?;
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+#[async_trait]
+impl FileOrganizer for JunkCleaner {
+    type Plan = JunkPlan;
+    const NAME: &'static str = "clean-junk";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include_repos: false,
+            fail_fast: false,
+            delete_mode: DeleteMode::default(),
+            junk: JunkList::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to clean of junk files")?);
+        }
+        self.junk = JunkList::load()?;
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<JunkPlan> {
+        let spinner = create_spinner("Scanning for junk files...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Lists what was found and asks before deleting it, except in a dry
+    /// run, which deletes nothing anyway
+    fn review(&self, plan: &JunkPlan) -> Result<bool> {
+        if plan.entries.is_empty() {
+            println!("No junk files found");
+            return Ok(false);
+        }
+        plan.print_report();
+        if self.delete_mode == DeleteMode::DryRun {
+            return Ok(true);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Delete {} junk entries?", plan.entries.len()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: JunkPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Deleting junk files...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!(
+            "{} junk entries ({}) {}",
+            summary.removed.len(),
+            format_size(summary.bytes(), BINARY),
+            options.delete_mode.verb()
+        ));
+        if self.delete_mode == DeleteMode::DryRun {
+            for entry in &summary.removed {
+                println!("  {}", entry.path.display());
+            }
+        }
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to clean them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "junk inside them was not looked for");
+        print_errors(&summary.errors);
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl JunkCleaner {
+    /// Presets the directory to clean so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Cleans inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Trashes, permanently deletes, or only lists the junk
+    pub fn with_delete_mode(mut self, mode: DeleteMode) -> Self {
+        self.delete_mode = mode;
+        self
+    }
+
+    /// Aborts on the first entry that cannot be deleted instead of
+    /// recording it and carrying on
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<CleanOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| {
+            anyhow::anyhow!("Input directory not set")
+        })?;
+        Ok(CleanOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            junk: self.junk.clone(),
+            delete_mode: self.delete_mode,
+            fail_fast: self.fail_fast,
+            ..CleanOptions::new(vec![input_dir])
+        })
+    }
+}
@@ -3,4 +3,21 @@ pub mod directory_flattener;
 pub mod image_optimizer;
 pub mod file_deduplicator;
 pub mod file_categorizer;
-pub mod archive_manager; 
\ No newline at end of file
+pub mod archive_manager;
+pub mod file_splitter;
+pub mod age_archiver;
+pub mod junk_cleaner;
+pub mod file_renamer;
+pub mod empty_dir_cleaner;
+pub mod disk_usage;
+pub mod checksum;
+pub mod broken_links;
+pub mod directory_sync;
+pub mod compare;
+pub mod file_shredder;
+pub mod inventory;
+pub mod name_sanitizer;
+pub mod permissions;
+pub mod text_normalizer;
+pub mod daemon;
+pub mod download_cleanup;
@@ -0,0 +1,492 @@
+mod policy;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::BTreeSet,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::prompt::{Confirm, Input, Select};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_renamer::Rename;
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    journal::Journal,
+    metrics::Metrics,
+    names::{escaped_name, is_same_file, NameSet},
+    progress::{Outcome, ProgressSink},
+    transfer::{move_file, TransferMode, Verify},
+    walk::{Inaccessible, Walker},
+};
+pub use policy::{NamePolicy, Target};
+
+/// Renames files and directories whose names another filesystem, sync
+/// client, or indexer would choke on, following a per-filesystem policy
+pub struct NameSanitizer {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Glob patterns a file's relative path must match to be renamed
+    include: Vec<String>,
+    /// Glob patterns that leave a file's name alone
+    exclude: Vec<String>,
+    /// Rename inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Preset policy; the policy prompts are skipped when set
+    policy: Option<NamePolicy>,
+    /// Rename offending directories as well as files
+    directories: bool,
+    /// Show the new names and stop without renaming anything
+    dry_run: bool,
+    /// Abort on the first name that cannot be changed
+    fail_fast: bool,
+    metrics: Metrics,
+}
+
+/// Everything a sanitizing run needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct SanitizeOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Rename inside working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    pub policy: NamePolicy,
+    /// Rename the directories holding the files as well. Directories are
+    /// found through the files in them, so empty ones keep their names.
+    pub directories: bool,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl SanitizeOptions {
+    /// Every file and directory under `input_dir`, recursively, made safe
+    /// for `target` with its defaults, writing no undo manifest
+    pub fn new(input_dir: PathBuf, target: Target) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            policy: NamePolicy::new(target),
+            directories: true,
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+}
+
+/// New names decided by [`plan`], before anything is renamed
+#[derive(Debug, Default, Serialize)]
+pub struct SanitizePlan {
+    /// Deepest first, so a directory is renamed after everything in it
+    pub renames: Vec<Rename>,
+    /// Names that already follow the policy
+    pub unchanged: usize,
+    /// Renames whose new name was taken, by an entry that stays or an
+    /// earlier rename, and that got a number instead
+    pub numbered: usize,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be read, left alone
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl SanitizePlan {
+    /// Prints `old → new` for every rename, paths relative to `input`,
+    /// then the totals
+    pub fn print_preview(&self, input: &Path) {
+        let relative = |path: &Path| path.strip_prefix(input).unwrap_or(path).display().to_string();
+        let width = self
+            .renames
+            .iter()
+            .map(|rename| relative(&rename.source).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(60);
+
+        println!();
+        for rename in &self.renames {
+            println!(
+                "{:<width$}  →  {}",
+                relative(&rename.source),
+                rename.target.file_name().unwrap_or_default().to_string_lossy()
+            );
+        }
+        println!();
+        println!("{} names would change, {} stay as they are", self.renames.len(), self.unchanged);
+        if self.numbered > 0 {
+            println!("{} new names were taken and got a number", self.numbered);
+        }
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct SanitizeSummary {
+    pub renamed: Vec<Rename>,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files and directories that could not be renamed
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&SanitizeSummary> for Summary {
+    fn from(summary: &SanitizeSummary) -> Self {
+        Summary {
+            files: summary.renamed.len(),
+            bytes: 0,
+            skipped: 0,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Works out the new name of every file, and of the directories holding
+/// them, under the input directory. Names that are not valid UTF-8 have
+/// their stray bytes written as `%XX` first. A new name that is taken gets
+/// a number, `name-2.ext`, compared the way the filesystem compares names.
+pub fn plan(options: &SanitizeOptions, progress: impl ProgressSink) -> Result<SanitizePlan> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    options.policy.check()?;
+    progress.scan_started(root);
+    // Hidden files are left alone, like the renamer leaves them
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .sorted(true)
+        .skip_hidden(true)
+        .filter(options.filter.clone())
+        .files(root);
+
+    let mut plan = SanitizePlan {
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..SanitizePlan::default()
+    };
+    let mut paths: Vec<PathBuf> = walk.files.into_iter().map(|entry| entry.path).collect();
+    if options.directories {
+        let dirs: BTreeSet<PathBuf> = paths
+            .iter()
+            .flat_map(|path| path.ancestors().skip(1).take_while(|dir| *dir != root.as_path()))
+            .map(Path::to_path_buf)
+            .collect();
+        paths.extend(dirs);
+    }
+
+    let mut by_dir: IndexMap<PathBuf, Vec<(PathBuf, String)>> = IndexMap::new();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default();
+        let new_name = options.policy.apply(&escaped_name(name));
+        if OsStr::new(&new_name) == name {
+            plan.unchanged += 1;
+            continue;
+        }
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        by_dir.entry(dir).or_default().push((path, new_name));
+    }
+    for (dir, renames) in by_dir {
+        number_taken(&dir, renames, &options.policy, &mut plan);
+    }
+    plan.renames.sort_by_key(|rename| Reverse(rename.source.components().count()));
+    Ok(plan)
+}
+
+/// Moves the renames in `dir` into the plan, numbering a new name that an
+/// entry of `dir` or an earlier rename already has. Every current name
+/// counts as taken, so no rename has to wait for another to move away.
+fn number_taken(dir: &Path, renames: Vec<(PathBuf, String)>, policy: &NamePolicy, plan: &mut SanitizePlan) {
+    let mut taken = NameSet::existing(dir);
+    for (source, name) in renames {
+        // A new name differing only in Unicode form is the file's own
+        let own = taken.key(source.file_name().unwrap_or_default());
+        let mut candidate = name.clone();
+        let mut n = 2;
+        while taken.key(OsStr::new(&candidate)) != own && !taken.insert(OsStr::new(&candidate)) {
+            candidate = policy.numbered(&name, n);
+            n += 1;
+        }
+        if candidate != name {
+            plan.numbered += 1;
+        }
+        plan.renames.push(Rename {
+            target: dir.join(candidate),
+            source,
+        });
+    }
+}
+
+/// Renames everything in `plan`, recording each in an undo manifest. A new
+/// name taken since planning is not overwritten; that rename fails.
+///
+/// ```
+/// use file_organizer_rust::modules::name_sanitizer::{self, SanitizeOptions, Target};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::create_dir(dir.path().join("Q3: results "))?;
+/// std::fs::write(dir.path().join("Q3: results ").join("draft?.txt"), "v1")?;
+/// std::fs::write(dir.path().join("Q3: results ").join("draft_.txt"), "v2")?;
+///
+/// let options = SanitizeOptions::new(dir.path().to_path_buf(), Target::Windows);
+/// let plan = name_sanitizer::plan(&options, ())?;
+/// assert_eq!(plan.numbered, 1);
+/// let summary = name_sanitizer::apply(&options, plan, ())?;
+/// assert_eq!(summary.renamed.len(), 2);
+/// assert!(dir.path().join("Q3_ results").join("draft_.txt").exists());
+/// assert!(dir.path().join("Q3_ results").join("draft_-2.txt").exists());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &SanitizeOptions, plan: SanitizePlan, progress: impl ProgressSink) -> Result<SanitizeSummary> {
+    progress.phase_changed("Renaming", plan.renames.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut journal = Journal::new("sanitize-names", TransferMode::Move);
+    let mut renamed = Vec::new();
+    let result = rename_each(plan.renames, &errors, &mut journal, &mut renamed, &progress);
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // renamed can be undone
+    let mut manifest = None;
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            manifest = Some(path);
+        }
+    }
+    result?;
+
+    let summary = SanitizeSummary {
+        renamed,
+        manifest,
+        repositories: plan.repositories,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    };
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+fn rename_each(
+    renames: Vec<Rename>,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    renamed: &mut Vec<Rename>,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    for rename in renames {
+        progress.item_started(&rename.source);
+        let result = if rename.target.exists() && !is_same_file(&rename.source, &rename.target) {
+            Err(anyhow::anyhow!("{} already exists, not overwriting it", rename.target.display()))
+        } else {
+            move_file(&rename.source, &rename.target, Verify::Size)
+                .with_context(|| format!("Failed to rename to {}", rename.target.display()))
+                .and_then(|_| journal.record(&rename.source, &rename.target, Some("sanitize")))
+        };
+        let outcome = match errors.check(&rename.source, "rename", result)? {
+            Some(()) => Outcome::Done,
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&rename.source, 0, outcome);
+        if outcome == Outcome::Done {
+            renamed.push(rename);
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl FileOrganizer for NameSanitizer {
+    type Plan = SanitizePlan;
+    const NAME: &'static str = "sanitize-names";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            policy: None,
+            directories: true,
+            dry_run: false,
+            fail_fast: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory with names to sanitize")?);
+        }
+        if self.policy.is_none() {
+            self.policy = Some(prompt_policy()?);
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<SanitizePlan> {
+        let spinner = create_spinner("Checking names...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Always shows every new name first; in a dry run stops there,
+    /// otherwise asks before renaming
+    fn review(&self, plan: &SanitizePlan) -> Result<bool> {
+        if plan.renames.is_empty() {
+            println!("Every name is fine as it is");
+            return Ok(false);
+        }
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print_preview(input_dir);
+        if self.dry_run {
+            println!("Dry run finished, nothing was renamed");
+            return Ok(false);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Rename {} files and directories?", plan.renames.len()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: SanitizePlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Renaming...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!("{} names sanitized", summary.renamed.len()));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to rename inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "names inside them were not checked");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl NameSanitizer {
+    /// Presets the directory to sanitize names in so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Only renames files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.mkv" or "downloads/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Renames inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Presets the policy so its prompts are skipped
+    pub fn with_policy(mut self, policy: NamePolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Whether the directories holding the files are renamed too
+    pub fn with_directories(mut self, directories: bool) -> Self {
+        self.directories = directories;
+        self
+    }
+
+    /// Shows the new names without renaming anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first name that cannot be changed
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<SanitizeOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let policy = self.policy.clone().unwrap_or_else(|| NamePolicy::new(Target::Windows));
+        Ok(SanitizeOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            directories: self.directories,
+            manifest_dir: Some(Journal::default_dir()?),
+            fail_fast: self.fail_fast,
+            policy,
+            ..SanitizeOptions::new(input_dir, Target::Windows)
+        })
+    }
+}
+
+/// Asks which filesystem the names are for, then about the optional steps
+fn prompt_policy() -> Result<NamePolicy> {
+    let theme = ColorfulTheme::default();
+    let targets = [Target::Windows, Target::Posix, Target::Fat32];
+    let choice = Select::with_theme(&theme)
+        .with_prompt("Make names safe for")
+        .items(&[
+            "Windows and SMB shares",
+            "Linux, macOS, and NAS volumes",
+            "FAT32 drives (USB sticks, SD cards)",
+        ])
+        .default(0)
+        .interact()?;
+    let mut policy = NamePolicy::new(targets[choice]);
+    policy.ascii = Confirm::with_theme(&theme)
+        .with_prompt("Spell names in plain ASCII (café → cafe)?")
+        .default(policy.ascii)
+        .interact()?;
+    policy.strip_emoji = Confirm::with_theme(&theme)
+        .with_prompt("Remove emoji?")
+        .default(policy.strip_emoji)
+        .interact()?;
+    policy.max_length = Input::with_theme(&theme)
+        .with_prompt("Longest name allowed")
+        .default(policy.max_length)
+        .interact_text()?;
+    Ok(policy)
+}
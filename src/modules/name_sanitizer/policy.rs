@@ -0,0 +1,324 @@
+use anyhow::Result;
+use serde::Serialize;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::utils::sanitize::RESERVED_NAMES;
+
+/// Longest extension kept apart from the name when truncating; a longer
+/// "extension" is more likely the end of a dotted title
+const MAX_EXTENSION: usize = 10;
+
+/// ASCII spellings of letters and punctuation that do not decompose into a
+/// base letter and accents
+const SPELLINGS: &[(char, &str)] = &[
+    ('ß', "ss"),
+    ('æ', "ae"),
+    ('Æ', "AE"),
+    ('œ', "oe"),
+    ('Œ', "OE"),
+    ('ø', "o"),
+    ('Ø', "O"),
+    ('đ', "d"),
+    ('Đ', "D"),
+    ('ð', "d"),
+    ('Ð', "D"),
+    ('þ', "th"),
+    ('Þ', "Th"),
+    ('ł', "l"),
+    ('Ł', "L"),
+    ('ı', "i"),
+    ('ħ', "h"),
+    ('Ħ', "H"),
+    ('‘', "'"),
+    ('’', "'"),
+    ('‚', ","),
+    ('“', "\""),
+    ('”', "\""),
+    ('„', "\""),
+    ('«', "\""),
+    ('»', "\""),
+    ('‐', "-"),
+    ('–', "-"),
+    ('—', "-"),
+    ('…', "..."),
+    ('·', "."),
+    ('×', "x"),
+    ('€', "EUR"),
+    ('£', "GBP"),
+    ('©', "(c)"),
+    ('®', "(R)"),
+    ('™', "TM"),
+    ('°', "deg"),
+    ('\u{a0}', " "),
+    ('\u{2009}', " "),
+    ('\u{202f}', " "),
+    ('\u{3000}', " "),
+];
+
+/// Filesystem whose naming rules the new names follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// NTFS and SMB shares: no `<>:"/\|?*`, no device names such as `CON`,
+    /// no trailing dots or spaces
+    Windows,
+    /// ext4, APFS, and most NAS volumes, where only `/` is ruled out
+    Posix,
+    /// USB sticks, SD cards, and the car stereos and cameras that read
+    /// them: Windows' rules, and ASCII by default since such devices often
+    /// only know one code page
+    Fat32,
+}
+
+impl Target {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Windows => "windows",
+            Self::Posix => "posix",
+            Self::Fat32 => "fat32",
+        }
+    }
+
+    /// Parses `windows`, `posix`, or `fat32`
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "windows" | "ntfs" => Ok(Self::Windows),
+            "posix" | "unix" => Ok(Self::Posix),
+            "fat32" | "fat" | "vfat" | "exfat" => Ok(Self::Fat32),
+            _ => anyhow::bail!("Unknown target filesystem: {} (expected windows, posix, or fat32)", name),
+        }
+    }
+
+    /// Whether `c` cannot be part of a name. Control characters are ruled
+    /// out everywhere: POSIX allows them, but little else copes with them.
+    pub fn is_illegal(self, c: char) -> bool {
+        if c == '/' || c.is_control() {
+            return true;
+        }
+        match self {
+            Self::Posix => false,
+            Self::Windows | Self::Fat32 => matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*'),
+        }
+    }
+
+    /// Length of `c` in the units the target limits names by: bytes on
+    /// POSIX filesystems, UTF-16 code units on Windows and FAT32
+    fn char_length(self, c: char) -> usize {
+        match self {
+            Self::Posix => c.len_utf8(),
+            Self::Windows | Self::Fat32 => c.len_utf16(),
+        }
+    }
+
+    pub fn length(self, name: &str) -> usize {
+        name.chars().map(|c| self.char_length(c)).sum()
+    }
+}
+
+/// How [`NamePolicy::apply`] rewrites a name, in this order: Unicode
+/// normalization, emoji, transliteration, illegal characters, trimming,
+/// reserved names, then the length limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NamePolicy {
+    pub target: Target,
+    /// Put in place of each character the target does not allow
+    pub replacement: char,
+    /// Trim leading whitespace and trailing dots and whitespace
+    pub trim: bool,
+    /// Compose names to NFC, so names synced from macOS match the rest
+    pub normalize: bool,
+    /// Spell letters in ASCII, `café` as `cafe`; characters with no ASCII
+    /// spelling become the replacement
+    pub ascii: bool,
+    /// Drop emoji and the joiners and modifiers around them
+    pub strip_emoji: bool,
+    /// Longest name, in the target's units (see [`Target::length`]). The
+    /// extension is kept and the rest of the name cut.
+    pub max_length: usize,
+}
+
+impl NamePolicy {
+    /// The defaults for `target`: trimmed, NFC, at most 255 long, and
+    /// ASCII only for FAT32
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            replacement: '_',
+            trim: true,
+            normalize: true,
+            ascii: target == Target::Fat32,
+            strip_emoji: false,
+            max_length: 255,
+        }
+    }
+
+    /// `name` following the policy. A name that already does is returned
+    /// as it is.
+    ///
+    /// ```
+    /// use file_organizer_rust::modules::name_sanitizer::{NamePolicy, Target};
+    ///
+    /// let windows = NamePolicy::new(Target::Windows);
+    /// assert_eq!(windows.apply("Report: Q3?.pdf "), "Report_ Q3_.pdf");
+    /// assert_eq!(windows.apply("con.txt"), "con_.txt");
+    /// assert_eq!(NamePolicy::new(Target::Posix).apply("Report: Q3?.pdf"), "Report: Q3?.pdf");
+    ///
+    /// let fat32 = NamePolicy { strip_emoji: true, ..NamePolicy::new(Target::Fat32) };
+    /// assert_eq!(fat32.apply("Crème brûlée 🎂 recipe.txt"), "Creme brulee recipe.txt");
+    ///
+    /// let short = NamePolicy { max_length: 20, ..NamePolicy::new(Target::Posix) };
+    /// assert_eq!(short.apply("a very long title for a film.mkv"), "a very long titl.mkv");
+    /// ```
+    pub fn apply(&self, name: &str) -> String {
+        let mut name = if self.normalize { name.nfc().collect() } else { name.to_string() };
+        if self.strip_emoji {
+            name = strip_emoji(&name);
+        }
+        if self.ascii {
+            name = transliterate(&name, self.replacement);
+        }
+        let mut name: String = name
+            .chars()
+            .map(|c| if self.target.is_illegal(c) { self.replacement } else { c })
+            .collect();
+        if self.trim {
+            name = trimmed(&name).to_string();
+        }
+        if name.is_empty() {
+            name.push(self.replacement);
+        }
+        if self.target != Target::Posix {
+            name = self.unreserved(name);
+        }
+        let (stem, extension) = split_extension(&name);
+        self.fit(stem, extension, "")
+    }
+
+    /// `name`, an output of [`apply`](Self::apply), with `-<n>` before its
+    /// extension, cut further if needed to stay within the length limit
+    pub fn numbered(&self, name: &str, n: u32) -> String {
+        let (stem, extension) = split_extension(name);
+        self.fit(stem, extension, &format!("-{}", n))
+    }
+
+    /// Whether the replacement and length limit can give usable names
+    pub fn check(&self) -> Result<()> {
+        if self.target.is_illegal(self.replacement) || self.replacement == '.' || self.replacement.is_whitespace() {
+            anyhow::bail!("{:?} cannot replace characters in names on {}", self.replacement, self.target.name());
+        }
+        if self.max_length < 2 * MAX_EXTENSION {
+            anyhow::bail!("The maximum name length must be at least {}", 2 * MAX_EXTENSION);
+        }
+        Ok(())
+    }
+
+    /// `name` with the replacement after a device name such as `CON`, which
+    /// Windows reserves whatever the extension: `CON.tar.gz` is the console
+    fn unreserved(&self, name: String) -> String {
+        let end = name.find('.').unwrap_or(name.len());
+        let reserved = RESERVED_NAMES
+            .iter()
+            .any(|reserved| name[..end].trim_end().eq_ignore_ascii_case(reserved));
+        if !reserved {
+            return name;
+        }
+        let mut name = name;
+        name.insert(end, self.replacement);
+        name
+    }
+
+    /// `stem`, `suffix`, and the extension, with the stem cut to keep the
+    /// whole within the length limit
+    fn fit(&self, stem: &str, extension: Option<&str>, suffix: &str) -> String {
+        let tail = match extension {
+            Some(ext) => format!("{}.{}", suffix, ext),
+            None => suffix.to_string(),
+        };
+        let budget = self.max_length.saturating_sub(self.target.length(&tail));
+        let mut used = 0;
+        let mut stem: String = stem
+            .chars()
+            .take_while(|&c| {
+                used += self.target.char_length(c);
+                used <= budget
+            })
+            .collect();
+        if self.trim {
+            stem.truncate(trimmed(&stem).len());
+        }
+        if stem.is_empty() {
+            stem.push(self.replacement);
+        }
+        stem + &tail
+    }
+}
+
+/// `name` without leading whitespace or trailing dots and whitespace,
+/// which Windows drops and other systems show as blank
+fn trimmed(name: &str) -> &str {
+    name.trim_start().trim_end_matches(|c: char| c == '.' || c.is_whitespace())
+}
+
+/// The name before its extension, and the extension: a short run of
+/// letters and digits after the last dot
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && (1..=MAX_EXTENSION).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            (stem, Some(ext))
+        }
+        _ => (name, None),
+    }
+}
+
+/// Emoji, with the skin tones, joiners, variation selectors, and tags that
+/// build sequences out of them
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE00..=0xFE0F | 0x200D | 0x20E3 | 0xE0020..=0xE007F
+    )
+}
+
+/// `name` without emoji. A space left doubled where one was removed goes
+/// too, so `Party 🎉 2024` becomes `Party 2024`.
+fn strip_emoji(name: &str) -> String {
+    let mut stripped = String::with_capacity(name.len());
+    let mut removed = false;
+    for c in name.chars() {
+        if is_emoji(c) {
+            removed = true;
+            continue;
+        }
+        if c == ' ' && removed && stripped.ends_with(' ') {
+            continue;
+        }
+        if c != ' ' {
+            removed = false;
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+/// `name` in ASCII: accents are dropped from the letters they decompose
+/// from, a few letters are spelled out, and anything else becomes
+/// `replacement`
+fn transliterate(name: &str, replacement: char) -> String {
+    let mut ascii = String::with_capacity(name.len());
+    for c in name.nfd() {
+        if c.is_ascii() {
+            ascii.push(c);
+        } else if is_combining_mark(c) {
+            continue;
+        } else if let Some((_, spelling)) = SPELLINGS.iter().find(|(letter, _)| *letter == c) {
+            ascii.push_str(spelling);
+        } else {
+            ascii.push(replacement);
+        }
+    }
+    ascii
+}
@@ -0,0 +1,526 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::utils::prompt::{Confirm, Input};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::utils::{
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    journal::Journal,
+    metrics::Metrics,
+    permissions::{
+        group_id, is_root, looks_executable, parse_mode, user_id, with_execute, Attributes, SPECIAL_BITS,
+        UNSUPPORTED,
+    },
+    progress::{Outcome, ProgressSink},
+    transfer::TransferMode,
+    walk::{Inaccessible, Walker},
+};
+
+/// Gives every file and directory under a directory the same modes, and
+/// optionally owner, such as after restoring a backup that left
+/// everything 777. Unix only.
+pub struct PermissionNormalizer {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Glob patterns a file's relative path must match to be changed
+    include: Vec<String>,
+    /// Glob patterns that leave a file alone
+    exclude: Vec<String>,
+    /// Change inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// Preset modes; the mode prompts are skipped when set
+    modes: Option<(u32, u32)>,
+    keep_executable: bool,
+    detect_executable: bool,
+    /// User and group, as names or ids, to give everything; resolved when
+    /// planning
+    owner: Option<String>,
+    group: Option<String>,
+    /// List the changes and stop
+    dry_run: bool,
+    /// Abort on the first entry that cannot be changed
+    fail_fast: bool,
+    metrics: Metrics,
+}
+
+/// Everything a permission run needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Change inside working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs for files, matched relative to the input
+    /// directory. Directories are always changed.
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Mode of every file, e.g. `0o644`
+    pub file_mode: u32,
+    /// Mode of every directory, e.g. `0o755`. Setgid and sticky bits a
+    /// directory has are kept, as they pass on a group or guard a shared
+    /// folder; files lose theirs.
+    pub dir_mode: u32,
+    /// Files with an execute bit keep execute bits, matching their read bits
+    pub keep_executable: bool,
+    /// `#!` scripts and ELF and Mach-O binaries get execute bits, whatever
+    /// they had
+    pub detect_executable: bool,
+    /// User id to give everything; changing it needs root
+    pub uid: Option<u32>,
+    /// Group id to give everything
+    pub gid: Option<u32>,
+    /// Where the undo manifest is written; `None` writes none
+    pub manifest_dir: Option<PathBuf>,
+    /// Abort on the first per-entry error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl PermissionOptions {
+    /// 644 files and 755 directories under `input_dir`, recursively, with
+    /// scripts and binaries kept executable, owners kept, and no undo
+    /// manifest
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            file_mode: 0o644,
+            dir_mode: 0o755,
+            keep_executable: false,
+            detect_executable: true,
+            uid: None,
+            gid: None,
+            manifest_dir: None,
+            fail_fast: false,
+        }
+    }
+
+    /// What `path` should have, given what it has
+    fn target(&self, path: &Path, is_dir: bool, current: &Attributes) -> Attributes {
+        let mode = if is_dir {
+            self.dir_mode | (current.mode & SPECIAL_BITS & !0o4000)
+        } else if self.keep_executable && current.mode & 0o111 != 0
+            || self.detect_executable && looks_executable(path)
+        {
+            with_execute(self.file_mode)
+        } else {
+            self.file_mode
+        };
+        Attributes {
+            mode,
+            uid: self.uid.unwrap_or(current.uid),
+            gid: self.gid.unwrap_or(current.gid),
+        }
+    }
+}
+
+/// One entry whose mode or owner changes
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionChange {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub before: Attributes,
+    pub after: Attributes,
+}
+
+/// Changes found by [`plan`], before anything is changed
+#[derive(Debug, Default, Serialize)]
+pub struct PermissionPlan {
+    /// In walk order, each directory before what it holds
+    pub changes: Vec<PermissionChange>,
+    /// Entries that already match
+    pub unchanged: usize,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl PermissionPlan {
+    /// Prints `before → after` for every change, paths relative to
+    /// `input`, then the totals
+    pub fn print_preview(&self, input: &Path) {
+        let relative = |change: &PermissionChange| {
+            let path = change.path.strip_prefix(input).unwrap_or(&change.path);
+            match (path.as_os_str().is_empty(), change.is_dir) {
+                (true, _) => ".".to_string(),
+                (false, true) => format!("{}/", path.display()),
+                (false, false) => path.display().to_string(),
+            }
+        };
+        let width = self
+            .changes
+            .iter()
+            .map(|change| relative(change).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(60);
+
+        println!();
+        for change in &self.changes {
+            println!("{:<width$}  {}  →  {}", relative(change), change.before, change.after);
+        }
+        let owners = self
+            .changes
+            .iter()
+            .filter(|change| (change.before.uid, change.before.gid) != (change.after.uid, change.after.gid))
+            .count();
+        println!();
+        println!("{} entries would change, {} already match", self.changes.len(), self.unchanged);
+        if owners > 0 {
+            println!("{} of them get a new owner", owners);
+        }
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct PermissionSummary {
+    pub changed: Vec<PermissionChange>,
+    /// Undo manifest of the run, if one was written
+    pub manifest: Option<PathBuf>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Entries whose mode or owner could not be changed
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl From<&PermissionSummary> for Summary {
+    fn from(summary: &PermissionSummary) -> Self {
+        Summary {
+            files: summary.changed.len(),
+            bytes: 0,
+            skipped: 0,
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Works out the mode and owner every file and directory under the input
+/// directory should have. Symlinks are left alone, and so is what they
+/// point to.
+pub fn plan(options: &PermissionOptions, progress: impl ProgressSink) -> Result<PermissionPlan> {
+    if !cfg!(unix) {
+        anyhow::bail!(UNSUPPORTED);
+    }
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    if options.uid.is_some() && !is_root() {
+        anyhow::bail!("Changing the owner needs root; run as root or leave out the owner");
+    }
+    progress.scan_started(root);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .sorted(true)
+        .entries(root);
+
+    let mut plan = PermissionPlan {
+        repositories: walk.repositories,
+        inaccessible: walk.inaccessible,
+        ..PermissionPlan::default()
+    };
+    for entry in walk.files {
+        let is_dir = entry.metadata.is_dir();
+        let before = Attributes::from_metadata(&entry.metadata);
+        let after = options.target(&entry.path, is_dir, &before);
+        if after == before {
+            plan.unchanged += 1;
+        } else {
+            plan.changes.push(PermissionChange {
+                path: entry.path,
+                is_dir,
+                before,
+                after,
+            });
+        }
+    }
+    Ok(plan)
+}
+
+/// Changes every entry in `plan`, recording the old mode and owner of each
+/// in an undo manifest
+///
+/// ```
+/// # #[cfg(unix)]
+/// # {
+/// use file_organizer_rust::modules::permissions::{self, PermissionOptions};
+/// use std::os::unix::fs::PermissionsExt;
+///
+/// let dir = tempfile::tempdir()?;
+/// let mode = |name: &str| std::fs::metadata(dir.path().join(name)).map(|m| m.permissions().mode() & 0o777);
+/// std::fs::write(dir.path().join("notes.txt"), "restored")?;
+/// std::fs::write(dir.path().join("backup.sh"), "#!/bin/sh\necho ok\n")?;
+/// for name in ["notes.txt", "backup.sh"] {
+///     std::fs::set_permissions(dir.path().join(name), std::fs::Permissions::from_mode(0o777))?;
+/// }
+///
+/// let options = PermissionOptions::new(dir.path().to_path_buf());
+/// let plan = permissions::plan(&options, ())?;
+/// let summary = permissions::apply(&options, plan, ())?;
+/// assert_eq!(summary.changed.iter().filter(|change| !change.is_dir).count(), 2);
+/// assert_eq!(mode("notes.txt")?, 0o644);
+/// assert_eq!(mode("backup.sh")?, 0o755);
+/// # }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &PermissionOptions, plan: PermissionPlan, progress: impl ProgressSink) -> Result<PermissionSummary> {
+    progress.phase_changed("Changing permissions", plan.changes.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let mut journal = Journal::new("permissions", TransferMode::Move);
+    let mut changed = Vec::new();
+    let result = change_each(plan.changes, &errors, &mut journal, &mut changed, &progress);
+
+    // Saved even when a fail-fast error cut the run short, so what was
+    // changed can be undone
+    let mut manifest = None;
+    if let Some(dir) = &options.manifest_dir {
+        if !journal.entries.is_empty() {
+            let path = dir.join(journal.file_name());
+            journal.save(&path)?;
+            manifest = Some(path);
+        }
+    }
+    result?;
+
+    let summary = PermissionSummary {
+        changed,
+        manifest,
+        repositories: plan.repositories,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    };
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+fn change_each(
+    changes: Vec<PermissionChange>,
+    errors: &ErrorLog,
+    journal: &mut Journal,
+    changed: &mut Vec<PermissionChange>,
+    progress: &impl ProgressSink,
+) -> Result<()> {
+    for change in changes {
+        progress.item_started(&change.path);
+        let result = journal.set_attributes(&change.path, change.before, change.after, Some("permissions"));
+        let outcome = match errors.check(&change.path, "chmod", result)? {
+            Some(()) => Outcome::Done,
+            None => Outcome::Failed,
+        };
+        progress.item_finished(&change.path, 0, outcome);
+        if outcome == Outcome::Done {
+            changed.push(change);
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl FileOrganizer for PermissionNormalizer {
+    type Plan = PermissionPlan;
+    const NAME: &'static str = "permissions";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            modes: None,
+            keep_executable: false,
+            detect_executable: true,
+            owner: None,
+            group: None,
+            dry_run: false,
+            fail_fast: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Refuses to start outside Unix rather than asking for settings it
+    /// cannot apply
+    fn configure(&mut self) -> Result<()> {
+        if !cfg!(unix) {
+            anyhow::bail!(UNSUPPORTED);
+        }
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory to normalize permissions in")?);
+        }
+        if self.modes.is_none() {
+            let theme = ColorfulTheme::default();
+            let file_mode: String = Input::with_theme(&theme)
+                .with_prompt("Mode for files")
+                .default("644".to_string())
+                .interact_text()?;
+            let dir_mode: String = Input::with_theme(&theme)
+                .with_prompt("Mode for directories")
+                .default("755".to_string())
+                .interact_text()?;
+            self.modes = Some((parse_mode(&file_mode)?, parse_mode(&dir_mode)?));
+            self.detect_executable = Confirm::with_theme(&theme)
+                .with_prompt("Keep scripts and programs executable (detected from their contents)?")
+                .default(self.detect_executable)
+                .interact()?;
+            self.keep_executable = Confirm::with_theme(&theme)
+                .with_prompt("Keep every file that is executable now executable?")
+                .default(self.keep_executable)
+                .interact()?;
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<PermissionPlan> {
+        let spinner = create_spinner("Checking permissions...");
+        self.metrics.phase("scanning");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Always shows every change first; in a dry run stops there,
+    /// otherwise asks before changing anything
+    fn review(&self, plan: &PermissionPlan) -> Result<bool> {
+        if plan.changes.is_empty() {
+            println!("Every mode and owner already matches");
+            return Ok(false);
+        }
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print_preview(input_dir);
+        if self.dry_run {
+            println!("Dry run finished, nothing was changed");
+            return Ok(false);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Change {} files and directories?", plan.changes.len()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: PermissionPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Changing permissions...");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!("{} files and directories changed", summary.changed.len()));
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to change inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "they were left as they are");
+        print_errors(&summary.errors);
+        if let Some(manifest) = &summary.manifest {
+            println!("Undo with: file-organizer-rust undo --manifest {}", manifest.display());
+        }
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl PermissionNormalizer {
+    /// Presets the directory so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Only changes files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.jpg" or "public/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Changes inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Presets the file and directory modes so the mode prompts are skipped
+    pub fn with_modes(mut self, file_mode: u32, dir_mode: u32) -> Self {
+        self.modes = Some((file_mode, dir_mode));
+        self
+    }
+
+    /// Whether files that are executable now stay executable
+    pub fn with_keep_executable(mut self, keep: bool) -> Self {
+        self.keep_executable = keep;
+        self
+    }
+
+    /// Whether scripts and binaries, told by their contents, are made
+    /// executable
+    pub fn with_detect_executable(mut self, detect: bool) -> Self {
+        self.detect_executable = detect;
+        self
+    }
+
+    /// Gives everything the user `owner`, a name or id; needs root
+    pub fn with_owner(mut self, owner: String) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// Gives everything the group `group`, a name or id
+    pub fn with_group(mut self, group: String) -> Self {
+        self.group = Some(group);
+        self
+    }
+
+    /// Lists the changes without making them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Aborts on the first entry that cannot be changed
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<PermissionOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let defaults = PermissionOptions::new(input_dir);
+        let (file_mode, dir_mode) = self.modes.unwrap_or((defaults.file_mode, defaults.dir_mode));
+        Ok(PermissionOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            file_mode,
+            dir_mode,
+            keep_executable: self.keep_executable,
+            detect_executable: self.detect_executable,
+            uid: self.owner.as_deref().map(user_id).transpose()?,
+            gid: self.group.as_deref().map(group_id).transpose()?,
+            manifest_dir: Some(Journal::default_dir()?),
+            fail_fast: self.fail_fast,
+            ..defaults
+        })
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::Serialize;
+
+/// How much of a file is looked at to tell text from binary
+const SNIFF_LENGTH: usize = 8192;
+
+/// UTF-8 byte order mark
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Line breaks a file uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    /// A single line without a break
+    None,
+    Lf,
+    Crlf,
+    /// Classic Mac OS
+    Cr,
+    /// More than one kind
+    Mixed,
+}
+
+impl LineEndings {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+            Self::Cr => "CR",
+            Self::Mixed => "mixed",
+        }
+    }
+
+    fn of(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+        for (i, &byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\n' if i > 0 && bytes[i - 1] == b'\r' => crlf += 1,
+                b'\n' => lf += 1,
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => cr += 1,
+                _ => {}
+            }
+        }
+        match (lf > 0, crlf > 0, cr > 0) {
+            (false, false, false) => Self::None,
+            (true, false, false) => Self::Lf,
+            (false, true, false) => Self::Crlf,
+            (false, false, true) => Self::Cr,
+            _ => Self::Mixed,
+        }
+    }
+}
+
+/// Line break converted files get
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Newline {
+    Lf,
+    Crlf,
+}
+
+impl Newline {
+    /// Parses `lf` or `crlf`
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "lf" | "unix" => Ok(Self::Lf),
+            "crlf" | "windows" | "dos" => Ok(Self::Crlf),
+            _ => anyhow::bail!("Unknown line ending: {} (expected lf or crlf)", name),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+}
+
+/// Whether converted files start with a UTF-8 byte order mark
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BomPolicy {
+    /// A file gets one if it had a BOM of any kind, e.g. a UTF-16 one
+    Keep,
+    Add,
+    Remove,
+}
+
+impl BomPolicy {
+    /// Parses `keep`, `add`, or `remove`
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "keep" => Ok(Self::Keep),
+            "add" => Ok(Self::Add),
+            "remove" | "strip" => Ok(Self::Remove),
+            _ => anyhow::bail!("Unknown BOM policy: {} (expected keep, add, or remove)", name),
+        }
+    }
+}
+
+/// What files are converted to: always UTF-8, with these line breaks and BOM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Conversion {
+    pub newline: Newline,
+    pub bom: BomPolicy,
+}
+
+impl Conversion {
+    /// UTF-8 with `newline` line breaks, keeping a BOM where there is one
+    pub fn new(newline: Newline) -> Self {
+        Self {
+            newline,
+            bom: BomPolicy::Keep,
+        }
+    }
+
+    /// `UTF-8 LF`, or `UTF-8 BOM LF` when every file gets a BOM
+    pub fn label(&self) -> String {
+        match self.bom {
+            BomPolicy::Add => format!("UTF-8 BOM {}", self.newline.label()),
+            _ => format!("UTF-8 {}", self.newline.label()),
+        }
+    }
+}
+
+/// Encoding, BOM, and line breaks detected in a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Detected {
+    /// WHATWG name, e.g. `UTF-8`, `UTF-16LE`, or `windows-1252` for what
+    /// is usually called Latin-1
+    pub encoding: &'static str,
+    pub bom: bool,
+    pub line_endings: LineEndings,
+}
+
+impl Detected {
+    /// `windows-1252 CRLF`, or `UTF-8 BOM LF`
+    pub fn label(&self) -> String {
+        match self.bom {
+            true => format!("{} BOM {}", self.encoding, self.line_endings.label()),
+            false => format!("{} {}", self.encoding, self.line_endings.label()),
+        }
+    }
+}
+
+/// What a file's contents turned out to be
+pub enum Content {
+    /// Not text, whatever the extension says; never converted
+    Binary,
+    /// Not valid in the encoding it most likely has, the name of which is
+    /// given
+    Undecodable(&'static str),
+    Text(Text),
+}
+
+/// A text file, decoded
+pub struct Text {
+    encoding: &'static Encoding,
+    bom: bool,
+    /// Offset of the contents after the BOM
+    start: usize,
+    text: String,
+}
+
+/// Tells binary from text, then finds the encoding: from the BOM if there
+/// is one, then UTF-16 by the zero bytes ASCII leaves in it, UTF-8 if the
+/// contents are valid UTF-8, and otherwise the legacy encoding chardetng
+/// finds likeliest
+pub fn analyze(bytes: &[u8]) -> Content {
+    let sample = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+    let (encoding, start) = match Encoding::for_bom(bytes) {
+        Some((encoding, length)) => (encoding, length),
+        None => match utf16_without_bom(sample) {
+            Some(encoding) => (encoding, 0),
+            None if sample.contains(&0) => return Content::Binary,
+            None => (guess(bytes), 0),
+        },
+    };
+    let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(&bytes[start..]) else {
+        return Content::Undecodable(encoding.name());
+    };
+    if looks_binary(&text) {
+        return Content::Binary;
+    }
+    Content::Text(Text {
+        encoding,
+        bom: start > 0,
+        start,
+        text: text.into_owned(),
+    })
+}
+
+impl Text {
+    pub fn detected(&self) -> Detected {
+        Detected {
+            encoding: self.encoding.name(),
+            bom: self.bom,
+            line_endings: LineEndings::of(&self.text),
+        }
+    }
+
+    /// The file as `conversion` has it, or `None` when that is what
+    /// `original`, the bytes the text was decoded from, already holds.
+    /// Fails, with the reason, when the result does not decode back to the
+    /// same text with only the line breaks changed.
+    pub fn convert(&self, original: &[u8], conversion: &Conversion) -> Result<Option<Vec<u8>>> {
+        let bom = match conversion.bom {
+            BomPolicy::Keep => self.bom,
+            BomPolicy::Add => true,
+            BomPolicy::Remove => false,
+        };
+        let mut converted = Vec::with_capacity(original.len() + UTF8_BOM.len());
+        if bom {
+            converted.extend_from_slice(UTF8_BOM);
+        }
+        converted.extend_from_slice(with_newlines(&self.text, conversion.newline.as_str()).as_bytes());
+        if converted == original {
+            return Ok(None);
+        }
+        self.verify(&original[self.start..], &converted)?;
+        Ok(Some(converted))
+    }
+
+    /// Checks that decoding lost nothing, by encoding the text back and
+    /// comparing it with `body`, and that `converted` decodes as UTF-8 to
+    /// the same lines
+    fn verify(&self, body: &[u8], converted: &[u8]) -> Result<()> {
+        if encode(&self.text, self.encoding).as_deref() != Some(body) {
+            anyhow::bail!("decoding as {} does not round-trip", self.encoding.name());
+        }
+        let converted = converted.strip_prefix(UTF8_BOM).unwrap_or(converted);
+        let Some(decoded) = UTF_8.decode_without_bom_handling_and_without_replacement(converted) else {
+            anyhow::bail!("the converted file is not valid UTF-8");
+        };
+        if with_newlines(&decoded, "\n") != with_newlines(&self.text, "\n") {
+            anyhow::bail!("the converted text differs from the original");
+        }
+        Ok(())
+    }
+}
+
+/// `text` in `encoding`, or `None` if it has characters the encoding
+/// cannot hold
+fn encode(text: &str, encoding: &'static Encoding) -> Option<Vec<u8>> {
+    // encoding_rs only encodes UTF-16 as UTF-8, as the web does
+    if encoding == UTF_16LE {
+        return Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == UTF_16BE {
+        return Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+    let (bytes, _, unmappable) = encoding.encode(text);
+    (!unmappable).then(|| bytes.into_owned())
+}
+
+/// `text` with every CRLF, LF, and lone CR replaced by `newline`
+fn with_newlines(text: &str, newline: &str) -> String {
+    let mut converted = String::with_capacity(text.len() + text.len() / 32);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                converted.push_str(newline);
+            }
+            '\n' => converted.push_str(newline),
+            c => converted.push(c),
+        }
+    }
+    converted
+}
+
+/// UTF-16 without a BOM, told by ASCII characters leaving every other byte
+/// zero: the odd ones in little-endian text, the even ones in big-endian
+fn utf16_without_bom(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeros = |offset: usize| sample.chunks_exact(2).filter(|pair| pair[offset] == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 >= pairs * 4 && even * 20 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 4 && odd * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// UTF-8 if `bytes` are valid UTF-8, otherwise chardetng's guess
+fn guess(bytes: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Whether more than one in a hundred of the first characters are control
+/// characters text does not use, as in binary data that happens to decode
+fn looks_binary(text: &str) -> bool {
+    let (mut total, mut control) = (0, 0);
+    for c in text.chars().take(SNIFF_LENGTH) {
+        total += 1;
+        if c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b') {
+            control += 1;
+        }
+    }
+    control * 100 > total
+}
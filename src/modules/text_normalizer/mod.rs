@@ -0,0 +1,699 @@
+mod detect;
+mod report;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::theme::ColorfulTheme;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::utils::prompt::{Confirm, Select};
+use crate::modules::base::{print_errors, print_inaccessible, ErrorLog, FileError, FileOrganizer, Summary};
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    atomic::AtomicFile,
+    create_spinner,
+    filter::PathFilter,
+    get_directory_from_user,
+    metrics::Metrics,
+    progress::{Outcome, ProgressSink},
+    walk::{Inaccessible, Walker},
+};
+pub use detect::{BomPolicy, Conversion, Detected, LineEndings, Newline};
+use detect::Content;
+
+/// Extensions of the files looked at unless others are given
+pub const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "csv", "tsv", "json", "jsonl", "xml", "yaml", "yml", "toml", "ini", "cfg",
+    "conf", "properties", "html", "htm", "css", "scss", "js", "mjs", "ts", "tsx", "jsx", "vue", "rs", "py", "rb",
+    "pl", "php", "java", "kt", "go", "c", "h", "cpp", "hpp", "cc", "cs", "swift", "sh", "bash", "zsh", "ps1",
+    "bat", "cmd", "sql", "log", "srt", "vtt", "tex", "bib", "svg",
+];
+
+/// Largest file looked at; anything bigger is left out of the scan
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Finds the encodings and line endings text files use, and converts them
+/// to UTF-8 with LF or CRLF line breaks, such as after a checkout shared
+/// between Windows and Unix left a mix
+pub struct TextNormalizer {
+    recursive: bool,
+    input_dir: Option<PathBuf>,
+    /// Extensions of the files looked at; `None` uses [`TEXT_EXTENSIONS`]
+    extensions: Option<Vec<String>>,
+    /// Glob patterns a file's relative path must match to be looked at
+    include: Vec<String>,
+    /// Glob patterns that leave a file alone
+    exclude: Vec<String>,
+    /// Look inside git, Mercurial, and Subversion working copies too
+    include_repos: bool,
+    /// `None` asks after the scan; `Some(None)` only reports
+    conversion: Option<Option<Conversion>>,
+    /// Report what would change and stop
+    dry_run: bool,
+    /// `None` asks after the run; `Some(None)` writes no report
+    report: Option<Option<ReportFormat>>,
+    report_path: Option<PathBuf>,
+    /// Abort on the first file that cannot be read or converted
+    fail_fast: bool,
+    metrics: Metrics,
+}
+
+/// Everything a text scan or conversion needs, with no prompting
+#[derive(Debug, Clone, Serialize)]
+pub struct TextOptions {
+    pub input_dir: PathBuf,
+    pub recursive: bool,
+    /// Look inside working copies too
+    pub include_repos: bool,
+    /// Include/exclude globs, matched relative to the input directory
+    #[serde(skip)]
+    pub filter: PathFilter,
+    /// Extensions of the files looked at, in lower case without the dot
+    pub extensions: Vec<String>,
+    /// What files are converted to; `None` only reports what they use
+    pub conversion: Option<Conversion>,
+    /// Abort on the first per-file error instead of recording it and
+    /// carrying on
+    pub fail_fast: bool,
+}
+
+impl TextOptions {
+    /// A recursive report on the files under `input_dir` with one of the
+    /// [`TEXT_EXTENSIONS`], converting nothing
+    pub fn new(input_dir: PathBuf) -> Self {
+        Self {
+            input_dir,
+            recursive: true,
+            include_repos: false,
+            filter: PathFilter::default(),
+            extensions: TEXT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            conversion: None,
+            fail_fast: false,
+        }
+    }
+
+    fn has_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| self.extensions.contains(&ext))
+    }
+}
+
+/// What became, or would become, of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    /// Already matches the target, or there is none
+    Unchanged,
+    /// Will be converted
+    Convert,
+    Converted,
+    /// The conversion did not decode back to the same text, so the file
+    /// was left alone
+    Unverified(String),
+    /// Binary contents under a text extension; never converted
+    Binary,
+    /// Not valid in the encoding it most likely has
+    Undecodable(String),
+    /// Could not be read or written
+    Failed(String),
+}
+
+impl FileStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::Convert => "convert",
+            Self::Converted => "converted",
+            Self::Unverified(_) => "unverified",
+            Self::Binary => "binary",
+            Self::Undecodable(_) => "undecodable",
+            Self::Failed(_) => "failed",
+        }
+    }
+}
+
+/// One file with a text extension
+#[derive(Debug, Clone, Serialize)]
+pub struct TextFile {
+    pub path: PathBuf,
+    /// `None` for binary and undecodable files
+    pub detected: Option<Detected>,
+    pub status: FileStatus,
+}
+
+/// Files found by [`plan`], before anything is converted
+#[derive(Debug, Default, Serialize)]
+pub struct TextPlan {
+    /// In walk order
+    pub files: Vec<TextFile>,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be read
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl TextPlan {
+    /// Files that will be converted
+    pub fn conversions(&self) -> usize {
+        self.count(|status| *status == FileStatus::Convert)
+    }
+
+    fn count(&self, matches: impl Fn(&FileStatus) -> bool) -> usize {
+        self.files.iter().filter(|file| matches(&file.status)).count()
+    }
+
+    /// Prints how many text files use each encoding and each kind of line
+    /// endings, and how many were binary or undecodable
+    pub fn print_distribution(&self) {
+        let mut encodings: BTreeMap<String, usize> = BTreeMap::new();
+        let mut endings: BTreeMap<LineEndings, usize> = BTreeMap::new();
+        for detected in self.files.iter().filter_map(|file| file.detected.as_ref()) {
+            let encoding = match detected.bom {
+                true => format!("{} BOM", detected.encoding),
+                false => detected.encoding.to_string(),
+            };
+            *encodings.entry(encoding).or_default() += 1;
+            *endings.entry(detected.line_endings).or_default() += 1;
+        }
+
+        println!();
+        println!("Encodings:");
+        for (encoding, count) in &encodings {
+            println!("  {:<16} {:>8}", encoding, count);
+        }
+        println!("Line endings:");
+        for (line_endings, count) in &endings {
+            println!("  {:<16} {:>8}", line_endings.label(), count);
+        }
+        let binary = self.count(|status| *status == FileStatus::Binary);
+        let undecodable = self.count(|status| matches!(status, FileStatus::Undecodable(_)));
+        if binary > 0 || undecodable > 0 {
+            println!("{} binary and {} undecodable files were left alone", binary, undecodable);
+        }
+    }
+
+    /// Prints `old → new` for every file to convert and the reason for
+    /// every one that cannot be, paths relative to `input`
+    pub fn print_changes(&self, input: &Path, conversion: &Conversion) {
+        let relative = |file: &TextFile| file.path.strip_prefix(input).unwrap_or(&file.path).display().to_string();
+        let width = self
+            .files
+            .iter()
+            .filter(|file| file.status != FileStatus::Unchanged)
+            .map(|file| relative(file).chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(60);
+
+        println!();
+        for file in &self.files {
+            match (&file.status, &file.detected) {
+                (FileStatus::Convert, Some(detected)) => {
+                    println!("{:<width$}  {}  →  {}", relative(file), detected.label(), conversion.label())
+                }
+                (FileStatus::Unverified(reason) | FileStatus::Undecodable(reason), _) => {
+                    println!("{:<width$}  left alone: {}", relative(file), reason)
+                }
+                _ => {}
+            }
+        }
+        let unverified = self.count(|status| matches!(status, FileStatus::Unverified(_)));
+        println!();
+        println!(
+            "{} files would be converted to {}, {} already match",
+            self.conversions(),
+            conversion.label(),
+            self.count(|status| *status == FileStatus::Unchanged)
+        );
+        if unverified > 0 {
+            println!("{} files fail round-trip verification and would be left alone", unverified);
+        }
+    }
+}
+
+/// Outcome of [`apply`]
+#[derive(Debug, Default, Serialize)]
+pub struct TextSummary {
+    /// Every file looked at, with what became of it
+    pub files: Vec<TextFile>,
+    /// Bytes of the files converted, as they were before
+    pub bytes: u64,
+    /// Working copies that were skipped
+    pub repositories: Vec<PathBuf>,
+    /// Files that could not be read or written
+    pub errors: Vec<FileError>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl TextSummary {
+    pub fn converted(&self) -> usize {
+        self.files.iter().filter(|file| file.status == FileStatus::Converted).count()
+    }
+
+    /// Files a conversion was planned for that were left alone, having
+    /// failed verification when read again
+    pub fn unverified(&self) -> impl Iterator<Item = &TextFile> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Unverified(_)))
+    }
+}
+
+impl From<&TextSummary> for Summary {
+    fn from(summary: &TextSummary) -> Self {
+        Summary {
+            files: summary.converted(),
+            bytes: summary.bytes,
+            skipped: summary.unverified().count(),
+            junk: 0,
+            inaccessible: summary.inaccessible.len(),
+            failed: summary.errors.len(),
+            phases: Vec::new(),
+        }
+    }
+}
+
+/// Reads every file under the input directory with one of the extensions,
+/// in parallel, and works out its encoding and line endings and, given a
+/// target, whether converting it is needed and safe. Binary contents are
+/// told apart by zero bytes and control characters, whatever the
+/// extension.
+pub fn plan(options: &TextOptions, progress: impl ProgressSink) -> Result<TextPlan> {
+    let root = &options.input_dir;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+    progress.scan_started(root);
+    let walk = Walker::new(options.recursive)
+        .include_repos(options.include_repos)
+        .filter(options.filter.clone())
+        .size_range(None, Some(MAX_SIZE))
+        .sorted(true)
+        .files(root);
+    let entries: Vec<_> = walk
+        .files
+        .into_iter()
+        .filter(|entry| options.has_extension(&entry.path))
+        .collect();
+
+    progress.phase_changed("detecting", entries.len() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    let inaccessible = Mutex::new(walk.inaccessible);
+    let files = entries
+        .par_iter()
+        .map(|entry| {
+            progress.item_started(&entry.path);
+            let file = match fs::read(&entry.path) {
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    inaccessible.lock().unwrap().push(Inaccessible::new(&entry.path, e));
+                    None
+                }
+                result => errors
+                    .check(&entry.path, "read", result.map_err(Into::into))?
+                    .map(|bytes| inspect(&entry.path, &bytes, options.conversion.as_ref())),
+            };
+            let outcome = if file.is_some() { Outcome::Done } else { Outcome::Failed };
+            progress.item_finished(&entry.path, entry.metadata.len(), outcome);
+            Ok(file)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(TextPlan {
+        files: files.into_iter().flatten().collect(),
+        repositories: walk.repositories,
+        errors: errors.into_errors(),
+        inaccessible: inaccessible.into_inner().unwrap(),
+    })
+}
+
+/// Detects what `bytes`, the contents of `path`, are and what `conversion`
+/// would make of them
+fn inspect(path: &Path, bytes: &[u8], conversion: Option<&Conversion>) -> TextFile {
+    let (detected, status) = match detect::analyze(bytes) {
+        Content::Binary => (None, FileStatus::Binary),
+        Content::Undecodable(encoding) => (None, FileStatus::Undecodable(format!("not valid {}", encoding))),
+        Content::Text(text) => {
+            let status = match conversion.map(|conversion| text.convert(bytes, conversion)) {
+                None | Some(Ok(None)) => FileStatus::Unchanged,
+                Some(Ok(Some(_))) => FileStatus::Convert,
+                Some(Err(e)) => FileStatus::Unverified(e.to_string()),
+            };
+            (Some(text.detected()), status)
+        }
+    };
+    TextFile {
+        path: path.to_path_buf(),
+        detected,
+        status,
+    }
+}
+
+/// Converts every file `plan` marks for it, in parallel. Each is read and
+/// checked again first, so a file that changed since the scan is only
+/// converted if that is still safe, and is written under a temporary name
+/// that replaces it once complete, keeping its permissions.
+///
+/// ```
+/// use file_organizer_rust::modules::text_normalizer::{self, Conversion, Newline, TextOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// std::fs::write(dir.path().join("notes.txt"), b"caf\xe9\r\nna\xefve\r\n")?;
+/// std::fs::write(dir.path().join("unix.md"), "# Title\n")?;
+/// std::fs::write(dir.path().join("logo.svg"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")?;
+///
+/// let options = TextOptions {
+///     conversion: Some(Conversion::new(Newline::Lf)),
+///     ..TextOptions::new(dir.path().to_path_buf())
+/// };
+/// let plan = text_normalizer::plan(&options, ())?;
+/// let summary = text_normalizer::apply(&options, plan, ())?;
+/// assert_eq!(summary.converted(), 1);
+/// assert_eq!(std::fs::read_to_string(dir.path().join("notes.txt"))?, "café\nnaïve\n");
+/// assert_eq!(std::fs::read(dir.path().join("logo.svg"))?, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn apply(options: &TextOptions, plan: TextPlan, progress: impl ProgressSink) -> Result<TextSummary> {
+    let Some(conversion) = &options.conversion else {
+        anyhow::bail!("No conversion target set");
+    };
+    progress.phase_changed("converting", plan.conversions() as u64);
+    let errors = ErrorLog::new(options.fail_fast);
+    errors.extend(plan.errors);
+    let files = plan
+        .files
+        .into_par_iter()
+        .map(|mut file| {
+            if file.status != FileStatus::Convert {
+                return Ok((file, 0));
+            }
+            progress.item_started(&file.path);
+            let result = convert(&file.path, conversion);
+            let (bytes, outcome) = match errors.check(&file.path, "convert", result)? {
+                Some(Converted::Written(bytes)) => {
+                    file.status = FileStatus::Converted;
+                    (bytes, Outcome::Done)
+                }
+                Some(Converted::Unchanged) => {
+                    file.status = FileStatus::Unchanged;
+                    (0, Outcome::Skipped)
+                }
+                Some(Converted::Refused(status)) => {
+                    file.status = status;
+                    (0, Outcome::Skipped)
+                }
+                None => {
+                    file.status = FileStatus::Failed("could not be converted".to_string());
+                    (0, Outcome::Failed)
+                }
+            };
+            progress.item_finished(&file.path, bytes, outcome);
+            Ok((file, bytes))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let summary = TextSummary {
+        bytes: files.iter().map(|(_, bytes)| bytes).sum(),
+        files: files.into_iter().map(|(file, _)| file).collect(),
+        repositories: plan.repositories,
+        errors: errors.into_errors(),
+        inaccessible: plan.inaccessible,
+    };
+    progress.finished(&Summary::from(&summary));
+    Ok(summary)
+}
+
+/// What [`convert`] did with a file
+enum Converted {
+    /// Replaced; the size it had before
+    Written(u64),
+    /// Already matched by the time it was read again
+    Unchanged,
+    /// Left alone, with why
+    Refused(FileStatus),
+}
+
+fn convert(path: &Path, conversion: &Conversion) -> Result<Converted> {
+    let bytes = fs::read(path)?;
+    let text = match detect::analyze(&bytes) {
+        Content::Text(text) => text,
+        Content::Binary => return Ok(Converted::Refused(FileStatus::Binary)),
+        Content::Undecodable(encoding) => {
+            return Ok(Converted::Refused(FileStatus::Undecodable(format!("not valid {}", encoding))))
+        }
+    };
+    let converted = match text.convert(&bytes, conversion) {
+        Ok(Some(converted)) => converted,
+        Ok(None) => return Ok(Converted::Unchanged),
+        Err(e) => return Ok(Converted::Refused(FileStatus::Unverified(e.to_string()))),
+    };
+
+    let permissions = fs::metadata(path)?.permissions();
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(&converted)?;
+    file.as_file().set_permissions(permissions)?;
+    file.commit()?;
+    Ok(Converted::Written(bytes.len() as u64))
+}
+
+#[async_trait]
+impl FileOrganizer for TextNormalizer {
+    type Plan = TextPlan;
+    const NAME: &'static str = "normalize-text";
+
+    fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            input_dir: None,
+            extensions: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            include_repos: false,
+            conversion: None,
+            dry_run: false,
+            report: None,
+            report_path: None,
+            fail_fast: false,
+            metrics: Metrics::default(),
+        }
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        if self.input_dir.is_none() {
+            self.input_dir = Some(get_directory_from_user("Enter directory with text files")?);
+        }
+        if self.conversion.is_none() {
+            let theme = ColorfulTheme::default();
+            let choice = Select::with_theme(&theme)
+                .with_prompt("Convert files to")
+                .items(&["Nothing, only report", "UTF-8 with LF line endings", "UTF-8 with CRLF line endings"])
+                .default(0)
+                .interact()?;
+            let newline = match choice {
+                1 => Newline::Lf,
+                2 => Newline::Crlf,
+                _ => {
+                    self.conversion = Some(None);
+                    return Ok(());
+                }
+            };
+            let bom = Select::with_theme(&theme)
+                .with_prompt("Byte order marks")
+                .items(&["Keep them where files have one", "Add one to every file", "Remove them"])
+                .default(0)
+                .interact()?;
+            self.conversion = Some(Some(Conversion {
+                newline,
+                bom: [BomPolicy::Keep, BomPolicy::Add, BomPolicy::Remove][bom],
+            }));
+        }
+        Ok(())
+    }
+
+    fn plan(&mut self) -> Result<TextPlan> {
+        let spinner = create_spinner("Detecting encodings...");
+        self.metrics.phase("detecting");
+        let plan = plan(&self.options()?, (&*spinner, &self.metrics))?;
+        spinner.finish_and_clear();
+        Ok(plan)
+    }
+
+    /// Shows the encodings and line endings found, then every planned
+    /// conversion. Without a target, or in a dry run, stops there after
+    /// offering the report; otherwise asks before converting anything.
+    fn review(&self, plan: &TextPlan) -> Result<bool> {
+        if plan.files.is_empty() {
+            println!("No text files found");
+            return Ok(false);
+        }
+        plan.print_distribution();
+        print_inaccessible(&plan.inaccessible, "they were not looked at");
+        print_errors(&plan.errors);
+        let Some(Some(conversion)) = &self.conversion else {
+            self.write_report(&plan.files)?;
+            return Ok(false);
+        };
+        let input_dir = self.input_dir.as_deref().unwrap_or(Path::new(""));
+        plan.print_changes(input_dir, conversion);
+        if self.dry_run {
+            self.write_report(&plan.files)?;
+            println!("Dry run finished, nothing was converted");
+            return Ok(false);
+        }
+        if plan.conversions() == 0 {
+            println!("Nothing to convert");
+            return Ok(false);
+        }
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Convert {} files to {}?", plan.conversions(), conversion.label()))
+            .default(true)
+            .interact()
+    }
+
+    fn execute(&self, plan: TextPlan) -> Result<Summary> {
+        let options = self.options()?;
+        let spinner = create_spinner("Converting files...");
+        self.metrics.phase("converting");
+        let summary = apply(&options, plan, (&*spinner, &self.metrics))?;
+        spinner.finish_with_message(format!("{} files converted", summary.converted()));
+        for file in summary.unverified() {
+            if let FileStatus::Unverified(reason) = &file.status {
+                println!("Left {} alone: {}", file.path.display(), reason);
+            }
+        }
+        if !summary.repositories.is_empty() {
+            println!(
+                "Skipped {} repositories (use --include-repos to convert inside them)",
+                summary.repositories.len()
+            );
+        }
+        print_inaccessible(&summary.inaccessible, "they were left as they are");
+        print_errors(&summary.errors);
+        self.write_report(&summary.files)?;
+
+        Ok(Summary {
+            phases: self.metrics.finish(),
+            ..Summary::from(&summary)
+        })
+    }
+}
+
+impl TextNormalizer {
+    /// Presets the directory so the prompt is skipped
+    pub fn with_input_dir(mut self, dir: PathBuf) -> Self {
+        self.input_dir = Some(dir);
+        self
+    }
+
+    /// Looks at files with these extensions instead of [`TEXT_EXTENSIONS`]
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Only looks at files whose path relative to the input matches one of
+    /// `patterns`, e.g. "*.md" or "docs/**"
+    pub fn with_include(mut self, patterns: Vec<String>) -> Self {
+        self.include = patterns;
+        self
+    }
+
+    /// Leaves files whose relative path matches one of `patterns` alone
+    pub fn with_exclude(mut self, patterns: Vec<String>) -> Self {
+        self.exclude = patterns;
+        self
+    }
+
+    /// Looks inside working copies as well instead of skipping them
+    pub fn with_include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Converts files to `conversion`, or with `None` only reports,
+    /// without asking
+    pub fn with_conversion(mut self, conversion: Option<Conversion>) -> Self {
+        self.conversion = Some(conversion);
+        self
+    }
+
+    /// Lists the conversions without making them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Writes the per-file report as CSV or JSON, to `path` or next to the
+    /// input, or with `None` writes none, without asking
+    pub fn with_report(mut self, format: Option<ReportFormat>, path: Option<PathBuf>) -> Self {
+        self.report = Some(format);
+        self.report_path = path;
+        self
+    }
+
+    /// Aborts on the first file that cannot be read or converted
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Writes the per-file report in the preset format, or asks whether to
+    /// write one and in which format; the path defaults to one in the input
+    /// directory
+    fn write_report(&self, files: &[TextFile]) -> Result<()> {
+        let format = match self.report {
+            Some(format) => format,
+            None => {
+                let choice = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Write a per-file report?")
+                    .items(&["No", "CSV", "JSON"])
+                    .default(0)
+                    .interact()?;
+                [None, Some(ReportFormat::Csv), Some(ReportFormat::Json)][choice]
+            }
+        };
+        let Some(format) = format else {
+            return Ok(());
+        };
+
+        let input_dir = self.input_dir.clone().unwrap_or_default();
+        let path = self
+            .report_path
+            .clone()
+            .unwrap_or_else(|| report::default_path(&input_dir, format));
+        report::write(files, &path, format)?;
+        println!("Report written to {}", path.display());
+        Ok(())
+    }
+
+    /// The configured settings as library options
+    fn options(&self) -> Result<TextOptions> {
+        let input_dir = self.input_dir.clone().ok_or_else(|| anyhow::anyhow!("Input directory not set"))?;
+        let defaults = TextOptions::new(input_dir);
+        let extensions = match &self.extensions {
+            Some(extensions) => extensions
+                .iter()
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .collect(),
+            None => defaults.extensions.clone(),
+        };
+        Ok(TextOptions {
+            recursive: self.recursive,
+            include_repos: self.include_repos,
+            filter: PathFilter::new(&self.include, &self.exclude)?,
+            extensions,
+            conversion: self.conversion.flatten(),
+            fail_fast: self.fail_fast,
+            ..defaults
+        })
+    }
+}
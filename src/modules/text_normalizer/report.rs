@@ -0,0 +1,59 @@
+use anyhow::Result;
+use chrono::Local;
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{FileStatus, TextFile};
+use crate::modules::file_categorizer::ReportFormat;
+use crate::utils::{
+    atomic::{write_atomic, AtomicFile},
+    csv_field,
+};
+
+/// `<input>/text-report-<timestamp>.<ext>`
+pub fn default_path(input_dir: &Path, format: ReportFormat) -> PathBuf {
+    input_dir.join(format!(
+        "text-report-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ))
+}
+
+/// Writes one row per file looked at, as JSON or as CSV with the columns
+/// path, encoding, bom, line endings, status, and the reason a file was
+/// left alone or failed
+pub fn write(files: &[TextFile], path: &Path, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Json => write_atomic(path, serde_json::to_string_pretty(files)?)?,
+        ReportFormat::Csv => {
+            let mut file = BufWriter::new(AtomicFile::create(path)?);
+            writeln!(file, "path,encoding,bom,line_endings,status,reason")?;
+            for text in files {
+                let (encoding, bom, line_endings) = match &text.detected {
+                    Some(detected) => (detected.encoding, detected.bom.to_string(), detected.line_endings.label()),
+                    None => ("", String::new(), ""),
+                };
+                let reason = match &text.status {
+                    FileStatus::Unverified(reason) | FileStatus::Undecodable(reason) | FileStatus::Failed(reason) => {
+                        reason.as_str()
+                    }
+                    _ => "",
+                };
+                writeln!(
+                    file,
+                    "{},{},{},{},{},{}",
+                    csv_field(&text.path.display().to_string()),
+                    encoding,
+                    bom,
+                    line_endings,
+                    text.status.label(),
+                    csv_field(reason)
+                )?;
+            }
+            file.into_inner()?.commit()?;
+        }
+    }
+    Ok(())
+}
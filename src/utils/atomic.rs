@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+/// A file written under a temporary name next to `path` and renamed over
+/// it by [`commit`](Self::commit), so an interrupted or failed write never
+/// leaves a truncated file at `path`. Dropped without committing, the
+/// temporary file is removed.
+#[derive(Debug)]
+pub struct AtomicFile {
+    temp: NamedTempFile,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    /// Starts writing what will become `path`. Its directory must exist.
+    pub fn create(path: &Path) -> Result<Self> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let prefix = format!(".{}.", path.file_name().unwrap_or_default().to_string_lossy());
+        let temp = tempfile::Builder::new()
+            .prefix(&prefix)
+            .suffix(".tmp")
+            .tempfile_in(dir)
+            .with_context(|| format!("Cannot create a temporary file in {}", dir.display()))?;
+        Ok(Self {
+            temp,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn as_file(&self) -> &File {
+        self.temp.as_file()
+    }
+
+    /// Flushes the contents to disk and renames them into place, replacing
+    /// any file already there
+    pub fn commit(self) -> Result<()> {
+        self.temp.as_file().sync_all()?;
+        self.temp
+            .persist(&self.path)
+            .with_context(|| format!("Cannot write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.temp.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.temp.flush()
+    }
+}
+
+impl Seek for AtomicFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.temp.seek(pos)
+    }
+}
+
+/// Replaces `path` with `contents` in one step
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(contents.as_ref())?;
+    file.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Read};
+
+    /// Yields `good` bytes, then fails
+    struct FailingReader {
+        good: usize,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.good == 0 {
+                return Err(io::Error::other("disk unplugged"));
+            }
+            let n = buf.len().min(self.good);
+            buf[..n].fill(b'x');
+            self.good -= n;
+            Ok(n)
+        }
+    }
+
+    fn copy_atomic(path: &Path, mut reader: impl Read) -> Result<()> {
+        let mut file = AtomicFile::create(path)?;
+        io::copy(&mut reader, &mut file)?;
+        file.commit()
+    }
+
+    #[test]
+    fn a_write_failing_partway_leaves_the_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, "original").unwrap();
+
+        assert!(copy_atomic(&path, FailingReader { good: 100_000 }).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, ["data.bin"]);
+    }
+
+    #[test]
+    fn a_committed_write_replaces_the_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, "original").unwrap();
+
+        copy_atomic(&path, &b"replacement"[..]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "replacement");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use super::secure_delete::{shred, ShredOptions};
+
+/// How destructive operations get rid of a file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeleteMode {
+    /// Move to the platform trash or recycle bin, where it can be restored
+    #[default]
+    Trash,
+    Permanent,
+    /// Overwrite the contents before deleting permanently, see
+    /// [`shred`]
+    Secure,
+    /// Leave the file alone, as if it had been deleted
+    DryRun,
+}
+
+impl DeleteMode {
+    /// Describes what happened to a file, e.g. "moved to the trash"
+    pub fn verb(self) -> &'static str {
+        match self {
+            Self::Trash => "moved to the trash",
+            Self::Permanent => "deleted",
+            Self::Secure => "overwritten and deleted",
+            Self::DryRun => "left in place (dry run)",
+        }
+    }
+}
+
+/// Deletes one file, or a directory with everything in it, the way `mode`
+/// asks
+pub fn safe_delete(path: &Path, mode: DeleteMode) -> Result<()> {
+    match mode {
+        DeleteMode::Trash => {
+            // Fail a missing file the same way in every mode, so the trash
+            // hint is only given when the trash itself is the problem
+            fs::symlink_metadata(path)?;
+            trash::delete(path).map_err(|e| trash_error(path, e))
+        }
+        DeleteMode::Permanent if fs::symlink_metadata(path)?.is_dir() => Ok(fs::remove_dir_all(path)?),
+        DeleteMode::Permanent => Ok(fs::remove_file(path)?),
+        DeleteMode::Secure => shred(path, &ShredOptions::default()),
+        DeleteMode::DryRun => Ok(()),
+    }
+}
+
+/// Deletes many files, handing them to the trash in one batch, which is
+/// much faster than one at a time. Returns a result per path, in order.
+pub fn safe_delete_all<P: AsRef<Path>>(paths: &[P], mode: DeleteMode) -> Vec<Result<()>> {
+    if mode != DeleteMode::Trash {
+        return paths.iter().map(|path| safe_delete(path.as_ref(), mode)).collect();
+    }
+
+    // Missing paths would fail the whole batch, so they are left out and
+    // reported on their own
+    let present: Vec<bool> = paths
+        .iter()
+        .map(|path| fs::symlink_metadata(path).is_ok())
+        .collect();
+    let batch: Vec<&Path> = paths
+        .iter()
+        .zip(&present)
+        .filter(|(_, present)| **present)
+        .map(|(path, _)| path.as_ref())
+        .collect();
+    let batched = batch.is_empty() || trash::delete_all(&batch).is_ok();
+
+    // A failed batch may have trashed some paths before stopping, so only
+    // the ones still there are retried to find out which failed
+    paths
+        .iter()
+        .zip(present)
+        .map(|(path, present)| {
+            let path = path.as_ref();
+            if present && (batched || fs::symlink_metadata(path).is_err()) {
+                return Ok(());
+            }
+            safe_delete(path, mode)
+        })
+        .collect()
+}
+
+/// Explains a trash failure, which usually means the filesystem has no
+/// trash (network shares, removable drives, some containers)
+fn trash_error(path: &Path, error: trash::Error) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Cannot move {} to the trash ({}); use --delete-mode permanent to delete it instead",
+        path.display(),
+        error
+    )
+}
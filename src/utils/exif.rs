@@ -0,0 +1,161 @@
+use chrono::NaiveDateTime;
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Reads the EXIF `DateTimeOriginal` (falling back to `DateTime`) of a photo.
+///
+/// Returns `None` for files without EXIF data or with an unparsable date.
+pub fn capture_date(path: &Path) -> Option<NaiveDateTime> {
+    let file = File::open(path).ok()?;
+    let exif = ::exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    [::exif::Tag::DateTimeOriginal, ::exif::Tag::DateTime]
+        .iter()
+        .find_map(|tag| {
+            let field = exif.get_field(*tag, ::exif::In::PRIMARY)?;
+            match &field.value {
+                ::exif::Value::Ascii(values) => {
+                    let dt = ::exif::DateTime::from_ascii(values.first()?).ok()?;
+                    chrono::NaiveDate::from_ymd_opt(dt.year as i32, dt.month as u32, dt.day as u32)?
+                        .and_hms_opt(dt.hour as u32, dt.minute as u32, dt.second as u32)
+                }
+                _ => None,
+            }
+        })
+}
+
+/// Reads the EXIF `Make` and `Model` tags of a photo, either of which may be
+/// missing
+pub fn camera(path: &Path) -> Option<(Option<String>, Option<String>)> {
+    let file = File::open(path).ok()?;
+    let exif = ::exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let ascii = |tag| {
+        let field = exif.get_field(tag, ::exif::In::PRIMARY)?;
+        match &field.value {
+            ::exif::Value::Ascii(values) => {
+                let text = String::from_utf8_lossy(values.first()?);
+                let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+                (!text.is_empty()).then(|| text.to_string())
+            }
+            _ => None,
+        }
+    };
+    Some((ascii(::exif::Tag::Make), ascii(::exif::Tag::Model)))
+}
+
+/// Reads the EXIF GPS position of a photo as decimal degrees, latitude
+/// then longitude, negative for south and west.
+///
+/// Returns `None` for photos without a complete position, and for 0°, 0°,
+/// which cameras without a fix write.
+pub fn gps(path: &Path) -> Option<(f64, f64)> {
+    let file = File::open(path).ok()?;
+    let exif = ::exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let degrees = |tag, reference, negative: u8| {
+        let value = match &exif.get_field(tag, ::exif::In::PRIMARY)?.value {
+            ::exif::Value::Rational(parts) if parts.len() >= 3 => {
+                parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+            }
+            _ => return None,
+        };
+        let sign = match &exif.get_field(reference, ::exif::In::PRIMARY)?.value {
+            ::exif::Value::Ascii(values) if values.first()?.first() == Some(&negative) => -1.0,
+            _ => 1.0,
+        };
+        value.is_finite().then_some(sign * value)
+    };
+    let latitude = degrees(::exif::Tag::GPSLatitude, ::exif::Tag::GPSLatitudeRef, b'S')?;
+    let longitude = degrees(::exif::Tag::GPSLongitude, ::exif::Tag::GPSLongitudeRef, b'W')?;
+    let valid = (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude);
+    (valid && (latitude, longitude) != (0.0, 0.0)).then_some((latitude, longitude))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// One coordinate as EXIF writes it: degrees, minutes, hundredths of a
+    /// second, and the hemisphere letter
+    pub(crate) type Dms = (u32, u32, u32, u8);
+
+    /// A JPEG holding nothing but an EXIF segment with a GPS position,
+    /// which is all the reader looks at
+    pub(crate) fn gps_jpeg(latitude: Dms, longitude: Dms) -> Vec<u8> {
+        let entry = |tag: u16, kind: u16, count: u32, value: [u8; 4]| {
+            [&tag.to_be_bytes()[..], &kind.to_be_bytes(), &count.to_be_bytes(), &value].concat()
+        };
+        let reference = |letter: u8| [letter, 0, 0, 0];
+        let rationals = |(degrees, minutes, hundredths, _): Dms| {
+            [(degrees, 1), (minutes, 1), (hundredths, 100)]
+                .iter()
+                .flat_map(|&(numerator, denominator): &(u32, u32)| {
+                    [numerator.to_be_bytes(), denominator.to_be_bytes()].concat()
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        // Big-endian TIFF: the header, IFD0 pointing at the GPS IFD at 26,
+        // the GPS IFD, then the two rational triples at 80 and 104
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        tiff.extend(1u16.to_be_bytes());
+        tiff.extend(entry(0x8825, 4, 1, 26u32.to_be_bytes()));
+        tiff.extend(0u32.to_be_bytes());
+        tiff.extend(4u16.to_be_bytes());
+        tiff.extend(entry(1, 2, 2, reference(latitude.3)));
+        tiff.extend(entry(2, 5, 3, 80u32.to_be_bytes()));
+        tiff.extend(entry(3, 2, 2, reference(longitude.3)));
+        tiff.extend(entry(4, 5, 3, 104u32.to_be_bytes()));
+        tiff.extend(0u32.to_be_bytes());
+        tiff.extend(rationals(latitude));
+        tiff.extend(rationals(longitude));
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff);
+        jpeg.extend([0xFF, 0xD9]);
+        jpeg
+    }
+
+    /// The Eiffel Tower
+    pub(crate) const PARIS: (Dms, Dms) = ((48, 51, 3024, b'N'), (2, 17, 4020, b'E'));
+    /// Plaza de Armas, Santiago de Chile
+    pub(crate) const SANTIAGO: (Dms, Dms) = ((33, 26, 5604, b'S'), (70, 40, 948, b'W'));
+
+    fn gps_of(latitude: Dms, longitude: Dms) -> Option<(f64, f64)> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.jpg");
+        std::fs::write(&path, gps_jpeg(latitude, longitude)).unwrap();
+        gps(&path)
+    }
+
+    fn assert_near((latitude, longitude): (f64, f64), expected: (f64, f64)) {
+        let near = (latitude - expected.0).abs() < 1e-4 && (longitude - expected.1).abs() < 1e-4;
+        assert!(near, "{:?} is not {:?}", (latitude, longitude), expected);
+    }
+
+    #[test]
+    fn positions_are_read_as_signed_decimal_degrees() {
+        assert_near(gps_of(PARIS.0, PARIS.1).unwrap(), (48.8584, 2.2945));
+        assert_near(gps_of(SANTIAGO.0, SANTIAGO.1).unwrap(), (-33.4489, -70.6693));
+        // Mixed hemispheres, as for Sydney and New York
+        assert_near(gps_of((33, 51, 2448, b'S'), (151, 12, 5508, b'E')).unwrap(), (-33.8568, 151.2153));
+        assert_near(gps_of((40, 41, 2112, b'N'), (74, 2, 4020, b'W')).unwrap(), (40.6892, -74.0445));
+    }
+
+    #[test]
+    fn photos_without_a_fix_have_no_position() {
+        assert_eq!(gps_of((0, 0, 0, b'N'), (0, 0, 0, b'E')), None);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain.jpg");
+        std::fs::write(&path, [0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+        assert_eq!(gps(&path), None);
+    }
+}
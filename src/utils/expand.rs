@@ -0,0 +1,167 @@
+use anyhow::Result;
+use std::{env, path::PathBuf};
+
+/// Expands a path as a shell would before it is checked: a leading `~` or
+/// `~user`, and `$VAR` / `${VAR}` anywhere (plus `%VAR%` on Windows). Fails
+/// on unset variables rather than leaving them in the path.
+pub fn expand_path(input: &str) -> Result<PathBuf> {
+    expand_with(input, cfg!(windows), |name| env::var(name).ok(), home_dir)
+}
+
+/// [`expand_path`] with the platform, environment, and home directory
+/// lookup passed in
+fn expand_with(
+    input: &str,
+    windows: bool,
+    var: impl Fn(&str) -> Option<String>,
+    home: impl Fn(Option<&str>) -> Option<PathBuf>,
+) -> Result<PathBuf> {
+    let expanded = expand_vars(input, windows, &var)?;
+    let separators: &[char] = if windows { &['/', '\\'] } else { &['/'] };
+
+    let Some(rest) = expanded.strip_prefix('~') else {
+        return Ok(PathBuf::from(expanded));
+    };
+    let (user, tail) = match rest.find(separators) {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let user = (!user.is_empty()).then_some(user);
+    let Some(dir) = home(user) else {
+        match user {
+            Some(user) => anyhow::bail!("Cannot find the home directory of user '{}'", user),
+            None => anyhow::bail!("Cannot find the home directory"),
+        }
+    };
+    Ok(if tail.is_empty() { dir } else { dir.join(tail) })
+}
+
+fn expand_vars(input: &str, windows: bool, var: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let lookup = |name: &str| {
+        var(name).ok_or_else(|| anyhow::anyhow!("Environment variable {} is not set", name))
+    };
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(i) = rest.find(|c| c == '$' || windows && c == '%') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, consumed) = if rest[i..].starts_with('%') {
+            match after.find('%') {
+                Some(end) if end > 0 && is_var_name(&after[..end]) => (&after[..end], end + 1),
+                _ => ("", 0),
+            }
+        } else if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => anyhow::bail!("Unclosed ${{ in {}", input),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+
+        if name.is_empty() {
+            // A lone `$` or `%` is part of the name, not a variable
+            out.push_str(&rest[i..i + 1]);
+        } else {
+            out.push_str(&lookup(name)?);
+        }
+        rest = &after[consumed..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_var_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '(' || c == ')')
+}
+
+/// The current user's home directory, or another user's where the platform
+/// makes it possible to look up
+fn home_dir(user: Option<&str>) -> Option<PathBuf> {
+    match user {
+        None => dirs::home_dir(),
+        Some(user) => user_home_dir(user),
+    }
+}
+
+#[cfg(unix)]
+fn user_home_dir(user: &str) -> Option<PathBuf> {
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() > 5 && fields[0] == user).then(|| PathBuf::from(fields[5]))
+    })
+}
+
+#[cfg(not(unix))]
+fn user_home_dir(_user: &str) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn var(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".to_string()),
+            "USERPROFILE" => Some(r"C:\Users\me".to_string()),
+            "PICS" => Some("Pictures".to_string()),
+            _ => None,
+        }
+    }
+
+    fn home(user: Option<&str>) -> Option<PathBuf> {
+        match user {
+            None => Some(PathBuf::from("/home/me")),
+            Some("ann") => Some(PathBuf::from("/home/ann")),
+            Some(_) => None,
+        }
+    }
+
+    fn unix(input: &str) -> Result<PathBuf> {
+        expand_with(input, false, var, home)
+    }
+
+    fn windows(input: &str) -> Result<PathBuf> {
+        expand_with(input, true, var, home)
+    }
+
+    #[test]
+    fn a_leading_tilde_is_the_home_directory() {
+        assert_eq!(unix("~").unwrap(), Path::new("/home/me"));
+        assert_eq!(unix("~/Pictures").unwrap(), Path::new("/home/me/Pictures"));
+        assert_eq!(unix("~ann/Music").unwrap(), Path::new("/home/ann/Music"));
+        assert!(unix("~bob/Music").is_err());
+        // Only at the start
+        assert_eq!(unix("a/~/b").unwrap(), Path::new("a/~/b"));
+    }
+
+    #[test]
+    fn dollar_variables_are_expanded_with_or_without_braces() {
+        assert_eq!(unix("$HOME/$PICS").unwrap(), Path::new("/home/me/Pictures"));
+        assert_eq!(unix("${HOME}/${PICS}2024").unwrap(), Path::new("/home/me/Pictures2024"));
+        assert_eq!(unix("/price$").unwrap(), Path::new("/price$"));
+        assert!(unix("$UNSET/files").is_err());
+        assert!(unix("${HOME/files").is_err());
+    }
+
+    #[test]
+    fn percent_variables_are_expanded_on_windows_only() {
+        assert_eq!(windows(r"%USERPROFILE%\%PICS%").unwrap(), Path::new(r"C:\Users\me\Pictures"));
+        assert_eq!(windows("100%").unwrap(), Path::new("100%"));
+        assert!(windows("%UNSET%").is_err());
+        assert_eq!(unix("%PICS%").unwrap(), Path::new("%PICS%"));
+    }
+
+    #[test]
+    fn a_tilde_ends_at_either_separator_on_windows() {
+        assert_eq!(windows(r"~\Documents").unwrap(), Path::new("/home/me").join("Documents"));
+        assert!(unix(r"~\Documents").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+/// Include/exclude glob filter over paths relative to the walked root.
+/// `*` also matches across directories, so `*.pdf` finds PDFs at any depth.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    /// Builds the filter; an empty include list includes everything
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    /// Whether `path` under `root` passes the filter
+    pub fn matches(&self, path: &Path, root: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.include.as_ref().is_none_or(|set| set.is_match(relative))
+            && !self.exclude.as_ref().is_some_and(|set| set.is_match(relative))
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).with_context(|| format!("Invalid glob '{}'", pattern))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
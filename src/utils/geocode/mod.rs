@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex};
+
+/// Centroids of countries and their regions, `country,region,lat,lon`
+const PLACES: &str = include_str!("places.csv");
+
+/// Mean radius of the Earth
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Positions farther than this from every row, such as the open sea, have
+/// no place
+const MAX_DISTANCE_KM: f64 = 1500.0;
+
+/// Where a position is, as far as the embedded table can tell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Place {
+    pub country: String,
+    /// `None` for countries the table has no regions for
+    pub region: Option<String>,
+}
+
+/// Reverse geocoder that needs no network: a position belongs to the
+/// nearest country or region centroid in a table built into the binary.
+/// That is coarse near borders, but enough to sort travel photos by
+/// country. Lookups are cached, as bursts of photos share a position.
+#[derive(Debug)]
+pub struct Geocoder {
+    places: Vec<(Place, f64, f64)>,
+    /// Row of the nearest place, by position rounded to about 100 m
+    cache: Mutex<HashMap<(i32, i32), Option<usize>>>,
+}
+
+impl Default for Geocoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Geocoder {
+    pub fn new() -> Self {
+        let places = PLACES
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split(',');
+                let country = fields.next()?.to_string();
+                let region = Some(fields.next()?).filter(|region| !region.is_empty()).map(str::to_string);
+                let latitude = fields.next()?.parse().ok()?;
+                let longitude = fields.next()?.parse().ok()?;
+                Some((Place { country, region }, latitude, longitude))
+            })
+            .collect();
+        Self {
+            places,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The place nearest to a position in decimal degrees, or `None` out
+    /// at sea
+    ///
+    /// ```
+    /// use file_organizer_rust::utils::geocode::Geocoder;
+    ///
+    /// let geocoder = Geocoder::new();
+    /// let place = |latitude, longitude| {
+    ///     geocoder
+    ///         .lookup(latitude, longitude)
+    ///         .map(|place| (place.country.as_str(), place.region.as_deref()))
+    /// };
+    /// assert_eq!(place(48.8584, 2.2945), Some(("France", Some("Île-de-France"))));
+    /// assert_eq!(place(-33.8568, 151.2153), Some(("Australia", Some("New South Wales"))));
+    /// assert_eq!(place(40.6892, -74.0445), Some(("United States", Some("New York"))));
+    /// assert_eq!(place(35.6586, 139.7454), Some(("Japan", Some("Kanto"))));
+    /// assert_eq!(place(-13.1631, -72.5450), Some(("Peru", None)));
+    /// assert_eq!(place(30.0, -50.0), None);
+    /// ```
+    pub fn lookup(&self, latitude: f64, longitude: f64) -> Option<&Place> {
+        let key = ((latitude * 1000.0).round() as i32, (longitude * 1000.0).round() as i32);
+        let cached = self.cache.lock().unwrap().get(&key).copied();
+        let row = match cached {
+            Some(row) => row,
+            None => {
+                let row = self.nearest(latitude, longitude);
+                self.cache.lock().unwrap().insert(key, row);
+                row
+            }
+        };
+        row.map(|row| &self.places[row].0)
+    }
+
+    fn nearest(&self, latitude: f64, longitude: f64) -> Option<usize> {
+        self.places
+            .iter()
+            .enumerate()
+            .map(|(row, (_, lat, lon))| (row, distance_km((latitude, longitude), (*lat, *lon))))
+            .filter(|(_, distance)| *distance <= MAX_DISTANCE_KM)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(row, _)| row)
+    }
+}
+
+/// Great-circle distance between two positions in decimal degrees
+fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let (dlat, dlon) = ((b.0 - a.0).to_radians(), (b.1 - a.1).to_radians());
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{
+    atomic::write_atomic,
+    hashing::{Algorithm, Hashing},
+};
+
+/// How often a run that keeps hashing saves the cache, so an interrupted
+/// run loses at most this much work
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whole-file digests kept between runs, shared by deduplication and
+/// checksum manifests. An entry is only used while the file still has the
+/// size and modification time it was hashed with, which catches every
+/// ordinary edit but not corruption that leaves both alone; verifying a
+/// manifest therefore always reads the files.
+#[derive(Debug)]
+pub struct HashCache {
+    /// Where the cache is saved; `None` keeps it in memory only
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, CachedDigest>>,
+    /// New entries since the last save, and when that was
+    unsaved: Mutex<(usize, Instant)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedDigest {
+    size: u64,
+    mtime: i64,
+    mtime_nanos: u32,
+    digest: String,
+}
+
+/// A file's size and digest as the cache recorded them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownFile {
+    pub algorithm: Algorithm,
+    pub size: u64,
+    pub digest: String,
+}
+
+impl HashCache {
+    /// `<cache dir>/file-organizer-rust/hashes.json`
+    pub fn default_path() -> Result<PathBuf> {
+        let dir = dirs::cache_dir().ok_or_else(|| anyhow::anyhow!("No cache directory on this platform"))?;
+        Ok(dir.join("file-organizer-rust").join("hashes.json"))
+    }
+
+    /// Loads the cache saved at `path`. A missing or unreadable cache
+    /// starts empty; it only ever saves time.
+    pub fn load(path: &Path) -> Self {
+        let entries = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path.to_path_buf()),
+            entries: Mutex::new(entries),
+            unsaved: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    /// A cache that is never saved, for runs asked not to use one
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: Mutex::new(HashMap::new()),
+            unsaved: Mutex::new((0, Instant::now())),
+        }
+    }
+
+    /// [`load`](Self::load) from `path`, or [`in_memory`](Self::in_memory)
+    /// for `None`
+    pub fn open(path: Option<&Path>) -> Self {
+        path.map_or_else(Self::in_memory, Self::load)
+    }
+
+    /// The digest of the file at `path`, whose current metadata is
+    /// `metadata`: from the cache when the file is unchanged since it was
+    /// hashed, otherwise read and remembered. Returns whether it came from
+    /// the cache.
+    pub fn digest(&self, hashing: &Hashing, path: &Path, metadata: &Metadata) -> Result<(String, bool)> {
+        let Some(key) = key(hashing.algorithm, path) else {
+            return Ok((hashing.digest_file(path)?, false));
+        };
+        let mtime = FileTime::from_last_modification_time(metadata);
+        let stamp = CachedDigest {
+            size: metadata.len(),
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            digest: String::new(),
+        };
+        if let Some(cached) = self.entries.lock().unwrap().get(&key) {
+            if (cached.size, cached.mtime, cached.mtime_nanos) == (stamp.size, stamp.mtime, stamp.mtime_nanos) {
+                return Ok((cached.digest.clone(), true));
+            }
+        }
+
+        // Stored with the metadata from before reading, so a file written
+        // to meanwhile no longer matches its entry on the next run
+        let digest = hashing.digest_file(path)?;
+        self.insert(key, CachedDigest { digest: digest.clone(), ..stamp });
+        Ok((digest, false))
+    }
+
+    /// What the cache last knew about the file at `path` under any
+    /// algorithm, whether or not the file is still there; for recognizing
+    /// a file that was moved away from `path`
+    pub fn lookup(&self, path: &Path) -> Option<KnownFile> {
+        let entries = self.entries.lock().unwrap();
+        [Algorithm::Xxh3, Algorithm::Blake3, Algorithm::Sha256]
+            .into_iter()
+            .find_map(|algorithm| {
+                let cached = entries.get(&key(algorithm, path)?)?;
+                Some(KnownFile {
+                    algorithm,
+                    size: cached.size,
+                    digest: cached.digest.clone(),
+                })
+            })
+    }
+
+    /// Remembers the digest of a file that was just read in full, e.g. to
+    /// verify it
+    pub fn record(&self, algorithm: Algorithm, path: &Path, metadata: &Metadata, digest: &str) {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        let entry = CachedDigest {
+            size: metadata.len(),
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            digest: digest.to_string(),
+        };
+        if let Some(key) = key(algorithm, path) {
+            self.insert(key, entry);
+        }
+    }
+
+    fn insert(&self, key: String, entry: CachedDigest) {
+        self.entries.lock().unwrap().insert(key, entry);
+        let due = {
+            let mut unsaved = self.unsaved.lock().unwrap();
+            unsaved.0 += 1;
+            unsaved.1.elapsed() >= SAVE_INTERVAL
+        };
+        if due {
+            // A failed intermediate save is retried at the next interval
+            // and by the final save
+            let _ = self.save();
+        }
+    }
+
+    /// Writes the cache out if anything was added since it was loaded or
+    /// last saved
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut unsaved = self.unsaved.lock().unwrap();
+        if unsaved.0 == 0 {
+            return Ok(());
+        }
+        let json = serde_json::to_string(&*self.entries.lock().unwrap())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, json)?;
+        *unsaved = (0, Instant::now());
+        Ok(())
+    }
+}
+
+/// `<algorithm>:<absolute path>`, so the same file reached through
+/// different relative paths shares one entry. Paths that are not valid
+/// UTF-8 are not cached, as they have no exact text form.
+fn key(algorithm: Algorithm, path: &Path) -> Option<String> {
+    let path = std::path::absolute(path).ok()?;
+    Some(format!("{}:{}", algorithm.name(), path.to_str()?))
+}
@@ -0,0 +1,291 @@
+use anyhow::Result;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Bytes read at a time unless [`Hashing::buffer_size`] says otherwise
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Bytes a quick hash reads from each end of a file
+const QUICK_HASH_SPAN: u64 = 1024 * 1024;
+
+/// Digest algorithms files can be hashed with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Algorithm {
+    /// Slowest, but the one other tools (`sha256sum`) can check
+    #[default]
+    Sha256,
+    /// Cryptographic like SHA-256 and several times faster
+    Blake3,
+    /// 128-bit XXH3: fastest, fine for finding duplicates, not for
+    /// detecting tampering
+    Xxh3,
+}
+
+impl Algorithm {
+    /// Lowercase name, as used in checksum file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+            Self::Xxh3 => "xxh3",
+        }
+    }
+}
+
+/// An in-progress digest of one of the [`Algorithm`]s
+enum State {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
+}
+
+impl State {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha256 => Self::Sha256(Sha256::new()),
+            Algorithm::Blake3 => Self::Blake3(Box::default()),
+            Algorithm::Xxh3 => Self::Xxh3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(bytes),
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Self::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// The digest as lowercase hex
+    fn finish(self) -> String {
+        match self {
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Self::Xxh3(hasher) => format!("{:032x}", hasher.digest128()),
+        }
+    }
+}
+
+/// A digest fed piece by piece, for hashing data while it is being copied
+/// rather than reading it a second time
+pub struct Hasher(State);
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self(State::new(algorithm))
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    /// The digest as lowercase hex
+    pub fn finish(self) -> String {
+        self.0.finish()
+    }
+}
+
+/// How contents are hashed: the algorithm and how much is read at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hashing {
+    pub algorithm: Algorithm,
+    pub buffer_size: usize,
+}
+
+impl Default for Hashing {
+    fn default() -> Self {
+        Self::new(Algorithm::default())
+    }
+}
+
+impl Hashing {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+
+    /// Hex digest of everything `reader` yields, read `buffer_size` bytes
+    /// at a time
+    pub fn digest(&self, mut reader: impl Read) -> Result<String> {
+        let mut state = State::new(self.algorithm);
+        let mut buffer = vec![0u8; self.buffer_size.max(1)];
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            state.update(&buffer[..n]);
+        }
+        Ok(state.finish())
+    }
+
+    /// Hex digest of the whole file
+    pub fn digest_file(&self, path: &Path) -> Result<String> {
+        self.digest(File::open(path)?)
+    }
+
+    /// Hex digest of the file size and its first and last 1 MiB, cheap
+    /// enough to rule out most non-duplicates before hashing whole files.
+    /// Files of up to 2 MiB are hashed whole.
+    pub fn quick_hash(&self, path: &Path) -> Result<String> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut state = State::new(self.algorithm);
+        state.update(&size.to_le_bytes());
+
+        let mut buffer = Vec::with_capacity(QUICK_HASH_SPAN.min(size) as usize);
+        file.by_ref().take(QUICK_HASH_SPAN).read_to_end(&mut buffer)?;
+        state.update(&buffer);
+        if size > QUICK_HASH_SPAN {
+            buffer.clear();
+            file.seek(SeekFrom::Start((size - QUICK_HASH_SPAN).max(QUICK_HASH_SPAN)))?;
+            file.take(QUICK_HASH_SPAN).read_to_end(&mut buffer)?;
+            state.update(&buffer);
+        }
+        Ok(state.finish())
+    }
+}
+
+/// Hex SHA-256 of the whole file
+pub fn sha256(path: &Path) -> Result<String> {
+    Hashing::new(Algorithm::Sha256).digest_file(path)
+}
+
+/// Hex SHA-256 quick hash, see [`Hashing::quick_hash`]
+pub fn quick_hash(path: &Path) -> Result<String> {
+    Hashing::new(Algorithm::Sha256).quick_hash(path)
+}
+
+/// Whether two files have the same contents. Sizes are compared first,
+/// then the bytes side by side, stopping at the first difference, so
+/// files that differ early cost almost nothing.
+pub fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let mut buffer_a = vec![0u8; DEFAULT_BUFFER_SIZE];
+    let mut buffer_b = vec![0u8; DEFAULT_BUFFER_SIZE];
+    loop {
+        let n = read_full(&mut a, &mut buffer_a)?;
+        if n != read_full(&mut b, &mut buffer_b)? || buffer_a[..n] != buffer_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fills `buffer` unless the reader ends first, returning the bytes read
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const BLAKE3_EMPTY: &str = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+
+    #[test]
+    fn digests_match_known_vectors() {
+        assert_eq!(Hashing::new(Algorithm::Sha256).digest(&b"abc"[..]).unwrap(), SHA256_ABC);
+        assert_eq!(Hashing::new(Algorithm::Blake3).digest(&b""[..]).unwrap(), BLAKE3_EMPTY);
+        assert_eq!(
+            Hashing::new(Algorithm::Xxh3).digest(&b""[..]).unwrap(),
+            format!("{:032x}", xxhash_rust::xxh3::xxh3_128(b""))
+        );
+    }
+
+    #[test]
+    fn the_buffer_size_does_not_change_the_digest() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        for algorithm in [Algorithm::Sha256, Algorithm::Blake3, Algorithm::Xxh3] {
+            let expected = Hashing::new(algorithm).digest(data.as_slice()).unwrap();
+            for buffer_size in [0, 1, 7, 4096, 1 << 20] {
+                let hashing = Hashing { algorithm, buffer_size };
+                assert_eq!(hashing.digest(data.as_slice()).unwrap(), expected, "{:?} {}", algorithm, buffer_size);
+            }
+        }
+    }
+
+    #[test]
+    fn a_fed_hasher_matches_a_streamed_digest() {
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(b"a");
+        hasher.update(b"bc");
+        assert_eq!(hasher.finish(), SHA256_ABC);
+    }
+
+    #[test]
+    fn quick_hashes_see_the_size_and_both_ends_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 3 * QUICK_HASH_SPAN as usize;
+        let write = |name: &str, change: Option<usize>| {
+            let mut data = vec![0u8; size];
+            if let Some(at) = change {
+                data[at] = 1;
+            }
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            quick_hash(&path).unwrap()
+        };
+        let plain = write("plain", None);
+        assert_eq!(write("middle", Some(size / 2)), plain);
+        assert_ne!(write("head", Some(0)), plain);
+        assert_ne!(write("tail", Some(size - 1)), plain);
+
+        let longer = dir.path().join("longer");
+        fs::write(&longer, vec![0u8; size + 1]).unwrap();
+        assert_ne!(quick_hash(&longer).unwrap(), plain);
+    }
+
+    #[test]
+    fn small_files_are_quick_hashed_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        let mut data = vec![0u8; QUICK_HASH_SPAN as usize + 10];
+        fs::write(&a, &data).unwrap();
+        data[QUICK_HASH_SPAN as usize + 5] = 1;
+        fs::write(&b, &data).unwrap();
+        assert_ne!(quick_hash(&a).unwrap(), quick_hash(&b).unwrap());
+    }
+
+    #[test]
+    fn files_compare_by_size_and_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let data = vec![7u8; DEFAULT_BUFFER_SIZE * 2 + 3];
+        let mut late = data.clone();
+        *late.last_mut().unwrap() = 8;
+        let a = write("a", &data);
+        assert!(same_contents(&a, &write("b", &data)).unwrap());
+        assert!(!same_contents(&a, &write("late", &late)).unwrap());
+        assert!(!same_contents(&a, &write("short", &data[1..])).unwrap());
+        assert!(same_contents(&write("empty", b""), &write("also_empty", b"")).unwrap());
+        assert!(same_contents(&a, &dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::config::Config;
+use crate::modules::base::Summary;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Runs the `[hooks]` of the config when operations finish from now on.
+/// They are off until this is called, so programs using the library never
+/// run them.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// What a hook reads on stdin
+#[derive(Serialize)]
+struct Report<'a> {
+    module: &'a str,
+    success: bool,
+    /// The run's tally, unless it stopped with an error
+    summary: Option<&'a Summary>,
+    error: Option<String>,
+}
+
+/// Runs the hook the config has for `module` finishing with `result`. A run
+/// succeeded when it finished with no file failing. A hook that cannot be
+/// started, fails, or times out is reported, and changes nothing else.
+pub fn finished(module: &str, result: &Result<Summary>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let config = match Config::load() {
+        Ok(config) => config.hooks,
+        Err(e) => {
+            println!("Hooks were not run: {:#}", e);
+            return;
+        }
+    };
+    let success = matches!(result, Ok(summary) if summary.failed == 0);
+    let Some(command) = config.command(module, success) else {
+        return;
+    };
+    if let Err(e) = run(command, module, success, result, Duration::from_secs(config.timeout)) {
+        let event = if success { "on_success" } else { "on_failure" };
+        println!("The {} hook for {} failed: {:#}", event, module, e);
+    }
+}
+
+fn run(command: &str, module: &str, success: bool, result: &Result<Summary>, timeout: Duration) -> Result<()> {
+    let summary = result.as_ref().ok();
+    let report = serde_json::to_string(&Report {
+        module,
+        success,
+        summary,
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
+    })?;
+    let mut child = shell(command)
+        .env("FO_MODULE", module)
+        .env("FO_STATUS", if success { "success" } else { "failure" })
+        .env("FO_FILES_PROCESSED", summary.map_or(0, |summary| summary.files).to_string())
+        .env("FO_BYTES_SAVED", summary.map_or(0, |summary| summary.bytes).to_string())
+        // A run that stopped with an error counts as one
+        .env("FO_ERRORS", summary.map_or(1, |summary| summary.failed).to_string())
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start `{}`", command))?;
+
+    // Written on its own thread, so a hook that never reads its input
+    // cannot hold up the wait below
+    let mut stdin = child.stdin.take().unwrap();
+    thread::spawn(move || {
+        let _ = stdin.write_all(report.as_bytes());
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            anyhow::bail!("`{}` was still running after {} s and was stopped", command, timeout.as_secs());
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+    if !status.success() {
+        anyhow::bail!("`{}` exited with {}", command, status);
+    }
+    Ok(())
+}
+
+/// `command` run by the platform's shell, so hooks may use pipes and
+/// variables
+fn shell(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut shell = Command::new(shell);
+    shell.args([flag, command]);
+    shell
+}
@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    atomic::{write_atomic, AtomicFile},
+    csv_field,
+    links::{create_symlink, replace_symlink},
+    permissions::{set_attributes, Attributes},
+    transfer::{transfer_file, TransferMode, Verify},
+};
+
+/// Record of the files an operation moved or copied, written after every
+/// run so it can be undone
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Journal {
+    /// Module that wrote the journal, e.g. "categorize"
+    pub operation: String,
+    pub created: String,
+    pub mode: TransferMode,
+    pub entries: Vec<JournalEntry>,
+    /// Directories the run removed after emptying them, recreated on undo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_dirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Size and mtime of the destination right after the transfer, used to
+    /// detect files that changed since
+    pub size: u64,
+    pub mtime: i64,
+    pub mtime_nanos: u32,
+    /// Rule or reason behind the transfer, for reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// When the transfer happened (RFC 3339)
+    #[serde(default)]
+    pub timestamp: String,
+    /// The source was deleted rather than transferred; such entries have
+    /// no destination and cannot be undone
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// For a symlink the run removed or pointed elsewhere, where it used
+    /// to point. Its destination is the new target, if any, and undo puts
+    /// the old one back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_target: Option<PathBuf>,
+    /// For a file whose mode or owner the run changed in place, what they
+    /// were and what they became. Source and destination are both the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<[Attributes; 2]>,
+}
+
+/// Result of replaying a journal in reverse
+#[derive(Default)]
+pub struct UndoSummary {
+    pub restored: usize,
+    pub failed: Vec<(PathBuf, String)>,
+    /// Deleted files, which the journal lists but cannot bring back
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Journal {
+    pub fn new(operation: &str, mode: TransferMode) -> Self {
+        Self {
+            operation: operation.to_string(),
+            created: Local::now().to_rfc3339(),
+            mode,
+            entries: Vec::new(),
+            removed_dirs: Vec::new(),
+        }
+    }
+
+    /// Records a finished transfer, reading the destination's current state
+    pub fn record(&mut self, source: &Path, destination: &Path, rule: Option<&str>) -> Result<()> {
+        let metadata = fs::metadata(destination)?;
+        self.record_transfer(source, destination, &metadata, rule);
+        Ok(())
+    }
+
+    /// Records a finished transfer of `source`, which had `metadata` before
+    /// it. Transfers keep the size and mtime, so reading them up front
+    /// leaves nothing to fail once the file has moved.
+    pub fn record_transfer(
+        &mut self,
+        source: &Path,
+        destination: &Path,
+        metadata: &fs::Metadata,
+        rule: Option<&str>,
+    ) {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        self.entries.push(JournalEntry {
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            size: metadata.len(),
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            rule: rule.map(str::to_string),
+            timestamp: Local::now().to_rfc3339(),
+            deleted: false,
+            link_target: None,
+            attributes: None,
+        });
+    }
+
+    /// Deletes `path` and records it, keeping its size and mtime for the
+    /// report
+    pub fn delete(&mut self, path: &Path, rule: Option<&str>) -> Result<()> {
+        let metadata = fs::metadata(path)?;
+        fs::remove_file(path)?;
+        self.record_deletion(path, &metadata, rule);
+        Ok(())
+    }
+
+    /// Records that `path`, which had `metadata`, was deleted some other
+    /// way, such as by moving it to the trash
+    pub fn record_deletion(&mut self, path: &Path, metadata: &fs::Metadata, rule: Option<&str>) {
+        let mtime = FileTime::from_last_modification_time(metadata);
+        self.entries.push(JournalEntry {
+            source: path.to_path_buf(),
+            destination: PathBuf::new(),
+            size: metadata.len(),
+            mtime: mtime.unix_seconds(),
+            mtime_nanos: mtime.nanoseconds(),
+            rule: rule.map(str::to_string),
+            timestamp: Local::now().to_rfc3339(),
+            deleted: true,
+            link_target: None,
+            attributes: None,
+        });
+    }
+
+    /// Removes the symlink at `link`, or with `new_target` points it there
+    /// instead, recording where it pointed so undo can restore it
+    pub fn relink(&mut self, link: &Path, new_target: Option<&Path>, rule: Option<&str>) -> Result<()> {
+        let old_target = fs::read_link(link)?;
+        match new_target {
+            Some(target) => replace_symlink(target, link)?,
+            None => fs::remove_file(link)?,
+        }
+        self.entries.push(JournalEntry {
+            source: link.to_path_buf(),
+            destination: new_target.map(Path::to_path_buf).unwrap_or_default(),
+            size: 0,
+            mtime: 0,
+            mtime_nanos: 0,
+            rule: rule.map(str::to_string),
+            timestamp: Local::now().to_rfc3339(),
+            deleted: new_target.is_none(),
+            link_target: Some(old_target),
+            attributes: None,
+        });
+        Ok(())
+    }
+
+    /// Gives `path` the mode and owner of `after`, recording `before` so
+    /// undo can put them back
+    pub fn set_attributes(&mut self, path: &Path, before: Attributes, after: Attributes, rule: Option<&str>) -> Result<()> {
+        set_attributes(path, &before, &after)?;
+        self.entries.push(JournalEntry {
+            source: path.to_path_buf(),
+            destination: path.to_path_buf(),
+            size: 0,
+            mtime: 0,
+            mtime_nanos: 0,
+            rule: rule.map(str::to_string),
+            timestamp: Local::now().to_rfc3339(),
+            deleted: false,
+            link_target: None,
+            attributes: Some([before, after]),
+        });
+        Ok(())
+    }
+
+    /// `<data dir>/file-organizer-rust/journal`
+    pub fn default_dir() -> Result<PathBuf> {
+        let dir = dirs::data_dir().ok_or_else(|| anyhow::anyhow!("No data directory on this platform"))?;
+        Ok(dir.join("file-organizer-rust").join("journal"))
+    }
+
+    /// File name for this journal: `<operation>-<timestamp>.json`
+    pub fn file_name(&self) -> String {
+        format!("{}-{}.json", self.operation, Local::now().format("%Y%m%d-%H%M%S"))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write journal {}", path.display()))
+    }
+
+    /// Writes one CSV row per entry (source, destination, rule, size,
+    /// timestamp, action), the move report shared by the modules
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(AtomicFile::create(path)?);
+        writeln!(writer, "source,destination,rule,size,timestamp,action")?;
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                csv_field(&entry.source.display().to_string()),
+                csv_field(&entry.destination.display().to_string()),
+                csv_field(entry.rule.as_deref().unwrap_or_default()),
+                entry.size,
+                entry.timestamp,
+                match (entry.deleted, &entry.link_target, &entry.attributes) {
+                    (true, _, _) => "deleted",
+                    (false, Some(_), _) => "relinked",
+                    (false, None, Some(_)) => "chmod",
+                    (false, None, None) => self.mode.verb(),
+                },
+            )?;
+        }
+        writer.into_inner()?.commit()
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read journal {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Failed to parse journal {}", path.display()))
+    }
+
+    /// Reverses every transfer, newest first. Moved files go back to their
+    /// source; copies are deleted. Files whose size or mtime changed since
+    /// the run, or whose original path is taken again, are left alone and
+    /// reported. Deleted files are skipped and listed, but removed or
+    /// relinked symlinks get their old target back. Removed directories
+    /// are recreated, even those no restored file needs. Changed modes and
+    /// owners are put back unless they changed again since.
+    pub fn undo(&self) -> UndoSummary {
+        let mut summary = UndoSummary::default();
+        for dir in &self.removed_dirs {
+            if let Err(e) = fs::create_dir_all(dir) {
+                summary.failed.push((dir.clone(), e.to_string()));
+            }
+        }
+        for entry in self.entries.iter().rev() {
+            if let Some([before, after]) = &entry.attributes {
+                match undo_attributes(&entry.source, before, after) {
+                    Ok(()) => summary.restored += 1,
+                    Err(e) => summary.failed.push((entry.source.clone(), e.to_string())),
+                }
+                continue;
+            }
+            if let Some(old_target) = &entry.link_target {
+                match undo_link(entry, old_target) {
+                    Ok(()) => summary.restored += 1,
+                    Err(e) => summary.failed.push((entry.source.clone(), e.to_string())),
+                }
+                continue;
+            }
+            if entry.deleted {
+                summary.deleted.push(entry.source.clone());
+                continue;
+            }
+            match self.undo_entry(entry) {
+                Ok(()) => summary.restored += 1,
+                Err(e) => summary.failed.push((entry.destination.clone(), e.to_string())),
+            }
+        }
+        summary
+    }
+
+    fn undo_entry(&self, entry: &JournalEntry) -> Result<()> {
+        let metadata = fs::metadata(&entry.destination).context("no longer exists")?;
+        let mtime = FileTime::from_last_modification_time(&metadata);
+        if metadata.len() != entry.size
+            || mtime.unix_seconds() != entry.mtime
+            || mtime.nanoseconds() != entry.mtime_nanos
+        {
+            anyhow::bail!("changed since the run (size or modification time differs)");
+        }
+
+        match self.mode {
+            TransferMode::Move => {
+                if entry.source.exists() {
+                    anyhow::bail!("original path {} is occupied", entry.source.display());
+                }
+                if let Some(parent) = entry.source.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                transfer_file(&entry.destination, &entry.source, TransferMode::Move, Verify::Size)?;
+            }
+            TransferMode::Copy => fs::remove_file(&entry.destination)?,
+        }
+        Ok(())
+    }
+}
+
+/// Puts back the mode and owner `path` had before the run, if it still has
+/// the ones the run gave it
+fn undo_attributes(path: &Path, before: &Attributes, after: &Attributes) -> Result<()> {
+    let current = Attributes::read(path).context("no longer exists")?;
+    if current != *after {
+        anyhow::bail!("changed since the run (now {}, the run left {})", current, after);
+    }
+    set_attributes(path, &current, before)
+}
+
+/// Recreates a removed symlink, or points a relinked one back at
+/// `old_target` if nothing changed it since
+fn undo_link(entry: &JournalEntry, old_target: &Path) -> Result<()> {
+    let current = fs::symlink_metadata(&entry.source).ok().map(|_| fs::read_link(&entry.source));
+    if entry.deleted {
+        if current.is_some() {
+            anyhow::bail!("original path {} is occupied", entry.source.display());
+        }
+        if let Some(parent) = entry.source.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        create_symlink(old_target, &entry.source)?;
+    } else {
+        match current {
+            Some(Ok(target)) if target == entry.destination => replace_symlink(old_target, &entry.source)?,
+            _ => anyhow::bail!("changed since the run (no longer a link to {})", entry.destination.display()),
+        }
+    }
+    Ok(())
+}
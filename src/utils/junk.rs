@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::config::Config;
+
+/// Files operating systems and file managers leave behind that carry no
+/// user data
+pub const DEFAULT_JUNK: &[&str] = &[
+    ".DS_Store",
+    ".localized",
+    "Icon\r",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+];
+
+/// Directories of indexes, thumbnails, and archive leftovers that operating
+/// systems, NAS boxes, and archivers create next to user data
+pub const DEFAULT_JUNK_DIRS: &[&str] = &[
+    ".Spotlight-V100",
+    ".fseventsd",
+    ".Trashes",
+    ".TemporaryItems",
+    "@eaDir",
+    "__MACOSX",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+];
+
+/// Known junk: the built-in lists plus `[junk] files` and `[junk] dirs`
+/// from the config. Files also match macOS `._*` resource forks.
+#[derive(Debug, Clone)]
+pub struct JunkList {
+    names: Vec<String>,
+    dirs: Vec<String>,
+}
+
+impl Default for JunkList {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_JUNK.iter().map(|name| name.to_lowercase()).collect(),
+            dirs: DEFAULT_JUNK_DIRS.iter().map(|name| name.to_lowercase()).collect(),
+        }
+    }
+}
+
+impl JunkList {
+    pub fn load() -> Result<Self> {
+        let mut list = Self::default();
+        let config = Config::load()?;
+        list.names
+            .extend(config.junk.files.iter().map(|name| name.to_lowercase()));
+        list.dirs.extend(config.junk.dirs.iter().map(|name| name.to_lowercase()));
+        Ok(list)
+    }
+
+    /// Whether the file at `path` is known junk, judged by its name alone
+    pub fn is_junk_file(&self, path: &Path) -> bool {
+        let Some(name) = lowercase_name(path) else {
+            return false;
+        };
+        name.starts_with("._") || self.names.contains(&name)
+    }
+
+    /// Whether the directory at `path` is known junk, along with everything
+    /// in it, judged by its name alone
+    pub fn is_junk_dir(&self, path: &Path) -> bool {
+        lowercase_name(path).is_some_and(|name| self.dirs.contains(&name))
+    }
+}
+
+fn lowercase_name(path: &Path) -> Option<String> {
+    path.file_name().map(|n| n.to_string_lossy().to_lowercase())
+}
+
+/// Whether `path` or any directory between `root` and it is a dotfile
+pub fn is_hidden(path: &Path, root: &Path) -> bool {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::{
+    fs,
+    path::{self, Component, Path, PathBuf},
+};
+
+pub fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Absolute, normalized form of `path`
+pub fn absolute(path: &Path) -> Result<PathBuf> {
+    Ok(normalize(&path::absolute(path)?))
+}
+
+/// Absolute, normalized form of the directory holding `path`
+pub fn absolute_parent(path: &Path) -> Result<PathBuf> {
+    absolute(path.parent().unwrap_or(Path::new(".")))
+}
+
+/// Where the symlink at `link` points, as an absolute, normalized path.
+/// A relative target is resolved from the link's directory; the target
+/// itself is not touched, so it need not exist.
+pub fn resolve(link: &Path) -> Result<PathBuf> {
+    let target = fs::read_link(link)?;
+    Ok(if target.is_relative() {
+        normalize(&absolute_parent(link)?.join(&target))
+    } else {
+        normalize(&target)
+    })
+}
+
+/// Creates a symlink at `at` pointing to `link`
+#[cfg(unix)]
+pub fn create_symlink(link: &Path, at: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(link, at)
+}
+
+/// Creates a symlink at `at` pointing to `link`
+#[cfg(windows)]
+pub fn create_symlink(link: &Path, at: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(link, at)
+}
+
+/// Resolves `.` and `..` components without touching the filesystem
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// `path` relative to the directory `base`, both absolute and normalized
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let path_components: Vec<_> = path.components().collect();
+    let base_components: Vec<_> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+/// Points the existing symlink `at` to `link` instead. The new link is
+/// made under a temporary name and renamed over the old one, so `at` is
+/// never missing.
+pub fn replace_symlink(link: &Path, at: &Path) -> Result<()> {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(at.file_name().unwrap_or_default());
+    name.push(".relinking");
+    let temporary = at.with_file_name(name);
+    create_symlink(link, &temporary)?;
+    if let Err(e) = fs::rename(&temporary, at) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use filetime::FileTime;
+use std::{fs, path::Path};
+
+/// Copies the modification time and, on Unix, the permission bits of
+/// `source` onto `dest`
+pub fn copy_file_metadata(source: &Path, dest: &Path) -> Result<()> {
+    let metadata = fs::metadata(source)?;
+    filetime::set_file_mtime(dest, FileTime::from_last_modification_time(&metadata))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode();
+        fs::set_permissions(dest, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mtime_and_permissions_are_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, dest) = (dir.path().join("source"), dir.path().join("dest"));
+        fs::write(&source, b"source").unwrap();
+        fs::write(&dest, b"dest").unwrap();
+        filetime::set_file_mtime(&source, FileTime::from_unix_time(1_600_000_000, 0)).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&source, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        copy_file_metadata(&source, &dest).unwrap();
+
+        let copied = fs::metadata(&dest).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&copied).unix_seconds(), 1_600_000_000);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(copied.permissions().mode() & 0o777, 0o640);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use humansize::{format_size, DECIMAL};
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::progress::{Outcome, ProgressSink};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Collects and prints phase timings from now on. Until this is called
+/// every [`Metrics`] ignores what it is given and finishes with no phases,
+/// so runs that do not show them pay for no clock reads or locking.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Time, items, and bytes of one phase of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub seconds: f64,
+    pub items: u64,
+    pub bytes: u64,
+    /// Time added up across parallel workers rather than measured on the
+    /// clock, e.g. decoding spread over every core
+    pub cumulative: bool,
+}
+
+/// Collects phase timings for the breakdown printed after a run. Phases
+/// run one after another: starting one ends the previous. Sections timed
+/// with [`time`](Self::time) add up instead, so they can be measured from
+/// several workers at once. As a [`ProgressSink`], it turns scans and phase
+/// changes into phases and counts finished items against the current one.
+/// It records nothing unless [`enable`] was called.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    phases: Vec<PhaseTiming>,
+    /// Index into `phases` and when it started
+    current: Option<(usize, Instant)>,
+}
+
+impl State {
+    fn close(&mut self) {
+        if let Some((index, started)) = self.current.take() {
+            self.phases[index].seconds += started.elapsed().as_secs_f64();
+        }
+    }
+
+    fn entry(&mut self, name: &str, cumulative: bool) -> usize {
+        match self.phases.iter().position(|p| p.name == name && p.cumulative == cumulative) {
+            Some(index) => index,
+            None => {
+                self.phases.push(PhaseTiming {
+                    name: name.to_string(),
+                    seconds: 0.0,
+                    items: 0,
+                    bytes: 0,
+                    cumulative,
+                });
+                self.phases.len() - 1
+            }
+        }
+    }
+}
+
+impl Metrics {
+    /// Ends the current phase and starts `name`; a phase seen before keeps
+    /// adding to its totals
+    pub fn phase(&self, name: &str) {
+        if !enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.current.is_some_and(|(index, _)| state.phases[index].name == name) {
+            return;
+        }
+        state.close();
+        let index = state.entry(name, false);
+        state.current = Some((index, Instant::now()));
+    }
+
+    /// Counts `items` totalling `bytes` against the current phase
+    pub fn add(&self, items: u64, bytes: u64) {
+        if !enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some((index, _)) = state.current {
+            state.phases[index].items += items;
+            state.phases[index].bytes += bytes;
+        }
+    }
+
+    /// Runs `f`, adding its time to the section `name`
+    pub fn time<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        if !enabled() {
+            return f();
+        }
+        let started = Instant::now();
+        let result = f();
+        let elapsed = started.elapsed().as_secs_f64();
+        let mut state = self.state.lock().unwrap();
+        let index = state.entry(name, true);
+        state.phases[index].seconds += elapsed;
+        state.phases[index].items += 1;
+        result
+    }
+
+    /// Ends the current phase and returns every timing so far
+    pub fn finish(&self) -> Vec<PhaseTiming> {
+        let mut state = self.state.lock().unwrap();
+        state.close();
+        state.phases.clone()
+    }
+}
+
+impl ProgressSink for Metrics {
+    fn scan_started(&self, _root: &Path) {
+        self.phase("scanning");
+    }
+
+    fn phase_changed(&self, phase: &str, _total: u64) {
+        self.phase(&phase.to_lowercase());
+    }
+
+    fn item_finished(&self, _path: &Path, bytes: u64, _outcome: Outcome) {
+        self.add(1, bytes);
+    }
+}
+
+/// One line such as "scanning 12 s · hashing 9 m 41 s (412 MB/s) ·
+/// deleting 3 s", with worker time in parentheses at the end
+pub fn format_phases(phases: &[PhaseTiming]) -> String {
+    let describe = |phase: &PhaseTiming| {
+        let mut text = format!("{} {}", phase.name, format_duration(Duration::from_secs_f64(phase.seconds)));
+        if phase.bytes > 0 && phase.seconds >= 0.001 {
+            let rate = (phase.bytes as f64 / phase.seconds) as u64;
+            text.push_str(&format!(" ({}/s)", format_size(rate, DECIMAL)));
+        }
+        text
+    };
+    let (worker, wall): (Vec<&PhaseTiming>, Vec<&PhaseTiming>) = phases.iter().partition(|p| p.cumulative);
+    let mut line = wall.into_iter().map(describe).collect::<Vec<_>>().join(" · ");
+    if !worker.is_empty() {
+        let worker = worker.into_iter().map(describe).collect::<Vec<_>>().join(" · ");
+        line.push_str(&format!(" (worker time: {})", worker));
+    }
+    line
+}
+
+/// "850 ms", "12 s", "9 m 41 s", or "1 h 5 m"
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0 => format!("{} ms", duration.as_millis()),
+        1..=59 => format!("{} s", secs),
+        60..=3599 => format!("{} m {} s", secs / 60, secs % 60),
+        _ => format!("{} h {} m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_metrics_time_each_phase_once() {
+        enable();
+        let metrics = Metrics::default();
+        metrics.phase("scanning");
+        metrics.add(2, 10);
+        metrics.phase("hashing");
+        metrics.add(1, 5);
+        metrics.phase("scanning");
+        metrics.add(1, 1);
+        let phases = metrics.finish();
+        let totals: Vec<_> = phases.iter().map(|p| (p.name.as_str(), p.items, p.bytes)).collect();
+        assert_eq!(totals, [("scanning", 3, 11), ("hashing", 1, 5)]);
+    }
+
+    #[test]
+    fn durations_use_the_largest_sensible_units() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850 ms");
+        assert_eq!(format_duration(Duration::from_secs(12)), "12 s");
+        assert_eq!(format_duration(Duration::from_secs(581)), "9 m 41 s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1 h 5 m");
+    }
+}
@@ -1,31 +1,228 @@
+pub mod atomic;
+pub mod delete;
+pub mod exif;
+pub mod expand;
+pub mod filter;
+pub mod geocode;
+pub mod hash_cache;
+pub mod hashing;
+pub mod hooks;
+pub mod journal;
+pub mod junk;
+pub mod links;
+pub mod metadata;
+pub mod metrics;
+pub mod names;
+pub mod permissions;
+pub mod progress;
+pub mod prompt;
+pub mod sanitize;
+pub mod secure_delete;
+pub mod session;
+pub mod space;
+pub mod stamp;
+pub mod transfer;
+pub mod video;
+pub mod walk;
+
 use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, Input};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input};
 use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use std::{ops::Deref, path::PathBuf, time::Duration};
+
+use expand::expand_path;
+use session::{Answer, Turn};
+
+/// A spinner that animates on its own thread and shows the elapsed time.
+/// Dropped before it is finished, e.g. when an error cuts the work short,
+/// it clears its line rather than leaving a frozen one behind.
+pub struct Spinner(ProgressBar);
 
-pub fn create_spinner(message: &str) -> ProgressBar {
+impl Deref for Spinner {
+    type Target = ProgressBar;
+
+    fn deref(&self) -> &ProgressBar {
+        &self.0
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if !self.0.is_finished() {
+            self.0.finish_and_clear();
+        }
+    }
+}
+
+pub fn create_spinner(message: &str) -> Spinner {
     let pb = ProgressBar::new_spinner();
     pb.set_style(
         ProgressStyle::default_spinner()
             .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ")
-            .template("{spinner:.green} {msg}")
+            .template("{spinner:.green} [{elapsed}] {msg}")
             .unwrap(),
     );
     pb.set_message(message.to_string());
-    pb
+    pb.enable_steady_tick(Duration::from_millis(100));
+    Spinner(pb)
 }
 
+/// A bar counting `total` bytes, with throughput, for [`ByteProgress`](progress::ByteProgress)
+pub fn create_byte_bar(total: u64) -> ProgressBar {
+    let bar = ProgressBar::new(total);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({binary_bytes_per_sec}) {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
+
+/// Prompts for an existing directory. `~` and environment variables are
+/// expanded, and when the resolved absolute path differs from what was
+/// typed it is shown for confirmation, asking again if declined.
 pub fn get_directory_from_user(prompt: &str) -> Result<PathBuf> {
-    let path: String = Input::with_theme(&ColorfulTheme::default())
-        .with_prompt(prompt)
-        .interact_text()?;
-    
-    let path = PathBuf::from(path);
+    match session::turn(prompt)? {
+        Turn::Replayed(Answer::Directory(dir)) => return replayed_directory(prompt, dir),
+        Turn::Replayed(answer) => return Err(session::mismatch(prompt, &answer, "this prompt takes a directory")),
+        Turn::Ask => {}
+    }
+    loop {
+        let typed: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .interact_text()?;
+
+        let dir = resolve_directory(typed.trim())?;
+        if confirm_resolved(typed.trim(), std::slice::from_ref(&dir))? {
+            session::record(prompt, Answer::Directory(dir.clone()));
+            return Ok(dir);
+        }
+    }
+}
+
+/// Prompts for one or more comma-separated directories, checking that each
+/// exists. Each is expanded and resolved as by [`get_directory_from_user`].
+pub fn get_directories_from_user(prompt: &str) -> Result<Vec<PathBuf>> {
+    match session::turn(prompt)? {
+        Turn::Replayed(Answer::Directories(dirs)) => {
+            return dirs.into_iter().map(|dir| replayed_directory(prompt, dir)).collect();
+        }
+        Turn::Replayed(answer) => return Err(session::mismatch(prompt, &answer, "this prompt takes directories")),
+        Turn::Ask => {}
+    }
+    loop {
+        let typed: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .interact_text()?;
+
+        let dirs = typed
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(resolve_directory)
+            .collect::<Result<Vec<PathBuf>>>()?;
+        if dirs.is_empty() {
+            anyhow::bail!("No directory given");
+        }
+        if confirm_resolved(typed.trim(), &dirs)? {
+            session::record(prompt, Answer::Directories(dirs.clone()));
+            return Ok(dirs);
+        }
+    }
+}
+
+/// A directory answer from a replayed recording, which must still be one
+fn replayed_directory(prompt: &str, dir: PathBuf) -> Result<PathBuf> {
+    if !dir.is_dir() {
+        return Err(session::mismatch(prompt, &Answer::Directory(dir), "it is no longer a directory"));
+    }
+    Ok(dir)
+}
+
+/// Expands `typed` and resolves it to an absolute path, which must be an
+/// existing directory
+fn resolve_directory(typed: &str) -> Result<PathBuf> {
+    let path = expand_path(typed)?;
     if !path.exists() {
-        anyhow::bail!("Directory does not exist");
+        anyhow::bail!("Directory does not exist: {}", path.display());
     }
     if !path.is_dir() {
-        anyhow::bail!("Path is not a directory");
+        anyhow::bail!("Path is not a directory: {}", path.display());
+    }
+    Ok(path.canonicalize()?)
+}
+
+/// Asks whether the resolved directories are the ones meant, unless they
+/// read exactly as typed
+fn confirm_resolved(typed: &str, dirs: &[PathBuf]) -> Result<bool> {
+    let resolved = dirs
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if resolved == typed {
+        return Ok(true);
+    }
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Use {}?", resolved))
+        .default(true)
+        .interact()?)
+}
+
+/// Parses a human-readable size such as `100MB` or `1.5GB` into bytes
+pub fn parse_size(size_str: &str) -> Result<u64> {
+    let size_str = size_str.trim().to_lowercase();
+    let mut num = String::new();
+    let mut unit = String::new();
+
+    for c in size_str.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            num.push(c);
+        } else {
+            unit.push(c);
+        }
+    }
+
+    let number: f64 = num.parse()?;
+    let multiplier = match unit.as_str() {
+        "b" => 1,
+        "kb" => 1024,
+        "mb" => 1024 * 1024,
+        "gb" => 1024 * 1024 * 1024,
+        _ => anyhow::bail!("Invalid size unit. Use B, KB, MB, or GB"),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses an age such as `12h`, `7d`, `2w`, `6m` (30 days), or `1y`
+/// (365 days)
+pub fn parse_age(age: &str) -> Result<Duration> {
+    let age = age.trim();
+    let split = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age '{}', use e.g. 12h, 7d, 2w, 6m, or 1y", age))?;
+    let hours = match unit.trim().to_lowercase().as_str() {
+        "h" => 1,
+        "d" => 24,
+        "w" => 7 * 24,
+        "m" => 30 * 24,
+        "y" => 365 * 24,
+        _ => anyhow::bail!("Invalid age unit in '{}', use h, d, w, m, or y", age),
+    };
+    Ok(Duration::from_secs(number * hours * 3600))
+}
+
+/// Quotes a value for a CSV cell when it contains a separator, quote, or
+/// newline
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
     }
-    Ok(path)
-} 
\ No newline at end of file
+}
@@ -0,0 +1,262 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Component, Path},
+};
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization};
+
+use crate::config::Config;
+
+/// The Unicode form names the tool creates are written in. macOS writes
+/// names decomposed (NFD) and most Linux and Windows software composed
+/// (NFC), so the same `café.txt` can arrive as two different byte strings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    #[default]
+    Nfc,
+    Nfd,
+    /// Keep names as they are
+    None,
+}
+
+impl Normalization {
+    /// The `[names] normalization` setting from the config
+    pub fn load() -> Result<Self> {
+        Ok(Config::load()?.names.normalization)
+    }
+
+    /// `name` in this form. A name that is not valid Unicode is kept byte
+    /// for byte.
+    pub fn apply(self, name: &OsStr) -> Cow<'_, OsStr> {
+        let Some(text) = name.to_str() else {
+            return Cow::Borrowed(name);
+        };
+        match self {
+            Self::Nfc if !is_nfc(text) => Cow::Owned(text.nfc().collect::<String>().into()),
+            Self::Nfd if !is_nfd(text) => Cow::Owned(text.nfd().collect::<String>().into()),
+            _ => Cow::Borrowed(name),
+        }
+    }
+}
+
+/// `name` as text without losing what tells it apart from other names:
+/// valid UTF-8 is kept, every other byte becomes `%XX`. Unlike
+/// `to_string_lossy`, which turns every such byte into the same
+/// replacement character, two different names stay different.
+pub fn escaped_name(name: &OsStr) -> Cow<'_, str> {
+    if let Some(text) = name.to_str() {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::new();
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// A relative path as `/`-separated text with every component passed
+/// through [`escaped_name`], as archive entry names are stored
+pub fn escaped_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(escaped_name(name)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `name-<n>.ext`, or `name-<n>` without an extension, keeping the bytes
+/// of the stem and extension as they are
+pub fn numbered_name(path: &Path, n: u32) -> OsString {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", n));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    name
+}
+
+/// Whether the filesystem holding `dir` treats `Photo.JPG` and `photo.jpg`
+/// as the same name, as Windows and macOS do by default. Looks for an entry
+/// in `dir`, or the nearest existing ancestor, whose name can be looked up
+/// under another case; nothing is written. Falls back to the platform
+/// default when no entry tells.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    for dir in dir.ancestors() {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        let names: HashSet<OsString> = entries.flatten().map(|entry| entry.file_name()).collect();
+        for name in names.iter().filter_map(|name| name.to_str()) {
+            let flipped = flip_case(name);
+            if flipped == name {
+                continue;
+            }
+            // Two names differing only in case can only both be listed
+            // where case matters
+            if names.contains(OsStr::new(&flipped)) {
+                return false;
+            }
+            return fs::symlink_metadata(dir.join(flipped)).is_ok();
+        }
+    }
+    cfg!(any(windows, target_os = "macos"))
+}
+
+fn flip_case(name: &str) -> String {
+    let mut flipped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_uppercase() {
+            flipped.extend(c.to_lowercase());
+        } else {
+            flipped.extend(c.to_uppercase());
+        }
+    }
+    flipped
+}
+
+/// Whether two paths name the same file, such as `photo.jpg` and
+/// `Photo.jpg` on a case-insensitive filesystem
+#[cfg(unix)]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Names in one directory, compared the way its filesystem compares them,
+/// so a planned `photo.jpg` collides with an existing `Photo.JPG` where
+/// the two would be the same file. Composed and decomposed spellings of
+/// the same name always collide: macOS treats them as one name, and
+/// elsewhere they look the same to the user anyway.
+#[derive(Debug, Clone, Default)]
+pub struct NameSet {
+    fold_case: bool,
+    /// Each name as first added, by its comparison key
+    names: HashMap<String, OsString>,
+}
+
+impl NameSet {
+    pub fn new(fold_case: bool) -> Self {
+        Self {
+            fold_case,
+            names: HashMap::new(),
+        }
+    }
+
+    /// The names of the entries already directly in `dir`, compared the
+    /// way the filesystem holding `dir` compares them
+    pub fn existing(dir: &Path) -> Self {
+        let mut set = Self::new(is_case_insensitive(dir));
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            set.insert(&entry.file_name());
+        }
+        set
+    }
+
+    /// What `name` is compared by: its NFC form, lowercased when case is
+    /// ignored
+    pub fn key(&self, name: &OsStr) -> String {
+        let key = escaped_name(&Normalization::Nfc.apply(name)).into_owned();
+        if self.fold_case {
+            key.to_lowercase()
+        } else {
+            key
+        }
+    }
+
+    pub fn contains(&self, name: &OsStr) -> bool {
+        self.names.contains_key(&self.key(name))
+    }
+
+    /// Adds `name`, returning whether it was free
+    pub fn insert(&mut self, name: &OsStr) -> bool {
+        let key = self.key(name);
+        if self.names.contains_key(&key) {
+            return false;
+        }
+        self.names.insert(key, name.to_os_string());
+        true
+    }
+
+    /// Every name, as it was added
+    pub fn names(&self) -> impl Iterator<Item = &OsStr> {
+        self.names.values().map(OsString::as_os_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_differing_in_case_collide_only_where_case_is_folded() {
+        let mut folded = NameSet::new(true);
+        assert!(folded.insert(OsStr::new("Photo.JPG")));
+        assert!(!folded.insert(OsStr::new("photo.jpg")));
+        assert_eq!(folded.names().collect::<Vec<_>>(), [OsStr::new("Photo.JPG")]);
+
+        let mut exact = NameSet::new(false);
+        assert!(exact.insert(OsStr::new("Photo.JPG")));
+        assert!(exact.insert(OsStr::new("photo.jpg")));
+    }
+
+    #[test]
+    fn composed_and_decomposed_names_collide() {
+        let mut set = NameSet::new(false);
+        assert!(set.insert(OsStr::new("caf\u{e9}.txt")));
+        assert!(set.contains(OsStr::new("cafe\u{301}.txt")));
+    }
+
+    #[test]
+    fn case_sensitivity_is_read_from_the_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Photo.JPG"), "").unwrap();
+        let insensitive = fs::metadata(dir.path().join("photo.jpg")).is_ok();
+        assert_eq!(is_case_insensitive(dir.path()), insensitive);
+        assert_eq!(NameSet::existing(dir.path()).contains(OsStr::new("photo.jpg")), insensitive);
+    }
+
+    #[test]
+    fn names_are_written_in_the_configured_form() {
+        let (composed, decomposed) = (OsStr::new("caf\u{e9}.txt"), OsStr::new("cafe\u{301}.txt"));
+        assert_eq!(Normalization::Nfc.apply(decomposed), composed);
+        assert_eq!(Normalization::Nfd.apply(composed), decomposed);
+        assert_eq!(Normalization::None.apply(decomposed), decomposed);
+        assert!(matches!(Normalization::Nfc.apply(composed), Cow::Borrowed(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_are_not_utf8_are_kept_and_escaped_apart() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let (e_acute, e_grave) = (OsStr::from_bytes(b"r\xe9sum\xe9"), OsStr::from_bytes(b"r\xe8sum\xe8"));
+        assert_eq!(Normalization::Nfc.apply(e_acute), e_acute);
+        assert_eq!(escaped_name(e_acute), "r%E9sum%E9");
+        assert_ne!(escaped_name(e_acute), escaped_name(e_grave));
+        assert_eq!(escaped_path(&Path::new("dir").join(e_acute)), "dir/r%E9sum%E9");
+        assert_eq!(numbered_name(&Path::new("dir").join(e_acute), 2).as_bytes(), b"r\xe9sum\xe9-2");
+    }
+
+}
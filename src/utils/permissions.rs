@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File, Metadata},
+    io::Read,
+    path::Path,
+};
+
+/// Why permission and owner changes fail outside Unix
+pub const UNSUPPORTED: &str = "Unix permissions and owners are not supported on this platform";
+
+/// Setuid, setgid, and sticky bits
+pub const SPECIAL_BITS: u32 = 0o7000;
+
+/// Mode bits and owner of a file or directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attributes {
+    /// Permission bits, special bits included, without the file type
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Attributes {
+    #[cfg(unix)]
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self {
+            mode: metadata.mode() & 0o7777,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn from_metadata(_metadata: &Metadata) -> Self {
+        Self { mode: 0, uid: 0, gid: 0 }
+    }
+
+    /// The attributes of `path` itself, not of what a symlink points to
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(Self::from_metadata(&fs::symlink_metadata(path)?))
+    }
+}
+
+impl fmt::Display for Attributes {
+    /// `0644 1000:1000`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04o} {}:{}", self.mode, self.uid, self.gid)
+    }
+}
+
+/// Parses an octal mode such as `644` or `2775`
+pub fn parse_mode(text: &str) -> Result<u32> {
+    let text = text.trim().trim_start_matches("0o");
+    match u32::from_str_radix(text, 8) {
+        Ok(mode) if !text.is_empty() && text.len() <= 4 && mode <= 0o7777 => Ok(mode),
+        _ => anyhow::bail!("Invalid mode: {} (expected octal, e.g. 644 or 2775)", text),
+    }
+}
+
+/// `mode` with an execute bit wherever it has a read bit, e.g. 644 → 755
+/// and 640 → 750
+pub fn with_execute(mode: u32) -> u32 {
+    mode | ((mode & 0o444) >> 2)
+}
+
+/// Whether `path` starts like something meant to be run: a `#!` script,
+/// or an ELF or Mach-O binary
+pub fn looks_executable(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    let Ok(read) = File::open(path).and_then(|mut file| file.read(&mut magic)) else {
+        return false;
+    };
+    let magic = &magic[..read];
+    magic.starts_with(b"#!")
+        || magic == b"\x7fELF"
+        || matches!(
+            magic,
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf] | [0xce | 0xcf, 0xfa, 0xed, 0xfe] | [0xca, 0xfe, 0xba, 0xbe]
+        )
+}
+
+/// Gives `path` the owner and mode of `after`. The owner goes first, since
+/// changing it clears setuid and setgid bits the mode may set.
+#[cfg(unix)]
+pub fn set_attributes(path: &Path, before: &Attributes, after: &Attributes) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if (before.uid, before.gid) != (after.uid, after.gid) {
+        std::os::unix::fs::chown(path, Some(after.uid), Some(after.gid))
+            .with_context(|| format!("Failed to change the owner of {} to {}:{}", path.display(), after.uid, after.gid))?;
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(after.mode))
+        .with_context(|| format!("Failed to change the mode of {} to {:04o}", path.display(), after.mode))
+}
+
+#[cfg(not(unix))]
+pub fn set_attributes(_path: &Path, _before: &Attributes, _after: &Attributes) -> Result<()> {
+    anyhow::bail!(UNSUPPORTED)
+}
+
+/// Whether the tool runs as root, which changing a file's owner needs
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid cannot fail and touches no memory
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// The id of the user `name`, which may also be a number
+#[cfg(unix)]
+pub fn user_id(name: &str) -> Result<u32> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    let c_name = std::ffi::CString::new(name)?;
+    // SAFETY: getpwnam takes a NUL-terminated string; the entry it returns
+    // is only read before the next call
+    let entry = unsafe { libc::getpwnam(c_name.as_ptr()) };
+    if entry.is_null() {
+        anyhow::bail!("No such user: {}", name);
+    }
+    Ok(unsafe { (*entry).pw_uid })
+}
+
+/// The id of the group `name`, which may also be a number
+#[cfg(unix)]
+pub fn group_id(name: &str) -> Result<u32> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let c_name = std::ffi::CString::new(name)?;
+    // SAFETY: as for getpwnam above
+    let entry = unsafe { libc::getgrnam(c_name.as_ptr()) };
+    if entry.is_null() {
+        anyhow::bail!("No such group: {}", name);
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+#[cfg(not(unix))]
+pub fn user_id(_name: &str) -> Result<u32> {
+    anyhow::bail!(UNSUPPORTED)
+}
+
+#[cfg(not(unix))]
+pub fn group_id(_name: &str) -> Result<u32> {
+    anyhow::bail!(UNSUPPORTED)
+}
@@ -0,0 +1,232 @@
+use indicatif::ProgressBar;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::modules::base::Summary;
+
+/// How processing one item ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Done,
+    Skipped,
+    Failed,
+}
+
+/// Receives progress from module logic, so the same code can drive a
+/// terminal progress bar, stay silent, or be recorded by a test. Every
+/// event defaults to doing nothing, and sinks are shared across worker
+/// threads.
+pub trait ProgressSink: Sync {
+    /// Walking `root` for input has begun
+    fn scan_started(&self, _root: &Path) {}
+
+    /// A new phase such as hashing or converting has begun, covering
+    /// `total` items
+    fn phase_changed(&self, _phase: &str, _total: u64) {}
+
+    /// Work on one item has begun
+    fn item_started(&self, _path: &Path) {}
+
+    /// Work on one item has ended, having processed `bytes`
+    fn item_finished(&self, _path: &Path, _bytes: u64, _outcome: Outcome) {}
+
+    /// Another `bytes` of the current item were written, for progress by
+    /// bytes within large files
+    fn bytes_written(&self, _bytes: u64) {}
+
+    /// The run is over
+    fn finished(&self, _summary: &Summary) {}
+}
+
+/// Discards every event, for `--quiet` runs and library callers that do
+/// not care
+impl ProgressSink for () {}
+
+impl<T: ProgressSink + ?Sized> ProgressSink for &T {
+    fn scan_started(&self, root: &Path) {
+        (**self).scan_started(root)
+    }
+
+    fn phase_changed(&self, phase: &str, total: u64) {
+        (**self).phase_changed(phase, total)
+    }
+
+    fn item_started(&self, path: &Path) {
+        (**self).item_started(path)
+    }
+
+    fn item_finished(&self, path: &Path, bytes: u64, outcome: Outcome) {
+        (**self).item_finished(path, bytes, outcome)
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        (**self).bytes_written(bytes)
+    }
+
+    fn finished(&self, summary: &Summary) {
+        (**self).finished(summary)
+    }
+}
+
+/// Sends every event to both sinks, e.g. a progress bar and metrics
+impl<A: ProgressSink, B: ProgressSink> ProgressSink for (A, B) {
+    fn scan_started(&self, root: &Path) {
+        self.0.scan_started(root);
+        self.1.scan_started(root);
+    }
+
+    fn phase_changed(&self, phase: &str, total: u64) {
+        self.0.phase_changed(phase, total);
+        self.1.phase_changed(phase, total);
+    }
+
+    fn item_started(&self, path: &Path) {
+        self.0.item_started(path);
+        self.1.item_started(path);
+    }
+
+    fn item_finished(&self, path: &Path, bytes: u64, outcome: Outcome) {
+        self.0.item_finished(path, bytes, outcome);
+        self.1.item_finished(path, bytes, outcome);
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        self.0.bytes_written(bytes);
+        self.1.bytes_written(bytes);
+    }
+
+    fn finished(&self, summary: &Summary) {
+        self.0.finished(summary);
+        self.1.finished(summary);
+    }
+}
+
+/// A sink that may not be there, such as a spinner only shown sometimes
+impl<T: ProgressSink> ProgressSink for Option<T> {
+    fn scan_started(&self, root: &Path) {
+        if let Some(sink) = self {
+            sink.scan_started(root);
+        }
+    }
+
+    fn phase_changed(&self, phase: &str, total: u64) {
+        if let Some(sink) = self {
+            sink.phase_changed(phase, total);
+        }
+    }
+
+    fn item_started(&self, path: &Path) {
+        if let Some(sink) = self {
+            sink.item_started(path);
+        }
+    }
+
+    fn item_finished(&self, path: &Path, bytes: u64, outcome: Outcome) {
+        if let Some(sink) = self {
+            sink.item_finished(path, bytes, outcome);
+        }
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        if let Some(sink) = self {
+            sink.bytes_written(bytes);
+        }
+    }
+
+    fn finished(&self, summary: &Summary) {
+        if let Some(sink) = self {
+            sink.finished(summary);
+        }
+    }
+}
+
+/// Drives the bar's length, position, and message; finishing is left to
+/// the caller, which knows what to say
+impl ProgressSink for ProgressBar {
+    fn phase_changed(&self, phase: &str, total: u64) {
+        self.set_message(format!("{}...", phase));
+        self.set_length(total);
+        self.set_position(0);
+    }
+
+    fn item_finished(&self, _path: &Path, _bytes: u64, _outcome: Outcome) {
+        self.inc(1);
+    }
+}
+
+/// Counts progress in bytes written rather than items, for a bar made by
+/// [`create_byte_bar`](super::create_byte_bar); the message shows the item
+/// being worked on
+pub struct ByteProgress(pub ProgressBar);
+
+impl ProgressSink for ByteProgress {
+    fn item_started(&self, path: &Path) {
+        self.0
+            .set_message(path.file_name().unwrap_or_default().to_string_lossy().into_owned());
+    }
+
+    fn bytes_written(&self, bytes: u64) {
+        self.0.inc(bytes);
+    }
+}
+
+/// One event as seen by a [`Recorder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    ScanStarted(PathBuf),
+    PhaseChanged { phase: String, total: u64 },
+    ItemStarted(PathBuf),
+    ItemFinished { path: PathBuf, bytes: u64, outcome: Outcome },
+    Finished { files: usize, bytes: u64 },
+}
+
+/// Keeps every event in order, for asserting on what a run reported
+#[derive(Debug, Default)]
+pub struct Recorder {
+    events: Mutex<Vec<ProgressEvent>>,
+}
+
+impl Recorder {
+    /// The events received so far
+    pub fn events(&self) -> Vec<ProgressEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn push(&self, event: ProgressEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ProgressSink for Recorder {
+    fn scan_started(&self, root: &Path) {
+        self.push(ProgressEvent::ScanStarted(root.to_path_buf()));
+    }
+
+    fn phase_changed(&self, phase: &str, total: u64) {
+        self.push(ProgressEvent::PhaseChanged {
+            phase: phase.to_string(),
+            total,
+        });
+    }
+
+    fn item_started(&self, path: &Path) {
+        self.push(ProgressEvent::ItemStarted(path.to_path_buf()));
+    }
+
+    fn item_finished(&self, path: &Path, bytes: u64, outcome: Outcome) {
+        self.push(ProgressEvent::ItemFinished {
+            path: path.to_path_buf(),
+            bytes,
+            outcome,
+        });
+    }
+
+    fn finished(&self, summary: &Summary) {
+        self.push(ProgressEvent::Finished {
+            files: summary.files,
+            bytes: summary.bytes,
+        });
+    }
+}
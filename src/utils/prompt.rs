@@ -0,0 +1,227 @@
+//! The prompts of [`dialoguer`] the modules use, with the same builders,
+//! taking part in a `--record` or `--replay` [session](super::session):
+//! each answer is saved while recording and taken from the recording while
+//! replaying.
+
+use anyhow::Result;
+use dialoguer::theme::Theme;
+use std::str::FromStr;
+
+use super::session::{self, Answer, Turn};
+
+pub struct Select<'a> {
+    inner: dialoguer::Select<'a>,
+    prompt: String,
+    items: Vec<String>,
+}
+
+impl<'a> Select<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { inner: dialoguer::Select::with_theme(theme), prompt: String::new(), items: Vec::new() }
+    }
+
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = prompt.into();
+        self.inner = self.inner.with_prompt(self.prompt.clone());
+        self
+    }
+
+    pub fn items<T: ToString>(mut self, items: &[T]) -> Self {
+        self.items.extend(items.iter().map(ToString::to_string));
+        self.inner = self.inner.items(items);
+        self
+    }
+
+    pub fn default(mut self, index: usize) -> Self {
+        self.inner = self.inner.default(index);
+        self
+    }
+
+    /// The index of the item picked. A replayed choice is found by the
+    /// item's text, so lists that grew or were reordered still work.
+    pub fn interact(self) -> Result<usize> {
+        match session::turn(&self.prompt)? {
+            Turn::Replayed(Answer::Choice(item)) => self
+                .items
+                .iter()
+                .position(|candidate| *candidate == item)
+                .ok_or_else(|| session::mismatch(&self.prompt, &Answer::Choice(item.clone()), "it is no longer offered")),
+            Turn::Replayed(answer) => Err(session::mismatch(&self.prompt, &answer, "this prompt picks one item")),
+            Turn::Ask => {
+                let index = self.inner.interact()?;
+                session::record(&self.prompt, Answer::Choice(self.items[index].clone()));
+                Ok(index)
+            }
+        }
+    }
+}
+
+pub struct MultiSelect<'a> {
+    inner: dialoguer::MultiSelect<'a>,
+    prompt: String,
+    items: Vec<String>,
+}
+
+impl<'a> MultiSelect<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { inner: dialoguer::MultiSelect::with_theme(theme), prompt: String::new(), items: Vec::new() }
+    }
+
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = prompt.into();
+        self.inner = self.inner.with_prompt(self.prompt.clone());
+        self
+    }
+
+    pub fn items<T: ToString>(mut self, items: &[T]) -> Self {
+        self.items.extend(items.iter().map(ToString::to_string));
+        self.inner = self.inner.items(items);
+        self
+    }
+
+    pub fn defaults(mut self, checked: &[bool]) -> Self {
+        self.inner = self.inner.defaults(checked);
+        self
+    }
+
+    /// The indexes of the items ticked, in list order
+    pub fn interact(self) -> Result<Vec<usize>> {
+        match session::turn(&self.prompt)? {
+            Turn::Replayed(Answer::Choices(items)) => {
+                let mut indexes = items
+                    .iter()
+                    .map(|item| {
+                        self.items.iter().position(|candidate| candidate == item).ok_or_else(|| {
+                            let reason = format!("{} is no longer offered", item);
+                            session::mismatch(&self.prompt, &Answer::Choices(items.clone()), reason)
+                        })
+                    })
+                    .collect::<Result<Vec<usize>>>()?;
+                indexes.sort_unstable();
+                Ok(indexes)
+            }
+            Turn::Replayed(answer) => Err(session::mismatch(&self.prompt, &answer, "this prompt ticks items")),
+            Turn::Ask => {
+                let indexes = self.inner.interact()?;
+                let items = indexes.iter().map(|&index| self.items[index].clone()).collect();
+                session::record(&self.prompt, Answer::Choices(items));
+                Ok(indexes)
+            }
+        }
+    }
+}
+
+pub struct Confirm<'a> {
+    inner: dialoguer::Confirm<'a>,
+    prompt: String,
+}
+
+impl<'a> Confirm<'a> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { inner: dialoguer::Confirm::with_theme(theme), prompt: String::new() }
+    }
+
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = prompt.into();
+        self.inner = self.inner.with_prompt(self.prompt.clone());
+        self
+    }
+
+    pub fn default(mut self, yes: bool) -> Self {
+        self.inner = self.inner.default(yes);
+        self
+    }
+
+    pub fn interact(self) -> Result<bool> {
+        match session::turn(&self.prompt)? {
+            Turn::Replayed(Answer::Confirm(yes)) => Ok(yes),
+            Turn::Replayed(answer) => Err(session::mismatch(&self.prompt, &answer, "this prompt is a yes or no")),
+            Turn::Ask => {
+                let yes = self.inner.interact()?;
+                session::record(&self.prompt, Answer::Confirm(yes));
+                Ok(yes)
+            }
+        }
+    }
+}
+
+type Validator<'a, T> = Box<dyn FnMut(&T) -> Result<(), String> + 'a>;
+
+pub struct Input<'a, T> {
+    inner: dialoguer::Input<'a, T>,
+    prompt: String,
+    validators: Vec<Validator<'a, T>>,
+}
+
+impl<T> Input<'static, T> {
+    pub fn new() -> Self {
+        Self { inner: dialoguer::Input::new(), prompt: String::new(), validators: Vec::new() }
+    }
+}
+
+impl<T> Default for Input<'static, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: 'a> Input<'a, T> {
+    pub fn with_theme(theme: &'a dyn Theme) -> Self {
+        Self { inner: dialoguer::Input::with_theme(theme), prompt: String::new(), validators: Vec::new() }
+    }
+
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = prompt.into();
+        self.inner = self.inner.with_prompt(self.prompt.clone());
+        self
+    }
+
+    pub fn default(mut self, value: T) -> Self {
+        self.inner = self.inner.default(value);
+        self
+    }
+
+    pub fn allow_empty(mut self, allow: bool) -> Self {
+        self.inner = self.inner.allow_empty(allow);
+        self
+    }
+
+    /// Checks answers with `validator`, replayed ones included
+    pub fn validate_with<V, E>(mut self, mut validator: V) -> Self
+    where
+        V: FnMut(&T) -> Result<(), E> + 'a,
+        E: ToString,
+    {
+        self.validators.push(Box::new(move |value: &T| validator(value).map_err(|e| e.to_string())));
+        self
+    }
+}
+
+impl<'a, T> Input<'a, T>
+where
+    T: Clone + ToString + FromStr + 'a,
+    <T as FromStr>::Err: ToString,
+{
+    pub fn interact_text(self) -> Result<T> {
+        let Self { mut inner, prompt, mut validators } = self;
+        match session::turn(&prompt)? {
+            Turn::Replayed(Answer::Text(text)) => {
+                let reject = |reason: String| session::mismatch(&prompt, &Answer::Text(text.clone()), reason);
+                let value = text.parse::<T>().map_err(|e| reject(e.to_string()))?;
+                for validator in &mut validators {
+                    validator(&value).map_err(reject)?;
+                }
+                Ok(value)
+            }
+            Turn::Replayed(answer) => Err(session::mismatch(&prompt, &answer, "this prompt takes text")),
+            Turn::Ask => {
+                for validator in validators {
+                    inner = inner.validate_with(validator);
+                }
+                let value = inner.interact_text()?;
+                session::record(&prompt, Answer::Text(value.to_string()));
+                Ok(value)
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    path::{Component, Path, PathBuf},
+};
+
+/// Turns a value such as a tag or a directory name into a safe single
+/// path component: trims it, collapses runs of whitespace, replaces
+/// characters that are invalid on common filesystems, strips trailing
+/// dots, and avoids names the platform reserves. Returns `None` when
+/// nothing usable is left.
+pub fn folder_name(value: &str) -> Option<String> {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let trimmed = collapsed.trim_end_matches('.').trim();
+    (!trimmed.is_empty()).then(|| safe_file_name(trimmed).into_owned())
+}
+
+/// Names Windows reserves for devices in every directory, whatever the
+/// extension: `CON.pdf` opens the console rather than a file
+pub(crate) const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Paths at least this long need the `\\?\` prefix on Windows
+const MAX_PATH: usize = 260;
+
+/// Makes a file or directory name the tool is about to create usable on
+/// this platform. On Windows, trailing dots and spaces (which Windows
+/// silently drops) are trimmed and reserved device names get an underscore,
+/// e.g. `CON.pdf` becomes `CON_.pdf`. Elsewhere names are left as they are.
+pub fn safe_file_name(name: &str) -> Cow<'_, str> {
+    safe_file_name_with(name, cfg!(windows))
+}
+
+/// [`safe_file_name`] for a name taken from the filesystem, which is kept
+/// byte for byte unless it has to change
+pub fn safe_os_name(name: &OsStr) -> Cow<'_, OsStr> {
+    match name.to_str().map(safe_file_name) {
+        Some(Cow::Owned(safe)) => Cow::Owned(OsString::from(safe)),
+        _ => Cow::Borrowed(name),
+    }
+}
+
+fn safe_file_name_with(name: &str, windows: bool) -> Cow<'_, str> {
+    if !windows {
+        return Cow::Borrowed(name);
+    }
+    let trimmed = match name.trim_end_matches(['.', ' ']) {
+        "" => "_",
+        trimmed => trimmed,
+    };
+    let (stem, extension) = trimmed.split_at(trimmed.find('.').unwrap_or(trimmed.len()));
+    // `CON .txt` is reserved too
+    let reserved = RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.trim_end().eq_ignore_ascii_case(reserved));
+    match (reserved, trimmed.len() == name.len()) {
+        (false, true) => Cow::Borrowed(name),
+        (false, false) => Cow::Owned(trimmed.to_string()),
+        (true, _) => Cow::Owned(format!("{}_{}", stem, extension)),
+    }
+}
+
+/// [`safe_file_name`] applied to every component of a relative path, such
+/// as a category folder
+pub fn safe_relative_path(path: &Path) -> PathBuf {
+    let mut safe = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => safe.push(safe_os_name(name)),
+            other => safe.push(other),
+        }
+    }
+    safe
+}
+
+/// On Windows, gives an absolute path too long for the classic API the
+/// `\\?\` prefix that lifts the limit, `\\?\UNC\` for network shares.
+/// Shorter, relative, and already prefixed paths are returned unchanged.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str().and_then(|text| long_path_with(text, cfg!(windows))) {
+        Some(long) => Cow::Owned(PathBuf::from(long)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+fn long_path_with(path: &str, windows: bool) -> Option<String> {
+    if !windows || path.len() < MAX_PATH || path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', r"\");
+    // Prefixed paths are taken literally, so `.` and `..` would not resolve
+    if path.split('\\').any(|part| part == "." || part == "..") {
+        return None;
+    }
+    let bytes = path.as_bytes();
+    if let Some(share) = path.strip_prefix(r"\\") {
+        Some(format!(r"\\?\UNC\{}", share))
+    } else if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        Some(format!(r"\\?\{}", path))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_device_names_get_an_underscore_on_windows() {
+        assert_eq!(safe_file_name_with("CON.pdf", true), "CON_.pdf");
+        assert_eq!(safe_file_name_with("con", true), "con_");
+        assert_eq!(safe_file_name_with("Lpt1.tar.gz", true), "Lpt1_.tar.gz");
+        assert_eq!(safe_file_name_with("CON .txt", true), "CON _.txt");
+        assert_eq!(safe_file_name_with("CONSOLE.pdf", true), "CONSOLE.pdf");
+        assert_eq!(safe_file_name_with("COM10", true), "COM10");
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_are_trimmed_on_windows() {
+        assert_eq!(safe_file_name_with("notes. . ", true), "notes");
+        assert_eq!(safe_file_name_with("...", true), "_");
+        assert_eq!(safe_file_name_with("AUX.", true), "AUX_");
+        assert!(matches!(safe_file_name_with("report.pdf", true), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn names_are_untouched_elsewhere() {
+        for name in ["CON.pdf", "notes. ", "..."] {
+            assert!(matches!(safe_file_name_with(name, false), Cow::Borrowed(n) if n == name));
+        }
+    }
+
+    #[test]
+    fn folder_names_lose_characters_filesystems_reject() {
+        assert_eq!(folder_name("  AC/DC:  Live\t\"1991\"... ").as_deref(), Some("AC_DC_ Live _1991_"));
+        assert_eq!(folder_name(" . "), None);
+        assert_eq!(folder_name(""), None);
+    }
+
+    #[test]
+    fn long_windows_paths_get_the_extended_prefix() {
+        let deep = format!(r"C:\Users\me\{}\file.txt", "a".repeat(MAX_PATH));
+        assert_eq!(long_path_with(&deep, true), Some(format!(r"\\?\{}", deep)));
+        let share = format!(r"\\server\share\{}", "a".repeat(MAX_PATH));
+        assert_eq!(long_path_with(&share, true), Some(format!(r"\\?\UNC\server\share\{}", "a".repeat(MAX_PATH))));
+        let slashes = format!("C:/data/{}", "a".repeat(MAX_PATH));
+        assert_eq!(long_path_with(&slashes, true), Some(format!(r"\\?\C:\data\{}", "a".repeat(MAX_PATH))));
+    }
+
+    #[test]
+    fn other_paths_keep_their_form() {
+        let long = "a".repeat(MAX_PATH);
+        assert_eq!(long_path_with(r"C:\short\file.txt", true), None);
+        assert_eq!(long_path_with(&format!(r"\\?\C:\{}", long), true), None);
+        assert_eq!(long_path_with(&format!(r"relative\{}", long), true), None);
+        assert_eq!(long_path_with(&format!(r"C:\{}\..\file", long), true), None);
+        assert_eq!(long_path_with(&format!(r"C:\{}", long), false), None);
+    }
+
+    #[test]
+    fn every_component_of_a_relative_path_is_made_safe() {
+        assert_eq!(safe_relative_path(Path::new("Documents/Reports")), Path::new("Documents/Reports"));
+        if cfg!(windows) {
+            assert_eq!(safe_relative_path(Path::new("NUL/notes.")), Path::new("NUL_/notes"));
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use anyhow::{Context, Result};
+use rand::{Rng, RngCore};
+use serde::Serialize;
+use std::{
+    fs::{self, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Bytes written at a time while overwriting
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Shown whenever files are securely deleted, whatever could be detected
+/// about the storage
+pub const CAVEAT: &str = "Overwriting only destroys the old contents where the storage writes in place. \
+SSDs and flash drives remap writes to fresh cells, copy-on-write filesystems (btrfs, ZFS, APFS) \
+write new blocks, and snapshots and backups keep their own copies; on those, only full-disk \
+encryption protects deleted data.";
+
+/// Filesystems that write changed data to new blocks, so overwriting
+/// leaves the old blocks alone
+const COPY_ON_WRITE: &[&str] = &["btrfs", "zfs", "bcachefs", "f2fs", "nilfs2", "apfs"];
+
+/// What one overwrite pass writes over a file's contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pass {
+    Zeros,
+    Ones,
+    Random,
+}
+
+impl Pass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zeros => "zeros",
+            Self::Ones => "ones",
+            Self::Random => "random",
+        }
+    }
+
+    /// Parses `zeros`, `ones`, or `random`
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "zeros" | "zero" => Ok(Self::Zeros),
+            "ones" | "one" => Ok(Self::Ones),
+            "random" => Ok(Self::Random),
+            _ => anyhow::bail!("Unknown overwrite pass: {} (expected zeros, ones, or random)", name),
+        }
+    }
+}
+
+/// How [`shred`] gets rid of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShredOptions {
+    /// Written in order, each over the whole file and flushed to disk
+    /// before the next
+    pub passes: Vec<Pass>,
+    /// Cut the file to nothing after the last pass, so its length is gone
+    /// too
+    pub truncate: bool,
+    /// Give the file a random name before unlinking it, so the old name
+    /// does not linger in the directory
+    pub rename: bool,
+}
+
+impl Default for ShredOptions {
+    /// One random pass, then zeros, then truncate and rename
+    fn default() -> Self {
+        Self {
+            passes: vec![Pass::Random, Pass::Zeros],
+            truncate: true,
+            rename: true,
+        }
+    }
+}
+
+/// Overwrites the first `len` bytes of `target` with each pass in turn,
+/// calling `sync` after each one; [`shred`] syncs the file to disk there.
+/// Anything seekable can be overwritten, which is how the passes can be
+/// checked without a disk.
+///
+/// ```
+/// use file_organizer_rust::utils::secure_delete::{overwrite, Pass};
+/// use std::io::Cursor;
+///
+/// let mut file = Cursor::new(b"client secret".to_vec());
+/// let mut seen = Vec::new();
+/// overwrite(&mut file, 13, &[Pass::Random, Pass::Ones, Pass::Zeros], |file| {
+///     seen.push(file.get_ref().clone());
+///     Ok(())
+/// })?;
+/// assert_eq!(seen.len(), 3);
+/// assert_ne!(seen[0], b"client secret");
+/// assert_eq!(seen[1], [0xff; 13]);
+/// assert_eq!(seen[2], [0; 13]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn overwrite<W: Write + Seek>(
+    target: &mut W,
+    len: u64,
+    passes: &[Pass],
+    mut sync: impl FnMut(&mut W) -> Result<()>,
+) -> Result<()> {
+    let mut block = vec![0u8; BLOCK_SIZE];
+    let mut rng = rand::thread_rng();
+    for &pass in passes {
+        match pass {
+            Pass::Zeros => block.fill(0),
+            Pass::Ones => block.fill(0xff),
+            Pass::Random => {}
+        }
+        target.seek(SeekFrom::Start(0))?;
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(BLOCK_SIZE as u64) as usize;
+            if pass == Pass::Random {
+                rng.fill_bytes(&mut block[..n]);
+            }
+            target.write_all(&block[..n])?;
+            left -= n as u64;
+        }
+        target.flush()?;
+        sync(target)?;
+    }
+    Ok(())
+}
+
+/// Overwrites a file as `options` asks, then removes it. A directory has
+/// every file in it shredded and is then removed; symlinks are removed
+/// without touching what they point to. A file with other hard links is
+/// refused, as overwriting it would overwrite them too.
+///
+/// ```
+/// use file_organizer_rust::utils::secure_delete::{shred, ShredOptions};
+///
+/// let dir = tempfile::tempdir()?;
+/// let path = dir.path().join("contract.pdf");
+/// std::fs::write(&path, "confidential")?;
+///
+/// shred(&path, &ShredOptions::default())?;
+/// assert!(!path.exists());
+/// assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn shred(path: &Path, options: &ShredOptions) -> Result<()> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        return shred_file(path, options);
+    }
+    for entry in WalkDir::new(path).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            fs::remove_dir(entry.path())?;
+        } else {
+            shred_file(entry.path(), options)?;
+        }
+    }
+    Ok(())
+}
+
+fn shred_file(path: &Path, options: &ShredOptions) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_file() {
+        fs::remove_file(path)?;
+        return Ok(());
+    }
+    if let Some(links) = hard_links(&metadata).filter(|&links| links > 1) {
+        anyhow::bail!(
+            "{} has {} other hard links, whose contents would be overwritten too",
+            path.display(),
+            links - 1
+        );
+    }
+    if metadata.permissions().readonly() {
+        let mut permissions = metadata.permissions();
+        // Only the owner may write, so a failed overwrite does not leave
+        // the file writable by everyone
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(permissions.mode() | 0o200);
+        }
+        #[cfg(windows)]
+        permissions.set_readonly(false);
+        fs::set_permissions(path, permissions)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {} for overwriting", path.display()))?;
+    overwrite(&mut file, metadata.len(), &options.passes, |file| Ok(file.sync_all()?))?;
+    if options.truncate {
+        file.set_len(0)?;
+        file.sync_all()?;
+    }
+    drop(file);
+
+    let path = if options.rename { rename_away(path)? } else { path.to_path_buf() };
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Renames `path` to a random hidden name in the same directory
+fn rename_away(path: &Path) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut rng = rand::thread_rng();
+    for _ in 0..8 {
+        let target = dir.join(format!(".{:016x}", rng.gen::<u64>()));
+        if fs::symlink_metadata(&target).is_err() {
+            fs::rename(path, &target)?;
+            return Ok(target);
+        }
+    }
+    anyhow::bail!("No free name to rename {} to", path.display())
+}
+
+#[cfg(unix)]
+fn hard_links(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+#[cfg(not(unix))]
+fn hard_links(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Reasons overwriting is known not to reach the old contents of files
+/// under `path`: a copy-on-write filesystem or a solid-state drive. Only
+/// Linux can tell; elsewhere nothing is detected and [`CAVEAT`] is all
+/// there is to go by.
+#[cfg(target_os = "linux")]
+pub fn storage_warnings(path: &Path) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(path) = fs::canonicalize(path) else {
+        return Vec::new();
+    };
+    let mut warnings = Vec::new();
+    if let Some(filesystem) = filesystem_type(&path).filter(|fs| COPY_ON_WRITE.contains(&fs.as_str())) {
+        warnings.push(format!(
+            "{} is on {}, a copy-on-write filesystem: overwrites go to new blocks and the old contents stay on disk",
+            path.display(),
+            filesystem
+        ));
+    }
+    let solid_state = fs::metadata(&path).ok().and_then(|metadata| solid_state(metadata.dev()));
+    if solid_state == Some(true) {
+        warnings.push(format!(
+            "{} is on a solid-state drive: overwrites land in fresh cells and the old ones may keep the contents",
+            path.display()
+        ));
+    }
+    warnings
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn storage_warnings(_path: &Path) -> Vec<String> {
+    Vec::new()
+}
+
+/// Prints [`CAVEAT`] and whatever [`storage_warnings`] finds for `path`
+pub fn print_caveat(path: &Path) {
+    println!("Note: {}", CAVEAT);
+    for warning in storage_warnings(path) {
+        println!("Warning: {}", warning);
+    }
+}
+
+/// The type of the filesystem mounted closest above `path`, from
+/// `/proc/self/mountinfo`
+#[cfg(target_os = "linux")]
+fn filesystem_type(path: &Path) -> Option<String> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mount_point = unescape_mount(mount.split(' ').nth(4)?);
+            let filesystem = filesystem.split(' ').next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.components().count(), filesystem.to_string()))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, filesystem)| filesystem)
+}
+
+/// Undoes the `\040`-style octal escapes of spaces and other characters
+/// in mount points
+#[cfg(target_os = "linux")]
+fn unescape_mount(field: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4).filter(|_| bytes[i] == b'\\');
+        match escaped.and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok()) {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(out))
+}
+
+/// Whether the block device `dev` is not rotational, from sysfs; `None`
+/// for devices sysfs does not describe, such as btrfs's virtual ones
+#[cfg(target_os = "linux")]
+fn solid_state(dev: u64) -> Option<bool> {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let device = PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor));
+    // A partition has no queue of its own; its disk, one level up, does
+    let rotational = fs::read_to_string(device.join("queue/rotational"))
+        .or_else(|_| fs::read_to_string(device.join("../queue/rotational")))
+        .ok()?;
+    Some(rotational.trim() == "0")
+}
@@ -0,0 +1,247 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{atomic::write_atomic, expand::expand_path};
+
+/// The answers of an interactive run, saved by `--record` and played back
+/// by `--replay`: the command line it was started with, and every prompt
+/// in the order it came up. Answers marked `ask = true` are asked again on
+/// each replay.
+///
+/// ```
+/// use file_organizer_rust::utils::session::{Answer, Recording};
+///
+/// let dir = tempfile::tempdir()?;
+/// let text = format!(r#"
+///     args = ["archive", "--recursive"]
+///
+///     [[answer]]
+///     prompt = "Select operation"
+///     choice = "Create"
+///
+///     [[answer]]
+///     prompt = "Enter input directory path"
+///     directory = "{}"
+///     ask = true
+/// "#, dir.path().display());
+/// let recording = Recording::parse(&text)?;
+/// assert_eq!(recording.args, ["archive", "--recursive"]);
+/// assert_eq!(recording.answers[0].answer, Answer::Choice("Create".to_string()));
+/// assert!(recording.answers[1].ask);
+///
+/// // A directory that is gone is only an error when it is not asked for
+/// let gone = text.replace(&dir.path().display().to_string(), "/nonexistent/photos");
+/// assert!(Recording::parse(&gone)?.check().is_ok());
+/// assert!(Recording::parse(&gone.replace("ask = true", ""))?.check().is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recording {
+    /// Command line of the recorded run, without `--record`
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default, rename = "answer")]
+    pub answers: Vec<RecordedAnswer>,
+}
+
+/// `[[answer]]`: one prompt and what was answered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedAnswer {
+    pub prompt: String,
+    #[serde(flatten)]
+    pub answer: Answer,
+    /// Ask again on each replay instead of using the answer
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ask: bool,
+}
+
+/// An answer, keyed by the kind of prompt it answers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Answer {
+    /// The item picked from a list
+    Choice(String),
+    /// The items ticked in a list
+    Choices(Vec<String>),
+    Confirm(bool),
+    Text(String),
+    /// A directory, which must still exist when replayed
+    Directory(PathBuf),
+    Directories(Vec<PathBuf>),
+}
+
+impl Answer {
+    /// The answer as one line, for listing
+    pub fn describe(&self) -> String {
+        match self {
+            Answer::Choice(item) | Answer::Text(item) => item.clone(),
+            Answer::Choices(items) => items.join(", "),
+            Answer::Confirm(yes) => String::from(if *yes { "yes" } else { "no" }),
+            Answer::Directory(dir) => dir.display().to_string(),
+            Answer::Directories(dirs) => {
+                dirs.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+            }
+        }
+    }
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid recording {}", path.display()))
+    }
+
+    /// Parses a recording, expanding `~` and environment variables in its
+    /// directories
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut recording: Self = toml::from_str(text)?;
+        for answer in &mut recording.answers {
+            match &mut answer.answer {
+                Answer::Directory(dir) => *dir = expand_path(&dir.to_string_lossy())?,
+                Answer::Directories(dirs) => {
+                    for dir in dirs {
+                        *dir = expand_path(&dir.to_string_lossy())?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(recording)
+    }
+
+    /// Checks that every directory a replay would use without asking still
+    /// exists, naming each one that does not
+    pub fn check(&self) -> Result<()> {
+        let missing: Vec<String> = self
+            .answers
+            .iter()
+            .filter(|answer| !answer.ask)
+            .flat_map(|answer| match &answer.answer {
+                Answer::Directory(dir) => vec![(answer, dir)],
+                Answer::Directories(dirs) => dirs.iter().map(|dir| (answer, dir)).collect(),
+                _ => Vec::new(),
+            })
+            .filter(|(_, dir)| !dir.is_dir())
+            .map(|(answer, dir)| format!("  {} (answer to \"{}\")", dir.display(), answer.prompt))
+            .collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "These recorded directories no longer exist:\n{}\nEdit the recording or mark those answers \
+                 with ask = true",
+                missing.join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes the recording as TOML, with a note on how to edit it
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = format!(
+            "# Recorded by file-organizer-rust; run it again with --replay {}\n\
+             # Add ask = true to an answer to be asked for it on each replay instead\n\n{}",
+            path.display(),
+            toml::to_string_pretty(self)?
+        );
+        write_atomic(path, text)
+    }
+}
+
+/// What the prompts of this run do besides asking
+enum Session {
+    Recording(Recording),
+    Replaying { recording: Recording, next: usize },
+}
+
+// Prompts come up deep inside every module, so the session is kept here
+// rather than threaded through each of them
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Starts saving the answers to every prompt, for a run started with
+/// `args`
+pub fn start_recording(args: Vec<String>) {
+    *SESSION.lock().unwrap() = Some(Session::Recording(Recording { args, answers: Vec::new() }));
+}
+
+/// Stops recording, returning what was answered, or `None` when nothing
+/// was being recorded
+pub fn finish_recording() -> Option<Recording> {
+    match SESSION.lock().unwrap().take() {
+        Some(Session::Recording(recording)) => Some(recording),
+        _ => None,
+    }
+}
+
+/// Answers prompts from `recording` from now on, after checking that its
+/// directories still exist
+pub fn start_replay(recording: Recording) -> Result<()> {
+    recording.check()?;
+    *SESSION.lock().unwrap() = Some(Session::Replaying { recording, next: 0 });
+    Ok(())
+}
+
+/// How a prompt gets its answer
+pub(crate) enum Turn {
+    /// From the user, as usual
+    Ask,
+    /// From the recording being replayed
+    Replayed(Answer),
+}
+
+/// How the prompt `prompt` is answered. Replaying, the next recorded
+/// answer must be for the same prompt, numbers aside, or the run has
+/// drifted from the recording and stops.
+pub(crate) fn turn(prompt: &str) -> Result<Turn> {
+    let mut session = SESSION.lock().unwrap();
+    let Some(Session::Replaying { recording, next }) = session.as_mut() else {
+        return Ok(Turn::Ask);
+    };
+    let Some(answer) = recording.answers.get(*next) else {
+        anyhow::bail!(
+            "The recording has no answer for \"{}\"; the run asks more than when it was recorded, so record it again",
+            prompt
+        );
+    };
+    if shape(&answer.prompt) != shape(prompt) {
+        anyhow::bail!(
+            "The run no longer matches the recording: answer {} was recorded for \"{}\", but the run now asks \
+             \"{}\". Record it again",
+            *next + 1,
+            answer.prompt,
+            prompt
+        );
+    }
+    *next += 1;
+    Ok(if answer.ask { Turn::Ask } else { Turn::Replayed(answer.answer.clone()) })
+}
+
+/// Saves the answer to `prompt` when recording
+pub(crate) fn record(prompt: &str, answer: Answer) {
+    if let Some(Session::Recording(recording)) = SESSION.lock().unwrap().as_mut() {
+        recording.answers.push(RecordedAnswer { prompt: prompt.to_string(), answer, ask: false });
+    }
+}
+
+/// The error for a replayed answer of the wrong kind or no longer valid
+pub(crate) fn mismatch(prompt: &str, answer: &Answer, reason: impl std::fmt::Display) -> anyhow::Error {
+    anyhow::anyhow!("Recorded answer \"{}\" to \"{}\" cannot be used: {}", answer.describe(), prompt, reason)
+}
+
+/// `prompt` with its numbers blanked, so counts such as "Delete 12 junk
+/// entries?" match whatever the replayed run found
+fn shape(prompt: &str) -> String {
+    let mut shape = String::with_capacity(prompt.len());
+    for c in prompt.chars() {
+        if !c.is_ascii_digit() {
+            shape.push(c);
+        } else if !shape.ends_with('#') {
+            shape.push('#');
+        }
+    }
+    shape
+}
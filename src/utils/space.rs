@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use dialoguer::theme::ColorfulTheme;
+use humansize::{format_size, BINARY};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::prompt::Confirm;
+
+/// Free space that should be left after an operation: a tenth of what it
+/// writes, and at least this much, since nearly full filesystems slow
+/// down and other programs need room too
+const MIN_MARGIN: u64 = 100 * 1024 * 1024;
+
+/// Bytes an operation is about to write to one filesystem, next to what is
+/// free there
+#[derive(Debug, Clone, Serialize)]
+pub struct SpaceEstimate {
+    /// Where the operation writes; the free space is read from the
+    /// filesystem holding it
+    pub dir: PathBuf,
+    pub required: u64,
+    pub available: u64,
+}
+
+/// How an estimate compares with the free space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Headroom {
+    Enough,
+    /// Fits, but leaves less than the margin free
+    Tight,
+    /// Does not fit
+    Insufficient,
+}
+
+impl SpaceEstimate {
+    /// Reads the free space for writing `required` bytes under `dir`, which
+    /// need not exist yet
+    pub fn new(dir: &Path, required: u64) -> Result<Self> {
+        // A relative path's last ancestor is empty, meaning the current
+        // directory
+        let existing = dir
+            .ancestors()
+            .map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    dir
+                }
+            })
+            .find(|dir| dir.exists())
+            .unwrap_or(dir);
+        let available = fs2::available_space(existing)
+            .with_context(|| format!("Cannot read the free space of {}", existing.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            required,
+            available,
+        })
+    }
+
+    pub fn headroom(&self) -> Headroom {
+        if self.available < self.required {
+            Headroom::Insufficient
+        } else if self.available - self.required < (self.required / 10).max(MIN_MARGIN) {
+            Headroom::Tight
+        } else {
+            Headroom::Enough
+        }
+    }
+
+    /// E.g. "needs up to 1.2 GiB in /mnt/backup, 800 MiB free"
+    pub fn describe(&self) -> String {
+        format!(
+            "needs up to {} in {}, {} free",
+            format_size(self.required, BINARY),
+            self.dir.display(),
+            format_size(self.available, BINARY)
+        )
+    }
+}
+
+/// Prints every estimate, for dry runs
+pub fn print_estimates(estimates: &[SpaceEstimate]) {
+    for estimate in estimates {
+        let note = match estimate.headroom() {
+            Headroom::Enough => "",
+            Headroom::Tight => " (little space would be left)",
+            Headroom::Insufficient => " (not enough space)",
+        };
+        println!("Disk space: {}{}", estimate.describe(), note);
+    }
+}
+
+/// Checks the estimates before an operation writes anything. One that
+/// cannot fit stops the run unless `force` is set; one that would leave
+/// little free space asks first, again unless `force` is set.
+pub fn preflight(estimates: &[SpaceEstimate], force: bool) -> Result<()> {
+    for estimate in estimates {
+        match estimate.headroom() {
+            Headroom::Enough => {}
+            Headroom::Insufficient if force => {
+                println!("Warning: not enough disk space: {}; continuing because of --force", estimate.describe());
+            }
+            Headroom::Insufficient => {
+                anyhow::bail!("Not enough disk space: {} (use --force to try anyway)", estimate.describe());
+            }
+            Headroom::Tight if force => {
+                println!("Warning: little disk space would be left: {}", estimate.describe());
+            }
+            Headroom::Tight => {
+                let proceed = Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(format!("Little disk space would be left ({}). Continue?", estimate.describe()))
+                    .default(false)
+                    .interact()?;
+                if !proceed {
+                    anyhow::bail!("Cancelled for lack of disk space");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails when an estimate cannot fit, without printing or asking, for runs
+/// driven through the library
+pub fn check(estimates: &[SpaceEstimate]) -> Result<()> {
+    match estimates.iter().find(|estimate| estimate.headroom() == Headroom::Insufficient) {
+        Some(estimate) => anyhow::bail!("Not enough disk space: {}", estimate.describe()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, Metadata},
+    path::Path,
+    time::SystemTime,
+};
+
+/// Size and modification time of a file as it was scanned, checked again
+/// right before the file is deleted, moved, or read into an archive. A
+/// long run over a tree something else writes to, such as a sync client
+/// or a finishing download, would otherwise act on what the scan saw
+/// rather than what is there now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    /// `None` where the platform does not record it
+    pub modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    pub fn of(metadata: &Metadata) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+
+    /// Whether the file at `path` still has this size and modification
+    /// time. A file that is gone or can no longer be read has changed.
+    pub fn matches(&self, path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|metadata| Self::of(&metadata) == *self)
+    }
+}
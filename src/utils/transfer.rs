@@ -0,0 +1,273 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{hashing::sha256, metadata::copy_file_metadata, sanitize::long_path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMode {
+    Move,
+    Copy,
+}
+
+impl TransferMode {
+    /// Past-tense verb for summaries, e.g. "moved"
+    pub fn verb(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "moved",
+            TransferMode::Copy => "copied",
+        }
+    }
+}
+
+/// How a move to another filesystem checks the copy before deleting the
+/// source
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Verify {
+    /// Compare sizes
+    #[default]
+    Size,
+    /// Compare SHA-256 hashes, which reads both files again
+    Hash,
+}
+
+/// Moves or copies `source` to `target`, returning the number of bytes
+/// transferred. Copies keep the source mtime and permissions. Moves to
+/// another filesystem fall back to copying, checking the copy as `verify`
+/// asks, and deleting the source. Paths past the Windows length limit are
+/// handled.
+pub fn transfer_file(source: &Path, target: &Path, mode: TransferMode, verify: Verify) -> Result<u64> {
+    match mode {
+        TransferMode::Move => move_file(source, target, verify),
+        TransferMode::Copy => {
+            let (source, target) = (long_path(source), long_path(target));
+            let size = fs::copy(&source, &target)?;
+            copy_file_metadata(&source, &target)?;
+            Ok(size)
+        }
+    }
+}
+
+/// Renames `source` to `target`, returning the file size. When the two are
+/// on different filesystems (EXDEV), copies the file, checks the copy's
+/// size or hash, and only then deletes the source, keeping mtime and
+/// permissions. A partial copy is removed on failure so the source is
+/// never lost.
+pub fn move_file(source: &Path, target: &Path, verify: Verify) -> Result<u64> {
+    move_file_with(source, target, verify, |from, to| fs::rename(from, to))
+}
+
+/// [`move_file`] with the rename step supplied by the caller, so the
+/// cross-device fallback can be exercised without a second filesystem
+pub fn move_file_with(
+    source: &Path,
+    target: &Path,
+    verify: Verify,
+    rename: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> Result<u64> {
+    let (source, target) = (&*long_path(source), &*long_path(target));
+    let size = fs::metadata(source)?.len();
+    match rename(source, target) {
+        Ok(()) => Ok(size),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            // The source is only removed once the copy is complete
+            let copied = fs::copy(source, target)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| copy_file_metadata(source, target))
+                .and_then(|_| {
+                    let copied_size = fs::metadata(target)?.len();
+                    if copied_size != size {
+                        anyhow::bail!(
+                            "copy of {} is {} bytes, expected {}",
+                            source.display(),
+                            copied_size,
+                            size
+                        );
+                    }
+                    if verify == Verify::Hash && sha256(target)? != sha256(source)? {
+                        anyhow::bail!("copy of {} does not match the original", source.display());
+                    }
+                    Ok(())
+                });
+            if let Err(e) = copied {
+                let _ = fs::remove_file(target);
+                return Err(e);
+            }
+            fs::remove_file(source)?;
+            Ok(size)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Copies `source` to `target`, replacing any file there, and returns the
+/// bytes copied. The copy is written next to `target` under a temporary
+/// name, checked as `verify` asks, given the source mtime and permissions,
+/// and only then renamed into place, so `target` is never left half
+/// written and the rename never crosses filesystems. `written` is called
+/// after every chunk with its size.
+pub fn copy_atomic(
+    source: &Path,
+    target: &Path,
+    verify: Verify,
+    mut written: impl FnMut(u64),
+) -> Result<u64> {
+    let (source, target) = (&*long_path(source), &*long_path(target));
+    let temporary = partial_path(target);
+    let copied = (|| {
+        let mut reader = fs::File::open(source)?;
+        let mut writer = fs::File::create(&temporary)?;
+        let mut buffer = vec![0; COPY_CHUNK];
+        let mut size = 0;
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            writer.write_all(&buffer[..read])?;
+            size += read as u64;
+            written(read as u64);
+        }
+        writer.flush()?;
+        drop(writer);
+        copy_file_metadata(source, &temporary)?;
+        let expected = fs::metadata(source)?.len();
+        if size != expected {
+            anyhow::bail!("{} changed size while it was copied", source.display());
+        }
+        if verify == Verify::Hash && sha256(&temporary)? != sha256(source)? {
+            anyhow::bail!("copy of {} does not match the original", source.display());
+        }
+        fs::rename(&temporary, target)?;
+        Ok(size)
+    })();
+    if copied.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    copied
+}
+
+/// Bytes [`copy_atomic`] reads and writes at a time
+const COPY_CHUNK: usize = 1024 * 1024;
+
+/// Temporary name [`copy_atomic`] writes `target` under:
+/// `.<name>.partial` in the same directory
+pub fn partial_path(target: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    name.push(".partial");
+    target.with_file_name(name)
+}
+
+/// Puts a new file at `target` with `write`, in place of `existing`, the
+/// file already holding that name (usually `target` itself). `existing` is
+/// moved aside first, then handed to `discard` once `write` succeeds, or
+/// put back when it fails, so a failed transfer never costs the existing
+/// file. A file `discard` cannot get rid of is reported and left aside.
+pub fn replace_with<T>(
+    existing: &Path,
+    target: &Path,
+    write: impl FnOnce() -> Result<T>,
+    discard: impl FnOnce(&Path) -> Result<()>,
+) -> Result<T> {
+    let aside = aside_path(existing);
+    fs::rename(existing, &aside)?;
+    match write() {
+        Ok(value) => {
+            if let Err(e) = discard(&aside) {
+                println!("Could not discard the replaced {}: {:#}", aside.display(), e);
+            }
+            Ok(value)
+        }
+        Err(e) => {
+            // Whatever `write` left behind gives way to the original
+            let _ = fs::remove_file(target);
+            match fs::rename(&aside, existing) {
+                Ok(()) => Err(e),
+                Err(restore) => Err(e.context(format!(
+                    "the replaced file could not be put back and is at {} ({})",
+                    aside.display(),
+                    restore
+                ))),
+            }
+        }
+    }
+}
+
+/// `.<name>.replaced` next to `existing`, where [`replace_with`] keeps the
+/// file being replaced
+pub fn aside_path(existing: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(existing.file_name().unwrap_or_default());
+    name.push(".replaced");
+    existing.with_file_name(name)
+}
+
+/// Whether `err` comes from a file another process holds open without
+/// sharing (Windows sharing and lock violations). Such files are skipped
+/// rather than aborting a run.
+pub fn is_in_use(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<io::Error>())
+        .any(|e| cfg!(windows) && matches!(e.raw_os_error(), Some(32 | 33)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cross_device(_: &Path, _: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::CrossesDevices.into())
+    }
+
+    #[test]
+    fn a_cross_device_move_verifies_the_copy_before_removing_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("source.txt"), dir.path().join("target.txt"));
+        fs::write(&source, "contents").unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_700_000_000, 0);
+        filetime::set_file_mtime(&source, mtime).unwrap();
+
+        assert_eq!(move_file_with(&source, &target, Verify::Hash, cross_device).unwrap(), 8);
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "contents");
+        let copied = fs::metadata(&target).unwrap();
+        assert_eq!(filetime::FileTime::from_last_modification_time(&copied), mtime);
+    }
+
+    #[test]
+    fn a_copy_that_fails_verification_is_removed_and_the_source_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("source.txt"), dir.path().join("target.txt"));
+        fs::write(&source, "contents").unwrap();
+
+        // The source grows between being measured and being copied
+        let result = move_file_with(&source, &target, Verify::Size, |from, to| {
+            fs::OpenOptions::new().append(true).open(from)?.write_all(b" and more")?;
+            cross_device(from, to)
+        });
+        assert!(result.is_err());
+        assert!(!target.exists());
+        assert_eq!(fs::read_to_string(&source).unwrap(), "contents and more");
+    }
+
+    #[test]
+    fn other_rename_errors_are_returned_without_copying() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("source.txt"), dir.path().join("target.txt"));
+        fs::write(&source, "contents").unwrap();
+
+        let denied = |_: &Path, _: &Path| Err(io::ErrorKind::PermissionDenied.into());
+        assert!(move_file_with(&source, &target, Verify::Size, denied).is_err());
+        assert!(source.exists());
+        assert!(!target.exists());
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use super::VideoInfo;
+
+/// Whether `ffprobe` runs, checked once per process
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        Command::new("ffprobe")
+            .arg("-version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+/// Asks `ffprobe` for the container and streams of `path`. `None` when it
+/// is not installed, cannot read the file, or finds no video stream.
+pub fn read(path: &Path) -> Option<VideoInfo> {
+    if !available() {
+        return None;
+    }
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    // Cover art in audio files is a video stream too, marked as attached
+    let stream = json["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["codec_type"] == "video" && stream["disposition"]["attached_pic"] != 1)?;
+    let format = &json["format"];
+
+    let dimension = |key: &str| stream[key].as_u64().and_then(|value| u32::try_from(value).ok()).filter(|&value| value > 0);
+    let (mut width, mut height) = (dimension("width"), dimension("height"));
+    if rotation(stream).is_some_and(|degrees| degrees.rem_euclid(180) == 90) {
+        std::mem::swap(&mut width, &mut height);
+    }
+    let created = [&format["tags"]["creation_time"], &stream["tags"]["creation_time"]]
+        .into_iter()
+        .filter_map(Value::as_str)
+        .find_map(|time| DateTime::parse_from_rfc3339(time).ok())
+        // Muxers without a clock write the epoch
+        .filter(|time| time.timestamp() > 0)
+        .map(|time| time.with_timezone(&Local).naive_local());
+
+    Some(VideoInfo {
+        duration: format["duration"]
+            .as_str()
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .map(Duration::from_secs_f64),
+        width,
+        height,
+        codec: stream["codec_name"].as_str().map(str::to_string),
+        created,
+    })
+}
+
+/// Degrees the picture is turned, from the older `rotate` tag or the
+/// display matrix side data newer versions report
+fn rotation(stream: &Value) -> Option<i64> {
+    if let Some(degrees) = stream["tags"]["rotate"].as_str().and_then(|degrees| degrees.parse().ok()) {
+        return Some(degrees);
+    }
+    stream["side_data_list"]
+        .as_array()?
+        .iter()
+        .find_map(|side_data| side_data["rotation"].as_f64())
+        .map(|degrees| degrees.round() as i64)
+}
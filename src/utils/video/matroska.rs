@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use super::{utc_to_local, VideoInfo};
+
+const EBML: u32 = 0x1A45_DFA3;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TRACKS: u32 = 0x1654_AE6B;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const DATE_UTC: u32 = 0x4461;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const DISPLAY_WIDTH: u32 = 0x54B0;
+const DISPLAY_HEIGHT: u32 = 0x54BA;
+
+/// Largest `Info` or `Tracks` element read
+const MAX_ELEMENT: u64 = 16 * 1024 * 1024;
+
+/// Seconds from 1970-01-01 to 2001-01-01, where Matroska dates start
+const MILLENNIUM: i64 = 978_307_200;
+
+/// Reads the `Info` and `Tracks` elements of a Matroska or WebM file: the
+/// duration and creation date, and the size and codec of the first video
+/// track. Stops at the first cluster, as muxers put both before it.
+/// `None` if the file is not Matroska.
+pub fn read(path: &Path) -> Option<VideoInfo> {
+    let mut file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let (id, size) = element_header(&mut file)?;
+    if id != EBML {
+        return None;
+    }
+    file.seek(SeekFrom::Current(size? as i64)).ok()?;
+    if element_header(&mut file)?.0 != SEGMENT {
+        return None;
+    }
+
+    let mut info = VideoInfo::default();
+    let mut found = (false, false);
+    while file.stream_position().ok()? < length && found != (true, true) {
+        let (id, size) = element_header(&mut file)?;
+        if id == CLUSTER {
+            break;
+        }
+        let size = size?;
+        match id {
+            INFO | TRACKS if size <= MAX_ELEMENT => {
+                let mut data = vec![0; size as usize];
+                file.read_exact(&mut data).ok()?;
+                if id == INFO {
+                    parse_info(&data, &mut info);
+                    found.0 = true;
+                } else {
+                    parse_tracks(&data, &mut info);
+                    found.1 = true;
+                }
+            }
+            _ => {
+                file.seek(SeekFrom::Current(size as i64)).ok()?;
+            }
+        }
+    }
+    Some(info)
+}
+
+fn parse_info(data: &[u8], info: &mut VideoInfo) {
+    let mut scale = 1_000_000;
+    let mut duration = None;
+    for (id, value) in elements(data) {
+        match id {
+            TIMESTAMP_SCALE => scale = uint(value).unwrap_or(scale),
+            DURATION => duration = float(value),
+            DATE_UTC => {
+                // Nanoseconds since 2001, signed; muxers without a clock write 0
+                info.created = int(value)
+                    .filter(|&nanos| nanos != 0)
+                    .and_then(|nanos| utc_to_local(MILLENNIUM + nanos.div_euclid(1_000_000_000)));
+            }
+            _ => {}
+        }
+    }
+    // The duration is counted in ticks of the timestamp scale, in nanoseconds
+    info.duration = duration
+        .map(|ticks| ticks * scale as f64 / 1e9)
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64);
+}
+
+fn parse_tracks(data: &[u8], info: &mut VideoInfo) {
+    for (_, entry) in elements(data).filter(|(id, _)| *id == TRACK_ENTRY) {
+        let fields: Vec<_> = elements(entry).collect();
+        let field = |wanted: u32| fields.iter().find(|(id, _)| *id == wanted).map(|(_, value)| *value);
+        if field(TRACK_TYPE).and_then(uint) != Some(1) {
+            continue;
+        }
+        info.codec = field(CODEC_ID)
+            .map(|codec| String::from_utf8_lossy(codec).trim_end_matches('\0').to_string())
+            .filter(|codec| !codec.is_empty());
+        if let Some(video) = field(VIDEO) {
+            let video: Vec<_> = elements(video).collect();
+            let size = |wanted: u32| {
+                video
+                    .iter()
+                    .find(|(id, _)| *id == wanted)
+                    .and_then(|(_, value)| uint(value))
+                    .and_then(|value| u32::try_from(value).ok())
+                    .filter(|&value| value > 0)
+            };
+            // The display size, where given, is what players show
+            info.width = size(DISPLAY_WIDTH).or_else(|| size(PIXEL_WIDTH));
+            info.height = size(DISPLAY_HEIGHT).or_else(|| size(PIXEL_HEIGHT));
+        }
+        break;
+    }
+}
+
+/// ID and size of the element at the reader's position; the size is `None`
+/// when unknown, as for live streams
+fn element_header(reader: &mut impl Read) -> Option<(u32, Option<u64>)> {
+    let (id, _) = vint(reader, false)?;
+    let (size, length) = vint(reader, true)?;
+    let unknown = size == (1u64 << (7 * length)) - 1;
+    Some((id as u32, (!unknown).then_some(size)))
+}
+
+/// A variable-length integer and its length in bytes; IDs keep their
+/// length marker, sizes lose it
+fn vint(reader: &mut impl Read, strip_marker: bool) -> Option<(u64, usize)> {
+    let mut first = [0; 1];
+    reader.read_exact(&mut first).ok()?;
+    let length = first[0].leading_zeros() as usize + 1;
+    if length > 8 {
+        return None;
+    }
+    let mut value = match strip_marker {
+        true => u64::from(first[0]) & ((1 << (8 - length)) - 1),
+        false => u64::from(first[0]),
+    };
+    for _ in 1..length {
+        reader.read_exact(&mut first).ok()?;
+        value = (value << 8) | u64::from(first[0]);
+    }
+    Some((value, length))
+}
+
+/// The elements directly inside `data`, as ID and contents
+fn elements(data: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let mut reader = rest;
+        let (id, size) = element_header(&mut reader)?;
+        let start = rest.len() - reader.len();
+        let end = start.checked_add(usize::try_from(size?).ok()?)?;
+        let contents = rest.get(start..end)?;
+        rest = &rest[end..];
+        Some((id, contents))
+    })
+}
+
+fn uint(data: &[u8]) -> Option<u64> {
+    (data.len() <= 8).then(|| data.iter().fold(0, |value, &byte| (value << 8) | u64::from(byte)))
+}
+
+fn int(data: &[u8]) -> Option<i64> {
+    let unsigned = uint(data)?;
+    let bits = data.len() * 8;
+    // Sign-extend from the element's own width
+    Some(match bits {
+        0 => 0,
+        64 => unsigned as i64,
+        _ => ((unsigned << (64 - bits)) as i64) >> (64 - bits),
+    })
+}
+
+fn float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes(data.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
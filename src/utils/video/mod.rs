@@ -0,0 +1,138 @@
+mod ffprobe;
+mod matroska;
+mod mp4;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::{path::Path, time::Duration};
+
+/// Extensions treated as video, whether or not their metadata can be read
+pub const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mov", "3gp", "3g2", "mkv", "webm", "avi", "wmv", "flv", "mpg", "mpeg", "mts", "m2ts", "ts",
+    "vob", "ogv",
+];
+
+/// Containers read without `ffprobe`
+const ISO_MEDIA: &[&str] = &["mp4", "m4v", "mov", "3gp", "3g2"];
+const MATROSKA: &[&str] = &["mkv", "webm"];
+
+/// Folder for videos whose size could not be read
+pub const UNKNOWN_RESOLUTION: &str = "Unknown Resolution";
+
+/// What a video's container says about it; anything it does not say, or
+/// that could not be read, is `None`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VideoInfo {
+    pub duration: Option<Duration>,
+    /// As displayed, so portrait phone clips are taller than wide
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// As the container names it, e.g. `avc1`, `V_VP9`, or `h264`
+    pub codec: Option<String>,
+    /// When the recording was made, in local time
+    pub created: Option<NaiveDateTime>,
+}
+
+impl VideoInfo {
+    /// `8K`, `4K`, `1440p`, `1080p`, `720p`, or `SD`, by the longer side so
+    /// portrait and letterboxed clips land with their landscape peers
+    pub fn resolution_class(&self) -> &'static str {
+        let (Some(width), Some(height)) = (self.width, self.height) else {
+            return UNKNOWN_RESOLUTION;
+        };
+        match width.max(height) {
+            7680.. => "8K",
+            3840.. => "4K",
+            2560.. => "1440p",
+            1920.. => "1080p",
+            1280.. => "720p",
+            _ => "SD",
+        }
+    }
+
+    /// `1920x1080, 0:42, avc1`, leaving out what is unknown
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            parts.push(format!("{}x{}", width, height));
+        }
+        if let Some(duration) = self.duration {
+            let seconds = duration.as_secs();
+            parts.push(match seconds / 3600 {
+                0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+                hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+            });
+        }
+        parts.extend(self.codec.clone());
+        match parts.is_empty() {
+            true => "no metadata".to_string(),
+            false => parts.join(", "),
+        }
+    }
+}
+
+/// Whether `path` has a video extension
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Reads the metadata of a video: MP4, MOV, and Matroska containers are
+/// parsed directly, anything else, or anything the parsers cannot make
+/// sense of, is handed to `ffprobe` when it is on the `PATH`. `None` when
+/// neither can read the file.
+///
+/// ```
+/// use file_organizer_rust::utils::video;
+///
+/// fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+///     [&(8 + body.len() as u32).to_be_bytes()[..], kind, body].concat()
+/// }
+/// // 2023-06-01 12:00:00 UTC, counted from 1904 as MP4 does
+/// let created: u32 = 1_685_620_800 + 2_082_844_800;
+/// let mvhd = [&[0u8; 4][..], &created.to_be_bytes(), &created.to_be_bytes(), &600u32.to_be_bytes(),
+///     &(42 * 600u32).to_be_bytes(), &[0; 80]].concat();
+/// // A portrait clip: stored 1920x1080, turned a quarter by the matrix
+/// let matrix = [0u32, 0x10000, 0, 0xffff_0000, 0, 0, 0, 0, 0x4000_0000].map(u32::to_be_bytes).concat();
+/// let tkhd = [&[0u8; 40][..], &matrix[..], &(1920u32 << 16).to_be_bytes(), &(1080u32 << 16).to_be_bytes()].concat();
+/// let hdlr = [&[0u8; 8][..], b"vide", &[0; 13]].concat();
+/// let stsd = [&[0u8, 0, 0, 0, 0, 0, 0, 1][..], &atom(b"avc1", &[0; 78])[..]].concat();
+/// let mdia = [atom(b"hdlr", &hdlr), atom(b"minf", &atom(b"stbl", &atom(b"stsd", &stsd)))].concat();
+/// let trak = [atom(b"tkhd", &tkhd), atom(b"mdia", &mdia)].concat();
+/// let moov = [atom(b"mvhd", &mvhd), atom(b"trak", &trak)].concat();
+///
+/// let dir = tempfile::tempdir()?;
+/// let clip = dir.path().join("clip.mp4");
+/// std::fs::write(&clip, [atom(b"ftyp", b"isom\0\0\0\0"), atom(b"moov", &moov)].concat())?;
+///
+/// let info = video::probe(&clip).unwrap();
+/// let expected = chrono::DateTime::from_timestamp(1_685_620_800, 0).unwrap();
+/// assert_eq!(info.created, Some(expected.with_timezone(&chrono::Local).naive_local()));
+/// assert_eq!(info.duration, Some(std::time::Duration::from_secs(42)));
+/// assert_eq!((info.width, info.height), (Some(1080), Some(1920)));
+/// assert_eq!(info.codec.as_deref(), Some("avc1"));
+/// assert_eq!(info.resolution_class(), "1080p");
+/// assert_eq!(info.describe(), "1080x1920, 0:42, avc1");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn probe(path: &Path) -> Option<VideoInfo> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    let native = if ISO_MEDIA.contains(&ext.as_str()) {
+        mp4::read(path)
+    } else if MATROSKA.contains(&ext.as_str()) {
+        matroska::read(path)
+    } else {
+        None
+    };
+    native.or_else(|| ffprobe::read(path))
+}
+
+/// Whether `ffprobe` is on the `PATH`; without it only MP4, MOV, and
+/// Matroska files have metadata
+pub fn ffprobe_available() -> bool {
+    ffprobe::available()
+}
+
+/// Local time of `seconds` since 1970 in UTC
+fn utc_to_local(seconds: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(seconds, 0).map(|time| time.with_timezone(&Local).naive_local())
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use super::{utc_to_local, VideoInfo};
+
+/// Largest `moov` box read; real ones are a few MiB at most even for long
+/// recordings
+const MAX_MOOV: u64 = 64 * 1024 * 1024;
+
+/// Seconds from 1904-01-01, where MP4 times start, to 1970-01-01
+const MAC_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Reads the `moov` box of an ISO base media file (MP4, MOV, M4V, 3GP):
+/// the duration and creation time from `mvhd`, and the size and codec of
+/// the first video track. `None` if there is no `moov` box.
+pub fn read(path: &Path) -> Option<VideoInfo> {
+    let mut file = File::open(path).ok()?;
+    let length = file.metadata().ok()?.len();
+    let mut offset = 0;
+    while offset + 8 <= length {
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let (kind, header, size) = box_header(&mut file, length - offset)?;
+        if &kind == b"moov" {
+            if size > MAX_MOOV {
+                return None;
+            }
+            let mut moov = vec![0; (size - header) as usize];
+            file.read_exact(&mut moov).ok()?;
+            return Some(parse_moov(&moov));
+        }
+        offset += size;
+    }
+    None
+}
+
+/// Type, header length, and total size of the box at the reader's
+/// position, `remaining` bytes before the end of the file
+fn box_header(reader: &mut impl Read, remaining: u64) -> Option<([u8; 4], u64, u64)> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).ok()?;
+    let kind = [header[4], header[5], header[6], header[7]];
+    let (header, size) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+        // Runs to the end of the file
+        0 => (8, remaining),
+        1 => {
+            let mut large = [0; 8];
+            reader.read_exact(&mut large).ok()?;
+            (16, u64::from_be_bytes(large))
+        }
+        size => (8, size as u64),
+    };
+    (size >= header && size <= remaining).then_some((kind, header, size))
+}
+
+/// The boxes directly inside `data`, as type and contents
+fn children(data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let (kind, header, size) = box_header(&mut &rest[..], rest.len() as u64)?;
+        let contents = &rest[header as usize..size as usize];
+        rest = &rest[size as usize..];
+        Some((kind, contents))
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    children(data).find(|(found, _)| found == kind).map(|(_, contents)| contents)
+}
+
+fn parse_moov(moov: &[u8]) -> VideoInfo {
+    let mut info = VideoInfo::default();
+    if let Some(mvhd) = child(moov, b"mvhd") {
+        let version = mvhd.first().copied().unwrap_or(0);
+        // Creation, modification, timescale, duration: 32-bit times in
+        // version 0, 64-bit in version 1
+        let (created, timescale, duration) = match version {
+            1 => (be_u64(mvhd, 4), be_u32(mvhd, 20), be_u64(mvhd, 24)),
+            _ => (be_u32(mvhd, 4).map(u64::from), be_u32(mvhd, 12), be_u32(mvhd, 16).map(u64::from)),
+        };
+        // Cameras without a clock write 0, which would be 1904
+        info.created = created
+            .filter(|&seconds| seconds > 0)
+            .and_then(|seconds| utc_to_local(seconds as i64 - MAC_EPOCH_OFFSET));
+        info.duration = match (timescale, duration) {
+            (Some(timescale), Some(duration)) if timescale > 0 && duration != u64::MAX && duration != u32::MAX as u64 => {
+                Some(Duration::from_secs_f64(duration as f64 / timescale as f64))
+            }
+            _ => None,
+        };
+    }
+
+    for (_, trak) in children(moov).filter(|(kind, _)| kind == b"trak") {
+        let Some(mdia) = child(trak, b"mdia") else {
+            continue;
+        };
+        // The handler is after version, flags, and a predefined field
+        if child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12)) != Some(b"vide") {
+            continue;
+        }
+        if let Some((width, height)) = child(trak, b"tkhd").and_then(track_size) {
+            info.width = Some(width);
+            info.height = Some(height);
+        }
+        // The first sample description names the codec, e.g. `avc1`
+        let stsd = child(mdia, b"minf")
+            .and_then(|minf| child(minf, b"stbl"))
+            .and_then(|stbl| child(stbl, b"stsd"));
+        info.codec = stsd
+            .and_then(|stsd| stsd.get(12..16))
+            .map(|format| String::from_utf8_lossy(format).trim().to_string())
+            .filter(|codec| !codec.is_empty());
+        break;
+    }
+    info
+}
+
+/// Display size from a `tkhd` box, swapped when its matrix turns the
+/// picture a quarter turn, as phones do for portrait clips
+fn track_size(tkhd: &[u8]) -> Option<(u32, u32)> {
+    // Version 1 widens the creation, modification, and duration fields
+    let matrix = match tkhd.first()? {
+        1 => 52,
+        _ => 40,
+    };
+    let (a, b) = (be_u32(tkhd, matrix)? as i32, be_u32(tkhd, matrix + 4)? as i32);
+    // 16.16 fixed point
+    let width = be_u32(tkhd, matrix + 36)? >> 16;
+    let height = be_u32(tkhd, matrix + 40)? >> 16;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    match a == 0 && b != 0 {
+        true => Some((height, width)),
+        false => Some((width, height)),
+    }
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
@@ -0,0 +1,466 @@
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+};
+use rayon::{iter::Either, prelude::*};
+use walkdir::WalkDir;
+
+use super::{filter::PathFilter, junk::JunkList};
+
+/// Entries that mark a version-controlled working copy
+const REPO_MARKERS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// Whether `dir` is the top of a git, Mercurial, or Subversion checkout
+pub fn is_repository(dir: &Path) -> bool {
+    REPO_MARKERS.iter().any(|marker| dir.join(marker).exists())
+}
+
+/// A file found by a walk
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// Read once during the walk; describes the target for symlinks, except
+    /// in [`Walker::links`]
+    pub metadata: Metadata,
+}
+
+/// A file or directory the walk could not read, such as one owned by
+/// another user. Unlike a file a filter excluded, nothing is known about
+/// what it holds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Inaccessible {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Inaccessible {
+    pub fn new(path: &Path, error: impl fmt::Display) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            reason: format!("{:#}", error),
+        }
+    }
+}
+
+/// Whether `err` comes from an entry the user may not read. Such entries
+/// are reported as inaccessible rather than failed.
+pub fn is_access_denied(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<io::Error>())
+        .any(|e| e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Files found by a walk, the working copies and junk left out of it, and
+/// the entries it could not read
+#[derive(Debug, Default)]
+pub struct WalkResult {
+    pub files: Vec<WalkEntry>,
+    pub repositories: Vec<PathBuf>,
+    /// Known junk files and directories; a directory stands for everything
+    /// in it
+    pub junk: Vec<PathBuf>,
+    pub inaccessible: Vec<Inaccessible>,
+}
+
+impl WalkResult {
+    /// The paths of the files found, in walk order
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|entry| entry.path.as_path())
+    }
+
+    /// The paths of the files found, dropping their metadata
+    pub fn into_paths(self) -> Vec<PathBuf> {
+        self.files.into_iter().map(|entry| entry.path).collect()
+    }
+}
+
+/// What a walk yields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Files,
+    Links,
+    Entries,
+}
+
+/// Directory walk shared by the modules. Working copies are pruned
+/// entirely unless `include_repos` is set, since reorganizing their files
+/// destroys the checkout. Known junk is left out unless `junk(None)` asks
+/// for everything.
+#[derive(Debug, Clone)]
+pub struct Walker {
+    recursive: bool,
+    include_repos: bool,
+    excluded: Vec<PathBuf>,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    symlinks: bool,
+    filter: PathFilter,
+    skip_hidden: bool,
+    junk: Option<JunkList>,
+    sorted: bool,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Walker {
+    pub fn new(recursive: bool) -> Self {
+        Self {
+            recursive,
+            include_repos: false,
+            excluded: Vec::new(),
+            max_depth: None,
+            follow_links: false,
+            symlinks: false,
+            filter: PathFilter::default(),
+            skip_hidden: false,
+            junk: Some(JunkList::default()),
+            sorted: false,
+            min_size: None,
+            max_size: None,
+        }
+    }
+
+    /// Walks into working copies like any other directory
+    pub fn include_repos(mut self, include: bool) -> Self {
+        self.include_repos = include;
+        self
+    }
+
+    /// Directories to skip entirely, such as a module's own output
+    pub fn exclude(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.excluded = dirs;
+        self
+    }
+
+    /// Limits a recursive walk to files at most `depth` levels below the
+    /// root, counting the root's own files as level 1
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Walks into symlinked directories, which may lie outside the root
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Also yields symlinks that resolve to files. Dangling links and
+    /// links to directories are never yielded.
+    pub fn symlinks(mut self, include: bool) -> Self {
+        self.symlinks = include;
+        self
+    }
+
+    /// Only yields files whose path relative to the root passes `filter`
+    pub fn filter(mut self, filter: PathFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Leaves out dotfiles and everything inside dot-directories
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// Junk to leave out: the built-in list unless replaced, e.g. by one
+    /// extended from the config. `None` yields junk like any other file.
+    pub fn junk(mut self, junk: Option<JunkList>) -> Self {
+        self.junk = junk;
+        self
+    }
+
+    /// Yields each directory's entries in name order, i.e. files ordered by
+    /// their path relative to the root, instead of in whatever order the
+    /// filesystem lists them. Everything decided in walk order, such as
+    /// which duplicate is kept, then comes out the same on every machine.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Only yields files of at least `min` and at most `max` bytes
+    pub fn size_range(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_size = min;
+        self.max_size = max;
+        self
+    }
+
+    /// Every file under each of `roots`, in order
+    pub fn files_in(&self, roots: &[PathBuf]) -> WalkResult {
+        let mut result = WalkResult::default();
+        for root in roots {
+            let walk = self.files(root);
+            result.files.extend(walk.files);
+            result.repositories.extend(walk.repositories);
+            result.junk.extend(walk.junk);
+            result.inaccessible.extend(walk.inaccessible);
+        }
+        result
+    }
+
+    /// Every file under `root`. A root that is itself a working copy is
+    /// skipped as a whole.
+    pub fn files(&self, root: &Path) -> WalkResult {
+        self.walk(root, Kind::Files)
+    }
+
+    /// Every symlink under `root`, whatever it points to and whether or not
+    /// that exists, with the metadata of the link itself. Symlinked
+    /// directories are not walked into, and the size range does not apply.
+    pub fn links(&self, root: &Path) -> WalkResult {
+        self.walk(root, Kind::Links)
+    }
+
+    /// Every file and directory under `root`, `root` included, each before
+    /// what it holds. The filter and size range only apply to files, so a
+    /// directory is yielded even when none of its files are.
+    pub fn entries(&self, root: &Path) -> WalkResult {
+        self.walk(root, Kind::Entries)
+    }
+
+    fn walk(&self, root: &Path, kind: Kind) -> WalkResult {
+        let links = kind == Kind::Links;
+        if !self.include_repos && is_repository(root) {
+            return WalkResult {
+                repositories: vec![root.to_path_buf()],
+                ..WalkResult::default()
+            };
+        }
+
+        let walker = match (self.recursive, self.max_depth) {
+            (false, _) => WalkDir::new(root).max_depth(1),
+            (true, Some(depth)) => WalkDir::new(root).max_depth(depth),
+            (true, None) => WalkDir::new(root),
+        };
+        let walker = if self.sorted { walker.sort_by_file_name() } else { walker };
+        let repositories = RefCell::new(Vec::new());
+        let junk = RefCell::new(Vec::new());
+        let inaccessible = RefCell::new(Vec::new());
+        let skip = |path: &Path, reason: String| {
+            inaccessible.borrow_mut().push(Inaccessible {
+                path: path.to_path_buf(),
+                reason,
+            });
+        };
+        let entries: Vec<walkdir::DirEntry> = walker
+            .follow_links(self.follow_links && !links)
+            .into_iter()
+            .filter_entry(|entry| {
+                if entry.depth() == 0 {
+                    return true;
+                }
+                if self.excluded.iter().any(|dir| dir == entry.path()) {
+                    return false;
+                }
+                if let Some(list) = &self.junk {
+                    let is_junk = if entry.file_type().is_dir() {
+                        list.is_junk_dir(entry.path())
+                    } else {
+                        list.is_junk_file(entry.path())
+                    };
+                    if is_junk {
+                        junk.borrow_mut().push(entry.path().to_path_buf());
+                        return false;
+                    }
+                }
+                if self.skip_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+                    return false;
+                }
+                let prune = self.recursive
+                    && !self.include_repos
+                    && entry.file_type().is_dir()
+                    && is_repository(entry.path());
+                if prune {
+                    repositories.borrow_mut().push(entry.path().to_path_buf());
+                }
+                !prune
+            })
+            .filter_map(|e| {
+                e.map_err(|error| {
+                    let reason = match error.io_error() {
+                        Some(io) => io.to_string(),
+                        None => error.to_string(),
+                    };
+                    skip(error.path().unwrap_or(root), reason);
+                })
+                .ok()
+            })
+            .filter(|entry| {
+                if links {
+                    return entry.path_is_symlink();
+                }
+                if kind == Kind::Entries && entry.file_type().is_dir() && !entry.path_is_symlink() {
+                    return true;
+                }
+                // A followed link reports its target's type
+                if entry.path_is_symlink() {
+                    return self.symlinks && entry.path().is_file();
+                }
+                entry.file_type().is_file()
+            })
+            .filter(|entry| entry.file_type().is_dir() || self.filter.matches(entry.path(), root))
+            .collect();
+
+        // Reading metadata is a syscall per file, slow on network shares
+        // and cold caches, so it is spread over threads; order is kept
+        let (files, failed): (Vec<WalkEntry>, Vec<Inaccessible>) =
+            entries.into_par_iter().partition_map(|entry| {
+                let metadata = if links { entry.path().symlink_metadata() } else { entry.path().metadata() };
+                match metadata {
+                    Ok(metadata) => Either::Left(WalkEntry {
+                        path: entry.into_path(),
+                        metadata,
+                    }),
+                    Err(error) => Either::Right(Inaccessible::new(entry.path(), error)),
+                }
+            });
+        let files = files
+            .into_iter()
+            .filter(|entry| {
+                let size = entry.metadata.len();
+                links || entry.metadata.is_dir() || self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max)
+            })
+            .collect();
+        let mut inaccessible = inaccessible.into_inner();
+        inaccessible.extend(failed);
+
+        WalkResult {
+            files,
+            repositories: repositories.into_inner(),
+            junk: junk.into_inner(),
+            inaccessible,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::{collections::BTreeMap, ffi::OsStr, fs};
+
+    /// Relative file paths with their sizes, under the directories `a`,
+    /// `b`, the hidden `.h`, and `r`, which is made a working copy
+    fn tree() -> impl Strategy<Value = BTreeMap<PathBuf, u64>> {
+        let dirs = prop::collection::vec(prop::sample::select(vec!["a", "b", ".h", "r"]), 0..4);
+        let name = prop::sample::select(vec!["x.txt", "y.log", ".z"]);
+        let path = (dirs, name).prop_map(|(dirs, name)| dirs.iter().collect::<PathBuf>().join(name));
+        prop::collection::btree_map(path, 0..64u64, 0..24)
+    }
+
+    fn build(root: &Path, tree: &BTreeMap<PathBuf, u64>) {
+        for (path, size) in tree {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, vec![0; *size as usize]).unwrap();
+            for dir in path.ancestors().filter(|dir| dir.file_name() == Some(OsStr::new("r"))) {
+                fs::create_dir_all(dir.join(".git")).unwrap();
+            }
+        }
+    }
+
+    /// The paths `walker` finds under `root`, relative to it and in walk
+    /// order
+    fn found(walker: Walker, root: &Path) -> Vec<PathBuf> {
+        walker.files(root).paths().map(|path| path.strip_prefix(root).unwrap().to_path_buf()).collect()
+    }
+
+    /// The paths in `tree` that `keep` accepts, in path order
+    fn expected(tree: &BTreeMap<PathBuf, u64>, keep: impl Fn(&Path, u64) -> bool) -> Vec<PathBuf> {
+        tree.iter().filter(|(path, size)| keep(path, **size)).map(|(path, _)| path.clone()).collect()
+    }
+
+    /// A sorted walk that leaves nothing out, for each test to narrow by
+    /// one option
+    fn everything() -> Walker {
+        Walker::new(true).include_repos(true).junk(None).sorted(true)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn a_sorted_walk_finds_every_file_in_path_order(tree in tree()) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            prop_assert_eq!(found(everything(), dir.path()), expected(&tree, |_, _| true));
+        }
+
+        #[test]
+        fn a_walk_that_is_not_recursive_finds_only_the_roots_files(tree in tree()) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let walker = Walker { recursive: false, ..everything() };
+            prop_assert_eq!(found(walker, dir.path()), expected(&tree, |path, _| path.components().count() == 1));
+        }
+
+        #[test]
+        fn the_depth_limit_counts_the_roots_files_as_level_one(tree in tree(), depth in 1usize..5) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let walker = everything().max_depth(Some(depth));
+            prop_assert_eq!(found(walker, dir.path()), expected(&tree, |path, _| path.components().count() <= depth));
+        }
+
+        #[test]
+        fn hidden_files_and_everything_in_hidden_directories_are_skipped(tree in tree()) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let hidden = |path: &Path| path.iter().any(|part| part.to_string_lossy().starts_with('.'));
+            let walker = everything().skip_hidden(true);
+            prop_assert_eq!(found(walker, dir.path()), expected(&tree, |path, _| !hidden(path)));
+        }
+
+        #[test]
+        fn working_copies_are_pruned_unless_included(tree in tree()) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let in_repo = |path: &Path| path.iter().any(|part| part == "r");
+            let walker = everything().include_repos(false);
+            prop_assert_eq!(found(walker, dir.path()), expected(&tree, |path, _| !in_repo(path)));
+        }
+
+        #[test]
+        fn only_files_within_the_size_range_are_found(tree in tree(), min in 0..64u64, max in 0..64u64) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let walker = everything().size_range(Some(min), Some(max));
+            prop_assert_eq!(found(walker, dir.path()), expected(&tree, |_, size| (min..=max).contains(&size)));
+        }
+
+        #[test]
+        fn the_filter_applies_to_paths_relative_to_the_root(tree in tree()) {
+            let dir = tempfile::tempdir().unwrap();
+            build(dir.path(), &tree);
+            let filter = PathFilter::new(&["*.txt".into(), "*.log".into()], &["a/**".into()]).unwrap();
+            let keep = |path: &Path| path.extension().is_some() && !path.starts_with("a");
+            prop_assert_eq!(found(everything().filter(filter), dir.path()), expected(&tree, |path, _| keep(path)));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_yielded_and_followed_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, outside) = (dir.path().join("root"), dir.path().join("outside"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("file.txt"), "file").unwrap();
+        fs::write(outside.join("far.txt"), "far").unwrap();
+        std::os::unix::fs::symlink("file.txt", root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("dir")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", root.join("dangling.txt")).unwrap();
+
+        let files = |walker: Walker| found(walker.sorted(true), &root);
+        assert_eq!(files(Walker::new(true)), [Path::new("file.txt")]);
+        assert_eq!(files(Walker::new(true).symlinks(true)), [Path::new("file.txt"), Path::new("link.txt")]);
+        assert_eq!(files(Walker::new(true).follow_links(true)), [Path::new("dir/far.txt"), Path::new("file.txt")]);
+        assert_eq!(Walker::new(true).links(&root).files.len(), 3);
+    }
+}